@@ -16,12 +16,17 @@ fn benchmark_message_parsing(c: &mut Criterion) {
         "JOIN #channel",
         "PART #channel :Goodbye",
         "QUIT :Leaving",
+        "@time=2024-01-01T00:00:00.000Z;msgid=abc123;+draft/reply=xyz :alice!user@host PRIVMSG #channel :Tagged hello",
+        ":server.example.com 005 alice CHANTYPES=# PREFIX=(ov)@+ CHANMODES=b,k,l,imnpst NICKLEN=30 CHANNELLEN=50 TOPICLEN=390 AWAYLEN=160 NETWORK=RustNet CASEMAPPING=rfc1459 :are supported by this server",
     ];
     
     for msg in messages {
         group.bench_with_input(BenchmarkId::from_parameter(msg), msg, |b, msg| {
             b.iter(|| Message::parse(black_box(msg)))
         });
+        group.bench_with_input(BenchmarkId::new("borrowed", msg), msg, |b, msg| {
+            b.iter(|| MessageRef::parse(black_box(msg)).map(|message| message.params().len()))
+        });
     }
     
     group.finish();
@@ -106,7 +111,7 @@ fn benchmark_database_operations(c: &mut Criterion) {
         
         b.iter(|| {
             user.nick = format!("testuser{}", rand::random::<u32>());
-            db.update_user(black_box(user.clone()))
+            db.update_user(&user.id, black_box(user.clone()))
         });
     });
     
@@ -203,6 +208,103 @@ fn benchmark_broadcast_operations(c: &mut Criterion) {
         });
     });
     
+    // One channel message to 500 members: resolving nicks through the
+    // database and senders one by one, against the membership index
+    let senders = std::sync::Arc::new(ClientSenders::new());
+    let db = Database::new(10000, 30);
+    let system = BroadcastSystem::with_client_senders(senders.clone());
+    let mut receivers = Vec::new();
+    for i in 0..500 {
+        let user = User::new(
+            format!("user{}", i),
+            format!("ident{}", i),
+            "Real Name".to_string(),
+            "host.example.com".to_string(),
+            "server.example.com".to_string(),
+        );
+        let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
+        senders.insert(user.id, tx);
+        receivers.push(rx);
+        system.subscribe_to_channel(user.id, "#bench".to_string());
+        db.add_user_to_channel(&user.nick, "#bench").ok();
+        db.add_user(user).ok();
+    }
+    let message = Message::new(MessageType::PrivMsg, vec!["#bench".to_string(), "Hello".to_string()]);
+    let via_database = || {
+        for nick in db.get_channel_users(black_box("#bench")) {
+            if let Some(user) = db.get_user_by_nick(&nick) {
+                senders.send(&user.id, message.clone());
+            }
+        }
+    };
+    let via_index = || {
+        system.send_to_channel_members(black_box("#bench"), &message);
+    };
+    let drain = |receivers: &mut Vec<tokio::sync::mpsc::UnboundedReceiver<Message>>| {
+        receivers.iter_mut().map(|rx| std::iter::from_fn(|| rx.try_recv().ok()).count()).sum::<usize>()
+    };
+    
+    // Both paths reach every member
+    via_database();
+    assert_eq!(drain(&mut receivers), 500);
+    via_index();
+    assert_eq!(drain(&mut receivers), 500);
+    
+    group.bench_function("channel_via_database", |b| {
+        b.iter(|| {
+            via_database();
+            drain(&mut receivers)
+        });
+    });
+    
+    group.bench_function("channel_via_index", |b| {
+        b.iter(|| {
+            via_index();
+            drain(&mut receivers)
+        });
+    });
+    
+    group.finish();
+}
+
+fn benchmark_broadcast_fanout(c: &mut Criterion) {
+    let mut group = c.benchmark_group("broadcast_fanout");
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    let message = Message::with_prefix(
+        Prefix::User {
+            nick: "alice".to_string(),
+            user: "user".to_string(),
+            host: "host".to_string(),
+        },
+        MessageType::PrivMsg,
+        vec!["#fanout".to_string(), "Hello everyone".to_string()],
+    );
+    
+    // One channel message to channels of growing size, through the
+    // membership index and each member's outbound queue
+    for members in [10, 100, 1000, 10000] {
+        let senders = std::sync::Arc::new(ClientSenders::new());
+        let system = BroadcastSystem::with_client_senders(senders.clone());
+        let mut receivers = Vec::with_capacity(members);
+        for _ in 0..members {
+            let id = Uuid::new_v4();
+            let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
+            senders.insert(id, tx);
+            receivers.push(rx);
+            system.subscribe_to_channel(id, "#fanout".to_string());
+        }
+        
+        group.throughput(criterion::Throughput::Elements(members as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(members), &members, |b, _| {
+            b.iter(|| {
+                rt.block_on(system.broadcast_to_channel("#fanout", black_box(message.clone()), None)).unwrap();
+                for rx in receivers.iter_mut() {
+                    while rx.try_recv().is_ok() {}
+                }
+            });
+        });
+    }
+    
     group.finish();
 }
 
@@ -269,7 +371,7 @@ fn benchmark_validation(c: &mut Criterion) {
 fn benchmark_user_modes(c: &mut Criterion) {
     let mut group = c.benchmark_group("user_modes");
     
-    group.bench_function("set_mode", |b| {
+    group.bench_function("add_mode", |b| {
         let mut user = User::new(
             "alice".to_string(),
             "user".to_string(),
@@ -279,7 +381,7 @@ fn benchmark_user_modes(c: &mut Criterion) {
         );
         
         b.iter(|| {
-            user.set_mode(black_box(UserMode::Invisible), black_box(true));
+            user.add_mode(black_box(UserMode::Invisible.to_char()));
         });
     });
     
@@ -291,10 +393,10 @@ fn benchmark_user_modes(c: &mut Criterion) {
             "host.example.com".to_string(),
             "server.example.com".to_string(),
         );
-        user.set_mode(UserMode::Invisible, true);
+        user.add_mode(UserMode::Invisible.to_char());
         
         b.iter(|| {
-            user.has_mode(black_box(&UserMode::Invisible))
+            user.has_mode(black_box(UserMode::Invisible.to_char()))
         });
     });
     
@@ -418,6 +520,7 @@ criterion_group!(
     benchmark_database_operations,
     benchmark_cache_operations,
     benchmark_broadcast_operations,
+    benchmark_broadcast_fanout,
     benchmark_batch_optimizer,
     benchmark_validation,
     benchmark_user_modes,
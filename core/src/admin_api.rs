@@ -0,0 +1,236 @@
+//! Admin HTTP API
+//!
+//! A JSON API for web panels and automation, built with the `admin-api`
+//! feature and served when `[admin_api]` is enabled. Every request needs
+//! `Authorization: Bearer <token>` where the token matches `token_hash`.
+//!
+//! - `GET /api/v1/users`, `GET /api/v1/users/:nick`
+//! - `GET /api/v1/channels`, `/api/v1/links`, `/api/v1/bans`, `/api/v1/stats`
+//! - `POST /api/v1/users/:nick/kill` with `{"reason": "..."}`
+//! - `POST /api/v1/bans` with `{"type": "KLINE", "mask": "...", "reason": "...", "duration": 3600}`
+//! - `POST /api/v1/rehash` with an optional `{"section": "MOTD"}`
+//!
+//! Actions are audited and shown to operators as coming from `admin-api`.
+
+use crate::audit::{AuditEvent, AuditEventType};
+use crate::ban_manager::MAX_BAN_DURATION;
+use crate::config::PasswordHasher;
+use crate::{BanEntry, Error, RehashSection, Server, User};
+use axum::extract::{Path, State};
+use axum::http::{header, Request, StatusCode};
+use axum::middleware::{self, Next};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+/// Name admin API actions are recorded under
+const ADMIN_API_ACTOR: &str = "admin-api";
+
+/// Build the API's routes for `server`
+pub fn router(server: Server) -> Router {
+    Router::new()
+        .route("/api/v1/users", get(list_users))
+        .route("/api/v1/users/:nick", get(get_user))
+        .route("/api/v1/users/:nick/kill", post(kill_user))
+        .route("/api/v1/channels", get(list_channels))
+        .route("/api/v1/links", get(list_links))
+        .route("/api/v1/bans", get(list_bans).post(add_ban))
+        .route("/api/v1/stats", get(stats))
+        .route("/api/v1/rehash", post(rehash))
+        .layer(middleware::from_fn_with_state(server.clone(), authenticate))
+        .with_state(server)
+}
+
+/// An error response: `{"error": "..."}` with a status code
+struct ApiError(StatusCode, String);
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        (self.0, Json(json!({ "error": self.1 }))).into_response()
+    }
+}
+
+impl From<Error> for ApiError {
+    fn from(error: Error) -> Self {
+        let status = match error {
+            Error::User(_) => StatusCode::NOT_FOUND,
+            Error::Config(_) => StatusCode::BAD_REQUEST,
+            _ => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        Self(status, error.to_string())
+    }
+}
+
+type ApiResult = std::result::Result<Json<Value>, ApiError>;
+
+/// Reject requests without a bearer token matching `admin_api.token_hash`
+async fn authenticate<B>(State(server): State<Server>, request: Request<B>, next: Next<B>) -> Response {
+    let token = request.headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    let token_hash = server.config().admin_api.token_hash.clone();
+    match token {
+        Some(token) if !token_hash.is_empty() && PasswordHasher::verify_password(token, &token_hash) => next.run(request).await,
+        _ => ApiError(StatusCode::UNAUTHORIZED, "Missing or invalid bearer token".to_string()).into_response(),
+    }
+}
+
+fn user_json(user: &User) -> Value {
+    let mut modes: Vec<char> = user.modes.iter().copied().collect();
+    modes.sort_unstable();
+    let mut channels: Vec<&String> = user.channels.iter().collect();
+    channels.sort();
+    json!({
+        "nick": user.nick,
+        "username": user.username,
+        "host": user.host,
+        "real_host": user.real_host,
+        "realname": user.realname,
+        "server": user.server,
+        "account": user.account,
+        "operator": user.is_operator,
+        "away": user.away_message,
+        "modes": modes.into_iter().collect::<String>(),
+        "channels": channels,
+        "registered_at": user.registered_at.to_rfc3339(),
+    })
+}
+
+fn ban_json(ban: &BanEntry) -> Value {
+    json!({
+        "type": ban.ban_type,
+        "mask": ban.mask,
+        "reason": ban.reason,
+        "set_by": ban.set_by,
+        "set_time": ban.set_time,
+        "expire_time": ban.expire_time,
+    })
+}
+
+async fn list_users(State(server): State<Server>) -> ApiResult {
+    let mut users = server.database().get_all_users();
+    users.sort_by(|a, b| a.nick.cmp(&b.nick));
+    Ok(Json(Value::Array(users.iter().map(user_json).collect())))
+}
+
+async fn get_user(State(server): State<Server>, Path(nick): Path<String>) -> ApiResult {
+    let user = server.database().get_user_by_nick(&nick)
+        .ok_or_else(|| ApiError(StatusCode::NOT_FOUND, format!("No such nick: {}", nick)))?;
+    Ok(Json(user_json(&user)))
+}
+
+async fn list_channels(State(server): State<Server>) -> ApiResult {
+    let mut channels = server.database().get_all_channels();
+    channels.sort_by(|a, b| a.name.cmp(&b.name));
+    let channels = channels.iter().map(|channel| {
+        let mut modes: Vec<char> = channel.modes.iter().copied().collect();
+        modes.sort_unstable();
+        json!({
+            "name": channel.name,
+            "topic": channel.topic,
+            "modes": modes.into_iter().collect::<String>(),
+            "members": server.database().get_channel_users(&channel.name),
+        })
+    });
+    Ok(Json(Value::Array(channels.collect())))
+}
+
+async fn list_links(State(server): State<Server>) -> ApiResult {
+    let links = server.network_servers().await.into_iter().map(|node| json!({
+        "name": node.name,
+        "uplink": node.uplink,
+        "hop_count": node.hop_count,
+        "description": node.description,
+    }));
+    Ok(Json(Value::Array(links.collect())))
+}
+
+async fn list_bans(State(server): State<Server>) -> ApiResult {
+    let ban_manager = server.ban_manager();
+    let bans = ban_manager.registered_types().into_iter()
+        .flat_map(|ban_type| ban_manager.list_bans(&ban_type))
+        .map(|ban| ban_json(&ban))
+        .collect();
+    Ok(Json(Value::Array(bans)))
+}
+
+async fn stats(State(server): State<Server>) -> ApiResult {
+    let statistics = server.statistics().await;
+    let local_server = server.config().server.name.clone();
+    Ok(Json(json!({
+        "server": local_server,
+        "uptime_seconds": statistics.uptime_seconds(),
+        "users": server.database().user_count(),
+        "local_users": server.database().get_users_by_server(&local_server).len(),
+        "channels": server.database().channel_count(),
+        "servers": server.network_servers().await.len() + 1,
+        "total_connections": statistics.total_connections,
+        "messages_received": statistics.total_messages_received,
+        "messages_sent": statistics.total_messages_sent,
+        "bytes_received": statistics.total_bytes_received,
+        "bytes_sent": statistics.total_bytes_sent,
+    })))
+}
+
+#[derive(Deserialize)]
+struct KillRequest {
+    reason: Option<String>,
+}
+
+async fn kill_user(State(server): State<Server>, Path(nick): Path<String>, Json(request): Json<KillRequest>) -> ApiResult {
+    let reason = request.reason.unwrap_or_else(|| "No reason given".to_string());
+    server.kill_user(&nick, ADMIN_API_ACTOR, &reason).await?;
+    server.audit(action("KILL", &nick).with_reason(reason.as_str())).await;
+    Ok(Json(json!({ "killed": nick })))
+}
+
+#[derive(Deserialize)]
+struct BanRequest {
+    #[serde(rename = "type")]
+    ban_type: String,
+    mask: String,
+    reason: String,
+    /// Seconds until the ban expires, at most [`MAX_BAN_DURATION`]; permanent when absent
+    duration: Option<u64>,
+}
+
+async fn add_ban(State(server): State<Server>, Json(request): Json<BanRequest>) -> ApiResult {
+    let ban_manager = server.ban_manager();
+    if !ban_manager.registered_types().iter().any(|name| name.eq_ignore_ascii_case(&request.ban_type)) {
+        return Err(ApiError(StatusCode::BAD_REQUEST, format!("Unknown ban type {}", request.ban_type)));
+    }
+    if request.duration.is_some_and(|duration| duration > MAX_BAN_DURATION) {
+        return Err(ApiError(StatusCode::BAD_REQUEST, format!("Duration may not exceed {} seconds", MAX_BAN_DURATION)));
+    }
+    let entry = BanEntry::new(&request.ban_type, &request.mask, &request.reason, ADMIN_API_ACTOR, request.duration);
+    let disconnected = server.add_ban(entry.clone()).await?;
+    server.audit(action(&entry.ban_type, &entry.mask).with_reason(entry.reason.as_str())).await;
+    Ok(Json(json!({ "ban": ban_json(&entry), "disconnected": disconnected })))
+}
+
+#[derive(Deserialize, Default)]
+struct RehashRequest {
+    section: Option<String>,
+}
+
+async fn rehash(State(server): State<Server>, request: Option<Json<RehashRequest>>) -> ApiResult {
+    let request = request.map(|Json(request)| request).unwrap_or_default();
+    let name = request.section.unwrap_or_default();
+    let section = RehashSection::parse(&name)
+        .ok_or_else(|| ApiError(StatusCode::BAD_REQUEST, format!("Unknown REHASH section {}", name)))?;
+    server.audit(action("REHASH", section.name())).await;
+    let report = server.rehash(section).await?;
+    Ok(Json(json!({ "section": section.name(), "report": report })))
+}
+
+/// Audit event for an action taken through the API
+fn action(command: &str, target: &str) -> AuditEvent {
+    AuditEvent::new(AuditEventType::OperAction)
+        .with_user(ADMIN_API_ACTOR)
+        .with_method("http")
+        .with_command(command)
+        .with_target(target)
+}
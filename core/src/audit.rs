@@ -3,7 +3,13 @@
 //! This module provides comprehensive security event logging for authentication,
 //! authorization, and operator actions as recommended by the security audit.
 
+use crate::config::AuditConfig;
+use crate::{Message, MessageType, User};
+use parking_lot::Mutex;
 use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
 use uuid::Uuid;
 
 /// Security audit event types
@@ -213,6 +219,43 @@ impl AuditEvent {
         self
     }
 
+    /// Create an operator action event for `oper` running `command` against `target`
+    pub fn privileged(oper: &User, command: impl Into<String>, target: impl Into<String>) -> Self {
+        Self::new(AuditEventType::OperAction)
+            .with_user(oper.nick.clone())
+            .with_user_id(oper.id)
+            .with_username(oper.username.clone())
+            .with_hostname(oper.host.clone())
+            .with_command(command)
+            .with_target(target)
+    }
+
+    /// One-line summary used for STATS A and the log channel
+    ///
+    /// e.g. `2024-01-01T12:00:00Z KILL alice!alice@host -> bob (spamming)`
+    pub fn summary(&self) -> String {
+        let mut summary = format!(
+            "{} {}",
+            self.timestamp.format("%Y-%m-%dT%H:%M:%SZ"),
+            self.command.clone().unwrap_or_else(|| self.event_type.to_string())
+        );
+        if let Some(nick) = &self.user {
+            summary.push_str(&format!(
+                " {}!{}@{}",
+                nick,
+                self.username.as_deref().unwrap_or("*"),
+                self.hostname.as_deref().unwrap_or("*")
+            ));
+        }
+        if let Some(target) = &self.target {
+            summary.push_str(&format!(" -> {}", target));
+        }
+        if let Some(reason) = &self.reason {
+            summary.push_str(&format!(" ({})", reason));
+        }
+        summary
+    }
+
     /// Log this event
     pub fn log(&self) {
         match self.event_type {
@@ -374,6 +417,111 @@ impl Default for AuditLogger {
     }
 }
 
+/// Audit trail of privileged operator actions
+///
+/// Each recorded event is traced, appended to the configured log file as a
+/// JSON line and kept in a bounded history for STATS A. Echoing to the log
+/// channel is left to the caller since it needs access to client connections.
+#[derive(Debug)]
+pub struct AuditTrail {
+    enabled: bool,
+    log_channel: Option<String>,
+    history_size: usize,
+    history: Mutex<VecDeque<AuditEvent>>,
+    file: Option<Mutex<File>>,
+}
+
+impl AuditTrail {
+    /// Create an audit trail from configuration
+    pub fn new(config: &AuditConfig) -> Self {
+        let file = config.log_file.as_ref().and_then(|path| {
+            match OpenOptions::new().create(true).append(true).open(path) {
+                Ok(file) => Some(Mutex::new(file)),
+                Err(e) => {
+                    tracing::warn!("Failed to open audit log {}: {}", path, e);
+                    None
+                }
+            }
+        });
+
+        Self {
+            enabled: config.enabled,
+            log_channel: config.log_channel.clone(),
+            history_size: config.history_size,
+            history: Mutex::new(VecDeque::new()),
+            file,
+        }
+    }
+
+    /// Check if the audit trail is enabled
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Channel events are echoed to, if configured
+    pub fn log_channel(&self) -> Option<&str> {
+        self.log_channel.as_deref()
+    }
+
+    /// Record an event
+    pub fn record(&self, event: &AuditEvent) {
+        if !self.enabled {
+            return;
+        }
+        event.log();
+
+        if let Some(file) = &self.file {
+            match serde_json::to_string(event) {
+                Ok(line) => {
+                    if let Err(e) = writeln!(file.lock(), "{}", line) {
+                        tracing::warn!("Failed to write audit log: {}", e);
+                    }
+                }
+                Err(e) => tracing::warn!("Failed to serialize audit event: {}", e),
+            }
+        }
+
+        let mut history = self.history.lock();
+        history.push_back(event.clone());
+        while history.len() > self.history_size {
+            history.pop_front();
+        }
+    }
+
+    /// Sync the audit log file to disk
+    pub fn flush(&self) {
+        if let Some(file) = &self.file {
+            if let Err(e) = file.lock().sync_all() {
+                tracing::warn!("Failed to flush audit log: {}", e);
+            }
+        }
+    }
+
+    /// Recent events, oldest first
+    pub fn recent(&self) -> Vec<AuditEvent> {
+        self.history.lock().iter().cloned().collect()
+    }
+
+    /// NOTICE echoing an event to the log channel, if one is configured
+    pub fn log_channel_notice(&self, event: &AuditEvent) -> Option<(String, Message)> {
+        if !self.enabled {
+            return None;
+        }
+        let channel = self.log_channel.clone()?;
+        let notice = Message::new(
+            MessageType::Notice,
+            vec![channel.clone(), format!("AUDIT: {}", event.summary())],
+        );
+        Some((channel, notice))
+    }
+}
+
+impl Default for AuditTrail {
+    fn default() -> Self {
+        Self::new(&AuditConfig::default())
+    }
+}
+
 /// Helper macros for creating audit events
 #[macro_export]
 macro_rules! audit_auth_success {
@@ -438,6 +586,35 @@ mod tests {
         assert_eq!(logger.min_level(), 1);
     }
 
+    #[test]
+    fn test_audit_trail_history() {
+        let config = AuditConfig {
+            history_size: 2,
+            log_channel: Some("&oper-log".to_string()),
+            ..AuditConfig::default()
+        };
+        let trail = AuditTrail::new(&config);
+        let oper = User::new(
+            "oper".to_string(),
+            "ops".to_string(),
+            "Operator".to_string(),
+            "example.com".to_string(),
+            "irc.example.com".to_string(),
+        );
+
+        for target in ["alice", "bob", "carol"] {
+            trail.record(&AuditEvent::privileged(&oper, "KILL", target).with_reason("spam"));
+        }
+        let recent = trail.recent();
+        assert_eq!(recent.len(), 2);
+        assert_eq!(recent[0].target.as_deref(), Some("bob"));
+        assert!(recent[1].summary().ends_with("KILL oper!ops@example.com -> carol (spam)"));
+
+        let (channel, notice) = trail.log_channel_notice(&recent[1]).unwrap();
+        assert_eq!(channel, "&oper-log");
+        assert_eq!(notice.params[0], "&oper-log");
+    }
+
     #[test]
     fn test_event_type_display() {
         assert_eq!(AuditEventType::AuthSuccess.to_string(), "auth_success");
@@ -26,6 +26,12 @@ pub enum AuthResult {
     InProgress,
 }
 
+/// `AuthInfo` metadata listing operator flags to grant on login, comma separated
+pub const AUTH_META_OPER_FLAGS: &str = "oper_flags";
+
+/// `AuthInfo` metadata naming the connection class to move the client to on login
+pub const AUTH_META_CLASS: &str = "class";
+
 /// Authentication information
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct AuthInfo {
@@ -425,6 +431,14 @@ impl AuthManager {
         cache.insert(client_id, (auth_info.clone(), chrono::Utc::now()));
     }
     
+    /// What a client last authenticated as, while it is cached
+    ///
+    /// The server reads provider metadata such as `AUTH_META_OPER_FLAGS`
+    /// from here when the client finishes registering.
+    pub async fn authenticated(&self, client_id: Uuid) -> Option<AuthInfo> {
+        self.get_cached_auth(client_id).await
+    }
+    
     /// Get cached authentication
     async fn get_cached_auth(&self, client_id: Uuid) -> Option<AuthInfo> {
         let cache = self.auth_cache.read().await;
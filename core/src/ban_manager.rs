@@ -0,0 +1,889 @@
+//! Centralized ban management
+//!
+//! Ban modules (KLINE, GLINE, DLINE, XLINE, ...) register their ban type with the
+//! [`BanManager`] and store their entries here. The manager owns mask matching,
+//! expiry and persistence, and exposes a single [`BanManager::find_ban`] call that
+//! the connection and registration path uses to enforce every registered type.
+
+use crate::utils::string::wildcard_match;
+use crate::{Error, Result, User};
+use parking_lot::RwLock;
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Longest ban duration accepted, in seconds (ten years)
+pub const MAX_BAN_DURATION: u64 = 10 * 365 * 86400;
+
+/// What part of a connecting user a ban type matches against
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BanTarget {
+    /// `nick!user@host`, `user@host` or bare host masks, matched against real and cloaked hosts
+    UserHost,
+    /// Bare host masks, IPs or CIDR ranges matched against the real host only
+    Host,
+    /// IP addresses or CIDR ranges, matched against the connecting address
+    Ip,
+    /// Wildcard masks matched against the realname (GECOS)
+    Realname,
+    /// Wildcard masks matched against a nick or channel name being claimed
+    Name,
+}
+
+/// A ban type registered by a module
+#[derive(Debug, Clone)]
+pub struct BanType {
+    /// Type name, e.g. "KLINE"
+    pub name: String,
+    /// What this type's masks match against
+    pub target: BanTarget,
+    /// Prefix used in disconnect reasons, e.g. "K-Lined"
+    pub reason_prefix: String,
+    /// Whether bans of this type are network-wide and sent in server bursts
+    pub global: bool,
+    /// Whether matching users are refused; `false` for types like SHUN that
+    /// restrict users without disconnecting them
+    pub disconnect: bool,
+}
+
+impl BanType {
+    /// Create a new ban type
+    pub fn new(name: &str, target: BanTarget, reason_prefix: &str) -> Self {
+        Self {
+            name: name.to_uppercase(),
+            target,
+            reason_prefix: reason_prefix.to_string(),
+            global: false,
+            disconnect: true,
+        }
+    }
+
+    /// Mark this ban type as network-wide
+    pub fn global(mut self) -> Self {
+        self.global = true;
+        self
+    }
+
+    /// Keep matching users connected; the owning module enforces the ban itself
+    pub fn without_disconnect(mut self) -> Self {
+        self.disconnect = false;
+        self
+    }
+}
+
+/// A single ban entry
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BanEntry {
+    /// Registered ban type name
+    pub ban_type: String,
+    /// Ban mask (hostmask, IP/CIDR or realname pattern depending on type)
+    pub mask: String,
+    /// Reason shown to the banned user
+    pub reason: String,
+    /// Who set the ban
+    pub set_by: String,
+    /// When the ban was set (Unix timestamp)
+    pub set_time: u64,
+    /// When the ban expires (Unix timestamp), `None` for permanent bans
+    pub expire_time: Option<u64>,
+}
+
+impl BanEntry {
+    /// Create a new ban entry set now, optionally expiring after `duration` seconds
+    pub fn new(ban_type: &str, mask: &str, reason: &str, set_by: &str, duration: Option<u64>) -> Self {
+        let now = unix_now();
+        Self {
+            ban_type: ban_type.to_uppercase(),
+            mask: mask.to_string(),
+            reason: reason.to_string(),
+            set_by: set_by.to_string(),
+            set_time: now,
+            expire_time: duration.map(|d| now.saturating_add(d)),
+        }
+    }
+
+    /// Check if the ban has expired at the given time
+    pub fn is_expired_at(&self, now: u64) -> bool {
+        self.expire_time.is_some_and(|expire| now >= expire)
+    }
+
+    /// Check if the ban has expired
+    pub fn is_expired(&self) -> bool {
+        self.is_expired_at(unix_now())
+    }
+
+    /// Server-to-server parameters: mask, reason, setter, duration (0 = permanent), set time
+    pub fn to_params(&self) -> Vec<String> {
+        let duration = self.expire_time
+            .map(|expire| expire.saturating_sub(self.set_time))
+            .unwrap_or(0);
+        vec![
+            self.mask.clone(),
+            self.reason.clone(),
+            self.set_by.clone(),
+            duration.to_string(),
+            self.set_time.to_string(),
+        ]
+    }
+
+    /// Parse server-to-server parameters produced by [`BanEntry::to_params`]
+    ///
+    /// Older peers only send mask and reason, with setter and duration optional;
+    /// a missing set time is taken to be now.
+    pub fn from_params(ban_type: &str, params: &[String]) -> Option<Self> {
+        let mask = params.first()?;
+        let reason = params.get(1)?;
+        let set_by = params.get(2).map(String::as_str).unwrap_or("unknown");
+        let duration = match params.get(3) {
+            Some(duration) => Some(duration.parse::<u64>().ok()?).filter(|d| *d > 0),
+            None => None,
+        };
+
+        let mut entry = Self::new(ban_type, mask, reason, set_by, duration);
+        if let Some(set_time) = params.get(4) {
+            entry.set_time = set_time.parse().ok()?;
+            entry.expire_time = duration.map(|d| entry.set_time.saturating_add(d));
+        }
+        Some(entry)
+    }
+}
+
+/// The identity a ban check is performed against
+///
+/// Fields that aren't known yet (e.g. nick before registration) are left `None`
+/// and masks that need them simply don't match.
+#[derive(Debug, Clone, Default)]
+pub struct BanQuery {
+    pub nick: Option<String>,
+    pub username: Option<String>,
+    /// Displayed (possibly cloaked) host
+    pub host: Option<String>,
+    /// Real host
+    pub real_host: Option<String>,
+    pub ip: Option<IpAddr>,
+    pub realname: Option<String>,
+    /// Nick or channel name being claimed
+    pub name: Option<String>,
+}
+
+impl BanQuery {
+    /// Query for a bare connection, before any registration data is known
+    pub fn from_ip(ip: IpAddr) -> Self {
+        Self {
+            ip: Some(ip),
+            real_host: Some(ip.to_string()),
+            ..Self::default()
+        }
+    }
+
+    /// Query for a registered (or registering) user
+    pub fn from_user(user: &User) -> Self {
+        Self {
+            nick: Some(user.nick.clone()),
+            username: Some(user.username.clone()),
+            host: Some(user.host.clone()),
+            real_host: Some(user.real_host.clone()),
+            ip: user.real_host.parse().ok(),
+            realname: Some(user.realname.clone()),
+            name: None,
+        }
+    }
+
+    /// Query for a nick or channel name someone is trying to use
+    pub fn from_name(name: &str) -> Self {
+        Self {
+            name: Some(name.to_string()),
+            ..Self::default()
+        }
+    }
+}
+
+/// Persistence hooks for ban entries
+///
+/// Implementations are called synchronously whenever the ban list changes and
+/// once at startup to restore saved bans.
+pub trait BanStore: Send + Sync {
+    /// Load all saved bans
+    fn load(&self) -> Result<Vec<BanEntry>>;
+    /// Save (insert or replace) a ban
+    fn save(&self, entry: &BanEntry) -> Result<()>;
+    /// Remove a saved ban
+    fn remove(&self, ban_type: &str, mask: &str) -> Result<()>;
+}
+
+/// Flat-file [`BanStore`], one tab-separated ban per line
+///
+/// Lines hold the ban type followed by the fields of [`BanEntry::to_params`].
+/// The whole file is rewritten on every change, which is fine for the
+/// hundreds of entries a ban list typically holds.
+pub struct FileBanStore {
+    path: PathBuf,
+    lock: parking_lot::Mutex<()>,
+}
+
+impl FileBanStore {
+    /// Create a store backed by the given file; it is created on first save
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            lock: parking_lot::Mutex::new(()),
+        }
+    }
+
+    fn read_entries(&self) -> Result<Vec<BanEntry>> {
+        let content = match std::fs::read_to_string(&self.path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(Error::Config(format!("Failed to read ban file {}: {}", self.path.display(), e))),
+        };
+
+        let mut entries = Vec::new();
+        for (line_no, line) in content.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let fields: Vec<String> = line.split('\t').map(str::to_string).collect();
+            match fields.split_first().and_then(|(ban_type, params)| BanEntry::from_params(ban_type, params)) {
+                Some(entry) => entries.push(entry),
+                None => tracing::warn!("Skipping malformed line {} in ban file {}", line_no + 1, self.path.display()),
+            }
+        }
+        Ok(entries)
+    }
+
+    fn write_entries(&self, entries: &[BanEntry]) -> Result<()> {
+        let mut content = String::new();
+        for entry in entries {
+            let mut fields = vec![entry.ban_type.clone()];
+            fields.extend(entry.to_params().into_iter().map(|f| f.replace(['\t', '\r', '\n'], " ")));
+            content.push_str(&fields.join("\t"));
+            content.push('\n');
+        }
+
+        // Write to a temporary file first so a crash never leaves a truncated ban list
+        let tmp_path = self.path.with_extension("tmp");
+        std::fs::write(&tmp_path, content)
+            .and_then(|_| std::fs::rename(&tmp_path, &self.path))
+            .map_err(|e| Error::Config(format!("Failed to write ban file {}: {}", self.path.display(), e)))
+    }
+}
+
+impl BanStore for FileBanStore {
+    fn load(&self) -> Result<Vec<BanEntry>> {
+        let _guard = self.lock.lock();
+        self.read_entries()
+    }
+
+    fn save(&self, entry: &BanEntry) -> Result<()> {
+        let _guard = self.lock.lock();
+        let mut entries = self.read_entries()?;
+        let key = BanManager::key(&entry.ban_type, &entry.mask);
+        entries.retain(|e| BanManager::key(&e.ban_type, &e.mask) != key);
+        entries.push(entry.clone());
+        self.write_entries(&entries)
+    }
+
+    fn remove(&self, ban_type: &str, mask: &str) -> Result<()> {
+        let _guard = self.lock.lock();
+        let mut entries = self.read_entries()?;
+        let key = BanManager::key(ban_type, mask);
+        entries.retain(|e| BanManager::key(&e.ban_type, &e.mask) != key);
+        self.write_entries(&entries)
+    }
+}
+
+/// Central ban registry shared by all ban modules
+#[derive(Default)]
+pub struct BanManager {
+    /// Registered ban types by name
+    types: RwLock<HashMap<String, BanType>>,
+    /// Ban entries keyed by (type, lowercased mask)
+    bans: RwLock<HashMap<(String, String), BanEntry>>,
+    /// Optional persistence backend
+    store: RwLock<Option<Arc<dyn BanStore>>>,
+}
+
+impl BanManager {
+    /// Create a new, empty ban manager
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A manager with a single ban type registered
+    ///
+    /// Ban modules hold one of these until they are loaded into a server,
+    /// which hands them the shared manager instead.
+    pub fn with_type(ban_type: BanType) -> Arc<Self> {
+        let manager = Arc::new(Self::new());
+        manager.register_type(ban_type);
+        manager
+    }
+
+    /// Parse a ban duration such as "1d", "2h", "30m", "3600s" or "3600"
+    ///
+    /// "0" or an empty string means permanent and gives `None`; durations
+    /// over [`MAX_BAN_DURATION`] are refused.
+    pub fn parse_duration(duration: &str) -> Result<Option<u64>> {
+        if duration == "0" || duration.is_empty() {
+            return Ok(None);
+        }
+
+        let duration = duration.to_lowercase();
+        let (number, multiplier) = match duration.chars().last() {
+            Some('d') => (&duration[..duration.len() - 1], 86400),
+            Some('h') => (&duration[..duration.len() - 1], 3600),
+            Some('m') => (&duration[..duration.len() - 1], 60),
+            Some('s') => (&duration[..duration.len() - 1], 1),
+            _ => (duration.as_str(), 1),
+        };
+
+        let number: u64 = number.parse()
+            .map_err(|_| Error::Config(format!("Invalid duration: {}", duration)))?;
+        number.checked_mul(multiplier)
+            .filter(|seconds| *seconds <= MAX_BAN_DURATION)
+            .map(Some)
+            .ok_or_else(|| Error::Config(format!("Duration too long: {}", duration)))
+    }
+
+    /// Register a ban type; re-registering the same name replaces it
+    pub fn register_type(&self, ban_type: BanType) {
+        tracing::debug!("Registered ban type {} ({:?})", ban_type.name, ban_type.target);
+        self.types.write().insert(ban_type.name.clone(), ban_type);
+    }
+
+    /// Get a registered ban type
+    pub fn get_type(&self, name: &str) -> Option<BanType> {
+        self.types.read().get(&name.to_uppercase()).cloned()
+    }
+
+    /// Names of all registered ban types
+    pub fn registered_types(&self) -> Vec<String> {
+        self.types.read().keys().cloned().collect()
+    }
+
+    /// Registered ban types that are network-wide
+    pub fn global_types(&self) -> Vec<BanType> {
+        self.types.read().values().filter(|t| t.global).cloned().collect()
+    }
+
+    /// Attach a persistence backend and load any saved bans from it
+    pub fn set_store(&self, store: Arc<dyn BanStore>) -> Result<usize> {
+        let saved = store.load()?;
+        let now = unix_now();
+        let mut loaded = 0;
+        {
+            let mut bans = self.bans.write();
+            for entry in saved.into_iter().filter(|e| !e.is_expired_at(now)) {
+                bans.insert(Self::key(&entry.ban_type, &entry.mask), entry);
+                loaded += 1;
+            }
+        }
+        *self.store.write() = Some(store);
+        tracing::info!("Loaded {} saved bans", loaded);
+        Ok(loaded)
+    }
+
+    /// Switch to `store` and replace every ban with `saved`, its contents
+    ///
+    /// Used on rehash, where the ban file is read first so a failure leaves
+    /// the current list alone. Bans removed from the file by hand are lifted.
+    /// Returns the active bans that weren't in force before.
+    pub fn replace_store(&self, store: Arc<dyn BanStore>, saved: Vec<BanEntry>) -> Vec<BanEntry> {
+        let now = unix_now();
+        let mut added = Vec::new();
+        {
+            let mut bans = self.bans.write();
+            let previous = std::mem::take(&mut *bans);
+            for entry in saved.into_iter().filter(|e| !e.is_expired_at(now)) {
+                let key = Self::key(&entry.ban_type, &entry.mask);
+                if previous.get(&key) != Some(&entry) {
+                    added.push(entry.clone());
+                }
+                bans.insert(key, entry);
+            }
+        }
+        *self.store.write() = Some(store);
+        tracing::info!("Reloaded saved bans, {} new", added.len());
+        added
+    }
+
+    /// Add or replace a ban; the ban type must be registered
+    pub fn add_ban(&self, entry: BanEntry) -> Result<()> {
+        let ban_type = self.get_type(&entry.ban_type)
+            .ok_or_else(|| Error::Config(format!("Unknown ban type: {}", entry.ban_type)))?;
+
+        if ban_type.target == BanTarget::Ip && parse_ip_mask(&entry.mask).is_none() {
+            return Err(Error::Config(format!("Invalid IP or CIDR mask: {}", entry.mask)));
+        }
+
+        if let Some(store) = self.store.read().as_ref() {
+            if let Err(e) = store.save(&entry) {
+                tracing::warn!("Failed to persist {} {}: {}", entry.ban_type, entry.mask, e);
+            }
+        }
+
+        self.bans.write().insert(Self::key(&entry.ban_type, &entry.mask), entry);
+        Ok(())
+    }
+
+    /// Remove a ban, returning it if it existed
+    pub fn remove_ban(&self, ban_type: &str, mask: &str) -> Option<BanEntry> {
+        let removed = self.bans.write().remove(&Self::key(ban_type, mask));
+
+        if removed.is_some() {
+            if let Some(store) = self.store.read().as_ref() {
+                if let Err(e) = store.remove(&ban_type.to_uppercase(), mask) {
+                    tracing::warn!("Failed to remove persisted {} {}: {}", ban_type, mask, e);
+                }
+            }
+        }
+
+        removed
+    }
+
+    /// Get a ban by type and mask
+    pub fn get_ban(&self, ban_type: &str, mask: &str) -> Option<BanEntry> {
+        self.bans.read().get(&Self::key(ban_type, mask)).cloned()
+    }
+
+    /// List unexpired bans of a type, oldest first
+    pub fn list_bans(&self, ban_type: &str) -> Vec<BanEntry> {
+        let ban_type = ban_type.to_uppercase();
+        let now = unix_now();
+        let mut bans: Vec<BanEntry> = self.bans.read()
+            .values()
+            .filter(|b| b.ban_type == ban_type && !b.is_expired_at(now))
+            .cloned()
+            .collect();
+        bans.sort_by_key(|b| b.set_time);
+        bans
+    }
+
+    /// Count unexpired bans of a type
+    pub fn active_count(&self, ban_type: &str) -> usize {
+        self.list_bans(ban_type).len()
+    }
+
+    /// Count expired bans of a type that haven't been cleaned up yet
+    pub fn expired_count(&self, ban_type: &str) -> usize {
+        let ban_type = ban_type.to_uppercase();
+        let now = unix_now();
+        self.bans.read()
+            .values()
+            .filter(|b| b.ban_type == ban_type && b.is_expired_at(now))
+            .count()
+    }
+
+    /// Find the first active ban of any disconnecting type matching the query
+    ///
+    /// This is the single enforcement call used when a connection is accepted
+    /// and again when a user completes registration.
+    pub fn find_ban(&self, query: &BanQuery) -> Option<BanEntry> {
+        let now = unix_now();
+        let types = self.types.read();
+        self.bans.read()
+            .values()
+            .filter(|b| !b.is_expired_at(now))
+            .find(|b| {
+                types.get(&b.ban_type)
+                    .is_some_and(|t| t.disconnect && Self::matches(t.target, &b.mask, query))
+            })
+            .cloned()
+    }
+
+    /// Find the first active ban of a single type matching the query
+    pub fn find_ban_of_type(&self, ban_type: &str, query: &BanQuery) -> Option<BanEntry> {
+        let ban_type = ban_type.to_uppercase();
+        let target = self.types.read().get(&ban_type)?.target;
+        let now = unix_now();
+        self.bans.read()
+            .values()
+            .filter(|b| b.ban_type == ban_type && !b.is_expired_at(now))
+            .find(|b| Self::matches(target, &b.mask, query))
+            .cloned()
+    }
+
+    /// Every active ban of any type matching the query, ordered by type and mask
+    ///
+    /// Unlike [`find_ban`](Self::find_ban) this includes types that don't
+    /// disconnect, for reporting what a ban would hit (TESTLINE).
+    pub fn matching_bans(&self, query: &BanQuery) -> Vec<BanEntry> {
+        let now = unix_now();
+        let types = self.types.read();
+        let mut bans: Vec<BanEntry> = self.bans.read()
+            .values()
+            .filter(|b| !b.is_expired_at(now))
+            .filter(|b| types.get(&b.ban_type).is_some_and(|t| Self::matches(t.target, &b.mask, query)))
+            .cloned()
+            .collect();
+        bans.sort_by(|a, b| (&a.ban_type, &a.mask).cmp(&(&b.ban_type, &b.mask)));
+        bans
+    }
+
+    /// Find an active ban matching a user
+    pub fn check_user(&self, user: &User) -> Option<BanEntry> {
+        self.find_ban(&BanQuery::from_user(user))
+    }
+
+    /// Check whether a mask of the given type matches a user (used when a new ban is set)
+    pub fn matching_ban(&self, ban_type: &str, mask: &str, user: &User) -> bool {
+        self.get_type(ban_type)
+            .is_some_and(|t| Self::matches(t.target, mask, &BanQuery::from_user(user)))
+    }
+
+    /// Disconnect reason for a ban, e.g. "K-Lined: spamming"
+    pub fn ban_reason(&self, entry: &BanEntry) -> String {
+        let prefix = self.get_type(&entry.ban_type)
+            .map(|t| t.reason_prefix)
+            .unwrap_or_else(|| "Banned".to_string());
+        format!("{}: {}", prefix, entry.reason)
+    }
+
+    /// Remove expired bans of every type, returning the removed entries
+    pub fn cleanup_expired(&self) -> Vec<BanEntry> {
+        self.remove_expired(|_| true)
+    }
+
+    /// Remove expired bans of a single type, returning the removed entries
+    pub fn cleanup_expired_type(&self, ban_type: &str) -> Vec<BanEntry> {
+        let ban_type = ban_type.to_uppercase();
+        self.remove_expired(|ban| ban.ban_type == ban_type)
+    }
+
+    fn remove_expired(&self, filter: impl Fn(&BanEntry) -> bool) -> Vec<BanEntry> {
+        let now = unix_now();
+        let mut expired = Vec::new();
+        self.bans.write().retain(|_, ban| {
+            if filter(ban) && ban.is_expired_at(now) {
+                expired.push(ban.clone());
+                false
+            } else {
+                true
+            }
+        });
+
+        if let Some(store) = self.store.read().as_ref() {
+            for ban in &expired {
+                if let Err(e) = store.remove(&ban.ban_type, &ban.mask) {
+                    tracing::warn!("Failed to remove expired {} {}: {}", ban.ban_type, ban.mask, e);
+                }
+            }
+        }
+
+        expired
+    }
+
+    /// Spawn a background task that removes expired bans every `interval`
+    pub fn start_expiry_task(self: &Arc<Self>, interval: Duration) -> tokio::task::JoinHandle<()> {
+        let manager = Arc::clone(self);
+        tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(interval).await;
+                for ban in manager.cleanup_expired() {
+                    tracing::info!("{} for {} expired (set by {})", ban.ban_type, ban.mask, ban.set_by);
+                }
+            }
+        })
+    }
+
+    /// Check a single mask of the given target kind against a query
+    pub fn matches(target: BanTarget, mask: &str, query: &BanQuery) -> bool {
+        match target {
+            BanTarget::Ip => match (parse_ip_mask(mask), query.ip) {
+                (Some((network, prefix)), Some(ip)) => ip_in_network(ip, network, prefix),
+                _ => false,
+            },
+            BanTarget::Host => {
+                let hosts = [query.real_host.clone(), query.ip.map(|ip| ip.to_string())];
+                hosts.iter().flatten().any(|host| {
+                    // A plain domain also matches its subdomains
+                    Self::matches_host(mask, host)
+                        || (!mask.contains(['*', '?'])
+                            && host.to_lowercase().ends_with(&format!(".{}", mask.to_lowercase())))
+                })
+            }
+            BanTarget::Realname => query.realname.as_deref()
+                .is_some_and(|realname| wildcard_match(mask, realname)),
+            BanTarget::Name => query.name.as_deref()
+                .is_some_and(|name| wildcard_match(mask, name)),
+            BanTarget::UserHost => {
+                // Try every host we know: real host, IP and displayed (cloaked) host
+                let hosts = [
+                    query.real_host.clone(),
+                    query.ip.map(|ip| ip.to_string()),
+                    query.host.clone(),
+                ];
+                hosts.iter().flatten().any(|host| Self::matches_user_host(mask, query, host))
+            }
+        }
+    }
+
+    fn matches_user_host(mask: &str, query: &BanQuery, host: &str) -> bool {
+        let (nick_user, host_mask) = match mask.rsplit_once('@') {
+            Some((nick_user, host_mask)) => (Some(nick_user), host_mask),
+            None => (None, mask),
+        };
+
+        if !Self::matches_host(host_mask, host) {
+            return false;
+        }
+
+        let Some(nick_user) = nick_user else {
+            return true;
+        };
+        let (nick_mask, user_mask) = match nick_user.split_once('!') {
+            Some((nick_mask, user_mask)) => (Some(nick_mask), user_mask),
+            None => (None, nick_user),
+        };
+
+        let user_matches = user_mask == "*" || query.username.as_deref()
+            .is_some_and(|username| wildcard_match(user_mask, username));
+        let nick_matches = match nick_mask {
+            None => true,
+            Some("*") => true,
+            Some(nick_mask) => query.nick.as_deref().is_some_and(|nick| wildcard_match(nick_mask, nick)),
+        };
+        user_matches && nick_matches
+    }
+
+    /// Match a host mask, which may be an IP address or CIDR range, against a
+    /// single host
+    ///
+    /// Addresses are compared as addresses, so `2001:db8::1` matches
+    /// `2001:DB8:0::1` and `192.0.2.1` matches `::ffff:192.0.2.1`.
+    fn matches_host(mask: &str, host: &str) -> bool {
+        match (parse_ip_mask(mask), host.parse::<IpAddr>()) {
+            (Some((network, prefix)), Ok(ip)) => ip_in_network(ip, network, prefix),
+            _ => wildcard_match(mask, host),
+        }
+    }
+
+    fn key(ban_type: &str, mask: &str) -> (String, String) {
+        (ban_type.to_uppercase(), mask.to_lowercase())
+    }
+}
+
+/// Parse an IP address or CIDR range into (network, prefix length)
+pub fn parse_ip_mask(mask: &str) -> Option<(IpAddr, u8)> {
+    match mask.split_once('/') {
+        Some((addr, prefix)) => {
+            let addr: IpAddr = addr.parse().ok()?;
+            let prefix: u8 = prefix.parse().ok()?;
+            let max = if addr.is_ipv4() { 32 } else { 128 };
+            (prefix <= max).then_some((addr, prefix))
+        }
+        None => {
+            let addr: IpAddr = mask.parse().ok()?;
+            Some((addr, if addr.is_ipv4() { 32 } else { 128 }))
+        }
+    }
+}
+
+/// Check if an IP lies within a network of the given prefix length
+///
+/// IPv4-mapped IPv6 addresses (`::ffff:192.0.2.1`, as seen on dual-stack
+/// sockets) are treated as the IPv4 address they carry.
+pub fn ip_in_network(ip: IpAddr, network: IpAddr, prefix: u8) -> bool {
+    let (ip, network) = (ip.to_canonical(), network.to_canonical());
+    ip.is_ipv4() == network.is_ipv4() && ip_network(ip, prefix) == ip_network(network, prefix)
+}
+
+/// The network of the given prefix length that `ip` lies in
+pub fn ip_network(ip: IpAddr, prefix: u8) -> IpAddr {
+    match ip {
+        IpAddr::V4(ip) => {
+            let mask = u32::MAX.checked_shl(32 - prefix.min(32) as u32).unwrap_or(0);
+            IpAddr::from((u32::from(ip) & mask).to_be_bytes())
+        }
+        IpAddr::V6(ip) => {
+            let mask = u128::MAX.checked_shl(128 - prefix.min(128) as u32).unwrap_or(0);
+            IpAddr::from((u128::from(ip) & mask).to_be_bytes())
+        }
+    }
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn manager() -> BanManager {
+        let manager = BanManager::new();
+        manager.register_type(BanType::new("KLINE", BanTarget::UserHost, "K-Lined"));
+        manager.register_type(BanType::new("DLINE", BanTarget::Host, "D-Lined"));
+        manager.register_type(BanType::new("ZLINE", BanTarget::Ip, "Z-Lined"));
+        manager.register_type(BanType::new("XLINE", BanTarget::Realname, "X-Lined"));
+        manager
+    }
+
+    fn user(nick: &str, username: &str, host: &str, realname: &str) -> User {
+        User::new(nick.to_string(), username.to_string(), realname.to_string(), host.to_string(), "irc.example.com".to_string())
+    }
+
+    #[test]
+    fn test_hostmask_matching() {
+        let manager = manager();
+        manager.add_ban(BanEntry::new("KLINE", "*@*.badhost.com", "spam", "oper", None)).unwrap();
+
+        assert!(manager.check_user(&user("alice", "alice", "dsl.badhost.com", "Alice")).is_some());
+        assert!(manager.check_user(&user("bob", "bob", "good.example.com", "Bob")).is_none());
+
+        // Cloaked users are still matched on their real host
+        let mut cloaked = user("carol", "carol", "dsl.badhost.com", "Carol");
+        cloaked.set_cloak("abcd1234.cloaked".to_string());
+        let ban = manager.check_user(&cloaked).unwrap();
+        assert_eq!(manager.ban_reason(&ban), "K-Lined: spam");
+    }
+
+    #[test]
+    fn test_cidr_and_realname_matching() {
+        let manager = manager();
+        manager.add_ban(BanEntry::new("ZLINE", "192.0.2.0/24", "bad range", "oper", None)).unwrap();
+        manager.add_ban(BanEntry::new("DLINE", "badisp.example", "bad isp", "oper", None)).unwrap();
+        manager.add_ban(BanEntry::new("XLINE", "*spambot*", "bots", "oper", None)).unwrap();
+        assert!(manager.add_ban(BanEntry::new("ZLINE", "not-an-ip", "x", "oper", None)).is_err());
+        assert!(manager.add_ban(BanEntry::new("UNKNOWN", "*", "x", "oper", None)).is_err());
+
+        assert!(manager.find_ban(&BanQuery::from_ip("192.0.2.77".parse().unwrap())).is_some());
+        assert!(manager.find_ban(&BanQuery::from_ip("198.51.100.1".parse().unwrap())).is_none());
+        assert!(manager.check_user(&user("dave", "dave", "203.0.113.5", "I am a SpamBot")).is_some());
+        assert!(manager.check_user(&user("fred", "fred", "dsl-1.badisp.example", "Fred")).is_some());
+        assert!(manager.check_user(&user("gina", "gina", "notbadisp.example", "Gina")).is_none());
+    }
+
+    #[test]
+    fn test_ipv6_matching() {
+        let manager = manager();
+        manager.add_ban(BanEntry::new("DLINE", "2001:db8:1::/48", "bad range", "oper", None)).unwrap();
+        manager.add_ban(BanEntry::new("DLINE", "2001:DB8:2:0::7", "one host", "oper", None)).unwrap();
+        manager.add_ban(BanEntry::new("ZLINE", "192.0.2.0/24", "bad range", "oper", None)).unwrap();
+
+        assert!(manager.find_ban(&BanQuery::from_ip("2001:db8:1:ffff::1".parse().unwrap())).is_some());
+        assert!(manager.find_ban(&BanQuery::from_ip("2001:db8:2::7".parse().unwrap())).is_some());
+        assert!(manager.find_ban(&BanQuery::from_ip("2001:db8:2::8".parse().unwrap())).is_none());
+        assert!(manager.check_user(&user("ivan", "ivan", "2001:db8:1::abcd", "Ivan")).is_some());
+
+        // Dual-stack sockets report IPv4 clients as mapped addresses
+        assert!(manager.find_ban(&BanQuery::from_ip("::ffff:192.0.2.9".parse().unwrap())).is_some());
+        assert!(!ip_in_network("::c000:209".parse().unwrap(), "192.0.2.0".parse().unwrap(), 24));
+        assert_eq!(ip_network("2001:db8:1:2:3:4:5:6".parse().unwrap(), 64), "2001:db8:1:2::".parse::<IpAddr>().unwrap());
+    }
+
+    #[test]
+    fn test_expiry_and_removal() {
+        let manager = manager();
+        let mut expired = BanEntry::new("KLINE", "*@old.example.com", "old", "oper", Some(60));
+        expired.expire_time = Some(expired.set_time - 1);
+        manager.add_ban(expired).unwrap();
+        manager.add_ban(BanEntry::new("KLINE", "*@new.example.com", "new", "oper", Some(3600))).unwrap();
+
+        assert!(manager.check_user(&user("erin", "erin", "old.example.com", "Erin")).is_none());
+        assert_eq!(manager.active_count("KLINE"), 1);
+        assert_eq!(manager.expired_count("KLINE"), 1);
+        assert_eq!(manager.cleanup_expired().len(), 1);
+
+        assert!(manager.remove_ban("kline", "*@NEW.example.com").is_some());
+        assert_eq!(manager.active_count("KLINE"), 0);
+    }
+
+    #[test]
+    fn test_non_disconnecting_types() {
+        let manager = manager();
+        manager.register_type(BanType::new("SHUN", BanTarget::UserHost, "Shunned").global().without_disconnect());
+        manager.add_ban(BanEntry::new("SHUN", "*@flood.example.com", "flooding", "oper", None)).unwrap();
+
+        let flooder = user("hank", "hank", "flood.example.com", "Hank");
+        assert!(manager.check_user(&flooder).is_none());
+        assert!(manager.find_ban_of_type("shun", &BanQuery::from_user(&flooder)).is_some());
+        assert!(manager.find_ban_of_type("KLINE", &BanQuery::from_user(&flooder)).is_none());
+
+        // Reporting sees every type, disconnecting or not
+        manager.add_ban(BanEntry::new("KLINE", "hank@*", "hank", "oper", None)).unwrap();
+        let types: Vec<String> = manager.matching_bans(&BanQuery::from_user(&flooder)).into_iter().map(|ban| ban.ban_type).collect();
+        assert_eq!(types, ["KLINE", "SHUN"]);
+    }
+
+    #[test]
+    fn test_name_reservations_and_file_store() {
+        let path = std::env::temp_dir().join(format!("rustircd-bans-{}.db", uuid::Uuid::new_v4()));
+        let bans = manager();
+        bans.register_type(BanType::new("RESV", BanTarget::Name, "Reserved").global().without_disconnect());
+        bans.set_store(Arc::new(FileBanStore::new(&path))).unwrap();
+        bans.add_ban(BanEntry::new("RESV", "*Serv", "services", "oper", None)).unwrap();
+        bans.add_ban(BanEntry::new("RESV", "#opers", "staff only", "oper", Some(3600))).unwrap();
+        bans.add_ban(BanEntry::new("KLINE", "*@old.example.com", "old", "oper", None)).unwrap();
+        bans.remove_ban("KLINE", "*@old.example.com");
+
+        assert!(bans.find_ban_of_type("RESV", &BanQuery::from_name("NickServ")).is_some());
+        assert!(bans.find_ban_of_type("RESV", &BanQuery::from_name("#OPERS")).is_some());
+        assert!(bans.find_ban_of_type("RESV", &BanQuery::from_name("#rust")).is_none());
+
+        // A fresh manager restores the same entries from disk
+        let restored = manager();
+        assert_eq!(restored.set_store(Arc::new(FileBanStore::new(&path))).unwrap(), 2);
+        assert_eq!(restored.get_ban("RESV", "#opers"), bans.get_ban("RESV", "#opers"));
+        assert!(restored.get_ban("KLINE", "*@old.example.com").is_none());
+
+        // Reloading picks up bans added behind the manager's back and drops removed ones
+        let store = Arc::new(FileBanStore::new(&path));
+        store.remove("RESV", "*Serv").unwrap();
+        store.save(&BanEntry::new("KLINE", "*@new.example.com", "new", "oper", None)).unwrap();
+        let added = restored.replace_store(store.clone(), store.load().unwrap());
+        assert_eq!(added.iter().map(|ban| ban.mask.as_str()).collect::<Vec<_>>(), ["*@new.example.com"]);
+        assert!(restored.get_ban("RESV", "*Serv").is_none());
+        assert!(restored.get_ban("RESV", "#opers").is_some());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(BanManager::parse_duration("1d").unwrap(), Some(86400));
+        assert_eq!(BanManager::parse_duration("2H").unwrap(), Some(7200));
+        assert_eq!(BanManager::parse_duration("30m").unwrap(), Some(1800));
+        assert_eq!(BanManager::parse_duration("3600s").unwrap(), Some(3600));
+        assert_eq!(BanManager::parse_duration("3600").unwrap(), Some(3600));
+        assert_eq!(BanManager::parse_duration("0").unwrap(), None);
+        assert_eq!(BanManager::parse_duration("").unwrap(), None);
+        assert!(BanManager::parse_duration("soon").is_err());
+        assert!(BanManager::parse_duration("1x").is_err());
+        assert!(BanManager::parse_duration(&format!("{}d", u64::MAX)).is_err());
+        assert!(BanManager::parse_duration("213503982334600d").is_err());
+        assert!(BanManager::parse_duration(&(MAX_BAN_DURATION + 1).to_string()).is_err());
+        assert_eq!(BanManager::parse_duration(&MAX_BAN_DURATION.to_string()).unwrap(), Some(MAX_BAN_DURATION));
+    }
+
+    #[test]
+    fn test_server_params_round_trip() {
+        let mut entry = BanEntry::new("GLINE", "*@spam.example.com", "spam bots", "oper", Some(3600));
+        entry.set_time -= 100;
+        entry.expire_time = Some(entry.set_time + 3600);
+
+        let parsed = BanEntry::from_params("GLINE", &entry.to_params()).unwrap();
+        assert_eq!(parsed, entry);
+
+        let permanent = BanEntry::from_params("GLINE", &["*@x".to_string(), "reason".to_string()]).unwrap();
+        assert_eq!(permanent.set_by, "unknown");
+        assert!(permanent.expire_time.is_none());
+        assert!(BanEntry::from_params("GLINE", &["*@x".to_string()]).is_none());
+    }
+
+    #[test]
+    fn test_huge_durations_saturate() {
+        let entry = BanEntry::new("KLINE", "*@x", "reason", "oper", Some(u64::MAX));
+        assert_eq!(entry.expire_time, Some(u64::MAX));
+        assert!(!entry.is_expired());
+
+        let params: Vec<String> = ["*@x", "reason", "oper", &u64::MAX.to_string(), "1000"].iter().map(|p| p.to_string()).collect();
+        let remote = BanEntry::from_params("KLINE", &params).unwrap();
+        assert_eq!(remote.expire_time, Some(u64::MAX));
+        assert!(!remote.is_expired());
+    }
+}
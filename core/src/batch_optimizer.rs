@@ -85,6 +85,7 @@ impl MessageBatch {
 }
 
 /// Message batching optimizer
+#[derive(Debug)]
 pub struct BatchOptimizer {
     /// Pending batches per target
     batches: DashMap<Uuid, Arc<RwLock<MessageBatch>>>,
@@ -103,6 +104,13 @@ pub struct BatchStats {
     pub average_batch_size: f64,
 }
 
+impl BatchStats {
+    /// Socket writes avoided by sending each batch in one write
+    pub fn writes_saved(&self) -> u64 {
+        self.total_messages_batched.saturating_sub(self.total_batches_sent)
+    }
+}
+
 impl BatchOptimizer {
     pub fn new(config: BatchConfig) -> Self {
         Self {
@@ -160,6 +168,44 @@ impl BatchOptimizer {
         ready
     }
 
+    /// Take the messages of every batch that is due, per target
+    ///
+    /// Unlike `get_ready_batches` the messages are handed back as they are,
+    /// for callers that deliver through a client's outbound queue.
+    pub async fn take_ready_batches(&self) -> Vec<(Uuid, Vec<Message>)> {
+        self.take_batches(false).await
+    }
+
+    /// Take the messages of every non-empty batch, due or not
+    pub async fn take_all_batches(&self) -> Vec<(Uuid, Vec<Message>)> {
+        self.take_batches(true).await
+    }
+
+    async fn take_batches(&self, force: bool) -> Vec<(Uuid, Vec<Message>)> {
+        let batches: Vec<_> = self.batches.iter()
+            .map(|entry| (*entry.key(), entry.value().clone()))
+            .collect();
+        let mut taken = Vec::new();
+        for (target_id, batch) in batches {
+            let mut batch_guard = batch.write().await;
+            if batch_guard.is_empty() || !(force || batch_guard.should_flush(&self.config)) {
+                continue;
+            }
+            let messages: Vec<Message> = batch_guard.messages.drain(..).collect();
+            batch_guard.total_bytes = 0;
+            batch_guard.created_at = Instant::now();
+            drop(batch_guard);
+
+            let mut stats = self.stats.write().await;
+            stats.total_messages_batched += messages.len() as u64;
+            stats.total_batches_sent += 1;
+            stats.average_batch_size =
+                (stats.total_messages_batched as f64) / (stats.total_batches_sent as f64);
+            taken.push((target_id, messages));
+        }
+        taken
+    }
+
     /// Force flush a specific target's batch
     pub async fn flush_target(&self, target_id: &Uuid) -> Option<String> {
         if let Some(batch) = self.batches.get(target_id) {
@@ -342,6 +388,33 @@ mod tests {
         assert_eq!(ready[0].0, target_id);
     }
 
+    #[tokio::test]
+    async fn test_take_batches() {
+        let config = BatchConfig {
+            max_batch_size: 10,
+            max_batch_delay: Duration::from_secs(60),
+            max_batch_bytes: 1000,
+        };
+        let optimizer = BatchOptimizer::new(config);
+        let target_id = Uuid::new_v4();
+        for text in ["one", "two", "three"] {
+            let msg = Message::new(MessageType::PrivMsg, vec!["#test".to_string(), text.to_string()]);
+            optimizer.add_message(target_id, msg).await.unwrap();
+        }
+
+        // Not due yet, so only a forced take returns it, in order
+        assert!(optimizer.take_ready_batches().await.is_empty());
+        let taken = optimizer.take_all_batches().await;
+        assert_eq!(taken.len(), 1);
+        let texts: Vec<_> = taken[0].1.iter().map(|m| m.params[1].as_str()).collect();
+        assert_eq!(texts, ["one", "two", "three"]);
+        assert!(optimizer.take_all_batches().await.is_empty());
+
+        let stats = optimizer.stats().await;
+        assert_eq!(stats.total_batches_sent, 1);
+        assert_eq!(stats.writes_saved(), 2);
+    }
+
     #[tokio::test]
     async fn test_connection_pool() {
         let pool = ConnectionPool::new(5);
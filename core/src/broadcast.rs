@@ -1,9 +1,9 @@
 //! Efficient message broadcasting system for IRC daemon
 
-use crate::{Message, User, Error, Result, Client};
-use std::collections::{HashSet, VecDeque};
+use crate::{Message, User, Error, Result, Client, ClientSenders, BatchConfig, BatchOptimizer, BatchStats};
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::sync::Arc;
-use tokio::sync::RwLock;
+use tokio::sync::{mpsc, RwLock};
 use uuid::Uuid;
 use dashmap::DashMap;
 
@@ -18,6 +18,8 @@ pub enum BroadcastTarget {
     AllExcept(Uuid),
     /// Specific users by nickname
     Users(Vec<String>),
+    /// Specific local clients by ID
+    Clients(Vec<Uuid>),
     /// All servers in the network
     AllServers,
     /// Specific servers
@@ -53,12 +55,18 @@ pub struct BroadcastSystem {
     message_queues: [VecDeque<BroadcastMessage>; 4],
     /// User subscriptions (user_id -> set of channels)
     user_subscriptions: DashMap<Uuid, HashSet<String>>,
-    /// Channel subscriptions (channel -> set of user_ids)
-    channel_subscriptions: DashMap<String, HashSet<Uuid>>,
+    /// Channel members with the outbound queue of each local one
+    /// (channel -> user_id -> sender), so a channel broadcast is one lookup
+    /// plus a send per member
+    channel_subscriptions: DashMap<String, HashMap<Uuid, Option<mpsc::UnboundedSender<Message>>>>,
     /// Server connections
     server_connections: DashMap<String, Uuid>,
     /// Client connections
     client_connections: DashMap<Uuid, Arc<Client>>,
+    /// Outbound queues of the server's clients, for indexing channel members
+    client_senders: Arc<ClientSenders>,
+    /// Coalesces queued broadcasts per recipient when batching is enabled
+    batcher: Option<Arc<BatchOptimizer>>,
     /// Broadcast statistics
     stats: Arc<RwLock<BroadcastStats>>,
 }
@@ -76,6 +84,11 @@ pub struct BroadcastStats {
 impl BroadcastSystem {
     /// Create a new broadcast system
     pub fn new() -> Self {
+        Self::with_client_senders(Arc::new(ClientSenders::new()))
+    }
+
+    /// Create a broadcast system delivering to the server's client queues
+    pub fn with_client_senders(client_senders: Arc<ClientSenders>) -> Self {
         Self {
             message_queues: [
                 VecDeque::new(), // Low
@@ -87,19 +100,59 @@ impl BroadcastSystem {
             channel_subscriptions: DashMap::new(),
             server_connections: DashMap::new(),
             client_connections: DashMap::new(),
+            client_senders,
+            batcher: None,
             stats: Arc::new(RwLock::new(BroadcastStats::default())),
         }
     }
 
+    /// Use the server's client queues for channel members subscribed from now on
+    pub fn set_client_senders(&mut self, client_senders: Arc<ClientSenders>) {
+        self.client_senders = client_senders;
+    }
+
+    /// Coalesce queued broadcasts per recipient
+    ///
+    /// `process_queues` then hands each recipient's messages to its queue
+    /// together once `config.max_batch_delay` has passed (or the batch is
+    /// full), so they go out in one socket write. Critical broadcasts are
+    /// never held back.
+    pub fn enable_batching(&mut self, config: BatchConfig) {
+        self.batcher = Some(Arc::new(BatchOptimizer::new(config)));
+    }
+
+    /// Batching statistics, when batching is enabled
+    pub async fn batch_stats(&self) -> Option<BatchStats> {
+        match &self.batcher {
+            Some(batcher) => Some(batcher.stats().await),
+            None => None,
+        }
+    }
+
     /// Register a client connection
     pub fn register_client(&self, client_id: Uuid, client: Arc<Client>) {
+        let sender = client.sender.clone();
         self.client_connections.insert(client_id, client);
+        if let Some(channels) = self.user_subscriptions.get(&client_id) {
+            for channel in channels.iter() {
+                if let Some(mut members) = self.channel_subscriptions.get_mut(channel) {
+                    members.insert(client_id, Some(sender.clone()));
+                }
+            }
+        }
     }
 
-    /// Unregister a client connection
+    /// Unregister a client connection, leaving every channel it was in
     pub fn unregister_client(&self, client_id: &Uuid) {
         self.client_connections.remove(client_id);
-        self.user_subscriptions.remove(client_id);
+        if let Some(batcher) = &self.batcher {
+            batcher.remove_target(client_id);
+        }
+        if let Some((_, channels)) = self.user_subscriptions.remove(client_id) {
+            for channel in channels {
+                self.remove_member(client_id, &channel);
+            }
+        }
     }
 
     /// Register a server connection
@@ -113,11 +166,15 @@ impl BroadcastSystem {
     }
 
     /// Subscribe user to a channel
+    ///
+    /// A local user's outbound queue is indexed along with the membership;
+    /// remote users are tracked without one.
     pub fn subscribe_to_channel(&self, user_id: Uuid, channel: String) {
-        self.user_subscriptions.entry(user_id).or_insert_with(HashSet::new)
+        let sender = self.sender_for(&user_id);
+        self.user_subscriptions.entry(user_id).or_default()
             .insert(channel.clone());
-        self.channel_subscriptions.entry(channel).or_insert_with(HashSet::new)
-            .insert(user_id);
+        self.channel_subscriptions.entry(channel).or_default()
+            .insert(user_id, sender);
     }
 
     /// Unsubscribe user from a channel
@@ -125,9 +182,59 @@ impl BroadcastSystem {
         if let Some(mut channels) = self.user_subscriptions.get_mut(user_id) {
             channels.remove(channel);
         }
-        if let Some(mut users) = self.channel_subscriptions.get_mut(channel) {
-            users.remove(user_id);
+        self.remove_member(user_id, channel);
+    }
+
+    /// Move a channel's subscriptions to a new name
+    pub fn rename_channel(&self, old_name: &str, new_name: &str) {
+        let Some((_, members)) = self.channel_subscriptions.remove(old_name) else {
+            return;
+        };
+        for user_id in members.keys() {
+            if let Some(mut channels) = self.user_subscriptions.get_mut(user_id) {
+                channels.remove(old_name);
+                channels.insert(new_name.to_string());
+            }
+        }
+        self.channel_subscriptions.insert(new_name.to_string(), members);
+    }
+
+    /// Outbound queue of a local client
+    fn sender_for(&self, client_id: &Uuid) -> Option<mpsc::UnboundedSender<Message>> {
+        self.client_senders.sender(client_id)
+            .or_else(|| self.client_connections.get(client_id).map(|client| client.sender.clone()))
+    }
+
+    /// Drop a member from a channel's index, and the channel once it is empty
+    fn remove_member(&self, user_id: &Uuid, channel: &str) {
+        self.channel_subscriptions.remove_if_mut(channel, |_, members| {
+            members.remove(user_id);
+            members.is_empty()
+        });
+    }
+
+    /// Number of members indexed for a channel
+    pub fn channel_member_count(&self, channel: &str) -> usize {
+        self.channel_subscriptions.get(channel).map_or(0, |members| members.len())
+    }
+
+    /// Queue a message for every local member of a channel
+    ///
+    /// Returns how many members it was queued for and how many queues had
+    /// already closed.
+    pub fn send_to_channel_members(&self, channel: &str, message: &Message) -> (u64, u64) {
+        let _span = tracing::debug_span!("irc.broadcast", channel).entered();
+        let Some(members) = self.channel_subscriptions.get(channel) else {
+            return (0, 0);
+        };
+        let (mut sent, mut failed) = (0, 0);
+        for sender in members.values().flatten() {
+            match sender.send(message.clone()) {
+                Ok(()) => sent += 1,
+                Err(_) => failed += 1,
+            }
         }
+        (sent, failed)
     }
 
     /// Queue a message for broadcasting
@@ -142,26 +249,82 @@ impl BroadcastSystem {
     }
 
     /// Process all queued messages
+    ///
+    /// With batching enabled, queued broadcasts join their recipients'
+    /// batches and the batches that are due are delivered.
     pub async fn process_queues(&mut self) -> Result<()> {
         // Process queues in priority order (Critical -> High -> Normal -> Low)
         for i in (0..self.message_queues.len()).rev() {
             while let Some(broadcast) = self.message_queues[i].pop_front() {
-                self.broadcast_message(broadcast).await?;
+                match &self.batcher {
+                    Some(batcher) if broadcast.priority != BroadcastPriority::Critical => {
+                        self.batch_message(batcher, broadcast).await?;
+                    }
+                    _ => self.broadcast_message(broadcast).await?,
+                }
             }
         }
+        self.flush_batches(false).await;
+        Ok(())
+    }
+
+    /// Add a broadcast to the batch of each local recipient
+    async fn batch_message(&self, batcher: &BatchOptimizer, broadcast: BroadcastMessage) -> Result<()> {
+        let mut batched = 0;
+        for target_id in self.resolve_targets(&broadcast.target).await? {
+            if self.client_senders.contains(&target_id) || self.client_connections.contains_key(&target_id) {
+                batcher.add_message(target_id, broadcast.message.clone()).await?;
+                batched += 1;
+            }
+        }
+
+        let mut stats = self.stats.write().await;
+        stats.messages_sent += 1;
+        stats.users_reached += batched;
+        if matches!(broadcast.target, BroadcastTarget::Channel(_)) {
+            stats.channels_broadcasted += 1;
+        }
         Ok(())
     }
 
+    /// Deliver the batches that are due, or every batch when `force` is set
+    ///
+    /// Returns how many batches were delivered.
+    pub async fn flush_batches(&self, force: bool) -> usize {
+        let Some(batcher) = &self.batcher else {
+            return 0;
+        };
+        let batches = if force {
+            batcher.take_all_batches().await
+        } else {
+            batcher.take_ready_batches().await
+        };
+        let mut delivered = 0;
+        for (target_id, messages) in batches {
+            let Some(sender) = self.sender_for(&target_id) else {
+                batcher.remove_target(&target_id);
+                continue;
+            };
+            if messages.into_iter().all(|message| sender.send(message).is_ok()) {
+                delivered += 1;
+            }
+        }
+        delivered
+    }
+
     /// Broadcast a message immediately
     pub async fn broadcast_message(&self, broadcast: BroadcastMessage) -> Result<()> {
-        let targets = self.resolve_targets(&broadcast.target).await?;
         let mut success_count = 0;
         let mut error_count = 0;
 
-        for target_id in targets {
-            match self.send_to_target(target_id, &broadcast.message).await {
-                Ok(_) => success_count += 1,
-                Err(_) => error_count += 1,
+        if let BroadcastTarget::Channel(channel) = &broadcast.target {
+            (success_count, error_count) = self.send_to_channel_members(channel, &broadcast.message);
+        } else {
+            for target_id in self.resolve_targets(&broadcast.target).await? {
+                match self.send_to_target(target_id, &broadcast.message).await {
+                    Ok(_) => success_count += 1,
+                    Err(_) => error_count += 1,
+                }
             }
         }
 
@@ -186,7 +349,7 @@ impl BroadcastSystem {
             }
             BroadcastTarget::Channel(channel) => {
                 Ok(self.channel_subscriptions.get(channel)
-                    .map(|entry| entry.iter().cloned().collect())
+                    .map(|members| members.keys().copied().collect())
                     .unwrap_or_default())
             }
             BroadcastTarget::AllExcept(sender_id) => {
@@ -209,6 +372,7 @@ impl BroadcastSystem {
                 }
                 Ok(user_ids)
             }
+            BroadcastTarget::Clients(client_ids) => Ok(client_ids.clone()),
             BroadcastTarget::AllServers => {
                 Ok(self.server_connections.iter().map(|entry| *entry.value()).collect())
             }
@@ -250,10 +414,13 @@ impl BroadcastSystem {
     async fn send_to_target(&self, target_id: Uuid, message: &Message) -> Result<()> {
         if let Some(client) = self.client_connections.get(&target_id) {
             client.send(message.clone())?;
+        } else if let Some(sender) = self.client_senders.sender(&target_id) {
+            sender.send(message.clone())
+                .map_err(|_| Error::Connection("Failed to send message to client".to_string()))?;
         } else if self.server_connections.iter().any(|entry| *entry.value() == target_id) {
             // Send to server connection
             // This would need server-specific sending logic
-            tracing::debug!("Sending to server: {:?}", message);
+            tracing::debug!("Sending to server: {}", message.redacted());
         }
         Ok(())
     }
@@ -246,6 +246,8 @@ pub struct ConnectionTiming {
     pub last_pong_received: Option<Instant>,
     /// Number of PINGs sent without PONG response
     pub unanswered_pings: u32,
+    /// Round-trip time of the last answered PING
+    pub lag: Option<Duration>,
     /// Ping frequency in seconds (from connection class)
     pub ping_frequency: u64,
     /// Connection timeout in seconds (from connection class)
@@ -262,6 +264,7 @@ impl ConnectionTiming {
             last_ping_sent: None,
             last_pong_received: None,
             unanswered_pings: 0,
+            lag: None,
             ping_frequency,
             connection_timeout,
         }
@@ -279,7 +282,12 @@ impl ConnectionTiming {
     }
 
     /// Record that we received a PONG
+    ///
+    /// Answers to an outstanding PING update the lag.
     pub fn record_pong_received(&mut self) {
+        if let (Some(sent), true) = (self.last_ping_sent, self.unanswered_pings > 0) {
+            self.lag = Some(sent.elapsed());
+        }
         self.last_pong_received = Some(Instant::now());
         self.unanswered_pings = 0;
         self.update_activity();
@@ -384,11 +392,15 @@ mod tests {
         assert!(!timing.is_timed_out());
         assert_eq!(timing.unanswered_pings, 0);
         
+        timing.record_pong_received();
+        assert!(timing.lag.is_none());
+        
         timing.record_ping_sent();
         assert_eq!(timing.unanswered_pings, 1);
         
         timing.record_pong_received();
         assert_eq!(timing.unanswered_pings, 0);
+        assert!(timing.lag.is_some());
     }
 }
 
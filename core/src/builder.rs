@@ -0,0 +1,187 @@
+//! Builder for embedding the IRC server in another application
+
+use crate::config::{PortConfig, PortConnectionType};
+use crate::{AuthProvider, BanStore, Config, Module, ModuleRegistry, Result, Server};
+use std::path::Path;
+use std::sync::Arc;
+
+/// Fluent construction of an initialized `Server`
+///
+/// ```no_run
+/// # async fn run() -> rustircd_core::Result<()> {
+/// use rustircd_core::{ServerBuilder, config::PortConnectionType};
+///
+/// let mut server = ServerBuilder::new()
+///     .server_name("irc.example.net")
+///     .port(6667, PortConnectionType::Client, false)
+///     .build()
+///     .await?;
+/// server.start().await?;
+/// server.join().await;
+/// # Ok(())
+/// # }
+/// ```
+pub struct ServerBuilder {
+    config: Config,
+    config_path: Option<String>,
+    /// Ports added with `port`; replace the configured ports when set
+    ports: Option<Vec<PortConfig>>,
+    registry: ModuleRegistry,
+    ban_store: Option<Arc<dyn BanStore>>,
+    auth_providers: Vec<Arc<dyn AuthProvider>>,
+}
+
+impl ServerBuilder {
+    /// Start from the default configuration
+    pub fn new() -> Self {
+        Self::with_config(Config::default())
+    }
+
+    /// Start from an existing configuration
+    pub fn with_config(config: Config) -> Self {
+        Self {
+            config,
+            config_path: None,
+            ports: None,
+            registry: ModuleRegistry::new(),
+            ban_store: None,
+            auth_providers: Vec::new(),
+        }
+    }
+
+    /// Start from a configuration file, which REHASH reloads
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let mut builder = Self::with_config(Config::from_file(path.as_ref())?);
+        builder.config_path = Some(path.as_ref().to_string_lossy().to_string());
+        Ok(builder)
+    }
+
+    /// Set the server name
+    pub fn server_name(mut self, name: &str) -> Self {
+        self.config.server.name = name.to_string();
+        self
+    }
+
+    /// Set the server description
+    pub fn description(mut self, description: &str) -> Self {
+        self.config.server.description = description.to_string();
+        self
+    }
+
+    /// Set the address listeners bind to unless a port overrides it
+    pub fn bind_address(mut self, address: &str) -> Self {
+        self.config.connection.bind_address = address.to_string();
+        self
+    }
+
+    /// Listen on `port`
+    ///
+    /// The first call replaces the ports from the configuration.
+    pub fn port(mut self, port: u16, connection_type: PortConnectionType, tls: bool) -> Self {
+        self.ports.get_or_insert_with(Vec::new).push(PortConfig {
+            port,
+            connection_type,
+            tls,
+            description: None,
+            bind_address: None,
+            bind_address_v6: None,
+            request_client_cert: false,
+        });
+        self
+    }
+
+    /// Register a module constructor and enable the module
+    pub fn module<F>(mut self, name: &str, factory: F) -> Self
+    where
+        F: Fn(&Config) -> Result<Box<dyn Module>> + Send + Sync + 'static,
+    {
+        self.registry.register(name, factory);
+        self.enable_module(name)
+    }
+
+    /// Register the constructors in `registry` without enabling them
+    pub fn module_registry(mut self, registry: ModuleRegistry) -> Self {
+        self.registry = registry;
+        self
+    }
+
+    /// Enable a module from the registry
+    pub fn enable_module(mut self, name: &str) -> Self {
+        if !self.config.modules.enabled_modules.iter().any(|enabled| enabled == name) {
+            self.config.modules.enabled_modules.push(name.to_string());
+        }
+        self
+    }
+
+    /// Keep K/G/D/X-lines in `store` instead of the configured ban file
+    pub fn ban_store(mut self, store: Arc<dyn BanStore>) -> Self {
+        self.ban_store = Some(store);
+        self
+    }
+
+    /// Add an authentication provider; the first one added is the primary
+    pub fn auth_provider(mut self, provider: Arc<dyn AuthProvider>) -> Self {
+        self.auth_providers.push(provider);
+        self
+    }
+
+    /// Validate the configuration and create an initialized server
+    ///
+    /// Call `start` on the result to begin accepting connections.
+    pub async fn build(mut self) -> Result<Server> {
+        if let Some(ports) = self.ports.take() {
+            self.config.connection.ports = ports;
+        }
+        self.config.validate()?;
+
+        let mut server = match self.config_path {
+            Some(path) => Server::new_with_config_path(self.config, path).await,
+            None => Server::new(self.config).await,
+        };
+        server.set_module_registry(self.registry);
+        if let Some(store) = self.ban_store {
+            server.ban_manager().set_store(store)?;
+        }
+        for provider in self.auth_providers {
+            server.auth_manager().register_provider(provider).await?;
+        }
+        server.init().await?;
+        Ok(server)
+    }
+}
+
+impl Default for ServerBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{AuditEvent, AuditEventType, ServerEvent};
+
+    #[tokio::test]
+    async fn test_builder_configures_and_stops_server() {
+        let server = ServerBuilder::new()
+            .server_name("embedded.test")
+            .port(16667, PortConnectionType::Client, false)
+            .build()
+            .await
+            .unwrap();
+        assert_eq!(server.config().server.name, "embedded.test");
+        assert_eq!(server.config().connection.ports.len(), 1);
+
+        let mut events = server.subscribe();
+        server.audit(AuditEvent::new(AuditEventType::OperAction).with_user("admin")).await;
+        match events.recv().await.unwrap() {
+            ServerEvent::OperAction(event) => assert_eq!(event.user.as_deref(), Some("admin")),
+            other => panic!("unexpected event {:?}", other),
+        }
+
+        server.stop().await.unwrap();
+        server.join().await;
+        assert!(matches!(events.recv().await.unwrap(), ServerEvent::ShuttingDown { .. }));
+        assert!(matches!(events.recv().await.unwrap(), ServerEvent::Stopped));
+    }
+}
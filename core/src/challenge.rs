@@ -0,0 +1,135 @@
+//! Challenge-response operator authentication
+//!
+//! CHALLENGE lets an operator prove they hold the private half of the
+//! keypair named by their oper block, so no password crosses the wire.
+//! RSA keys work as in ratbox: the server encrypts a random secret with the
+//! public key (OAEP with SHA-1) and the client answers with the base64
+//! SHA-1 digest of the decrypted secret. Ed25519 keys can't encrypt, so the
+//! server sends a random nonce and the client answers with a base64
+//! signature over the nonce's bytes.
+
+use crate::{Error, Result};
+use base64::{engine::general_purpose, Engine as _};
+use ed25519_dalek::{Signature, Verifier, VerifyingKey};
+use rand::{rngs::OsRng, RngCore};
+use rsa::pkcs1::DecodeRsaPublicKey;
+use rsa::pkcs8::DecodePublicKey;
+use rsa::{Oaep, RsaPublicKey};
+use sha1::{Digest, Sha1};
+
+/// Random bytes in each secret or nonce
+const SECRET_LEN: usize = 32;
+
+/// Characters of challenge text per RPL_RSACHALLENGE2 line
+pub const CHALLENGE_LINE_LEN: usize = 60;
+
+/// Public key an oper block uses for CHALLENGE
+#[derive(Debug, Clone)]
+pub enum ChallengeKey {
+    Rsa(RsaPublicKey),
+    Ed25519(VerifyingKey),
+}
+
+impl ChallengeKey {
+    /// Parse a PEM public key: SPKI `PUBLIC KEY` for either type, or PKCS#1 `RSA PUBLIC KEY`
+    pub fn from_pem(pem: &str) -> Result<Self> {
+        if let Ok(key) = VerifyingKey::from_public_key_pem(pem) {
+            return Ok(Self::Ed25519(key));
+        }
+        RsaPublicKey::from_public_key_pem(pem)
+            .or_else(|_| RsaPublicKey::from_pkcs1_pem(pem))
+            .map(Self::Rsa)
+            .map_err(|_| Error::Config("Challenge key is not an RSA or Ed25519 public key in PEM format".to_string()))
+    }
+
+    /// Read and parse a PEM public key file
+    pub fn load(path: &str) -> Result<Self> {
+        let pem = std::fs::read_to_string(path)
+            .map_err(|e| Error::Config(format!("Failed to read challenge key {}: {}", path, e)))?;
+        Self::from_pem(&pem)
+    }
+
+    /// Start a challenge, returning the base64 text to send and what answers it
+    pub fn issue(&self) -> Result<(String, PendingChallenge)> {
+        let mut secret = [0u8; SECRET_LEN];
+        OsRng.fill_bytes(&mut secret);
+        match self {
+            Self::Rsa(key) => {
+                let encrypted = key.encrypt(&mut OsRng, Oaep::new::<Sha1>(), &secret)
+                    .map_err(|e| Error::Auth(format!("Failed to encrypt challenge: {}", e)))?;
+                let digest = Sha1::digest(secret).into();
+                Ok((general_purpose::STANDARD.encode(encrypted), PendingChallenge::Digest(digest)))
+            }
+            Self::Ed25519(key) => {
+                Ok((general_purpose::STANDARD.encode(secret), PendingChallenge::Signature(Box::new(*key), secret)))
+            }
+        }
+    }
+}
+
+/// The answer a CHALLENGE is waiting for
+#[derive(Debug, Clone)]
+pub enum PendingChallenge {
+    /// SHA-1 digest of the plaintext secret, which the client decrypts and hashes
+    Digest([u8; 20]),
+    /// Signature over the nonce by the given key
+    Signature(Box<VerifyingKey>, [u8; SECRET_LEN]),
+}
+
+impl PendingChallenge {
+    /// Whether a client's base64 response answers the challenge
+    pub fn verify(&self, response: &str) -> bool {
+        let Ok(response) = general_purpose::STANDARD.decode(response) else {
+            return false;
+        };
+        match self {
+            Self::Digest(expected) => {
+                // Compare in constant time
+                response.len() == expected.len()
+                    && response.iter().zip(expected).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+            }
+            Self::Signature(key, nonce) => Signature::from_slice(&response)
+                .is_ok_and(|signature| key.verify(nonce, &signature).is_ok()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ed25519_dalek::pkcs8::{spki::der::pem::LineEnding, EncodePublicKey};
+    use ed25519_dalek::{Signer, SigningKey};
+    use rsa::RsaPrivateKey;
+
+    #[test]
+    fn test_rsa_challenge() {
+        let private = RsaPrivateKey::new(&mut OsRng, 1024).unwrap();
+        let pem = private.to_public_key().to_public_key_pem(LineEnding::LF).unwrap();
+        let key = ChallengeKey::from_pem(&pem).unwrap();
+        assert!(matches!(key, ChallengeKey::Rsa(_)));
+
+        let (text, pending) = key.issue().unwrap();
+        let encrypted = general_purpose::STANDARD.decode(text).unwrap();
+        let secret = private.decrypt(Oaep::new::<Sha1>(), &encrypted).unwrap();
+        let response = general_purpose::STANDARD.encode(Sha1::digest(&secret));
+        assert!(pending.verify(&response));
+        assert!(!pending.verify(&general_purpose::STANDARD.encode([0u8; 20])));
+        assert!(!pending.verify("not base64!"));
+    }
+
+    #[test]
+    fn test_ed25519_challenge() {
+        let signing = SigningKey::generate(&mut OsRng);
+        let pem = signing.verifying_key().to_public_key_pem(LineEnding::LF).unwrap();
+        let key = ChallengeKey::from_pem(&pem).unwrap();
+
+        let (text, pending) = key.issue().unwrap();
+        let nonce = general_purpose::STANDARD.decode(text).unwrap();
+        let signature = signing.sign(&nonce);
+        assert!(pending.verify(&general_purpose::STANDARD.encode(signature.to_bytes())));
+
+        let other = SigningKey::generate(&mut OsRng).sign(&nonce);
+        assert!(!pending.verify(&general_purpose::STANDARD.encode(other.to_bytes())));
+        assert!(ChallengeKey::from_pem("-----BEGIN PUBLIC KEY-----\nAAAA\n-----END PUBLIC KEY-----\n").is_err());
+    }
+}
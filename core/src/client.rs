@@ -1,6 +1,6 @@
 //! Client connection management
 
-use crate::{Message, User, Error, NumericReply, Result, SendQueue, RecvQueue, ConnectionTiming};
+use crate::{Message, User, Error, NumericReply, Result, SendQueue, RecvQueue, ConnectionTiming, RegistrationState};
 use tokio::sync::mpsc;
 use uuid::Uuid;
 
@@ -14,6 +14,9 @@ pub enum ConnectionType {
 }
 
 /// Client connection state
+///
+/// Progress through NICK, USER and CAP is tracked in `RegistrationState`;
+/// local clients go straight from `Connected` to `Registered`.
 #[derive(Debug, Clone)]
 pub enum ClientState {
     /// Just connected, not registered
@@ -47,6 +50,8 @@ pub struct Client {
     pub sender: mpsc::UnboundedSender<Message>,
     /// Whether connection is encrypted
     pub encrypted: bool,
+    /// Local port the client connected to
+    pub port: u16,
     /// Capabilities being negotiated
     pub capabilities: std::collections::HashSet<String>,
     /// Whether client supports IRCv3
@@ -63,6 +68,10 @@ pub struct Client {
     pub timing: ConnectionTiming,
     /// Server password (for server connections only)
     pub server_password: Option<String>,
+    /// Registration steps completed so far
+    pub registration: RegistrationState,
+    /// SHA-256 fingerprint of the TLS client certificate, if one was presented
+    pub certfp: Option<String>,
 }
 
 impl Client {
@@ -120,6 +129,7 @@ impl Client {
             local_addr,
             sender,
             encrypted: false,
+            port: 0,
             capabilities: std::collections::HashSet::new(),
             supports_ircv3: false,
             connection_type,
@@ -128,6 +138,8 @@ impl Client {
             recvq: RecvQueue::new(max_recvq),
             timing: ConnectionTiming::new(ping_frequency, connection_timeout),
             server_password: None,
+            registration: RegistrationState::new(),
+            certfp: None,
         }
     }
     
@@ -138,6 +150,37 @@ impl Client {
         Ok(())
     }
     
+    /// Send replies in batches of `batch_size`, pausing `delay` between batches
+    ///
+    /// Output that fits in one batch goes out at once. Anything longer is
+    /// streamed from a task of its own, so the caller isn't held up and the
+    /// client's send queue isn't flooded.
+    pub fn send_paced(&self, replies: Vec<Message>, batch_size: usize, delay: std::time::Duration) {
+        let batch_size = batch_size.max(1);
+        if replies.len() <= batch_size {
+            for reply in replies {
+                let _ = self.send(reply);
+            }
+            return;
+        }
+        let sender = self.sender.clone();
+        tokio::spawn(async move {
+            for (index, reply) in replies.into_iter().enumerate() {
+                if index > 0 && index % batch_size == 0 {
+                    if delay.is_zero() {
+                        tokio::task::yield_now().await;
+                    } else {
+                        tokio::time::sleep(delay).await;
+                    }
+                }
+                if sender.send(reply).is_err() {
+                    // The client went away
+                    return;
+                }
+            }
+        });
+    }
+    
     /// Send a raw string message to the client
     pub fn send_raw(&self, message: &str) -> Result<()> {
         let msg = Message::parse(message)?;
@@ -155,6 +198,11 @@ impl Client {
         self.id
     }
     
+    /// IP address the client connected from
+    pub fn ip(&self) -> Option<std::net::IpAddr> {
+        self.remote_addr.parse::<std::net::SocketAddr>().ok().map(|addr| addr.ip())
+    }
+    
     /// Check if client is registered
     pub fn is_registered(&self) -> bool {
         matches!(self.state, ClientState::Registered)
@@ -162,17 +210,20 @@ impl Client {
     
     /// Check if client has provided password
     pub fn has_password(&self) -> bool {
-        matches!(self.state, ClientState::PasswordProvided | ClientState::NickSet | ClientState::UserSet | ClientState::Registered)
+        self.registration.password_accepted
+            || matches!(self.state, ClientState::PasswordProvided | ClientState::NickSet | ClientState::UserSet | ClientState::Registered)
     }
     
     /// Check if client has set nickname
     pub fn has_nick(&self) -> bool {
-        matches!(self.state, ClientState::NickSet | ClientState::UserSet | ClientState::Registered)
+        self.registration.nick.is_some()
+            || matches!(self.state, ClientState::NickSet | ClientState::UserSet | ClientState::Registered)
     }
     
     /// Check if client has provided user info
     pub fn has_user(&self) -> bool {
-        matches!(self.state, ClientState::UserSet | ClientState::Registered)
+        self.registration.username.is_some()
+            || matches!(self.state, ClientState::UserSet | ClientState::Registered)
     }
     
     /// Get client nickname
@@ -264,6 +315,18 @@ impl Client {
         self.timing.update_parameters(ping_frequency, connection_timeout);
     }
     
+    /// Move the client to a connection class, unset limits taking the defaults
+    pub fn apply_class(&mut self, class: &crate::config::ConnectionClass) {
+        let defaults = crate::config::ConnectionClass::default();
+        self.update_class_parameters(
+            class.name.clone(),
+            class.max_sendq.or(defaults.max_sendq).unwrap_or(1048576),
+            class.max_recvq.or(defaults.max_recvq).unwrap_or(8192),
+            class.ping_frequency.or(defaults.ping_frequency).unwrap_or(120),
+            class.connection_timeout.or(defaults.connection_timeout).unwrap_or(300),
+        );
+    }
+    
     /// Check if connection has timed out
     pub fn is_timed_out(&self) -> bool {
         self.timing.is_timed_out()
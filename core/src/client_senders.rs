@@ -0,0 +1,154 @@
+//! Outbound queues of local clients, reachable without the connection lock
+//!
+//! The connection handler owns every `Client` behind the server's single
+//! RwLock. Delivering a message only needs the client's queue, so queues
+//! are also kept here in a sharded map: broadcast paths send through it
+//! without holding the connection handler, and the message loop notes
+//! activity here instead of taking the write lock for every line.
+
+use crate::{Message, MessageType, Prefix};
+use dashmap::DashMap;
+use std::time::Instant;
+use tokio::sync::mpsc;
+use uuid::Uuid;
+
+/// Sharded map from client ID to its outbound queue
+#[derive(Debug, Default)]
+pub struct ClientSenders {
+    senders: DashMap<Uuid, mpsc::UnboundedSender<Message>>,
+    /// When each client last sent a line
+    activity: DashMap<Uuid, Instant>,
+}
+
+impl ClientSenders {
+    /// Create an empty map
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Track a newly accepted client
+    pub fn insert(&self, client_id: Uuid, sender: mpsc::UnboundedSender<Message>) {
+        self.senders.insert(client_id, sender);
+        self.activity.insert(client_id, Instant::now());
+    }
+
+    /// Forget a disconnected client
+    pub fn remove(&self, client_id: &Uuid) {
+        self.senders.remove(client_id);
+        self.activity.remove(client_id);
+    }
+
+    /// Whether a client is connected here
+    pub fn contains(&self, client_id: &Uuid) -> bool {
+        self.senders.contains_key(client_id)
+    }
+
+    /// Number of connected clients
+    pub fn len(&self) -> usize {
+        self.senders.len()
+    }
+
+    /// Whether no clients are connected
+    pub fn is_empty(&self) -> bool {
+        self.senders.is_empty()
+    }
+
+    /// A local client's outbound queue
+    pub fn sender(&self, client_id: &Uuid) -> Option<mpsc::UnboundedSender<Message>> {
+        self.senders.get(client_id).map(|sender| sender.clone())
+    }
+
+    /// Queue a message for a local client
+    ///
+    /// Returns false when the client isn't connected here or its
+    /// connection is closing.
+    pub fn send(&self, client_id: &Uuid, message: Message) -> bool {
+        match self.senders.get(client_id) {
+            Some(sender) => sender.send(message).is_ok(),
+            None => false,
+        }
+    }
+
+    /// Queue one copy of a message for each local client in `client_ids`
+    pub fn send_each<I: IntoIterator<Item = Uuid>>(&self, client_ids: I, message: &Message) {
+        for client_id in client_ids {
+            self.send(&client_id, message.clone());
+        }
+    }
+
+    /// Queue messages for a local client wrapped in a `BATCH` of `batch_type`,
+    /// such as a netsplit's QUITs
+    pub fn send_batch(&self, client_id: &Uuid, server_name: &str, batch_type: &str, params: &[String], messages: &[Message]) -> bool {
+        let reference = Uuid::new_v4().simple().to_string()[..12].to_string();
+        let mut start = vec![format!("+{}", reference), batch_type.to_string()];
+        start.extend(params.iter().cloned());
+        let batch = |params| Message::with_prefix(Prefix::Server(server_name.to_string()), MessageType::Custom("BATCH".to_string()), params);
+        if !self.send(client_id, batch(start)) {
+            return false;
+        }
+        for message in messages {
+            let mut message = message.clone();
+            message.set_tag("batch", &reference);
+            self.send(client_id, message);
+        }
+        self.send(client_id, batch(vec![format!("-{}", reference)]))
+    }
+
+    /// Note that a line just arrived from a client
+    pub fn touch(&self, client_id: &Uuid) {
+        if let Some(mut last) = self.activity.get_mut(client_id) {
+            *last = Instant::now();
+        }
+    }
+
+    /// When a client last sent a line
+    pub fn last_activity(&self, client_id: &Uuid) -> Option<Instant> {
+        self.activity.get(client_id).map(|last| *last)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_send_and_remove() {
+        let senders = ClientSenders::new();
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let alice = Uuid::new_v4();
+        senders.insert(alice, tx);
+        let before = senders.last_activity(&alice).unwrap();
+        senders.touch(&alice);
+        assert!(senders.last_activity(&alice).unwrap() >= before);
+
+        let ping = Message::new(MessageType::Ping, vec!["irc.test".to_string()]);
+        assert!(senders.send(&alice, ping.clone()));
+        assert!(!senders.send(&Uuid::new_v4(), ping.clone()));
+        senders.send_each([alice, Uuid::new_v4(), alice], &ping);
+        assert_eq!(std::iter::from_fn(|| rx.try_recv().ok()).count(), 3);
+
+        senders.remove(&alice);
+        assert!(senders.is_empty());
+        assert!(!senders.send(&alice, ping));
+        assert!(senders.last_activity(&alice).is_none());
+    }
+
+    #[test]
+    fn test_send_batch() {
+        let senders = ClientSenders::new();
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let alice = Uuid::new_v4();
+        senders.insert(alice, tx);
+
+        let quit = Message::new(MessageType::Quit, vec!["hub.test leaf.test".to_string()]);
+        let params = ["hub.test".to_string(), "leaf.test".to_string()];
+        assert!(senders.send_batch(&alice, "hub.test", "netsplit", &params, &[quit.clone(), quit]));
+        let lines: Vec<Message> = std::iter::from_fn(|| rx.try_recv().ok()).collect();
+        assert_eq!(lines.len(), 4);
+        let reference = lines[0].params[0].strip_prefix('+').unwrap();
+        assert_eq!(lines[0].params[1..], ["netsplit", "hub.test", "leaf.test"]);
+        assert!(lines[1..3].iter().all(|line| line.tag("batch") == Some(reference)));
+        assert_eq!(lines[3].params, [format!("-{}", reference)]);
+        assert!(!senders.send_batch(&Uuid::new_v4(), "hub.test", "netsplit", &params, &[]));
+    }
+}
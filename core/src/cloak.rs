@@ -0,0 +1,160 @@
+//! Hostname cloaking
+//!
+//! Generates keyed, deterministic cloaks for user hosts so other users never see
+//! a real address. Cloaks are stable for a given secret key, which lets every
+//! server on the network derive the same cloak and lets operators ban cloaked
+//! ranges with wildcards.
+
+use crate::ban_manager::ip_network;
+use crate::config::IpCloakConfig;
+use crate::utils::string::wildcard_match;
+use sha2::{Digest, Sha256};
+use std::net::IpAddr;
+
+/// Host cloaker built from the `[modules.ip_cloak]` configuration
+#[derive(Debug, Clone)]
+pub struct HostCloaker {
+    config: IpCloakConfig,
+    key: String,
+}
+
+impl HostCloaker {
+    /// Create a cloaker, falling back to the network name when no secret key is set
+    pub fn new(config: &IpCloakConfig, network_name: &str) -> Self {
+        let key = if config.secret_key.is_empty() {
+            network_name.to_string()
+        } else {
+            config.secret_key.clone()
+        };
+        Self {
+            config: config.clone(),
+            key,
+        }
+    }
+
+    /// Whether hosts should be cloaked automatically on connect
+    pub fn enabled(&self) -> bool {
+        self.config.enabled
+    }
+
+    /// Cloak a real host (IP address or resolved hostname)
+    ///
+    /// IPv4 addresses become `<addr hash>.<range hash><suffix>` and IPv6
+    /// addresses `<addr hash>:<64 hash>:<range hash><suffix>`, so a client's
+    /// /64 can be banned with `*:<64 hash>:*`; hostnames keep their last two
+    /// labels and hash the rest. Hosts matching a preserve pattern are
+    /// returned unchanged.
+    pub fn cloak(&self, real_host: &str) -> String {
+        if self.config.preserve_patterns.iter().any(|p| wildcard_match(p, real_host)) {
+            return real_host.to_string();
+        }
+
+        match real_host.parse::<IpAddr>() {
+            Ok(ip) => self.cloak_ip(ip),
+            Err(_) => self.cloak_hostname(real_host),
+        }
+    }
+
+    fn cloak_ip(&self, ip: IpAddr) -> String {
+        // A mapped IPv4 address gets the same cloak as the address itself
+        let ip = ip.to_canonical();
+        match ip {
+            IpAddr::V4(_) => format!(
+                "{}.{}{}",
+                self.hash(&ip.to_string()),
+                self.hash(&ip_network(ip, self.config.ipv4_cidr).to_string()),
+                self.config.suffix
+            ),
+            IpAddr::V6(_) => format!(
+                "{}:{}:{}{}",
+                self.hash(&ip.to_string()),
+                self.hash(&format!("{}/64", ip_network(ip, 64))),
+                self.hash(&ip_network(ip, self.config.ipv6_cidr).to_string()),
+                self.config.suffix
+            ),
+        }
+    }
+
+    fn cloak_hostname(&self, hostname: &str) -> String {
+        let labels: Vec<&str> = hostname.split('.').collect();
+        if labels.len() <= 2 {
+            return format!("{}{}", self.hash(hostname), self.config.suffix);
+        }
+
+        let domain = labels[labels.len() - 2..].join(".");
+        format!("{}.{}", self.hash(hostname), domain)
+    }
+
+    /// Keyed hash of the input, truncated to `mac_bits` and hex encoded
+    fn hash(&self, input: &str) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(self.key.as_bytes());
+        hasher.update(b":");
+        hasher.update(input.as_bytes());
+        let digest = hasher.finalize();
+
+        let bytes = (self.config.mac_bits as usize / 8).clamp(2, digest.len());
+        digest[..bytes].iter().map(|b| format!("{:02x}", b)).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cloaker() -> HostCloaker {
+        let config = IpCloakConfig {
+            secret_key: "test-secret".to_string(),
+            preserve_patterns: vec!["*.localhost".to_string()],
+            ..IpCloakConfig::default()
+        };
+        HostCloaker::new(&config, "TestNet")
+    }
+
+    #[test]
+    fn test_cloak_is_stable_and_hides_host() {
+        let cloaker = cloaker();
+        let cloaked = cloaker.cloak("192.0.2.10");
+        assert_eq!(cloaked, cloaker.cloak("192.0.2.10"));
+        assert!(!cloaked.contains("192.0.2"));
+        assert!(cloaked.ends_with(".cloaked"));
+
+        let host = cloaker.cloak("dsl-1-2-3.isp.example.net");
+        assert!(host.ends_with(".example.net"));
+        assert!(!host.contains("dsl"));
+    }
+
+    #[test]
+    fn test_cloak_shares_range_segment() {
+        let cloaker = cloaker();
+        let a = cloaker.cloak("192.0.2.10");
+        let b = cloaker.cloak("192.0.99.20");
+        let c = cloaker.cloak("198.51.100.1");
+
+        let range = |s: &str| s.split('.').nth(1).map(str::to_string);
+        assert_eq!(range(&a), range(&b));
+        assert_ne!(range(&a), range(&c));
+    }
+
+    #[test]
+    fn test_ipv6_cloak_segments() {
+        let cloaker = cloaker();
+        let a = cloaker.cloak("2001:db8:1:2::10");
+        let b = cloaker.cloak("2001:db8:1:2:ffff::1");
+        let c = cloaker.cloak("2001:db8:9::1");
+        assert!(!a.contains("2001") && a.ends_with(".cloaked"));
+        assert_eq!(a.split(':').count(), 3);
+
+        // Same /64 shares the middle segment, same /32 the last
+        let segment = |s: &str, n: usize| s.trim_end_matches(".cloaked").split(':').nth(n).map(str::to_string);
+        assert_eq!(segment(&a, 1), segment(&b, 1));
+        assert_ne!(segment(&a, 1), segment(&c, 1));
+        assert_eq!(segment(&a, 2), segment(&c, 2));
+        assert_eq!(cloaker.cloak("::ffff:192.0.2.10"), cloaker.cloak("192.0.2.10"));
+    }
+
+    #[test]
+    fn test_preserve_patterns() {
+        assert_eq!(cloaker().cloak("box.localhost"), "box.localhost");
+    }
+}
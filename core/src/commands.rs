@@ -0,0 +1,286 @@
+//! Command validation and commands registered by modules and embedders
+//!
+//! Registered commands are dispatched from a table after the module message
+//! handlers have run. Arity, registration and operator checks happen once,
+//! before the handler is called; core's own commands get the same checks
+//! from `core_command_spec` before any module sees them.
+
+use crate::module::ModuleContext;
+use crate::{Client, Error, Message, MessageType, NumericReply, Result};
+use async_trait::async_trait;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// Runs a command registered with `ModuleManager::register_command`
+#[async_trait]
+pub trait CommandHandler: Send + Sync {
+    /// Handle a command that passed the checks in its `CommandSpec`
+    async fn handle(&self, client: &Client, message: &Message, context: &ModuleContext) -> Result<()>;
+}
+
+/// A command name and the checks run before its handler
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CommandSpec {
+    /// Uppercase command name
+    pub name: String,
+    /// Parameters required, answered with 461 when missing
+    pub min_params: usize,
+    /// Whether unregistered clients get 451 instead
+    pub requires_registration: bool,
+    /// Whether non-operators get 481 instead
+    pub oper_only: bool,
+}
+
+impl CommandSpec {
+    /// A command for registered users taking at least `min_params` parameters
+    pub fn new(name: &str, min_params: usize) -> Self {
+        Self {
+            name: name.to_uppercase(),
+            min_params,
+            requires_registration: true,
+            oper_only: false,
+        }
+    }
+
+    /// Allow the command before registration completes
+    pub fn unregistered(mut self) -> Self {
+        self.requires_registration = false;
+        self
+    }
+
+    /// Restrict the command to IRC operators
+    pub fn oper_only(mut self) -> Self {
+        self.oper_only = true;
+        self
+    }
+
+    /// The error numeric `client` gets for `message`, if it may not run
+    pub fn check(&self, client: &Client, message: &Message) -> Option<Message> {
+        if self.requires_registration && !client.is_registered() {
+            return Some(NumericReply::not_registered());
+        }
+        if self.oper_only && !client.get_user().is_some_and(|user| user.is_operator) {
+            return Some(NumericReply::no_privileges());
+        }
+        if message.params.len() < self.min_params {
+            return Some(NumericReply::need_more_params(&self.name));
+        }
+        None
+    }
+}
+
+/// Checks for a command core knows, run before modules see it
+///
+/// Commands that answer missing parameters with something other than 461
+/// (NICK, PRIVMSG, NOTICE, PING) leave the count to their handler.
+pub fn core_command_spec(command: &MessageType) -> Option<CommandSpec> {
+    let spec = match command {
+        MessageType::Password => CommandSpec::new("PASS", 1).unregistered(),
+        MessageType::User => CommandSpec::new("USER", 4).unregistered(),
+        MessageType::Join => CommandSpec::new("JOIN", 1),
+        MessageType::Part => CommandSpec::new("PART", 1),
+        MessageType::Mode => CommandSpec::new("MODE", 1),
+        MessageType::Topic => CommandSpec::new("TOPIC", 1),
+        MessageType::Invite => CommandSpec::new("INVITE", 2),
+        MessageType::Kick => CommandSpec::new("KICK", 2),
+        MessageType::Oper => CommandSpec::new("OPER", 1),
+        MessageType::Kill => CommandSpec::new("KILL", 2),
+        MessageType::ServerQuit => CommandSpec::new("SQUIT", 1),
+        MessageType::Connect => CommandSpec::new("CONNECT", 2),
+        MessageType::Wallops => CommandSpec::new("WALLOPS", 1),
+        MessageType::Whois => CommandSpec::new("WHOIS", 1),
+        MessageType::Whowas => CommandSpec::new("WHOWAS", 1),
+        MessageType::Ison => CommandSpec::new("ISON", 1),
+        MessageType::Userhost => CommandSpec::new("USERHOST", 1),
+        MessageType::PrivMsg
+        | MessageType::Names
+        | MessageType::List
+        | MessageType::Who
+        | MessageType::Away
+        | MessageType::Admin
+        | MessageType::Version
+        | MessageType::Stats
+        | MessageType::Links
+        | MessageType::Map
+        | MessageType::Time
+        | MessageType::Info
+        | MessageType::Trace
+        | MessageType::Motd
+        | MessageType::Lusers
+        | MessageType::Users
+        | MessageType::Rehash => CommandSpec::new(&command.to_string(), 0),
+        MessageType::Custom(name) if name.eq_ignore_ascii_case("SILENCE") => CommandSpec::new(name, 0),
+        MessageType::Custom(name) if name.eq_ignore_ascii_case("RULES") => CommandSpec::new(name, 0),
+        MessageType::Custom(name) if name.eq_ignore_ascii_case("LAG") => CommandSpec::new(name, 1).oper_only(),
+        MessageType::Custom(name) if name.eq_ignore_ascii_case("CHECK") => CommandSpec::new(name, 1).oper_only(),
+        MessageType::Custom(name) if name.eq_ignore_ascii_case("CHALLENGE") => CommandSpec::new(name, 1),
+        _ => return None,
+    };
+    Some(spec)
+}
+
+/// A registered command, as listed by HELP and STATS m
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CommandInfo {
+    pub spec: CommandSpec,
+    /// Module that owns the command, `None` for handlers registered directly
+    pub module: Option<String>,
+}
+
+/// What runs a registered command
+#[derive(Clone)]
+pub(crate) enum CommandTarget {
+    /// The owning module's `handle_command`
+    Module(String),
+    /// A handler registered with `ModuleManager::register_command`
+    Handler(Arc<dyn CommandHandler>),
+}
+
+/// Registered commands by uppercase name
+#[derive(Clone, Default)]
+pub(crate) struct CommandTable {
+    commands: HashMap<String, (CommandSpec, CommandTarget)>,
+}
+
+impl CommandTable {
+    /// Register commands for `target`
+    ///
+    /// Fails without registering anything if one of the names is taken by
+    /// something else.
+    pub(crate) fn register(&mut self, specs: Vec<CommandSpec>, target: CommandTarget) -> Result<()> {
+        for spec in &specs {
+            if let Some((_, existing)) = self.commands.get(&spec.name) {
+                let same_module = matches!((existing, &target),
+                    (CommandTarget::Module(a), CommandTarget::Module(b)) if a == b);
+                if !same_module {
+                    return Err(Error::Module(format!(
+                        "Command {} is already registered by {}", spec.name, Self::owner_name(existing)
+                    )));
+                }
+            }
+        }
+        for spec in specs {
+            self.commands.insert(spec.name.clone(), (spec, target.clone()));
+        }
+        Ok(())
+    }
+
+    /// Drop every command owned by a module
+    pub(crate) fn unregister_module(&mut self, module: &str) {
+        self.commands.retain(|_, (_, target)| !matches!(target, CommandTarget::Module(owner) if owner == module));
+    }
+
+    /// Spec and target of a command, looked up case-insensitively
+    pub(crate) fn get(&self, name: &str) -> Option<&(CommandSpec, CommandTarget)> {
+        self.commands.get(&name.to_uppercase())
+    }
+
+    /// Every registered command, sorted by name
+    pub(crate) fn list(&self) -> Vec<CommandInfo> {
+        let mut commands: Vec<CommandInfo> = self.commands.values()
+            .map(|(spec, target)| CommandInfo {
+                spec: spec.clone(),
+                module: match target {
+                    CommandTarget::Module(module) => Some(module.clone()),
+                    CommandTarget::Handler(_) => None,
+                },
+            })
+            .collect();
+        commands.sort_by(|a, b| a.spec.name.cmp(&b.spec.name));
+        commands
+    }
+
+    fn owner_name(target: &CommandTarget) -> String {
+        match target {
+            CommandTarget::Module(module) => format!("module {}", module),
+            CommandTarget::Handler(_) => "a command handler".to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::ClientState;
+    use crate::module::ModuleResult;
+    use crate::{ModuleManager, User};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    struct Counter(AtomicUsize);
+
+    #[async_trait]
+    impl CommandHandler for Counter {
+        async fn handle(&self, _client: &Client, _message: &Message, _context: &ModuleContext) -> Result<()> {
+            self.0.fetch_add(1, Ordering::SeqCst);
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_registered_command_checks() {
+        let mut manager = ModuleManager::default();
+        let counter = Arc::new(Counter(AtomicUsize::new(0)));
+        manager.register_command("purge", counter.clone(), 1, true, true).unwrap();
+        assert!(manager.register_command("PURGE", counter.clone(), 0, false, false).is_err());
+        assert_eq!(manager.commands()[0].spec.name, "PURGE");
+
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        let mut client = Client::new(uuid::Uuid::new_v4(), "127.0.0.1:50000".to_string(), "127.0.0.1:6667".to_string(), tx);
+        let purge = Message::new(MessageType::Custom("purge".to_string()), vec!["#spam".to_string()]);
+        assert!(matches!(manager.handle_message(&client, &purge).await.unwrap(), ModuleResult::Handled));
+        assert_eq!(rx.try_recv().unwrap().command.to_string(), "451");
+
+        client.set_state(ClientState::Registered);
+        let mut user = User::new("alice".to_string(), "alice".to_string(), "Alice".to_string(), "example.com".to_string(), "irc.example.com".to_string());
+        client.set_user(user.clone());
+        manager.handle_message(&client, &purge).await.unwrap();
+        assert_eq!(rx.try_recv().unwrap().command.to_string(), "481");
+
+        user.is_operator = true;
+        client.set_user(user);
+        let bare = Message::new(MessageType::Custom("PURGE".to_string()), vec![]);
+        manager.handle_message(&client, &bare).await.unwrap();
+        assert_eq!(rx.try_recv().unwrap().command.to_string(), "461");
+        manager.handle_message(&client, &purge).await.unwrap();
+        assert!(rx.try_recv().is_err());
+        assert_eq!(counter.0.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_command_table_conflicts() {
+        let mut table = CommandTable::default();
+        table.register(vec![CommandSpec::new("knock", 2)], CommandTarget::Module("knock".to_string())).unwrap();
+        assert_eq!(table.get("KNOCK").unwrap().0.min_params, 2);
+
+        // Another module can't take the name, and nothing is registered on failure
+        let clash = vec![CommandSpec::new("HELPME", 0), CommandSpec::new("KNOCK", 1)];
+        assert!(table.register(clash, CommandTarget::Module("other".to_string())).is_err());
+        assert!(table.get("HELPME").is_none());
+
+        table.unregister_module("knock");
+        assert!(table.list().is_empty());
+    }
+
+    #[test]
+    fn test_core_command_checks() {
+        let (tx, _rx) = tokio::sync::mpsc::unbounded_channel();
+        let mut client = Client::new(uuid::Uuid::new_v4(), "127.0.0.1:50000".to_string(), "127.0.0.1:6667".to_string(), tx);
+        let numeric = |client: &Client, command: MessageType, params: &[&str]| {
+            let spec = core_command_spec(&command)?;
+            let message = Message::new(command, params.iter().map(|p| p.to_string()).collect());
+            spec.check(client, &message).map(|reply| reply.command.to_string())
+        };
+
+        assert_eq!(numeric(&client, MessageType::Join, &["#rust"]).as_deref(), Some("451"));
+        assert_eq!(numeric(&client, MessageType::User, &["alice", "0", "*"]).as_deref(), Some("461"));
+        assert_eq!(numeric(&client, MessageType::User, &["alice", "0", "*", "Alice"]), None);
+        // Handlers with their own numerics for missing parameters aren't checked
+        assert_eq!(numeric(&client, MessageType::Nick, &[]), None);
+
+        client.set_state(ClientState::Registered);
+        assert_eq!(numeric(&client, MessageType::Join, &[]).as_deref(), Some("461"));
+        assert_eq!(numeric(&client, MessageType::Kill, &["bob"]).as_deref(), Some("461"));
+        assert_eq!(numeric(&client, MessageType::Custom("silence".to_string()), &[]), None);
+        assert_eq!(numeric(&client, MessageType::Custom("lag".to_string()), &["bob"]).as_deref(), Some("481"));
+    }
+}
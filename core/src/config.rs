@@ -3,7 +3,7 @@
 use crate::{Error, Result, RepliesConfig};
 use serde::{Deserialize, Serialize};
 use std::path::Path;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 
 /// Server configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -32,6 +32,129 @@ pub struct Config {
     pub netsplit: NetsplitConfig,
     /// Numeric replies configuration
     pub replies: Option<RepliesConfig>,
+    /// Admin HTTP API settings
+    #[serde(default)]
+    pub admin_api: AdminApiConfig,
+    /// Logging settings
+    #[serde(default)]
+    pub logging: LoggingConfig,
+    /// Trace export settings
+    #[serde(default)]
+    pub telemetry: TelemetryConfig,
+}
+
+/// Log output format
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    /// Human-readable lines
+    #[default]
+    Text,
+    /// One JSON object per line, with event fields as keys
+    Json,
+}
+
+/// Logging
+///
+/// `level` applies to everything not named in `targets`, which maps a
+/// module path to its own level, e.g. `"rustircd_core::server" = "debug"`.
+/// The `--log-level` command line flag overrides `level`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LoggingConfig {
+    /// Output format
+    pub format: LogFormat,
+    /// Default level: trace, debug, info, warn, error or off
+    pub level: String,
+    /// Per-module level overrides
+    pub targets: BTreeMap<String, String>,
+    /// Log to this file instead of stdout
+    pub file: Option<String>,
+    /// Rotate the file when it reaches this many bytes (0 = never)
+    pub max_file_size: u64,
+    /// Rotated files to keep (`file.1` is the newest)
+    pub max_files: usize,
+}
+
+impl Default for LoggingConfig {
+    fn default() -> Self {
+        Self {
+            format: LogFormat::Text,
+            level: "info".to_string(),
+            targets: BTreeMap::new(),
+            file: None,
+            max_file_size: 10 * 1024 * 1024,
+            max_files: 5,
+        }
+    }
+}
+
+/// Admin HTTP API
+///
+/// Only served when the daemon is built with the `admin-api` feature.
+/// Requests must carry `Authorization: Bearer <token>`, where the token
+/// matches `token_hash` (made with `PasswordHasher::hash_password`).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AdminApiConfig {
+    /// Whether to serve the API
+    pub enabled: bool,
+    /// Address to listen on; keep it on loopback behind a TLS proxy
+    pub bind_address: String,
+    /// Port to listen on (0 = any free port)
+    pub port: u16,
+    /// Hash of the bearer token
+    pub token_hash: String,
+}
+
+impl Default for AdminApiConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            bind_address: "127.0.0.1".to_string(),
+            port: 8081,
+            token_hash: String::new(),
+        }
+    }
+}
+
+/// OpenTelemetry trace export
+///
+/// When enabled, the spans covering each connection's accept and each
+/// command's parse, module dispatch, core handler and broadcast are sent to
+/// an OTLP/HTTP collector (JSON encoding), for per-command latency
+/// breakdowns.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TelemetryConfig {
+    /// Whether to export spans
+    pub enabled: bool,
+    /// OTLP/HTTP traces endpoint
+    pub otlp_endpoint: String,
+    /// Extra request headers, such as a collector API key
+    pub headers: BTreeMap<String, String>,
+    /// `service.name` reported with every span
+    pub service_name: String,
+    /// Fraction of commands traced, from 0.0 to 1.0
+    pub sample_ratio: f64,
+    /// Export once this many spans are waiting
+    pub batch_size: usize,
+    /// Export at least this often, in seconds
+    pub export_interval: u64,
+}
+
+impl Default for TelemetryConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            otlp_endpoint: "http://127.0.0.1:4318/v1/traces".to_string(),
+            headers: BTreeMap::new(),
+            service_name: "rustircd".to_string(),
+            sample_ratio: 1.0,
+            batch_size: 512,
+            export_interval: 5,
+        }
+    }
 }
 
 /// Server-specific configuration
@@ -71,12 +194,104 @@ pub struct ServerConfig {
     pub show_server_details_in_stats: bool,
     /// MOTD (Message of the Day) file path
     pub motd_file: Option<String>,
+    /// Per-class and per-port MOTDs, remote MOTD and short MOTD
+    #[serde(default)]
+    pub motd: MotdConfig,
+    /// RULES (network policy) file path
+    #[serde(default)]
+    pub rules_file: Option<String>,
     /// WHOIS string for IRC operators (default: "is an IRC Operator")
     #[serde(default = "default_oper_whois_string")]
     pub oper_whois_string: String,
     /// WHOIS string for server administrators (default: "is a Server Administrator")
     #[serde(default = "default_admin_whois_string")]
     pub admin_whois_string: String,
+    /// Maximum number of entries in a user's SILENCE list
+    #[serde(default = "default_max_silence_entries")]
+    pub max_silence_entries: usize,
+    /// Server notice mask given to operators on OPER and when they set +s without one
+    #[serde(default = "default_oper_snomask")]
+    pub oper_snomask: String,
+    /// Channels operators are joined to when they OPER (e.g. "#opers")
+    #[serde(default)]
+    pub oper_autojoin: Vec<String>,
+    /// Pacing of long LIST and WHO output
+    #[serde(default)]
+    pub safelist: SafeListConfig,
+    /// Nickname changes a user may make within `nick_change_window` seconds (0 = unlimited)
+    #[serde(default = "default_max_nick_changes")]
+    pub max_nick_changes: usize,
+    /// Time window in seconds for counting nickname changes
+    #[serde(default = "default_nick_change_window")]
+    pub nick_change_window: u64,
+}
+
+/// Pacing of long LIST and WHO output (SAFELIST)
+///
+/// Replies go out in batches with a pause in between, so listing thousands
+/// of channels or users doesn't flood the client's send queue.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SafeListConfig {
+    /// Replies sent per batch
+    pub batch_size: usize,
+    /// Pause between batches in milliseconds
+    pub batch_delay_ms: u64,
+    /// Seconds a client must wait between LIST commands (0 = no limit; opers are exempt)
+    pub list_cooldown: u64,
+}
+
+impl Default for SafeListConfig {
+    fn default() -> Self {
+        Self {
+            batch_size: 50,
+            batch_delay_ms: 10,
+            list_cooldown: 10,
+        }
+    }
+}
+
+/// MOTD selection, remote fetch and short MOTD
+///
+/// `motd_file` stays the default MOTD. A remote MOTD replaces it once it has
+/// been fetched, and the first matching override replaces both.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MotdConfig {
+    /// HTTPS URL the default MOTD is fetched from
+    pub remote_url: Option<String>,
+    /// Seconds between fetches of `remote_url` (at least 60)
+    pub refresh_interval: u64,
+    /// Send only 375/376 on connect, pointing at the MOTD command
+    pub short_motd: bool,
+    /// MOTD files for particular connection classes or ports
+    pub overrides: Vec<MotdOverride>,
+}
+
+impl Default for MotdConfig {
+    fn default() -> Self {
+        Self {
+            remote_url: None,
+            refresh_interval: 3600,
+            short_motd: false,
+            overrides: Vec::new(),
+        }
+    }
+}
+
+/// A MOTD file shown to clients in a connection class or on a port
+///
+/// When both `class` and `port` are set a client must match both.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MotdOverride {
+    /// Connection class name
+    #[serde(default)]
+    pub class: Option<String>,
+    /// Listener port
+    #[serde(default)]
+    pub port: Option<u16>,
+    /// MOTD file path
+    pub file: String,
 }
 
 fn default_oper_whois_string() -> String {
@@ -87,6 +302,22 @@ fn default_admin_whois_string() -> String {
     "is a Server Administrator".to_string()
 }
 
+fn default_max_silence_entries() -> usize {
+    15
+}
+
+fn default_oper_snomask() -> String {
+    "+abckno".to_string()
+}
+
+fn default_max_nick_changes() -> usize {
+    5
+}
+
+fn default_nick_change_window() -> u64 {
+    20
+}
+
 /// Network configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct NetworkConfig {
@@ -119,6 +350,21 @@ pub struct ServerLink {
     pub outgoing: bool,
     /// Connection class for this server link
     pub class: Option<String>,
+    /// Dial this link on startup and redial it with backoff after it drops
+    #[serde(default)]
+    pub autoconnect: bool,
+    /// SHA-256 fingerprint the peer certificate must match (outgoing TLS links)
+    #[serde(default)]
+    pub tls_fingerprint: Option<String>,
+    /// CA bundle to verify the peer certificate against when no fingerprint is pinned
+    #[serde(default)]
+    pub tls_ca_file: Option<String>,
+    /// Name sent as SNI and verified against the certificate (defaults to the hostname)
+    #[serde(default)]
+    pub tls_server_name: Option<String>,
+    /// Offer zlib compression (CAPAB ZIP) on this link
+    #[serde(default)]
+    pub compression: bool,
 }
 
 /// Operator flags for different privileges
@@ -138,6 +384,29 @@ pub enum OperatorFlag {
     Spy,
     /// Can use SQUIT command to disconnect servers
     Squit,
+    /// Can override channel restrictions (+i/+k/+l/+b/+m) and set modes without ops
+    Override,
+}
+
+impl std::str::FromStr for OperatorFlag {
+    type Err = Error;
+
+    /// Parse a flag by its config name, ignoring case, e.g. `GlobalOper`
+    fn from_str(name: &str) -> Result<Self> {
+        [
+            OperatorFlag::GlobalOper,
+            OperatorFlag::LocalOper,
+            OperatorFlag::RemoteConnect,
+            OperatorFlag::LocalConnect,
+            OperatorFlag::Administrator,
+            OperatorFlag::Spy,
+            OperatorFlag::Squit,
+            OperatorFlag::Override,
+        ]
+        .into_iter()
+        .find(|flag| format!("{:?}", flag).eq_ignore_ascii_case(name.trim()))
+        .ok_or_else(|| Error::Config(format!("Unknown operator flag {}", name)))
+    }
 }
 
 /// Operator configuration
@@ -145,7 +414,8 @@ pub enum OperatorFlag {
 pub struct OperatorConfig {
     /// Operator nickname
     pub nickname: String,
-    /// Operator password (SHA256 hashed)
+    /// Operator password (SHA256 hashed); empty when `account` or `certfp` is used instead
+    #[serde(default)]
     pub password_hash: String,
     /// Operator hostmask (user@host pattern)
     pub hostmask: String,
@@ -153,6 +423,15 @@ pub struct OperatorConfig {
     pub flags: Vec<OperatorFlag>,
     /// Whether this operator is enabled
     pub enabled: bool,
+    /// Services account the user must be logged in to, e.g. with SASL
+    #[serde(default)]
+    pub account: Option<String>,
+    /// SHA-256 fingerprint of the TLS client certificate the user must present
+    #[serde(default)]
+    pub certfp: Option<String>,
+    /// PEM public key (RSA or Ed25519) that lets the operator use CHALLENGE
+    #[serde(default)]
+    pub challenge_key_file: Option<String>,
 }
 
 impl OperatorConfig {
@@ -164,6 +443,9 @@ impl OperatorConfig {
             hostmask,
             flags,
             enabled: true,
+            account: None,
+            certfp: None,
+            challenge_key_file: None,
         }
     }
     
@@ -207,11 +489,47 @@ impl OperatorConfig {
         self.has_flag(OperatorFlag::Squit)
     }
     
+    /// Check if operator can override channel restrictions
+    pub fn can_override(&self) -> bool {
+        self.has_flag(OperatorFlag::Override)
+    }
+    
     /// Verify password
     pub fn verify_password(&self, password: &str) -> bool {
         PasswordHasher::verify_password(password, &self.password_hash)
     }
     
+    /// Whether OPER needs a password for this operator
+    pub fn requires_password(&self) -> bool {
+        !self.password_hash.is_empty()
+    }
+    
+    /// Check everything this operator is configured to require
+    ///
+    /// The password, the account the user is logged in to and the client
+    /// certificate fingerprint must each match when configured. A block
+    /// that configures none of them matches nobody.
+    pub fn verify_credentials(&self, password: Option<&str>, account: Option<&str>, certfp: Option<&str>) -> bool {
+        if !self.requires_password() && self.account.is_none() && self.certfp.is_none() {
+            return false;
+        }
+        let password_ok = !self.requires_password() || password.is_some_and(|password| self.verify_password(password));
+        password_ok && self.verify_identity(account, certfp)
+    }
+    
+    /// Check the account and client certificate this operator requires
+    ///
+    /// CHALLENGE checks these in place of the password.
+    pub fn verify_identity(&self, account: Option<&str>, certfp: Option<&str>) -> bool {
+        let account_ok = self.account.as_ref()
+            .is_none_or(|required| account.is_some_and(|account| account.eq_ignore_ascii_case(required)));
+        let certfp_ok = self.certfp.as_ref().is_none_or(|required| {
+            let required = crate::link_tls::normalize_fingerprint(required);
+            required.is_some() && certfp.and_then(crate::link_tls::normalize_fingerprint) == required
+        });
+        account_ok && certfp_ok
+    }
+    
     /// Check if hostmask matches
     pub fn matches_hostmask(&self, user: &str, host: &str) -> bool {
         self.matches_hostmask_pattern(&format!("{}@{}", user, host))
@@ -392,12 +710,53 @@ pub struct ConnectionConfig {
     pub max_connections_per_ip: usize,
     /// Maximum connection rate per host
     pub max_connections_per_host: usize,
+    /// Limits on concurrent client connections from one address
+    #[serde(default)]
+    pub clones: CloneLimitConfig,
+    /// Disconnect clients on a listener a rehash removes, rather than
+    /// letting them stay until they quit
+    #[serde(default)]
+    pub close_removed_listener_connections: bool,
+}
+
+/// Limits on concurrent client connections from one host (clones)
+///
+/// IPv6 clients usually hold a whole prefix, so besides the per-address
+/// limits their connections are also counted per `ipv6_prefix`. Reaching a
+/// soft limit warns operators with the `c` snomask; reaching a hard limit
+/// refuses the connection at accept time. A limit of 0 is disabled, and
+/// addresses exempt from throttling are exempt here too.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CloneLimitConfig {
+    /// Connections from one address before operators are warned
+    pub soft_limit_per_ip: usize,
+    /// Connections from one address beyond which new ones are refused
+    pub hard_limit_per_ip: usize,
+    /// Length of the IPv6 prefix counted as one host
+    pub ipv6_prefix: u8,
+    /// Connections from one IPv6 prefix before operators are warned
+    pub soft_limit_per_prefix: usize,
+    /// Connections from one IPv6 prefix beyond which new ones are refused
+    pub hard_limit_per_prefix: usize,
+}
+
+impl Default for CloneLimitConfig {
+    fn default() -> Self {
+        Self {
+            soft_limit_per_ip: 3,
+            hard_limit_per_ip: 5,
+            ipv6_prefix: 64,
+            soft_limit_per_prefix: 5,
+            hard_limit_per_prefix: 10,
+        }
+    }
 }
 
 /// Port configuration for listening
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PortConfig {
-    /// Port number
+    /// Port number, or 0 for any free port (see `Server::listen_addrs`)
     pub port: u16,
     /// Connection type (client, server, or both)
     pub connection_type: PortConnectionType,
@@ -407,6 +766,14 @@ pub struct PortConfig {
     pub description: Option<String>,
     /// Optional bind address for this specific port (overrides global bind_address)
     pub bind_address: Option<String>,
+    /// IPv6 address to also listen on, for dual-stack ports; it is bound
+    /// IPv6-only so it can share the port with an IPv4 `bind_address`
+    #[serde(default)]
+    pub bind_address_v6: Option<String>,
+    /// Ask TLS clients for a certificate, without requiring one, so its
+    /// fingerprint can be used for SASL EXTERNAL, oper blocks and WHOIS
+    #[serde(default)]
+    pub request_client_cert: bool,
 }
 
 /// Types of connections allowed on a port
@@ -498,10 +865,66 @@ pub struct SecurityConfig {
     pub enable_dns: bool,
     /// Enable reverse DNS
     pub enable_reverse_dns: bool,
+    /// Seconds the connect-time lookups (reverse DNS, ident and DNSBLs),
+    /// run side by side, may take in total before the client registers
+    /// with whatever answered
+    #[serde(default = "default_lookup_timeout")]
+    pub lookup_timeout: u64,
+    /// DNS blocklists connecting addresses are checked against
+    #[serde(default)]
+    pub dnsbl: Vec<DnsblConfig>,
     /// TLS configuration
     pub tls: TlsConfig,
     /// Server security settings
     pub server_security: ServerSecurityConfig,
+    /// File that K/G-lines, RESVs and other bans are saved to so they survive restarts
+    #[serde(default)]
+    pub ban_file: Option<String>,
+    /// Audit trail of privileged operator actions
+    #[serde(default)]
+    pub audit: AuditConfig,
+}
+
+fn default_lookup_timeout() -> u64 {
+    5
+}
+
+/// A DNS blocklist clients are checked against when they connect
+///
+/// Clients whose address is listed are disconnected with `reason`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DnsblConfig {
+    /// Blocklist zone, such as "dnsbl.dronebl.org"
+    pub zone: String,
+    /// Reason given to listed clients
+    #[serde(default = "default_dnsbl_reason")]
+    pub reason: String,
+    /// Last octets of the 127.0.0.x answers that count as listed; any
+    /// answer counts when empty
+    #[serde(default)]
+    pub replies: Vec<u8>,
+}
+
+fn default_dnsbl_reason() -> String {
+    "Your address is listed in a DNS blocklist".to_string()
+}
+
+/// Audit trail configuration
+///
+/// Privileged actions (OPER, KILL, SQUIT, G-line, REHASH, CONNECT) are always
+/// traced; these settings add a JSON-lines log file, a channel the events are
+/// echoed to, and how many recent events STATS A shows.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AuditConfig {
+    /// Record privileged actions
+    pub enabled: bool,
+    /// File each event is appended to as a JSON line
+    pub log_file: Option<String>,
+    /// Channel events are echoed to (e.g. "&oper-log")
+    pub log_channel: Option<String>,
+    /// Number of recent events kept in memory for STATS A
+    pub history_size: usize,
 }
 
 /// Server security configuration
@@ -536,6 +959,89 @@ pub struct TlsConfig {
     pub version: String,
     /// Cipher suites
     pub cipher_suites: Vec<String>,
+    /// Warn operators (snomask `s`) once the certificate is this close to
+    /// expiring, in days; 0 disables the warnings
+    #[serde(default = "default_expiry_warning_days")]
+    pub expiry_warning_days: u64,
+    /// Reload the certificate when its certificate or key file changes,
+    /// noticed through filesystem notifications
+    #[serde(default = "default_tls_watch")]
+    pub watch: bool,
+    /// Automatic certificates from an ACME CA such as Let's Encrypt
+    #[serde(default)]
+    pub acme: AcmeConfig,
+}
+
+fn default_expiry_warning_days() -> u64 {
+    14
+}
+
+fn default_tls_watch() -> bool {
+    true
+}
+
+/// How the ACME CA checks that we control a domain
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AcmeChallengeType {
+    /// Serve a token over plain HTTP on port 80
+    #[serde(rename = "http-01")]
+    Http01,
+    /// Publish a TXT record through `dns_hook`
+    #[serde(rename = "dns-01")]
+    Dns01,
+}
+
+/// ACME certificate provisioning (needs the `acme` feature)
+///
+/// The certificate and key are written to `cert_file` and `key_file` and
+/// swapped into the TLS acceptor without dropping connections.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AcmeConfig {
+    /// Request and renew the TLS certificate over ACME
+    pub enabled: bool,
+    /// ACME directory URL of the CA
+    pub directory_url: String,
+    /// Names on the certificate; the first is also its common name
+    pub domains: Vec<String>,
+    /// Contact email addresses for the ACME account
+    pub contact: Vec<String>,
+    /// Challenge used to prove control of the domains
+    pub challenge: AcmeChallengeType,
+    /// Address the HTTP-01 responder binds
+    pub http_bind_address: String,
+    /// Port the HTTP-01 responder binds; the CA always connects to port 80
+    pub http_port: u16,
+    /// Command run as `<hook> add|remove <record> <value>` to publish the
+    /// DNS-01 TXT record
+    pub dns_hook: Option<String>,
+    /// Seconds to wait after adding the TXT record before validation
+    pub dns_propagation_delay: u64,
+    /// Where the ACME account key is kept; created on first use
+    pub account_key_file: String,
+    /// Renew once the certificate expires within this many days
+    pub renew_before_days: u64,
+    /// Seconds between certificate checks
+    pub check_interval: u64,
+}
+
+impl Default for AcmeConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            directory_url: "https://acme-v02.api.letsencrypt.org/directory".to_string(),
+            domains: Vec::new(),
+            contact: Vec::new(),
+            challenge: AcmeChallengeType::Http01,
+            http_bind_address: "0.0.0.0".to_string(),
+            http_port: 80,
+            dns_hook: None,
+            dns_propagation_delay: 30,
+            account_key_file: "acme_account.key".to_string(),
+            renew_before_days: 30,
+            check_interval: 43200,
+        }
+    }
 }
 
 /// Module configuration
@@ -553,6 +1059,173 @@ pub struct ModuleConfig {
     pub command_rate_limiting: CommandRateLimitConfig,
     /// Messaging modules configuration
     pub messaging: MessagingConfig,
+    /// Hostname cloaking configuration
+    #[serde(default)]
+    pub ip_cloak: IpCloakConfig,
+    /// CTCP replies and flood control
+    #[serde(default)]
+    pub ctcp: CtcpConfig,
+    /// Built-in account service
+    #[serde(default)]
+    pub nickserv: NickServConfig,
+    /// User and channel metadata
+    #[serde(default)]
+    pub metadata: MetadataConfig,
+    /// Typing notification limits
+    #[serde(default)]
+    pub typing: TypingConfig,
+}
+
+/// Limits on typing notifications (the `+typing` client tag on TAGMSG)
+///
+/// Clients resend typing state every few seconds while the user types, so
+/// the notifications are capped per client to keep them from amplifying
+/// traffic. Notifications over the limit are dropped silently.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TypingConfig {
+    /// Relay typing notifications at all
+    pub enabled: bool,
+    /// Typing notifications a client may send per window
+    pub max_per_window: usize,
+    /// Window length in seconds
+    pub window_secs: u64,
+}
+
+impl Default for TypingConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            max_per_window: 5,
+            window_secs: 10,
+        }
+    }
+}
+
+/// User and channel metadata (METADATA) configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MetadataConfig {
+    /// Keys a single user or channel may have set
+    pub max_keys: usize,
+    /// Longest value accepted, in bytes, unless the key sets its own
+    pub max_value_bytes: usize,
+    /// Keys one client may subscribe to
+    pub max_subscriptions: usize,
+    /// Whether keys missing from `keys` may be set
+    pub allow_unlisted_keys: bool,
+    /// Known keys and who may see and set them
+    pub keys: Vec<MetadataKeyConfig>,
+}
+
+/// Policy for one metadata key
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MetadataKeyConfig {
+    /// Key name, e.g. `avatar`
+    pub name: String,
+    /// Who can see values of this key
+    #[serde(default)]
+    pub visibility: MetadataVisibility,
+    /// Longest value for this key, overriding `max_value_bytes`
+    #[serde(default)]
+    pub max_value_bytes: Option<usize>,
+    /// Whether only IRC operators may set this key
+    #[serde(default)]
+    pub oper_only: bool,
+}
+
+/// Who can see a metadata value
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MetadataVisibility {
+    /// Everyone
+    #[default]
+    Public,
+    /// IRC operators and whoever may change the value
+    Operators,
+    /// Only whoever may change the value
+    Private,
+}
+
+impl MetadataVisibility {
+    /// Visibility token sent in METADATA replies, `*` when public
+    pub fn token(&self) -> &'static str {
+        match self {
+            MetadataVisibility::Public => "*",
+            MetadataVisibility::Operators => "oper",
+            MetadataVisibility::Private => "private",
+        }
+    }
+
+    /// Parse a visibility token, treating unknown ones as private
+    pub fn from_token(token: &str) -> Self {
+        match token {
+            "*" => MetadataVisibility::Public,
+            "oper" => MetadataVisibility::Operators,
+            _ => MetadataVisibility::Private,
+        }
+    }
+}
+
+/// Built-in account service (NickServ-lite) configuration
+///
+/// Used by the `nickserv` module for networks that don't link external services.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NickServConfig {
+    /// Nickname the service answers to
+    pub nick: String,
+    /// JSON file accounts are kept in; unset keeps them in memory only
+    pub accounts_file: Option<String>,
+    /// Shortest password REGISTER and SET PASSWORD accept
+    pub min_password_length: usize,
+    /// Whether users may register new accounts
+    pub allow_registration: bool,
+    /// Seconds a user on a registered nick has to identify before being
+    /// renamed to a guest nick (0 = never enforce)
+    pub enforce_timeout: u64,
+    /// Prefix of the nicks unidentified users are renamed to; digits follow
+    pub guest_prefix: String,
+}
+
+/// CTCP handling configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CtcpConfig {
+    /// Answer CTCP requests sent to the server name itself
+    pub server_replies: bool,
+    /// Text of the server's CTCP VERSION reply; empty uses the server version
+    pub version_reply: String,
+    /// Answer CTCP PING sent to the server
+    pub reply_to_ping: bool,
+    /// Answer CTCP TIME sent to the server
+    pub reply_to_time: bool,
+    /// Maximum CTCP messages a client may send within the time window (0 = unlimited)
+    pub max_ctcps: usize,
+    /// Time window in seconds for CTCP counting
+    pub time_window_seconds: u64,
+    /// Whether to exempt operators from CTCP rate limiting
+    pub exempt_operators: bool,
+}
+
+/// Hostname cloaking configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct IpCloakConfig {
+    /// Cloak hosts (set umode +x) automatically on connect
+    pub enabled: bool,
+    /// Secret key mixed into cloak hashes; must match across the network
+    pub secret_key: String,
+    /// Suffix appended to cloaked IP addresses
+    pub suffix: String,
+    /// IPv4 prefix length hashed separately so ranges stay bannable
+    pub ipv4_cidr: u8,
+    /// IPv6 prefix length hashed separately so ranges stay bannable
+    pub ipv6_cidr: u8,
+    /// Number of hash bits shown per cloak segment
+    pub mac_bits: u16,
+    /// Hosts matching these patterns are never cloaked
+    pub preserve_patterns: Vec<String>,
 }
 
 /// Messaging modules configuration
@@ -588,6 +1261,10 @@ pub struct ThrottlingConfig {
     pub enabled: bool,
     /// Maximum connections allowed per IP within the time window
     pub max_connections_per_ip: usize,
+    /// Length of the IPv6 prefix throttled as one address, since IPv6
+    /// clients usually hold a whole /64
+    #[serde(default = "default_throttle_ipv6_prefix")]
+    pub ipv6_prefix: u8,
     /// Time window in seconds for connection counting
     pub time_window_seconds: u64,
     /// Initial throttling duration in seconds (stage 1)
@@ -598,6 +1275,59 @@ pub struct ThrottlingConfig {
     pub stage_factor: u64,
     /// Cleanup interval in seconds for expired throttle entries
     pub cleanup_interval_seconds: u64,
+    /// Lockouts for IPs that keep failing OPER, SASL or PASS
+    #[serde(default)]
+    pub auth_failures: AuthFailureConfig,
+    /// IPs or CIDR ranges never throttled, such as localhost and services hosts
+    #[serde(default = "default_throttle_exempt")]
+    pub exempt: Vec<String>,
+}
+
+fn default_throttle_ipv6_prefix() -> u8 {
+    64
+}
+
+fn default_throttle_exempt() -> Vec<String> {
+    vec!["127.0.0.0/8".to_string(), "::1".to_string()]
+}
+
+/// Fail2ban-style lockouts for repeated failed logins from one IP
+///
+/// Tracked independently of connection throttling's `enabled` flag.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AuthFailureConfig {
+    /// Track failed OPER, CHALLENGE, SASL and PASS attempts
+    pub enabled: bool,
+    /// Failures within the window that lock the IP out
+    pub max_failures: u32,
+    /// Window in seconds failures are counted over
+    pub window_seconds: u64,
+    /// Length of the first lockout in seconds
+    pub initial_lockout_seconds: u64,
+    /// Factor each further lockout is longer by
+    pub lockout_factor: u64,
+    /// Longest lockout in seconds
+    pub max_lockout_seconds: u64,
+    /// Failures after which the IP is D-lined for `dline_duration_seconds`; 0 never D-lines
+    pub dline_after_failures: u32,
+    /// Length of an automatic D-line in seconds
+    pub dline_duration_seconds: u64,
+}
+
+impl Default for AuthFailureConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            max_failures: 5,
+            window_seconds: 600,
+            initial_lockout_seconds: 60,
+            lockout_factor: 2,
+            max_lockout_seconds: 3600,
+            dline_after_failures: 0,
+            dline_duration_seconds: 3600,
+        }
+    }
 }
 
 /// Action to take when a rate limit is exceeded
@@ -649,6 +1379,9 @@ pub struct DatabaseConfig {
     pub user_cache_ttl_seconds: Option<u64>,
     /// Channel member cache TTL in seconds
     pub channel_cache_ttl_seconds: Option<u64>,
+    /// File permanent (+P) channels are saved to, so they survive restarts
+    #[serde(default)]
+    pub permanent_channels_file: Option<String>,
 }
 
 /// Broadcasting configuration
@@ -754,6 +1487,20 @@ pub struct NetsplitConfig {
     pub burst_optimization_window: u64,
     /// Notify operators about netsplits and reconnections
     pub notify_opers_on_split: bool,
+    /// Seconds between PINGs sent to each registered server link
+    #[serde(default = "default_link_ping_interval")]
+    pub link_ping_interval: u64,
+    /// Seconds without a PONG after which a link is considered dead and split
+    #[serde(default = "default_link_dead_timeout")]
+    pub link_dead_timeout: u64,
+}
+
+fn default_link_ping_interval() -> u64 {
+    90
+}
+
+fn default_link_dead_timeout() -> u64 {
+    240
 }
 
 impl Default for DatabaseConfig {
@@ -766,6 +1513,7 @@ impl Default for DatabaseConfig {
             user_cache_size: Some(10000),
             user_cache_ttl_seconds: Some(300),  // 5 minutes
             channel_cache_ttl_seconds: Some(30),  // 30 seconds
+            permanent_channels_file: None,
         }
     }
 }
@@ -791,6 +1539,8 @@ impl Default for NetsplitConfig {
             burst_optimization_enabled: true,
             burst_optimization_window: 300, // 5 minutes window
             notify_opers_on_split: true,
+            link_ping_interval: default_link_ping_interval(),
+            link_dead_timeout: default_link_dead_timeout(),
         }
     }
 }
@@ -810,6 +1560,9 @@ impl Default for Config {
             authentication: None, // No authentication by default
             netsplit: NetsplitConfig::default(),
             replies: None, // Will be loaded from replies.toml if available
+            admin_api: AdminApiConfig::default(),
+            logging: LoggingConfig::default(),
+            telemetry: TelemetryConfig::default(),
         }
     }
 }
@@ -834,8 +1587,16 @@ impl Default for ServerConfig {
             admin_location2: "https://github.com/rustircd/rustircd".to_string(),
             show_server_details_in_stats: true, // Default to showing details for operators
             motd_file: Some("motd.txt".to_string()), // Default MOTD file
+            motd: MotdConfig::default(),
+            rules_file: None,
             oper_whois_string: default_oper_whois_string(),
             admin_whois_string: default_admin_whois_string(),
+            max_silence_entries: default_max_silence_entries(),
+            oper_snomask: default_oper_snomask(),
+            oper_autojoin: Vec::new(),
+            safelist: SafeListConfig::default(),
+            max_nick_changes: default_max_nick_changes(),
+            nick_change_window: default_nick_change_window(),
         }
     }
 }
@@ -862,6 +1623,8 @@ impl Default for ConnectionConfig {
                     tls: false,
                     description: Some("Standard IRC port".to_string()),
                     bind_address: None, // Use global bind_address
+                    bind_address_v6: None,
+                    request_client_cert: false,
                 },
                 PortConfig {
                     port: 6668,
@@ -869,6 +1632,8 @@ impl Default for ConnectionConfig {
                     tls: false,
                     description: Some("Server-to-server connections".to_string()),
                     bind_address: None, // Use global bind_address
+                    bind_address_v6: None,
+                    request_client_cert: false,
                 },
                 PortConfig {
                     port: 6697,
@@ -876,6 +1641,8 @@ impl Default for ConnectionConfig {
                     tls: true,
                     description: Some("Secure IRC port".to_string()),
                     bind_address: None, // Use global bind_address
+                    bind_address_v6: None,
+                    request_client_cert: false,
                 },
                 PortConfig {
                     port: 6698,
@@ -883,6 +1650,8 @@ impl Default for ConnectionConfig {
                     tls: true,
                     description: Some("Secure server-to-server connections".to_string()),
                     bind_address: None, // Use global bind_address
+                    bind_address_v6: None,
+                    request_client_cert: false,
                 },
             ],
             bind_address: "0.0.0.0".to_string(),
@@ -890,6 +1659,8 @@ impl Default for ConnectionConfig {
             ping_timeout: 300,
             max_connections_per_ip: 5,
             max_connections_per_host: 10,
+            clones: CloneLimitConfig::default(),
+            close_removed_listener_connections: false,
         }
     }
 }
@@ -905,8 +1676,23 @@ impl Default for SecurityConfig {
             enable_ident: true,
             enable_dns: true,
             enable_reverse_dns: true,
+            lookup_timeout: default_lookup_timeout(),
+            dnsbl: Vec::new(),
             tls: TlsConfig::default(),
             server_security: ServerSecurityConfig::default(),
+            ban_file: None,
+            audit: AuditConfig::default(),
+        }
+    }
+}
+
+impl Default for AuditConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            log_file: None,
+            log_channel: None,
+            history_size: 500,
         }
     }
 }
@@ -933,6 +1719,9 @@ impl Default for TlsConfig {
             ca_file: None,
             version: "1.3".to_string(),
             cipher_suites: vec!["TLS_AES_256_GCM_SHA384".to_string(), "TLS_CHACHA20_POLY1305_SHA256".to_string()],
+            expiry_warning_days: default_expiry_warning_days(),
+            watch: default_tls_watch(),
+            acme: AcmeConfig::default(),
         }
     }
 }
@@ -946,6 +1735,77 @@ impl Default for ModuleConfig {
             throttling: ThrottlingConfig::default(),
             command_rate_limiting: CommandRateLimitConfig::default(),
             messaging: MessagingConfig::default(),
+            ip_cloak: IpCloakConfig::default(),
+            ctcp: CtcpConfig::default(),
+            nickserv: NickServConfig::default(),
+            metadata: MetadataConfig::default(),
+            typing: TypingConfig::default(),
+        }
+    }
+}
+
+impl Default for MetadataConfig {
+    fn default() -> Self {
+        let public = |name: &str| MetadataKeyConfig {
+            name: name.to_string(),
+            visibility: MetadataVisibility::Public,
+            max_value_bytes: None,
+            oper_only: false,
+        };
+        Self {
+            max_keys: 20,
+            max_value_bytes: 300,
+            max_subscriptions: 50,
+            allow_unlisted_keys: false,
+            keys: vec![public("url"), public("avatar"), public("display-name")],
+        }
+    }
+}
+
+impl MetadataConfig {
+    /// The policy for `key`, if it is listed
+    pub fn key(&self, key: &str) -> Option<&MetadataKeyConfig> {
+        self.keys.iter().find(|policy| policy.name.eq_ignore_ascii_case(key))
+    }
+}
+
+impl Default for NickServConfig {
+    fn default() -> Self {
+        Self {
+            nick: "NickServ".to_string(),
+            accounts_file: None,
+            min_password_length: 8,
+            allow_registration: true,
+            enforce_timeout: 0,
+            guest_prefix: "Guest".to_string(),
+        }
+    }
+}
+
+impl Default for CtcpConfig {
+    fn default() -> Self {
+        Self {
+            server_replies: true,
+            version_reply: String::new(),
+            reply_to_ping: true,
+            reply_to_time: true,
+            max_ctcps: 5,
+            time_window_seconds: 10,
+            exempt_operators: true,
+        }
+    }
+}
+
+impl Default for IpCloakConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            secret_key: String::new(),
+            suffix: ".cloaked".to_string(),
+            ipv4_cidr: 16,
+            ipv6_cidr: 32,
+            mac_bits: 32,
+            preserve_patterns: Vec::new(),
         }
     }
 }
@@ -989,11 +1849,14 @@ impl Default for ThrottlingConfig {
         Self {
             enabled: true,
             max_connections_per_ip: 5,
+            ipv6_prefix: default_throttle_ipv6_prefix(),
             time_window_seconds: 60,
             initial_throttle_seconds: 10,
             max_stages: 10,
             stage_factor: 10,
             cleanup_interval_seconds: 300, // 5 minutes
+            auth_failures: AuthFailureConfig::default(),
+            exempt: default_throttle_exempt(),
         }
     }
 }
@@ -1012,6 +1875,127 @@ impl Default for CommandRateLimitConfig {
     }
 }
 
+/// Resolve `table`'s includes and environment references
+///
+/// `stack` holds the files being loaded, to catch include cycles.
+fn load_config_table(path: &Path, mut table: toml::Table, stack: &mut Vec<std::path::PathBuf>) -> Result<toml::Table> {
+    let canonical = path.canonicalize()
+        .map_err(|e| Error::Config(format!("Failed to read config file {}: {}", path.display(), e)))?;
+    if let Some(start) = stack.iter().position(|loaded| *loaded == canonical) {
+        let cycle: Vec<String> = stack[start..].iter().chain([&canonical]).map(|p| p.display().to_string()).collect();
+        return Err(Error::Config(format!("Config include cycle: {}", cycle.join(" -> "))));
+    }
+    stack.push(canonical);
+
+    for (_, value) in table.iter_mut() {
+        interpolate_env(value).map_err(|e| Error::Config(format!("{}: {}", path.display(), e)))?;
+    }
+
+    let mut merged = toml::Table::new();
+    if let Some(includes) = table.remove("include") {
+        let patterns: Vec<String> = includes.try_into()
+            .map_err(|_| Error::Config(format!("{}: include must be a list of file names", path.display())))?;
+        let base = path.parent().unwrap_or_else(|| Path::new(""));
+        for pattern in patterns {
+            for include in expand_include(base, &pattern)? {
+                let content = std::fs::read_to_string(&include)
+                    .map_err(|e| Error::Config(format!("Failed to read included config file {}: {}", include.display(), e)))?;
+                let included: toml::Table = toml::from_str(&content)
+                    .map_err(|e| Error::Config(format!("Failed to parse included config file {}: {}", include.display(), e)))?;
+                let included = load_config_table(&include, included, stack)?;
+                merge_config_tables(&mut merged, included);
+            }
+        }
+    }
+    merge_config_tables(&mut merged, table);
+
+    stack.pop();
+    Ok(merged)
+}
+
+/// The files an include pattern names, in sorted order
+///
+/// A pattern without wildcards must name an existing file; one with
+/// wildcards may match nothing.
+fn expand_include(base: &Path, pattern: &str) -> Result<Vec<std::path::PathBuf>> {
+    let path = base.join(pattern);
+    let file_name = path.file_name().and_then(|name| name.to_str()).unwrap_or_default();
+    if !file_name.contains(['*', '?']) {
+        if !path.is_file() {
+            return Err(Error::Config(format!("Included config file not found: {}", path.display())));
+        }
+        return Ok(vec![path]);
+    }
+
+    let directory = path.parent().unwrap_or_else(|| Path::new(""));
+    let entries = std::fs::read_dir(if directory.as_os_str().is_empty() { Path::new(".") } else { directory })
+        .map_err(|e| Error::Config(format!("Failed to read config directory {}: {}", directory.display(), e)))?;
+    let mut matches: Vec<std::path::PathBuf> = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|entry| entry.is_file())
+        .filter(|entry| entry.file_name().and_then(|name| name.to_str())
+            .is_some_and(|name| crate::utils::string::wildcard_match(file_name, name)))
+        .collect();
+    matches.sort();
+    Ok(matches)
+}
+
+/// Merge `overlay` into `base`: tables merge, arrays append and other
+/// values in `overlay` replace those in `base`
+fn merge_config_tables(base: &mut toml::Table, overlay: toml::Table) {
+    for (key, value) in overlay {
+        match (base.get_mut(&key), value) {
+            (Some(toml::Value::Table(existing)), toml::Value::Table(table)) => merge_config_tables(existing, table),
+            (Some(toml::Value::Array(existing)), toml::Value::Array(array)) => existing.extend(array),
+            (_, value) => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+/// Replace `${VAR}` and `${VAR:-default}` in every string within `value`
+fn interpolate_env(value: &mut toml::Value) -> std::result::Result<(), String> {
+    match value {
+        toml::Value::String(text) if text.contains("${") => *text = expand_env(text)?,
+        toml::Value::Array(values) => values.iter_mut().try_for_each(interpolate_env)?,
+        toml::Value::Table(table) => table.iter_mut().try_for_each(|(_, value)| interpolate_env(value))?,
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Expand the environment references in one string
+fn expand_env(text: &str) -> std::result::Result<String, String> {
+    let mut expanded = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find("${") {
+        if rest[..start].ends_with('$') {
+            // `$${` is a literal `${`
+            expanded.push_str(&rest[..start - 1]);
+            expanded.push_str("${");
+            rest = &rest[start + 2..];
+            continue;
+        }
+        expanded.push_str(&rest[..start]);
+        let end = rest[start..].find('}')
+            .ok_or_else(|| format!("Unterminated ${{ in \"{}\"", text))?;
+        let reference = &rest[start + 2..start + end];
+        let (name, default) = match reference.split_once(":-") {
+            Some((name, default)) => (name, Some(default)),
+            None => (reference, None),
+        };
+        match (std::env::var(name), default) {
+            (Ok(value), _) => expanded.push_str(&value),
+            (Err(_), Some(default)) => expanded.push_str(default),
+            (Err(_), None) => return Err(format!("Environment variable {} is not set", name)),
+        }
+        rest = &rest[start + end + 1..];
+    }
+    expanded.push_str(rest);
+    Ok(expanded)
+}
+
 impl Default for ServicesConfig {
     fn default() -> Self {
         Self {
@@ -1024,13 +2008,30 @@ impl Default for ServicesConfig {
 
 impl Config {
     /// Load configuration from file
+    ///
+    /// A top-level `include = ["opers.toml", "links/*.toml"]` pulls in more
+    /// files, relative to the including file; `*` and `?` match within the
+    /// last path component. Included tables are merged, arrays (such as
+    /// `[[network.operators]]`) are appended, and a value set by the
+    /// including file overrides one from its includes. String values may
+    /// use `${VAR}` or `${VAR:-default}` to read the environment; `$${`
+    /// writes a literal `${`.
     pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
         let config_path = path.as_ref();
         let content = std::fs::read_to_string(config_path)
             .map_err(|e| Error::Config(format!("Failed to read config file: {}", e)))?;
         
-        let mut config: Config = toml::from_str(&content)
+        let table: toml::Table = toml::from_str(&content)
             .map_err(|e| Error::Config(format!("Failed to parse config file: {}", e)))?;
+        let mut config: Config = if table.contains_key("include") || content.contains("${") {
+            let table = load_config_table(config_path, table, &mut Vec::new())?;
+            toml::Value::Table(table).try_into()
+                .map_err(|e| Error::Config(format!("Failed to parse config file: {}", e)))?
+        } else {
+            // Parsed from the text so errors keep their line numbers
+            toml::from_str(&content)
+                .map_err(|e| Error::Config(format!("Failed to parse config file: {}", e)))?
+        };
         
         // Try to load replies configuration if not already set
         if config.replies.is_none() {
@@ -1114,11 +2115,8 @@ impl Config {
         
         let mut seen_ports = std::collections::HashSet::new();
         for port_config in &self.connection.ports {
-            if port_config.port == 0 {
-                return Err(Error::Config("Port cannot be 0".to_string()));
-            }
-            
-            if seen_ports.contains(&port_config.port) {
+            // Port 0 binds whatever free port the OS assigns, so it can repeat
+            if port_config.port != 0 && seen_ports.contains(&port_config.port) {
                 return Err(Error::Config(format!("Duplicate port {} in configuration", port_config.port)));
             }
             seen_ports.insert(port_config.port);
@@ -1346,8 +2344,40 @@ impl Config {
     }
 
     /// Check if a server is a super server
+    ///
+    /// Enabled services count as super servers too.
     pub fn is_super_server(&self, server_name: &str) -> bool {
         self.network.super_servers.iter().any(|server| server.name == server_name)
+            || self.services.services.iter().any(|service| service.enabled && service.name == server_name)
+    }
+
+    /// Link settings for a super server or service that connects to us
+    ///
+    /// Services dial in rather than being dialed, so they only need a
+    /// name and password; everything else takes the link defaults.
+    pub fn super_server_link(&self, server_name: &str) -> Option<ServerLink> {
+        let (hostname, port, password, tls) = match self.get_super_server(server_name) {
+            Some(server) => (&server.hostname, server.port, &server.password, server.tls),
+            None => {
+                let service = self.services.services.iter()
+                    .find(|service| service.enabled && service.name == server_name)?;
+                (&service.hostname, service.port, &service.password, service.tls)
+            }
+        };
+        Some(ServerLink {
+            name: server_name.to_string(),
+            hostname: hostname.clone(),
+            port,
+            password: password.clone(),
+            tls,
+            outgoing: false,
+            class: None,
+            autoconnect: false,
+            tls_fingerprint: None,
+            tls_ca_file: None,
+            tls_server_name: None,
+            compression: false,
+        })
     }
 
     /// Find operator by nickname
@@ -1359,7 +2389,7 @@ impl Config {
     /// Authenticate operator with password
     pub fn authenticate_operator(&self, nickname: &str, password: &str, user: &str, host: &str) -> Option<&OperatorConfig> {
         if let Some(operator) = self.find_operator_by_nickname(nickname) {
-            if operator.verify_password(password) && operator.matches_hostmask(user, host) {
+            if operator.verify_credentials(Some(password), None, None) && operator.matches_hostmask(user, host) {
                 return Some(operator);
             }
         }
@@ -1402,6 +2432,18 @@ impl Config {
         self.classes.iter().find(|class| class.name == name)
     }
 
+    /// Connection class for a connecting host
+    ///
+    /// The class of the first matching allow block, else the class named
+    /// "default", else the built-in defaults.
+    pub fn connection_class(&self, host: &str, ip: &str) -> ConnectionClass {
+        self.find_allow_block(host, ip)
+            .and_then(|block| self.get_class(&block.class))
+            .or_else(|| self.get_class("default"))
+            .cloned()
+            .unwrap_or_default()
+    }
+
     /// Get the bind address for a specific port
     pub fn get_bind_address_for_port(&self, port_config: &PortConfig) -> String {
         port_config.bind_address.as_ref()
@@ -1409,6 +2451,14 @@ impl Config {
             .clone()
     }
 
+    /// Every address a port listens on: its bind address and, on dual-stack
+    /// ports, its IPv6 bind address
+    pub fn get_bind_addresses_for_port(&self, port_config: &PortConfig) -> Vec<String> {
+        std::iter::once(self.get_bind_address_for_port(port_config))
+            .chain(port_config.bind_address_v6.clone())
+            .collect()
+    }
+
     /// Find allow block that matches a host or IP
     pub fn find_allow_block(&self, host: &str, ip: &str) -> Option<&AllowBlock> {
         for allow_block in &self.security.allow_blocks {
@@ -1812,6 +2862,31 @@ mod tests {
         assert!(!PasswordHasher::is_sha256_hash(invalid_hash));
     }
 
+    #[test]
+    fn test_operator_credentials() {
+        let certfp = "AB:".repeat(31) + "AB";
+        let mut operator = OperatorConfig::new("admin".to_string(), "secret", "*@*".to_string(), vec![]);
+        assert!(operator.verify_credentials(Some("secret"), None, None));
+        assert!(!operator.verify_credentials(None, None, None));
+
+        // Every configured factor has to match
+        operator.account = Some("Alice".to_string());
+        assert!(!operator.verify_credentials(Some("secret"), None, None));
+        assert!(operator.verify_credentials(Some("secret"), Some("alice"), None));
+
+        operator.password_hash.clear();
+        operator.certfp = Some(certfp.clone());
+        assert!(!operator.requires_password());
+        assert!(!operator.verify_credentials(None, Some("alice"), None));
+        assert!(operator.verify_credentials(None, Some("alice"), Some(&certfp.to_lowercase().replace(':', ""))));
+        assert!(!operator.verify_credentials(None, Some("bob"), Some(&certfp)));
+
+        // A block with nothing to check matches nobody
+        operator.account = None;
+        operator.certfp = None;
+        assert!(!operator.verify_credentials(Some(""), None, None));
+    }
+
     #[test]
     fn test_argon2_unique_salts() {
         let password = "same_password";
@@ -1825,4 +2900,60 @@ mod tests {
         assert!(PasswordHasher::verify_password(password, &hash1));
         assert!(PasswordHasher::verify_password(password, &hash2));
     }
+
+    fn write_config(dir: &Path, name: &str, content: &str) {
+        let path = dir.join(name);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, content).unwrap();
+    }
+
+    fn operator_block(nickname: &str) -> String {
+        format!("[[network.operators]]\nnickname = \"{}\"\npassword_hash = \"$argon2id$v=19$m=19456,t=2,p=1$x\"\nhostmask = \"*@*\"\nflags = [\"GlobalOper\"]\nenabled = true\n", nickname)
+    }
+
+    #[test]
+    fn test_config_includes() {
+        let dir = tempfile::tempdir().unwrap();
+        let base = Config::default();
+        let default_operators = base.network.operators.len();
+        write_config(dir.path(), "base.toml", &toml::to_string(&base).unwrap());
+        write_config(dir.path(), "opers/a.toml", &operator_block("alice"));
+        write_config(dir.path(), "opers/b.toml", &operator_block("bob"));
+        write_config(dir.path(), "opers/notes.txt", "not toml");
+        write_config(dir.path(), "ircd.toml", "include = [\"base.toml\", \"opers/*.toml\"]\n\n[server]\nname = \"irc.example.net\"\n");
+
+        let config = Config::from_file(dir.path().join("ircd.toml")).unwrap();
+        assert_eq!(config.server.name, "irc.example.net");
+        assert_eq!(config.server.description, base.server.description);
+        let operators: Vec<&str> = config.network.operators.iter().skip(default_operators).map(|o| o.nickname.as_str()).collect();
+        assert_eq!(operators, ["alice", "bob"]);
+
+        write_config(dir.path(), "ircd.toml", "include = [\"missing.toml\"]\n");
+        assert!(Config::from_file(dir.path().join("ircd.toml")).unwrap_err().to_string().contains("not found"));
+    }
+
+    #[test]
+    fn test_config_include_cycle() {
+        let dir = tempfile::tempdir().unwrap();
+        write_config(dir.path(), "a.toml", "include = [\"b.toml\"]\n");
+        write_config(dir.path(), "b.toml", "include = [\"a.toml\"]\n");
+        let error = Config::from_file(dir.path().join("a.toml")).unwrap_err().to_string();
+        assert!(error.contains("include cycle"), "{}", error);
+    }
+
+    #[test]
+    fn test_config_env_interpolation() {
+        std::env::set_var("RUSTIRCD_TEST_LINK_PASSWORD", "s3cret");
+        assert_eq!(expand_env("${RUSTIRCD_TEST_LINK_PASSWORD}").unwrap(), "s3cret");
+        assert_eq!(expand_env("pw-${RUSTIRCD_TEST_UNSET:-fallback}!").unwrap(), "pw-fallback!");
+        assert_eq!(expand_env("$${RUSTIRCD_TEST_LINK_PASSWORD}").unwrap(), "${RUSTIRCD_TEST_LINK_PASSWORD}");
+        assert!(expand_env("${RUSTIRCD_TEST_UNSET}").unwrap_err().contains("RUSTIRCD_TEST_UNSET"));
+        assert!(expand_env("${RUSTIRCD_TEST_LINK_PASSWORD").is_err());
+
+        let dir = tempfile::tempdir().unwrap();
+        let mut content = toml::to_string(&Config::default()).unwrap();
+        content = content.replacen("name = \"localhost\"", "name = \"${RUSTIRCD_TEST_UNSET:-irc.env.test}\"", 1);
+        write_config(dir.path(), "ircd.toml", &content);
+        assert_eq!(Config::from_file(dir.path().join("ircd.toml")).unwrap().server.name, "irc.env.test");
+    }
 }
@@ -1,44 +1,118 @@
 //! Connection handling and management
 
-use crate::{Client, Message, Error, Result, LookupService};
+use crate::config::ConnectionClass;
+use crate::message::{MAX_LINE_LENGTH, MAX_TAGS_LENGTH};
+use crate::{Client, ClientSenders, Message, Error, NumericReplies, NumericReply, Result};
+use std::collections::VecDeque;
+use std::io::IoSlice;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
 use std::net::SocketAddr;
 use tokio::{
-    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    io::{AsyncBufRead, AsyncBufReadExt, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader},
     net::TcpStream,
-    sync::mpsc,
+    sync::{mpsc, oneshot, Notify},
 };
 use tokio_rustls::{TlsAcceptor, TlsStream};
 use uuid::Uuid;
 
+/// Most queued lines handed to one vectored write
+const MAX_WRITE_SLICES: usize = 64;
+
+/// Most bytes combined into one write on streams without vectored writes
+const MAX_COALESCED_WRITE: usize = 16 * 1024;
+
+/// Longest line a client may send: a full tag section, its separating
+/// space and a full message
+const MAX_INPUT_LINE: usize = MAX_TAGS_LENGTH + 1 + MAX_LINE_LENGTH;
+
+/// A line read from a client, with the charge it holds against the
+/// client's recvq until the server is done with it and the `irc.command`
+/// span its handling runs in
+pub type ReceivedMessage = (Uuid, Message, RecvqCharge, tracing::Span);
+
 /// Connection handler for managing client connections
 pub struct ConnectionHandler {
     /// Client ID to client mapping
     clients: std::collections::HashMap<Uuid, Client>,
     /// Nickname to client ID mapping
     nick_to_id: std::collections::HashMap<String, Uuid>,
-    /// Message receiver for incoming messages
-    #[allow(dead_code)]
-    message_receiver: mpsc::UnboundedReceiver<(Uuid, Message)>,
+    /// Message receiver for incoming messages, until the server takes it
+    message_receiver: Option<mpsc::UnboundedReceiver<ReceivedMessage>>,
     /// Message sender for outgoing messages
-    message_sender: mpsc::UnboundedSender<(Uuid, Message)>,
+    message_sender: mpsc::UnboundedSender<ReceivedMessage>,
+    /// Connections whose socket closed, with the reason, until the server takes it
+    closed_receiver: Option<mpsc::UnboundedReceiver<(Uuid, String)>>,
+    /// Sender handed to every connection task for reporting its close
+    closed_sender: mpsc::UnboundedSender<(Uuid, String)>,
+    /// Outbound queues of the clients above, shared with the server
+    senders: Arc<ClientSenders>,
+    /// Numeric reply templates applied by every connection's writer
+    replies: Arc<NumericReplies>,
 }
 
 impl ConnectionHandler {
     /// Create a new connection handler
-    pub fn new() -> (Self, mpsc::UnboundedSender<(Uuid, Message)>) {
+    pub fn new() -> (Self, mpsc::UnboundedSender<ReceivedMessage>) {
         let (message_sender, message_receiver) = mpsc::unbounded_channel();
+        let (closed_sender, closed_receiver) = mpsc::unbounded_channel();
         
         let handler = Self {
             clients: std::collections::HashMap::new(),
             nick_to_id: std::collections::HashMap::new(),
-            message_receiver,
+            message_receiver: Some(message_receiver),
             message_sender: message_sender.clone(),
+            closed_receiver: Some(closed_receiver),
+            closed_sender,
+            senders: Arc::new(ClientSenders::new()),
+            replies: Arc::new(NumericReplies::default()),
         };
         
         (handler, message_sender)
     }
     
-    /// Handle a new connection with type information
+    /// Outbound queues of every client, usable without this handler's lock
+    pub fn senders(&self) -> Arc<ClientSenders> {
+        self.senders.clone()
+    }
+    
+    /// Numeric reply templates applied to every client's output
+    pub fn replies(&self) -> Arc<NumericReplies> {
+        self.replies.clone()
+    }
+    
+    /// Copy activity noted in the shared senders onto each client's timing
+    pub fn sync_activity(&mut self) {
+        for (client_id, client) in self.clients.iter_mut() {
+            if let Some(last) = self.senders.last_activity(client_id) {
+                client.timing.last_activity = client.timing.last_activity.max(last);
+            }
+        }
+    }
+    
+    /// Take the receiving end of the messages read from every connection
+    ///
+    /// Returns `None` once something has already taken it. Drop each
+    /// message's `RecvqCharge` once the message has been handled; until then
+    /// it counts against the client's recvq.
+    pub fn take_message_receiver(&mut self) -> Option<mpsc::UnboundedReceiver<ReceivedMessage>> {
+        self.message_receiver.take()
+    }
+    
+    /// Take the receiving end of the close notifications
+    ///
+    /// Each connection reports its client ID and a reason once its socket
+    /// closes, after everything it read has gone to the message receiver.
+    pub fn take_closed_receiver(&mut self) -> Option<mpsc::UnboundedReceiver<(Uuid, String)>> {
+        self.closed_receiver.take()
+    }
+    
+    /// Handle a new connection with type information, returning its client ID
+    ///
+    /// `class` sets the connection's sendq and recvq limits and timing;
+    /// without one the defaults apply. The hostname and ident lookups are
+    /// left to the caller (see [`crate::LookupService::lookup_client`]), so
+    /// they don't hold up accepting the next connection.
     pub async fn handle_connection_with_type(
         &mut self,
         stream: TcpStream,
@@ -46,8 +120,8 @@ impl ConnectionHandler {
         tls_acceptor: Option<TlsAcceptor>,
         is_client_connection: bool,
         is_server_connection: bool,
-        lookup_service: Option<&LookupService>,
-    ) -> Result<()> {
+        class: Option<&ConnectionClass>,
+    ) -> Result<Uuid> {
         // Check throttling for client connections
         if is_client_connection && !is_server_connection {
             // TODO: Integrate with throttling module
@@ -58,44 +132,8 @@ impl ConnectionHandler {
         let local_addr = stream.local_addr()?;
         let client_id = Uuid::new_v4();
         
-        // Perform DNS and ident lookups for client connections
-        let (hostname, ident_username) = if is_client_connection && !is_server_connection {
-            if let Some(lookup) = lookup_service {
-                // Perform DNS reverse lookup
-                let dns_result = lookup.reverse_dns_lookup(remote_addr.ip()).await;
-                let hostname = if dns_result.success {
-                    dns_result.hostname
-                } else {
-                    tracing::debug!("DNS lookup failed for {}: {:?}", remote_addr, dns_result.error);
-                    None
-                };
-                
-                // Perform ident lookup
-                let ident_result = lookup.ident_lookup(remote_addr, local_addr).await;
-                let ident_username = if ident_result.success {
-                    ident_result.username
-                } else {
-                    tracing::debug!("Ident lookup failed for {}: {:?}", remote_addr, ident_result.error);
-                    None
-                };
-                
-                (hostname, ident_username)
-            } else {
-                (None, None)
-            }
-        } else {
-            (None, None)
-        };
-        
-        // Log connection with lookup results
         if is_client_connection && !is_server_connection {
-            if let (Some(host), Some(user)) = (&hostname, &ident_username) {
-                tracing::info!("Client connection from {} ({}) with ident user: {}", remote_addr, host, user);
-            } else if let Some(host) = &hostname {
-                tracing::info!("Client connection from {} ({})", remote_addr, host);
-            } else {
-                tracing::info!("Client connection from {}", remote_addr);
-            }
+            tracing::info!(%client_id, addr = %remote_addr, "Client connected");
         }
         
         // Create message channel for this client
@@ -109,13 +147,22 @@ impl ConnectionHandler {
         };
         
         // Create client
-        let client = Client::new_with_type(
+        let reply_sender = client_sender.clone();
+        self.senders.insert(client_id, client_sender.clone());
+        let mut client = Client::new_with_type(
             client_id,
             remote_addr.to_string(),
             local_addr.to_string(),
             client_sender,
             connection_type,
         );
+        client.encrypted = tls_acceptor.is_some();
+        client.port = local_addr.port();
+        if let Some(class) = class {
+            client.apply_class(class);
+        }
+        let max_sendq = client.sendq.max_size();
+        let max_recvq = client.recvq.max_size();
         
         // Store client
         self.clients.insert(client_id, client);
@@ -125,6 +172,14 @@ impl ConnectionHandler {
             tracing::debug!("Upgrading connection to TLS for client {}", client_id);
             let tls_stream = acceptor.accept(stream).await
                 .map_err(|e| Error::Connection(format!("TLS handshake failed: {}", e)))?;
+            // Ports with request_client_cert may have been given one
+            let certfp = tls_stream.get_ref().1.peer_certificates()
+                .and_then(|certificates| certificates.first())
+                .map(|certificate| crate::link_tls::fingerprint(&certificate.0));
+            if let (Some(certfp), Some(client)) = (certfp, self.clients.get_mut(&client_id)) {
+                tracing::debug!(%client_id, %certfp, "Client presented a certificate");
+                client.certfp = Some(certfp);
+            }
             Box::new(tls_stream) as Box<dyn ConnectionStream>
         } else {
             Box::new(stream) as Box<dyn ConnectionStream>
@@ -133,19 +188,30 @@ impl ConnectionHandler {
         // Spawn connection handler
         let client_id = client_id;
         let message_sender = self.message_sender.clone();
+        let closed_sender = self.closed_sender.clone();
+        let replies = self.replies.clone();
         
         tokio::spawn(async move {
-            if let Err(e) = Self::handle_client_connection(
+            let reason = match Self::handle_client_connection(
                 client_id,
                 stream,
                 client_receiver,
+                replies,
+                max_sendq,
+                max_recvq,
+                reply_sender,
                 message_sender,
             ).await {
-                tracing::error!("Error handling client connection: {}", e);
-            }
+                Ok(reason) => reason,
+                Err(e) => {
+                    tracing::error!("Error handling client connection: {}", e);
+                    e.to_string()
+                }
+            };
+            let _ = closed_sender.send((client_id, reason));
         });
         
-        Ok(())
+        Ok(client_id)
     }
     
     /// Handle a new client connection (legacy method for backward compatibility)
@@ -155,52 +221,92 @@ impl ConnectionHandler {
         remote_addr: SocketAddr,
         tls_acceptor: Option<TlsAcceptor>,
     ) -> Result<()> {
-        self.handle_connection_with_type(stream, remote_addr, tls_acceptor, true, false, None).await
+        self.handle_connection_with_type(stream, remote_addr, tls_acceptor, true, false, None).await.map(|_| ())
     }
     
     /// Handle individual client connection
+    ///
+    /// Returns why the connection ended once the socket stops being readable,
+    /// or once the writer gives up because the client isn't keeping up with
+    /// its output. Lines over the length limits are answered with 417 on
+    /// `reply_sender`.
+    ///
+    /// Reading pauses while more than `max_recvq` bytes the client sent are
+    /// waiting for the server, so a flooding client is held back by TCP
+    /// instead of buffered here. A client that sends more than a line's
+    /// worth without a line ending is dropped with "Excess Flood".
+    #[allow(clippy::too_many_arguments)]
     async fn handle_client_connection(
         client_id: Uuid,
         stream: Box<dyn ConnectionStream>,
-        mut client_receiver: mpsc::UnboundedReceiver<Message>,
-        message_sender: mpsc::UnboundedSender<(Uuid, Message)>,
-    ) -> Result<()> {
-        let (read_half, mut write_half) = stream.split();
+        client_receiver: mpsc::UnboundedReceiver<Message>,
+        replies: Arc<NumericReplies>,
+        max_sendq: usize,
+        max_recvq: usize,
+        reply_sender: mpsc::UnboundedSender<Message>,
+        message_sender: mpsc::UnboundedSender<ReceivedMessage>,
+    ) -> Result<String> {
+        let (read_half, write_half) = stream.split();
         let mut reader = BufReader::new(read_half);
-        let mut line = String::new();
+        let mut line = Vec::new();
+        let recvq = Arc::new(Recvq::default());
         
         // Send messages to client
-        let _message_sender_clone = message_sender.clone();
+        let (writer_closed_sender, mut writer_closed) = oneshot::channel();
         tokio::spawn(async move {
-            while let Some(message) = client_receiver.recv().await {
-                if let Err(e) = write_half.write_all(message.to_string().as_bytes()).await {
-                    tracing::error!("Error writing to client {}: {}", client_id, e);
-                    break;
-                }
+            if let Some(reason) = Self::write_client_output(client_id, write_half, client_receiver, &replies, max_sendq).await {
+                let _ = writer_closed_sender.send(reason);
             }
         });
+        let mut writer_open = true;
         
         // Read messages from client
         loop {
             line.clear();
-            match reader.read_line(&mut line).await {
-                Ok(0) => {
+            let read = tokio::select! {
+                read = async {
+                    recvq.wait_below(max_recvq).await;
+                    read_line_bounded(&mut reader, &mut line, MAX_INPUT_LINE.max(max_recvq)).await
+                } => read,
+                reason = &mut writer_closed, if writer_open => match reason {
+                    Ok(reason) => return Ok(reason),
+                    Err(_) => {
+                        writer_open = false;
+                        continue;
+                    }
+                },
+            };
+            match read {
+                Ok(LineRead::Closed) => {
                     // Connection closed
-                    break;
+                    return Ok("Connection closed".to_string());
                 }
-                Ok(_) => {
-                    let line = line.trim();
-                    if line.is_empty() {
+                Ok(LineRead::Overflow) => {
+                    tracing::warn!("Client {} sent more than a line without a line ending", client_id);
+                    return Ok("Excess Flood".to_string());
+                }
+                Ok(LineRead::Line) => {
+                    let text = String::from_utf8_lossy(&line);
+                    let text = text.trim_end_matches(['\r', '\n']);
+                    if text.trim().is_empty() {
                         continue;
                     }
                     
-                    match Message::parse(line) {
+                    // The span follows the message to the processor and covers its handling
+                    let span = tracing::debug_span!("irc.command", %client_id, command = tracing::field::Empty);
+                    match span.in_scope(|| tracing::debug_span!("irc.parse").in_scope(|| Message::parse(text))) {
                         Ok(message) => {
-                            if let Err(e) = message_sender.send((client_id, message)) {
+                            tracing::trace!("Received from client {}: {}", client_id, message.redacted());
+                            span.record("command", tracing::field::display(&message.command));
+                            let charge = RecvqCharge::new(recvq.clone(), line.len());
+                            if let Err(e) = message_sender.send((client_id, message, charge, span)) {
                                 tracing::error!("Error sending message: {}", e);
-                                break;
+                                return Ok("Server shutting down".to_string());
                             }
                         }
+                        Err(Error::InputTooLong) => {
+                            let _ = reply_sender.send(NumericReply::input_too_long());
+                        }
                         Err(e) => {
                             tracing::warn!("Error parsing message from client {}: {}", client_id, e);
                         }
@@ -208,12 +314,73 @@ impl ConnectionHandler {
                 }
                 Err(e) => {
                     tracing::error!("Error reading from client {}: {}", client_id, e);
-                    break;
+                    return Ok(format!("Read error: {}", e));
                 }
             }
         }
-        
-        Ok(())
+    }
+    
+    /// Write a client's queued lines to its socket
+    ///
+    /// Numeric replies get their configured templates applied on the way.
+    /// Lines queued while a write is in progress are gathered and sent
+    /// together, with one vectored write where the stream supports it, so a
+    /// busy client costs a system call per wakeup rather than per line.
+    /// Returns why the connection must close when the unwritten output
+    /// outgrows `max_sendq` bytes or writing fails, and `None` once the
+    /// queue closes and everything in it has been written.
+    async fn write_client_output<W: AsyncWrite + Unpin>(
+        client_id: Uuid,
+        mut write_half: W,
+        mut client_receiver: mpsc::UnboundedReceiver<Message>,
+        replies: &NumericReplies,
+        max_sendq: usize,
+    ) -> Option<String> {
+        let mut pending = OutputBuffer::default();
+        let mut open = true;
+        loop {
+            if pending.is_empty() {
+                let message = client_receiver.recv().await?;
+                pending.push(client_id, replies.localize(message));
+            }
+            while let Ok(message) = client_receiver.try_recv() {
+                pending.push(client_id, replies.localize(message));
+            }
+            if pending.len() > max_sendq {
+                tracing::warn!("Client {} exceeded its sendq ({} > {} bytes)", client_id, pending.len(), max_sendq);
+                return Some("Max SendQ exceeded".to_string());
+            }
+            
+            // Keep taking lines while the socket is busy, so the sendq
+            // limit sees them
+            let event = if write_half.is_write_vectored() {
+                let slices = pending.slices();
+                tokio::select! {
+                    written = write_half.write_vectored(&slices) => WriterEvent::Written(written),
+                    message = client_receiver.recv(), if open => WriterEvent::Queued(message),
+                }
+            } else {
+                let chunk = pending.chunk(MAX_COALESCED_WRITE);
+                tokio::select! {
+                    written = write_half.write(&chunk) => WriterEvent::Written(written),
+                    message = client_receiver.recv(), if open => WriterEvent::Queued(message),
+                }
+            };
+            match event {
+                WriterEvent::Written(Ok(0)) => return Some("Write error: connection closed".to_string()),
+                WriterEvent::Written(Ok(written)) => pending.consume(written),
+                WriterEvent::Written(Err(e)) => {
+                    tracing::error!("Error writing to client {}: {}", client_id, e);
+                    return Some(format!("Write error: {}", e));
+                }
+                WriterEvent::Queued(Some(message)) => pending.push(client_id, replies.localize(message)),
+                WriterEvent::Queued(None) => open = false,
+            }
+            if !open && pending.is_empty() {
+                let _ = write_half.flush().await;
+                return None;
+            }
+        }
     }
     
     /// Get client by ID
@@ -233,6 +400,7 @@ impl ConnectionHandler {
     
     /// Remove a client by ID
     pub fn remove_client(&mut self, id: &Uuid) -> Option<Client> {
+        self.senders.remove(id);
         self.clients.remove(id)
     }
     
@@ -303,6 +471,138 @@ pub trait ConnectionStream: Send + Sync {
     fn split(self: Box<Self>) -> (Box<dyn ConnectionReadHalf>, Box<dyn ConnectionWriteHalf>);
 }
 
+/// Outcome of reading one line from a client
+#[derive(Debug, PartialEq, Eq)]
+enum LineRead {
+    /// A line, or the data before the end of the stream
+    Line,
+    /// The stream ended
+    Closed,
+    /// `limit` bytes arrived without a line ending
+    Overflow,
+}
+
+/// Read up to and including the next `\n` into `line`, but no more than
+/// `limit` bytes
+async fn read_line_bounded<R: AsyncBufRead + Unpin>(reader: &mut R, line: &mut Vec<u8>, limit: usize) -> std::io::Result<LineRead> {
+    let read = reader.take(limit as u64).read_until(b'\n', line).await?;
+    if read == 0 {
+        Ok(LineRead::Closed)
+    } else if read == limit && !line.ends_with(b"\n") {
+        Ok(LineRead::Overflow)
+    } else {
+        Ok(LineRead::Line)
+    }
+}
+
+/// Bytes a client sent that the server hasn't handled yet
+#[derive(Debug, Default)]
+struct Recvq {
+    unprocessed: AtomicUsize,
+    drained: Notify,
+}
+
+impl Recvq {
+    /// Wait until no more than `limit` bytes are unprocessed
+    async fn wait_below(&self, limit: usize) {
+        while self.unprocessed.load(Ordering::Acquire) > limit {
+            self.drained.notified().await;
+        }
+    }
+}
+
+/// A received line's share of its client's recvq, released on drop
+#[derive(Debug)]
+pub struct RecvqCharge {
+    recvq: Arc<Recvq>,
+    bytes: usize,
+}
+
+impl RecvqCharge {
+    fn new(recvq: Arc<Recvq>, bytes: usize) -> Self {
+        recvq.unprocessed.fetch_add(bytes, Ordering::AcqRel);
+        Self { recvq, bytes }
+    }
+}
+
+impl Drop for RecvqCharge {
+    fn drop(&mut self) {
+        self.recvq.unprocessed.fetch_sub(self.bytes, Ordering::AcqRel);
+        self.recvq.drained.notify_one();
+    }
+}
+
+/// What woke a client's writer
+enum WriterEvent {
+    /// A write finished
+    Written(std::io::Result<usize>),
+    /// A line was queued, or the queue closed
+    Queued(Option<Message>),
+}
+
+/// Serialized lines waiting to be written to a client
+#[derive(Debug, Default)]
+struct OutputBuffer {
+    lines: VecDeque<Vec<u8>>,
+    /// Bytes of the first line already written
+    offset: usize,
+    /// Bytes not yet written
+    unwritten: usize,
+}
+
+impl OutputBuffer {
+    fn push(&mut self, client_id: Uuid, message: Message) {
+        tracing::trace!("Sending to client {}: {}", client_id, message.redacted());
+        let line = message.to_string().into_bytes();
+        self.unwritten += line.len();
+        self.lines.push_back(line);
+    }
+    
+    fn is_empty(&self) -> bool {
+        self.unwritten == 0
+    }
+    
+    fn len(&self) -> usize {
+        self.unwritten
+    }
+    
+    /// The unwritten bytes as slices for a vectored write
+    fn slices(&self) -> Vec<IoSlice<'_>> {
+        self.lines.iter().enumerate()
+            .take(MAX_WRITE_SLICES)
+            .map(|(i, line)| IoSlice::new(if i == 0 { &line[self.offset..] } else { line }))
+            .collect()
+    }
+    
+    /// Up to `limit` unwritten bytes copied into one buffer, at least a line
+    fn chunk(&self, limit: usize) -> Vec<u8> {
+        let mut chunk = Vec::new();
+        for (i, line) in self.lines.iter().enumerate() {
+            let line = if i == 0 { &line[self.offset..] } else { line };
+            if !chunk.is_empty() && chunk.len() + line.len() > limit {
+                break;
+            }
+            chunk.extend_from_slice(line);
+        }
+        chunk
+    }
+    
+    /// Drop `written` bytes from the front
+    fn consume(&mut self, mut written: usize) {
+        self.unwritten = self.unwritten.saturating_sub(written);
+        while let Some(line) = self.lines.front() {
+            let remaining = line.len() - self.offset;
+            if written < remaining {
+                self.offset += written;
+                return;
+            }
+            written -= remaining;
+            self.lines.pop_front();
+            self.offset = 0;
+        }
+    }
+}
+
 /// Trait for connection read half
 pub trait ConnectionReadHalf: Send + Sync + tokio::io::AsyncRead + Unpin {
     // This will be implemented by tokio's AsyncRead
@@ -355,3 +655,97 @@ impl ConnectionStream for tokio_rustls::server::TlsStream<tokio::net::TcpStream>
 
 impl ConnectionReadHalf for tokio::io::ReadHalf<tokio_rustls::server::TlsStream<tokio::net::TcpStream>> {}
 impl ConnectionWriteHalf for tokio::io::WriteHalf<tokio_rustls::server::TlsStream<tokio::net::TcpStream>> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MessageType;
+    use tokio::io::AsyncReadExt;
+
+    fn line(text: &str) -> Message {
+        Message::new(MessageType::Notice, vec!["alice".to_string(), text.to_string()])
+    }
+
+    #[test]
+    fn test_output_buffer_partial_writes() {
+        let client_id = Uuid::new_v4();
+        let mut pending = OutputBuffer::default();
+        pending.push(client_id, line("one"));
+        pending.push(client_id, line("two"));
+        let total = pending.len();
+        assert_eq!(pending.slices().len(), 2);
+
+        // A write that stops inside the first line resumes from there
+        pending.consume(7);
+        assert_eq!(pending.len(), total - 7);
+        assert_eq!(pending.chunk(usize::MAX), b"alice one\r\nNOTICE alice two\r\n"[..].to_vec());
+        assert_eq!(pending.chunk(1), b"alice one\r\n"[..].to_vec());
+        pending.consume(pending.len());
+        assert!(pending.is_empty());
+        assert!(pending.slices().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_bounded_line_reads() {
+        let mut reader = BufReader::new(&b"PING :a\r\nNICKNAMEWITHOUTEND"[..]);
+        let mut line = Vec::new();
+        assert_eq!(read_line_bounded(&mut reader, &mut line, 16).await.unwrap(), LineRead::Line);
+        assert_eq!(line, b"PING :a\r\n");
+        line.clear();
+        assert_eq!(read_line_bounded(&mut reader, &mut line, 16).await.unwrap(), LineRead::Overflow);
+        assert_eq!(line.len(), 16);
+        line.clear();
+        assert_eq!(read_line_bounded(&mut reader, &mut line, 16).await.unwrap(), LineRead::Line);
+        line.clear();
+        assert_eq!(read_line_bounded(&mut reader, &mut line, 16).await.unwrap(), LineRead::Closed);
+    }
+
+    #[tokio::test]
+    async fn test_recvq_pauses_until_processed() {
+        let recvq = Arc::new(Recvq::default());
+        let first = RecvqCharge::new(recvq.clone(), 300);
+        let second = RecvqCharge::new(recvq.clone(), 300);
+        recvq.wait_below(1000).await;
+
+        // Over the limit, reading waits for the server to catch up
+        let waiting = tokio::spawn({
+            let recvq = recvq.clone();
+            async move { recvq.wait_below(500).await }
+        });
+        tokio::task::yield_now().await;
+        assert!(!waiting.is_finished());
+        drop(first);
+        tokio::time::timeout(std::time::Duration::from_secs(1), waiting).await.unwrap().unwrap();
+        drop(second);
+        assert_eq!(recvq.unprocessed.load(Ordering::Acquire), 0);
+    }
+
+    #[tokio::test]
+    async fn test_writer_coalesces_and_enforces_sendq() {
+        let (tx, rx) = mpsc::unbounded_channel();
+        for text in ["one", "two", "three"] {
+            tx.send(line(text)).unwrap();
+        }
+        drop(tx);
+        let (mut client_end, server_end) = tokio::io::duplex(4096);
+        let reason = ConnectionHandler::write_client_output(Uuid::new_v4(), server_end, rx, &NumericReplies::default(), 4096).await;
+        assert!(reason.is_none());
+        let mut written = String::new();
+        client_end.read_to_string(&mut written).await.unwrap();
+        assert_eq!(written, "NOTICE alice one\r\nNOTICE alice two\r\nNOTICE alice three\r\n");
+
+        // A client that stops reading is dropped once its output passes the sendq
+        let (tx, rx) = mpsc::unbounded_channel();
+        let (_client_end, server_end) = tokio::io::duplex(64);
+        let writer = tokio::spawn(async move {
+            ConnectionHandler::write_client_output(Uuid::new_v4(), server_end, rx, &NumericReplies::default(), 256).await
+        });
+        for _ in 0..40 {
+            if tx.send(line("flood")).is_err() {
+                break;
+            }
+            tokio::task::yield_now().await;
+        }
+        assert_eq!(writer.await.unwrap().as_deref(), Some("Max SendQ exceeded"));
+    }
+}
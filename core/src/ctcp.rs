@@ -0,0 +1,211 @@
+//! CTCP (Client-To-Client Protocol) handling
+//!
+//! CTCP requests travel inside PRIVMSG and NOTICE text delimited by `\x01`,
+//! e.g. `\x01VERSION\x01` or `\x01ACTION waves\x01`. This module parses them,
+//! builds the server's own replies and rate-limits CTCP traffic per client.
+
+use crate::config::CtcpConfig;
+use parking_lot::Mutex;
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+/// CTCP delimiter
+pub const CTCP_DELIM: char = '\x01';
+
+/// A parsed CTCP request or reply
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Ctcp {
+    /// CTCP command, uppercased (e.g. "VERSION", "ACTION")
+    pub command: String,
+    /// Everything after the command, if anything
+    pub params: Option<String>,
+}
+
+impl Ctcp {
+    /// Create a CTCP message
+    pub fn new(command: &str, params: Option<&str>) -> Self {
+        Self {
+            command: command.to_uppercase(),
+            params: params.map(str::to_string),
+        }
+    }
+
+    /// Parse PRIVMSG/NOTICE text as CTCP
+    ///
+    /// The closing delimiter is optional since many clients omit it.
+    pub fn parse(text: &str) -> Option<Self> {
+        let body = text.strip_prefix(CTCP_DELIM)?;
+        let body = body.strip_suffix(CTCP_DELIM).unwrap_or(body);
+        let (command, params) = match body.split_once(' ') {
+            Some((command, params)) => (command, Some(params)),
+            None => (body, None),
+        };
+        if command.is_empty() {
+            return None;
+        }
+        Some(Self::new(command, params))
+    }
+
+    /// Check whether text is a CTCP message
+    pub fn is_ctcp(text: &str) -> bool {
+        Self::parse(text).is_some()
+    }
+
+    /// ACTION (/me) is ordinary chat and is exempt from CTCP restrictions
+    pub fn is_action(&self) -> bool {
+        self.command == "ACTION"
+    }
+
+    /// Encode as PRIVMSG/NOTICE text
+    pub fn to_text(&self) -> String {
+        match &self.params {
+            Some(params) => format!("{}{} {}{}", CTCP_DELIM, self.command, params, CTCP_DELIM),
+            None => format!("{}{}{}", CTCP_DELIM, self.command, CTCP_DELIM),
+        }
+    }
+
+    /// Reply the server sends when a CTCP request targets it directly
+    ///
+    /// Returns `None` for requests the server doesn't answer.
+    pub fn server_reply(&self, config: &CtcpConfig, server_version: &str) -> Option<Ctcp> {
+        if !config.server_replies {
+            return None;
+        }
+        match self.command.as_str() {
+            "VERSION" => {
+                let version = if config.version_reply.is_empty() {
+                    format!("rustircd-{}", server_version)
+                } else {
+                    config.version_reply.clone()
+                };
+                Some(Ctcp::new("VERSION", Some(&version)))
+            }
+            "PING" if config.reply_to_ping => Some(Ctcp::new("PING", self.params.as_deref())),
+            "TIME" if config.reply_to_time => {
+                let now = chrono::Local::now().format("%a %b %d %H:%M:%S %Y").to_string();
+                Some(Ctcp::new("TIME", Some(&now)))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Per-client CTCP rate limiter
+///
+/// Tracks a sliding window of CTCP timestamps per client; requests beyond
+/// `max_ctcps` within `time_window_seconds` are refused.
+#[derive(Debug)]
+pub struct CtcpFloodControl {
+    max_ctcps: usize,
+    window: Duration,
+    history: Mutex<HashMap<Uuid, VecDeque<Instant>>>,
+}
+
+impl CtcpFloodControl {
+    /// Create a rate limiter from configuration
+    pub fn new(config: &CtcpConfig) -> Self {
+        Self {
+            max_ctcps: config.max_ctcps,
+            window: Duration::from_secs(config.time_window_seconds),
+            history: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Record a CTCP from a client, returning `false` if it exceeds the limit
+    pub fn check(&self, client_id: Uuid) -> bool {
+        self.check_at(client_id, Instant::now())
+    }
+
+    /// Record a parsed CTCP from a client, returning `false` if it exceeds the limit
+    ///
+    /// ACTION is ordinary chat, so it is neither counted nor refused.
+    pub fn check_ctcp(&self, client_id: Uuid, ctcp: &Ctcp) -> bool {
+        ctcp.is_action() || self.check(client_id)
+    }
+
+    fn check_at(&self, client_id: Uuid, now: Instant) -> bool {
+        if self.max_ctcps == 0 {
+            return true;
+        }
+
+        let mut history = self.history.lock();
+        let times = history.entry(client_id).or_default();
+        while times.front().is_some_and(|t| now.duration_since(*t) >= self.window) {
+            times.pop_front();
+        }
+
+        if times.len() >= self.max_ctcps {
+            return false;
+        }
+        times.push_back(now);
+        true
+    }
+
+    /// Forget a disconnected client
+    pub fn remove_client(&self, client_id: &Uuid) {
+        self.history.lock().remove(client_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_ctcp() {
+        let version = Ctcp::parse("\x01version\x01").unwrap();
+        assert_eq!(version, Ctcp::new("VERSION", None));
+
+        let action = Ctcp::parse("\x01ACTION waves hello").unwrap();
+        assert!(action.is_action());
+        assert_eq!(action.params.as_deref(), Some("waves hello"));
+        assert_eq!(action.to_text(), "\x01ACTION waves hello\x01");
+
+        assert!(Ctcp::parse("hello").is_none());
+        assert!(Ctcp::parse("\x01\x01").is_none());
+    }
+
+    #[test]
+    fn test_server_replies() {
+        let config = CtcpConfig::default();
+        let version = Ctcp::new("VERSION", None).server_reply(&config, "1.0.0").unwrap();
+        assert_eq!(version.params.as_deref(), Some("rustircd-1.0.0"));
+
+        let ping = Ctcp::new("PING", Some("12345")).server_reply(&config, "1.0.0").unwrap();
+        assert_eq!(ping.to_text(), "\x01PING 12345\x01");
+        assert!(Ctcp::new("FINGER", None).server_reply(&config, "1.0.0").is_none());
+
+        let silent = CtcpConfig { server_replies: false, ..CtcpConfig::default() };
+        assert!(Ctcp::new("VERSION", None).server_reply(&silent, "1.0.0").is_none());
+    }
+
+    #[test]
+    fn test_flood_control_window() {
+        let config = CtcpConfig { max_ctcps: 2, time_window_seconds: 10, ..CtcpConfig::default() };
+        let flood = CtcpFloodControl::new(&config);
+        let client = Uuid::new_v4();
+        let start = Instant::now();
+
+        assert!(flood.check_at(client, start));
+        assert!(flood.check_at(client, start + Duration::from_secs(1)));
+        assert!(!flood.check_at(client, start + Duration::from_secs(2)));
+        assert!(flood.check_at(Uuid::new_v4(), start + Duration::from_secs(2)));
+        assert!(flood.check_at(client, start + Duration::from_secs(11)));
+    }
+
+    #[test]
+    fn test_flood_control_ignores_action() {
+        let config = CtcpConfig { max_ctcps: 1, time_window_seconds: 10, ..CtcpConfig::default() };
+        let flood = CtcpFloodControl::new(&config);
+        let client = Uuid::new_v4();
+        let action = Ctcp::new("ACTION", Some("waves"));
+
+        for _ in 0..10 {
+            assert!(flood.check_ctcp(client, &action));
+        }
+        assert!(flood.check_ctcp(client, &Ctcp::new("VERSION", None)));
+        assert!(!flood.check_ctcp(client, &Ctcp::new("VERSION", None)));
+        assert!(flood.check_ctcp(client, &action));
+    }
+}
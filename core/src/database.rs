@@ -1,8 +1,11 @@
 //! In-memory database for users, servers, and user history
 
 use crate::{User, Error, Result, UserLookupCache, ChannelMemberCache};
-use std::collections::{HashSet, VecDeque};
+use crate::config::MetadataVisibility;
+use std::collections::{BTreeMap, HashSet, VecDeque};
+use std::path::PathBuf;
 use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use tokio::sync::RwLock;
 use chrono::{DateTime, Utc, Duration};
 use uuid::Uuid;
@@ -37,6 +40,29 @@ pub struct ChannelInfo {
     pub modes: HashSet<char>,
 }
 
+/// Whose metadata an entry is
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum MetadataTarget {
+    /// A local or remote user
+    User(Uuid),
+    /// A channel, by lowercased name
+    Channel(String),
+}
+
+impl MetadataTarget {
+    /// The target for a channel name, in any case
+    pub fn channel(name: &str) -> Self {
+        MetadataTarget::Channel(name.to_lowercase())
+    }
+}
+
+/// A metadata value and who can see it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MetadataEntry {
+    pub value: String,
+    pub visibility: MetadataVisibility,
+}
+
 /// In-memory database for IRC daemon
 #[derive(Debug)]
 pub struct Database {
@@ -48,6 +74,8 @@ pub struct Database {
     users_by_ident: DashMap<String, Uuid>,
     /// Connected servers
     servers: DashMap<String, ServerInfo>,
+    /// Most users known at once, network-wide
+    max_users: AtomicUsize,
     /// User history for WHOWAS (FIFO with max size)
     user_history: Arc<RwLock<VecDeque<UserHistoryEntry>>>,
     /// Channels (when channel module is enabled)
@@ -60,6 +88,12 @@ pub struct Database {
     user_lookup_cache: Arc<UserLookupCache>,
     /// Cache for channel member lists (channel -> member nicknames)
     channel_member_cache: Arc<ChannelMemberCache>,
+    /// Saved state of permanent (+P) channels, opaque to the core
+    permanent_channels: DashMap<String, serde_json::Value>,
+    /// File permanent channels are saved to
+    permanent_channels_file: Option<PathBuf>,
+    /// METADATA keys of users and channels, by lowercased key
+    metadata: DashMap<MetadataTarget, BTreeMap<String, MetadataEntry>>,
     /// Configuration
     #[allow(dead_code)]
     max_history_size: usize,
@@ -91,12 +125,16 @@ impl Database {
             users_by_nick: DashMap::new(),
             users_by_ident: DashMap::new(),
             servers: DashMap::new(),
+            max_users: AtomicUsize::new(0),
             user_history: Arc::new(RwLock::new(VecDeque::new())),
             channels: DashMap::new(),
             user_channels: DashMap::new(),
             channel_members: DashMap::new(),
             user_lookup_cache: Arc::new(UserLookupCache::new(user_cache_size, user_cache_ttl)),
             channel_member_cache: Arc::new(ChannelMemberCache::new(channel_cache_ttl)),
+            permanent_channels: DashMap::new(),
+            permanent_channels_file: None,
+            metadata: DashMap::new(),
             max_history_size,
             history_retention_days,
         }
@@ -112,13 +150,32 @@ impl Database {
             config.channel_cache_ttl_seconds.unwrap_or(30)
         );
 
-        Self::new_with_cache_config(
+        let database = Self::new_with_cache_config(
             config.max_history_size,
             config.history_retention_days,
             user_cache_size,
             user_cache_ttl,
             channel_cache_ttl,
-        )
+        );
+        match &config.permanent_channels_file {
+            Some(path) => database.with_permanent_channels_file(path),
+            None => database,
+        }
+    }
+
+    /// Save permanent channels to a JSON file, loading any it already holds
+    pub fn with_permanent_channels_file(mut self, path: impl Into<PathBuf>) -> Self {
+        let path = path.into();
+        match std::fs::read_to_string(&path) {
+            Ok(contents) => match serde_json::from_str::<BTreeMap<String, serde_json::Value>>(&contents) {
+                Ok(channels) => self.permanent_channels.extend(channels),
+                Err(e) => tracing::warn!("Ignoring malformed permanent channel file {}: {}", path.display(), e),
+            },
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => tracing::warn!("Failed to read permanent channel file {}: {}", path.display(), e),
+        }
+        self.permanent_channels_file = Some(path);
+        self
     }
 
     // User management
@@ -139,9 +196,13 @@ impl Database {
             return Err(Error::User("Ident already in use".to_string()));
         }
 
+        if let Some(mut server) = self.servers.get_mut(&user.server) {
+            server.user_count += 1;
+        }
         self.users.insert(user_id, user.clone());
         self.users_by_nick.insert(nick_lower.clone(), user_id);
         self.users_by_ident.insert(ident, user_id);
+        self.max_users.fetch_max(self.users.len(), Ordering::Relaxed);
 
         // Cache the user lookup
         self.user_lookup_cache.insert(nick_lower, user_id);
@@ -157,9 +218,13 @@ impl Database {
 
             self.users_by_nick.remove(&nick_lower);
             self.users_by_ident.remove(&ident);
+            if let Some(mut server) = self.servers.get_mut(&user.server) {
+                server.user_count = server.user_count.saturating_sub(1);
+            }
 
             // Invalidate user lookup cache
             self.user_lookup_cache.remove(&nick_lower);
+            self.metadata.remove(&MetadataTarget::User(user_id));
 
             // Remove from all channels and invalidate channel member cache
             if let Some((_, channels)) = self.user_channels.remove(&user.nick) {
@@ -181,6 +246,13 @@ impl Database {
         }
     }
 
+    /// Reset a user's idle time
+    pub fn update_user_activity(&self, user_id: &Uuid) {
+        if let Some(mut user) = self.users.get_mut(user_id) {
+            user.update_activity();
+        }
+    }
+
     /// Get user by ID
     pub fn get_user(&self, user_id: &Uuid) -> Option<User> {
         self.users.get(user_id).map(|entry| entry.value().clone())
@@ -236,6 +308,18 @@ impl Database {
                 // Invalidate old nickname from cache and add new one
                 self.user_lookup_cache.remove(&old_nick_lower);
                 self.user_lookup_cache.insert(new_nick_lower, *user_id);
+
+                // Channel membership is keyed by nickname, so carry it over
+                if let Some((_, channels)) = self.user_channels.remove(&old_nick) {
+                    for channel in &channels {
+                        if let Some(mut members) = self.channel_members.get_mut(channel) {
+                            members.remove(&old_nick);
+                            members.insert(user.nick.clone());
+                        }
+                        self.channel_member_cache.invalidate(channel);
+                    }
+                    self.user_channels.insert(user.nick.clone(), channels);
+                }
             }
 
             // Update ident mapping if changed
@@ -244,7 +328,10 @@ impl Database {
                 self.users_by_ident.insert(new_ident, *user_id);
             }
 
+            // Activity is tracked here; an older copy mustn't roll it back
+            let last_activity = entry.last_activity.max(user.last_activity);
             *entry = user;
+            entry.last_activity = last_activity;
             Ok(())
         } else {
             Err(Error::User("User not found".to_string()))
@@ -280,7 +367,11 @@ impl Database {
     // Server management
 
     /// Add a server to the database
-    pub fn add_server(&self, server: ServerInfo) -> Result<()> {
+    ///
+    /// Its user count is taken from the users already known on it and kept
+    /// up to date as users are added and removed.
+    pub fn add_server(&self, mut server: ServerInfo) -> Result<()> {
+        server.user_count = self.users.iter().filter(|entry| entry.server == server.name).count() as u32;
         self.servers.insert(server.name.clone(), server);
         Ok(())
     }
@@ -315,11 +406,124 @@ impl Database {
         Ok(())
     }
 
+    /// Get a channel
+    pub fn get_channel(&self, channel_name: &str) -> Option<ChannelInfo> {
+        self.channels.get(channel_name).map(|channel| channel.clone())
+    }
+
+    /// Get all channels
+    pub fn get_all_channels(&self) -> Vec<ChannelInfo> {
+        self.channels.iter().map(|entry| entry.value().clone()).collect()
+    }
+
     /// Remove a channel
     pub fn remove_channel(&self, channel_name: &str) -> Option<ChannelInfo> {
+        self.metadata.remove(&MetadataTarget::channel(channel_name));
         self.channels.remove(channel_name).map(|(_, channel)| channel)
     }
 
+    /// Move a channel, its members and its metadata to a new name
+    pub fn rename_channel(&self, old_name: &str, new_name: &str) -> Result<()> {
+        if let Some((_, mut channel)) = self.channels.remove(old_name) {
+            channel.name = new_name.to_string();
+            self.channels.insert(new_name.to_string(), channel);
+        }
+        if let Some((_, members)) = self.channel_members.remove(old_name) {
+            for nick in &members {
+                if let Some(mut channels) = self.user_channels.get_mut(nick) {
+                    channels.remove(old_name);
+                    channels.insert(new_name.to_string());
+                }
+            }
+            self.channel_members.insert(new_name.to_string(), members);
+        }
+        if let Some((_, entries)) = self.metadata.remove(&MetadataTarget::channel(old_name)) {
+            self.metadata.insert(MetadataTarget::channel(new_name), entries);
+        }
+        self.channel_member_cache.invalidate(old_name);
+        self.channel_member_cache.invalidate(new_name);
+        Ok(())
+    }
+
+    /// Get one metadata key of a user or channel
+    pub fn get_metadata(&self, target: &MetadataTarget, key: &str) -> Option<MetadataEntry> {
+        self.metadata.get(target)?.get(&key.to_lowercase()).cloned()
+    }
+
+    /// All metadata of a user or channel, sorted by key
+    pub fn list_metadata(&self, target: &MetadataTarget) -> Vec<(String, MetadataEntry)> {
+        self.metadata.get(target)
+            .map(|entries| entries.iter().map(|(key, entry)| (key.clone(), entry.clone())).collect())
+            .unwrap_or_default()
+    }
+
+    /// Number of metadata keys a user or channel has set
+    pub fn metadata_count(&self, target: &MetadataTarget) -> usize {
+        self.metadata.get(target).map_or(0, |entries| entries.len())
+    }
+
+    /// Set a metadata key, or remove it when `entry` is `None`
+    ///
+    /// Returns the previous entry.
+    pub fn set_metadata(&self, target: &MetadataTarget, key: &str, entry: Option<MetadataEntry>) -> Option<MetadataEntry> {
+        let key = key.to_lowercase();
+        match entry {
+            Some(entry) => self.metadata.entry(target.clone()).or_default().insert(key, entry),
+            None => {
+                let previous = self.metadata.get_mut(target)?.remove(&key);
+                self.metadata.remove_if(target, |_, entries| entries.is_empty());
+                previous
+            }
+        }
+    }
+
+    /// Remove every metadata key of a user or channel, returning them
+    pub fn clear_metadata(&self, target: &MetadataTarget) -> Vec<(String, MetadataEntry)> {
+        self.metadata.remove(target)
+            .map(|(_, entries)| entries.into_iter().collect())
+            .unwrap_or_default()
+    }
+
+    /// Every user and channel with metadata set
+    pub fn metadata_targets(&self) -> Vec<MetadataTarget> {
+        self.metadata.iter().map(|entry| entry.key().clone()).collect()
+    }
+
+    /// Store the state of a permanent (+P) channel
+    pub fn set_permanent_channel(&self, channel_name: &str, state: serde_json::Value) -> Result<()> {
+        self.permanent_channels.insert(channel_name.to_string(), state);
+        self.save_permanent_channels()
+    }
+
+    /// Forget a channel that is no longer permanent
+    pub fn remove_permanent_channel(&self, channel_name: &str) -> Result<()> {
+        if self.permanent_channels.remove(channel_name).is_some() {
+            self.save_permanent_channels()?;
+        }
+        Ok(())
+    }
+
+    /// All stored permanent channels
+    pub fn get_permanent_channels(&self) -> Vec<(String, serde_json::Value)> {
+        self.permanent_channels.iter()
+            .map(|entry| (entry.key().clone(), entry.value().clone()))
+            .collect()
+    }
+
+    /// Write out everything that is persisted, e.g. before shutting down
+    pub fn flush(&self) -> Result<()> {
+        self.save_permanent_channels()
+    }
+
+    fn save_permanent_channels(&self) -> Result<()> {
+        let Some(path) = &self.permanent_channels_file else {
+            return Ok(());
+        };
+        let channels: BTreeMap<String, serde_json::Value> = self.get_permanent_channels().into_iter().collect();
+        std::fs::write(path, serde_json::to_string_pretty(&channels)?)?;
+        Ok(())
+    }
+
     /// Add user to channel
     pub fn add_user_to_channel(&self, nick: &str, channel: &str) -> Result<()> {
         // Add to user's channel list
@@ -383,9 +587,11 @@ impl Database {
 
     // User history management
 
-    #[allow(dead_code)]
     /// Add user to history
-    async fn add_to_history(&self, user: User) -> Result<()> {
+    ///
+    /// Called when a user quits, is killed or changes nickname. Entries
+    /// older than `history_retention_days` are dropped on the way.
+    pub async fn add_to_history(&self, user: User) -> Result<()> {
         let entry = UserHistoryEntry {
             user: user.clone(),
             disconnect_time: Utc::now(),
@@ -394,6 +600,7 @@ impl Database {
 
         let mut history = self.user_history.write().await;
         history.push_back(entry);
+        self.expire_history(&mut history);
 
         // Maintain max size
         while history.len() > self.max_history_size {
@@ -403,29 +610,33 @@ impl Database {
         Ok(())
     }
 
-    /// Get user history by nickname
+    /// Get user history by nickname, oldest first
     pub async fn get_user_history(&self, nick: &str) -> Vec<UserHistoryEntry> {
+        let cutoff = self.history_cutoff();
         let history = self.user_history.read().await;
         history.iter()
-            .filter(|entry| entry.user.nick.to_lowercase() == nick.to_lowercase())
+            .filter(|entry| entry.disconnect_time >= cutoff && entry.user.nick.to_lowercase() == nick.to_lowercase())
             .cloned()
             .collect()
     }
 
     /// Clean up old history entries
     pub async fn cleanup_history(&self) -> Result<()> {
-        let cutoff = Utc::now() - Duration::days(self.history_retention_days);
         let mut history = self.user_history.write().await;
+        self.expire_history(&mut history);
+        Ok(())
+    }
 
-        while let Some(entry) = history.front() {
-            if entry.disconnect_time < cutoff {
-                history.pop_front();
-            } else {
-                break;
-            }
-        }
+    /// Oldest disconnect time still within the retention period
+    fn history_cutoff(&self) -> DateTime<Utc> {
+        Utc::now() - Duration::days(self.history_retention_days)
+    }
 
-        Ok(())
+    fn expire_history(&self, history: &mut VecDeque<UserHistoryEntry>) {
+        let cutoff = self.history_cutoff();
+        while history.front().is_some_and(|entry| entry.disconnect_time < cutoff) {
+            history.pop_front();
+        }
     }
 
     // Utility methods
@@ -492,10 +703,26 @@ impl Database {
     }
 
     /// Get total user count across all servers
+    ///
+    /// Remote users are stored alongside local ones, so this is every user
+    /// on the network.
     pub fn total_user_count(&self) -> u32 {
-        self.servers.iter()
-            .map(|entry| entry.user_count)
-            .sum::<u32>() + self.user_count() as u32
+        self.user_count() as u32
+    }
+
+    /// Most users known at once since startup
+    pub fn max_user_count(&self) -> u32 {
+        self.max_users.load(Ordering::Relaxed) as u32
+    }
+
+    /// Operators on the network
+    pub fn operator_count(&self) -> u32 {
+        self.users.iter().filter(|entry| entry.is_operator).count() as u32
+    }
+
+    /// Users on the network with +i set
+    pub fn invisible_count(&self) -> u32 {
+        self.users.iter().filter(|entry| entry.has_mode('i')).count() as u32
     }
 
     // Cache management
@@ -14,6 +14,9 @@ pub enum Error {
     #[error("Message parsing error: {0}")]
     MessageParse(String),
     
+    #[error("Input line too long")]
+    InputTooLong,
+    
     #[error("Connection error: {0}")]
     Connection(String),
     
@@ -0,0 +1,33 @@
+//! Events the server publishes to embedders and modules
+
+use crate::{AuditEvent, User};
+
+/// How many events a slow subscriber may fall behind before it misses some
+pub const EVENT_CHANNEL_CAPACITY: usize = 1024;
+
+/// Something that happened on the server
+///
+/// Subscribe with `Server::subscribe`, or `ModuleContext::subscribe_events`
+/// from a module. Subscribers that fall more than `EVENT_CHANNEL_CAPACITY`
+/// events behind skip the oldest ones.
+#[derive(Debug, Clone)]
+pub enum ServerEvent {
+    /// Listeners are bound and background tasks are running
+    Started,
+    /// Shutdown began for the given reason
+    ShuttingDown { reason: String },
+    /// Shutdown finished and persistent state was flushed
+    Stopped,
+    /// A local client completed registration
+    UserRegistered { user: User },
+    /// A local or remote user left the network
+    UserQuit { user: User, reason: String },
+    /// A JOIN created a channel that did not exist before
+    ChannelCreated { channel: String, creator: String },
+    /// A local user sent a PRIVMSG or NOTICE
+    MessageSent { from: String, target: String, text: String, notice: bool },
+    /// An operator performed a privileged action
+    OperAction(AuditEvent),
+    /// A server linked directly to this one
+    LinkEstablished { server: String },
+}
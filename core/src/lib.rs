@@ -9,6 +9,7 @@
 pub mod client;
 pub mod config;
 pub mod connection;
+pub mod client_senders;
 pub mod server_connection;
 pub mod error;
 pub mod message;
@@ -26,6 +27,13 @@ pub mod network;
 pub mod throttling_manager;
 pub mod statistics;
 pub mod motd;
+pub mod rules;
+pub mod logging;
+pub mod telemetry;
+#[cfg(feature = "admin-api")]
+pub mod admin_api;
+#[cfg(feature = "acme")]
+pub mod acme;
 pub mod lookup;
 pub mod module_numerics;
 pub mod rehash;
@@ -36,18 +44,36 @@ pub mod cache;
 pub mod batch_optimizer;
 pub mod auth;
 pub mod audit;
+pub mod cloak;
+pub mod ban_manager;
+pub mod ctcp;
+pub mod nick_flood;
+pub mod snomask;
+pub mod topology;
+pub mod link_tls;
+pub mod challenge;
+pub mod link_compression;
+pub mod events;
+pub mod commands;
+pub mod registration;
+pub mod builder;
 
 #[cfg(test)]
 mod tests;
 
 pub use client::Client;
+pub use client_senders::ClientSenders;
 pub use config::Config;
 // pub use connection::Connection; // Commented out - Connection is not exported from connection module
-pub use server_connection::{ServerConnection, ServerConnectionManager, ServerInfo, ServerConnectionState};
+pub use server_connection::{ServerConnection, ServerConnectionManager, ServerInfo, ServerConnectionState, IncomingHandshake, IncomingRegistration};
 pub use error::{Error, Result};
-pub use message::{Message, MessageType, Prefix};
-pub use module::{Module, ModuleManager};
+pub use message::{Message, MessageRef, MessageType, Prefix, RedactedMessage};
+pub use module::{Module, ModuleManager, ModuleFactory, ModuleRegistry};
 pub use server::Server;
+pub use builder::ServerBuilder;
+pub use events::ServerEvent;
+pub use commands::{CommandHandler, CommandInfo, CommandSpec, core_command_spec};
+pub use registration::RegistrationState;
 pub use user::{User, UserState};
 pub use user_modes::{UserMode, UserModeManager};
 pub use extensible_modes::{
@@ -58,22 +84,31 @@ pub use extensible_modes::{
     get_custom_modes_by_module
 };
 pub use numeric::NumericReply;
-pub use replies_config::{RepliesConfig, ReplyConfig, ServerInfo as RepliesServerInfo};
-pub use database::{Database, DatabaseConfig, UserHistoryEntry, ServerInfo as DatabaseServerInfo, ChannelInfo};
+pub use replies_config::{NumericReplies, RepliesConfig, ReplyConfig, ServerInfo as RepliesServerInfo};
+pub use database::{Database, DatabaseConfig, UserHistoryEntry, ServerInfo as DatabaseServerInfo, ChannelInfo, MetadataTarget, MetadataEntry};
 pub use broadcast::{BroadcastSystem, BroadcastTarget, BroadcastMessage, BroadcastPriority, MessageBuilder};
-pub use network::{NetworkQueryManager, NetworkMessageHandler, NetworkQuery, NetworkResponse, NetworkMessage};
-pub use throttling_manager::ThrottlingManager;
+pub use network::{NetworkQueryManager, NetworkMessageHandler, NetworkQuery, NetworkResponse, NetworkMessage, EncapRegistry};
+pub use throttling_manager::{AuthFailureKind, AuthFailureOutcome, ThrottleExemption, ThrottlingManager};
 pub use statistics::{StatisticsManager, ServerStatistics, CommandStats};
-pub use auth::{AuthManager, AuthProvider, AuthResult, AuthInfo, AuthRequest, ClientInfo, AuthProviderCapabilities};
-pub use motd::MotdManager;
-pub use lookup::{LookupService, DnsResolver, IdentClient, LookupResult, IdentResult};
+pub use auth::{AuthManager, AuthProvider, AuthResult, AuthInfo, AuthRequest, ClientInfo, AuthProviderCapabilities, AUTH_META_OPER_FLAGS, AUTH_META_CLASS};
+pub use motd::{MotdManager, MotdSet};
+pub use rules::RulesManager;
+pub use logging::init_logging;
+pub use lookup::{LookupService, DnsResolver, IdentClient, LookupResult, IdentResult, ip_host};
 pub use module_numerics::{ModuleNumericManager, ModuleNumeric, ModuleNumericClient};
-pub use rehash::RehashService;
+pub use rehash::{RehashService, RehashSection};
 pub use buffer::{SendQueue, RecvQueue, ConnectionTiming};
 pub use class_tracker::{ClassTracker, ClassStats};
 pub use validation::{ConfigValidator, ValidationResult, ValidationError, ValidationWarning, ErrorCategory, print_validation_result};
 pub use cache::{LruCache, MessageCache, DnsCache, ChannelMemberCache, UserLookupCache, CacheStats};
 pub use batch_optimizer::{BatchOptimizer, BatchConfig, MessageBatch, BatchStats, ConnectionPool, ConnectionPoolStats};
+pub use cloak::HostCloaker;
+pub use ban_manager::{BanManager, BanType, BanTarget, BanEntry, BanQuery, BanStore, FileBanStore};
+pub use ctcp::{Ctcp, CtcpFloodControl};
+pub use nick_flood::NickFloodControl;
+pub use snomask::SnoMask;
+pub use topology::{NetworkTopology, ServerNode};
+pub use audit::{AuditEvent, AuditEventType, AuditTrail};
 
 /// Re-exports for convenience
 pub use async_trait::async_trait;
@@ -0,0 +1,203 @@
+//! Server link compression
+//!
+//! Links that both advertise `ZIP` in CAPAB switch to zlib once negotiated:
+//! each side sends a plain `ZIPSTART` line, and everything it writes after
+//! that line is one zlib stream, sync-flushed after every write so the peer
+//! can decode each message as soon as it arrives.
+
+use crate::{Error, Result};
+use flate2::{Compress, Compression, Decompress, FlushCompress, FlushDecompress};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+
+/// CAPAB token advertising compression support
+pub const CAPAB_TOKEN: &str = "ZIP";
+
+/// Line after which the sender's side of the link is compressed
+pub const START_COMMAND: &str = "ZIPSTART";
+
+/// Byte counters for one compressed link
+#[derive(Debug, Default)]
+pub struct CompressionStats {
+    active: AtomicBool,
+    plain_out: AtomicU64,
+    compressed_out: AtomicU64,
+    compressed_in: AtomicU64,
+    plain_in: AtomicU64,
+}
+
+impl CompressionStats {
+    /// Mark compression as running on the link
+    pub fn activate(&self) {
+        self.active.store(true, Ordering::Relaxed);
+    }
+
+    /// Whether compression has started in at least one direction
+    pub fn is_active(&self) -> bool {
+        self.active.load(Ordering::Relaxed)
+    }
+
+    /// Count an outgoing write
+    pub fn record_out(&self, plain: usize, compressed: usize) {
+        self.plain_out.fetch_add(plain as u64, Ordering::Relaxed);
+        self.compressed_out.fetch_add(compressed as u64, Ordering::Relaxed);
+    }
+
+    /// Count an incoming read
+    pub fn record_in(&self, compressed: usize, plain: usize) {
+        self.compressed_in.fetch_add(compressed as u64, Ordering::Relaxed);
+        self.plain_in.fetch_add(plain as u64, Ordering::Relaxed);
+    }
+
+    /// Uncompressed and compressed bytes sent
+    pub fn sent(&self) -> (u64, u64) {
+        (self.plain_out.load(Ordering::Relaxed), self.compressed_out.load(Ordering::Relaxed))
+    }
+
+    /// Uncompressed and compressed bytes received
+    pub fn received(&self) -> (u64, u64) {
+        (self.plain_in.load(Ordering::Relaxed), self.compressed_in.load(Ordering::Relaxed))
+    }
+
+    /// Compressed size of sent data as a percentage of its plain size
+    pub fn sent_ratio(&self) -> f64 {
+        let (plain, compressed) = self.sent();
+        Self::ratio(plain, compressed)
+    }
+
+    /// Compressed size of received data as a percentage of its plain size
+    pub fn received_ratio(&self) -> f64 {
+        let (plain, compressed) = self.received();
+        Self::ratio(plain, compressed)
+    }
+
+    fn ratio(plain: u64, compressed: u64) -> f64 {
+        if plain == 0 {
+            100.0
+        } else {
+            compressed as f64 * 100.0 / plain as f64
+        }
+    }
+}
+
+/// Compressing half of a link
+pub struct LinkCompressor {
+    stream: Compress,
+}
+
+impl Default for LinkCompressor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LinkCompressor {
+    /// Start a new zlib stream
+    pub fn new() -> Self {
+        Self { stream: Compress::new(Compression::default(), true) }
+    }
+
+    /// Compress one write, flushed so the peer can decode it right away
+    pub fn compress(&mut self, data: &[u8]) -> Result<Vec<u8>> {
+        let mut output = Vec::with_capacity(data.len() / 2 + 64);
+        let mut consumed = 0;
+        loop {
+            if output.capacity() - output.len() < 64 {
+                output.reserve(4096);
+            }
+            let before = self.stream.total_in();
+            self.stream.compress_vec(&data[consumed..], &mut output, FlushCompress::Sync)
+                .map_err(|e| Error::Connection(format!("Link compression failed: {}", e)))?;
+            consumed += (self.stream.total_in() - before) as usize;
+            // The flush is complete once the output stops filling up
+            if consumed == data.len() && output.len() < output.capacity() {
+                return Ok(output);
+            }
+        }
+    }
+}
+
+/// Decompressing half of a link, reassembling lines split across reads
+pub struct LinkDecompressor {
+    stream: Decompress,
+    pending: Vec<u8>,
+}
+
+impl Default for LinkDecompressor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LinkDecompressor {
+    /// Expect a new zlib stream
+    pub fn new() -> Self {
+        Self { stream: Decompress::new(true), pending: Vec::new() }
+    }
+
+    /// Decompress a chunk read from the link, returning complete lines
+    ///
+    /// Returns the lines and the number of plain bytes produced.
+    pub fn feed(&mut self, data: &[u8]) -> Result<(Vec<String>, usize)> {
+        let start = self.pending.len();
+        let mut consumed = 0;
+        loop {
+            if self.pending.capacity() - self.pending.len() < 64 {
+                self.pending.reserve(4096);
+            }
+            let before = self.stream.total_in();
+            self.stream.decompress_vec(&data[consumed..], &mut self.pending, FlushDecompress::Sync)
+                .map_err(|e| Error::Connection(format!("Link decompression failed: {}", e)))?;
+            consumed += (self.stream.total_in() - before) as usize;
+            if consumed == data.len() && self.pending.len() < self.pending.capacity() {
+                break;
+            }
+        }
+        let produced = self.pending.len() - start;
+
+        let mut lines = Vec::new();
+        while let Some(end) = self.pending.iter().position(|&byte| byte == b'\n') {
+            let line: Vec<u8> = self.pending.drain(..=end).collect();
+            lines.push(String::from_utf8_lossy(&line).trim_end().to_string());
+        }
+        Ok((lines, produced))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip_across_chunks() {
+        let mut compressor = LinkCompressor::new();
+        let mut decompressor = LinkDecompressor::new();
+        let burst: String = (0..200)
+            .map(|i| format!("UBURST user{} user{} host.example.com :Real Name\r\n", i, i))
+            .collect();
+
+        let compressed = compressor.compress(burst.as_bytes()).unwrap();
+        assert!(compressed.len() < burst.len() / 4);
+
+        // Chunks may end mid-line; lines come out once complete
+        let (first, _) = decompressor.feed(&compressed[..compressed.len() / 2]).unwrap();
+        let (second, _) = decompressor.feed(&compressed[compressed.len() / 2..]).unwrap();
+        let lines: Vec<String> = first.into_iter().chain(second).collect();
+        assert_eq!(lines.len(), 200);
+        assert_eq!(lines[199], "UBURST user199 user199 host.example.com :Real Name");
+
+        // Later writes continue the same stream
+        let more = compressor.compress(b"PING :hub.example.com\r\n").unwrap();
+        assert_eq!(decompressor.feed(&more).unwrap().0, vec!["PING :hub.example.com".to_string()]);
+    }
+
+    #[test]
+    fn test_stats_ratio() {
+        let stats = CompressionStats::default();
+        assert_eq!(stats.sent_ratio(), 100.0);
+        stats.record_out(1000, 250);
+        stats.record_in(100, 400);
+        assert_eq!(stats.sent_ratio(), 25.0);
+        assert_eq!(stats.received_ratio(), 25.0);
+        assert_eq!(stats.sent(), (1000, 250));
+    }
+}
@@ -0,0 +1,154 @@
+//! TLS for outgoing server links
+//!
+//! A link block with `tls = true` is dialed over TLS with SNI set to
+//! `tls_server_name` (or the hostname). The peer certificate is accepted when
+//! its SHA-256 fingerprint matches `tls_fingerprint`, or, without a pin, when
+//! it chains to a CA in `tls_ca_file`.
+
+use crate::config::ServerLink;
+use crate::{Error, Result};
+use rustls::client::{ServerCertVerified, ServerCertVerifier, WebPkiVerifier};
+use rustls::{Certificate, ClientConfig, RootCertStore, ServerName};
+use sha2::{Digest, Sha256};
+use std::sync::Arc;
+use std::time::SystemTime;
+use tokio_rustls::TlsConnector;
+
+/// Hex SHA-256 fingerprint of a DER certificate, lowercase without separators
+pub fn fingerprint(certificate: &[u8]) -> String {
+    Sha256::digest(certificate).iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// Normalize a configured fingerprint, accepting `AA:BB:...` and any case
+///
+/// Returns `None` unless it's 64 hex digits.
+pub fn normalize_fingerprint(fingerprint: &str) -> Option<String> {
+    let normalized: String = fingerprint.chars()
+        .filter(|c| *c != ':')
+        .map(|c| c.to_ascii_lowercase())
+        .collect();
+    (normalized.len() == 64 && normalized.chars().all(|c| c.is_ascii_hexdigit())).then_some(normalized)
+}
+
+/// Verifies link certificates against a pinned fingerprint or CA roots
+struct LinkCertVerifier {
+    link: String,
+    pinned: Option<String>,
+    roots: Option<WebPkiVerifier>,
+}
+
+impl ServerCertVerifier for LinkCertVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &Certificate,
+        intermediates: &[Certificate],
+        server_name: &ServerName,
+        scts: &mut dyn Iterator<Item = &[u8]>,
+        ocsp_response: &[u8],
+        now: SystemTime,
+    ) -> std::result::Result<ServerCertVerified, rustls::Error> {
+        if let Some(pinned) = &self.pinned {
+            let presented = fingerprint(&end_entity.0);
+            if &presented != pinned {
+                tracing::warn!(
+                    "Rejecting TLS link to {}: certificate fingerprint {} doesn't match pinned {}",
+                    self.link, presented, pinned
+                );
+                return Err(rustls::Error::General(format!("certificate fingerprint mismatch for {}", self.link)));
+            }
+            return Ok(ServerCertVerified::assertion());
+        }
+        match &self.roots {
+            Some(roots) => roots.verify_server_cert(end_entity, intermediates, server_name, scts, ocsp_response, now)
+                .inspect_err(|e| tracing::warn!("Rejecting TLS link to {}: {}", self.link, e)),
+            None => Err(rustls::Error::General(format!("no tls_fingerprint or tls_ca_file configured for {}", self.link))),
+        }
+    }
+}
+
+/// Build the connector and SNI name for a TLS link
+pub fn connector(link: &ServerLink) -> Result<(TlsConnector, ServerName)> {
+    let pinned = match &link.tls_fingerprint {
+        Some(configured) => Some(normalize_fingerprint(configured)
+            .ok_or_else(|| Error::Config(format!("Invalid tls_fingerprint for link {}", link.name)))?),
+        None => None,
+    };
+    let roots = match &link.tls_ca_file {
+        Some(ca_file) => {
+            let mut store = RootCertStore::empty();
+            let mut reader = std::io::BufReader::new(std::fs::File::open(ca_file)
+                .map_err(|e| Error::Config(format!("Failed to open tls_ca_file for link {}: {}", link.name, e)))?);
+            let certificates = rustls_pemfile::certs(&mut reader)
+                .map_err(|e| Error::Config(format!("Failed to parse tls_ca_file for link {}: {}", link.name, e)))?;
+            store.add_parsable_certificates(&certificates);
+            Some(WebPkiVerifier::new(store, None))
+        }
+        None => None,
+    };
+    if pinned.is_none() && roots.is_none() {
+        return Err(Error::Config(format!("TLS link {} needs tls_fingerprint or tls_ca_file", link.name)));
+    }
+
+    let verifier = LinkCertVerifier { link: link.name.clone(), pinned, roots };
+    let config = ClientConfig::builder()
+        .with_safe_defaults()
+        .with_custom_certificate_verifier(Arc::new(verifier))
+        .with_no_client_auth();
+    let sni = link.tls_server_name.as_deref().unwrap_or(&link.hostname);
+    let server_name = ServerName::try_from(sni)
+        .map_err(|e| Error::Config(format!("Invalid TLS server name {} for link {}: {}", sni, link.name, e)))?;
+    Ok((TlsConnector::from(Arc::new(config)), server_name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn link() -> ServerLink {
+        ServerLink {
+            name: "hub.test".to_string(),
+            hostname: "hub.test".to_string(),
+            port: 6697,
+            password: "linkpass".to_string(),
+            tls: true,
+            outgoing: true,
+            class: None,
+            autoconnect: false,
+            tls_fingerprint: None,
+            tls_ca_file: None,
+            tls_server_name: None,
+            compression: false,
+        }
+    }
+
+    #[test]
+    fn test_fingerprint_normalization() {
+        let hex = fingerprint(b"certificate");
+        assert_eq!(hex.len(), 64);
+        let colons: Vec<String> = hex.as_bytes().chunks(2).map(|pair| String::from_utf8_lossy(pair).to_uppercase()).collect();
+        assert_eq!(normalize_fingerprint(&colons.join(":")), Some(hex));
+        assert_eq!(normalize_fingerprint("abcd"), None);
+    }
+
+    #[test]
+    fn test_pinned_certificate() {
+        let mut link = link();
+        let certificate = Certificate(b"not really der".to_vec());
+        link.tls_fingerprint = Some(fingerprint(&certificate.0));
+        let verifier = LinkCertVerifier { link: link.name.clone(), pinned: Some(fingerprint(&certificate.0)), roots: None };
+        let name = ServerName::try_from("hub.test").unwrap();
+        assert!(verifier.verify_server_cert(&certificate, &[], &name, &mut std::iter::empty(), &[], SystemTime::now()).is_ok());
+
+        let other = Certificate(b"someone else".to_vec());
+        assert!(verifier.verify_server_cert(&other, &[], &name, &mut std::iter::empty(), &[], SystemTime::now()).is_err());
+        assert!(connector(&link).is_ok());
+    }
+
+    #[test]
+    fn test_link_needs_trust_anchor() {
+        assert!(connector(&link()).is_err());
+        let mut link = link();
+        link.tls_fingerprint = Some("zz".to_string());
+        assert!(connector(&link).is_err());
+    }
+}
@@ -0,0 +1,285 @@
+//! Logging setup
+//!
+//! Builds the tracing subscriber from `[logging]`: text or JSON lines,
+//! a default level with per-module overrides, and stdout or a size-rotated
+//! file. In JSON output each event's fields (`client_id`, `nick`,
+//! `command`, ...) become keys next to `timestamp`, `level`, `target` and
+//! `message`. With `[telemetry]` enabled, spans are also exported over
+//! OTLP (see [`crate::telemetry`]).
+
+use crate::config::{Config, LogFormat, LoggingConfig};
+use crate::telemetry::OtlpLayer;
+use crate::{Error, Result};
+use serde_json::{Map, Value};
+use std::fmt;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tracing::field::{Field, Visit};
+use tracing::level_filters::LevelFilter;
+use tracing::{Event, Subscriber};
+use tracing_subscriber::filter::{filter_fn, Targets};
+use tracing_subscriber::fmt::format::Writer;
+use tracing_subscriber::fmt::writer::BoxMakeWriter;
+use tracing_subscriber::fmt::{FmtContext, FormatEvent, FormatFields};
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::Layer;
+
+/// Parse a level name: trace, debug, info, warn, error or off
+pub fn parse_level(level: &str) -> Option<LevelFilter> {
+    level.parse().ok()
+}
+
+/// The level filter described by `config`, with `level` replacing
+/// `config.level` when given
+pub fn build_filter(config: &LoggingConfig, level: Option<&str>) -> Result<Targets> {
+    let level = level.unwrap_or(&config.level);
+    let default = parse_level(level)
+        .ok_or_else(|| Error::Config(format!("Invalid log level: {}", level)))?;
+    config.targets.iter().try_fold(Targets::new().with_default(default), |filter, (target, level)| {
+        let level = parse_level(level)
+            .ok_or_else(|| Error::Config(format!("Invalid log level for {}: {}", target, level)))?;
+        Ok(filter.with_target(target.clone(), level))
+    })
+}
+
+/// Install the global subscriber described by `config.logging` and
+/// `config.telemetry`
+///
+/// `level` (from `--log-level`) replaces `config.logging.level` when given.
+/// Trace export starts its exporter on the current Tokio runtime.
+pub fn init_logging(config: &Config, level: Option<&str>) -> Result<()> {
+    // Only the daemon's own spans are exported; events and dependencies'
+    // spans are left out
+    let telemetry = if config.telemetry.enabled {
+        Some(OtlpLayer::start(&config.telemetry)?.with_filter(filter_fn(|metadata| {
+            metadata.is_span() && metadata.target().starts_with("rustircd")
+        })))
+    } else {
+        None
+    };
+    let config = &config.logging;
+    let filter = build_filter(config, level)?;
+    let writer = match &config.file {
+        Some(path) => BoxMakeWriter::new(Arc::new(
+            RotatingFile::open(path, config.max_file_size, config.max_files)
+                .map_err(|e| Error::Config(format!("Failed to open log file {}: {}", path, e)))?,
+        )),
+        None => BoxMakeWriter::new(io::stdout),
+    };
+
+    let layer = tracing_subscriber::fmt::layer().with_writer(writer);
+    let layer = match config.format {
+        LogFormat::Text => layer
+            .with_ansi(config.file.is_none())
+            .with_thread_ids(true)
+            .with_thread_names(true)
+            .boxed(),
+        LogFormat::Json => layer.event_format(JsonFormat).boxed(),
+    };
+
+    tracing_subscriber::registry()
+        .with(layer.with_filter(filter))
+        .with(telemetry)
+        .try_init()
+        .map_err(|e| Error::Config(format!("Failed to initialize logging: {}", e)))
+}
+
+/// Formats each event as a single-line JSON object
+pub struct JsonFormat;
+
+impl<S, N> FormatEvent<S, N> for JsonFormat
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    N: for<'a> FormatFields<'a> + 'static,
+{
+    fn format_event(&self, ctx: &FmtContext<'_, S, N>, mut writer: Writer<'_>, event: &Event<'_>) -> fmt::Result {
+        let metadata = event.metadata();
+        let mut object = Map::new();
+        object.insert("timestamp".to_string(), Value::String(chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Millis, true)));
+        object.insert("level".to_string(), Value::String(metadata.level().to_string()));
+        object.insert("target".to_string(), Value::String(metadata.target().to_string()));
+        if let Some(scope) = ctx.event_scope() {
+            let spans: Vec<Value> = scope.from_root().map(|span| Value::String(span.name().to_string())).collect();
+            object.insert("spans".to_string(), Value::Array(spans));
+        }
+        event.record(&mut JsonVisitor(&mut object));
+        writeln!(writer, "{}", Value::Object(object))
+    }
+}
+
+/// Collects event or span fields into a JSON object
+pub(crate) struct JsonVisitor<'a>(pub(crate) &'a mut Map<String, Value>);
+
+impl Visit for JsonVisitor<'_> {
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.0.insert(field.name().to_string(), Value::from(value));
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.0.insert(field.name().to_string(), Value::from(value));
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.0.insert(field.name().to_string(), Value::from(value));
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.0.insert(field.name().to_string(), Value::from(value));
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0.insert(field.name().to_string(), Value::from(value));
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.0.insert(field.name().to_string(), Value::String(format!("{:?}", value)));
+    }
+}
+
+/// A log file rotated by size
+///
+/// When a write would take the file past `max_size`, `file` is renamed to
+/// `file.1`, `file.1` to `file.2` and so on, dropping the oldest beyond
+/// `max_files`.
+pub struct RotatingFile {
+    path: PathBuf,
+    max_size: u64,
+    max_files: usize,
+    /// The open file and its current size
+    state: parking_lot::Mutex<(File, u64)>,
+}
+
+impl RotatingFile {
+    /// Open (or create) `path` for appending
+    pub fn open(path: impl AsRef<Path>, max_size: u64, max_files: usize) -> io::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        if let Some(parent) = path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+            fs::create_dir_all(parent)?;
+        }
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        let size = file.metadata()?.len();
+        Ok(Self {
+            path,
+            max_size,
+            max_files,
+            state: parking_lot::Mutex::new((file, size)),
+        })
+    }
+
+    fn rotated_path(&self, index: usize) -> PathBuf {
+        let mut path = self.path.clone().into_os_string();
+        path.push(format!(".{}", index));
+        path.into()
+    }
+
+    /// Shift the rotated files up by one and start a new file
+    fn rotate(&self, state: &mut (File, u64)) -> io::Result<()> {
+        state.0.flush()?;
+        if self.max_files == 0 {
+            fs::remove_file(&self.path)?;
+        } else {
+            let _ = fs::remove_file(self.rotated_path(self.max_files));
+            for index in (1..self.max_files).rev() {
+                let from = self.rotated_path(index);
+                if from.exists() {
+                    fs::rename(&from, self.rotated_path(index + 1))?;
+                }
+            }
+            fs::rename(&self.path, self.rotated_path(1))?;
+        }
+        *state = (OpenOptions::new().create(true).append(true).open(&self.path)?, 0);
+        Ok(())
+    }
+}
+
+impl Write for &RotatingFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut state = self.state.lock();
+        if self.max_size > 0 && state.1 > 0 && state.1 + buf.len() as u64 > self.max_size {
+            self.rotate(&mut state)?;
+        }
+        let written = state.0.write(buf)?;
+        state.1 += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.state.lock().0.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tracing_subscriber::fmt::MakeWriter;
+
+    #[test]
+    fn test_build_filter() {
+        let mut config = LoggingConfig::default();
+        config.targets.insert("rustircd_core::server".to_string(), "debug".to_string());
+        let filter = build_filter(&config, None).unwrap();
+        assert!(filter.would_enable("rustircd_core::server", &tracing::Level::DEBUG));
+        assert!(!filter.would_enable("rustircd_core::database", &tracing::Level::DEBUG));
+
+        let filter = build_filter(&config, Some("warn")).unwrap();
+        assert!(!filter.would_enable("rustircd_core::database", &tracing::Level::INFO));
+
+        config.targets.insert("rustircd_modules".to_string(), "loud".to_string());
+        assert!(build_filter(&config, None).is_err());
+    }
+
+    #[test]
+    fn test_json_format() {
+        let buffer = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let sink = buffer.clone();
+        let subscriber = tracing_subscriber::fmt()
+            .event_format(JsonFormat)
+            .with_writer(move || SharedBuffer(sink.clone()))
+            .finish();
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info!(client_id = 7, nick = "alice", command = "JOIN", "Command received");
+        });
+
+        let output = String::from_utf8(buffer.lock().unwrap().clone()).unwrap();
+        let line: Value = serde_json::from_str(output.trim()).unwrap();
+        assert_eq!(line["level"], "INFO");
+        assert_eq!(line["message"], "Command received");
+        assert_eq!(line["client_id"], 7);
+        assert_eq!(line["nick"], "alice");
+        assert_eq!(line["command"], "JOIN");
+    }
+
+    #[test]
+    fn test_rotating_file() {
+        let path = std::env::temp_dir().join(format!("rustircd_log_{}.log", uuid::Uuid::new_v4()));
+        let file = Arc::new(RotatingFile::open(&path, 16, 2).unwrap());
+        for line in ["first line 1\n", "second line\n", "third line\n", "fourth line\n"] {
+            file.make_writer().write_all(line.as_bytes()).unwrap();
+        }
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "fourth line\n");
+        assert_eq!(fs::read_to_string(file.rotated_path(1)).unwrap(), "third line\n");
+        assert_eq!(fs::read_to_string(file.rotated_path(2)).unwrap(), "second line\n");
+        assert!(!file.rotated_path(3).exists());
+
+        for index in 0..=2 {
+            let _ = fs::remove_file(if index == 0 { path.clone() } else { file.rotated_path(index) });
+        }
+    }
+
+    struct SharedBuffer(Arc<std::sync::Mutex<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+}
@@ -1,11 +1,16 @@
 //! DNS and ident lookup functionality for RFC compliance
+//!
+//! A connecting client's reverse DNS, ident and DNS blocklist lookups run
+//! side by side under one time budget (see [`LookupService::lookup_client`]),
+//! so adding a lookup doesn't add to the time a client waits to register.
 
+use crate::config::DnsblConfig;
 use crate::{Error, Result};
 use std::net::{IpAddr, SocketAddr};
 use std::sync::Arc;
 use std::time::Duration;
 use tokio::net::TcpStream;
-use tokio::time::timeout;
+use tokio::time::{timeout, timeout_at};
 use tokio::io::AsyncWriteExt;
 use hickory_resolver::TokioAsyncResolver;
 
@@ -33,12 +38,26 @@ pub struct IdentResult {
     pub error: Option<String>,
 }
 
+/// What the connect-time lookups found out about a client
+#[derive(Debug, Clone, Default)]
+pub struct ClientLookup {
+    /// Forward-confirmed reverse DNS name
+    pub hostname: Option<String>,
+    /// Username from the ident lookup
+    pub ident: Option<String>,
+    /// A blocklist the client's address is listed in
+    pub dnsbl: Option<DnsblConfig>,
+}
+
 /// DNS resolver for hostname lookups
 pub struct DnsResolver {
     resolver: TokioAsyncResolver,
     enabled: bool,
     reverse_enabled: bool,
     cache: Arc<crate::DnsCache>,
+    /// Reverse names that resolved back to their address, kept apart from
+    /// `cache` so forward lookups can't plant a host for an address
+    confirmed: Arc<crate::DnsCache>,
 }
 
 impl DnsResolver {
@@ -52,9 +71,85 @@ impl DnsResolver {
             enabled: enable_dns,
             reverse_enabled: enable_reverse_dns,
             cache: Arc::new(crate::DnsCache::new(std::time::Duration::from_secs(300))),
+            confirmed: Arc::new(crate::DnsCache::new(std::time::Duration::from_secs(300))),
         })
     }
 
+    /// Hostname to show for a client connecting from `ip`
+    ///
+    /// The reverse DNS name is only used when it is a valid hostname and a
+    /// forward lookup of it includes `ip` again, so whoever controls the
+    /// reverse zone can't claim someone else's domain. Needs both DNS
+    /// toggles; `None` means the client should be shown by address.
+    pub async fn client_hostname(&self, ip: IpAddr) -> Option<String> {
+        let ip = ip.to_canonical();
+        if !self.enabled || !self.reverse_enabled {
+            return None;
+        }
+        if let Some(hostname) = self.confirmed.get_hostname(&ip.to_string()) {
+            return Some(hostname);
+        }
+
+        let hostname = normalize_hostname(&self.reverse_lookup(ip).await.hostname?)?;
+        let confirmed = match timeout(Duration::from_secs(5), self.resolver.lookup_ip(hostname.as_str())).await {
+            Ok(Ok(ips)) => ips.iter().any(|addr| addr.to_canonical() == ip),
+            Ok(Err(e)) => {
+                tracing::debug!("Forward lookup of {} for {} failed: {}", hostname, ip, e);
+                false
+            }
+            Err(_) => {
+                tracing::debug!("Forward lookup of {} for {} timed out", hostname, ip);
+                false
+            }
+        };
+        if !confirmed {
+            tracing::debug!("Reverse name {} of {} doesn't resolve back to it", hostname, ip);
+            return None;
+        }
+        self.confirmed.cache_hostname(ip.to_string(), hostname.clone());
+        Some(hostname)
+    }
+
+    /// The first of `dnsbls` that lists `ip`, checking them all at once
+    ///
+    /// Only 127.0.0.x answers count, and of those only the ones in the
+    /// blocklist's `replies` when it has any. Failed lookups count as not
+    /// listed. Needs DNS enabled.
+    pub async fn dnsbl_listing(&self, ip: IpAddr, dnsbls: &[DnsblConfig]) -> Option<DnsblConfig> {
+        if !self.enabled {
+            return None;
+        }
+        let mut checks = tokio::task::JoinSet::new();
+        for dnsbl in dnsbls {
+            let (resolver, dnsbl) = (self.resolver.clone(), dnsbl.clone());
+            checks.spawn(async move {
+                let query = dnsbl_query(ip, &dnsbl.zone);
+                let answers = match resolver.lookup_ip(query.as_str()).await {
+                    Ok(answers) => answers,
+                    Err(e) => {
+                        tracing::trace!("DNSBL lookup {} failed: {}", query, e);
+                        return None;
+                    }
+                };
+                let listed = answers.iter().any(|answer| match answer {
+                    IpAddr::V4(answer) => {
+                        let [first, _, _, last] = answer.octets();
+                        first == 127 && (dnsbl.replies.is_empty() || dnsbl.replies.contains(&last))
+                    }
+                    IpAddr::V6(_) => false,
+                });
+                listed.then_some(dnsbl)
+            });
+        }
+        // Dropping the set cancels the checks still running
+        while let Some(result) = checks.join_next().await {
+            if let Ok(Some(dnsbl)) = result {
+                return Some(dnsbl);
+            }
+        }
+        None
+    }
+
     /// Perform reverse DNS lookup (IP to hostname)
     pub async fn reverse_lookup(&self, ip: IpAddr) -> LookupResult {
         if !self.reverse_enabled {
@@ -284,6 +379,51 @@ impl IdentClient {
     }
 }
 
+/// Longest hostname shown for a client, as in other IRC servers
+const MAX_HOSTNAME_LEN: usize = 63;
+
+/// A reverse DNS answer in the form shown to users, if it is usable
+///
+/// Drops the root dot and lowercases. Names with characters hostmasks
+/// can't carry, empty labels, or that read as an IP address are refused.
+fn normalize_hostname(name: &str) -> Option<String> {
+    let hostname = name.strip_suffix('.').unwrap_or(name).to_ascii_lowercase();
+    let valid = hostname.len() <= MAX_HOSTNAME_LEN
+        && crate::utils::string::is_valid_hostname(&hostname)
+        && hostname.split('.').all(|label| !label.is_empty() && !label.starts_with('-') && !label.ends_with('-'))
+        && hostname.parse::<IpAddr>().is_err();
+    valid.then_some(hostname)
+}
+
+/// The name looked up to check `ip` against the blocklist `zone`
+///
+/// IPv4 addresses are written octet by octet and IPv6 addresses nibble by
+/// nibble, in reverse, as in `1.2.0.192.dnsbl.example.`.
+pub fn dnsbl_query(ip: IpAddr, zone: &str) -> String {
+    let labels: Vec<String> = match ip.to_canonical() {
+        IpAddr::V4(ip) => ip.octets().iter().rev().map(|octet| octet.to_string()).collect(),
+        IpAddr::V6(ip) => ip.octets().iter().rev()
+            .flat_map(|byte| [byte & 0xf, byte >> 4])
+            .map(|nibble| format!("{:x}", nibble))
+            .collect(),
+    };
+    format!("{}.{}.", labels.join("."), zone.trim_end_matches('.'))
+}
+
+/// How a client without a usable hostname is shown
+///
+/// IPv4-mapped IPv6 addresses are shown as IPv4, and IPv6 addresses that
+/// start with ':' get a leading '0' so they can't be mistaken for a
+/// trailing parameter.
+pub fn ip_host(ip: IpAddr) -> String {
+    let host = ip.to_canonical().to_string();
+    if host.starts_with(':') {
+        format!("0{}", host)
+    } else {
+        host
+    }
+}
+
 /// Combined lookup service that handles both DNS and ident lookups
 pub struct LookupService {
     dns_resolver: DnsResolver,
@@ -311,6 +451,11 @@ impl LookupService {
         self.dns_resolver.reverse_lookup(ip).await
     }
 
+    /// Forward-confirmed reverse DNS name for a client's address
+    pub async fn client_hostname(&self, ip: IpAddr) -> Option<String> {
+        self.dns_resolver.client_hostname(ip).await
+    }
+
     /// Perform forward DNS lookup
     pub async fn forward_dns_lookup(&self, hostname: &str) -> LookupResult {
         self.dns_resolver.forward_lookup(hostname).await
@@ -320,6 +465,71 @@ impl LookupService {
     pub async fn ident_lookup(&self, client_addr: SocketAddr, server_addr: SocketAddr) -> IdentResult {
         self.ident_client.lookup(client_addr, server_addr).await
     }
+
+    /// Whether connecting clients have any lookups to wait for
+    pub fn has_client_lookups(&self, dnsbls: &[DnsblConfig]) -> bool {
+        (self.dns_resolver.enabled && self.dns_resolver.reverse_enabled)
+            || self.ident_client.enabled
+            || (self.dns_resolver.enabled && !dnsbls.is_empty())
+    }
+
+    /// Run a connecting client's reverse DNS, ident and DNSBL lookups side
+    /// by side
+    ///
+    /// Each lookup gives up once `budget` has passed, keeping whatever the
+    /// others found. `notice` is handed the usual `*** ...` progress lines
+    /// to pass on to the client.
+    pub async fn lookup_client(
+        &self,
+        client_addr: SocketAddr,
+        server_addr: SocketAddr,
+        budget: Duration,
+        dnsbls: &[DnsblConfig],
+        notice: &(dyn Fn(&str) + Send + Sync),
+    ) -> ClientLookup {
+        let deadline = tokio::time::Instant::now() + budget;
+        let dns = self.dns_resolver.enabled && self.dns_resolver.reverse_enabled;
+        let ident = self.ident_client.enabled;
+        if dns {
+            notice("*** Looking up your hostname...");
+        }
+        if ident {
+            notice("*** Checking Ident");
+        }
+
+        let hostname = async {
+            if !dns {
+                return None;
+            }
+            let hostname = timeout_at(deadline, self.client_hostname(client_addr.ip())).await.ok().flatten();
+            notice(if hostname.is_some() { "*** Found your hostname" } else { "*** Couldn't look up your hostname" });
+            hostname
+        };
+        let ident_username = async {
+            if !ident {
+                return None;
+            }
+            let username = match timeout_at(deadline, self.ident_lookup(client_addr, server_addr)).await {
+                Ok(IdentResult { username: Some(username), success: true, .. }) => Some(username),
+                Ok(result) => {
+                    tracing::debug!("Ident lookup failed for {}: {:?}", client_addr, result.error);
+                    None
+                }
+                Err(_) => {
+                    tracing::debug!("Ident lookup for {} ran out of time", client_addr);
+                    None
+                }
+            };
+            notice(if username.is_some() { "*** Got Ident response" } else { "*** No Ident response" });
+            username
+        };
+        let dnsbl = async {
+            timeout_at(deadline, self.dns_resolver.dnsbl_listing(client_addr.ip(), dnsbls)).await.ok().flatten()
+        };
+
+        let (hostname, ident, dnsbl) = tokio::join!(hostname, ident_username, dnsbl);
+        ClientLookup { hostname, ident, dnsbl }
+    }
 }
 
 #[cfg(test)]
@@ -348,5 +558,53 @@ mod tests {
         assert!(!result.success);
         assert!(result.error.is_some());
         assert!(result.error.unwrap().contains("disabled"));
+        assert!(resolver.client_hostname(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1))).await.is_none());
+    }
+
+    #[test]
+    fn test_normalize_hostname() {
+        assert_eq!(normalize_hostname("Host-1.Example.COM.").as_deref(), Some("host-1.example.com"));
+        assert!(normalize_hostname("").is_none());
+        assert!(normalize_hostname(".").is_none());
+        assert!(normalize_hostname("bad..example.com").is_none());
+        assert!(normalize_hostname("-bad.example.com").is_none());
+        assert!(normalize_hostname("evil@example.com").is_none());
+        assert!(normalize_hostname("under_score.example.com").is_none());
+        assert!(normalize_hostname("10.0.0.1").is_none());
+        assert!(normalize_hostname(&format!("{}.com", "a".repeat(60))).is_none());
+    }
+
+    #[test]
+    fn test_dnsbl_query() {
+        assert_eq!(dnsbl_query("192.0.2.1".parse().unwrap(), "dnsbl.example"), "1.2.0.192.dnsbl.example.");
+        assert_eq!(dnsbl_query("::ffff:192.0.2.1".parse().unwrap(), "dnsbl.example."), "1.2.0.192.dnsbl.example.");
+        let query = dnsbl_query("2001:db8::1".parse().unwrap(), "dnsbl.example");
+        assert!(query.starts_with("1.0.0.0.0.0.0.0."));
+        assert!(query.ends_with(".8.b.d.0.1.0.0.2.dnsbl.example."));
+        assert_eq!(query.split('.').count(), 32 + 3);
+    }
+
+    #[tokio::test]
+    async fn test_lookup_client_disabled() {
+        let service = LookupService::new(false, false, false).await.unwrap();
+        assert!(!service.has_client_lookups(&[]));
+        let notices = std::sync::Mutex::new(Vec::new());
+        let lookup = service.lookup_client(
+            "192.0.2.1:1234".parse().unwrap(),
+            "192.0.2.2:6667".parse().unwrap(),
+            Duration::from_secs(1),
+            &[],
+            &|text| notices.lock().unwrap().push(text.to_string()),
+        ).await;
+        assert!(lookup.hostname.is_none() && lookup.ident.is_none() && lookup.dnsbl.is_none());
+        assert!(notices.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_ip_host() {
+        assert_eq!(ip_host("192.0.2.1".parse().unwrap()), "192.0.2.1");
+        assert_eq!(ip_host("::ffff:192.0.2.1".parse().unwrap()), "192.0.2.1");
+        assert_eq!(ip_host("2001:db8::1".parse().unwrap()), "2001:db8::1");
+        assert_eq!(ip_host("::1".parse().unwrap()), "0::1");
     }
 }
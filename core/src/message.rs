@@ -3,6 +3,7 @@
 //! This module implements the IRC message format as defined in RFC 1459.
 
 use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
 use std::fmt;
 
 /// IRC message prefix (server or user)
@@ -53,6 +54,7 @@ pub enum MessageType {
     Version,
     Stats,
     Links,
+    Map,
     Time,
     Connect,
     Trace,
@@ -72,6 +74,7 @@ pub enum MessageType {
     
     // Miscellaneous
     Kill,
+    Rehash,
     Ping,
     Pong,
     Error,
@@ -85,6 +88,8 @@ pub enum MessageType {
     ServerBurst,
     UserBurst,
     ChannelBurst,
+    /// Module-defined command tunneled between servers
+    Encap,
     ServerPing,
     ServerPong,
     
@@ -117,6 +122,7 @@ impl fmt::Display for MessageType {
             MessageType::Version => "VERSION",
             MessageType::Stats => "STATS",
             MessageType::Links => "LINKS",
+            MessageType::Map => "MAP",
             MessageType::Time => "TIME",
             MessageType::Connect => "CONNECT",
             MessageType::Trace => "TRACE",
@@ -130,6 +136,7 @@ impl fmt::Display for MessageType {
             MessageType::Whois => "WHOIS",
             MessageType::Whowas => "WHOWAS",
             MessageType::Kill => "KILL",
+            MessageType::Rehash => "REHASH",
             MessageType::Ping => "PING",
             MessageType::Pong => "PONG",
             MessageType::Error => "ERROR",
@@ -141,6 +148,7 @@ impl fmt::Display for MessageType {
             MessageType::ServerBurst => "BURST",
             MessageType::UserBurst => "UBURST",
             MessageType::ChannelBurst => "CBURST",
+            MessageType::Encap => "ENCAP",
             MessageType::ServerPing => "PING",
             MessageType::ServerPong => "PONG",
             MessageType::Cap => "CAP",
@@ -153,7 +161,12 @@ impl fmt::Display for MessageType {
 
 impl From<&str> for MessageType {
     fn from(s: &str) -> Self {
-        match s.to_uppercase().as_str() {
+        let upper = if s.bytes().any(|b| b.is_ascii_lowercase()) {
+            Cow::Owned(s.to_ascii_uppercase())
+        } else {
+            Cow::Borrowed(s)
+        };
+        match upper.as_ref() {
             "PASS" => MessageType::Password,
             "NICK" => MessageType::Nick,
             "USER" => MessageType::User,
@@ -172,6 +185,7 @@ impl From<&str> for MessageType {
             "VERSION" => MessageType::Version,
             "STATS" => MessageType::Stats,
             "LINKS" => MessageType::Links,
+            "MAP" => MessageType::Map,
             "TIME" => MessageType::Time,
             "CONNECT" => MessageType::Connect,
             "TRACE" => MessageType::Trace,
@@ -185,6 +199,7 @@ impl From<&str> for MessageType {
             "WHOIS" => MessageType::Whois,
             "WHOWAS" => MessageType::Whowas,
             "KILL" => MessageType::Kill,
+            "REHASH" => MessageType::Rehash,
             "PING" => MessageType::Ping,
             "PONG" => MessageType::Pong,
             "ERROR" => MessageType::Error,
@@ -196,6 +211,7 @@ impl From<&str> for MessageType {
             "BURST" => MessageType::ServerBurst,
             "UBURST" => MessageType::UserBurst,
             "CBURST" => MessageType::ChannelBurst,
+            "ENCAP" => MessageType::Encap,
             "CAP" => MessageType::Cap,
             "AUTHENTICATE" => MessageType::Authenticate,
             _ => MessageType::Custom(s.to_string()),
@@ -203,9 +219,14 @@ impl From<&str> for MessageType {
     }
 }
 
-/// IRC message as defined in RFC 1459
+/// IRC message as defined in RFC 1459, with IRCv3 message tags
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Message {
+    /// IRCv3 tags as unescaped key/value pairs, in the order received
+    ///
+    /// A tag sent without a value has an empty value.
+    #[serde(default)]
+    pub tags: Vec<(String, String)>,
     /// Optional prefix (server or user)
     pub prefix: Option<Prefix>,
     /// Message command/type
@@ -218,6 +239,7 @@ impl Message {
     /// Create a new message
     pub fn new(command: MessageType, params: Vec<String>) -> Self {
         Self {
+            tags: Vec::new(),
             prefix: None,
             command,
             params,
@@ -227,99 +249,351 @@ impl Message {
     /// Create a new message with prefix
     pub fn with_prefix(prefix: Prefix, command: MessageType, params: Vec<String>) -> Self {
         Self {
+            tags: Vec::new(),
             prefix: Some(prefix),
             command,
             params,
         }
     }
     
-    /// Parse an IRC message from a string
+    /// Value of a tag, empty when the tag was sent without one
+    pub fn tag(&self, key: &str) -> Option<&str> {
+        self.tags.iter().find(|(k, _)| k == key).map(|(_, v)| v.as_str())
+    }
+    
+    /// Set a tag, replacing any existing value
+    pub fn set_tag(&mut self, key: &str, value: &str) {
+        match self.tags.iter_mut().find(|(k, _)| k == key) {
+            Some(tag) => tag.1 = value.to_string(),
+            None => self.tags.push((key.to_string(), value.to_string())),
+        }
+    }
+    
+    /// Remove a tag
+    pub fn remove_tag(&mut self, key: &str) {
+        self.tags.retain(|(k, _)| k != key);
+    }
+    
+    /// Parse an IRC message from a line, with or without its CRLF
+    ///
+    /// Validates as `MessageRef::parse` does and copies the result into
+    /// owned strings. Paths that only inspect or relay a line should use
+    /// `MessageRef` directly.
     pub fn parse(input: &str) -> crate::Result<Self> {
-        let input = input.trim();
-        if input.is_empty() {
-            return Err(crate::Error::MessageParse("Empty message".to_string()));
+        MessageRef::parse(input).map(|message| message.to_message())
+    }
+    
+    /// Serialize message to a CRLF-terminated line
+    ///
+    /// NUL, CR and LF are dropped from the prefix and parameters, parameters
+    /// past the 15th are dropped, and the line without tags is cut at a
+    /// character boundary to fit `MAX_LINE_LENGTH`. Tags that would push the
+    /// tag section past `MAX_TAGS_LENGTH` are left off.
+    pub fn to_string(&self) -> String {
+        let mut result = String::new();
+        
+        let mut section = String::new();
+        for (key, value) in &self.tags {
+            let mut tag = key.clone();
+            if !value.is_empty() {
+                tag.push('=');
+                tag.push_str(&escape_tag_value(value));
+            }
+            // One byte for the '@' or ';' before the tag, one for the final space
+            if section.len() + tag.len() + 2 > MAX_TAGS_LENGTH {
+                continue;
+            }
+            section.push(if section.is_empty() { '@' } else { ';' });
+            section.push_str(&tag);
+        }
+        if !section.is_empty() {
+            result.push_str(&section);
+            result.push(' ');
         }
         
-        let parts = input.split_whitespace().collect::<Vec<_>>();
-        if parts.is_empty() {
-            return Err(crate::Error::MessageParse("No command found".to_string()));
+        let mut line = String::new();
+        if let Some(ref prefix) = self.prefix {
+            line.push(':');
+            push_line_safe(&mut line, &prefix.to_string());
+            line.push(' ');
         }
         
-        let (prefix, command_str) = if parts[0].starts_with(':') {
-            let prefix_str = &parts[0][1..];
-            let prefix = if prefix_str.contains('!') {
-                // User prefix: nick!user@host
-                let parts: Vec<&str> = prefix_str.split('!').collect();
-                if parts.len() != 2 {
-                    return Err(crate::Error::MessageParse("Invalid user prefix format".to_string()));
-                }
-                let user_host: Vec<&str> = parts[1].split('@').collect();
-                if user_host.len() != 2 {
-                    return Err(crate::Error::MessageParse("Invalid user prefix format".to_string()));
-                }
-                Prefix::User {
-                    nick: parts[0].to_string(),
-                    user: user_host[0].to_string(),
-                    host: user_host[1].to_string(),
+        line.push_str(&self.command.to_string());
+        
+        let params = &self.params[..self.params.len().min(MAX_PARAMS)];
+        for (i, param) in params.iter().enumerate() {
+            line.push(' ');
+            if i == params.len() - 1 && (param.is_empty() || param.contains(' ') || param.starts_with(':')) {
+                line.push(':');
+            }
+            push_line_safe(&mut line, param);
+        }
+        
+        let mut end = line.len().min(MAX_LINE_LENGTH - 2);
+        while !line.is_char_boundary(end) {
+            end -= 1;
+        }
+        line.truncate(end);
+        
+        result.push_str(&line);
+        result.push_str("\r\n");
+        result
+    }
+}
+
+/// Append `text` without the characters that would end or corrupt a line
+fn push_line_safe(line: &mut String, text: &str) {
+    line.extend(text.chars().filter(|c| !matches!(c, '\0' | '\r' | '\n')));
+}
+
+/// Split `nick!user@host` into its parts, `None` for a server name
+fn split_prefix(prefix: &str) -> crate::Result<Option<(&str, &str, &str)>> {
+    if prefix.is_empty() {
+        return Err(crate::Error::MessageParse("Empty prefix".to_string()));
+    }
+    let Some((nick, user_host)) = prefix.split_once('!') else {
+        return Ok(None);
+    };
+    let (user, host) = user_host.split_once('@')
+        .filter(|(user, host)| !user.contains('!') && !host.contains('@'))
+        .ok_or_else(|| crate::Error::MessageParse("Invalid user prefix format".to_string()))?;
+    Ok(Some((nick, user, host)))
+}
+
+/// Split a tag into its key and still-escaped value
+fn split_tag(tag: &str) -> (&str, &str) {
+    tag.split_once('=').unwrap_or((tag, ""))
+}
+
+/// Unescape a tag value, borrowing it when there is nothing to undo
+fn unescape_tag_cow(value: &str) -> Cow<'_, str> {
+    if value.contains('\\') {
+        Cow::Owned(unescape_tag_value(value))
+    } else {
+        Cow::Borrowed(value)
+    }
+}
+
+/// Check every key in the tag section after the '@'
+fn validate_tags(section: &str) -> crate::Result<()> {
+    for tag in section.split(';').filter(|tag| !tag.is_empty()) {
+        let (key, _) = split_tag(tag);
+        if !is_valid_tag_key(key) {
+            return Err(crate::Error::MessageParse(format!("Invalid tag key: {}", key)));
+        }
+    }
+    Ok(())
+}
+
+/// Whether `key` is `[+][vendor/]name` with a hostname vendor and a name of letters, digits and '-'
+fn is_valid_tag_key(key: &str) -> bool {
+    let key = key.strip_prefix('+').unwrap_or(key);
+    let (vendor, name) = match key.rsplit_once('/') {
+        Some((vendor, name)) => (Some(vendor), name),
+        None => (None, key),
+    };
+    let vendor_ok = vendor.is_none_or(|vendor| {
+        !vendor.is_empty() && vendor.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'.' || b == b'-')
+    });
+    vendor_ok && !name.is_empty() && name.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'-')
+}
+
+/// Escape a tag value as IRCv3 message-tags specifies
+pub fn escape_tag_value(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            ';' => escaped.push_str("\\:"),
+            ' ' => escaped.push_str("\\s"),
+            '\\' => escaped.push_str("\\\\"),
+            '\r' => escaped.push_str("\\r"),
+            '\n' => escaped.push_str("\\n"),
+            '\0' => {}
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Undo `escape_tag_value`
+///
+/// An unknown escape stands for the character after the backslash, and a
+/// lone trailing backslash is dropped.
+pub fn unescape_tag_value(value: &str) -> String {
+    let mut unescaped = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some(':') => unescaped.push(';'),
+            Some('s') => unescaped.push(' '),
+            Some('r') => unescaped.push('\r'),
+            Some('n') => unescaped.push('\n'),
+            Some(other) => unescaped.push(other),
+            None => {}
+        }
+    }
+    unescaped
+}
+
+/// A message borrowing from the line it was parsed from
+///
+/// Parsing allocates nothing: parameters are slices of the line and tag
+/// values stay escaped until asked for. Relay paths inspect this and call
+/// `to_message` only for messages they keep.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MessageRef<'a> {
+    /// Tag section without the leading '@', still escaped
+    tags: &'a str,
+    prefix: Option<&'a str>,
+    command: &'a str,
+    params: [&'a str; MAX_PARAMS],
+    param_count: usize,
+}
+
+impl<'a> MessageRef<'a> {
+    /// Parse an IRC message from a line, with or without its CRLF
+    ///
+    /// Rejects lines longer than `MAX_LINE_LENGTH` (not counting tags) with
+    /// `Error::InputTooLong`, tag sections longer than `MAX_TAGS_LENGTH`,
+    /// NUL or embedded CR/LF, malformed tags and prefixes, and commands that
+    /// aren't letters or a three-digit numeric. Anything after the 14th
+    /// middle parameter becomes the 15th, as RFC 1459 specifies.
+    pub fn parse(input: &'a str) -> crate::Result<Self> {
+        let input = input.trim_end_matches(['\r', '\n']).trim_start_matches(' ');
+        if input.contains(['\0', '\r', '\n']) {
+            return Err(crate::Error::MessageParse("Message contains NUL, CR or LF".to_string()));
+        }
+        
+        let (tags, rest) = match input.strip_prefix('@') {
+            Some(tagged) => {
+                let (section, rest) = tagged.split_once(' ')
+                    .ok_or_else(|| crate::Error::MessageParse("No command found".to_string()))?;
+                // The limit counts the leading '@' and the space after the tags
+                if section.len() + 2 > MAX_TAGS_LENGTH {
+                    return Err(crate::Error::InputTooLong);
                 }
-            } else {
-                // Server prefix
-                Prefix::Server(prefix_str.to_string())
-            };
-            (Some(prefix), parts[1])
-        } else {
-            (None, parts[0])
+                validate_tags(section)?;
+                (section, rest.trim_start_matches(' '))
+            }
+            None => ("", input),
         };
+        if rest.len() + 2 > MAX_LINE_LENGTH {
+            return Err(crate::Error::InputTooLong);
+        }
+        if rest.is_empty() {
+            return Err(crate::Error::MessageParse("Empty message".to_string()));
+        }
         
-        let command = MessageType::from(command_str);
-        let params = if parts.len() > 1 {
-            let start_idx = if prefix.is_some() { 2 } else { 1 };
-            let mut params = Vec::new();
-            
-            for (i, part) in parts.iter().enumerate().skip(start_idx) {
-                if part.starts_with(':') {
-                    // Last parameter can contain spaces
-                    let last_param = &parts[start_idx + i..].join(" ");
-                    params.push(last_param[1..].to_string());
-                    break;
-                } else {
-                    params.push(part.to_string());
-                }
+        let (prefix, rest) = match rest.strip_prefix(':') {
+            Some(prefixed) => {
+                let (prefix, rest) = prefixed.split_once(' ')
+                    .ok_or_else(|| crate::Error::MessageParse("No command found".to_string()))?;
+                split_prefix(prefix)?;
+                (Some(prefix), rest.trim_start_matches(' '))
             }
-            params
-        } else {
-            Vec::new()
+            None => (None, rest),
         };
         
-        Ok(Message {
+        let (command, mut rest) = rest.split_once(' ').unwrap_or((rest, ""));
+        let is_numeric = command.len() == 3 && command.bytes().all(|b| b.is_ascii_digit());
+        if command.is_empty() || !(is_numeric || command.bytes().all(|b| b.is_ascii_alphabetic())) {
+            return Err(crate::Error::MessageParse(format!("Invalid command: {}", command)));
+        }
+        
+        let mut params = [""; MAX_PARAMS];
+        let mut param_count = 0;
+        loop {
+            rest = rest.trim_start_matches(' ');
+            if rest.is_empty() {
+                break;
+            }
+            if let Some(trailing) = rest.strip_prefix(':') {
+                params[param_count] = trailing;
+                param_count += 1;
+                break;
+            }
+            if param_count == MAX_PARAMS - 1 {
+                params[param_count] = rest;
+                param_count += 1;
+                break;
+            }
+            let (param, remainder) = rest.split_once(' ').unwrap_or((rest, ""));
+            params[param_count] = param;
+            param_count += 1;
+            rest = remainder;
+        }
+        
+        Ok(Self {
+            tags,
             prefix,
             command,
             params,
+            param_count,
         })
     }
     
-    /// Serialize message to string
-    pub fn to_string(&self) -> String {
-        let mut result = String::new();
-        
-        if let Some(ref prefix) = self.prefix {
-            result.push(':');
-            result.push_str(&prefix.to_string());
-            result.push(' ');
-        }
-        
-        result.push_str(&self.command.to_string());
-        
-        for (i, param) in self.params.iter().enumerate() {
-            result.push(' ');
-            if i == self.params.len() - 1 && param.contains(' ') {
-                result.push(':');
+    /// Command as sent, numerics included
+    pub fn command(&self) -> &'a str {
+        self.command
+    }
+    
+    /// Whether the command is `command`, ignoring case
+    pub fn is_command(&self, command: &str) -> bool {
+        self.command.eq_ignore_ascii_case(command)
+    }
+    
+    /// Prefix without the leading ':'
+    pub fn prefix(&self) -> Option<&'a str> {
+        self.prefix
+    }
+    
+    /// Parameters, the trailing one without its ':'
+    pub fn params(&self) -> &[&'a str] {
+        &self.params[..self.param_count]
+    }
+    
+    /// Tags in the order sent, duplicates included, with unescaped values
+    pub fn tags(&self) -> impl Iterator<Item = (&'a str, Cow<'a, str>)> {
+        self.tags.split(';')
+            .filter(|tag| !tag.is_empty())
+            .map(|tag| {
+                let (key, value) = split_tag(tag);
+                (key, unescape_tag_cow(value))
+            })
+    }
+    
+    /// Value of a tag, the last one when sent more than once
+    pub fn tag(&self, key: &str) -> Option<Cow<'a, str>> {
+        self.tags().filter(|(k, _)| *k == key).last().map(|(_, value)| value)
+    }
+    
+    /// Copy into an owned `Message`, later duplicate tags winning
+    pub fn to_message(&self) -> Message {
+        let mut tags: Vec<(String, String)> = Vec::new();
+        for (key, value) in self.tags() {
+            match tags.iter_mut().find(|(k, _)| k == key) {
+                Some(existing) => existing.1 = value.into_owned(),
+                None => tags.push((key.to_string(), value.into_owned())),
             }
-            result.push_str(param);
         }
-        
-        result.push_str("\r\n");
-        result
+        let prefix = self.prefix.map(|prefix| match split_prefix(prefix) {
+            Ok(Some((nick, user, host))) => Prefix::User {
+                nick: nick.to_string(),
+                user: user.to_string(),
+                host: host.to_string(),
+            },
+            _ => Prefix::Server(prefix.to_string()),
+        });
+        Message {
+            tags,
+            prefix,
+            command: MessageType::from(self.command),
+            params: self.params().iter().map(|param| param.to_string()).collect(),
+        }
     }
 }
 
@@ -329,6 +603,94 @@ impl fmt::Display for Message {
     }
 }
 
+/// Longest line a client or server may send, CRLF included and tags excluded
+pub const MAX_LINE_LENGTH: usize = 512;
+
+/// Longest tag section, from the leading '@' through the space after it
+pub const MAX_TAGS_LENGTH: usize = 8191;
+
+/// Most parameters a message may carry
+pub const MAX_PARAMS: usize = 15;
+
+/// Placeholder written in place of sensitive parameters
+pub const REDACTED: &str = "<redacted>";
+
+/// Channel modes that take a parameter when set or unset
+const CHANNEL_MODES_WITH_PARAM: &str = "beIkohvqa";
+
+/// Channel modes that take a parameter only when set
+const CHANNEL_MODES_WITH_SET_PARAM: &str = "lfj";
+
+impl Message {
+    /// Wrap the message for logging with sensitive parameters masked
+    ///
+    /// Masks every parameter of PASS and AUTHENTICATE, the password of OPER,
+    /// and channel keys given with JOIN or MODE +k/-k. Always log messages through this.
+    pub fn redacted(&self) -> RedactedMessage<'_> {
+        RedactedMessage(self)
+    }
+    
+    /// Indices of parameters that must not be logged
+    fn sensitive_param_indices(&self) -> Vec<usize> {
+        match &self.command {
+            MessageType::Password | MessageType::Authenticate => (0..self.params.len()).collect(),
+            MessageType::Oper => (1..self.params.len()).collect(),
+            MessageType::Join if self.params.len() > 1 => vec![1],
+            MessageType::Mode => {
+                let Some(mode_string) = self.params.get(1) else {
+                    return Vec::new();
+                };
+                let mut indices = Vec::new();
+                let mut adding = true;
+                let mut next_param = 2;
+                for c in mode_string.chars() {
+                    match c {
+                        '+' => adding = true,
+                        '-' => adding = false,
+                        _ if CHANNEL_MODES_WITH_PARAM.contains(c)
+                            || (adding && CHANNEL_MODES_WITH_SET_PARAM.contains(c)) =>
+                        {
+                            if c == 'k' {
+                                indices.push(next_param);
+                            }
+                            next_param += 1;
+                        }
+                        _ => {}
+                    }
+                }
+                indices
+            }
+            _ => Vec::new(),
+        }
+    }
+}
+
+/// Display wrapper that masks sensitive parameters, see [`Message::redacted`]
+pub struct RedactedMessage<'a>(&'a Message);
+
+impl fmt::Display for RedactedMessage<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let sensitive = self.0.sensitive_param_indices();
+        if sensitive.is_empty() {
+            return write!(f, "{}", self.0);
+        }
+        
+        let mut masked = self.0.clone();
+        for i in sensitive {
+            if let Some(param) = masked.params.get_mut(i) {
+                *param = REDACTED.to_string();
+            }
+        }
+        write!(f, "{}", masked)
+    }
+}
+
+impl fmt::Debug for RedactedMessage<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -341,6 +703,14 @@ mod tests {
         assert!(msg.prefix.is_none());
     }
     
+    #[test]
+    fn test_trailing_parameter_serialization() {
+        let ison = Message::new(MessageType::Ison, vec!["alice".to_string(), String::new()]);
+        assert_eq!(ison.to_string(), "ISON alice :\r\n");
+        let smiley = Message::new(MessageType::PrivMsg, vec!["#rust".to_string(), ":)".to_string()]);
+        assert_eq!(Message::parse(&smiley.to_string()).unwrap().params, vec!["#rust", ":)"]);
+    }
+    
     #[test]
     fn test_parse_message_with_prefix() {
         let msg = Message::parse(":alice!user@host PRIVMSG #channel :Hello world").unwrap();
@@ -356,6 +726,165 @@ mod tests {
         assert_eq!(msg.params, vec!["#channel", "Hello world"]);
     }
     
+    #[test]
+    fn test_redacted_message() {
+        let pass = Message::parse("PASS hunter2").unwrap();
+        assert_eq!(pass.redacted().to_string(), "PASS <redacted>");
+        
+        let oper = Message::parse("OPER admin hunter2").unwrap();
+        assert_eq!(oper.redacted().to_string(), "OPER admin <redacted>");
+        
+        let auth = Message::parse("AUTHENTICATE dXNlcgB1c2VyAGh1bnRlcjI=").unwrap();
+        assert!(!auth.redacted().to_string().contains("dXNl"));
+        
+        let mode = Message::parse("MODE #chan +olk alice 10 secret").unwrap();
+        assert_eq!(mode.redacted().to_string(), "MODE #chan +olk alice 10 <redacted>");
+        
+        let mode = Message::parse("MODE #chan -lk secret").unwrap();
+        assert_eq!(mode.redacted().to_string(), "MODE #chan -lk <redacted>");
+        
+        let join = Message::parse("JOIN #chan,#other secret,hunter2").unwrap();
+        assert_eq!(join.redacted().to_string(), "JOIN #chan,#other <redacted>");
+        
+        let join = Message::parse("JOIN #chan").unwrap();
+        assert_eq!(join.redacted().to_string(), "JOIN #chan");
+        
+        let privmsg = Message::new(MessageType::PrivMsg, vec!["#chan".to_string(), "hello there".to_string()]);
+        assert_eq!(privmsg.redacted().to_string(), "PRIVMSG #chan :hello there");
+    }
+    
+    #[test]
+    fn test_line_and_parameter_limits() {
+        let long = format!("PRIVMSG #rust :{}", "a".repeat(MAX_LINE_LENGTH));
+        assert!(matches!(Message::parse(&long), Err(crate::Error::InputTooLong)));
+        let fits = format!("PRIVMSG #rust :{}", "a".repeat(MAX_LINE_LENGTH - 2 - "PRIVMSG #rust :".len()));
+        assert!(Message::parse(&fits).is_ok());
+        // Tags don't count against the 512 bytes
+        assert!(Message::parse(&format!("@label={} {}", "x".repeat(1000), fits)).is_ok());
+        let tags = format!("@label={} PING x", "x".repeat(MAX_TAGS_LENGTH));
+        assert!(matches!(Message::parse(&tags), Err(crate::Error::InputTooLong)));
+        
+        let many = Message::parse("CMD 1 2 3 4 5 6 7 8 9 10 11 12 13 14 15  16 :17").unwrap();
+        assert_eq!(many.params.len(), MAX_PARAMS);
+        assert_eq!(many.params[14], "15  16 :17");
+        
+        let spaced = Message::parse("PRIVMSG   #rust   :two  spaces ").unwrap();
+        assert_eq!(spaced.params, vec!["#rust", "two  spaces "]);
+        
+        for bad in ["", ":server", ":server ", "@a=b", "PRIV\0MSG x", "PRIVMSG #a :b\rNICK x", "NICK_ x", "12 x", ": PING"] {
+            assert!(Message::parse(bad).is_err(), "{:?} should not parse", bad);
+        }
+        assert!(Message::parse(":a!b PING x").is_err());
+        assert!(Message::parse(":a!b@c@d PING x").is_err());
+    }
+    
+    #[test]
+    fn test_output_truncation() {
+        let long = Message::with_prefix(
+            Prefix::Server("irc.example.com".to_string()),
+            MessageType::PrivMsg,
+            vec!["#rust".to_string(), "é".repeat(MAX_LINE_LENGTH)],
+        );
+        let line = long.to_string();
+        assert!(line.len() <= MAX_LINE_LENGTH);
+        assert!(line.ends_with("é\r\n"));
+        
+        let injected = Message::new(MessageType::PrivMsg, vec!["#rust".to_string(), "hi\r\nQUIT :bye\0".to_string()]);
+        assert_eq!(injected.to_string(), "PRIVMSG #rust :hiQUIT :bye\r\n");
+        
+        let params = (1..=20).map(|i| i.to_string()).collect();
+        assert_eq!(Message::parse(&Message::new(MessageType::Custom("CMD".to_string()), params).to_string()).unwrap().params.len(), MAX_PARAMS);
+        
+        let mut tagged = Message::new(MessageType::Ping, vec!["x".to_string()]);
+        tagged.set_tag("big", &"x".repeat(MAX_TAGS_LENGTH));
+        tagged.set_tag("time", "2024-01-01T00:00:00.000Z");
+        assert_eq!(tagged.to_string(), "@time=2024-01-01T00:00:00.000Z PING x\r\n");
+    }
+    
+    #[test]
+    fn test_tag_escaping() {
+        let msg = Message::parse("@+example.com/draft=a\\:b\\sc\\\\d\\r\\n\\x;flag;dup=1;dup=2 :nick!u@h TAGMSG #rust").unwrap();
+        assert_eq!(msg.tag("+example.com/draft"), Some("a;b c\\d\r\nx"));
+        assert_eq!(msg.tag("flag"), Some(""));
+        assert_eq!(msg.tag("dup"), Some("2"));
+        assert_eq!(msg.tags.len(), 3);
+        
+        let reparsed = Message::parse(&msg.to_string()).unwrap();
+        assert_eq!(reparsed, msg);
+        
+        assert!(Message::parse("@bad_key=1 PING x").is_err());
+        assert!(Message::parse("@/key=1 PING x").is_err());
+        assert_eq!(unescape_tag_value("trailing\\"), "trailing");
+    }
+    
+    #[test]
+    fn test_borrowed_parse() {
+        let line = "@time=2024-01-01T00:00:00.000Z;+draft=a\\sb :alice!user@host PRIVMSG #rust :Hello world\r\n";
+        let message = MessageRef::parse(line).unwrap();
+        assert!(message.is_command("privmsg"));
+        assert_eq!(message.prefix(), Some("alice!user@host"));
+        assert_eq!(message.params(), ["#rust", "Hello world"]);
+        // Parameters are slices of the line, and unescaped tags borrow too
+        assert!(line.as_bytes().as_ptr_range().contains(&message.params()[1].as_ptr()));
+        assert!(matches!(message.tag("time"), Some(Cow::Borrowed(_))));
+        assert_eq!(message.tag("+draft").as_deref(), Some("a b"));
+        assert_eq!(message.tag("missing"), None);
+        
+        let owned = message.to_message();
+        assert_eq!(owned, Message::parse(line).unwrap());
+        assert_eq!(owned.command, MessageType::PrivMsg);
+        assert_eq!(owned.tag("+draft"), Some("a b"));
+        assert!(MessageRef::parse(":a!b PING x").is_err());
+    }
+    
+    /// Random messages survive a serialize/parse round trip, and random bytes
+    /// either fail to parse or produce a message that serializes to one line
+    #[test]
+    fn test_parse_properties() {
+        use rand::{Rng, SeedableRng};
+        let mut rng = rand::rngs::StdRng::seed_from_u64(0x1459);
+        let word = |rng: &mut rand::rngs::StdRng, len: usize| -> String {
+            (0..rng.gen_range(1..=len)).map(|_| rng.gen_range(b'!'..=b'~') as char).filter(|c| *c != ':').collect::<String>()
+        };
+        
+        for _ in 0..2000 {
+            let mut message = Message::new(MessageType::from("PRIVMSG"), Vec::new());
+            for _ in 0..rng.gen_range(0..MAX_PARAMS - 1) {
+                let param = word(&mut rng, 10);
+                if !param.is_empty() {
+                    message.params.push(param);
+                }
+            }
+            let trailing: String = (0..rng.gen_range(0..40)).map(|_| rng.gen_range(b' '..=b'~') as char).collect();
+            message.params.push(trailing);
+            for i in 0..rng.gen_range(0..4) {
+                let value: String = (0..rng.gen_range(0..20)).map(|_| rng.gen_range(1u8..=127) as char).filter(|c| !c.is_ascii_control()).collect();
+                message.set_tag(&format!("+t{}", i), &value);
+            }
+            if rng.gen_bool(0.5) {
+                message.prefix = Some(Prefix::User { nick: "n".to_string(), user: "u".to_string(), host: "h".to_string() });
+            }
+            let parsed = Message::parse(&message.to_string()).unwrap();
+            assert_eq!(parsed, message, "round trip of {:?}", message.to_string());
+        }
+        
+        for _ in 0..5000 {
+            let bytes: Vec<u8> = (0..rng.gen_range(0..700)).map(|_| rng.gen_range(0u8..=255)).collect();
+            let input = String::from_utf8_lossy(&bytes);
+            if let Ok(message) = Message::parse(&input) {
+                let line = message.to_string();
+                let body = match line.strip_prefix('@') {
+                    Some(tagged) => tagged.split_once(' ').map_or("", |(_, body)| body),
+                    None => &line,
+                };
+                assert!(body.len() <= MAX_LINE_LENGTH);
+                assert_eq!(line.matches("\r\n").count(), 1);
+                assert!(!line.contains('\0'));
+                assert!(line.ends_with("\r\n"));
+            }
+        }
+    }
+    
     #[test]
     fn test_serialize_message() {
         let msg = Message::new(MessageType::Nick, vec!["alice".to_string()]);
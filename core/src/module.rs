@@ -1,26 +1,83 @@
 //! Module system for extensible IRC daemon
 
-use crate::{Client, Message, User, Result, ModuleNumericManager, Database, ServerConnectionManager, ChannelInfo, Config};
+use crate::{AuthFailureKind, AuthFailureOutcome, AuthManager, ThrottlingManager, Client, ClientSenders, Message, User, Result, ModuleNumericManager, Database, ServerConnectionManager, ChannelInfo, Config, BanManager, SnoMask, ServerEvent};
+use crate::audit::{AuditEvent, AuditTrail};
+use crate::commands::{CommandHandler, CommandInfo, CommandSpec, CommandTable, CommandTarget};
+use crate::events::EVENT_CHANNEL_CAPACITY;
+use crate::network::{self, EncapRegistry};
 use async_trait::async_trait;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
-use tokio::sync::RwLock;
+use tokio::sync::{broadcast, RwLock};
+use tracing::Instrument;
 use uuid::Uuid;
 
+/// Renames a module asked the server to make: client and new nickname
+pub type NickChange = (Uuid, String);
+
 /// Context provided to modules for database and server access
+#[derive(Clone)]
 pub struct ModuleContext {
     pub database: Arc<Database>,
     pub server_connections: Arc<ServerConnectionManager>,
+    /// Shared ban registry used by K/G/D/X-line style modules
+    pub ban_manager: Arc<BanManager>,
     /// Client connection manager for sending messages to users
     pub client_connections: Arc<RwLock<HashMap<Uuid, Arc<Client>>>>,
+    /// Audit trail of privileged operator actions, shared with the server
+    pub audit_trail: Arc<AuditTrail>,
+    /// Server event bus, shared with the server
+    pub events: broadcast::Sender<ServerEvent>,
+    /// Outbound queues of local clients, shared with the server
+    pub client_senders: Arc<ClientSenders>,
+    /// Authentication providers, shared with the server
+    pub auth_manager: Arc<AuthManager>,
+    /// Connection throttling and failed login tracking, shared with the server
+    pub throttling_manager: Arc<ThrottlingManager>,
+    /// Commands in the module manager's dispatch table
+    commands: Arc<parking_lot::RwLock<Vec<CommandInfo>>>,
+    /// Clients whose registration a module is holding, shared with the server
+    registration_holds: Arc<parking_lot::RwLock<HashSet<Uuid>>>,
+    /// Accounts unregistered clients logged in to, shared with the server
+    registration_accounts: Arc<parking_lot::RwLock<HashMap<Uuid, String>>>,
+    /// Clients a module asked the server to disconnect, with the reason
+    exit_requests: Arc<parking_lot::Mutex<Vec<(Uuid, String)>>>,
+    /// Channels a module asked the server to join clients to
+    join_requests: Arc<parking_lot::Mutex<Vec<(Uuid, String)>>>,
+    /// Capabilities each client enabled with CAP REQ
+    client_capabilities: Arc<parking_lot::RwLock<HashMap<Uuid, HashSet<String>>>>,
+    /// Forced nick changes for the server to carry out
+    nick_changes: tokio::sync::mpsc::UnboundedSender<NickChange>,
+    /// Receiving end of `nick_changes`, until the server takes it
+    nick_change_receiver: Arc<parking_lot::Mutex<Option<tokio::sync::mpsc::UnboundedReceiver<NickChange>>>>,
 }
 
 impl ModuleContext {
     pub fn new(database: Arc<Database>, server_connections: Arc<ServerConnectionManager>) -> Self {
+        Self::with_ban_manager(database, server_connections, Arc::new(BanManager::new()))
+    }
+
+    /// Create a context sharing an existing ban manager
+    pub fn with_ban_manager(database: Arc<Database>, server_connections: Arc<ServerConnectionManager>, ban_manager: Arc<BanManager>) -> Self {
+        let (nick_changes, nick_change_receiver) = tokio::sync::mpsc::unbounded_channel();
         Self {
             database,
             server_connections,
+            ban_manager,
             client_connections: Arc::new(RwLock::new(HashMap::new())),
+            audit_trail: Arc::new(AuditTrail::default()),
+            events: broadcast::channel(EVENT_CHANNEL_CAPACITY).0,
+            client_senders: Arc::new(ClientSenders::new()),
+            auth_manager: Arc::new(AuthManager::default()),
+            throttling_manager: Arc::new(ThrottlingManager::default()),
+            commands: Arc::new(parking_lot::RwLock::new(Vec::new())),
+            registration_holds: Arc::new(parking_lot::RwLock::new(HashSet::new())),
+            registration_accounts: Arc::new(parking_lot::RwLock::new(HashMap::new())),
+            exit_requests: Arc::new(parking_lot::Mutex::new(Vec::new())),
+            join_requests: Arc::new(parking_lot::Mutex::new(Vec::new())),
+            client_capabilities: Arc::new(parking_lot::RwLock::new(HashMap::new())),
+            nick_changes,
+            nick_change_receiver: Arc::new(parking_lot::Mutex::new(Some(nick_change_receiver))),
         }
     }
     
@@ -80,32 +137,182 @@ impl ModuleContext {
         self.server_connections.send_to_server(server_name, message).await
     }
     
+    /// Tunnel a module command to the servers matching `target` (a name or mask)
+    ///
+    /// A directly linked target gets it straight away; anything else is sent
+    /// to every link and relayed from there.
+    pub async fn send_encap(&self, target: &str, subcommand: &str, params: Vec<String>) -> Result<()> {
+        let message = network::encap_message(self.server_connections.local_server_name(), target, subcommand, params);
+        if self.server_connections.is_connected(target).await {
+            self.server_connections.send_to_server(target, message).await
+        } else {
+            self.server_connections.broadcast_to_servers(message).await
+        }
+    }
+    
     /// Send a message to a specific user
     pub async fn send_to_user(&self, nick: &str, message: Message) -> Result<()> {
         if let Some(user) = self.get_user_by_nick(nick) {
-            let client_connections = self.client_connections.read().await;
-            if let Some(client) = client_connections.get(&user.id) {
-                client.send(message)?;
-            }
+            self.client_senders.send(&user.id, message);
         }
         Ok(())
     }
     
-    /// Send a message to a channel
+    /// Send a message to the local members of a channel
     pub async fn send_to_channel(&self, channel: &str, message: Message) -> Result<()> {
-        let channel_users = self.get_channel_users(channel);
-        let client_connections = self.client_connections.read().await;
-        
-        for nick in channel_users {
-            if let Some(user) = self.get_user_by_nick(&nick) {
-                if let Some(client) = client_connections.get(&user.id) {
-                    let _ = client.send(message.clone());
-                }
+        let members = self.get_channel_users(channel).into_iter()
+            .filter_map(|nick| self.get_user_by_nick(&nick))
+            .map(|user| user.id);
+        self.client_senders.send_each(members, &message);
+        Ok(())
+    }
+    
+    /// Send a server notice to local operators subscribed to `mask`
+    pub async fn send_snotice(&self, mask: SnoMask, text: &str) -> Result<()> {
+        for user in self.database.get_all_users() {
+            if crate::snomask::wants_snotice(&user, mask) {
+                self.client_senders.send(&user.id, mask.notice(&user.nick, text));
             }
         }
         Ok(())
     }
     
+    /// Record a privileged action in the audit trail and echo it to the log channel
+    pub async fn audit(&self, event: AuditEvent) -> Result<()> {
+        self.audit_trail.record(&event);
+        if let Some((channel, notice)) = self.audit_trail.log_channel_notice(&event) {
+            self.send_to_channel(&channel, notice).await?;
+        }
+        self.publish(ServerEvent::OperAction(event));
+        Ok(())
+    }
+    
+    /// Publish an event to everything subscribed to the server event bus
+    pub fn publish(&self, event: ServerEvent) {
+        // Nobody listening is not an error
+        let _ = self.events.send(event);
+    }
+    
+    /// Subscribe to the server event bus
+    pub fn subscribe_events(&self) -> broadcast::Receiver<ServerEvent> {
+        self.events.subscribe()
+    }
+    
+    /// Commands registered with the module manager, sorted by name
+    pub fn commands(&self) -> Vec<CommandInfo> {
+        self.commands.read().clone()
+    }
+    
+    /// Keep an unregistered client from being welcomed until released
+    pub fn hold_registration(&self, client_id: Uuid) {
+        self.registration_holds.write().insert(client_id);
+    }
+    
+    /// Let a held client register
+    ///
+    /// The server finishes the registration once the message being handled
+    /// has been processed, if NICK, USER and CAP negotiation are done.
+    pub fn release_registration(&self, client_id: Uuid) {
+        self.registration_holds.write().remove(&client_id);
+    }
+    
+    /// Log an unregistered client in to `account`
+    ///
+    /// The account is set on the client's user when registration completes.
+    pub fn set_registration_account(&self, client_id: Uuid, account: &str) {
+        self.registration_accounts.write().insert(client_id, account.to_string());
+    }
+    
+    /// Account an unregistered client has logged in to, if any
+    pub fn registration_account(&self, client_id: Uuid) -> Option<String> {
+        self.registration_accounts.read().get(&client_id).cloned()
+    }
+    
+    /// Ask the server to disconnect a local client
+    ///
+    /// The client exits once the message being handled has been processed.
+    pub fn exit_client(&self, client_id: Uuid, reason: &str) {
+        self.exit_requests.lock().push((client_id, reason.to_string()));
+    }
+    
+    /// Ask the server to join a local client to a channel, as SVSJOIN does
+    ///
+    /// The JOIN runs once the message being handled has been processed, so
+    /// it goes through the usual channel checks with the user's new state.
+    pub fn force_join(&self, client_id: Uuid, channel: &str) {
+        self.join_requests.lock().push((client_id, channel.to_string()));
+    }
+    
+    /// Record a capability a client enabled or disabled with CAP REQ
+    pub fn set_client_capability(&self, client_id: Uuid, capability: &str, enabled: bool) {
+        let mut capabilities = self.client_capabilities.write();
+        if enabled {
+            capabilities.entry(client_id).or_default().insert(capability.to_string());
+        } else if let Some(enabled) = capabilities.get_mut(&client_id) {
+            enabled.remove(capability);
+        }
+    }
+    
+    /// Whether a client has enabled a capability, e.g. to pick how to tell it about a change
+    pub fn client_has_capability(&self, client_id: &Uuid, capability: &str) -> bool {
+        self.client_capabilities.read().get(client_id).is_some_and(|enabled| enabled.contains(capability))
+    }
+    
+    /// Forget the capabilities of a client that disconnected
+    pub fn forget_client_capabilities(&self, client_id: &Uuid) {
+        self.client_capabilities.write().remove(client_id);
+    }
+    
+    /// Ask the server to rename a local client, as services do with SVSNICK
+    ///
+    /// Unlike `exit_client` this may be called from a module's own tasks; the
+    /// server renames the client as soon as it picks up the request. Nicks
+    /// that are invalid or already taken are ignored.
+    pub fn force_nick(&self, client_id: Uuid, nick: &str) {
+        let _ = self.nick_changes.send((client_id, nick.to_string()));
+    }
+    
+    /// Take the queue `force_nick` feeds; only the first caller gets it
+    pub fn take_nick_change_receiver(&self) -> Option<tokio::sync::mpsc::UnboundedReceiver<NickChange>> {
+        self.nick_change_receiver.lock().take()
+    }
+    
+    /// How much longer the client's IP is locked out after failed logins
+    pub async fn auth_lockout(&self, client: &Client) -> Option<std::time::Duration> {
+        self.throttling_manager.auth_lockout(client.ip()?).await
+    }
+    
+    /// Count a failed OPER, SASL or PASS attempt from a client
+    ///
+    /// Lockouts are announced to +a operators. Once the IP reaches the
+    /// D-line threshold it is D-lined and the client disconnected.
+    pub async fn record_auth_failure(&self, client: &Client, kind: AuthFailureKind) -> AuthFailureOutcome {
+        let Some(ip) = client.ip() else {
+            return AuthFailureOutcome::default();
+        };
+        let outcome = self.throttling_manager.record_auth_failure(ip, kind).await;
+        if let Some(lockout) = outcome.lockout {
+            let _ = self.send_snotice(SnoMask::AuthFailures, &format!(
+                "{} locked out of OPER, SASL and PASS for {}s after {} failed {} attempts",
+                ip, lockout.as_secs(), outcome.failures, kind.as_str(),
+            )).await;
+        }
+        if outcome.dline {
+            let ban = self.throttling_manager.auth_dline(ip, self.server_connections.local_server_name());
+            let reason = ban.reason.clone();
+            match self.ban_manager.add_ban(ban) {
+                Ok(()) => {
+                    let _ = self.send_snotice(SnoMask::AuthFailures, &format!(
+                        "{} D-lined after {} failed login attempts", ip, outcome.failures,
+                    )).await;
+                }
+                Err(e) => tracing::warn!("Failed to D-line {} after failed logins: {}", ip, e),
+            }
+            self.exit_client(client.id, &reason);
+        }
+        outcome
+    }
+    
     /// Register a client connection for a user
     pub async fn register_client(&self, user_id: Uuid, client: Arc<Client>) -> Result<()> {
         let mut client_connections = self.client_connections.write().await;
@@ -173,6 +380,9 @@ pub trait Module: Send + Sync {
     fn get_capabilities(&self) -> Vec<String>;
     
     /// Check if module supports a capability
+    ///
+    /// Message handlers that also support `message_filter` see client
+    /// messages before ordinary handlers, whatever the load order.
     fn supports_capability(&self, capability: &str) -> bool;
     
     /// Get module-specific numeric replies
@@ -194,6 +404,71 @@ pub trait Module: Send + Sync {
     
     /// Register module-specific numeric replies
     fn register_numerics(&self, manager: &mut ModuleNumericManager) -> Result<()>;
+
+    /// Register ban types with the shared ban manager
+    /// Called before init(); ban modules should keep the manager and store their entries in it
+    fn register_ban_types(&mut self, _ban_manager: Arc<BanManager>) -> Result<()> {
+        Ok(())
+    }
+
+    /// Hand the module the outbound queues of the server's clients
+    /// Called before init(); modules that deliver through their own broadcast system index these
+    async fn attach_client_senders(&mut self, _client_senders: Arc<ClientSenders>) {}
+
+    /// Hand the module the server's user and channel database
+    /// Called before init(); modules that keep their own view of users and channels use this one instead
+    async fn attach_database(&mut self, _database: Arc<Database>) {}
+
+    /// Hand the module the server's authentication providers
+    /// Called before init(); modules that check credentials use these instead of their own
+    async fn attach_auth_manager(&mut self, _auth_manager: Arc<AuthManager>) {}
+
+    /// ISUPPORT (005) tokens this module advertises, e.g. `ELIST=CMNTU`
+    fn isupport_tokens(&self) -> Vec<String> {
+        Vec::new()
+    }
+
+    /// Messages this module adds to the burst sent to a newly linked server
+    ///
+    /// Sent after the user burst, so the receiving side already knows
+    /// every user referenced.
+    async fn server_burst(&self, _target_server: &str) -> Vec<Message> {
+        Vec::new()
+    }
+
+    /// ENCAP subcommands this module owns, e.g. `GLINE`
+    ///
+    /// Matching ENCAPs from other servers are unwrapped and passed to
+    /// `handle_server_message` with the subcommand as a custom command.
+    fn encap_subcommands(&self) -> Vec<String> {
+        Vec::new()
+    }
+
+    /// Snapshot runtime state that should survive a reload or upgrade
+    ///
+    /// Called before `cleanup`; the value is handed to `import_state` on the
+    /// instance that replaces this one. Stateless modules return `None`.
+    async fn export_state(&self) -> Result<Option<serde_json::Value>> {
+        Ok(None)
+    }
+
+    /// Restore state captured by `export_state`, called after `init`
+    async fn import_state(&mut self, _state: serde_json::Value) -> Result<()> {
+        Ok(())
+    }
+
+    /// Commands this module owns, dispatched to `handle_command`
+    ///
+    /// The module manager answers with 451, 481 or 461 itself when a
+    /// command's `CommandSpec` isn't met, so the handler never sees it.
+    fn commands(&self) -> Vec<CommandSpec> {
+        Vec::new()
+    }
+
+    /// Handle a command declared in `commands`
+    async fn handle_command(&mut self, _client: &Client, _message: &Message, _context: &ModuleContext) -> Result<()> {
+        Ok(())
+    }
 }
 
 /// Result of module message handling
@@ -218,6 +493,50 @@ pub enum ModuleStatsResponse {
     ModuleStats(String, String),
 }
 
+/// Builds a module from the server configuration
+pub type ModuleFactory = Arc<dyn Fn(&Config) -> Result<Box<dyn Module>> + Send + Sync>;
+
+/// Module constructors by the name used in `modules.enabled_modules`
+///
+/// Core can't depend on the crates that define modules, so the binary (or an
+/// embedder) registers their constructors here before `Server::init`.
+#[derive(Clone, Default)]
+pub struct ModuleRegistry {
+    factories: HashMap<String, ModuleFactory>,
+}
+
+impl ModuleRegistry {
+    /// Create an empty registry
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register the constructor for `name`, replacing any earlier one
+    pub fn register<F>(&mut self, name: &str, factory: F)
+    where
+        F: Fn(&Config) -> Result<Box<dyn Module>> + Send + Sync + 'static,
+    {
+        self.factories.insert(name.to_string(), Arc::new(factory));
+    }
+
+    /// Build the module registered as `name`, or `None` if there is none
+    pub fn create(&self, name: &str, config: &Config) -> Option<Result<Box<dyn Module>>> {
+        self.factories.get(name).map(|factory| factory(config))
+    }
+
+    /// Whether a constructor is registered for `name`
+    pub fn contains(&self, name: &str) -> bool {
+        self.factories.contains_key(name)
+    }
+
+    /// Registered module names, sorted
+    pub fn names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self.factories.keys().map(String::as_str).collect();
+        names.sort();
+        names
+    }
+}
+
 /// Module manager for loading and managing modules
 pub struct ModuleManager {
     modules: HashMap<String, Box<dyn Module>>,
@@ -225,30 +544,111 @@ pub struct ModuleManager {
     server_message_handlers: Vec<String>,
     user_handlers: Vec<String>,
     context: ModuleContext,
+    /// ISUPPORT tokens of loaded modules, readable without locking the manager
+    isupport_tokens: Arc<parking_lot::RwLock<Vec<String>>>,
+    /// ENCAP subcommands and the modules that own them
+    encap: EncapRegistry,
+    /// Commands dispatched by name to modules and handlers
+    commands: CommandTable,
 }
 
 impl ModuleManager {
     /// Create a new module manager
     pub fn new(database: Arc<Database>, server_connections: Arc<ServerConnectionManager>) -> Self {
+        Self::with_ban_manager(database, server_connections, Arc::new(BanManager::new()))
+    }
+
+    /// Create a new module manager sharing an existing ban manager
+    pub fn with_ban_manager(database: Arc<Database>, server_connections: Arc<ServerConnectionManager>, ban_manager: Arc<BanManager>) -> Self {
         Self {
             modules: HashMap::new(),
             message_handlers: Vec::new(),
             server_message_handlers: Vec::new(),
             user_handlers: Vec::new(),
-            context: ModuleContext::new(database, server_connections),
+            context: ModuleContext::with_ban_manager(database, server_connections, ban_manager),
+            isupport_tokens: Arc::new(parking_lot::RwLock::new(Vec::new())),
+            encap: EncapRegistry::new(),
+            commands: CommandTable::default(),
         }
     }
+
+    /// The context handed to modules
+    pub fn context(&self) -> &ModuleContext {
+        &self.context
+    }
+
+    /// Get the shared ban manager
+    pub fn ban_manager(&self) -> Arc<BanManager> {
+        self.context.ban_manager.clone()
+    }
+
+    /// Share the server's audit trail with modules
+    pub fn set_audit_trail(&mut self, audit_trail: Arc<AuditTrail>) {
+        self.context.audit_trail = audit_trail;
+    }
+
+    /// Share the server's event bus with modules
+    pub fn set_event_sender(&mut self, events: broadcast::Sender<ServerEvent>) {
+        self.context.events = events;
+    }
+
+    /// Share the server's client queues with modules
+    pub fn set_client_senders(&mut self, client_senders: Arc<ClientSenders>) {
+        self.context.client_senders = client_senders;
+    }
+
+    /// Share the server's authentication providers with modules
+    pub fn set_auth_manager(&mut self, auth_manager: Arc<AuthManager>) {
+        self.context.auth_manager = auth_manager;
+    }
+
+    /// Share the server's throttling manager with modules
+    pub fn set_throttling_manager(&mut self, throttling_manager: Arc<ThrottlingManager>) {
+        self.context.throttling_manager = throttling_manager;
+    }
     
     /// Load a module
     pub async fn load_module(&mut self, mut module: Box<dyn Module>) -> Result<()> {
         let name = module.name().to_string();
         
+        // Let ban modules hook into the shared ban manager before they initialize
+        module.register_ban_types(self.context.ban_manager.clone())?;
+        module.attach_client_senders(self.context.client_senders.clone()).await;
+        module.attach_database(self.context.database.clone()).await;
+        module.attach_auth_manager(self.context.auth_manager.clone()).await;
+        
+        // Claim ENCAP subcommands and commands up front so a conflict fails the load cleanly
+        self.encap.register(&name, &module.encap_subcommands())?;
+        if let Err(e) = self.commands.register(module.commands(), CommandTarget::Module(name.clone())) {
+            self.encap.unregister_module(&name);
+            return Err(e);
+        }
+        
         // Initialize the module
-        module.init().await?;
+        if let Err(e) = module.init().await {
+            self.encap.unregister_module(&name);
+            self.commands.unregister_module(&name);
+            self.refresh_commands();
+            return Err(e);
+        }
         
+        self.insert_module(name, module);
+        Ok(())
+    }
+    
+    /// Store an initialized module and register its handlers
+    fn insert_module(&mut self, name: String, module: Box<dyn Module>) {
         // Register handlers based on module capabilities
         if module.supports_capability("message_handler") {
-            self.message_handlers.push(name.clone());
+            if module.supports_capability("message_filter") {
+                // Filters go ahead of handlers that would claim the message first
+                let position = self.message_handlers.iter()
+                    .take_while(|handler| self.modules.get(*handler).is_some_and(|m| m.supports_capability("message_filter")))
+                    .count();
+                self.message_handlers.insert(position, name.clone());
+            } else {
+                self.message_handlers.push(name.clone());
+            }
         }
         
         if module.supports_capability("server_message_handler") {
@@ -261,24 +661,91 @@ impl ModuleManager {
         
         // Store the module
         self.modules.insert(name, module);
-        
+        self.refresh_isupport_tokens();
+        self.refresh_commands();
+    }
+    
+    /// Register a command run by `handler` rather than a module
+    ///
+    /// Unregistered clients get 451 when `requires_registration` is set,
+    /// non-operators get 481 when `oper_only` is set, and fewer than
+    /// `min_params` parameters get 461; the handler only sees the rest.
+    pub fn register_command(&mut self, name: &str, handler: Arc<dyn CommandHandler>, min_params: usize, requires_registration: bool, oper_only: bool) -> Result<()> {
+        let spec = CommandSpec {
+            requires_registration,
+            oper_only,
+            ..CommandSpec::new(name, min_params)
+        };
+        self.commands.register(vec![spec], CommandTarget::Handler(handler))?;
+        self.refresh_commands();
         Ok(())
     }
     
+    /// Commands in the dispatch table, sorted by name
+    pub fn commands(&self) -> Vec<CommandInfo> {
+        self.commands.list()
+    }
+    
+    fn refresh_commands(&self) {
+        *self.context.commands.write() = self.commands.list();
+    }
+    
     /// Unload a module
     pub async fn unload_module(&mut self, name: &str) -> Result<()> {
-        if let Some(mut module) = self.modules.remove(name) {
+        if let Some(mut module) = self.remove_module(name) {
             module.cleanup().await?;
-            
-            // Remove from handler lists
-            self.message_handlers.retain(|n| n != name);
-            self.server_message_handlers.retain(|n| n != name);
-            self.user_handlers.retain(|n| n != name);
         }
         
         Ok(())
     }
     
+    /// Shared list of ISUPPORT tokens advertised by loaded modules
+    ///
+    /// The list is updated as modules are loaded and unloaded, so the server
+    /// can read it during registration without taking the manager lock.
+    pub fn isupport_handle(&self) -> Arc<parking_lot::RwLock<Vec<String>>> {
+        self.isupport_tokens.clone()
+    }
+    
+    /// Shared set of clients whose registration a module is holding
+    pub fn registration_holds_handle(&self) -> Arc<parking_lot::RwLock<HashSet<Uuid>>> {
+        self.context.registration_holds.clone()
+    }
+    
+    /// Shared map of accounts unregistered clients have logged in to
+    pub fn registration_accounts_handle(&self) -> Arc<parking_lot::RwLock<HashMap<Uuid, String>>> {
+        self.context.registration_accounts.clone()
+    }
+    
+    /// Shared queue of clients modules asked to disconnect
+    pub fn exit_requests_handle(&self) -> Arc<parking_lot::Mutex<Vec<(Uuid, String)>>> {
+        self.context.exit_requests.clone()
+    }
+    
+    /// Capabilities clients enabled with CAP REQ, shared with the server
+    pub fn client_capabilities_handle(&self) -> Arc<parking_lot::RwLock<HashMap<Uuid, HashSet<String>>>> {
+        self.context.client_capabilities.clone()
+    }
+    
+    /// Channel joins modules have asked for, shared with the server
+    pub fn join_requests_handle(&self) -> Arc<parking_lot::Mutex<Vec<(Uuid, String)>>> {
+        self.context.join_requests.clone()
+    }
+    
+    /// Take the queue of nick changes modules asked for; only the first caller gets it
+    pub fn take_nick_change_receiver(&self) -> Option<tokio::sync::mpsc::UnboundedReceiver<NickChange>> {
+        self.context.take_nick_change_receiver()
+    }
+    
+    fn refresh_isupport_tokens(&self) {
+        let mut tokens: Vec<String> = self.modules.values()
+            .flat_map(|module| module.isupport_tokens())
+            .collect();
+        tokens.sort();
+        tokens.dedup();
+        *self.isupport_tokens.write() = tokens;
+    }
+    
     /// Get a module by name
     pub fn get_module(&self, name: &str) -> Option<&dyn Module> {
         self.modules.get(name).map(|m| m.as_ref())
@@ -308,34 +775,65 @@ impl ModuleManager {
                     Ok(ModuleResult::Handled) => return Ok(ModuleResult::Handled),
                     Ok(ModuleResult::NotHandled) => continue,
                     Err(e) => {
+                        // The module took the command and already answered it;
+                        // passing it on would run it twice or report it unknown
                         tracing::error!("Error in module {}: {}", module_name, e);
-                        continue;
+                        return Ok(ModuleResult::Handled);
                     }
                 }
             }
         }
         
-        Ok(ModuleResult::NotHandled)
+        self.dispatch_command(client, message).await
     }
     
     /// Handle a message from a client with server reference
     pub async fn handle_message_with_server(&mut self, client: &Client, message: &Message, server: Option<&crate::Server>) -> Result<ModuleResult> {
         for module_name in &self.message_handlers {
             if let Some(module) = self.modules.get_mut(module_name) {
-                match module.handle_message_with_server(client, message, server, &self.context).await {
+                let span = tracing::debug_span!("irc.module", module = %module_name);
+                match module.handle_message_with_server(client, message, server, &self.context).instrument(span).await {
                     Ok(ModuleResult::HandledStop) => return Ok(ModuleResult::HandledStop),
                     Ok(ModuleResult::Rejected(reason)) => return Ok(ModuleResult::Rejected(reason)),
                     Ok(ModuleResult::Handled) => return Ok(ModuleResult::Handled),
                     Ok(ModuleResult::NotHandled) => continue,
                     Err(e) => {
+                        // The module took the command and already answered it;
+                        // passing it on would run it twice or report it unknown
                         tracing::error!("Error in module {}: {}", module_name, e);
-                        continue;
+                        return Ok(ModuleResult::Handled);
                     }
                 }
             }
         }
         
-        Ok(ModuleResult::NotHandled)
+        self.dispatch_command(client, message).await
+    }
+    
+    /// Run a command from the dispatch table once its checks pass
+    ///
+    /// Called after every message handler passed on the message, so
+    /// filters like shuns and throttling still apply to registered commands.
+    async fn dispatch_command(&mut self, client: &Client, message: &Message) -> Result<ModuleResult> {
+        let Some((spec, target)) = self.commands.get(&message.command.to_string()) else {
+            return Ok(ModuleResult::NotHandled);
+        };
+        if let Some(refusal) = spec.check(client, message) {
+            let _ = client.send(refusal);
+            return Ok(ModuleResult::Handled);
+        }
+        
+        let (owner, result) = match target {
+            CommandTarget::Module(name) => match self.modules.get_mut(name) {
+                Some(module) => (name.as_str(), module.handle_command(client, message, &self.context).await),
+                None => return Ok(ModuleResult::NotHandled),
+            },
+            CommandTarget::Handler(handler) => ("command handler", handler.handle(client, message, &self.context).await),
+        };
+        if let Err(e) = result {
+            tracing::error!("Error in {} handling {}: {}", owner, spec.name, e);
+        }
+        Ok(ModuleResult::Handled)
     }
     
     /// Handle a message from a server
@@ -358,6 +856,23 @@ impl ModuleManager {
         Ok(ModuleResult::NotHandled)
     }
     
+    /// Hand an unwrapped ENCAP command to the module that owns its subcommand
+    pub async fn handle_encap(&mut self, server: &str, message: &Message) -> Result<ModuleResult> {
+        let subcommand = message.command.to_string();
+        let Some(owner) = self.encap.owner(&subcommand) else {
+            return Ok(ModuleResult::NotHandled);
+        };
+        match self.modules.get_mut(owner) {
+            Some(module) => module.handle_server_message(server, message, &self.context).await,
+            None => Ok(ModuleResult::NotHandled),
+        }
+    }
+    
+    /// ENCAP subcommands registered by loaded modules
+    pub fn encap_subcommands(&self) -> Vec<String> {
+        self.encap.subcommands()
+    }
+    
     /// Handle user registration
     pub async fn handle_user_registration(&mut self, user: &User) -> Result<()> {
         for module_name in &self.user_handlers {
@@ -405,6 +920,15 @@ impl ModuleManager {
         Ok(responses)
     }
     
+    /// Collect burst messages from all modules for a newly linked server
+    pub async fn server_burst(&self, target_server: &str) -> Vec<Message> {
+        let mut messages = Vec::new();
+        for module in self.modules.values() {
+            messages.extend(module.server_burst(target_server).await);
+        }
+        messages
+    }
+    
     /// Get all loaded modules
     pub fn get_loaded_modules(&self) -> Vec<&str> {
         self.modules.keys().map(|k| k.as_str()).collect()
@@ -426,6 +950,92 @@ impl ModuleManager {
         self.modules.values().any(|m| m.supports_capability(capability))
     }
     
+    /// Re-run cleanup and init on every loaded module, in name order
+    ///
+    /// Each module's exported state is imported again after init. A module
+    /// whose init fails is unloaded. Returns each module's name with the
+    /// outcome of its reload.
+    pub async fn reinitialize_modules(&mut self) -> Vec<(String, Result<()>)> {
+        let mut names: Vec<String> = self.modules.keys().cloned().collect();
+        names.sort();
+        
+        let mut results = Vec::with_capacity(names.len());
+        for name in names {
+            let Some(module) = self.modules.get_mut(&name) else { continue };
+            let result = Self::reinitialize(module.as_mut()).await;
+            if result.is_err() {
+                self.remove_module(&name);
+            }
+            results.push((name, result));
+        }
+        self.refresh_isupport_tokens();
+        
+        results
+    }
+    
+    async fn reinitialize(module: &mut dyn Module) -> Result<()> {
+        let state = module.export_state().await?;
+        if let Err(e) = module.cleanup().await {
+            tracing::warn!("Failed to cleanup module {}: {}", module.name(), e);
+        }
+        module.init().await?;
+        if let Some(state) = state {
+            module.import_state(state).await?;
+        }
+        Ok(())
+    }
+    
+    /// Replace a loaded module with a new instance of it, carrying its state over
+    ///
+    /// The old instance stays loaded if the new one fails to load.
+    pub async fn replace_module(&mut self, module: Box<dyn Module>) -> Result<()> {
+        let name = module.name().to_string();
+        let state = match self.modules.get(&name) {
+            Some(old) => old.export_state().await?,
+            None => None,
+        };
+        
+        let old = self.remove_module(&name);
+        if let Err(e) = self.load_module(module).await {
+            if let Some(old) = old {
+                self.restore_module(old)?;
+            }
+            return Err(e);
+        }
+        if let Some(mut old) = old {
+            if let Err(e) = old.cleanup().await {
+                tracing::warn!("Failed to cleanup replaced module {}: {}", name, e);
+            }
+        }
+        
+        if let (Some(state), Some(module)) = (state, self.modules.get_mut(&name)) {
+            module.import_state(state).await?;
+        }
+        Ok(())
+    }
+    
+    /// Take a module out of the manager without cleaning it up
+    fn remove_module(&mut self, name: &str) -> Option<Box<dyn Module>> {
+        let module = self.modules.remove(name)?;
+        self.message_handlers.retain(|n| n != name);
+        self.server_message_handlers.retain(|n| n != name);
+        self.user_handlers.retain(|n| n != name);
+        self.encap.unregister_module(name);
+        self.commands.unregister_module(name);
+        self.refresh_isupport_tokens();
+        self.refresh_commands();
+        Some(module)
+    }
+    
+    /// Put back a module taken out by `remove_module`
+    fn restore_module(&mut self, module: Box<dyn Module>) -> Result<()> {
+        let name = module.name().to_string();
+        self.encap.register(&name, &module.encap_subcommands())?;
+        self.commands.register(module.commands(), CommandTarget::Module(name.clone()))?;
+        self.insert_module(name, module);
+        Ok(())
+    }
+    
     /// Clear all modules (for reloading)
     pub async fn clear_modules(&mut self) -> Result<()> {
         // Cleanup all modules before clearing
@@ -439,6 +1049,11 @@ impl ModuleManager {
         self.message_handlers.clear();
         self.server_message_handlers.clear();
         self.user_handlers.clear();
+        self.encap = EncapRegistry::new();
+        for module in self.commands.list().into_iter().filter_map(|command| command.module) {
+            self.commands.unregister_module(&module);
+        }
+        self.refresh_commands();
         
         Ok(())
     }
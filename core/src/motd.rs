@@ -1,18 +1,54 @@
 //! MOTD (Message of the Day) management system
+//!
+//! The default MOTD comes from `motd_file`, or from `motd.remote_url` once a
+//! fetch has succeeded. Overrides give particular connection classes or
+//! ports their own MOTD. Lines are wrapped to 80 characters when sent.
 
+use crate::config::{MotdOverride, ServerConfig};
 use crate::{Error, Result, Message, NumericReply};
 use std::fs;
 use std::path::Path;
 use std::sync::Arc;
-use tokio::sync::RwLock;
+use std::sync::atomic::{AtomicBool, Ordering};
+use tokio::sync::{Notify, RwLock};
 use tracing::{info, warn, error, debug};
 
+/// Width RPL_MOTD text is wrapped to
+pub const MOTD_LINE_WIDTH: usize = 80;
+
+/// Largest remote MOTD accepted, in bytes
+const MAX_REMOTE_MOTD_SIZE: usize = 64 * 1024;
+
+/// MOTD files read for a configuration, ready to be applied
+///
+/// Reading happens before a rehash commits, so a bad file leaves the running
+/// MOTD alone.
+#[derive(Debug, Clone, Default)]
+pub struct MotdSet {
+    /// The default MOTD
+    pub lines: Vec<String>,
+    /// Overrides whose files exist, in config order
+    pub overrides: Vec<(MotdOverride, Vec<String>)>,
+    /// Whether connecting clients get the short MOTD
+    pub short_motd: bool,
+    /// URL the default MOTD is fetched from
+    pub remote_url: Option<String>,
+}
+
 /// MOTD (Message of the Day) manager
 pub struct MotdManager {
     /// MOTD lines loaded from file
     lines: Arc<RwLock<Vec<String>>>,
     /// Whether MOTD is enabled
-    enabled: bool,
+    enabled: AtomicBool,
+    /// MOTDs for particular classes or ports, in config order
+    overrides: RwLock<Vec<(MotdOverride, Vec<String>)>>,
+    /// Last MOTD fetched and the URL it came from, served instead of `lines`
+    remote: RwLock<Option<(String, Vec<String>)>>,
+    /// Whether connecting clients get the short MOTD
+    short_motd: AtomicBool,
+    /// Wakes the remote refresh task early, e.g. when the URL changes
+    refresh: Notify,
 }
 
 impl MotdManager {
@@ -20,43 +56,40 @@ impl MotdManager {
     pub fn new() -> Self {
         Self {
             lines: Arc::new(RwLock::new(Vec::new())),
-            enabled: false,
+            enabled: AtomicBool::new(false),
+            overrides: RwLock::new(Vec::new()),
+            remote: RwLock::new(None),
+            short_motd: AtomicBool::new(false),
+            refresh: Notify::new(),
         }
     }
 
     /// Load MOTD from file (supports both relative and absolute paths)
-    pub async fn load_motd(&mut self, motd_file: &str) -> Result<()> {
-        let path = if Path::new(motd_file).is_absolute() {
-            // Absolute path - use as-is
-            Path::new(motd_file).to_path_buf()
-        } else {
-            // Relative path - resolve from current working directory
-            std::env::current_dir()
-                .map_err(|e| Error::Config(format!("Failed to get current directory: {}", e)))?
-                .join(motd_file)
-        };
+    pub async fn load_motd(&self, motd_file: &str) -> Result<()> {
+        if let Some(lines) = Self::read_motd_file(motd_file)? {
+            let mut motd_lines = self.lines.write().await;
+            *motd_lines = lines;
+            self.enabled.store(true, Ordering::Relaxed);
+
+            info!("Loaded MOTD from {} ({} lines)", motd_file, motd_lines.len());
+            debug!("MOTD lines: {:?}", *motd_lines);
+        }
+        Ok(())
+    }
+
+    /// Read the lines of a MOTD file without applying them
+    ///
+    /// Returns `None` if the file does not exist, which is not an error.
+    pub fn read_motd_file(motd_file: &str) -> Result<Option<Vec<String>>> {
+        let path = Self::resolve_motd_path(motd_file)?;
         
         if !path.exists() {
             warn!("MOTD file not found: {} (resolved from: {})", path.display(), motd_file);
-            return Ok(()); // Not an error, just no MOTD
+            return Ok(None); // Not an error, just no MOTD
         }
 
         match fs::read_to_string(&path) {
-            Ok(content) => {
-                let lines: Vec<String> = content
-                    .lines()
-                    .map(|line| line.to_string())
-                    .collect();
-
-                let mut motd_lines = self.lines.write().await;
-                *motd_lines = lines;
-                self.enabled = true;
-
-                info!("Loaded MOTD from {} (resolved from: {}, {} lines)", 
-                      path.display(), motd_file, motd_lines.len());
-                debug!("MOTD lines: {:?}", *motd_lines);
-                Ok(())
-            }
+            Ok(content) => Ok(Some(content.lines().map(|line| line.to_string()).collect())),
             Err(e) => {
                 error!("Failed to read MOTD file {} (resolved from: {}): {}", 
                        path.display(), motd_file, e);
@@ -65,9 +98,92 @@ impl MotdManager {
         }
     }
 
+    /// Read every MOTD file `config` names
+    ///
+    /// Missing files are skipped: the default MOTD is then empty, and a
+    /// missing override falls back to the default.
+    pub fn read_config(config: &ServerConfig) -> Result<MotdSet> {
+        let lines = match &config.motd_file {
+            Some(motd_file) => Self::read_motd_file(motd_file)?.unwrap_or_default(),
+            None => Vec::new(),
+        };
+        let mut overrides = Vec::new();
+        for motd_override in &config.motd.overrides {
+            if let Some(lines) = Self::read_motd_file(&motd_override.file)? {
+                overrides.push((motd_override.clone(), lines));
+            }
+        }
+        Ok(MotdSet {
+            lines,
+            overrides,
+            short_motd: config.motd.short_motd,
+            remote_url: config.motd.remote_url.clone(),
+        })
+    }
+
+    /// Replace the running MOTDs with `set`
+    ///
+    /// A fetched remote MOTD is kept while the URL stays the same; a new URL
+    /// drops it and wakes the refresh task.
+    pub async fn apply(&self, set: MotdSet) {
+        self.set_lines(set.lines).await;
+        *self.overrides.write().await = set.overrides;
+        self.short_motd.store(set.short_motd, Ordering::Relaxed);
+
+        let mut remote = self.remote.write().await;
+        let url_changed = remote.as_ref().map(|(url, _)| url) != set.remote_url.as_ref();
+        if url_changed {
+            *remote = None;
+            if set.remote_url.is_some() {
+                self.refresh.notify_one();
+            }
+        }
+    }
+
+    /// Whether `set` is what is already being served
+    pub async fn is_loaded(&self, set: &MotdSet) -> bool {
+        *self.lines.read().await == set.lines
+            && *self.overrides.read().await == set.overrides
+            && self.short_motd.load(Ordering::Relaxed) == set.short_motd
+    }
+
+    /// Serve `lines`, fetched from `url`, as the default MOTD
+    pub async fn set_remote(&self, url: &str, lines: Vec<String>) {
+        info!("Fetched MOTD from {} ({} lines)", url, lines.len());
+        *self.remote.write().await = Some((url.to_string(), lines));
+    }
+
+    /// Wait until the remote MOTD should be fetched ahead of schedule
+    pub async fn refresh_requested(&self) {
+        self.refresh.notified().await
+    }
+
+    /// Fetch a MOTD over HTTPS
+    pub async fn fetch_remote(url: &str) -> Result<Vec<String>> {
+        if !url.starts_with("https://") {
+            return Err(Error::Config(format!("Remote MOTD URL must use https: {}", url)));
+        }
+        let client = reqwest::Client::builder()
+            .timeout(std::time::Duration::from_secs(10))
+            .build()
+            .map_err(|e| Error::Network(format!("Failed to build HTTP client: {}", e)))?;
+        let response = client.get(url).send().await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| Error::Network(format!("Failed to fetch MOTD from {}: {}", url, e)))?;
+        let body = response.bytes().await
+            .map_err(|e| Error::Network(format!("Failed to read MOTD from {}: {}", url, e)))?;
+        if body.len() > MAX_REMOTE_MOTD_SIZE {
+            return Err(Error::Network(format!("MOTD from {} is larger than {} bytes", url, MAX_REMOTE_MOTD_SIZE)));
+        }
+        Ok(String::from_utf8_lossy(&body).lines().map(str::to_string).collect())
+    }
+
     /// Check if MOTD is enabled and has content
     pub async fn is_enabled(&self) -> bool {
-        self.enabled && !self.lines.read().await.is_empty()
+        if self.remote.read().await.as_ref().is_some_and(|(_, lines)| !lines.is_empty()) {
+            return true;
+        }
+        self.enabled.load(Ordering::Relaxed) && !self.lines.read().await.is_empty()
     }
 
     /// Get MOTD start message
@@ -97,24 +213,71 @@ impl MotdManager {
 
     /// Get all MOTD messages for a complete MOTD display
     pub async fn get_all_motd_messages(&self, server_name: &str) -> Vec<Message> {
-        let mut messages = Vec::new();
+        let lines = self.default_lines().await;
+        self.messages_for(server_name, &lines)
+    }
 
-        if !self.is_enabled().await {
-            messages.push(self.get_no_motd(server_name));
-            return messages;
-        }
+    /// The MOTD for a client in `class` connected to `port`
+    pub async fn motd_messages(&self, server_name: &str, class: &str, port: u16) -> Vec<Message> {
+        let lines = self.lines_for(class, port).await;
+        self.messages_for(server_name, &lines)
+    }
 
-        messages.push(self.get_motd_start(server_name));
+    /// The MOTD sent on registration
+    ///
+    /// With the short MOTD only 375 and 376 are sent, and 376 points the
+    /// client at the MOTD command for the full text.
+    pub async fn connect_messages(&self, server_name: &str, class: &str, port: u16) -> Vec<Message> {
+        let lines = self.lines_for(class, port).await;
+        if lines.is_empty() || !self.short_motd.load(Ordering::Relaxed) {
+            return self.messages_for(server_name, &lines);
+        }
+        vec![
+            self.get_motd_start(server_name),
+            NumericReply::RplMotdEnd.reply("*", vec!["End of /MOTD command. Type /MOTD to read it in full.".to_string()]),
+        ]
+    }
 
-        let lines = self.lines.read().await;
-        for line in lines.iter() {
-            messages.push(NumericReply::motd_line(line));
+    fn messages_for(&self, server_name: &str, lines: &[String]) -> Vec<Message> {
+        if lines.is_empty() {
+            return vec![self.get_no_motd(server_name)];
         }
 
+        let mut messages = vec![self.get_motd_start(server_name)];
+        for line in lines {
+            for wrapped in wrap_line(line, MOTD_LINE_WIDTH) {
+                messages.push(NumericReply::motd_line(&wrapped));
+            }
+        }
         messages.push(self.get_motd_end(server_name));
         messages
     }
 
+    /// The remote MOTD once fetched, else the MOTD file
+    async fn default_lines(&self) -> Vec<String> {
+        if let Some((_, lines)) = self.remote.read().await.as_ref() {
+            return lines.clone();
+        }
+        if !self.enabled.load(Ordering::Relaxed) {
+            return Vec::new();
+        }
+        self.lines.read().await.clone()
+    }
+
+    /// The first override matching `class` and `port`, else the default MOTD
+    async fn lines_for(&self, class: &str, port: u16) -> Vec<String> {
+        let overrides = self.overrides.read().await;
+        let matching = overrides.iter().find(|(motd_override, _)| {
+            (motd_override.class.is_some() || motd_override.port.is_some())
+                && motd_override.class.as_deref().is_none_or(|name| name == class)
+                && motd_override.port.is_none_or(|p| p == port)
+        });
+        match matching {
+            Some((_, lines)) => lines.clone(),
+            None => self.default_lines().await,
+        }
+    }
+
     /// Get MOTD line count
     pub async fn line_count(&self) -> usize {
         self.lines.read().await.len()
@@ -122,25 +285,25 @@ impl MotdManager {
 
     /// Reload MOTD from file (useful for runtime updates)
     /// Supports both relative and absolute paths
-    pub async fn reload(&mut self, motd_file: &str) -> Result<()> {
+    pub async fn reload(&self, motd_file: &str) -> Result<()> {
         info!("Reloading MOTD from: {}", motd_file);
         self.load_motd(motd_file).await
     }
 
     /// Clear MOTD (disable it)
-    pub async fn clear(&mut self) {
+    pub async fn clear(&self) {
         let mut lines = self.lines.write().await;
         lines.clear();
-        self.enabled = false;
+        self.enabled.store(false, Ordering::Relaxed);
         info!("MOTD cleared and disabled");
     }
 
     /// Set MOTD lines directly (for testing or dynamic updates)
-    pub async fn set_lines(&mut self, lines: Vec<String>) {
+    pub async fn set_lines(&self, lines: Vec<String>) {
         let mut motd_lines = self.lines.write().await;
         *motd_lines = lines;
-        self.enabled = !motd_lines.is_empty();
-        info!("MOTD lines set directly ({} lines, enabled: {})", motd_lines.len(), self.enabled);
+        self.enabled.store(!motd_lines.is_empty(), Ordering::Relaxed);
+        info!("MOTD lines set directly ({} lines)", motd_lines.len());
     }
 
     /// Resolve MOTD file path (supports both relative and absolute paths)
@@ -166,6 +329,27 @@ impl MotdManager {
     }
 }
 
+/// Split `line` into pieces of at most `width` characters
+///
+/// Breaks at the last space that fits, or mid-word when a word alone is
+/// too long. An empty line stays a single empty line.
+pub fn wrap_line(line: &str, width: usize) -> Vec<String> {
+    let mut pieces = Vec::new();
+    let mut rest = line.trim_end();
+    while rest.chars().count() > width {
+        let limit = rest.char_indices().nth(width).map_or(rest.len(), |(i, _)| i);
+        let space = if rest[limit..].starts_with(' ') { Some(limit) } else { rest[..limit].rfind(' ') };
+        let (piece, next) = match space {
+            Some(space) if space > 0 => (&rest[..space], &rest[space + 1..]),
+            _ => (&rest[..limit], &rest[limit..]),
+        };
+        pieces.push(piece.trim_end().to_string());
+        rest = next.trim_start();
+    }
+    pieces.push(rest.to_string());
+    pieces
+}
+
 impl Default for MotdManager {
     fn default() -> Self {
         Self::new()
@@ -194,7 +378,7 @@ mod tests {
         writeln!(temp_file, "This is line 3").unwrap();
         temp_file.flush().unwrap();
 
-        let mut manager = MotdManager::new();
+        let manager = MotdManager::new();
         let result = manager.load_motd(temp_file.path().to_str().unwrap()).await;
         
         assert!(result.is_ok());
@@ -204,7 +388,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_motd_messages() {
-        let mut manager = MotdManager::new();
+        let manager = MotdManager::new();
         manager.set_lines(vec![
             "Line 1".to_string(),
             "Line 2".to_string(),
@@ -224,7 +408,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_no_motd_file() {
-        let mut manager = MotdManager::new();
+        let manager = MotdManager::new();
         let result = manager.load_motd("nonexistent_file.txt").await;
         
         // Should not error, just not load anything
@@ -234,7 +418,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_motd_clear() {
-        let mut manager = MotdManager::new();
+        let manager = MotdManager::new();
         manager.set_lines(vec!["Test line".to_string()]).await;
         
         assert!(manager.is_enabled().await);
@@ -270,6 +454,57 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_wrap_line() {
+        assert_eq!(wrap_line("", 80), vec![""]);
+        assert_eq!(wrap_line("short line", 80), vec!["short line"]);
+        assert_eq!(wrap_line("aaaa bbbb cccc", 9), vec!["aaaa bbbb", "cccc"]);
+        assert_eq!(wrap_line("aaaa bbbb cccc", 10), vec!["aaaa bbbb", "cccc"]);
+        assert_eq!(wrap_line(&"x".repeat(100), 80), vec!["x".repeat(80), "x".repeat(20)]);
+        assert_eq!(wrap_line("ééééé", 3), vec!["ééé", "éé"]);
+    }
+
+    #[tokio::test]
+    async fn test_motd_overrides_and_remote() {
+        let manager = MotdManager::new();
+        let tls_only = MotdOverride { class: None, port: Some(6697), file: String::new() };
+        let vip_tls = MotdOverride { class: Some("vip".to_string()), port: Some(6697), file: String::new() };
+        manager.apply(MotdSet {
+            lines: vec!["default".to_string()],
+            overrides: vec![(vip_tls, vec!["vip tls".to_string()]), (tls_only, vec!["tls".to_string()])],
+            short_motd: false,
+            remote_url: Some("https://example.com/motd".to_string()),
+        }).await;
+
+        let text = |messages: Vec<Message>| messages[1].params[1].clone();
+        assert_eq!(text(manager.motd_messages("test.server", "vip", 6697).await), "- vip tls");
+        assert_eq!(text(manager.motd_messages("test.server", "users", 6697).await), "- tls");
+        assert_eq!(text(manager.motd_messages("test.server", "vip", 6667).await), "- default");
+
+        // A fetched MOTD replaces the file until the URL changes
+        manager.set_remote("https://example.com/motd", vec!["remote".to_string()]).await;
+        assert_eq!(text(manager.get_all_motd_messages("test.server").await), "- remote");
+        manager.apply(MotdSet { lines: vec!["default".to_string()], remote_url: Some("https://example.com/motd".to_string()), ..MotdSet::default() }).await;
+        assert_eq!(text(manager.get_all_motd_messages("test.server").await), "- remote");
+        manager.apply(MotdSet { lines: vec!["default".to_string()], ..MotdSet::default() }).await;
+        assert_eq!(text(manager.get_all_motd_messages("test.server").await), "- default");
+    }
+
+    #[tokio::test]
+    async fn test_short_motd() {
+        let manager = MotdManager::new();
+        manager.apply(MotdSet { lines: vec!["Line 1".to_string()], short_motd: true, ..MotdSet::default() }).await;
+
+        let messages = manager.connect_messages("test.server", "default", 6667).await;
+        assert_eq!(messages.len(), 2);
+        assert!(messages[1].to_string().contains("376"));
+        assert_eq!(manager.motd_messages("test.server", "default", 6667).await.len(), 3);
+
+        // Without a MOTD the short form still reports it missing
+        manager.apply(MotdSet { short_motd: true, ..MotdSet::default() }).await;
+        assert!(manager.connect_messages("test.server", "default", 6667).await[0].to_string().contains("422"));
+    }
+
     #[test]
     fn test_motd_file_exists() {
         // Test with a file that definitely doesn't exist
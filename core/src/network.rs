@@ -1,6 +1,6 @@
 //! Network-wide query system for IRC daemon
 
-use crate::{User, Error, Result, Database, DatabaseServerInfo as ServerInfo};
+use crate::{User, Error, Result, Database, DatabaseServerInfo as ServerInfo, Message, MessageType, NumericReply, Prefix};
 use std::collections::HashMap;
 use std::sync::Arc;
 use tokio::sync::RwLock;
@@ -38,6 +38,42 @@ pub enum NetworkQuery {
         requestor: Uuid,
         request_id: String,
     },
+    /// ADMIN, VERSION, TIME or STATS answered by a remote server
+    ServerInfo {
+        command: MessageType,
+        requestor: Uuid,
+        request_id: String,
+    },
+}
+
+impl NetworkQuery {
+    /// Client that asked
+    pub fn requestor(&self) -> Uuid {
+        match self {
+            NetworkQuery::Who { requestor, .. }
+            | NetworkQuery::Whois { requestor, .. }
+            | NetworkQuery::Whowas { requestor, .. }
+            | NetworkQuery::UserCount { requestor, .. }
+            | NetworkQuery::ServerList { requestor, .. }
+            | NetworkQuery::ServerInfo { requestor, .. } => *requestor,
+        }
+    }
+
+    /// Numeric that ends a remote server's answer to this query
+    pub fn final_numeric(&self) -> Option<NumericReply> {
+        match self {
+            NetworkQuery::Whois { .. } => Some(NumericReply::RplEndOfWhois),
+            NetworkQuery::Whowas { .. } => Some(NumericReply::RplEndOfWhoWas),
+            NetworkQuery::ServerInfo { command, .. } => match command {
+                MessageType::Admin => Some(NumericReply::RplAdminEmail),
+                MessageType::Version => Some(NumericReply::RplVersion),
+                MessageType::Time => Some(NumericReply::RplTime),
+                MessageType::Stats => Some(NumericReply::RplEndOfStats),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
 }
 
 /// Network query response
@@ -79,6 +115,39 @@ pub enum NetworkResponse {
         server: String,
         error: String,
     },
+    /// Numeric reply relayed from a remote server
+    Numeric {
+        request_id: String,
+        server: String,
+        message: Message,
+        /// Whether this is the reply that ends the remote answer (e.g. 318)
+        last: bool,
+    },
+}
+
+impl NetworkResponse {
+    /// Request this response belongs to
+    pub fn request_id(&self) -> &str {
+        match self {
+            NetworkResponse::WhoResponse { request_id, .. }
+            | NetworkResponse::WhoisResponse { request_id, .. }
+            | NetworkResponse::WhowasResponse { request_id, .. }
+            | NetworkResponse::UserCountResponse { request_id, .. }
+            | NetworkResponse::ServerListResponse { request_id, .. }
+            | NetworkResponse::ErrorResponse { request_id, .. }
+            | NetworkResponse::Numeric { request_id, .. } => request_id,
+        }
+    }
+
+    /// Whether this response finishes a server's part of the query
+    ///
+    /// Relayed numerics only count once the final one arrives.
+    pub fn completes(&self) -> bool {
+        match self {
+            NetworkResponse::Numeric { last, .. } => *last,
+            _ => true,
+        }
+    }
 }
 
 /// Pending network query
@@ -89,6 +158,15 @@ pub struct PendingQuery {
     pub timeout: Duration,
     pub responses: Vec<NetworkResponse>,
     pub expected_servers: Vec<String>,
+    /// Signalled once every expected server has answered
+    pub completion: Arc<tokio::sync::Notify>,
+}
+
+impl PendingQuery {
+    /// Whether every expected server has finished answering
+    pub fn is_complete(&self) -> bool {
+        self.responses.iter().filter(|response| response.completes()).count() >= self.expected_servers.len()
+    }
 }
 
 /// Network query manager
@@ -136,6 +214,7 @@ impl NetworkQueryManager {
             timeout: self.default_timeout,
             responses: Vec::new(),
             expected_servers,
+            completion: Arc::new(tokio::sync::Notify::new()),
         };
 
         {
@@ -151,23 +230,67 @@ impl NetworkQueryManager {
 
     /// Handle a network response
     pub async fn handle_response(&self, response: NetworkResponse) -> Result<()> {
-        let request_id = match &response {
-            NetworkResponse::WhoResponse { request_id, .. } => request_id,
-            NetworkResponse::WhoisResponse { request_id, .. } => request_id,
-            NetworkResponse::WhowasResponse { request_id, .. } => request_id,
-            NetworkResponse::UserCountResponse { request_id, .. } => request_id,
-            NetworkResponse::ServerListResponse { request_id, .. } => request_id,
-            NetworkResponse::ErrorResponse { request_id, .. } => request_id,
-        };
-
         let mut queries = self.pending_queries.write().await;
-        if let Some(pending_query) = queries.get_mut(request_id) {
+        if let Some(pending_query) = queries.get_mut(response.request_id()) {
             pending_query.responses.push(response);
+            if pending_query.is_complete() {
+                pending_query.completion.notify_one();
+            }
         }
 
         Ok(())
     }
 
+    /// Wait for a query to complete and take its responses
+    ///
+    /// Returns `None` if the query times out first; either way it is removed.
+    pub async fn wait_for_query(&self, request_id: &str) -> Option<Vec<NetworkResponse>> {
+        let completion = {
+            let queries = self.pending_queries.read().await;
+            queries.get(request_id)?.completion.clone()
+        };
+        let timeout = self.default_timeout.to_std().unwrap_or(std::time::Duration::from_secs(30));
+        let completed = tokio::time::timeout(timeout, completion.notified()).await.is_ok();
+
+        let pending_query = self.pending_queries.write().await.remove(request_id)?;
+        completed.then_some(pending_query.responses)
+    }
+
+    /// Pending query from `requestor` that is waiting on `server`
+    pub async fn pending_query_for(&self, requestor: Uuid, server: &str) -> Option<String> {
+        let queries = self.pending_queries.read().await;
+        queries.iter()
+            .find(|(_, pending_query)| {
+                pending_query.query.requestor() == requestor
+                    && pending_query.expected_servers.iter().any(|expected| expected.eq_ignore_ascii_case(server))
+            })
+            .map(|(request_id, _)| request_id.clone())
+    }
+
+    /// Collect a numeric a remote server relayed for `requestor`
+    ///
+    /// Returns false if no query from the requestor is waiting on that server.
+    pub async fn collect_numeric(&self, requestor: Uuid, server: &str, message: Message) -> bool {
+        let mut queries = self.pending_queries.write().await;
+        let Some((request_id, pending_query)) = queries.iter_mut().find(|(_, pending_query)| {
+            pending_query.query.requestor() == requestor
+                && pending_query.expected_servers.iter().any(|expected| expected.eq_ignore_ascii_case(server))
+        }) else {
+            return false;
+        };
+        let last = pending_query.query.final_numeric().is_some_and(|end| message.command.to_string() == end.code());
+        pending_query.responses.push(NetworkResponse::Numeric {
+            request_id: request_id.clone(),
+            server: server.to_string(),
+            message,
+            last,
+        });
+        if pending_query.is_complete() {
+            pending_query.completion.notify_one();
+        }
+        true
+    }
+
     /// Get query results
     pub async fn get_query_results(&self, request_id: &str) -> Result<Vec<NetworkResponse>> {
         let queries = self.pending_queries.read().await;
@@ -182,9 +305,7 @@ impl NetworkQueryManager {
     pub async fn is_query_complete(&self, request_id: &str) -> Result<bool> {
         let queries = self.pending_queries.read().await;
         if let Some(pending_query) = queries.get(request_id) {
-            let expected_count = pending_query.expected_servers.len();
-            let response_count = pending_query.responses.len();
-            Ok(response_count >= expected_count)
+            Ok(pending_query.is_complete())
         } else {
             Err(Error::User("Query not found".to_string()))
         }
@@ -459,6 +580,10 @@ impl NetworkMessageHandler {
                 };
                 self.send_network_response(response, from_server).await?;
             }
+            NetworkQuery::ServerInfo { command, .. } => {
+                // Answered by the server itself, which relays its numerics
+                tracing::debug!("Ignoring {} query from {}: answered with numerics", command, from_server);
+            }
         }
         Ok(())
     }
@@ -508,4 +633,171 @@ impl NetworkQueryManager {
         };
         self.submit_query(query, servers).await
     }
+
+    /// Submit an ADMIN, VERSION, TIME or STATS query to a remote server
+    pub async fn query_server_info(&self, command: MessageType, requestor: Uuid, server: String) -> Result<String> {
+        let query = NetworkQuery::ServerInfo {
+            command,
+            requestor,
+            request_id: Uuid::new_v4().to_string(),
+        };
+        self.submit_query(query, vec![server]).await
+    }
+}
+
+/// Owners of module-defined ENCAP subcommands
+///
+/// `:<source> ENCAP <target> <SUBCOMMAND> [params]` tunnels commands that only
+/// a module understands. Every server relays the envelope; those whose name
+/// matches the target mask also hand the unwrapped command to the module that
+/// registered the subcommand.
+#[derive(Debug, Default)]
+pub struct EncapRegistry {
+    /// Owning module by uppercase subcommand
+    owners: HashMap<String, String>,
+}
+
+impl EncapRegistry {
+    /// Create an empty registry
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register a module's subcommands
+    ///
+    /// Fails without registering anything if another module already owns one.
+    pub fn register(&mut self, module: &str, subcommands: &[String]) -> Result<()> {
+        for subcommand in subcommands {
+            if let Some(owner) = self.owners.get(&subcommand.to_uppercase()) {
+                if owner != module {
+                    return Err(Error::Server(format!(
+                        "ENCAP subcommand {} is already registered by module {}", subcommand, owner
+                    )));
+                }
+            }
+        }
+        for subcommand in subcommands {
+            self.owners.insert(subcommand.to_uppercase(), module.to_string());
+        }
+        Ok(())
+    }
+
+    /// Drop every subcommand owned by a module
+    pub fn unregister_module(&mut self, module: &str) {
+        self.owners.retain(|_, owner| owner != module);
+    }
+
+    /// Module that handles a subcommand
+    pub fn owner(&self, subcommand: &str) -> Option<&str> {
+        self.owners.get(&subcommand.to_uppercase()).map(String::as_str)
+    }
+
+    /// All registered subcommands, sorted
+    pub fn subcommands(&self) -> Vec<String> {
+        let mut subcommands: Vec<String> = self.owners.keys().cloned().collect();
+        subcommands.sort();
+        subcommands
+    }
+}
+
+/// Build `:<source> ENCAP <target> <subcommand> [params]`
+pub fn encap_message(source: &str, target: &str, subcommand: &str, params: Vec<String>) -> Message {
+    let mut encap_params = vec![target.to_string(), subcommand.to_uppercase()];
+    encap_params.extend(params);
+    Message::with_prefix(Prefix::Server(source.to_string()), MessageType::Encap, encap_params)
+}
+
+/// Split an ENCAP into its target mask and the tunneled command
+///
+/// The tunneled command keeps the envelope's prefix.
+pub fn unwrap_encap(message: &Message) -> Option<(String, Message)> {
+    if message.command != MessageType::Encap || message.params.len() < 2 {
+        return None;
+    }
+    let inner = Message {
+        tags: message.tags.clone(),
+        prefix: message.prefix.clone(),
+        command: MessageType::Custom(message.params[1].to_uppercase()),
+        params: message.params[2..].to_vec(),
+    };
+    Some((message.params[0].clone(), inner))
+}
+
+/// Whether an ENCAP target mask (`*`, `*.example.net`, a server name) covers a server
+pub fn encap_targets(mask: &str, server: &str) -> bool {
+    crate::utils::string::wildcard_match(mask, server)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encap_registry_ownership() {
+        let mut registry = EncapRegistry::new();
+        registry.register("gline", &["GLINE".to_string(), "ungline".to_string()]).unwrap();
+        assert_eq!(registry.owner("UNGLINE"), Some("gline"));
+        assert!(registry.register("other", &["METADATA".to_string(), "Gline".to_string()]).is_err());
+        assert_eq!(registry.owner("METADATA"), None);
+
+        registry.unregister_module("gline");
+        assert!(registry.subcommands().is_empty());
+    }
+
+    #[test]
+    fn test_encap_round_trip() {
+        let message = encap_message("hub.test", "*.test", "gline", vec!["*@bad.host".to_string(), "No spam".to_string()]);
+        assert_eq!(message.to_string(), ":hub.test ENCAP *.test GLINE *@bad.host :No spam\r\n");
+
+        let parsed = Message::parse(&message.to_string()).unwrap();
+        let (target, inner) = unwrap_encap(&parsed).unwrap();
+        assert_eq!(target, "*.test");
+        assert_eq!(inner.command, MessageType::Custom("GLINE".to_string()));
+        assert_eq!(inner.params, vec!["*@bad.host".to_string(), "No spam".to_string()]);
+        assert_eq!(inner.prefix, Some(Prefix::Server("hub.test".to_string())));
+        assert!(encap_targets("*.test", "leaf.test"));
+        assert!(!encap_targets("hub.test", "leaf.test"));
+    }
+
+    #[tokio::test]
+    async fn test_relayed_numerics_complete_a_query() {
+        let manager = NetworkQueryManager::new(1, 10);
+        let requestor = Uuid::new_v4();
+        let request_id = manager.query_whois("carol".to_string(), requestor, vec!["leaf.test".to_string()]).await.unwrap();
+        assert_eq!(manager.pending_query_for(requestor, "LEAF.test").await, Some(request_id.clone()));
+        assert_eq!(manager.pending_query_for(requestor, "hub.test").await, None);
+
+        for (code, last) in [("311", false), ("318", true)] {
+            let message = Message::new(MessageType::Custom(code.to_string()), vec!["bob".to_string(), "carol".to_string()]);
+            manager.handle_response(NetworkResponse::Numeric {
+                request_id: request_id.clone(),
+                server: "leaf.test".to_string(),
+                message,
+                last,
+            }).await.unwrap();
+            assert_eq!(manager.is_query_complete(&request_id).await.unwrap(), last);
+        }
+
+        let responses = manager.wait_for_query(&request_id).await.unwrap();
+        assert_eq!(responses.len(), 2);
+        assert_eq!(manager.pending_query_count().await, 0);
+
+        // Nobody answers this one, so it times out
+        let request_id = manager.query_whois("dave".to_string(), requestor, vec!["leaf.test".to_string()]).await.unwrap();
+        assert!(manager.wait_for_query(&request_id).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_collected_numerics_end_on_the_query_final_numeric() {
+        let manager = NetworkQueryManager::new(1, 10);
+        let requestor = Uuid::new_v4();
+        let request_id = manager.query_server_info(MessageType::Stats, requestor, "leaf.test".to_string()).await.unwrap();
+        let numeric = |code: &str| Message::new(MessageType::Custom(code.to_string()), vec!["bob".to_string(), "u".to_string()]);
+
+        assert!(!manager.collect_numeric(requestor, "hub.test", numeric("242")).await);
+        assert!(manager.collect_numeric(requestor, "leaf.test", numeric("242")).await);
+        assert!(!manager.is_query_complete(&request_id).await.unwrap());
+        assert!(manager.collect_numeric(requestor, "leaf.test", numeric("219")).await);
+        assert_eq!(manager.wait_for_query(&request_id).await.map(|responses| responses.len()), Some(2));
+    }
 }
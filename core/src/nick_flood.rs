@@ -0,0 +1,79 @@
+//! Nickname change rate limiting
+//!
+//! Each user may change nickname `max_nick_changes` times within
+//! `nick_change_window` seconds; further changes get ERR_NICKTOOFAST (438)
+//! until the oldest change leaves the window.
+
+use crate::config::ServerConfig;
+use parking_lot::Mutex;
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+/// Per-client nickname change limiter
+#[derive(Debug)]
+pub struct NickFloodControl {
+    max_changes: usize,
+    window: Duration,
+    history: Mutex<HashMap<Uuid, VecDeque<Instant>>>,
+}
+
+impl NickFloodControl {
+    /// Create a limiter from configuration
+    pub fn new(config: &ServerConfig) -> Self {
+        Self {
+            max_changes: config.max_nick_changes,
+            window: Duration::from_secs(config.nick_change_window),
+            history: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Record a nickname change, or return how long the client must wait
+    pub fn check(&self, client_id: Uuid) -> Result<(), Duration> {
+        self.check_at(client_id, Instant::now())
+    }
+
+    fn check_at(&self, client_id: Uuid, now: Instant) -> Result<(), Duration> {
+        if self.max_changes == 0 {
+            return Ok(());
+        }
+        let mut history = self.history.lock();
+        let times = history.entry(client_id).or_default();
+        while times.front().is_some_and(|t| now.duration_since(*t) >= self.window) {
+            times.pop_front();
+        }
+        if times.len() >= self.max_changes {
+            let oldest = times.front().copied().unwrap_or(now);
+            return Err(self.window.saturating_sub(now.duration_since(oldest)));
+        }
+        times.push_back(now);
+        Ok(())
+    }
+
+    /// Forget a disconnected client
+    pub fn remove_client(&self, client_id: &Uuid) {
+        self.history.lock().remove(client_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_nick_changes_limited_per_window() {
+        let config = ServerConfig { max_nick_changes: 2, nick_change_window: 20, ..ServerConfig::default() };
+        let limiter = NickFloodControl::new(&config);
+        let client = Uuid::new_v4();
+        let start = Instant::now();
+
+        assert!(limiter.check_at(client, start).is_ok());
+        assert!(limiter.check_at(client, start + Duration::from_secs(5)).is_ok());
+        let wait = limiter.check_at(client, start + Duration::from_secs(8)).unwrap_err();
+        assert_eq!(wait, Duration::from_secs(12));
+
+        // Other clients have their own allowance, and the window slides
+        assert!(limiter.check_at(Uuid::new_v4(), start + Duration::from_secs(8)).is_ok());
+        assert!(limiter.check_at(client, start + Duration::from_secs(20)).is_ok());
+    }
+}
@@ -12,6 +12,9 @@ pub enum NumericReply {
     RplCreated = 003,
     RplMyInfo = 004,
     RplBounce = 005,
+    RplSnoMask = 8,
+    RplMap = 15,
+    RplMapEnd = 17,
     
     // Server queries
     RplAdminMe = 256,
@@ -25,12 +28,17 @@ pub enum NumericReply {
     RplWhoisIdle = 317,
     RplEndOfWhois = 318,
     RplWhoisChannels = 319,
+    RplWhoWasUser = 314,
     RplWhoisSpecial = 320,
+    RplWhoisBot = 335,
+    RplWhoisCertFp = 276,
     RplList = 322,
     RplListEnd = 323,
     RplChannelModeIs = 324,
     RplNoTopic = 331,
     RplTopic = 332,
+    RplTopicWhoTime = 333,
+    RplWhoisActually = 338,
     RplInviting = 341,
     RplSummoning = 342,
     RplInviteList = 346,
@@ -40,6 +48,7 @@ pub enum NumericReply {
     RplWhoReply = 352,
     RplEndOfWho = 315,
     RplNameReply = 353,
+    RplWhoSpcRpl = 354,
     RplEndOfNames = 366,
     RplLinks = 364,
     RplEndOfLinks = 365,
@@ -51,6 +60,9 @@ pub enum NumericReply {
     RplMotdStart = 375,
     RplMotd = 372,
     RplMotdEnd = 376,
+    RplRules = 232,
+    RplRulesStart = 308,
+    RplEndOfRules = 309,
     // RplEndOfMotd = same as RplMotdEnd per RFC
     // ERR_NOMOTD is an error, moved to error section
     RplYoureOper = 381,
@@ -60,6 +72,7 @@ pub enum NumericReply {
     RplUsers = 393,
     RplEndOfUsers = 394,
     RplNoUsers = 395,
+    RplHostHidden = 396,
     RplTraceLink = 200,
     RplTraceConnecting = 201,
     RplTraceHandshake = 202,
@@ -93,6 +106,9 @@ pub enum NumericReply {
     RplLUserMe = 255,
     RplLocalUsers = 265,
     RplGlobalUsers = 266,
+    RplLuserSecure = 267,
+    RplSileList = 271,
+    RplEndOfSileList = 272,
     RplAway = 301,
     RplUnaway = 305,
     RplNowAway = 306,
@@ -118,8 +134,10 @@ pub enum NumericReply {
     ErrNoTopLevel = 413,
     ErrWildTopLevel = 414,
     ErrBadMask = 415,
+    ErrInputTooLong = 417,
     ErrUnknownCommand = 421,
     ErrNoMotd = 422,  // ERR_NOMOTD per RFC
+    ErrNoRules = 434,
     ErrNoAdminInfo = 423,
     ErrFileError = 424,
     ErrNoNicknameGiven = 431,
@@ -127,6 +145,8 @@ pub enum NumericReply {
     ErrNicknameInUse = 433,
     ErrNickCollision = 436,
     ErrUnavailResource = 437,
+    ErrNickTooFast = 438,
+    ErrServicesDown = 440,
     ErrUserNotInChannel = 441,
     ErrNotOnChannel = 442,
     ErrUserOnChannel = 443,
@@ -140,6 +160,7 @@ pub enum NumericReply {
     ErrPasswordMismatch = 464,
     ErrYoureBannedCreep = 465,
     ErrKeySet = 467,
+    ErrLinkChannel = 470,
     ErrChannelIsFull = 471,
     ErrUnknownMode = 472,
     ErrInviteOnlyChan = 473,
@@ -148,15 +169,20 @@ pub enum NumericReply {
     ErrBadChanMask = 476,
     ErrNoChanModes = 477,
     ErrBanListFull = 478,
+    ErrBadChanName = 479,
+    ErrThrottle = 480,
     ErrNoPrivileges = 481,
     ErrChanOpPrivsNeeded = 482,
     ErrCantKillServer = 483,
     ErrRestricted = 484,
     ErrUniqOpPrivsNeeded = 485,
+    ErrSecureOnlyChan = 489,
     ErrNoOperHost = 491,
     ErrUModeUnknownFlag = 501,
     ErrUsersDontMatch = 502,
     ErrCantSetOperatorMode = 504,
+    ErrSileListFull = 511,
+    ErrOperOnly = 520,
     
     // Additional numeric replies for modules
     RplHelpStart = 704,
@@ -182,6 +208,9 @@ pub enum NumericReply {
     RplSettings = 724,
     RplSetting = 725,
     RplEndOfSettings = 726,
+    RplEline = 727,
+    RplQuietList = 728,
+    RplEndOfQuietList = 729,
 
     // Additional error replies for modules
     ErrHelpNotFound = 524,
@@ -195,6 +224,30 @@ pub enum NumericReply {
     ErrTooManyServices = 532,
     ErrInvalidName = 533,
     ErrDisabled = 534,
+    ErrNoSuchEline = 535,
+
+    // CHALLENGE (ratbox)
+    RplRsaChallenge2 = 740,
+    RplEndOfRsaChallenge2 = 741,
+
+    // METADATA (IRCv3 draft)
+    RplKeyValue = 761,
+    RplMetadataEnd = 762,
+    RplKeyNotSet = 766,
+    RplMetadataSubOk = 770,
+    RplMetadataUnsubOk = 771,
+    RplMetadataSubs = 772,
+
+    // SASL (IRCv3)
+    RplLoggedIn = 900,
+    RplLoggedOut = 901,
+    ErrNickLocked = 902,
+    RplSaslSuccess = 903,
+    ErrSaslFail = 904,
+    ErrSaslTooLong = 905,
+    ErrSaslAborted = 906,
+    ErrSaslAlready = 907,
+    RplSaslMechs = 908,
 
     // Custom numeric replies
     Custom(u16),
@@ -209,6 +262,9 @@ impl NumericReply {
             NumericReply::RplCreated => 003,
             NumericReply::RplMyInfo => 004,
             NumericReply::RplBounce => 005,
+            NumericReply::RplSnoMask => 8,
+            NumericReply::RplMap => 15,
+            NumericReply::RplMapEnd => 17,
             NumericReply::RplAdminMe => 256,
             NumericReply::RplAdminLoc1 => 257,
             NumericReply::RplAdminLoc2 => 258,
@@ -221,11 +277,15 @@ impl NumericReply {
             NumericReply::RplEndOfWhois => 318,
             NumericReply::RplWhoisChannels => 319,
             NumericReply::RplWhoisSpecial => 320,
+            NumericReply::RplWhoisBot => 335,
+            NumericReply::RplWhoisCertFp => 276,
             NumericReply::RplList => 322,
             NumericReply::RplListEnd => 323,
             NumericReply::RplChannelModeIs => 324,
             NumericReply::RplNoTopic => 331,
             NumericReply::RplTopic => 332,
+            NumericReply::RplTopicWhoTime => 333,
+            NumericReply::RplWhoisActually => 338,
             NumericReply::RplInviting => 341,
             NumericReply::RplSummoning => 342,
             NumericReply::RplInviteList => 346,
@@ -235,11 +295,13 @@ impl NumericReply {
             NumericReply::RplWhoReply => 352,
             NumericReply::RplEndOfWho => 315,
             NumericReply::RplNameReply => 353,
+            NumericReply::RplWhoSpcRpl => 354,
             NumericReply::RplEndOfNames => 366,
             NumericReply::RplLinks => 364,
             NumericReply::RplEndOfLinks => 365,
             NumericReply::RplBanList => 367,
             NumericReply::RplEndOfBanList => 368,
+            NumericReply::RplWhoWasUser => 314,
             NumericReply::RplEndOfWhoWas => 369,
             NumericReply::RplInfo => 371,
             NumericReply::RplEndOfInfo => 374,
@@ -247,6 +309,10 @@ impl NumericReply {
             NumericReply::RplMotd => 372,
             NumericReply::RplMotdEnd => 376,
             NumericReply::ErrNoMotd => 422,
+            NumericReply::RplRules => 232,
+            NumericReply::RplRulesStart => 308,
+            NumericReply::RplEndOfRules => 309,
+            NumericReply::ErrNoRules => 434,
             NumericReply::RplYoureOper => 381,
             NumericReply::RplRehashing => 382,
             NumericReply::RplTime => 391,
@@ -254,6 +320,7 @@ impl NumericReply {
             NumericReply::RplUsers => 393,
             NumericReply::RplEndOfUsers => 394,
             NumericReply::RplNoUsers => 395,
+            NumericReply::RplHostHidden => 396,
             NumericReply::RplTraceLink => 200,
             NumericReply::RplTraceConnecting => 201,
             NumericReply::RplTraceHandshake => 202,
@@ -298,6 +365,7 @@ impl NumericReply {
             NumericReply::ErrNoTopLevel => 413,
             NumericReply::ErrWildTopLevel => 414,
             NumericReply::ErrBadMask => 415,
+            NumericReply::ErrInputTooLong => 417,
             NumericReply::ErrUnknownCommand => 421,
             NumericReply::ErrNoAdminInfo => 423,
             NumericReply::ErrFileError => 424,
@@ -306,6 +374,8 @@ impl NumericReply {
             NumericReply::ErrNicknameInUse => 433,
             NumericReply::ErrNickCollision => 436,
             NumericReply::ErrUnavailResource => 437,
+            NumericReply::ErrNickTooFast => 438,
+            NumericReply::ErrServicesDown => 440,
             NumericReply::ErrUserNotInChannel => 441,
             NumericReply::ErrNotOnChannel => 442,
             NumericReply::ErrUserOnChannel => 443,
@@ -319,6 +389,7 @@ impl NumericReply {
             NumericReply::ErrPasswordMismatch => 464,
             NumericReply::ErrYoureBannedCreep => 465,
             NumericReply::ErrKeySet => 467,
+            NumericReply::ErrLinkChannel => 470,
             NumericReply::ErrChannelIsFull => 471,
             NumericReply::ErrUnknownMode => 472,
             NumericReply::ErrInviteOnlyChan => 473,
@@ -327,6 +398,8 @@ impl NumericReply {
             NumericReply::ErrBadChanMask => 476,
             NumericReply::ErrNoChanModes => 477,
             NumericReply::ErrBanListFull => 478,
+            NumericReply::ErrBadChanName => 479,
+            NumericReply::ErrThrottle => 480,
             NumericReply::ErrNoPrivileges => 481,
             NumericReply::ErrChanOpPrivsNeeded => 482,
             NumericReply::ErrCantKillServer => 483,
@@ -342,10 +415,16 @@ impl NumericReply {
             NumericReply::RplStatsM => 245,
             NumericReply::RplLocalUsers => 265,
             NumericReply::RplGlobalUsers => 266,
+            NumericReply::RplLuserSecure => 267,
+            NumericReply::RplSileList => 271,
+            NumericReply::RplEndOfSileList => 272,
             NumericReply::ErrUniqOpPrivsNeeded => 485,
+            NumericReply::ErrSecureOnlyChan => 489,
             NumericReply::ErrNoOperHost => 491,
             NumericReply::ErrUModeUnknownFlag => 501,
             NumericReply::ErrCantSetOperatorMode => 504,
+            NumericReply::ErrSileListFull => 511,
+            NumericReply::ErrOperOnly => 520,
             NumericReply::RplHelpStart => 704,
             NumericReply::RplHelpTxt => 705,
             NumericReply::RplEndOfHelp => 706,
@@ -369,6 +448,9 @@ impl NumericReply {
             NumericReply::RplSettings => 724,
             NumericReply::RplSetting => 725,
             NumericReply::RplEndOfSettings => 726,
+            NumericReply::RplEline => 727,
+            NumericReply::RplQuietList => 728,
+            NumericReply::RplEndOfQuietList => 729,
             NumericReply::ErrHelpNotFound => 524,
             NumericReply::ErrNoSuchGline => 525,
             NumericReply::ErrNoSuchKline => 526,
@@ -380,6 +462,24 @@ impl NumericReply {
             NumericReply::ErrTooManyServices => 532,
             NumericReply::ErrInvalidName => 533,
             NumericReply::ErrDisabled => 534,
+            NumericReply::ErrNoSuchEline => 535,
+            NumericReply::RplRsaChallenge2 => 740,
+            NumericReply::RplEndOfRsaChallenge2 => 741,
+            NumericReply::RplKeyValue => 761,
+            NumericReply::RplMetadataEnd => 762,
+            NumericReply::RplKeyNotSet => 766,
+            NumericReply::RplMetadataSubOk => 770,
+            NumericReply::RplMetadataUnsubOk => 771,
+            NumericReply::RplMetadataSubs => 772,
+            NumericReply::RplLoggedIn => 900,
+            NumericReply::RplLoggedOut => 901,
+            NumericReply::ErrNickLocked => 902,
+            NumericReply::RplSaslSuccess => 903,
+            NumericReply::ErrSaslFail => 904,
+            NumericReply::ErrSaslTooLong => 905,
+            NumericReply::ErrSaslAborted => 906,
+            NumericReply::ErrSaslAlready => 907,
+            NumericReply::RplSaslMechs => 908,
             NumericReply::Custom(code) => *code,
         }
     }
@@ -396,6 +496,9 @@ impl NumericReply {
                     NumericReply::RplCreated => 3,
                     NumericReply::RplMyInfo => 4,
                     NumericReply::RplBounce => 5,
+                    NumericReply::RplSnoMask => 8,
+                    NumericReply::RplMap => 15,
+                    NumericReply::RplMapEnd => 17,
                     NumericReply::RplAdminMe => 256,
                     NumericReply::RplAdminLoc1 => 257,
                     NumericReply::RplAdminLoc2 => 258,
@@ -408,11 +511,15 @@ impl NumericReply {
                     NumericReply::RplEndOfWhois => 318,
                     NumericReply::RplWhoisChannels => 319,
                     NumericReply::RplWhoisSpecial => 320,
+                    NumericReply::RplWhoisBot => 335,
+                    NumericReply::RplWhoisCertFp => 276,
                     NumericReply::RplList => 322,
                     NumericReply::RplListEnd => 323,
                     NumericReply::RplChannelModeIs => 324,
                     NumericReply::RplNoTopic => 331,
                     NumericReply::RplTopic => 332,
+                    NumericReply::RplTopicWhoTime => 333,
+                    NumericReply::RplWhoisActually => 338,
                     NumericReply::RplInviting => 341,
                     NumericReply::RplSummoning => 342,
                     NumericReply::RplInviteList => 346,
@@ -422,11 +529,13 @@ impl NumericReply {
                     NumericReply::RplWhoReply => 352,
                     NumericReply::RplEndOfWho => 315,
                     NumericReply::RplNameReply => 353,
+                    NumericReply::RplWhoSpcRpl => 354,
                     NumericReply::RplEndOfNames => 366,
                     NumericReply::RplLinks => 364,
                     NumericReply::RplEndOfLinks => 365,
                     NumericReply::RplBanList => 367,
                     NumericReply::RplEndOfBanList => 368,
+                    NumericReply::RplWhoWasUser => 314,
                     NumericReply::RplEndOfWhoWas => 369,
                     NumericReply::RplInfo => 371,
                     NumericReply::RplEndOfInfo => 374,
@@ -434,6 +543,10 @@ impl NumericReply {
                     NumericReply::RplMotd => 372,
                     NumericReply::RplMotdEnd => 376,
                             NumericReply::ErrNoMotd => 422,
+                    NumericReply::RplRules => 232,
+                    NumericReply::RplRulesStart => 308,
+                    NumericReply::RplEndOfRules => 309,
+                    NumericReply::ErrNoRules => 434,
                     NumericReply::RplYoureOper => 381,
                     NumericReply::RplRehashing => 382,
                     NumericReply::RplTime => 391,
@@ -441,6 +554,7 @@ impl NumericReply {
                     NumericReply::RplUsers => 393,
                     NumericReply::RplEndOfUsers => 394,
                     NumericReply::RplNoUsers => 395,
+                    NumericReply::RplHostHidden => 396,
                     NumericReply::RplTraceLink => 200,
                     NumericReply::RplTraceConnecting => 201,
                     NumericReply::RplTraceHandshake => 202,
@@ -485,6 +599,7 @@ impl NumericReply {
                     NumericReply::ErrNoTopLevel => 413,
                     NumericReply::ErrWildTopLevel => 414,
                     NumericReply::ErrBadMask => 415,
+                    NumericReply::ErrInputTooLong => 417,
                     NumericReply::ErrUnknownCommand => 421,
                     NumericReply::ErrNoAdminInfo => 423,
                     NumericReply::ErrFileError => 424,
@@ -493,6 +608,8 @@ impl NumericReply {
                     NumericReply::ErrNicknameInUse => 433,
                     NumericReply::ErrNickCollision => 436,
                     NumericReply::ErrUnavailResource => 437,
+                    NumericReply::ErrNickTooFast => 438,
+                    NumericReply::ErrServicesDown => 440,
                     NumericReply::ErrUserNotInChannel => 441,
                     NumericReply::ErrNotOnChannel => 442,
                     NumericReply::ErrUserOnChannel => 443,
@@ -506,6 +623,7 @@ impl NumericReply {
                     NumericReply::ErrPasswordMismatch => 464,
                     NumericReply::ErrYoureBannedCreep => 465,
                     NumericReply::ErrKeySet => 467,
+                    NumericReply::ErrLinkChannel => 470,
                     NumericReply::ErrChannelIsFull => 471,
                     NumericReply::ErrUnknownMode => 472,
                     NumericReply::ErrInviteOnlyChan => 473,
@@ -514,15 +632,20 @@ impl NumericReply {
                     NumericReply::ErrBadChanMask => 476,
                     NumericReply::ErrNoChanModes => 477,
                     NumericReply::ErrBanListFull => 478,
+                    NumericReply::ErrBadChanName => 479,
+                    NumericReply::ErrThrottle => 480,
                     NumericReply::ErrNoPrivileges => 481,
                     NumericReply::ErrChanOpPrivsNeeded => 482,
                     NumericReply::ErrCantKillServer => 483,
                     NumericReply::ErrRestricted => 484,
                     NumericReply::ErrUniqOpPrivsNeeded => 485,
+                    NumericReply::ErrSecureOnlyChan => 489,
                     NumericReply::ErrNoOperHost => 491,
                     NumericReply::ErrUModeUnknownFlag => 501,
                     NumericReply::ErrUsersDontMatch => 502,
                     NumericReply::ErrCantSetOperatorMode => 504,
+                    NumericReply::ErrSileListFull => 511,
+                    NumericReply::ErrOperOnly => 520,
                     NumericReply::RplStatsCLine => 213,
                     NumericReply::RplStatsNLine => 214,
                     NumericReply::RplStatsILine => 215,
@@ -533,6 +656,9 @@ impl NumericReply {
                     NumericReply::RplStatsM => 245,
                     NumericReply::RplLocalUsers => 265,
                     NumericReply::RplGlobalUsers => 266,
+                    NumericReply::RplLuserSecure => 267,
+                    NumericReply::RplSileList => 271,
+                    NumericReply::RplEndOfSileList => 272,
                     NumericReply::RplHelpStart => 704,
                     NumericReply::RplHelpTxt => 705,
                     NumericReply::RplEndOfHelp => 706,
@@ -556,6 +682,9 @@ impl NumericReply {
                     NumericReply::RplSettings => 724,
                     NumericReply::RplSetting => 725,
                     NumericReply::RplEndOfSettings => 726,
+                    NumericReply::RplEline => 727,
+                    NumericReply::RplQuietList => 728,
+                    NumericReply::RplEndOfQuietList => 729,
                     NumericReply::ErrHelpNotFound => 524,
                     NumericReply::ErrNoSuchGline => 525,
                     NumericReply::ErrNoSuchKline => 526,
@@ -567,6 +696,24 @@ impl NumericReply {
                     NumericReply::ErrTooManyServices => 532,
                     NumericReply::ErrInvalidName => 533,
                     NumericReply::ErrDisabled => 534,
+                    NumericReply::ErrNoSuchEline => 535,
+                    NumericReply::RplRsaChallenge2 => 740,
+                    NumericReply::RplEndOfRsaChallenge2 => 741,
+                    NumericReply::RplKeyValue => 761,
+                    NumericReply::RplMetadataEnd => 762,
+                    NumericReply::RplKeyNotSet => 766,
+                    NumericReply::RplMetadataSubOk => 770,
+                    NumericReply::RplMetadataUnsubOk => 771,
+                    NumericReply::RplMetadataSubs => 772,
+                    NumericReply::RplLoggedIn => 900,
+                    NumericReply::RplLoggedOut => 901,
+                    NumericReply::ErrNickLocked => 902,
+                    NumericReply::RplSaslSuccess => 903,
+                    NumericReply::ErrSaslFail => 904,
+                    NumericReply::ErrSaslTooLong => 905,
+                    NumericReply::ErrSaslAborted => 906,
+                    NumericReply::ErrSaslAlready => 907,
+                    NumericReply::RplSaslMechs => 908,
                     NumericReply::Custom(_) => unreachable!(), // Already handled above
                 };
                 format!("{:03}", code)
@@ -584,27 +731,6 @@ impl NumericReply {
             all_params,
         )
     }
-    
-    /// Create a numeric reply message using configurable replies
-    pub fn reply_with_config(&self, target: &str, params: &std::collections::HashMap<String, String>, replies_config: &crate::RepliesConfig, server_info: &crate::RepliesServerInfo) -> Message {
-        let code = self.numeric_code();
-        
-        // Try to get custom reply text from configuration
-        if let Some(reply_text) = replies_config.format_reply(code, params, server_info) {
-            // Split the reply text into parts (target + message)
-            let parts: Vec<&str> = reply_text.splitn(2, ' ').collect();
-            if parts.len() >= 2 {
-                let message = parts[1].to_string();
-                return Message::new(
-                    crate::MessageType::Custom(self.code()),
-                    vec![target.to_string(), message],
-                );
-            }
-        }
-        
-        // Fall back to default behavior
-        self.reply(target, vec![])
-    }
 }
 
 /// Common numeric replies
@@ -620,7 +746,7 @@ impl NumericReply {
     /// RPL_YOURHOST
     pub fn your_host(server: &str, version: &str) -> Message {
         Self::RplYourHost.reply(
-            "client",
+            "*",
             vec![format!("Your host is {}, running version {}", server, version)],
         )
     }
@@ -628,7 +754,7 @@ impl NumericReply {
     /// RPL_CREATED
     pub fn created(_server: &str, date: &str) -> Message {
         Self::RplCreated.reply(
-            "client",
+            "*",
             vec![format!("This server was created {}", date)],
         )
     }
@@ -636,11 +762,18 @@ impl NumericReply {
     /// RPL_MYINFO
     pub fn my_info(server: &str, version: &str, user_modes: &str, channel_modes: &str) -> Message {
         Self::RplMyInfo.reply(
-            "client",
-            vec![format!("{} {} {} {}", server, version, user_modes, channel_modes)],
+            "*",
+            vec![server.to_string(), version.to_string(), user_modes.to_string(), channel_modes.to_string()],
         )
     }
     
+    /// RPL_ISUPPORT (shares 005 with RPL_BOUNCE)
+    pub fn isupport(nick: &str, tokens: &[String]) -> Message {
+        let mut params: Vec<String> = tokens.to_vec();
+        params.push("are supported by this server".to_string());
+        Self::RplBounce.reply(nick, params)
+    }
+    
     /// ERR_NONICKNAMEGIVEN
     pub fn no_nickname_given() -> Message {
         Self::ErrNoNicknameGiven.reply(
@@ -657,6 +790,14 @@ impl NumericReply {
         )
     }
     
+    /// ERR_ERRONEUSNICKNAME for a reserved (RESV) nickname
+    pub fn reserved_nickname(nick: &str, reason: &str) -> Message {
+        Self::ErrErroneousNickname.reply(
+            nick,
+            vec![format!("Erroneous nickname: {}", reason)],
+        )
+    }
+    
     /// ERR_NICKNAMEINUSE
     pub fn nickname_in_use(nick: &str) -> Message {
         Self::ErrNicknameInUse.reply(
@@ -665,6 +806,32 @@ impl NumericReply {
         )
     }
     
+    /// ERR_NICKTOOFAST
+    pub fn nick_too_fast(nick: &str, new_nick: &str, wait_seconds: u64) -> Message {
+        Self::ErrNickTooFast.reply(
+            nick,
+            vec![new_nick.to_string(), format!("Nick change too fast. Please wait {} seconds.", wait_seconds)],
+        )
+    }
+    
+    /// ERR_SERVICESDOWN - `target` is a `nick@server` service that isn't linked
+    pub fn services_down(nick: &str, target: &str) -> Message {
+        Self::ErrServicesDown.reply(
+            nick,
+            vec![target.to_string(), "Services are currently unavailable".to_string()],
+        )
+    }
+    
+    /// RPL_HOSTHIDDEN
+    pub fn host_hidden(nick: &str, host: &str) -> Message {
+        Self::RplHostHidden.reply(nick, vec![host.to_string(), "is now your displayed host".to_string()])
+    }
+    
+    /// ERR_INPUTTOOLONG
+    pub fn input_too_long() -> Message {
+        Self::ErrInputTooLong.reply("*", vec!["Input line was too long".to_string()])
+    }
+    
     /// ERR_NOTREGISTERED
     pub fn not_registered() -> Message {
         Self::ErrNotRegistered.reply(
@@ -705,11 +872,19 @@ impl NumericReply {
         )
     }
     
+    /// ERR_NOSUCHSERVER for a server that didn't answer a relayed query in time
+    pub fn remote_query_timeout(server: &str) -> Message {
+        Self::ErrNoSuchServer.reply(
+            "*",
+            vec![server.to_string(), "Server did not answer in time".to_string()],
+        )
+    }
+    
     /// ERR_NEEDMOREPARAMS
     pub fn need_more_params(command: &str) -> Message {
         Self::ErrNeedMoreParams.reply(
             "*",
-            vec![format!("Not enough parameters"), command.to_string()],
+            vec![command.to_string(), "Not enough parameters".to_string()],
         )
     }
     
@@ -729,6 +904,22 @@ impl NumericReply {
         )
     }
     
+    /// ERR_YOUREBANNEDCREEP
+    pub fn youre_banned_creep(reason: &str) -> Message {
+        Self::ErrYoureBannedCreep.reply(
+            "*",
+            vec![format!("You are banned from this server: {}", reason)],
+        )
+    }
+    
+    /// ERR_BADCHANNAME (channel reserved with RESV)
+    pub fn bad_chan_name(channel: &str, reason: &str) -> Message {
+        Self::ErrBadChanName.reply(
+            channel,
+            vec![format!("Cannot join channel: {}", reason)],
+        )
+    }
+    
     // Server query replies
     
     /// RPL_ADMINME
@@ -802,18 +993,32 @@ impl NumericReply {
     }
     
     /// RPL_LINKS
-    pub fn links(mask: &str, server: &str, hopcount: u32, server_info: &str) -> Message {
+    pub fn links(nick: &str, server: &str, uplink: &str, hopcount: u32, server_info: &str) -> Message {
         Self::RplLinks.reply(
-            "*",
+            nick,
             vec![
-                mask.to_string(),
                 server.to_string(),
-                hopcount.to_string(),
-                server_info.to_string(),
+                uplink.to_string(),
+                format!("{} {}", hopcount, server_info),
             ],
         )
     }
     
+    /// RPL_MAP
+    pub fn map(nick: &str, line: &str) -> Message {
+        Self::RplMap.reply(nick, vec![line.to_string()])
+    }
+
+    /// RPL_MAPEND
+    pub fn map_end(nick: &str) -> Message {
+        Self::RplMapEnd.reply(nick, vec!["End of /MAP".to_string()])
+    }
+    
+    /// RPL_REHASHING
+    pub fn rehashing(nick: &str, file: &str) -> Message {
+        Self::RplRehashing.reply(nick, vec![file.to_string(), "Rehashing".to_string()])
+    }
+    
     /// RPL_ENDOFLINKS
     pub fn end_of_links(mask: &str) -> Message {
         Self::RplEndOfLinks.reply(
@@ -882,6 +1087,23 @@ impl NumericReply {
         )
     }
     
+    /// RPL_STATSLINKINFO line with compression ratios for a link
+    pub fn stats_link_compression(server: &str, stats: &crate::link_compression::CompressionStats) -> Message {
+        let (plain_out, compressed_out) = stats.sent();
+        let (plain_in, compressed_in) = stats.received();
+        let info_text = format!(
+            "{} Zip: sent {}/{} bytes ({:.1}%) received {}/{} bytes ({:.1}%)",
+            server,
+            compressed_out, plain_out, stats.sent_ratio(),
+            compressed_in, plain_in, stats.received_ratio()
+        );
+
+        Self::RplStatsLinkInfo.reply(
+            "*",
+            vec![info_text],
+        )
+    }
+    
     /// RPL_STATSCOMMANDS
     pub fn stats_commands(command: &str, count: u32, bytes: u32, remote_count: u32) -> Message {
         Self::RplStatsCommands.reply(
@@ -939,7 +1161,7 @@ impl NumericReply {
     pub fn motd_start(server: &str) -> Message {
         Self::RplMotdStart.reply(
             "*",
-            vec![format!(":- {} Message of the Day -", server)],
+            vec![format!("- {} Message of the Day -", server)],
         )
     }
     
@@ -947,7 +1169,7 @@ impl NumericReply {
     pub fn motd_line(line: &str) -> Message {
         Self::RplMotd.reply(
             "*",
-            vec![format!(":- {}", line)],
+            vec![format!("- {}", line)],
         )
     }
     
@@ -955,7 +1177,7 @@ impl NumericReply {
     pub fn motd_end(_server: &str) -> Message {
         Self::RplMotdEnd.reply(
             "*",
-            vec![format!(":End of /MOTD command.")],
+            vec!["End of /MOTD command.".to_string()],
         )
     }
     
@@ -963,15 +1185,39 @@ impl NumericReply {
     pub fn no_motd(_server: &str) -> Message {
         Self::ErrNoMotd.reply(
             "*",
-            vec![format!(":MOTD file is missing")],
+            vec!["MOTD file is missing".to_string()],
+        )
+    }
+    
+    /// RPL_RULESSTART
+    pub fn rules_start(server: &str) -> Message {
+        Self::RplRulesStart.reply(
+            "*",
+            vec![format!("- {} Server Rules -", server)],
+        )
+    }
+    
+    /// RPL_RULES (rules line)
+    pub fn rules_line(line: &str) -> Message {
+        Self::RplRules.reply(
+            "*",
+            vec![format!("- {}", line)],
         )
     }
     
-    /// RPL_TRACEUSER
-    pub fn trace_user(class: &str, client: &str) -> Message {
-        Self::RplTraceUser.reply(
+    /// RPL_ENDOFRULES
+    pub fn rules_end() -> Message {
+        Self::RplEndOfRules.reply(
             "*",
-            vec![class.to_string(), client.to_string()],
+            vec!["End of RULES command.".to_string()],
+        )
+    }
+    
+    /// ERR_NORULES
+    pub fn no_rules(_server: &str) -> Message {
+        Self::ErrNoRules.reply(
+            "*",
+            vec!["RULES File is missing".to_string()],
         )
     }
     
@@ -989,6 +1235,23 @@ impl NumericReply {
         )
     }
     
+    /// RPL_TRACEUSER, or RPL_TRACEOPERATOR for operators
+    ///
+    /// After the RFC fields come the idle time in seconds and the lag of
+    /// the last answered PING, when there was one.
+    pub fn trace_user(class: &str, nick: &str, is_operator: bool, idle_seconds: u64, lag: Option<std::time::Duration>) -> Message {
+        let (numeric, kind) = if is_operator {
+            (Self::RplTraceOperator, "Oper")
+        } else {
+            (Self::RplTraceUser, "User")
+        };
+        let lag = lag.map(|lag| format!("{}ms", lag.as_millis())).unwrap_or_else(|| "-".to_string());
+        numeric.reply(
+            "*",
+            vec![kind.to_string(), class.to_string(), nick.to_string(), idle_seconds.to_string(), lag],
+        )
+    }
+    
     /// RPL_TRACEEND
     pub fn trace_end(server: &str, version: &str) -> Message {
         Self::RplTraceEnd.reply(
@@ -1038,6 +1301,32 @@ impl NumericReply {
         )
     }
     
+    /// RPL_WHOISCERTFP
+    pub fn whois_certfp(nick: &str, certfp: &str) -> Message {
+        Self::RplWhoisCertFp.reply(
+            "*",
+            vec![nick.to_string(), format!("has client certificate fingerprint {}", certfp)],
+        )
+    }
+    
+    /// RPL_WHOISACTUALLY
+    pub fn whois_actually(nick: &str, user_host: &str, ip: &str) -> Message {
+        Self::RplWhoisActually.reply(
+            "*",
+            vec![
+                nick.to_string(),
+                user_host.to_string(),
+                ip.to_string(),
+                "actually using host".to_string(),
+            ],
+        )
+    }
+    
+    /// RPL_WHOSPCRPL (WHOX reply, fields already in request order)
+    pub fn whox_reply(fields: Vec<String>) -> Message {
+        Self::RplWhoSpcRpl.reply("*", fields)
+    }
+    
     /// RPL_WHOISSERVER
     pub fn whois_server(nick: &str, server: &str, server_info: &str) -> Message {
         Self::RplWhoisServer.reply(
@@ -1113,7 +1402,7 @@ impl NumericReply {
     
     /// RPL_WHOWASUSER
     pub fn whowas_user(nick: &str, username: &str, host: &str, realname: &str) -> Message {
-        Self::RplWhoisUser.reply( // Reuse WHOISUSER numeric
+        Self::RplWhoWasUser.reply(
             "*",
             vec![
                 nick.to_string(),
@@ -1125,6 +1414,14 @@ impl NumericReply {
         )
     }
     
+    /// ERR_WASNOSUCHNICK
+    pub fn was_no_such_nick(nick: &str) -> Message {
+        Self::ErrWasNoSuchNick.reply(
+            "*",
+            vec![nick.to_string(), "There was no such nickname".to_string()],
+        )
+    }
+    
     /// RPL_ENDOFWHOWAS
     pub fn end_of_whowas(nick: &str) -> Message {
         Self::RplEndOfWhoWas.reply(
@@ -1136,7 +1433,15 @@ impl NumericReply {
     // Bot mode replies
     
     /// RPL_WHOISBOT
-    pub fn whois_bot(nick: &str, bot_name: &str, description: &str) -> Message {
+    pub fn whois_bot(nick: &str, network: &str) -> Message {
+        Self::RplWhoisBot.reply(
+            "*",
+            vec![nick.to_string(), format!("is a bot on {}", network)],
+        )
+    }
+    
+    /// RPL_WHOISSPECIAL naming a registered bot
+    pub fn whois_bot_name(nick: &str, bot_name: &str, description: &str) -> Message {
         Self::RplWhoisSpecial.reply(
             "*",
             vec![nick.to_string(), format!("is a bot named {}: {}", bot_name, description)],
@@ -1183,7 +1488,7 @@ impl NumericReply {
     pub fn ison(nicks: &[String]) -> Message {
         Self::RplIson.reply(
             "*",
-            vec![format!(":{}", nicks.join(" "))],
+            vec![nicks.join(" ")],
         )
     }
     
@@ -1193,18 +1498,18 @@ impl NumericReply {
     pub fn userhost(entries: &[String]) -> Message {
         Self::RplUserhost.reply(
             "*",
-            vec![format!(":{}", entries.join(" "))],
+            vec![entries.join(" ")],
         )
     }
 
     // LUSERS command replies
     
     /// RPL_LUSERCLIENT
-    pub fn luser_client(users: u32, services: u32, servers: u32) -> Message {
+    pub fn luser_client(users: u32, invisible: u32, servers: u32) -> Message {
         Self::RplLUserClient.reply(
             "*",
             vec![
-                format!("There are {} users and {} services on {} servers", users, services, servers),
+                format!("There are {} users and {} invisible on {} servers", users, invisible, servers),
             ],
         )
     }
@@ -1261,6 +1566,16 @@ impl NumericReply {
         )
     }
 
+    /// RPL_LUSERSECURE - split of local users by TLS and plaintext connections
+    pub fn luser_secure(secure: u32, plaintext: u32) -> Message {
+        Self::RplLuserSecure.reply(
+            "*",
+            vec![
+                format!("Current local users over TLS: {}, plaintext: {}", secure, plaintext),
+            ],
+        )
+    }
+
     // USERS command replies
     
     /// RPL_USERSSTART
@@ -1299,6 +1614,14 @@ impl NumericReply {
 
     // User mode replies
     
+    /// RPL_SNOMASK
+    pub fn snomask(nick: &str, masks: &str) -> Message {
+        Self::RplSnoMask.reply(
+            nick,
+            vec![masks.to_string(), "Server notice mask".to_string()],
+        )
+    }
+    
     /// RPL_UMODEIS
     pub fn umode_is(nick: &str, modes: &str) -> Message {
         Self::RplUmodeIs.reply(
@@ -1307,6 +1630,252 @@ impl NumericReply {
         )
     }
 
+    // SILENCE replies
+
+    /// RPL_SILELIST
+    pub fn sile_list(nick: &str, mask: &str) -> Message {
+        Self::RplSileList.reply(
+            nick,
+            vec![mask.to_string()],
+        )
+    }
+
+    /// RPL_ENDOFSILELIST
+    pub fn end_of_sile_list(nick: &str) -> Message {
+        Self::RplEndOfSileList.reply(
+            nick,
+            vec!["End of Silence List".to_string()],
+        )
+    }
+
+    /// ERR_SILELISTFULL
+    pub fn sile_list_full(nick: &str, mask: &str) -> Message {
+        Self::ErrSileListFull.reply(
+            nick,
+            vec![mask.to_string(), "Your silence list is full".to_string()],
+        )
+    }
+
+    /// RPL_NOTOPIC
+    pub fn no_topic(nick: &str, channel: &str) -> Message {
+        Self::RplNoTopic.reply(nick, vec![channel.to_string(), "No topic is set".to_string()])
+    }
+
+    /// RPL_TOPIC
+    pub fn topic(nick: &str, channel: &str, topic: &str) -> Message {
+        Self::RplTopic.reply(nick, vec![channel.to_string(), topic.to_string()])
+    }
+
+    /// RPL_TOPICWHOTIME
+    pub fn topic_who_time(nick: &str, channel: &str, setter: &str, set_at: i64) -> Message {
+        Self::RplTopicWhoTime.reply(
+            nick,
+            vec![channel.to_string(), setter.to_string(), set_at.to_string()],
+        )
+    }
+
+    /// Reply for a JOIN refused by a channel mode (i, k, b, l, j, R, S or O)
+    pub fn cannot_join(nick: &str, channel: &str, mode: char) -> Message {
+        let (numeric, reason) = match mode {
+            'i' => (Self::ErrInviteOnlyChan, "you must be invited"),
+            'k' => (Self::ErrBadChannelKey, "you need the correct key"),
+            'b' => (Self::ErrBannedFromChan, "you are banned"),
+            'l' => (Self::ErrChannelIsFull, "channel is full"),
+            'j' => (Self::ErrThrottle, "throttle exceeded, try again later"),
+            'R' => (Self::ErrNoChanModes, "you need to be identified with services"),
+            'S' => (Self::ErrSecureOnlyChan, "you need a TLS connection"),
+            'O' => (Self::ErrOperOnly, "you need to be an IRC operator"),
+            _ => (Self::ErrNoChanModes, "channel restriction"),
+        };
+        numeric.reply(
+            nick,
+            vec![channel.to_string(), format!("Cannot join channel (+{}) - {}", mode, reason)],
+        )
+    }
+
+    /// ERR_NOSUCHCHANNEL
+    pub fn no_such_channel(nick: &str, channel: &str) -> Message {
+        Self::ErrNoSuchChannel.reply(nick, vec![channel.to_string(), "No such channel".to_string()])
+    }
+
+    /// ERR_USERNOTINCHANNEL
+    pub fn user_not_in_channel(nick: &str, target: &str, channel: &str) -> Message {
+        Self::ErrUserNotInChannel.reply(
+            nick,
+            vec![target.to_string(), channel.to_string(), "They aren't on that channel".to_string()],
+        )
+    }
+
+    /// ERR_NOTONCHANNEL
+    pub fn not_on_channel(nick: &str, channel: &str) -> Message {
+        Self::ErrNotOnChannel.reply(
+            nick,
+            vec![channel.to_string(), "You're not on that channel".to_string()],
+        )
+    }
+
+    /// ERR_CHANOPRIVSNEEDED
+    pub fn chanop_privs_needed(nick: &str, channel: &str) -> Message {
+        Self::ErrChanOpPrivsNeeded.reply(
+            nick,
+            vec![channel.to_string(), "You're not channel operator".to_string()],
+        )
+    }
+
+    /// RPL_BANLIST
+    pub fn ban_list(nick: &str, channel: &str, mask: &str) -> Message {
+        Self::RplBanList.reply(nick, vec![channel.to_string(), mask.to_string()])
+    }
+
+    /// RPL_ENDOFBANLIST
+    pub fn end_of_ban_list(nick: &str, channel: &str) -> Message {
+        Self::RplEndOfBanList.reply(
+            nick,
+            vec![channel.to_string(), "End of channel ban list".to_string()],
+        )
+    }
+
+    /// RPL_EXCEPTLIST
+    pub fn except_list(nick: &str, channel: &str, mask: &str) -> Message {
+        Self::RplExceptList.reply(nick, vec![channel.to_string(), mask.to_string()])
+    }
+
+    /// RPL_ENDOFEXCEPTLIST
+    pub fn end_of_except_list(nick: &str, channel: &str) -> Message {
+        Self::RplEndOfExceptList.reply(
+            nick,
+            vec![channel.to_string(), "End of channel exception list".to_string()],
+        )
+    }
+
+    /// RPL_INVITELIST
+    pub fn invite_list(nick: &str, channel: &str, mask: &str) -> Message {
+        Self::RplInviteList.reply(nick, vec![channel.to_string(), mask.to_string()])
+    }
+
+    /// RPL_ENDOFINVITELIST
+    pub fn end_of_invite_list(nick: &str, channel: &str) -> Message {
+        Self::RplEndOfInviteList.reply(
+            nick,
+            vec![channel.to_string(), "End of channel invite list".to_string()],
+        )
+    }
+
+    /// RPL_QUIETLIST - one +q entry, with the mode letter as in charybdis
+    pub fn quiet_list(nick: &str, channel: &str, mask: &str) -> Message {
+        Self::RplQuietList.reply(
+            nick,
+            vec![channel.to_string(), "q".to_string(), mask.to_string()],
+        )
+    }
+
+    /// RPL_ENDOFQUIETLIST
+    pub fn end_of_quiet_list(nick: &str, channel: &str) -> Message {
+        Self::RplEndOfQuietList.reply(
+            nick,
+            vec![channel.to_string(), "q".to_string(), "End of channel quiet list".to_string()],
+        )
+    }
+
+    /// ERR_NOOPERHOST
+    pub fn no_oper_host(nick: &str) -> Message {
+        Self::ErrNoOperHost.reply(nick, vec!["No appropriate operator blocks were found for your host".to_string()])
+    }
+
+    /// RPL_RSACHALLENGE2 - one line of a CHALLENGE
+    pub fn rsa_challenge(nick: &str, chunk: &str) -> Message {
+        Self::RplRsaChallenge2.reply(nick, vec![chunk.to_string()])
+    }
+
+    /// RPL_ENDOFRSACHALLENGE2
+    pub fn end_of_rsa_challenge(nick: &str) -> Message {
+        Self::RplEndOfRsaChallenge2.reply(nick, vec!["End of CHALLENGE".to_string()])
+    }
+
+    /// RPL_KEYVALUE
+    pub fn key_value(nick: &str, target: &str, key: &str, visibility: &str, value: &str) -> Message {
+        Self::RplKeyValue.reply(nick, vec![target.to_string(), key.to_string(), visibility.to_string(), value.to_string()])
+    }
+
+    /// RPL_METADATAEND
+    pub fn metadata_end(nick: &str) -> Message {
+        Self::RplMetadataEnd.reply(nick, vec!["end of metadata".to_string()])
+    }
+
+    /// RPL_KEYNOTSET
+    pub fn key_not_set(nick: &str, target: &str, key: &str) -> Message {
+        Self::RplKeyNotSet.reply(nick, vec![target.to_string(), key.to_string(), "key not set".to_string()])
+    }
+
+    /// RPL_METADATASUBOK
+    pub fn metadata_sub_ok(nick: &str, keys: &[String]) -> Message {
+        Self::RplMetadataSubOk.reply(nick, keys.to_vec())
+    }
+
+    /// RPL_METADATAUNSUBOK
+    pub fn metadata_unsub_ok(nick: &str, keys: &[String]) -> Message {
+        Self::RplMetadataUnsubOk.reply(nick, keys.to_vec())
+    }
+
+    /// RPL_METADATASUBS
+    pub fn metadata_subs(nick: &str, keys: &[String]) -> Message {
+        Self::RplMetadataSubs.reply(nick, keys.to_vec())
+    }
+
+    /// RPL_LOGGEDIN - `mask` is the client's nick!user@host, or `*` before registering
+    pub fn logged_in(nick: &str, mask: &str, account: &str) -> Message {
+        Self::RplLoggedIn.reply(
+            nick,
+            vec![mask.to_string(), account.to_string(), format!("You are now logged in as {}", account)],
+        )
+    }
+
+    /// RPL_LOGGEDOUT
+    pub fn logged_out(nick: &str, mask: &str) -> Message {
+        Self::RplLoggedOut.reply(nick, vec![mask.to_string(), "You are now logged out".to_string()])
+    }
+
+    /// RPL_SASLSUCCESS
+    pub fn sasl_success(nick: &str) -> Message {
+        Self::RplSaslSuccess.reply(nick, vec!["SASL authentication successful".to_string()])
+    }
+
+    /// ERR_SASLFAIL
+    pub fn sasl_fail(nick: &str) -> Message {
+        Self::ErrSaslFail.reply(nick, vec!["SASL authentication failed".to_string()])
+    }
+
+    /// ERR_SASLTOOLONG
+    pub fn sasl_too_long(nick: &str) -> Message {
+        Self::ErrSaslTooLong.reply(nick, vec!["SASL message too long".to_string()])
+    }
+
+    /// ERR_SASLABORTED
+    pub fn sasl_aborted(nick: &str) -> Message {
+        Self::ErrSaslAborted.reply(nick, vec!["SASL authentication aborted".to_string()])
+    }
+
+    /// ERR_SASLALREADY
+    pub fn sasl_already(nick: &str) -> Message {
+        Self::ErrSaslAlready.reply(nick, vec!["You have already authenticated using SASL".to_string()])
+    }
+
+    /// RPL_SASLMECHS - `mechanisms` is comma-separated
+    pub fn sasl_mechs(nick: &str, mechanisms: &str) -> Message {
+        Self::RplSaslMechs.reply(
+            nick,
+            vec![mechanisms.to_string(), "are available SASL mechanisms".to_string()],
+        )
+    }
+
+    /// ERR_LINKCHANNEL - join redirected by channel forwarding (+f)
+    pub fn link_channel(nick: &str, channel: &str, forward: &str) -> Message {
+        Self::ErrLinkChannel.reply(
+            nick,
+            vec![channel.to_string(), forward.to_string(), "Forwarding to another channel".to_string()],
+        )
+    }
+
     /// ERR_USERSDONTMATCH
     pub fn err_users_dont_match() -> Message {
         Self::ErrUsersDontMatch.reply(
@@ -1327,7 +1896,7 @@ impl NumericReply {
     pub fn err_unknown_command(command: &str) -> Message {
         Self::ErrUnknownCommand.reply(
             "*",
-            vec![format!("{} :Unknown command", command)],
+            vec![command.to_string(), "Unknown command".to_string()],
         )
     }
 
@@ -1347,6 +1916,14 @@ impl NumericReply {
             )
         }
 
+        /// RPL_TRYAGAIN
+        pub fn try_again(command: &str) -> Message {
+            Self::RplTryAgain.reply(
+                "*",
+                vec![command.to_string(), "Please wait a while and try again.".to_string()],
+            )
+        }
+
         /// ERR_CANTKILLSERVER
         pub fn cant_kill_server() -> Message {
             Self::ErrCantKillServer.reply(
@@ -0,0 +1,120 @@
+//! Client registration state
+//!
+//! A client is welcomed once it has sent NICK and USER, given the password
+//! when one is required, finished any CAP negotiation it started, its
+//! connect-time lookups are done, and no module holds its registration
+//! (SASL does while an exchange is running). The order of those steps
+//! doesn't matter.
+
+use std::time::{Duration, Instant};
+
+/// What an unregistered client has told us so far
+#[derive(Debug, Clone)]
+pub struct RegistrationState {
+    /// Nickname from NICK
+    pub nick: Option<String>,
+    /// Username from USER
+    pub username: Option<String>,
+    /// Real name from USER
+    pub realname: Option<String>,
+    /// Whether PASS matched the configured client password
+    pub password_accepted: bool,
+    /// Whether CAP LS or CAP REQ was sent without a CAP END yet
+    pub cap_negotiating: bool,
+    /// Hostname from the reverse DNS lookup at connect time
+    pub hostname: Option<String>,
+    /// Username from the ident lookup at connect time
+    pub ident: Option<String>,
+    /// Whether the reverse DNS, ident and DNSBL lookups are still running
+    pub lookups_pending: bool,
+    /// When the connection was accepted
+    pub started: Instant,
+}
+
+impl RegistrationState {
+    /// State for a connection accepted just now
+    pub fn new() -> Self {
+        Self {
+            nick: None,
+            username: None,
+            realname: None,
+            password_accepted: false,
+            cap_negotiating: false,
+            hostname: None,
+            ident: None,
+            lookups_pending: false,
+            started: Instant::now(),
+        }
+    }
+
+    /// Track a CAP subcommand the client sent before registering
+    ///
+    /// LS and REQ suspend registration until END, so the client can finish
+    /// negotiating (and authenticating) before it is welcomed.
+    pub fn cap(&mut self, subcommand: &str) {
+        match subcommand.to_uppercase().as_str() {
+            "LS" | "REQ" => self.cap_negotiating = true,
+            "END" => self.cap_negotiating = false,
+            _ => {}
+        }
+    }
+
+    /// Whether NICK and USER were both sent, CAP negotiation is over and
+    /// the lookups are done
+    pub fn is_ready(&self) -> bool {
+        self.nick.is_some() && self.username.is_some() && !self.cap_negotiating && !self.lookups_pending
+    }
+
+    /// Whether the client has been registering for longer than `timeout`
+    pub fn is_expired(&self, timeout: Duration) -> bool {
+        self.started.elapsed() >= timeout
+    }
+
+    /// Username to register with
+    ///
+    /// An ident reply wins over USER. When ident is enabled but nobody
+    /// answered, the USER value is marked with a leading `~`.
+    pub fn effective_username(&self, ident_enabled: bool) -> Option<String> {
+        if let Some(ident) = &self.ident {
+            return Some(ident.clone());
+        }
+        let username = self.username.as_ref()?;
+        Some(if ident_enabled { format!("~{}", username) } else { username.clone() })
+    }
+}
+
+impl Default for RegistrationState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_registration_order_and_cap_gating() {
+        let mut state = RegistrationState::new();
+        state.username = Some("alice".to_string());
+        assert!(!state.is_ready());
+
+        state.cap("LS");
+        state.nick = Some("alice".to_string());
+        assert!(!state.is_ready());
+        state.cap("REQ");
+        state.cap("end");
+        assert!(state.is_ready());
+        state.lookups_pending = true;
+        assert!(!state.is_ready());
+        state.lookups_pending = false;
+
+        assert_eq!(state.effective_username(false).as_deref(), Some("alice"));
+        assert_eq!(state.effective_username(true).as_deref(), Some("~alice"));
+        state.ident = Some("al".to_string());
+        assert_eq!(state.effective_username(true).as_deref(), Some("al"));
+
+        assert!(!state.is_expired(Duration::from_secs(60)));
+        assert!(state.is_expired(Duration::ZERO));
+    }
+}
@@ -1,16 +1,245 @@
 //! Rehash system for runtime configuration reloading
 
 use crate::{Error, Result, Config, MotdManager};
+use serde::Serialize;
+use serde_json::Value;
 use std::path::Path;
 use std::sync::Arc;
 use tokio::sync::RwLock;
 use tracing::{info, warn};
 
+/// Part of the server state reloaded by REHASH
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RehashSection {
+    /// The configuration file, along with the MOTD, rules and TLS settings it names
+    Config,
+    /// The MOTD file
+    Motd,
+    /// The RULES file
+    Rules,
+    /// The TLS certificate and key
+    Tls,
+    /// Loaded modules
+    Modules,
+}
+
+impl RehashSection {
+    /// Parse a REHASH parameter; SSL is accepted as an alias for TLS
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.to_uppercase().as_str() {
+            "" | "CONFIG" => Some(Self::Config),
+            "MOTD" => Some(Self::Motd),
+            "RULES" => Some(Self::Rules),
+            "TLS" | "SSL" => Some(Self::Tls),
+            "MODULES" => Some(Self::Modules),
+            _ => None,
+        }
+    }
+
+    /// Name of the section as shown to operators
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Config => "CONFIG",
+            Self::Motd => "MOTD",
+            Self::Rules => "RULES",
+            Self::Tls => "TLS",
+            Self::Modules => "MODULES",
+        }
+    }
+}
+
+/// Entries of a named config list that a rehash adds, removes or changes
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ListDiff {
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    pub changed: Vec<String>,
+}
+
+impl ListDiff {
+    /// Compare two lists whose entries are identified by `key`
+    pub fn between<T: Serialize>(old: &[T], new: &[T], key: impl Fn(&T) -> String) -> Self {
+        let mut diff = Self::default();
+        for entry in new {
+            let name = key(entry);
+            match old.iter().find(|old_entry| key(old_entry) == name) {
+                None => diff.added.push(name),
+                Some(old_entry) if to_value(old_entry) != to_value(entry) => diff.changed.push(name),
+                Some(_) => {}
+            }
+        }
+        for entry in old {
+            let name = key(entry);
+            if !new.iter().any(|new_entry| key(new_entry) == name) {
+                diff.removed.push(name);
+            }
+        }
+        diff
+    }
+
+    /// Whether nothing was added, removed or changed
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+
+    /// One report line, e.g. `Operators: added alice; removed bob`
+    fn describe(&self, what: &str) -> Option<String> {
+        if self.is_empty() {
+            return None;
+        }
+        let parts: Vec<String> = [("added", &self.added), ("removed", &self.removed), ("changed", &self.changed)]
+            .into_iter()
+            .filter(|(_, names)| !names.is_empty())
+            .map(|(verb, names)| format!("{} {}", verb, names.join(", ")))
+            .collect();
+        Some(format!("{}: {}", what, parts.join("; ")))
+    }
+}
+
+/// What a configuration rehash changes
+///
+/// Lists are compared entry by entry; everything else is reported by the
+/// settings that differ, such as `server.max_clients`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ConfigDiff {
+    /// Listeners, by `address:port`
+    pub listeners: ListDiff,
+    /// Operator blocks, by nickname
+    pub operators: ListDiff,
+    /// Connection classes, by name
+    pub classes: ListDiff,
+    /// Server links, by server name
+    pub links: ListDiff,
+    /// Whether `security.tls` changed
+    pub tls: bool,
+    /// Whether the MOTD settings changed
+    pub motd: bool,
+    /// Whether the rules file changed
+    pub rules: bool,
+    /// Whether the numeric reply templates changed
+    pub replies: bool,
+    /// Other settings that changed
+    pub settings: Vec<String>,
+}
+
+impl ConfigDiff {
+    /// Settings covered by the fields above rather than `settings`
+    const COMPARED_SEPARATELY: &'static [&'static str] = &[
+        "connection.ports",
+        "network.operators",
+        "classes",
+        "network.links",
+        "security.tls",
+        "server.motd_file",
+        "server.motd",
+        "server.rules_file",
+        "replies",
+    ];
+
+    /// Compare the running configuration with a newly loaded one
+    pub fn between(old: &Config, new: &Config) -> Self {
+        let listener = |config: &Config| {
+            let bind_address = config.connection.bind_address.clone();
+            move |port: &crate::config::PortConfig| {
+                format!("{}:{}", port.bind_address.as_deref().unwrap_or(&bind_address), port.port)
+            }
+        };
+        let mut settings = Vec::new();
+        changed_settings(&to_value(old), &to_value(new), "", &mut settings);
+        settings.retain(|setting| !Self::COMPARED_SEPARATELY.iter().any(|covered| {
+            setting == covered || setting.starts_with(&format!("{}.", covered))
+        }));
+
+        Self {
+            listeners: ListDiff::between(&old.connection.ports, &new.connection.ports, listener(new)),
+            operators: ListDiff::between(&old.network.operators, &new.network.operators, |oper| oper.nickname.clone()),
+            classes: ListDiff::between(&old.classes, &new.classes, |class| class.name.clone()),
+            links: ListDiff::between(&old.network.links, &new.network.links, |link| link.name.clone()),
+            tls: to_value(&old.security.tls) != to_value(&new.security.tls),
+            motd: old.server.motd_file != new.server.motd_file
+                || to_value(&old.server.motd) != to_value(&new.server.motd),
+            rules: old.server.rules_file != new.server.rules_file,
+            replies: to_value(&old.replies) != to_value(&new.replies),
+            settings,
+        }
+    }
+
+    /// Whether the configurations are the same
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Lines describing the changes for the rehashing operator
+    pub fn report(&self) -> Vec<String> {
+        if self.is_empty() {
+            return vec!["No configuration changes".to_string()];
+        }
+        let mut report: Vec<String> = [
+            self.listeners.describe("Listeners"),
+            self.operators.describe("Operators"),
+            self.classes.describe("Classes"),
+            self.links.describe("Links"),
+        ].into_iter().flatten().collect();
+        if !self.classes.changed.is_empty() {
+            report.push("Changed classes apply to new connections".to_string());
+        }
+        if !self.links.removed.is_empty() {
+            report.push("Removed links stay up until they split or are SQUIT".to_string());
+        }
+        if self.replies {
+            report.push("Numeric reply templates changed".to_string());
+        }
+        if !self.settings.is_empty() {
+            report.push(format!("Settings changed: {}", self.settings.join(", ")));
+        }
+        report
+    }
+}
+
+fn to_value<T: Serialize>(value: &T) -> Value {
+    serde_json::to_value(value).unwrap_or(Value::Null)
+}
+
+/// Collect the dotted paths, two levels deep, at which `old` and `new` differ
+fn changed_settings(old: &Value, new: &Value, path: &str, changed: &mut Vec<String>) {
+    if old == new {
+        return;
+    }
+    match (old, new) {
+        (Value::Object(old), Value::Object(new)) if !path.contains('.') => {
+            let mut keys: Vec<&String> = old.keys().chain(new.keys()).collect();
+            keys.sort();
+            keys.dedup();
+            for key in keys {
+                let key_path = if path.is_empty() { key.clone() } else { format!("{}.{}", path, key) };
+                changed_settings(old.get(key).unwrap_or(&Value::Null), new.get(key).unwrap_or(&Value::Null), &key_path, changed);
+            }
+        }
+        _ => changed.push(path.to_string()),
+    }
+}
+
+/// Modification times and sizes of some files, to notice when they change
+///
+/// A missing or unreadable file has no stamp, so creating or deleting one
+/// counts as a change too.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileStamps(Vec<Option<(std::time::SystemTime, u64)>>);
+
+impl FileStamps {
+    /// Stamp `paths` as they are now
+    pub fn of<'a>(paths: impl IntoIterator<Item = &'a str>) -> Self {
+        Self(paths.into_iter().map(|path| {
+            let metadata = std::fs::metadata(path).ok()?;
+            Some((metadata.modified().ok()?, metadata.len()))
+        }).collect())
+    }
+}
+
 /// Rehash service for runtime configuration reloading
 pub struct RehashService {
     /// Current configuration
     config: Arc<RwLock<Config>>,
-    #[allow(dead_code)]
     /// MOTD manager
     motd_manager: Arc<MotdManager>,
     /// Configuration file path
@@ -31,21 +260,30 @@ impl RehashService {
         }
     }
 
+    /// Path of the configuration file
+    pub fn config_path(&self) -> &str {
+        &self.config_path
+    }
+
+    /// Load and validate the configuration file without applying it
+    pub fn load_config(&self) -> Result<Config> {
+        let config = Config::from_file(&self.config_path)?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Replace the current configuration with one from `load_config`
+    pub async fn commit_config(&self, new_config: Config) {
+        let mut config = self.config.write().await;
+        *config = new_config;
+    }
+
     /// Reload main configuration file
     pub async fn reload_main_config(&self) -> Result<()> {
         info!("Reloading main configuration from: {}", self.config_path);
         
-        // Load new configuration
-        let new_config = Config::from_file(&self.config_path)?;
-        
-        // Validate the new configuration
-        new_config.validate()?;
-        
-        // Update the configuration
-        {
-            let mut config = self.config.write().await;
-            *config = new_config;
-        }
+        let new_config = self.load_config()?;
+        self.commit_config(new_config).await;
         
         info!("Main configuration reloaded successfully");
         Ok(())
@@ -104,31 +342,19 @@ impl RehashService {
     pub async fn reload_motd(&self) -> Result<()> {
         info!("Reloading MOTD file");
         
-        // Load current configuration
-        let config = self.config.read().await;
+        let motd_file = self.config.read().await.server.motd_file.clone();
+        match motd_file {
+            Some(motd_file) => {
+                let lines = MotdManager::read_motd_file(&motd_file)?.unwrap_or_default();
+                self.motd_manager.set_lines(lines).await;
+                info!("MOTD file reloaded successfully from: {}", motd_file);
+            }
+            None => {
+                warn!("No MOTD file configured, clearing MOTD");
+                self.motd_manager.clear().await;
+            }
+        }
         
-        // Check if MOTD file is configured
-        if let Some(motd_file) = &config.server.motd_file {
-            // Reload MOTD from file
-            info!("MOTD file configured: {}", motd_file);
-            
-            // Create a new MotdManager and load the MOTD
-            let mut new_motd_manager = MotdManager::new();
-            new_motd_manager.load_motd(motd_file).await?;
-            
-            // Replace the existing MOTD manager
-            // Note: This requires access to the server's MOTD manager
-            // For now, we'll log success and the actual replacement would need to be done
-            // through a server method that we'll implement
-            info!("MOTD file reloaded successfully from: {}", motd_file);
-        } else {
-            warn!("No MOTD file configured, clearing MOTD");
-            // Create empty MOTD manager
-            let _new_motd_manager = MotdManager::new();
-            info!("MOTD cleared successfully");
-        }
-        
-        info!("MOTD reload completed successfully");
         Ok(())
     }
 
@@ -190,6 +416,29 @@ mod tests {
         assert!(info.contains("rustircd"));
     }
 
+    #[test]
+    fn test_config_diff() {
+        let old = Config::default();
+        assert!(ConfigDiff::between(&old, &old).is_empty());
+        assert_eq!(ConfigDiff::between(&old, &old).report(), ["No configuration changes"]);
+
+        let mut new = old.clone();
+        new.server.max_clients += 1;
+        new.network.operators.push(crate::config::OperatorConfig::new(
+            "newoper".to_string(), "secret", "*@*".to_string(), vec![],
+        ));
+        let removed_port = new.connection.ports.remove(0);
+        new.classes[0].max_sendq = Some(1);
+
+        let diff = ConfigDiff::between(&old, &new);
+        assert_eq!(diff.operators.added, ["newoper"]);
+        assert_eq!(diff.listeners.removed, [format!("{}:{}", old.connection.bind_address, removed_port.port)]);
+        assert_eq!(diff.classes.changed, [old.classes[0].name.clone()]);
+        assert_eq!(diff.settings, ["server.max_clients"]);
+        assert!(!diff.tls && !diff.motd && !diff.rules && diff.links.is_empty());
+        assert!(diff.report().contains(&"Operators: added newoper".to_string()));
+    }
+
     #[tokio::test]
     async fn test_rehash_section_validation() {
         let config = Arc::new(RwLock::new(Config::default()));
@@ -204,4 +453,23 @@ mod tests {
         // Test invalid section
         assert!(service.reload_section("INVALID").await.is_err());
     }
+
+    #[test]
+    fn test_file_stamps() {
+        let dir = tempfile::tempdir().unwrap();
+        let cert = dir.path().join("cert.pem");
+        let key = dir.path().join("key.pem");
+        std::fs::write(&cert, "certificate").unwrap();
+        let paths = [cert.to_str().unwrap(), key.to_str().unwrap()];
+
+        let before = FileStamps::of(paths);
+        assert_eq!(FileStamps::of(paths), before);
+
+        // Creating a file and rewriting one both count
+        std::fs::write(&key, "key").unwrap();
+        let created = FileStamps::of(paths);
+        assert_ne!(created, before);
+        std::fs::write(&cert, "renewed certificate").unwrap();
+        assert_ne!(FileStamps::of(paths), created);
+    }
 }
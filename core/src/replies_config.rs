@@ -3,11 +3,19 @@
 //! This module allows server administrators to customize IRC numeric replies
 //! by loading them from a TOML configuration file, while maintaining
 //! sensible defaults for all RFC 1459 defined replies.
+//!
+//! A template gives the parameters sent after the client's nick, written as
+//! on the wire (`{channel} :Cannot send to channel`). Every 0xx-5xx numeric
+//! has a built-in template; the reply the server builds is matched against
+//! it to find the values its placeholders stand for, and the configured
+//! template is filled in from those.
 
+use crate::{Message, MessageType};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::fs;
 use std::path::Path;
+use std::sync::Arc;
 use tracing::info;
 
 /// Configuration for a single numeric reply
@@ -15,8 +23,12 @@ use tracing::info;
 pub struct ReplyConfig {
     /// The numeric code (e.g., 001, 401, etc.)
     pub code: u16,
-    /// The reply text template with placeholders
-    /// Placeholders: {nick}, {user}, {host}, {server}, {channel}, {target}, {reason}, {count}, {info}
+    /// The reply parameters after the client's nick, with placeholders
+    ///
+    /// Placeholders are those of the numeric's built-in template, plus
+    /// `{client}` and the server placeholders (`{server_name}`,
+    /// `{server_version}`, `{server_description}`, `{server_created}`,
+    /// `{admin_email}`, `{admin_location1}`, `{admin_location2}`).
     pub text: String,
     /// Optional description of what this reply is for
     pub description: Option<String>,
@@ -26,9 +38,34 @@ pub struct ReplyConfig {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RepliesConfig {
     /// All numeric replies by their code
+    #[serde(with = "reply_codes")]
     pub replies: HashMap<u16, ReplyConfig>,
 }
 
+/// Replies keyed by zero-padded code (`[replies.001]`), as TOML keys are strings
+mod reply_codes {
+    use super::ReplyConfig;
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+    use std::collections::{BTreeMap, HashMap};
+    
+    pub fn serialize<S: Serializer>(replies: &HashMap<u16, ReplyConfig>, serializer: S) -> Result<S::Ok, S::Error> {
+        let keyed: BTreeMap<String, &ReplyConfig> = replies.iter()
+            .map(|(code, reply)| (format!("{:03}", code), reply))
+            .collect();
+        serializer.collect_map(keyed)
+    }
+    
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<HashMap<u16, ReplyConfig>, D::Error> {
+        HashMap::<String, ReplyConfig>::deserialize(deserializer)?
+            .into_iter()
+            .map(|(key, reply)| match key.parse() {
+                Ok(code) => Ok((code, reply)),
+                Err(_) => Err(D::Error::custom(format!("reply key {} is not a numeric code", key))),
+            })
+            .collect()
+    }
+}
+
 /// Server information used in reply templates
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ServerInfo {
@@ -62,6 +99,20 @@ impl Default for ServerInfo {
     }
 }
 
+impl From<&crate::config::ServerConfig> for ServerInfo {
+    fn from(server: &crate::config::ServerConfig) -> Self {
+        Self {
+            name: server.name.clone(),
+            version: server.version.clone(),
+            description: server.description.clone(),
+            created: server.created.clone(),
+            admin_email: server.admin_email.clone(),
+            admin_location1: server.admin_location1.clone(),
+            admin_location2: server.admin_location2.clone(),
+        }
+    }
+}
+
 impl Default for RepliesConfig {
     fn default() -> Self {
         Self {
@@ -76,15 +127,10 @@ impl RepliesConfig {
         let content = fs::read_to_string(path)
             .map_err(|e| format!("Failed to read replies config file: {}", e))?;
         
-        let mut config: RepliesConfig = toml::from_str(&content)
+        let config: RepliesConfig = toml::from_str(&content)
             .map_err(|e| format!("Failed to parse replies config file: {}", e))?;
         
-        // Merge with defaults for any missing replies
-        let defaults = Self::default_replies();
-        for (code, default_reply) in defaults {
-            config.replies.entry(code).or_insert(default_reply);
-        }
-        
+        // Replies not listed keep the server's built-in text
         info!("Loaded replies configuration with {} custom replies", config.replies.len());
         Ok(config)
     }
@@ -105,902 +151,576 @@ impl RepliesConfig {
         self.replies.get(&code)
     }
     
-    /// Format a reply with the given parameters and server information
-    pub fn format_reply(&self, code: u16, params: &HashMap<String, String>, server_info: &ServerInfo) -> Option<String> {
-        let reply_config = self.get_reply(code)?;
-        Some(self.format_template(&reply_config.text, params, server_info))
+    /// Template problems, by numeric, that would stop the replies applying
+    ///
+    /// Checks that each entry is filed under its own code, customizes a
+    /// 0xx-5xx reply, has as many parameters as the reply and only uses
+    /// placeholders that reply provides.
+    pub fn validation_errors(&self) -> Vec<(u16, String)> {
+        let mut codes: Vec<&u16> = self.replies.keys().collect();
+        codes.sort();
+        let mut errors = Vec::new();
+        for code in codes {
+            let reply = &self.replies[code];
+            if reply.code != *code {
+                errors.push((*code, format!("entry {:03} has code {:03}", code, reply.code)));
+                continue;
+            }
+            let Some(default) = default_template(*code) else {
+                errors.push((*code, format!("{:03} is not a customizable 0xx-5xx reply", code)));
+                continue;
+            };
+            let expected = split_params(default, usize::MAX);
+            let params = split_params(&reply.text, expected.len());
+            if params.len() != expected.len() {
+                errors.push((*code, format!("expects {} parameter(s), template has {}", expected.len(), params.len())));
+                continue;
+            }
+            let mut available = placeholders(default);
+            available.extend(SERVER_PLACEHOLDERS.iter().map(|name| name.to_string()));
+            available.push("client".to_string());
+            for param in params {
+                match segments(param) {
+                    Ok(segments) => {
+                        for segment in segments {
+                            if let Segment::Placeholder(name) = segment {
+                                if !available.iter().any(|known| known == name) {
+                                    errors.push((*code, format!("unknown placeholder {{{}}}", name)));
+                                }
+                            }
+                        }
+                    }
+                    Err(e) => errors.push((*code, e)),
+                }
+            }
+        }
+        errors
     }
     
-    /// Format a template string with parameters and server information
-    fn format_template(&self, template: &str, params: &HashMap<String, String>, server_info: &ServerInfo) -> String {
-        let mut result = template.to_string();
-        
-        // Replace server-specific placeholders
-        result = result.replace("{server_name}", &server_info.name);
-        result = result.replace("{server_version}", &server_info.version);
-        result = result.replace("{server_description}", &server_info.description);
-        result = result.replace("{server_created}", &server_info.created);
-        result = result.replace("{admin_email}", &server_info.admin_email);
-        result = result.replace("{admin_location1}", &server_info.admin_location1);
-        result = result.replace("{admin_location2}", &server_info.admin_location2);
-        
-        // Replace parameter placeholders
-        for (key, value) in params {
-            let placeholder = format!("{{{}}}", key);
-            result = result.replace(&placeholder, value);
+    /// Parameters for a numeric rebuilt from its configured template
+    ///
+    /// `params` are the ones after the client's nick. The built-in template
+    /// for the numeric picks the named values out of them; leading
+    /// parameters it doesn't cover (ISUPPORT tokens) are kept as they are.
+    /// Returns `None` when there's no template for the numeric or the reply
+    /// doesn't have the built-in shape.
+    pub fn render(&self, code: u16, client: &str, params: &[String], server_info: &ServerInfo) -> Option<Vec<String>> {
+        let template = self.get_reply(code)?;
+        let default = split_params(default_template(code)?, usize::MAX);
+        let lead = params.len().checked_sub(default.len())?;
+        
+        let mut values = HashMap::new();
+        for (pattern, value) in default.iter().zip(&params[lead..]) {
+            if !capture(&segments(pattern).ok()?, value, &mut values) {
+                return None;
+            }
         }
-        
-        result
+        let value = |name: &str| -> Option<String> {
+            if let Some(value) = values.get(name) {
+                return Some(value.clone());
+            }
+            Some(match name {
+                "client" => client.to_string(),
+                "server_name" => server_info.name.clone(),
+                "server_version" => server_info.version.clone(),
+                "server_description" => server_info.description.clone(),
+                "server_created" => server_info.created.clone(),
+                "admin_email" => server_info.admin_email.clone(),
+                "admin_location1" => server_info.admin_location1.clone(),
+                "admin_location2" => server_info.admin_location2.clone(),
+                _ => return None,
+            })
+        };
+        
+        let custom = split_params(&template.text, default.len());
+        if custom.len() != default.len() {
+            return None;
+        }
+        let mut rendered = params[..lead].to_vec();
+        for (index, param) in custom.iter().enumerate() {
+            let mut text = String::new();
+            for segment in segments(param).ok()? {
+                match segment {
+                    Segment::Literal(literal) => text.push_str(literal),
+                    Segment::Placeholder(name) => text.push_str(&value(name)?),
+                }
+            }
+            // Only the last parameter may be empty, hold spaces or start with ':'
+            if index + 1 < custom.len() && (text.is_empty() || text.contains(' ') || text.starts_with(':')) {
+                return None;
+            }
+            rendered.push(text);
+        }
+        Some(rendered)
     }
     
     /// Create default replies configuration based on RFC 1459
     fn default_replies() -> HashMap<u16, ReplyConfig> {
+        DEFAULT_TEMPLATES.iter()
+            .map(|(code, name, text)| (*code, ReplyConfig {
+                code: *code,
+                text: text.to_string(),
+                description: Some(name.to_string()),
+            }))
+            .collect()
+    }
+}
+
+/// Reply templates in effect on a server
+///
+/// Shared with every connection's writer, so numerics are rewritten as
+/// they go out whichever handler or module built them, and a rehash
+/// applies to clients already connected.
+#[derive(Debug, Default)]
+pub struct NumericReplies {
+    active: parking_lot::RwLock<Option<Arc<(RepliesConfig, ServerInfo)>>>,
+}
+
+impl NumericReplies {
+    /// Use `config`'s templates from now on, or the built-in replies for `None`
+    pub fn set(&self, config: Option<RepliesConfig>, server_info: ServerInfo) {
+        *self.active.write() = config
+            .filter(|config| !config.replies.is_empty())
+            .map(|config| Arc::new((config, server_info)));
+    }
+    
+    /// A numeric reply with its configured template applied
+    ///
+    /// Anything that isn't a 0xx-5xx numeric, or that the template doesn't
+    /// fit, is returned unchanged.
+    pub fn localize(&self, message: Message) -> Message {
+        let Some(active) = self.active.read().clone() else {
+            return message;
+        };
+        let MessageType::Custom(ref command) = message.command else {
+            return message;
+        };
+        let code = match command.parse::<u16>() {
+            Ok(code) if command.len() == 3 && code < 600 => code,
+            _ => return message,
+        };
+        let Some((client, params)) = message.params.split_first() else {
+            return message;
+        };
+        let (config, server_info) = &*active;
+        match config.render(code, client, params, server_info) {
+            Some(rendered) => {
+                let mut localized = message.clone();
+                localized.params.truncate(1);
+                localized.params.extend(rendered);
+                localized
+            }
+            None => message,
+        }
+    }
+}
+
+/// Placeholders filled from the `[server]` section
+const SERVER_PLACEHOLDERS: &[&str] = &[
+    "server_name", "server_version", "server_description", "server_created",
+    "admin_email", "admin_location1", "admin_location2",
+];
+
+/// Part of a template parameter
+#[derive(Debug, PartialEq)]
+enum Segment<'a> {
+    Literal(&'a str),
+    Placeholder(&'a str),
+}
+
+/// The parameters a template stands for, written as on the wire
+///
+/// Splits on spaces into at most `max` parameters; a parameter starting
+/// with ':', and the last one allowed, take the rest of the line.
+fn split_params(template: &str, max: usize) -> Vec<&str> {
+    let mut params = Vec::new();
+    let mut rest = template;
+    while !rest.is_empty() && params.len() < max {
+        if let Some(trailing) = rest.strip_prefix(':') {
+            params.push(trailing);
+            break;
+        }
+        if params.len() + 1 == max {
+            params.push(rest);
+            break;
+        }
+        match rest.split_once(' ') {
+            Some((param, remainder)) => {
+                params.push(param);
+                rest = remainder;
+            }
+            None => {
+                params.push(rest);
+                break;
+            }
+        }
+    }
+    params
+}
+
+/// A template parameter split into literal text and `{name}` placeholders
+fn segments(param: &str) -> std::result::Result<Vec<Segment<'_>>, String> {
+    let mut segments = Vec::new();
+    let mut rest = param;
+    while let Some(open) = rest.find(['{', '}']) {
+        if rest[open..].starts_with('}') {
+            return Err(format!("unmatched '}}' in \"{}\"", param));
+        }
+        let Some(close) = rest[open..].find('}').map(|close| open + close) else {
+            return Err(format!("unclosed placeholder in \"{}\"", param));
+        };
+        let name = &rest[open + 1..close];
+        if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+            return Err(format!("invalid placeholder {{{}}}", name));
+        }
+        if open > 0 {
+            segments.push(Segment::Literal(&rest[..open]));
+        }
+        segments.push(Segment::Placeholder(name));
+        rest = &rest[close + 1..];
+    }
+    if !rest.is_empty() {
+        segments.push(Segment::Literal(rest));
+    }
+    Ok(segments)
+}
+
+/// Names of the placeholders in a template
+fn placeholders(template: &str) -> Vec<String> {
+    split_params(template, usize::MAX).into_iter()
+        .filter_map(|param| segments(param).ok())
+        .flatten()
+        .filter_map(|segment| match segment {
+            Segment::Placeholder(name) => Some(name.to_string()),
+            Segment::Literal(_) => None,
+        })
+        .collect()
+}
+
+/// Match `value` against a template parameter, recording what each
+/// placeholder stands for; earlier placeholders take as little as they can
+fn capture(segments: &[Segment], value: &str, values: &mut HashMap<String, String>) -> bool {
+    match segments.split_first() {
+        None => value.is_empty(),
+        Some((Segment::Literal(literal), rest)) => {
+            value.strip_prefix(literal).is_some_and(|value| capture(rest, value, values))
+        }
+        Some((Segment::Placeholder(name), [])) => {
+            values.insert(name.to_string(), value.to_string());
+            true
+        }
+        Some((Segment::Placeholder(name), rest)) => {
+            for (end, _) in value.char_indices().skip(1).chain(std::iter::once((value.len(), ' '))) {
+                if capture(rest, &value[end..], values) {
+                    values.insert(name.to_string(), value[..end].to_string());
+                    return true;
+                }
+            }
+            false
+        }
+    }
+}
+
+/// Built-in template for a 0xx-5xx numeric
+pub fn default_template(code: u16) -> Option<&'static str> {
+    DEFAULT_TEMPLATES.iter()
+        .find(|(default_code, _, _)| *default_code == code)
+        .map(|(_, _, text)| *text)
+}
+
+/// What the server sends for each 0xx-5xx numeric after the client's nick
+///
+/// Placeholders here are the names a custom template can use for that
+/// reply, besides `{client}` and the server placeholders.
+const DEFAULT_TEMPLATES: &[(u16, &str, &str)] = &[
+    // Connection registration
+    (1, "RPL_WELCOME", ":Welcome to the Internet Relay Network {nick}!{user}@{host}"),
+    (2, "RPL_YOURHOST", ":Your host is {server_name}, running version {server_version}"),
+    (3, "RPL_CREATED", ":This server was created {server_created}"),
+    (4, "RPL_MYINFO", "{server_name} {server_version} {usermodes} {channelmodes}"),
+    (5, "RPL_ISUPPORT", ":are supported by this server"),
+    (8, "RPL_SNOMASK", "{snomask} :Server notice mask"),
+    (15, "RPL_MAP", ":{line}"),
+    (17, "RPL_MAPEND", ":End of /MAP"),
+    
+    // TRACE and STATS
+    (200, "RPL_TRACELINK", "Link {version} {destination} {next_server}"),
+    (201, "RPL_TRACECONNECTING", "Try. {class} {server}"),
+    (202, "RPL_TRACEHANDSHAKE", "H.S. {class} {server}"),
+    (203, "RPL_TRACEUNKNOWN", "???? {class} {ip}"),
+    (204, "RPL_TRACEOPERATOR", "Oper {class} {nick} {idle} {lag}"),
+    (205, "RPL_TRACEUSER", "User {class} {nick} {idle} {lag}"),
+    (206, "RPL_TRACESERVER", "{class} {server} {version} {debug_level} {link}"),
+    (207, "RPL_TRACESERVICE", "Service {class} {name} {type} {active_type}"),
+    (208, "RPL_TRACENEWTYPE", "{newtype} 0 {name}"),
+    (209, "RPL_TRACECLASS", "Class {class} {count}"),
+    (211, "RPL_STATSLINKINFO", ":{linkinfo}"),
+    (212, "RPL_STATSCOMMANDS", "{command} {count} {bytes} {remote_count}"),
+    (213, "RPL_STATSCLINE", "C {host} * {name} {port} {class}"),
+    (214, "RPL_STATSNLINE", "N {host} * {name} {port} {class}"),
+    (215, "RPL_STATSILINE", "I {host} * {name} {port} {class}"),
+    (216, "RPL_STATSKLINE", "K {host} * {username} {port} {class}"),
+    (218, "RPL_STATSYLINE", "{class} {ping_freq} {connect_freq} {max_sendq}"),
+    (219, "RPL_ENDOFSTATS", "{letter} :End of STATS report"),
+    (221, "RPL_UMODEIS", "{nick} {modes}"),
+    (232, "RPL_RULES", ":- {line}"),
+    (241, "RPL_STATSLLINE", "L {hostmask} * {server} {port} {class}"),
+    (242, "RPL_STATSUPTIME", "{server} {uptime}"),
+    (243, "RPL_STATSOLINE", "{hostmask} {name} {port} {class}"),
+    (244, "RPL_STATSHLINE", "H {hostmask} * {server}"),
+    (245, "RPL_STATSM", "{letter} :{data}"),
+    
+    // LUSERS and ADMIN
+    (251, "RPL_LUSERCLIENT", ":There are {users} users and {invisible} invisible on {servers} servers"),
+    (252, "RPL_LUSEROP", ":{ops} operator(s) online"),
+    (253, "RPL_LUSERUNKNOWN", ":{unknown} unknown connection(s)"),
+    (254, "RPL_LUSERCHANNELS", ":{channels} channels formed"),
+    (255, "RPL_LUSERME", ":I have {clients} clients and {servers} servers"),
+    (256, "RPL_ADMINME", ":Administrative info for {server}"),
+    (257, "RPL_ADMINLOC1", ":{location}"),
+    (258, "RPL_ADMINLOC2", ":{location}"),
+    (259, "RPL_ADMINEMAIL", ":{email}"),
+    (261, "RPL_TRACELOG", "File {logfile} {debug_level}"),
+    (262, "RPL_TRACEEND", "{server} {version} :End of TRACE"),
+    (263, "RPL_TRYAGAIN", "{command} :Please wait a while and try again."),
+    (265, "RPL_LOCALUSERS", ":Current local users: {current}, max: {max}"),
+    (266, "RPL_GLOBALUSERS", ":Current global users: {current}, max: {max}"),
+    (267, "RPL_LUSERSECURE", ":Current local users over TLS: {secure}, plaintext: {plaintext}"),
+    (271, "RPL_SILELIST", "{mask}"),
+    (272, "RPL_ENDOFSILELIST", ":End of Silence List"),
+    
+    // User queries
+    (276, "RPL_WHOISCERTFP", "{nick} :has client certificate fingerprint {certfp}"),
+    (301, "RPL_AWAY", "{nick} :{message}"),
+    (302, "RPL_USERHOST", ":{replies}"),
+    (303, "RPL_ISON", ":{nicks}"),
+    (305, "RPL_UNAWAY", ":You are no longer marked as being away"),
+    (306, "RPL_NOWAWAY", ":You have been marked as being away"),
+    (308, "RPL_RULESSTART", ":- {server} Server Rules -"),
+    (309, "RPL_ENDOFRULES", ":End of RULES command."),
+    (311, "RPL_WHOISUSER", "{nick} {user} {host} * :{realname}"),
+    (312, "RPL_WHOISSERVER", "{nick} {server} :{server_info}"),
+    (313, "RPL_WHOISOPERATOR", "{nick} :is an IRC operator"),
+    (314, "RPL_WHOWASUSER", "{nick} {user} {host} * :{realname}"),
+    (315, "RPL_ENDOFWHO", "{mask} :End of WHO list"),
+    (317, "RPL_WHOISIDLE", "{nick} {idle} {signon} :seconds idle, signon time"),
+    (318, "RPL_ENDOFWHOIS", "{nick} :End of WHOIS list"),
+    (319, "RPL_WHOISCHANNELS", "{nick} :{channels}"),
+    (320, "RPL_WHOISSPECIAL", "{nick} :{info}"),
+    (335, "RPL_WHOISBOT", "{nick} :is a bot on {network}"),
+    (338, "RPL_WHOISACTUALLY", "{nick} {userhost} {ip} :actually using host"),
+    (352, "RPL_WHOREPLY", "{channel} {user} {host} {server} {nick} {flags} {hopcount} :{realname}"),
+    (354, "RPL_WHOSPCRPL", ":{field}"),
+    (369, "RPL_ENDOFWHOWAS", "{nick} :End of WHOWAS list"),
+    
+    // Channels
+    (321, "RPL_LISTSTART", "Channel :Users Name"),
+    (322, "RPL_LIST", "{channel} {visible} :{topic}"),
+    (323, "RPL_LISTEND", ":End of /LIST"),
+    (324, "RPL_CHANNELMODEIS", "{channel} {modes} {mode_params}"),
+    (329, "RPL_CREATIONTIME", "{channel} {created}"),
+    (331, "RPL_NOTOPIC", "{channel} :No topic is set"),
+    (332, "RPL_TOPIC", "{channel} :{topic}"),
+    (333, "RPL_TOPICWHOTIME", "{channel} {setter} {set_at}"),
+    (341, "RPL_INVITING", "{nick} {channel}"),
+    (342, "RPL_SUMMONING", "{user} :Summoning user to IRC"),
+    (346, "RPL_INVITELIST", "{channel} {mask}"),
+    (347, "RPL_ENDOFINVITELIST", "{channel} :End of channel invite list"),
+    (348, "RPL_EXCEPTLIST", "{channel} {mask}"),
+    (349, "RPL_ENDOFEXCEPTLIST", "{channel} :End of channel exception list"),
+    (353, "RPL_NAMREPLY", "{symbol} {channel} :{names}"),
+    (366, "RPL_ENDOFNAMES", "{channel} :End of /NAMES list"),
+    (367, "RPL_BANLIST", "{channel} {mask}"),
+    (368, "RPL_ENDOFBANLIST", "{channel} :End of channel ban list"),
+    
+    // Server queries
+    (351, "RPL_VERSION", "{server} {version} {debug_level} {server_name} :{comments}"),
+    (364, "RPL_LINKS", "{server} {uplink} :{hopcount} {server_info}"),
+    (365, "RPL_ENDOFLINKS", "{mask} :End of LINKS list"),
+    (371, "RPL_INFO", ":{info}"),
+    (372, "RPL_MOTD", ":- {line}"),
+    (374, "RPL_ENDOFINFO", ":End of INFO list"),
+    (375, "RPL_MOTDSTART", ":- {server} Message of the Day -"),
+    (376, "RPL_ENDOFMOTD", ":End of /MOTD command."),
+    (381, "RPL_YOUREOPER", ":You are now an IRC operator"),
+    (382, "RPL_REHASHING", "{file} :Rehashing"),
+    (391, "RPL_TIME", "{server} :{time}"),
+    (392, "RPL_USERSSTART", ":UserID Terminal Host"),
+    (393, "RPL_USERS", ":{entry}"),
+    (394, "RPL_ENDOFUSERS", ":End of users"),
+    (395, "RPL_NOUSERS", ":Nobody logged in"),
+    (396, "RPL_HOSTHIDDEN", "{host} :is now your displayed host"),
+    
+    // Errors
+    (401, "ERR_NOSUCHNICK", ":No such nick/channel: {nick}"),
+    (402, "ERR_NOSUCHSERVER", ":No such server: {server}"),
+    (403, "ERR_NOSUCHCHANNEL", "{channel} :No such channel"),
+    (404, "ERR_CANNOTSENDTOCHAN", "{channel} :Cannot send to channel"),
+    (405, "ERR_TOOMANYCHANNELS", "{channel} :You have joined too many channels"),
+    (406, "ERR_WASNOSUCHNICK", "{nick} :There was no such nickname"),
+    (407, "ERR_TOOMANYTARGETS", "{target} :{reason}"),
+    (408, "ERR_NOSUCHSERVICE", "{service} :No such service"),
+    (409, "ERR_NOORIGIN", ":No origin specified"),
+    (411, "ERR_NORECIPIENT", ":No recipient given ({command})"),
+    (412, "ERR_NOTEXTTOSEND", ":No text to send"),
+    (413, "ERR_NOTOPLEVEL", "{mask} :No toplevel domain specified"),
+    (414, "ERR_WILDTOPLEVEL", "{mask} :Wildcard in toplevel domain"),
+    (415, "ERR_BADMASK", "{mask} :Bad Server/host mask"),
+    (417, "ERR_INPUTTOOLONG", ":Input line was too long"),
+    (421, "ERR_UNKNOWNCOMMAND", "{command} :Unknown command"),
+    (422, "ERR_NOMOTD", ":MOTD file is missing"),
+    (423, "ERR_NOADMININFO", "{server} :No administrative info available"),
+    (424, "ERR_FILEERROR", ":File error doing {operation} on {file}"),
+    (431, "ERR_NONICKNAMEGIVEN", ":No nickname given"),
+    (432, "ERR_ERRONEUSNICKNAME", ":Erroneous nickname"),
+    (433, "ERR_NICKNAMEINUSE", ":Nickname is already in use"),
+    (434, "ERR_NORULES", ":RULES File is missing"),
+    (436, "ERR_NICKCOLLISION", "{nick} :Nickname collision KILL from {user}@{host}"),
+    (437, "ERR_UNAVAILRESOURCE", "{target} :Nick/channel is temporarily unavailable"),
+    (438, "ERR_NICKTOOFAST", "{nick} :Nick change too fast. Please wait {seconds} seconds."),
+    (440, "ERR_SERVICESDOWN", "{target} :Services are currently unavailable"),
+    (441, "ERR_USERNOTINCHANNEL", "{nick} {channel} :They aren't on that channel"),
+    (442, "ERR_NOTONCHANNEL", "{channel} :You're not on that channel"),
+    (443, "ERR_USERONCHANNEL", "{nick} {channel} :is already on channel"),
+    (444, "ERR_NOLOGIN", "{user} :User not logged in"),
+    (445, "ERR_SUMMONDISABLED", ":SUMMON has been disabled"),
+    (446, "ERR_USERSDISABLED", ":USERS has been disabled"),
+    (451, "ERR_NOTREGISTERED", ":You have not registered"),
+    (461, "ERR_NEEDMOREPARAMS", "{command} :Not enough parameters"),
+    (462, "ERR_ALREADYREGISTRED", ":You may not reregister"),
+    (463, "ERR_NOPERMFORHOST", ":Your host isn't among the privileged"),
+    (464, "ERR_PASSWDMISMATCH", ":Password incorrect"),
+    (465, "ERR_YOUREBANNEDCREEP", ":You are banned from this server: {reason}"),
+    (467, "ERR_KEYSET", "{channel} :Channel key already set"),
+    (470, "ERR_LINKCHANNEL", "{channel} {forward} :Forwarding to another channel"),
+    (471, "ERR_CHANNELISFULL", "{channel} :Cannot join channel (+l) - channel is full"),
+    (472, "ERR_UNKNOWNMODE", "{mode} :is unknown mode char to me"),
+    (473, "ERR_INVITEONLYCHAN", "{channel} :Cannot join channel (+i) - you must be invited"),
+    (474, "ERR_BANNEDFROMCHAN", "{channel} :Cannot join channel (+b) - you are banned"),
+    (475, "ERR_BADCHANNELKEY", "{channel} :Cannot join channel (+k) - you need the correct key"),
+    (476, "ERR_BADCHANMASK", "{channel} :Bad Channel Mask"),
+    (477, "ERR_NEEDREGGEDNICK", "{channel} :Cannot join channel (+R) - you need to be identified with services"),
+    (478, "ERR_BANLISTFULL", "{channel} :Channel list is full"),
+    (479, "ERR_BADCHANNAME", ":Cannot join channel: {reason}"),
+    (480, "ERR_THROTTLE", "{channel} :Cannot join channel (+j) - throttle exceeded, try again later"),
+    (481, "ERR_NOPRIVILEGES", ":Permission Denied- You're not an IRC operator"),
+    (482, "ERR_CHANOPRIVSNEEDED", "{channel} :You're not channel operator"),
+    (483, "ERR_CANTKILLSERVER", ":You can't kill a server!"),
+    (484, "ERR_RESTRICTED", ":Your connection is restricted!"),
+    (485, "ERR_UNIQOPPRIVSNEEDED", ":You're not the original channel operator"),
+    (489, "ERR_SECUREONLYCHAN", "{channel} :Cannot join channel (+S) - you need a TLS connection"),
+    (491, "ERR_NOOPERHOST", ":No appropriate operator blocks were found for your host"),
+    (501, "ERR_UMODEUNKNOWNFLAG", ":Unknown MODE flag"),
+    (502, "ERR_USERSDONTMATCH", ":Cannot change mode for other users"),
+    (504, "ERR_CANTSETOPERATORMODE", ":Operator mode can only be granted through OPER command"),
+    (511, "ERR_SILELISTFULL", "{mask} :Your silence list is full"),
+    (520, "ERR_OPERONLY", "{channel} :Cannot join channel (+O) - you need to be an IRC operator"),
+    (524, "ERR_HELPNOTFOUND", "{topic} :No help available for this command"),
+    (525, "ERR_NOSUCHGLINE", "{mask} :No such GLINE"),
+    (526, "ERR_NOSUCHKLINE", "{mask} :No such KLINE"),
+    (527, "ERR_NOSUCHDLINE", "{mask} :No such DLINE"),
+    (528, "ERR_NOSUCHXLINE", "{mask} :No such XLINE"),
+    (529, "ERR_INVALIDDURATION", ":Maximum duration is {seconds} seconds"),
+    (530, "ERR_INVALIDVALUE", "{name} :Invalid value"),
+    (531, "ERR_NOSUCHSETTING", "{setting} :No such setting"),
+    (532, "ERR_TOOMANYSERVICES", ":Maximum {count} services allowed"),
+    (533, "ERR_INVALIDNAME", "{name} :Invalid service name"),
+    (534, "ERR_DISABLED", "{command} :{reason}"),
+    (535, "ERR_NOSUCHELINE", "{mask} :No such ELINE"),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::NumericReply;
+    
+    fn custom(code: u16, text: &str) -> RepliesConfig {
         let mut replies = HashMap::new();
-        
-        // Connection registration replies
-        replies.insert(001, ReplyConfig {
-            code: 001,
-            text: "Welcome to the Internet Relay Network {nick}!{user}@{host}".to_string(),
-            description: Some("RPL_WELCOME - Welcome message".to_string()),
-        });
-        
-        replies.insert(002, ReplyConfig {
-            code: 002,
-            text: "Your host is {server_name}, running version {server_version}".to_string(),
-            description: Some("RPL_YOURHOST - Server information".to_string()),
-        });
-        
-        replies.insert(003, ReplyConfig {
-            code: 003,
-            text: "This server was created {server_created}".to_string(),
-            description: Some("RPL_CREATED - Server creation date".to_string()),
-        });
-        
-        replies.insert(004, ReplyConfig {
-            code: 004,
-            text: "{server_name} {server_version} {usermodes} {channelmodes}".to_string(),
-            description: Some("RPL_MYINFO - Server capabilities".to_string()),
-        });
-        
-        // Server queries
-        replies.insert(256, ReplyConfig {
-            code: 256,
-            text: "{server_name} :Administrative info".to_string(),
-            description: Some("RPL_ADMINME - Admin info header".to_string()),
-        });
-        
-        replies.insert(257, ReplyConfig {
-            code: 257,
-            text: ":{admin_location1}".to_string(),
-            description: Some("RPL_ADMINLOC1 - Admin location line 1".to_string()),
-        });
-        
-        replies.insert(258, ReplyConfig {
-            code: 258,
-            text: ":{admin_location2}".to_string(),
-            description: Some("RPL_ADMINLOC2 - Admin location line 2".to_string()),
-        });
-        
-        replies.insert(259, ReplyConfig {
-            code: 259,
-            text: ":{admin_email}".to_string(),
-            description: Some("RPL_ADMINEMAIL - Admin email".to_string()),
-        });
-        
-        replies.insert(351, ReplyConfig {
-            code: 351,
-            text: "{server_name} :{server_version} {server_name} :{server_description}".to_string(),
-            description: Some("RPL_VERSION - Server version info".to_string()),
-        });
-        
-        // User queries
-        replies.insert(311, ReplyConfig {
-            code: 311,
-            text: "{nick} {user} {host} * :{realname}".to_string(),
-            description: Some("RPL_WHOISUSER - User information".to_string()),
-        });
-        
-        replies.insert(312, ReplyConfig {
-            code: 312,
-            text: "{nick} {server_name} :{server_description}".to_string(),
-            description: Some("RPL_WHOISSERVER - Server information".to_string()),
-        });
-        
-        replies.insert(313, ReplyConfig {
-            code: 313,
-            text: "{nick} :is an IRC operator".to_string(),
-            description: Some("RPL_WHOISOPERATOR - Operator status".to_string()),
-        });
-        
-        replies.insert(315, ReplyConfig {
-            code: 315,
-            text: "{target} :End of WHO list".to_string(),
-            description: Some("RPL_ENDOFWHO - End of WHO list".to_string()),
-        });
-        
-        replies.insert(318, ReplyConfig {
-            code: 318,
-            text: "{nick} :End of WHOIS list".to_string(),
-            description: Some("RPL_ENDOFWHOIS - End of WHOIS list".to_string()),
-        });
-        
-        replies.insert(319, ReplyConfig {
-            code: 319,
-            text: "{nick} :{channels}".to_string(),
-            description: Some("RPL_WHOISCHANNELS - User channels".to_string()),
-        });
-        
-        // Channel operations
-        replies.insert(322, ReplyConfig {
-            code: 322,
-            text: "{channel} {visible} :{topic}".to_string(),
-            description: Some("RPL_LIST - Channel list entry".to_string()),
-        });
-        
-        replies.insert(323, ReplyConfig {
-            code: 323,
-            text: ":End of LIST".to_string(),
-            description: Some("RPL_LISTEND - End of LIST".to_string()),
-        });
-        
-        replies.insert(331, ReplyConfig {
-            code: 331,
-            text: "{channel} :No topic is set".to_string(),
-            description: Some("RPL_NOTOPIC - No topic set".to_string()),
-        });
-        
-        replies.insert(332, ReplyConfig {
-            code: 332,
-            text: "{channel} :{topic}".to_string(),
-            description: Some("RPL_TOPIC - Channel topic".to_string()),
-        });
-        
-        replies.insert(341, ReplyConfig {
-            code: 341,
-            text: "{nick} {channel}".to_string(),
-            description: Some("RPL_INVITING - Invitation sent".to_string()),
-        });
-        
-        replies.insert(353, ReplyConfig {
-            code: 353,
-            text: "= {channel} :{names}".to_string(),
-            description: Some("RPL_NAMREPLY - Channel names list".to_string()),
-        });
-        
-        replies.insert(366, ReplyConfig {
-            code: 366,
-            text: "{channel} :End of NAMES list".to_string(),
-            description: Some("RPL_ENDOFNAMES - End of NAMES list".to_string()),
-        });
-        
-        // Error replies
-        replies.insert(401, ReplyConfig {
-            code: 401,
-            text: "{nick} :No such nick/channel".to_string(),
-            description: Some("ERR_NOSUCHNICK - No such nick/channel".to_string()),
-        });
-        
-        replies.insert(402, ReplyConfig {
-            code: 402,
-            text: "{server} :No such server".to_string(),
-            description: Some("ERR_NOSUCHSERVER - No such server".to_string()),
-        });
-        
-        replies.insert(403, ReplyConfig {
-            code: 403,
-            text: "{channel} :No such channel".to_string(),
-            description: Some("ERR_NOSUCHCHANNEL - No such channel".to_string()),
-        });
-        
-        replies.insert(404, ReplyConfig {
-            code: 404,
-            text: "{channel} :Cannot send to channel".to_string(),
-            description: Some("ERR_CANNOTSENDTOCHAN - Cannot send to channel".to_string()),
-        });
-        
-        replies.insert(405, ReplyConfig {
-            code: 405,
-            text: "{channel} :You have joined too many channels".to_string(),
-            description: Some("ERR_TOOMANYCHANNELS - Too many channels".to_string()),
-        });
-        
-        replies.insert(421, ReplyConfig {
-            code: 421,
-            text: "{command} :Unknown command".to_string(),
-            description: Some("ERR_UNKNOWNCOMMAND - Unknown command".to_string()),
-        });
-        
-        replies.insert(422, ReplyConfig {
-            code: 422,
-            text: ":MOTD File is missing".to_string(),
-            description: Some("ERR_NOMOTD - No MOTD file".to_string()),
-        });
-        
-        replies.insert(431, ReplyConfig {
-            code: 431,
-            text: ":No nickname given".to_string(),
-            description: Some("ERR_NONICKNAMEGIVEN - No nickname given".to_string()),
-        });
-        
-        replies.insert(432, ReplyConfig {
-            code: 432,
-            text: "{nick} :Erroneous nickname".to_string(),
-            description: Some("ERR_ERRONEUSNICKNAME - Erroneous nickname".to_string()),
-        });
-        
-        replies.insert(433, ReplyConfig {
-            code: 433,
-            text: "{nick} :Nickname is already in use".to_string(),
-            description: Some("ERR_NICKNAMEINUSE - Nickname in use".to_string()),
-        });
-        
-        replies.insert(436, ReplyConfig {
-            code: 436,
-            text: "{nick} :Nickname collision KILL from {server}".to_string(),
-            description: Some("ERR_NICKCOLLISION - Nickname collision".to_string()),
-        });
-        
-        replies.insert(441, ReplyConfig {
-            code: 441,
-            text: "{nick} {channel} :They aren't on that channel".to_string(),
-            description: Some("ERR_USERNOTINCHANNEL - User not in channel".to_string()),
-        });
-        
-        replies.insert(442, ReplyConfig {
-            code: 442,
-            text: "{channel} :You're not on that channel".to_string(),
-            description: Some("ERR_NOTONCHANNEL - Not on channel".to_string()),
-        });
-        
-        replies.insert(443, ReplyConfig {
-            code: 443,
-            text: "{user} {channel} :is already on channel".to_string(),
-            description: Some("ERR_USERONCHANNEL - User already on channel".to_string()),
-        });
-        
-        replies.insert(451, ReplyConfig {
-            code: 451,
-            text: ":You have not registered".to_string(),
-            description: Some("ERR_NOTREGISTERED - Not registered".to_string()),
-        });
-        
-        replies.insert(461, ReplyConfig {
-            code: 461,
-            text: "{command} :Not enough parameters".to_string(),
-            description: Some("ERR_NEEDMOREPARAMS - Need more parameters".to_string()),
-        });
-        
-        replies.insert(462, ReplyConfig {
-            code: 462,
-            text: ":You may not reregister".to_string(),
-            description: Some("ERR_ALREADYREGISTERED - Already registered".to_string()),
-        });
-        
-        replies.insert(463, ReplyConfig {
-            code: 463,
-            text: ":Your host isn't among the privileged".to_string(),
-            description: Some("ERR_NOPERMFORHOST - No permission for host".to_string()),
-        });
-        
-        replies.insert(464, ReplyConfig {
-            code: 464,
-            text: ":Password incorrect".to_string(),
-            description: Some("ERR_PASSWDMISMATCH - Password mismatch".to_string()),
-        });
-        
-        replies.insert(465, ReplyConfig {
-            code: 465,
-            text: ":You are banned from this server".to_string(),
-            description: Some("ERR_YOUREBANNEDCREEP - Banned from server".to_string()),
-        });
-        
-        replies.insert(471, ReplyConfig {
-            code: 471,
-            text: "{channel} :Cannot join channel (+l)".to_string(),
-            description: Some("ERR_CHANNELISFULL - Channel is full".to_string()),
-        });
-        
-        replies.insert(472, ReplyConfig {
-            code: 472,
-            text: "{char} :is unknown mode char to me for {channel}".to_string(),
-            description: Some("ERR_UNKNOWNMODE - Unknown mode".to_string()),
-        });
-        
-        replies.insert(473, ReplyConfig {
-            code: 473,
-            text: "{channel} :Cannot join channel (+i)".to_string(),
-            description: Some("ERR_INVITEONLYCHAN - Invite only channel".to_string()),
-        });
-        
-        replies.insert(474, ReplyConfig {
-            code: 474,
-            text: "{channel} :Cannot join channel (+b)".to_string(),
-            description: Some("ERR_BANNEDFROMCHAN - Banned from channel".to_string()),
-        });
-        
-        replies.insert(475, ReplyConfig {
-            code: 475,
-            text: "{channel} :Cannot join channel (+k)".to_string(),
-            description: Some("ERR_BADCHANNELKEY - Bad channel key".to_string()),
-        });
-        
-        replies.insert(476, ReplyConfig {
-            code: 476,
-            text: "{channel} :Bad channel mask".to_string(),
-            description: Some("ERR_BADCHANMASK - Bad channel mask".to_string()),
-        });
-        
-        replies.insert(477, ReplyConfig {
-            code: 477,
-            text: "{channel} :Channel doesn't support modes".to_string(),
-            description: Some("ERR_NOCHANMODES - No channel modes".to_string()),
-        });
-        
-        replies.insert(478, ReplyConfig {
-            code: 478,
-            text: "{channel} {char} :Channel list is full".to_string(),
-            description: Some("ERR_BANLISTFULL - Ban list full".to_string()),
-        });
-        
-        replies.insert(481, ReplyConfig {
-            code: 481,
-            text: ":Permission Denied- You're not an IRC operator".to_string(),
-            description: Some("ERR_NOPRIVILEGES - No privileges".to_string()),
-        });
-        
-        replies.insert(482, ReplyConfig {
-            code: 482,
-            text: "{channel} :You're not channel operator".to_string(),
-            description: Some("ERR_CHANOPRIVSNEEDED - Channel operator privileges needed".to_string()),
-        });
-        
-        replies.insert(483, ReplyConfig {
-            code: 483,
-            text: ":You can't kill a server!".to_string(),
-            description: Some("ERR_CANTKILLSERVER - Can't kill server".to_string()),
-        });
-        
-        replies.insert(484, ReplyConfig {
-            code: 484,
-            text: ":Your connection is restricted!".to_string(),
-            description: Some("ERR_RESTRICTED - Connection restricted".to_string()),
-        });
-        
-        // Operator commands
-        replies.insert(381, ReplyConfig {
-            code: 381,
-            text: ":You are now an IRC operator".to_string(),
-            description: Some("RPL_YOUREOPER - You are now an operator".to_string()),
-        });
-        
-        replies.insert(200, ReplyConfig {
-            code: 200,
-            text: "Link {version} {destination} {next_server} {hopcount} {protocol_version} {flags} {link_data} {link_creation}".to_string(),
-            description: Some("RPL_TRACELINK - Trace link information".to_string()),
-        });
-        
-        replies.insert(201, ReplyConfig {
-            code: 201,
-            text: "Try. {class} {server}".to_string(),
-            description: Some("RPL_TRACECONNECTING - Trace connecting".to_string()),
-        });
-        
-        replies.insert(202, ReplyConfig {
-            code: 202,
-            text: "H.S. {class} {server}".to_string(),
-            description: Some("RPL_TRACEHANDSHAKE - Trace handshake".to_string()),
-        });
-        
-        replies.insert(203, ReplyConfig {
-            code: 203,
-            text: "???? {class} {client_ip} {client_port} {server_ip} {server_port}".to_string(),
-            description: Some("RPL_TRACEUNKNOWN - Trace unknown".to_string()),
-        });
-        
-        replies.insert(204, ReplyConfig {
-            code: 204,
-            text: "Oper {class} {nick}".to_string(),
-            description: Some("RPL_TRACEOPERATOR - Trace operator".to_string()),
-        });
-        
-        replies.insert(205, ReplyConfig {
-            code: 205,
-            text: "User {class} {nick}".to_string(),
-            description: Some("RPL_TRACEUSER - Trace user".to_string()),
-        });
-        
-        replies.insert(206, ReplyConfig {
-            code: 206,
-            text: "Serv {class} {int}S {int}C {server} {nick}!{user}@{host}".to_string(),
-            description: Some("RPL_TRACESERVER - Trace server".to_string()),
-        });
-        
-        replies.insert(207, ReplyConfig {
-            code: 207,
-            text: "Service {class} {name} {type} {active_type}".to_string(),
-            description: Some("RPL_TRACESERVICE - Trace service".to_string()),
-        });
-        
-        replies.insert(208, ReplyConfig {
-            code: 208,
-            text: "{newtype} 0 {client_name}".to_string(),
-            description: Some("RPL_TRACENEWTYPE - Trace new type".to_string()),
-        });
-        
-        replies.insert(209, ReplyConfig {
-            code: 209,
-            text: "Class {class} {count}".to_string(),
-            description: Some("RPL_TRACECLASS - Trace class".to_string()),
-        });
-        
-        replies.insert(261, ReplyConfig {
-            code: 261,
-            text: "File {logfile} {debug_level}".to_string(),
-            description: Some("RPL_TRACELOG - Trace log".to_string()),
-        });
-        
-        replies.insert(262, ReplyConfig {
-            code: 262,
-            text: "{server_name} {version} {debug_level} {current_path} {max_connections}".to_string(),
-            description: Some("RPL_TRACEEND - Trace end".to_string()),
-        });
-        
-        // Stats replies
-        replies.insert(211, ReplyConfig {
-            code: 211,
-            text: "Link {hostname} {port} {port} {class} {sendq} {sent_msgs} {sent_bytes} {recv_msgs} {recv_bytes} {time_open}".to_string(),
-            description: Some("RPL_STATSLINKINFO - Stats link info".to_string()),
-        });
-        
-        replies.insert(212, ReplyConfig {
-            code: 212,
-            text: "Commands {char} {count} {byte_count} {remote_count}".to_string(),
-            description: Some("RPL_STATSCOMMANDS - Stats commands".to_string()),
-        });
-        
-        replies.insert(213, ReplyConfig {
-            code: 213,
-            text: "C {host} * {name} {port} {class}".to_string(),
-            description: Some("RPL_STATSCLINE - Stats C line".to_string()),
-        });
-        
-        replies.insert(214, ReplyConfig {
-            code: 214,
-            text: "N {host} * {name} {port} {class}".to_string(),
-            description: Some("RPL_STATSNLINE - Stats N line".to_string()),
-        });
-        
-        replies.insert(215, ReplyConfig {
-            code: 215,
-            text: "I {host} * {name} {port} {class}".to_string(),
-            description: Some("RPL_STATSILINE - Stats I line".to_string()),
-        });
-        
-        replies.insert(216, ReplyConfig {
-            code: 216,
-            text: "K {host} * {username} {port} {class}".to_string(),
-            description: Some("RPL_STATSKLINE - Stats K line".to_string()),
-        });
-        
-        replies.insert(218, ReplyConfig {
-            code: 218,
-            text: "Y {class} {ping_freq} {connect_freq} {max_sendq}".to_string(),
-            description: Some("RPL_STATSYLINE - Stats Y line".to_string()),
-        });
-        
-        replies.insert(219, ReplyConfig {
-            code: 219,
-            text: "{letter} :End of STATS report".to_string(),
-            description: Some("RPL_ENDOFSTATS - End of stats".to_string()),
-        });
-        
-        replies.insert(242, ReplyConfig {
-            code: 242,
-            text: ":Server Up {uptime} seconds".to_string(),
-            description: Some("RPL_STATSUPTIME - Server uptime".to_string()),
-        });
-        
-        replies.insert(243, ReplyConfig {
-            code: 243,
-            text: "O {hostmask} * {name} {port} {class}".to_string(),
-            description: Some("RPL_STATSOLINE - Stats O line".to_string()),
-        });
-        
-        replies.insert(244, ReplyConfig {
-            code: 244,
-            text: "H {host} * {name} {port} {class}".to_string(),
-            description: Some("RPL_STATSHLINE - Stats H line".to_string()),
-        });
-        
-        replies.insert(375, ReplyConfig {
-            code: 375,
-            text: ":- {server} Message of the Day -".to_string(),
-            description: Some("RPL_MOTDSTART - MOTD start".to_string()),
-        });
-        
-        replies.insert(372, ReplyConfig {
-            code: 372,
-            text: ":- {line}".to_string(),
-            description: Some("RPL_MOTD - MOTD line".to_string()),
-        });
-        
-        replies.insert(376, ReplyConfig {
-            code: 376,
-            text: ":End of /MOTD command.".to_string(),
-            description: Some("RPL_ENDOFMOTD - MOTD end".to_string()),
-        });
-        
-        replies.insert(422, ReplyConfig {
-            code: 422,
-            text: ":MOTD file is missing".to_string(),
-            description: Some("ERR_NOMOTD - No MOTD file".to_string()),
-        });
-        
-        replies.insert(215, ReplyConfig {
-            code: 215,
-            text: "I {host} * {host} {port} {class}".to_string(),
-            description: Some("RPL_STATSILINE - Stats I line".to_string()),
-        });
-        
-        replies.insert(216, ReplyConfig {
-            code: 216,
-            text: "K {host} * {username} {port} {class}".to_string(),
-            description: Some("RPL_STATSKLINE - Stats K line".to_string()),
-        });
-        
-        replies.insert(218, ReplyConfig {
-            code: 218,
-            text: "Y {class} {ping_freq} {connect_freq} {max_sendq}".to_string(),
-            description: Some("RPL_STATSYLINE - Stats Y line".to_string()),
-        });
-        
-        replies.insert(219, ReplyConfig {
-            code: 219,
-            text: "{char} :End of STATS report".to_string(),
-            description: Some("RPL_ENDOFSTATS - End of stats".to_string()),
-        });
-        
-        replies.insert(241, ReplyConfig {
-            code: 241,
-            text: "L {hostmask} * {servername} {port} {class}".to_string(),
-            description: Some("RPL_STATSLLINE - Stats L line".to_string()),
-        });
-        
-        replies.insert(242, ReplyConfig {
-            code: 242,
-            text: ":Server Up {days} days, {hours:02}:{minutes:02}:{seconds:02}".to_string(),
-            description: Some("RPL_STATSUPTIME - Server uptime".to_string()),
-        });
-        
-        replies.insert(243, ReplyConfig {
-            code: 243,
-            text: "O {hostmask} * {name}".to_string(),
-            description: Some("RPL_STATSOLINE - Stats O line".to_string()),
-        });
-        
-        replies.insert(244, ReplyConfig {
-            code: 244,
-            text: "H {hostmask} * {servername}".to_string(),
-            description: Some("RPL_STATSHLINE - Stats H line".to_string()),
-        });
-        
-        replies.insert(251, ReplyConfig {
-            code: 251,
-            text: ":There are {users} users and {invisible} invisible on {servers} servers".to_string(),
-            description: Some("RPL_LUSERCLIENT - LUSER client".to_string()),
-        });
-        
-        replies.insert(252, ReplyConfig {
-            code: 252,
-            text: "{ops} :operator(s) online".to_string(),
-            description: Some("RPL_LUSEROP - LUSER op".to_string()),
-        });
-        
-        replies.insert(253, ReplyConfig {
-            code: 253,
-            text: "{unknown} :unknown connection(s)".to_string(),
-            description: Some("RPL_LUSERUNKNOWN - LUSER unknown".to_string()),
-        });
-        
-        replies.insert(254, ReplyConfig {
-            code: 254,
-            text: "{channels} :channels formed".to_string(),
-            description: Some("RPL_LUSERCHANNELS - LUSER channels".to_string()),
-        });
-        
-        replies.insert(255, ReplyConfig {
-            code: 255,
-            text: ":I have {clients} clients and {servers} servers".to_string(),
-            description: Some("RPL_LUSERME - LUSER me".to_string()),
-        });
-        
-        replies.insert(265, ReplyConfig {
-            code: 265,
-            text: ":Current local users: {current} Max: {max}".to_string(),
-            description: Some("RPL_LOCALUSERS - Local users".to_string()),
-        });
-        
-        replies.insert(266, ReplyConfig {
-            code: 266,
-            text: ":Current global users: {current} Max: {max}".to_string(),
-            description: Some("RPL_GLOBALUSERS - Global users".to_string()),
-        });
-        
-        // Time and info
-        replies.insert(391, ReplyConfig {
-            code: 391,
-            text: "{server} :{time}".to_string(),
-            description: Some("RPL_TIME - Server time".to_string()),
-        });
-        
-        replies.insert(371, ReplyConfig {
-            code: 371,
-            text: ":{info}".to_string(),
-            description: Some("RPL_INFO - Server info".to_string()),
-        });
-        
-        replies.insert(374, ReplyConfig {
-            code: 374,
-            text: ":End of INFO list".to_string(),
-            description: Some("RPL_ENDOFINFO - End of info".to_string()),
-        });
-        
-        // Links
-        replies.insert(364, ReplyConfig {
-            code: 364,
-            text: "{mask} {server} :{hopcount} {server_info}".to_string(),
-            description: Some("RPL_LINKS - Server links".to_string()),
-        });
-        
-        replies.insert(365, ReplyConfig {
-            code: 365,
-            text: "{mask} :End of LINKS list".to_string(),
-            description: Some("RPL_ENDOFLINKS - End of links".to_string()),
-        });
-        
-        // Away
-        replies.insert(301, ReplyConfig {
-            code: 301,
-            text: "{nick} :{away_message}".to_string(),
-            description: Some("RPL_AWAY - User is away".to_string()),
-        });
-        
-        replies.insert(305, ReplyConfig {
-            code: 305,
-            text: ":You are no longer marked as being away".to_string(),
-            description: Some("RPL_UNAWAY - No longer away".to_string()),
-        });
-        
-        replies.insert(306, ReplyConfig {
-            code: 306,
-            text: ":You have been marked as being away".to_string(),
-            description: Some("RPL_NOWAWAY - Now away".to_string()),
-        });
-        
-        // Userhost and Ison
-        replies.insert(302, ReplyConfig {
-            code: 302,
-            text: ":{reply}".to_string(),
-            description: Some("RPL_USERHOST - User host".to_string()),
-        });
-        
-        replies.insert(303, ReplyConfig {
-            code: 303,
-            text: ":{nick_list}".to_string(),
-            description: Some("RPL_ISON - Is on".to_string()),
-        });
-        
-        // MOTD
-        replies.insert(375, ReplyConfig {
-            code: 375,
-            text: ":- {server_name} Message of the day - ".to_string(),
-            description: Some("RPL_MOTDSTART - MOTD start".to_string()),
-        });
-        
-        replies.insert(372, ReplyConfig {
-            code: 372,
-            text: ":- {line}".to_string(),
-            description: Some("RPL_MOTD - MOTD line".to_string()),
-        });
-        
-        replies.insert(376, ReplyConfig {
-            code: 376,
-            text: ":End of MOTD command".to_string(),
-            description: Some("RPL_ENDOFMOTD - End of MOTD".to_string()),
-        });
-        
-        // LUSERS command replies
-        replies.insert(251, ReplyConfig {
-            code: 251,
-            text: ":There are {param0} users and {param1} services on {param2} servers".to_string(),
-            description: Some("RPL_LUSERCLIENT - LUSER client info".to_string()),
-        });
-        
-        replies.insert(252, ReplyConfig {
-            code: 252,
-            text: "{param0} :operator(s) online".to_string(),
-            description: Some("RPL_LUSEROP - LUSER operator info".to_string()),
-        });
-        
-        replies.insert(253, ReplyConfig {
-            code: 253,
-            text: "{param0} :unknown connection(s)".to_string(),
-            description: Some("RPL_LUSERUNKNOWN - LUSER unknown connections".to_string()),
-        });
-        
-        replies.insert(254, ReplyConfig {
-            code: 254,
-            text: "{param0} :channels formed".to_string(),
-            description: Some("RPL_LUSERCHANNELS - LUSER channels".to_string()),
-        });
-        
-        replies.insert(255, ReplyConfig {
-            code: 255,
-            text: ":I have {param0} clients and {param1} servers".to_string(),
-            description: Some("RPL_LUSERME - LUSER server info".to_string()),
-        });
-        
-        replies.insert(265, ReplyConfig {
-            code: 265,
-            text: ":Current local users: {param0}, max: {param1}".to_string(),
-            description: Some("RPL_LOCALUSERS - Local users info".to_string()),
-        });
-        
-        replies.insert(266, ReplyConfig {
-            code: 266,
-            text: ":Current global users: {param0}, max: {param1}".to_string(),
-            description: Some("RPL_GLOBALUSERS - Global users info".to_string()),
-        });
-        
-        // User mode replies
-        replies.insert(221, ReplyConfig {
-            code: 221,
-            text: "{param0} {param1}".to_string(),
-            description: Some("RPL_UMODEIS - User mode is".to_string()),
-        });
-        
-        replies.insert(502, ReplyConfig {
-            code: 502,
-            text: ":Cannot change mode for other users".to_string(),
-            description: Some("ERR_USERSDONTMATCH - Users don't match".to_string()),
-        });
-        
-        replies.insert(503, ReplyConfig {
-            code: 503,
-            text: ":Operator mode can only be granted through OPER command".to_string(),
-            description: Some("ERR_CANTSETOPERATORMODE - Can't set operator mode".to_string()),
-        });
-        
-        // Rehash
-        replies.insert(382, ReplyConfig {
-            code: 382,
-            text: "{file} :Rehashing".to_string(),
-            description: Some("RPL_REHASHING - Rehashing".to_string()),
-        });
-        
-        // Connect command
-        replies.insert(200, ReplyConfig {
-            code: 200,
-            text: "Link {version} {destination} {next_server} {hopcount} {protocol_version} {flags} {link_data} {link_creation}".to_string(),
-            description: Some("RPL_CONNECTSUCCESS - Connect success".to_string()),
-        });
-        
-        replies.insert(201, ReplyConfig {
-            code: 201,
-            text: "Try. {class} {server}".to_string(),
-            description: Some("RPL_CONNECTFAILED - Connect failed".to_string()),
-        });
-        
-        // Custom numeric replies
-        replies.insert(320, ReplyConfig {
-            code: 320,
-            text: "{nick} :{info}".to_string(),
-            description: Some("RPL_WHOISSPECIAL - Special WHOIS info".to_string()),
-        });
-        
-        replies.insert(317, ReplyConfig {
-            code: 317,
-            text: "{nick} {seconds} {signon}".to_string(),
-            description: Some("RPL_WHOISIDLE - WHOIS idle time".to_string()),
-        });
-        
-        replies.insert(324, ReplyConfig {
-            code: 324,
-            text: "{channel} {mode} {mode_params}".to_string(),
-            description: Some("RPL_CHANNELMODEIS - Channel mode is".to_string()),
-        });
-        
-        replies.insert(341, ReplyConfig {
-            code: 341,
-            text: "{nick} {channel}".to_string(),
-            description: Some("RPL_INVITING - Inviting".to_string()),
-        });
-        
-        replies.insert(346, ReplyConfig {
-            code: 346,
-            text: "{channel} {invite_mask}".to_string(),
-            description: Some("RPL_INVITELIST - Invite list".to_string()),
-        });
-        
-        replies.insert(347, ReplyConfig {
-            code: 347,
-            text: "{channel} :End of channel invite list".to_string(),
-            description: Some("RPL_ENDOFINVITELIST - End of invite list".to_string()),
-        });
-        
-        replies.insert(348, ReplyConfig {
-            code: 348,
-            text: "{channel} {exception_mask}".to_string(),
-            description: Some("RPL_EXCEPTLIST - Exception list".to_string()),
-        });
-        
-        replies.insert(349, ReplyConfig {
-            code: 349,
-            text: "{channel} :End of channel exception list".to_string(),
-            description: Some("RPL_ENDOFEXCEPTLIST - End of exception list".to_string()),
-        });
-        
-        replies.insert(352, ReplyConfig {
-            code: 352,
-            text: "{channel} {user} {host} {server} {nick} {flags} :{hopcount} {realname}".to_string(),
-            description: Some("RPL_WHOREPLY - WHO reply".to_string()),
-        });
-        
-        replies.insert(367, ReplyConfig {
-            code: 367,
-            text: "{channel} {ban_mask}".to_string(),
-            description: Some("RPL_BANLIST - Ban list".to_string()),
-        });
-        
-        replies.insert(368, ReplyConfig {
-            code: 368,
-            text: "{channel} :End of channel ban list".to_string(),
-            description: Some("RPL_ENDOFBANLIST - End of ban list".to_string()),
-        });
-        
-        replies.insert(369, ReplyConfig {
-            code: 369,
-            text: "{nick} :End of WHOWAS".to_string(),
-            description: Some("RPL_ENDOFWHOWAS - End of WHOWAS".to_string()),
-        });
-        
-        replies.insert(221, ReplyConfig {
-            code: 221,
-            text: "{user_modes}".to_string(),
-            description: Some("RPL_UMODEIS - User mode is".to_string()),
-        });
-        
-        replies.insert(392, ReplyConfig {
-            code: 392,
-            text: ":UserID Terminal Host".to_string(),
-            description: Some("RPL_USERSSTART - Users start".to_string()),
-        });
-        
-        replies.insert(393, ReplyConfig {
-            code: 393,
-            text: ":%-8s %-9s %-8s".to_string(),
-            description: Some("RPL_USERS - Users".to_string()),
-        });
-        
-        replies.insert(394, ReplyConfig {
-            code: 394,
-            text: ":End of users".to_string(),
-            description: Some("RPL_ENDOFUSERS - End of users".to_string()),
-        });
-        
-        replies.insert(395, ReplyConfig {
-            code: 395,
-            text: ":Nobody logged in".to_string(),
-            description: Some("RPL_NOUSERS - No users".to_string()),
-        });
-        
-        // Bot mode IRCv3
-        replies.insert(320, ReplyConfig {
-            code: 320,
-            text: "{nick} :{bot_info}".to_string(),
-            description: Some("RPL_WHOISBOT - WHOIS bot info".to_string()),
-        });
-        
-        replies
+        replies.insert(code, ReplyConfig { code, text: text.to_string(), description: None });
+        RepliesConfig { replies }
+    }
+    
+    fn localized(config: RepliesConfig, message: Message) -> Vec<String> {
+        let replies = NumericReplies::default();
+        replies.set(Some(config), ServerInfo::default());
+        replies.localize(message).params
+    }
+    
+    #[test]
+    fn test_default_templates_are_valid() {
+        assert!(RepliesConfig::default().validation_errors().is_empty());
+        let mut codes: Vec<u16> = DEFAULT_TEMPLATES.iter().map(|(code, _, _)| *code).collect();
+        codes.sort();
+        codes.dedup();
+        assert_eq!(codes.len(), DEFAULT_TEMPLATES.len());
+    }
+    
+    #[test]
+    fn test_default_templates_match_built_replies() {
+        // The built-in templates reproduce what the server sends
+        for message in [
+            NumericReply::welcome("irc.test", "alice", "~alice", "example.com"),
+            NumericReply::isupport("alice", &["CHANTYPES=#".to_string(), "NICKLEN=30".to_string()]),
+            NumericReply::no_such_channel("alice", "#nowhere"),
+            NumericReply::whois_user("bob", "~bob", "host", "Bob B"),
+            NumericReply::luser_client(3, 1, 2),
+            NumericReply::cannot_join("alice", "#full", 'l'),
+        ] {
+            let code: u16 = message.command.to_string().parse().unwrap();
+            assert_eq!(localized(RepliesConfig::default(), message.clone()), message.params, "{:03}", code);
+            assert!(RepliesConfig::default().render(code, &message.params[0], &message.params[1..], &ServerInfo::default()).is_some(), "{:03}", code);
+        }
+    }
+    
+    #[test]
+    fn test_custom_templates() {
+        let params = localized(custom(403, "{channel} :Le canal {channel} n'existe pas, {client}"), NumericReply::no_such_channel("alice", "#nowhere"));
+        assert_eq!(params, ["alice", "#nowhere", "Le canal #nowhere n'existe pas, alice"]);
+        
+        let params = localized(custom(1, "Bienvenue sur {server_name}, {nick}!"), NumericReply::welcome("irc.test", "alice", "~alice", "example.com"));
+        assert_eq!(params, ["alice", "Bienvenue sur rustircd, alice!"]);
+        
+        // ISUPPORT tokens ahead of the template are left alone
+        let params = localized(custom(5, ":sont pris en charge"), NumericReply::isupport("alice", &["NICKLEN=30".to_string()]));
+        assert_eq!(params, ["alice", "NICKLEN=30", "sont pris en charge"]);
+        
+        // Replies without the built-in shape go out as built
+        let message = NumericReply::remote_query_timeout("far.example");
+        assert_eq!(localized(custom(402, ":Serveur inconnu : {server}"), message.clone()).len(), message.params.len());
+        assert_eq!(localized(custom(402, ":Serveur inconnu : {server}"), message.clone()), message.params);
+    }
+    
+    #[test]
+    fn test_validation_errors() {
+        assert!(custom(433, ":{client} est déjà pris").validation_errors().is_empty());
+        assert!(custom(311, "{nick} {user} {host} * :{realname} ({server_name})").validation_errors().is_empty());
+        
+        let errors = custom(403, "{channel} :{topic}").validation_errors();
+        assert_eq!(errors, [(403, "unknown placeholder {topic}".to_string())]);
+        assert_eq!(custom(441, "{nick} :gone").validation_errors()[0].1, "expects 3 parameter(s), template has 2");
+        assert_eq!(custom(403, "{channel :oops").validation_errors()[0].1, "unclosed placeholder in \"{channel\"");
+        assert_eq!(custom(703, ":nope").validation_errors()[0].1, "703 is not a customizable 0xx-5xx reply");
+        
+        let mut config = custom(401, ":{nick}?");
+        config.replies.get_mut(&401).unwrap().code = 402;
+        assert_eq!(config.validation_errors()[0].1, "entry 401 has code 402");
+    }
+    
+    #[test]
+    fn test_save_and_load() {
+        let path = std::env::temp_dir().join(format!("replies-{}.toml", std::process::id()));
+        custom(403, "{channel} :Pas de canal {channel}").save_to_file(&path).unwrap();
+        let loaded = RepliesConfig::from_file(&path).unwrap();
+        let _ = fs::remove_file(&path);
+        assert_eq!(loaded.get_reply(403).map(|reply| reply.text.as_str()), Some("{channel} :Pas de canal {channel}"));
+    }
+    
+    #[test]
+    fn test_shipped_replies_files_are_valid() {
+        for file in ["../replies.toml", "../examples/custom_replies.toml"] {
+            let path = Path::new(env!("CARGO_MANIFEST_DIR")).join(file);
+            let config = RepliesConfig::from_file(&path).unwrap();
+            assert_eq!(config.validation_errors(), [], "{}", file);
+        }
     }
 }
@@ -0,0 +1,110 @@
+//! RULES: the network's acceptable-use policy, served from a file
+//!
+//! Works like the MOTD: `server.rules_file` is read at startup and on
+//! `REHASH RULES`, and the text is sent as RPL_RULESSTART (308), RPL_RULES
+//! (232) and RPL_ENDOFRULES (309), wrapped to 80 characters.
+
+use crate::motd::{wrap_line, MOTD_LINE_WIDTH};
+use crate::{Error, Message, NumericReply, Result};
+use std::fs;
+use tokio::sync::RwLock;
+use tracing::{info, warn};
+
+/// RULES manager
+pub struct RulesManager {
+    /// Rules lines loaded from file
+    lines: RwLock<Vec<String>>,
+}
+
+impl RulesManager {
+    /// Create a new rules manager with no rules
+    pub fn new() -> Self {
+        Self {
+            lines: RwLock::new(Vec::new()),
+        }
+    }
+
+    /// Read the lines of a rules file without applying them
+    ///
+    /// Returns `None` if the file does not exist, which is not an error.
+    pub fn read_rules_file(rules_file: &str) -> Result<Option<Vec<String>>> {
+        let path = crate::MotdManager::resolve_motd_path(rules_file)?;
+        if !path.exists() {
+            warn!("Rules file not found: {} (resolved from: {})", path.display(), rules_file);
+            return Ok(None);
+        }
+
+        fs::read_to_string(&path)
+            .map(|content| Some(content.lines().map(str::to_string).collect()))
+            .map_err(|e| Error::Config(format!("Failed to read rules file {}: {}", rules_file, e)))
+    }
+
+    /// Replace the rules
+    pub async fn set_lines(&self, lines: Vec<String>) {
+        info!("Rules set ({} lines)", lines.len());
+        *self.lines.write().await = lines;
+    }
+
+    /// Whether `lines` are the rules already loaded
+    pub async fn is_loaded(&self, lines: &[String]) -> bool {
+        *self.lines.read().await == lines
+    }
+
+    /// Number of lines in the rules file
+    pub async fn line_count(&self) -> usize {
+        self.lines.read().await.len()
+    }
+
+    /// Whether there are rules to show
+    pub async fn is_enabled(&self) -> bool {
+        !self.lines.read().await.is_empty()
+    }
+
+    /// The RULES reply, or ERR_NORULES when there are none
+    pub async fn get_all_rules_messages(&self, server_name: &str) -> Vec<Message> {
+        let lines = self.lines.read().await;
+        if lines.is_empty() {
+            return vec![NumericReply::no_rules(server_name)];
+        }
+
+        let mut messages = vec![NumericReply::rules_start(server_name)];
+        for line in lines.iter() {
+            for wrapped in wrap_line(line, MOTD_LINE_WIDTH) {
+                messages.push(NumericReply::rules_line(&wrapped));
+            }
+        }
+        messages.push(NumericReply::rules_end());
+        messages
+    }
+}
+
+impl Default for RulesManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_rules_messages() {
+        let manager = RulesManager::new();
+        let messages = manager.get_all_rules_messages("test.server").await;
+        assert_eq!(messages.len(), 1);
+        assert!(messages[0].to_string().contains("434"));
+
+        manager.set_lines(vec!["No spam.".to_string(), "Be nice.".to_string()]).await;
+        let numerics: Vec<String> = manager.get_all_rules_messages("test.server").await
+            .iter()
+            .map(|message| message.command.to_string())
+            .collect();
+        assert_eq!(numerics, ["308", "232", "232", "309"]);
+    }
+
+    #[test]
+    fn test_missing_rules_file() {
+        assert!(RulesManager::read_rules_file("nonexistent_rules_12345.txt").unwrap().is_none());
+    }
+}
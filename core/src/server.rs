@@ -1,40 +1,169 @@
 //! Main IRC server implementation
 
 use crate::{
-    User, Message, MessageType, NumericReply, Config, ModuleManager,
+    User, Message, MessageRef, MessageType, ClientSenders, NumericReply, Config, ModuleManager, ModuleRegistry,
     connection::ConnectionHandler, Error, Result, module::{ModuleResult, ModuleStatsResponse}, client::{Client, ClientState},
     Database, BroadcastSystem, NetworkQueryManager, NetworkMessageHandler,
     ServerConnectionManager, ServerConnection, Prefix,
-    ThrottlingManager, StatisticsManager, MotdManager,
-    LookupService, RehashService,
+    ThrottlingManager, StatisticsManager, MotdManager, MotdSet,
+    LookupService, RehashService, RehashSection, SnoMask, NetworkTopology,
+    AuthManager, ServerEvent, events::EVENT_CHANNEL_CAPACITY,
     config::{SuperServerConfig, AuthenticationMethod, AuthenticationConfig},
+    link_compression::{self, CompressionStats, LinkCompressor, LinkDecompressor},
+    utils::network::display_host,
 };
+use arc_swap::ArcSwapOption;
 use chrono::Utc;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
-use tokio::sync::RwLock;
+use std::sync::atomic::{AtomicU32, Ordering};
+use tokio::sync::{broadcast, RwLock};
 use tokio::net::TcpListener;
 use tokio_rustls::TlsAcceptor;
 use rustls::{ServerConfig, Certificate, PrivateKey};
 use std::io::BufReader;
+use tokio_util::sync::CancellationToken;
 use uuid::Uuid;
-use tokio::io::{AsyncWriteExt, AsyncBufReadExt};
-use tracing::{info, warn};
+use tokio::io::{AsyncWriteExt, AsyncBufReadExt, AsyncReadExt};
+use tracing::{info, warn, Instrument};
+
+/// Lifetime of cached authentication results, in seconds
+const AUTH_CACHE_TTL: u64 = 3600;
+
+/// Nicknames looked up per ISON; the rest are ignored
+const MAX_ISON_NICKS: usize = 100;
+
+/// Nicknames looked up per USERHOST, as in RFC 2812
+const MAX_USERHOST_NICKS: usize = 5;
+
+/// Seconds between TLS certificate expiry checks
+const CERTIFICATE_CHECK_INTERVAL: u64 = 12 * 3600;
+
+/// How long a change to the certificate or key file settles before it is
+/// loaded, so a renewal that rewrites both files is picked up as one
+const TLS_RELOAD_SETTLE: std::time::Duration = std::time::Duration::from_millis(500);
+
+/// Commands only super servers (services) may send over a link
+const SERVICES_COMMANDS: [&str; 5] = ["SVSNICK", "SVSMODE", "SVSHOST", "SVSJOIN", "SVSPART"];
+
+/// A message read from a server link, with the name of the link
+type LinkMessage = (String, Message);
+
+/// What a link's I/O tasks need besides the stream itself
+struct LinkHandle {
+    /// Sender for replies generated while reading (ZIPSTART)
+    sender: tokio::sync::mpsc::UnboundedSender<Message>,
+    /// Where messages read from the link go to be handled
+    inbound: tokio::sync::mpsc::UnboundedSender<LinkMessage>,
+    /// Whether we dialed this link
+    outgoing: bool,
+    /// Compression counters, present when compression is offered
+    compression: Option<Arc<CompressionStats>>,
+}
+
+/// A running client or server listener
+struct Listener {
+    /// `address:port` as configured (see [`listener_key`])
+    key: String,
+    /// Address the socket is bound to
+    local_addr: std::net::SocketAddr,
+    /// Port settings, updated in place when a rehash changes them
+    settings: Arc<parking_lot::RwLock<crate::config::PortConfig>>,
+    /// Cancelled to stop accepting and close the socket
+    stop: CancellationToken,
+}
+
+impl Listener {
+    /// Whether `client` connected through this listener
+    fn accepted(&self, client: &Client) -> bool {
+        client.port == self.local_addr.port()
+            && (self.local_addr.ip().is_unspecified()
+                || client.local_addr.parse::<std::net::SocketAddr>().is_ok_and(|addr| addr.ip() == self.local_addr.ip()))
+    }
+}
+
+/// `address:port` for a listener, with IPv6 addresses bracketed
+fn listener_key(address: &str, port: u16) -> String {
+    if address.contains(':') {
+        format!("[{}]:{}", address, port)
+    } else {
+        format!("{}:{}", address, port)
+    }
+}
+
+/// TLS acceptors sharing the server certificate
+#[derive(Clone)]
+struct TlsAcceptors {
+    /// For ports that don't ask for a client certificate
+    default: TlsAcceptor,
+    /// For ports with `request_client_cert`
+    request_cert: TlsAcceptor,
+}
+
+/// Asks TLS clients for a certificate and accepts whatever they present
+///
+/// The certificate only identifies the client by its fingerprint (certfp),
+/// which oper blocks and services check, so it isn't verified against any
+/// CA. Clients without one connect as usual.
+struct OptionalClientCert;
+
+impl rustls::server::ClientCertVerifier for OptionalClientCert {
+    fn client_auth_mandatory(&self) -> bool {
+        false
+    }
+
+    fn client_auth_root_subjects(&self) -> &[rustls::DistinguishedName] {
+        &[]
+    }
+
+    fn verify_client_cert(
+        &self,
+        _end_entity: &Certificate,
+        _intermediates: &[Certificate],
+        _now: std::time::SystemTime,
+    ) -> std::result::Result<rustls::server::ClientCertVerified, rustls::Error> {
+        Ok(rustls::server::ClientCertVerified::assertion())
+    }
+}
+
+/// Server that should answer a query command
+enum QueryTarget {
+    /// No target, or the target is this server
+    Local,
+    /// Forward to this remote server
+    Remote(String),
+    /// Neither a known server nor a known user
+    Unknown(String),
+}
 
 /// Main IRC server
+///
+/// Every piece of shared state sits behind an `Arc`, so clones are cheap
+/// handles that background tasks use to run the full server logic.
+#[derive(Clone)]
 pub struct Server {
-    /// Server configuration
-    config: Config,
+    /// Server configuration, replaced wholesale by REHASH
+    config: Arc<parking_lot::RwLock<Arc<Config>>>,
     /// Module manager
     module_manager: Arc<RwLock<ModuleManager>>,
+    /// Constructors for the modules named in `modules.enabled_modules`
+    module_registry: ModuleRegistry,
     /// Connection handler
     connection_handler: Arc<RwLock<ConnectionHandler>>,
+    /// Outbound queues of local clients, for sending without the connection handler lock
+    client_senders: Arc<ClientSenders>,
+    /// Numeric reply templates, applied as replies are written to clients
+    numeric_replies: Arc<crate::NumericReplies>,
     /// Users by ID
     users: Arc<RwLock<HashMap<uuid::Uuid, User>>>,
     /// Users by nickname
     nick_to_id: Arc<RwLock<HashMap<String, uuid::Uuid>>>,
     /// Super servers (u-lined)
     super_servers: Arc<RwLock<HashMap<String, bool>>>,
+    /// Tree of known servers, used for routing and hop counts
+    topology: Arc<RwLock<NetworkTopology>>,
+    /// Most local users seen at once, for LUSERS
+    max_local_users: Arc<AtomicU32>,
     /// Database for users, servers, and history
     database: Arc<Database>,
     /// Broadcasting system
@@ -52,48 +181,56 @@ pub struct Server {
     statistics_manager: Arc<StatisticsManager>,
     /// MOTD manager for Message of the Day
     motd_manager: Arc<MotdManager>,
+    /// Network policy text sent by RULES
+    rules_manager: Arc<crate::RulesManager>,
     /// DNS and ident lookup service
     lookup_service: Arc<LookupService>,
     /// Rehash service for runtime configuration reloading
     rehash_service: Arc<RehashService>,
-    /// TLS acceptor (if enabled) - wrapped in Arc<RwLock> to allow runtime updates
-    tls_acceptor: Arc<RwLock<Option<TlsAcceptor>>>,
-    /// Replies configuration
-    #[allow(dead_code)]
-    replies_config: Option<crate::RepliesConfig>,
+    /// Shared ban registry enforced at connect and registration time
+    ban_manager: Arc<crate::BanManager>,
+    /// Per-client CTCP rate limiting
+    ctcp_flood_control: Arc<crate::CtcpFloodControl>,
+    /// Per-client nickname change rate limiting
+    nick_flood_control: Arc<crate::NickFloodControl>,
+    /// Audit trail of privileged operator actions, shared with modules
+    audit_trail: Arc<crate::AuditTrail>,
+    /// ISUPPORT tokens advertised by loaded modules
+    module_isupport: Arc<parking_lot::RwLock<Vec<String>>>,
+    /// Clients a module is keeping from registering, such as during SASL
+    registration_holds: Arc<parking_lot::RwLock<std::collections::HashSet<Uuid>>>,
+    /// Accounts clients logged in to before registering, such as with SASL
+    registration_accounts: Arc<parking_lot::RwLock<HashMap<Uuid, String>>>,
+    /// Clients modules asked to disconnect, such as after a D-line
+    exit_requests: Arc<parking_lot::Mutex<Vec<(Uuid, String)>>>,
+    /// Channels modules asked to join clients to, such as on OPER
+    join_requests: Arc<parking_lot::Mutex<Vec<(Uuid, String)>>>,
+    /// Capabilities clients enabled with CAP REQ, recorded by modules
+    client_capabilities: Arc<parking_lot::RwLock<HashMap<Uuid, HashSet<String>>>>,
+    /// Messages read from server links
+    link_messages: tokio::sync::mpsc::UnboundedSender<LinkMessage>,
+    /// Taken by the message processor when the server starts
+    link_message_receiver: Arc<parking_lot::Mutex<Option<tokio::sync::mpsc::UnboundedReceiver<LinkMessage>>>>,
+    /// TLS acceptor (if enabled), read by listeners for every handshake and
+    /// swapped atomically when the certificate is reloaded
+    tls_acceptor: Arc<ArcSwapOption<TlsAcceptors>>,
+    /// Tells the TLS file watcher to pick up changed settings after a rehash
+    tls_watch_reset: Arc<tokio::sync::Notify>,
+    /// Running listeners, in the order they started
+    listeners: Arc<parking_lot::Mutex<Vec<Listener>>>,
+    /// Address the admin HTTP API is bound to, once started
+    admin_api_addr: Arc<parking_lot::RwLock<Option<std::net::SocketAddr>>>,
+    /// Cancelled when the server shuts down, stopping listeners and background tasks
+    shutdown: CancellationToken,
+    /// Cancelled once shutdown has finished
+    stopped: CancellationToken,
+    /// Lifecycle events for embedders and modules
+    events: broadcast::Sender<ServerEvent>,
+    /// Authentication providers registered by embedders, shared with modules
+    auth_manager: Arc<AuthManager>,
 }
 
 impl Server {
-    /// Create a numeric reply using configurable replies if available
-    #[allow(dead_code)]
-    fn create_numeric_reply(&self, reply: NumericReply, target: &str, params: Vec<String>) -> Message {
-        if let Some(ref replies_config) = self.replies_config {
-            let mut param_map = std::collections::HashMap::new();
-            // Add common parameters
-            param_map.insert("nick".to_string(), target.to_string());
-            
-            // Add custom parameters from the params vector
-            for (i, param) in params.iter().enumerate() {
-                param_map.insert(format!("param{}", i), param.clone());
-            }
-            
-            // Create server info from main config
-            let server_info = crate::RepliesServerInfo {
-                name: self.config.server.name.clone(),
-                version: self.config.server.version.clone(),
-                description: self.config.server.description.clone(),
-                created: self.config.server.created.clone(),
-                admin_email: self.config.server.admin_email.clone(),
-                admin_location1: self.config.server.admin_location1.clone(),
-                admin_location2: self.config.server.admin_location2.clone(),
-            };
-            
-            reply.reply_with_config(target, &param_map, replies_config, &server_info)
-        } else {
-            reply.reply(target, params)
-        }
-    }
-
     /// Create a new server instance
     pub async fn new(config: Config) -> Self {
         Self::new_with_config_path(config, "config.toml".to_string()).await
@@ -102,15 +239,13 @@ impl Server {
     /// Create a new server instance with a specific config path
     pub async fn new_with_config_path(config: Config, config_path: String) -> Self {
         let (connection_handler, _) = ConnectionHandler::new();
+        connection_handler.replies().set(config.replies.clone(), (&config.server).into());
         
         // Initialize database
-        let database = Arc::new(Database::new(
-            config.database.max_history_size,
-            config.database.history_retention_days,
-        ));
+        let database = Arc::new(Database::from_config(&config.database));
         
         // Initialize broadcasting system
-        let broadcast_system = Arc::new(BroadcastSystem::new());
+        let broadcast_system = Arc::new(BroadcastSystem::with_client_senders(connection_handler.senders()));
         
         // Initialize network query manager
         let network_query_manager = Arc::new(NetworkQueryManager::new(
@@ -136,14 +271,20 @@ impl Server {
         let statistics_manager = Arc::new(StatisticsManager::new());
         
         // Initialize MOTD manager
-        let mut motd_manager = MotdManager::new();
-        if let Some(motd_file) = &config.server.motd_file {
-            if let Err(e) = motd_manager.load_motd(motd_file).await {
-                tracing::warn!("Failed to load MOTD file {}: {}", motd_file, e);
-            }
+        let motd_manager = MotdManager::new();
+        match MotdManager::read_config(&config.server) {
+            Ok(motd) => motd_manager.apply(motd).await,
+            Err(e) => tracing::warn!("Failed to load MOTD: {}", e),
         }
         let motd_manager = Arc::new(motd_manager);
         
+        // Initialize rules manager
+        let rules_manager = Arc::new(crate::RulesManager::new());
+        match Self::stage_rules(&config) {
+            Ok(lines) => rules_manager.set_lines(lines).await,
+            Err(e) => tracing::warn!("Failed to load rules: {}", e),
+        }
+        
         // Initialize lookup service
         let lookup_service = Arc::new(LookupService::new(
             config.security.enable_dns,
@@ -163,13 +304,45 @@ impl Server {
             config_path,
         ));
         
+        // Initialize ban manager shared with ban modules
+        let ban_manager = Arc::new(crate::BanManager::new());
+        if let Some(ban_file) = &config.security.ban_file {
+            if let Err(e) = ban_manager.set_store(Arc::new(crate::FileBanStore::new(ban_file))) {
+                tracing::warn!("Failed to load ban file {}: {}", ban_file, e);
+            }
+        }
+        
+        // Initialize audit trail shared with modules
+        let audit_trail = Arc::new(crate::AuditTrail::new(&config.security.audit));
+        let mut module_manager = ModuleManager::with_ban_manager(database.clone(), server_connections.clone(), ban_manager.clone());
+        module_manager.set_audit_trail(audit_trail.clone());
+        // One event bus for the server and its modules
+        let events = broadcast::channel(EVENT_CHANNEL_CAPACITY).0;
+        module_manager.set_event_sender(events.clone());
+        module_manager.set_client_senders(connection_handler.senders());
+        let auth_manager = Arc::new(AuthManager::new(AUTH_CACHE_TTL));
+        module_manager.set_auth_manager(auth_manager.clone());
+        module_manager.set_throttling_manager(throttling_manager.clone());
+        let module_isupport = module_manager.isupport_handle();
+        let registration_holds = module_manager.registration_holds_handle();
+        let registration_accounts = module_manager.registration_accounts_handle();
+        let exit_requests = module_manager.exit_requests_handle();
+        let join_requests = module_manager.join_requests_handle();
+        let client_capabilities = module_manager.client_capabilities_handle();
+        let (link_messages, link_message_receiver) = tokio::sync::mpsc::unbounded_channel();
+        
         Self {
-            config: config.clone(),
-            module_manager: Arc::new(RwLock::new(ModuleManager::new(database.clone(), server_connections.clone()))),
+            config: Arc::new(parking_lot::RwLock::new(Arc::new(config.clone()))),
+            module_manager: Arc::new(RwLock::new(module_manager)),
+            module_registry: ModuleRegistry::new(),
+            client_senders: connection_handler.senders(),
+            numeric_replies: connection_handler.replies(),
             connection_handler: Arc::new(RwLock::new(connection_handler)),
             users: Arc::new(RwLock::new(HashMap::new())),
             nick_to_id: Arc::new(RwLock::new(HashMap::new())),
             super_servers: Arc::new(RwLock::new(HashMap::new())),
+            topology: Arc::new(RwLock::new(NetworkTopology::new(config.server.name.clone()))),
+            max_local_users: Arc::new(AtomicU32::new(0)),
             database,
             broadcast_system,
             network_query_manager,
@@ -178,20 +351,39 @@ impl Server {
             throttling_manager,
             statistics_manager,
             motd_manager,
+            rules_manager,
             lookup_service,
             rehash_service,
-            tls_acceptor: Arc::new(RwLock::new(None)),
-            replies_config: config.replies.clone(),
+            ban_manager,
+            ctcp_flood_control: Arc::new(crate::CtcpFloodControl::new(&config.modules.ctcp)),
+            nick_flood_control: Arc::new(crate::NickFloodControl::new(&config.server)),
+            audit_trail,
+            module_isupport,
+            registration_holds,
+            registration_accounts,
+            exit_requests,
+            join_requests,
+            client_capabilities,
+            link_messages,
+            link_message_receiver: Arc::new(parking_lot::Mutex::new(Some(link_message_receiver))),
+            tls_acceptor: Arc::new(ArcSwapOption::empty()),
+            tls_watch_reset: Arc::new(tokio::sync::Notify::new()),
+            listeners: Arc::new(parking_lot::Mutex::new(Vec::new())),
+            admin_api_addr: Arc::new(parking_lot::RwLock::new(None)),
+            shutdown: CancellationToken::new(),
+            stopped: CancellationToken::new(),
+            events,
+            auth_manager,
         }
     }
     
     /// Initialize the server
     pub async fn init(&mut self) -> Result<()> {
         // Validate configuration
-        self.config.validate()?;
+        self.config().validate()?;
         
-        // Setup TLS if enabled
-        if self.config.security.tls.enabled {
+        // Setup TLS if enabled; an ACME certificate may not be issued yet
+        if self.config().security.tls.enabled && !Self::awaiting_acme_certificate(&self.config()) {
             self.setup_tls().await?;
         }
         
@@ -213,9 +405,19 @@ impl Server {
     
     /// Setup TLS configuration
     async fn setup_tls(&self) -> Result<()> {
-        let cert_file = self.config.security.tls.cert_file.as_ref()
+        let acceptor = Self::build_tls_acceptor(&self.config().security.tls)?;
+        
+        self.tls_acceptor.store(Some(Arc::new(acceptor)));
+
+        tracing::info!("TLS configuration loaded");
+        Ok(())
+    }
+    
+    /// Build the TLS acceptors from the certificate and key in `tls`
+    fn build_tls_acceptor(tls: &crate::config::TlsConfig) -> Result<TlsAcceptors> {
+        let cert_file = tls.cert_file.as_ref()
             .ok_or_else(|| Error::Config("TLS certificate file not specified".to_string()))?;
-        let key_file = self.config.security.tls.key_file.as_ref()
+        let key_file = tls.key_file.as_ref()
             .ok_or_else(|| Error::Config("TLS key file not specified".to_string()))?;
         
         // Load certificate
@@ -226,25 +428,27 @@ impl Server {
         let tls_config = ServerConfig::builder()
             .with_safe_defaults()
             .with_no_client_auth()
-            .with_single_cert(cert_chain, private_key)
+            .with_single_cert(cert_chain.clone(), private_key.clone())
             .map_err(|e| Error::Tls(e))?;
+        let request_cert_config = ServerConfig::builder()
+            .with_safe_defaults()
+            .with_client_cert_verifier(Arc::new(OptionalClientCert))
+            .with_single_cert(cert_chain, private_key)?;
         
         // Configure cipher suites if specified
-        if !self.config.security.tls.cipher_suites.is_empty() {
+        if !tls.cipher_suites.is_empty() {
             // For now, we'll use the safe defaults since rustls handles cipher suite selection
             // The configured cipher suites are logged for reference
-            tracing::info!("Configured cipher suites: {:?}", self.config.security.tls.cipher_suites);
+            tracing::info!("Configured cipher suites: {:?}", tls.cipher_suites);
         }
         
         // Log TLS version configuration
-        tracing::info!("TLS version configured: {}", self.config.security.tls.version);
-
-        // Update the TLS acceptor - acquire write lock to update shared reference
-        let mut tls_acceptor = self.tls_acceptor.write().await;
-        *tls_acceptor = Some(TlsAcceptor::from(Arc::new(tls_config)));
-
-        tracing::info!("TLS configuration loaded");
-        Ok(())
+        tracing::info!("TLS version configured: {}", tls.version);
+        
+        Ok(TlsAcceptors {
+            default: TlsAcceptor::from(Arc::new(tls_config)),
+            request_cert: TlsAcceptor::from(Arc::new(request_cert_config)),
+        })
     }
     
     /// Load super servers from configuration
@@ -252,13 +456,13 @@ impl Server {
         let mut super_servers = self.super_servers.write().await;
         
         // Load explicitly configured super servers
-        for super_server in &self.config.network.super_servers {
+        for super_server in &self.config().network.super_servers {
             super_servers.insert(super_server.name.clone(), true);
             tracing::info!("Loaded super server: {}", super_server.name);
         }
         
         // Automatically create super servers from enabled services
-        for service in &self.config.services.services {
+        for service in &self.config().services.services {
             if service.enabled {
                 // Check if this service is already configured as a super server
                 if !super_servers.contains_key(&service.name) {
@@ -278,7 +482,7 @@ impl Server {
                         tls_ca_file: service.tls_ca_file.clone(),
                         privileges: vec!["all".to_string()], // Services get all privileges
                     };
-                    self.config.network.super_servers.push(super_server_config);
+                    Arc::make_mut(&mut self.config.write()).network.super_servers.push(super_server_config);
                 } else {
                     tracing::debug!("Service {} already configured as super server", service.name);
                 }
@@ -290,7 +494,7 @@ impl Server {
     
     /// Initialize authentication system
     async fn initialize_authentication(&mut self) -> Result<()> {
-        if let Some(auth_config) = &self.config.authentication {
+        if let Some(auth_config) = &self.config().authentication {
             if !auth_config.enabled {
                 tracing::info!("Authentication is disabled");
                 return Ok(());
@@ -331,7 +535,7 @@ impl Server {
         tracing::info!("Initializing services authentication");
         
         // Find the first enabled service
-        if let Some(service) = self.config.services.services.iter().find(|s| s.enabled) {
+        if let Some(service) = self.config().services.services.iter().find(|s| s.enabled) {
             tracing::info!("Using service '{}' (type: {}) for authentication", 
                          service.name, service.service_type);
             
@@ -359,38 +563,16 @@ impl Server {
     
     /// Load modules from configuration
     async fn load_modules(&mut self) -> Result<()> {
-        let _module_manager = self.module_manager.write().await;
-        
-        for module_name in &self.config.modules.enabled_modules {
-            match module_name.as_str() {
-                "channel" => {
-                    // Load channel module
-                    // let channel_module = rustircd_modules::ChannelModule::new(); // Commented out - modules crate not available in core
-                    // module_manager.load_module(Box::new(channel_module)).await?; // Commented out - modules crate not available
-                    tracing::info!("Loaded channel module");
-                    
-                    // Register channel burst extension
-                    self.register_channel_burst_extension().await?;
-                }
-                "ircv3" => {
-                    // Load IRCv3 module
-                    // let ircv3_module = rustircd_modules::Ircv3Module::new(); // Commented out - modules crate not available
-                    // module_manager.load_module(Box::new(ircv3_module)).await?; // Commented out - modules crate not available
-                    tracing::info!("Loaded IRCv3 module");
-                }
-                "optional" => {
-                    // Load optional commands module
-                    // let optional_module = rustircd_modules::OptionalModule::new(); // Commented out - modules crate not available
-                    // module_manager.load_module(Box::new(optional_module)).await?; // Commented out - modules crate not available
-                    tracing::info!("Loaded optional commands module");
-                }
-                "throttling" => {
-                    // Load throttling module
-                    // let throttling_module = rustircd_modules::ThrottlingModule::new(self.config.modules.throttling.clone()); // Commented out - modules crate not available
-                    // module_manager.load_module(Box::new(throttling_module)).await?; // Commented out - modules crate not available
-                    tracing::info!("Loaded throttling module");
+        let config = self.config();
+        let mut module_manager = self.module_manager.write().await;
+        
+        for module_name in &config.modules.enabled_modules {
+            match self.module_registry.create(module_name, &config) {
+                Some(module) => {
+                    module_manager.load_module(module?).await?;
+                    tracing::info!("Loaded {} module", module_name);
                 }
-                _ => {
+                None => {
                     tracing::warn!("Unknown module: {}", module_name);
                 }
             }
@@ -403,11 +585,15 @@ impl Server {
     /// Start the server
     pub async fn start(&mut self) -> Result<()> {
         tracing::info!("Starting IRC server with {} configured ports", 
-                      self.config.connection.ports.len());
+                      self.config().connection.ports.len());
         
         // Start listeners for all configured ports
-        for port_config in &self.config.connection.ports {
-            self.start_port_listener(port_config).await?;
+        let config = self.config();
+        for port_config in &config.connection.ports {
+            for address in config.get_bind_addresses_for_port(port_config) {
+                let listener = Self::bind_listener(&address, port_config).await?;
+                self.start_port_listener(&address, port_config, listener)?;
+            }
         }
         
         // Start message processing loop
@@ -422,48 +608,218 @@ impl Server {
         // Start automatic reconnection task
         self.start_auto_reconnect_task()?;
         
+        // Start server link PING and dead-link detection
+        self.start_link_ping_task();
+        
+        // Start remote MOTD refresh
+        self.start_motd_refresh_task();
+        
+        // Start TLS certificate reloads, expiry warnings and ACME renewal
+        self.start_tls_watch_task();
+        self.start_certificate_expiry_task();
+        #[cfg(feature = "acme")]
+        self.start_acme_task();
+        
+        // Start the admin HTTP API
+        #[cfg(feature = "admin-api")]
+        self.start_admin_api()?;
+        
+        // Start ban expiry task
+        self.ban_manager.start_expiry_task(tokio::time::Duration::from_secs(60));
+        
+        self.publish(ServerEvent::Started);
+        Ok(())
+    }
+    
+    /// Addresses the started listeners are bound to
+    ///
+    /// Listeners configured with port 0 appear here with the port the OS
+    /// assigned, so tests and embedders can find them.
+    pub fn listen_addrs(&self) -> Vec<std::net::SocketAddr> {
+        self.listeners.lock().iter().map(|listener| listener.local_addr).collect()
+    }
+    
+    /// Address the admin HTTP API is bound to
+    ///
+    /// `None` unless the daemon was built with the `admin-api` feature and
+    /// `[admin_api]` enables it.
+    pub fn admin_api_addr(&self) -> Option<std::net::SocketAddr> {
+        *self.admin_api_addr.read()
+    }
+    
+    /// Run until the server is told to stop
+    ///
+    /// SIGTERM and SIGINT shut the server down and SIGHUP rehashes the
+    /// configuration. Also returns when an embedder calls `shutdown`.
+    pub async fn run_until_shutdown(&self) -> Result<()> {
+        #[cfg(unix)]
+        {
+            use tokio::signal::unix::{signal, SignalKind};
+            let mut terminate = signal(SignalKind::terminate())?;
+            let mut hangup = signal(SignalKind::hangup())?;
+            loop {
+                tokio::select! {
+                    _ = self.shutdown.cancelled() => return Ok(()),
+                    _ = tokio::signal::ctrl_c() => return self.shutdown("Received SIGINT").await,
+                    _ = terminate.recv() => return self.shutdown("Received SIGTERM").await,
+                    _ = hangup.recv() => self.rehash_on_signal().await,
+                }
+            }
+        }
+        #[cfg(not(unix))]
+        {
+            tokio::select! {
+                _ = self.shutdown.cancelled() => Ok(()),
+                _ = tokio::signal::ctrl_c() => self.shutdown("Received interrupt").await,
+            }
+        }
+    }
+    
+    /// Shut the server down
+    ///
+    /// Stops accepting connections, tells linked servers and local clients
+    /// why, and flushes persistent state. Only the first call does anything.
+    pub async fn shutdown(&self, reason: &str) -> Result<()> {
+        if self.shutdown.is_cancelled() {
+            return Ok(());
+        }
+        self.shutdown.cancel();
+        info!("Shutting down: {}", reason);
+        self.publish(ServerEvent::ShuttingDown { reason: reason.to_string() });
+        
+        let squit = Message::new(
+            MessageType::ServerQuit,
+            vec![self.config().server.name.clone(), reason.to_string()],
+        );
+        if let Err(e) = self.server_connections.broadcast_to_servers(squit).await {
+            warn!("Failed to send SQUIT to linked servers: {}", e);
+        }
+        
+        {
+            let mut connection_handler = self.connection_handler.write().await;
+            let client_ids: Vec<uuid::Uuid> = connection_handler.iter_clients().map(|(id, _)| *id).collect();
+            for client_id in client_ids {
+                if let Some(client) = connection_handler.remove_client(&client_id) {
+                    let host = client.get_user().map(|user| user.host.clone()).unwrap_or_else(|| client.remote_addr.clone());
+                    let _ = client.send(Message::new(
+                        MessageType::Error,
+                        vec![format!("Closing Link: {} (Server shutting down: {})", host, reason)],
+                    ));
+                }
+            }
+        }
+        
+        if let Err(e) = self.database.flush() {
+            warn!("Failed to save database state: {}", e);
+        }
+        self.audit_trail.flush();
+        
+        // Give the writer tasks a moment to deliver the goodbyes
+        tokio::time::sleep(tokio::time::Duration::from_millis(500)).await;
+        info!("Shutdown complete");
+        self.publish(ServerEvent::Stopped);
+        self.stopped.cancel();
         Ok(())
     }
     
+    /// Shut the server down with a generic reason
+    pub async fn stop(&self) -> Result<()> {
+        self.shutdown("Server stopped").await
+    }
+    
+    /// Wait until shutdown has finished
+    pub async fn join(&self) {
+        self.stopped.cancelled().await;
+    }
+    
+    /// Whether `shutdown` has been called
+    pub fn is_shutting_down(&self) -> bool {
+        self.shutdown.is_cancelled()
+    }
+    
+    /// Subscribe to server events
+    pub fn subscribe(&self) -> broadcast::Receiver<ServerEvent> {
+        self.events.subscribe()
+    }
+    
+    /// Send an event to subscribers, if there are any
+    pub(crate) fn publish(&self, event: ServerEvent) {
+        let _ = self.events.send(event);
+    }
+    
+    /// Authentication providers available to modules
+    pub fn auth_manager(&self) -> Arc<AuthManager> {
+        self.auth_manager.clone()
+    }
+    
+    /// Reload configuration and MOTD after SIGHUP
+    async fn rehash_on_signal(&self) {
+        info!("Received SIGHUP, rehashing");
+        let _ = self.send_snotice(SnoMask::General, "Got signal SIGHUP, reloading configuration").await;
+        if let Err(e) = self.rehash(RehashSection::Config).await {
+            warn!("Rehash failed: {}", e);
+            let _ = self.send_snotice(SnoMask::General, &format!("Rehash failed: {}", e)).await;
+        }
+    }
+    
     /// Start connection timeout checker
+    ///
+    /// PINGs idle connections, disconnects the ones that stopped answering
+    /// and drops clients that haven't registered within
+    /// `connection.connection_timeout` seconds.
     async fn start_timeout_checker(&self) -> Result<()> {
-        let connection_handler = self.connection_handler.clone();
+        let server = self.clone();
         
         tokio::spawn(async move {
             loop {
-                // Check every 30 seconds
-                tokio::time::sleep(tokio::time::Duration::from_secs(30)).await;
+                // Check every 10 seconds
+                tokio::select! {
+                    _ = server.shutdown.cancelled() => break,
+                    _ = tokio::time::sleep(tokio::time::Duration::from_secs(10)) => {}
+                }
                 
-                let mut handler = connection_handler.write().await;
-                let mut timed_out_clients = Vec::new();
+                let registration_timeout = std::time::Duration::from_secs(server.config().connection.connection_timeout);
+                let mut handler = server.connection_handler.write().await;
+                handler.sync_activity();
+                let mut expired = Vec::new();
+                let mut to_ping = Vec::new();
                 
                 // Find timed out clients
                 for (client_id, client) in handler.iter_clients() {
-                    if client.timing.is_timed_out() {
-                        timed_out_clients.push(*client_id);
+                    if !client.is_registered() && client.connection_type == crate::client::ConnectionType::Client {
+                        if client.registration.is_expired(registration_timeout) {
+                            tracing::info!("Client {} did not register in time", client_id);
+                            expired.push((*client_id, "Registration timed out".to_string()));
+                        }
+                    } else if client.timing.is_timed_out() {
                         tracing::info!("Client {} timed out (no PONG received)", client_id);
+                        let silent = client.timing.time_since_activity().as_secs();
+                        expired.push((*client_id, format!("Ping timeout: {} seconds", silent)));
                     } else if client.timing.should_send_ping() {
-                        // Send PING if it's time
+                        to_ping.push(*client_id);
+                    }
+                }
+                
+                // The PING is recorded so the PONG can be timed for lag
+                for client_id in to_ping {
+                    if let Some(client) = handler.get_client_mut(&client_id) {
                         let ping_msg = Message::new(
                             MessageType::Ping,
-                            vec![chrono::Utc::now().timestamp().to_string()],
+                            vec![server.config().server.name.clone()],
                         );
                         if let Err(e) = client.send(ping_msg) {
                             tracing::warn!("Failed to send PING to client {}: {}", client_id, e);
                         } else {
+                            client.record_ping_sent();
                             tracing::debug!("Sent PING to client {}", client_id);
                         }
                     }
                 }
+                drop(handler);
                 
-                // Disconnect timed out clients
-                for client_id in timed_out_clients {
-                    if let Some(client) = handler.remove_client(&client_id) {
-                        tracing::info!("Disconnecting timed out client: {}", client_id);
-                        let _ = client.send(Message::new(
-                            MessageType::Custom("ERROR".to_string()),
-                            vec!["Connection timeout".to_string()],
-                        ));
+                for (client_id, reason) in expired {
+                    if let Err(e) = server.exit_client(client_id, &reason, true).await {
+                        tracing::warn!("Failed to disconnect client {}: {}", client_id, e);
                     }
                 }
             }
@@ -472,9 +828,71 @@ impl Server {
         Ok(())
     }
     
+    /// Serve the admin HTTP API if `[admin_api]` enables it
+    #[cfg(feature = "admin-api")]
+    fn start_admin_api(&self) -> Result<()> {
+        let config = self.config().admin_api.clone();
+        if !config.enabled {
+            return Ok(());
+        }
+        if config.token_hash.is_empty() {
+            return Err(Error::Config("admin_api.token_hash must be set to serve the admin API".to_string()));
+        }
+        
+        let listener = std::net::TcpListener::bind((config.bind_address.as_str(), config.port))?;
+        listener.set_nonblocking(true)?;
+        let addr = listener.local_addr()?;
+        let api = axum::Server::from_tcp(listener)
+            .map_err(|e| Error::Server(format!("Failed to start admin API on {}: {}", addr, e)))?
+            .serve(crate::admin_api::router(self.clone()).into_make_service())
+            .with_graceful_shutdown(self.shutdown.clone().cancelled_owned());
+        tokio::spawn(async move {
+            if let Err(e) = api.await {
+                tracing::warn!("Admin API stopped: {}", e);
+            }
+        });
+        *self.admin_api_addr.write() = Some(addr);
+        tracing::info!("Admin API listening on http://{}", addr);
+        Ok(())
+    }
+    
+    /// Start the task that PINGs server links and splits the ones that stop answering
+    fn start_link_ping_task(&self) {
+        let interval = self.config().netsplit.link_ping_interval;
+        let dead_timeout = self.config().netsplit.link_dead_timeout;
+        if interval == 0 {
+            tracing::info!("Server link PING is disabled");
+            return;
+        }
+        
+        let server = self.clone();
+        tokio::spawn(async move {
+            loop {
+                tokio::select! {
+                    _ = server.shutdown.cancelled() => break,
+                    _ = tokio::time::sleep(tokio::time::Duration::from_secs(interval.min(15))) => {}
+                }
+                
+                let dead_links = server.server_connections
+                    .ping_links(&server.config().server.name, interval, dead_timeout)
+                    .await;
+                for server_name in dead_links {
+                    let reason = format!("Ping timeout: {} seconds", dead_timeout);
+                    tracing::warn!("Server {} stopped answering PING, splitting link", server_name);
+                    let error = Message::new(MessageType::Error, vec![format!("Closing Link: {} ({})", server_name, reason)]);
+                    let _ = server.server_connections.send_to_server(&server_name, error).await;
+                    let _ = server.send_snotice(SnoMask::NetJoins, &format!("Link with {} timed out: {}", server_name, reason)).await;
+                    if let Err(e) = server.handle_server_link_closed(&server_name, &reason).await {
+                        tracing::warn!("Failed to split dead link {}: {}", server_name, e);
+                    }
+                }
+            }
+        });
+    }
+    
     /// Start split cleanup task to remove users that have been in netsplit for too long
     async fn start_split_cleanup_task(&self) -> Result<()> {
-        let grace_period = self.config.netsplit.split_user_grace_period;
+        let grace_period = self.config().netsplit.split_user_grace_period;
         
         if grace_period == 0 {
             tracing::info!("Split user grace period is disabled");
@@ -484,11 +902,15 @@ impl Server {
         let database = self.database.clone();
         let nick_to_id = self.nick_to_id.clone();
         let users = self.users.clone();
+        let shutdown = self.shutdown.clone();
         
         tokio::spawn(async move {
             loop {
                 // Check every 30 seconds
-                tokio::time::sleep(tokio::time::Duration::from_secs(30)).await;
+                tokio::select! {
+                    _ = shutdown.cancelled() => break,
+                    _ = tokio::time::sleep(tokio::time::Duration::from_secs(30)) => {}
+                }
                 
                 let mut users_to_remove = Vec::new();
                 let now = chrono::Utc::now();
@@ -534,59 +956,86 @@ impl Server {
         Ok(())
     }
     
-    /// Start automatic reconnection task for disconnected servers
+    /// Start the autoconnect task
+    ///
+    /// Links flagged `autoconnect` are dialed on startup and, after they drop,
+    /// redialed with exponential backoff between `reconnect_delay_base` and
+    /// `reconnect_delay_max`. Operators see each attempt and failure. Links
+    /// come and go with rehashes, so the task runs even when none are set.
     fn start_auto_reconnect_task(&self) -> Result<()> {
-        if !self.config.netsplit.auto_reconnect {
-            tracing::info!("Automatic reconnection is disabled");
-            return Ok(());
+        if !self.config().network.links.iter().any(|link| link.autoconnect) {
+            tracing::info!("No autoconnect links configured");
         }
         
         let server_connections = self.server_connections.clone();
-        let config = self.config.clone();
-        // Note: Cannot use Arc::new(self.clone()) - would need to restructure for full auto-reconnect
-        // For now, we'll do basic checking without calling connect_to_server
+        let database = self.database.clone();
+        let client_senders = self.client_senders.clone();
+        let link_messages = self.link_messages.clone();
+        let shutdown = self.shutdown.clone();
         
         tokio::spawn(async move {
             loop {
-                // Check every 30 seconds for servers that need reconnection
-                tokio::time::sleep(tokio::time::Duration::from_secs(30)).await;
-                
-                // Get configured server links
-                let configured_servers: Vec<_> = config.network.links.iter()
-                    .filter(|link| link.outgoing) // Only auto-reconnect outgoing connections
-                    .collect();
-                
-                for link in configured_servers {
-                    // Check if this server is currently connected
-                    let is_connected = server_connections.get_connection(&link.name).await.is_some();
+                for link in server_connections.due_autoconnects().await {
+                    let (attempt, next_delay) = server_connections.record_autoconnect_attempt(&link.name);
+                    let notice = format!("Autoconnecting to {} ({}:{}), attempt {}", link.name, link.hostname, link.port, attempt);
+                    Self::snotice_opers(&database, &client_senders, SnoMask::NetJoins, &notice);
                     
-                    if !is_connected {
-                        // Server is disconnected - log for monitoring
-                        // TODO: Implement actual reconnection logic
-                        // This would require refactoring Server to be Arc-wrapped at creation
-                        // or using a separate reconnection service
-                        tracing::info!(
-                            "Server {} is disconnected and configured for auto-reconnect ({}:{})",
-                            link.name, link.hostname, link.port
-                        );
-                        tracing::debug!(
-                            "Auto-reconnection framework active - manual CONNECT or service restart required for actual reconnection"
-                        );
+                    let config = server_connections.config();
+                    if let Err(e) = Self::dial_server(&server_connections, &config, &link_messages, &link.name, link.port).await {
+                        tracing::warn!("Autoconnect to {} failed: {}", link.name, e);
+                        let notice = format!("Autoconnect to {} failed: {} (retrying in {}s)", link.name, e, next_delay);
+                        Self::snotice_opers(&database, &client_senders, SnoMask::NetJoins, &notice);
                     }
                 }
+                tokio::select! {
+                    _ = shutdown.cancelled() => break,
+                    _ = tokio::time::sleep(tokio::time::Duration::from_secs(5)) => {}
+                }
             }
         });
         
         Ok(())
     }
     
-    /// Start a listener for a specific port configuration
-    async fn start_port_listener(&self, port_config: &crate::config::PortConfig) -> Result<()> {
-        let listener = TcpListener::bind(
-            format!("{}:{}", self.config.connection.bind_address, port_config.port)
-        ).await?;
+    /// Bind the socket for a port on one of its addresses
+    ///
+    /// The port's `bind_address_v6` is bound IPv6-only, so it can share the
+    /// port with an IPv4 wildcard address.
+    async fn bind_listener(address: &str, port_config: &crate::config::PortConfig) -> Result<TcpListener> {
+        let failed = |e: std::io::Error| {
+            Error::Config(format!("Failed to listen on {}: {}", listener_key(address, port_config.port), e))
+        };
+        if port_config.bind_address_v6.as_deref() != Some(address) {
+            return TcpListener::bind((address, port_config.port)).await.map_err(failed);
+        }
+        
+        let ip: std::net::Ipv6Addr = address.parse()
+            .map_err(|_| Error::Config(format!("Invalid IPv6 bind address: {}", address)))?;
+        let socket = socket2::Socket::new(socket2::Domain::IPV6, socket2::Type::STREAM, Some(socket2::Protocol::TCP))
+            .map_err(failed)?;
+        socket.set_only_v6(true).map_err(failed)?;
+        socket.set_reuse_address(true).map_err(failed)?;
+        socket.set_nonblocking(true).map_err(failed)?;
+        socket.bind(&std::net::SocketAddr::from((ip, port_config.port)).into()).map_err(failed)?;
+        socket.listen(1024).map_err(failed)?;
+        TcpListener::from_std(socket.into()).map_err(failed)
+    }
+    
+    /// Start accepting connections on a listener bound to `address`,
+    /// returning its address
+    fn start_port_listener(&self, address: &str, port_config: &crate::config::PortConfig, listener: TcpListener) -> Result<std::net::SocketAddr> {
+        let local_addr = listener.local_addr()?;
+        let settings = Arc::new(parking_lot::RwLock::new(port_config.clone()));
+        let stop = self.shutdown.child_token();
+        self.listeners.lock().push(Listener {
+            key: listener_key(address, port_config.port),
+            local_addr,
+            settings: settings.clone(),
+            stop: stop.clone(),
+        });
         
-        let port = port_config.port;
+        // Port 0 asks the OS for a free port; log the one it picked
+        let port = local_addr.port();
         let connection_type = port_config.connection_type.clone();
         let tls_enabled = port_config.tls;
         // Clone the Arc reference to the shared TLS acceptor
@@ -601,16 +1050,40 @@ impl Server {
         let throttling_manager = self.throttling_manager.clone();
         let statistics_manager = self.statistics_manager.clone();
         let lookup_service = self.lookup_service.clone();
+        let ban_manager = self.ban_manager.clone();
+        let config = self.config.clone();
+        let server = self.clone();
         tokio::spawn(async move {
             loop {
-                match listener.accept().await {
+                let accepted = tokio::select! {
+                    _ = stop.cancelled() => break,
+                    accepted = listener.accept() => accepted,
+                };
+                match accepted {
                     Ok((mut stream, addr)) => {
+                        // Dual-stack sockets report IPv4 clients as ::ffff:a.b.c.d;
+                        // hosts, bans and limits all use the plain IPv4 address
+                        let addr = std::net::SocketAddr::new(addr.ip().to_canonical(), addr.port());
+                        // Rehash may have changed the port's settings
+                        let (connection_type, tls_enabled, request_client_cert) = {
+                            let settings = settings.read();
+                            (settings.connection_type.clone(), settings.tls, settings.request_client_cert)
+                        };
                         // Determine connection type based on port configuration
                         let is_client_connection = matches!(connection_type, crate::config::PortConnectionType::Client | crate::config::PortConnectionType::Both);
                         let is_server_connection = matches!(connection_type, crate::config::PortConnectionType::Server | crate::config::PortConnectionType::Both);
 
                         // Check throttling for client connections
                         if is_client_connection && !is_server_connection {
+                            // Reject banned addresses before doing any further work
+                            if let Some(ban) = ban_manager.find_ban(&crate::BanQuery::from_ip(addr.ip())) {
+                                tracing::info!("Connection from {} rejected by {} {}", addr, ban.ban_type, ban.mask);
+                                let error = format!("ERROR :Closing Link: {} ({})\r\n", addr.ip(), ban_manager.ban_reason(&ban));
+                                let _ = stream.write_all(error.as_bytes()).await;
+                                let _ = stream.shutdown().await;
+                                continue;
+                            }
+
                             match throttling_manager.check_connection_allowed(addr.ip()).await {
                                 Ok(allowed) => {
                                     if !allowed {
@@ -626,24 +1099,60 @@ impl Server {
                                 }
                             }
 
+                            if let Some(reason) = server.check_clone_limits(addr.ip()).await {
+                                let error = format!("ERROR :Closing Link: {} ({})\r\n", addr.ip(), reason);
+                                let _ = stream.write_all(error.as_bytes()).await;
+                                let _ = stream.shutdown().await;
+                                continue;
+                            }
+
                             // Record connection statistics
                             statistics_manager.record_connection().await;
                         } else if is_server_connection && !is_client_connection {
                             // Record server connection statistics
                             statistics_manager.record_server_connection().await;
+                            
+                            // Plain server ports run the link handshake directly
+                            if !tls_enabled {
+                                let server = server.clone();
+                                tokio::spawn(async move {
+                                    if let Err(e) = server.handle_incoming_server_connection(stream, addr).await {
+                                        tracing::debug!("Server link from {} failed: {}", addr, e);
+                                    }
+                                });
+                                continue;
+                            }
                         }
 
-                        // Get the current TLS acceptor (if TLS is enabled for this port)
+                        // Take the acceptor current at accept time, so a reloaded
+                        // certificate applies to the next handshake
                         let tls_acceptor = if tls_enabled {
-                            // Acquire read lock and clone the current TLS acceptor
-                            tls_acceptor_ref.read().await.clone()
+                            tls_acceptor_ref.load_full().map(|acceptors| {
+                                if request_client_cert { acceptors.request_cert.clone() } else { acceptors.default.clone() }
+                            })
                         } else {
                             None
                         };
 
+                        let ip = addr.ip().to_string();
+                        let class = config.read().connection_class(&ip, &ip);
                         let mut conn_handler = connection_handler.write().await;
-                        if let Err(e) = conn_handler.handle_connection_with_type(stream, addr, tls_acceptor, is_client_connection, is_server_connection, Some(&lookup_service)).await {
-                            tracing::error!("Error handling connection from {}: {}", addr, e);
+                        match conn_handler.handle_connection_with_type(stream, addr, tls_acceptor, is_client_connection, is_server_connection, Some(&class)).await {
+                            // Lookups run on their own task so the next connection
+                            // is accepted straight away; registration waits for them
+                            Ok(client_id) if is_client_connection && !is_server_connection
+                                && lookup_service.has_client_lookups(&config.read().security.dnsbl) =>
+                            {
+                                if let Some(client) = conn_handler.get_client_mut(&client_id) {
+                                    client.registration.lookups_pending = true;
+                                }
+                                let server = server.clone();
+                                tokio::spawn(async move {
+                                    server.run_client_lookups(client_id).await
+                                }.instrument(tracing::debug_span!("irc.accept", %client_id, addr = %addr)));
+                            }
+                            Ok(_) => {}
+                            Err(e) => tracing::error!("Error handling connection from {}: {}", addr, e),
                         }
                     }
                     Err(e) => {
@@ -653,49 +1162,164 @@ impl Server {
             }
         });
         
-        Ok(())
+        Ok(local_addr)
     }
     
-    /// Start message processing loop
-    /// Note: Message processing is currently handled directly in handle_client_message
-    /// and through the module system. This method is kept for potential future use
-    /// if we want to implement a dedicated message queue/processing thread.
+    /// Start the task that handles every message read from a connection
     async fn start_message_processor(&self) -> Result<()> {
-        // Message processing is currently handled synchronously in handle_client_message
-        // No async processing loop needed at this time
+        let mut connection_handler = self.connection_handler.write().await;
+        let (Some(mut messages), Some(mut closed)) = (connection_handler.take_message_receiver(), connection_handler.take_closed_receiver()) else {
+            return Err(Error::Server("Message processor is already running".to_string()));
+        };
+        drop(connection_handler);
+        
+        let server = self.clone();
+        tokio::spawn(async move {
+            loop {
+                // Messages first, so whatever a connection sent before closing is handled
+                let received = tokio::select! {
+                    biased;
+                    _ = server.shutdown.cancelled() => break,
+                    received = messages.recv() => received,
+                    Some((client_id, reason)) = closed.recv() => {
+                        if let Err(e) = server.exit_client(client_id, &reason, true).await {
+                            tracing::debug!("Error cleaning up after {}: {}", client_id, e);
+                        }
+                        continue;
+                    }
+                };
+                let Some((client_id, message, recvq_charge, span)) = received else {
+                    break;
+                };
+                if let Err(e) = server.handle_message(client_id, message).instrument(span).await {
+                    tracing::debug!("Error handling message from {}: {}", client_id, e);
+                }
+                // The connection may read more once its lines are handled
+                drop(recvq_charge);
+            }
+        });
+        
+        // Modules rename users from their own tasks, such as NickServ enforcement
+        let Some(mut nick_changes) = self.module_manager.read().await.take_nick_change_receiver() else {
+            return Err(Error::Server("Message processor is already running".to_string()));
+        };
+        let server = self.clone();
+        tokio::spawn(async move {
+            loop {
+                let (client_id, nick) = tokio::select! {
+                    _ = server.shutdown.cancelled() => break,
+                    received = nick_changes.recv() => match received {
+                        Some(received) => received,
+                        None => break,
+                    },
+                };
+                if let Err(e) = server.apply_forced_nick(client_id, &nick).await {
+                    tracing::debug!("Error renaming {} to {}: {}", client_id, nick, e);
+                }
+            }
+        });
+        
+        // Server links get their own task so a slow link doesn't hold up clients
+        let Some(mut link_messages) = self.link_message_receiver.lock().take() else {
+            return Err(Error::Server("Message processor is already running".to_string()));
+        };
+        let server = self.clone();
+        tokio::spawn(async move {
+            loop {
+                let (server_name, message) = tokio::select! {
+                    _ = server.shutdown.cancelled() => break,
+                    received = link_messages.recv() => match received {
+                        Some(received) => received,
+                        None => break,
+                    },
+                };
+                if let Err(e) = server.handle_server_message(&server_name, message).await {
+                    tracing::debug!("Error handling message from server {}: {}", server_name, e);
+                }
+            }
+        });
+        
         Ok(())
     }
     
     /// Handle a message from a client
     pub async fn handle_message(&self, client_id: uuid::Uuid, message: Message) -> Result<()> {
         // Record message statistics (from local client, is_remote = false)
-        let command_name = match &message.command {
-            MessageType::Custom(cmd) => cmd.as_str(),
-            _ => "UNKNOWN",
-        };
-        self.statistics_manager.record_message_received(command_name, message.to_string().len(), false).await;
+        let command_name = message.command.to_string().to_uppercase();
+        self.statistics_manager.record_message_received(&command_name, message.to_string().len(), false).await;
         
-        let connection_handler = self.connection_handler.read().await;
+        // Any line shows the connection is alive; anything but keepalives ends idleness
+        self.client_senders.touch(&client_id);
+        if !matches!(message.command, MessageType::Ping | MessageType::Pong) {
+            self.database.update_user_activity(&client_id);
+        }
+        
+        // Handlers change users through the database (OPER, modes), so refresh
+        // the connection's copy before anything checks it
+        let mut connection_handler = self.connection_handler.write().await;
+        if let (Some(user), Some(client)) = (self.database.get_user(&client_id), connection_handler.get_client_mut(&client_id)) {
+            client.set_user(user);
+        }
+        let connection_handler = connection_handler.downgrade();
         let client = connection_handler.get_client(&client_id)
             .ok_or_else(|| Error::User("Client not found".to_string()))?;
+        let registering = !client.is_registered() && client.connection_type == crate::client::ConnectionType::Client;
+        tracing::debug!(%client_id, nick = client.nickname().unwrap_or("*"), command = %command_name, "Command received");
+        
+        // Registration and parameter checks for core commands, ahead of modules
+        if let Some(refusal) = crate::core_command_spec(&message.command).and_then(|spec| spec.check(client, &message)) {
+            let _ = client.send(refusal);
+            return Ok(());
+        }
         
         // Process through modules first
         let mut module_manager = self.module_manager.write().await;
-        match module_manager.handle_message_with_server(client, &message, Some(self)).await? {
-            ModuleResult::HandledStop => return Ok(()),
+        let result = module_manager.handle_message_with_server(client, &message, Some(self)).await?;
+        drop(module_manager);
+        drop(connection_handler);
+        
+        // CAP only delays registration when a module actually negotiates it
+        let cap_subcommand = match (&message.command, &result) {
+            (MessageType::Cap, ModuleResult::Handled | ModuleResult::HandledStop) => message.params.first().cloned(),
+            _ => None,
+        };
+        
+        match result {
+            ModuleResult::HandledStop | ModuleResult::Handled => {}
             ModuleResult::Rejected(reason) => {
                 // Send error message to client
                 let error_msg = Message::new(MessageType::Custom("ERROR".to_string()), vec![reason]);
-                if let Some(client) = connection_handler.get_client(&client_id) {
+                if let Some(client) = self.connection_handler.read().await.get_client(&client_id) {
                     let _ = client.send(error_msg);
                 }
-                return Ok(());
             }
-            ModuleResult::Handled => return Ok(()),
             ModuleResult::NotHandled => {
                 // Handle core commands
-                self.handle_core_command(client_id, message).await?;
+                self.handle_core_command(client_id, message)
+                    .instrument(tracing::debug_span!("irc.core_handler"))
+                    .await?;
+            }
+        }
+        
+        // Disconnect anyone a handler asked to, such as a client just D-lined
+        let exits = std::mem::take(&mut *self.exit_requests.lock());
+        for (exit_id, reason) in exits {
+            self.exit_client(exit_id, &reason, true).await?;
+        }
+        
+        // Carry out joins a handler asked for, such as an operator's auto-join channels
+        let joins = std::mem::take(&mut *self.join_requests.lock());
+        for (join_id, channel) in joins {
+            self.dispatch_for_client(join_id, Message::new(MessageType::Join, vec![channel])).await?;
+        }
+        
+        if registering {
+            if let Some(subcommand) = cap_subcommand {
+                if let Some(client) = self.connection_handler.write().await.get_client_mut(&client_id) {
+                    client.registration.cap(&subcommand);
+                }
             }
+            self.try_complete_registration(client_id).await?;
         }
         
         Ok(())
@@ -704,11 +1328,8 @@ impl Server {
     /// Handle a message from a server
     pub async fn handle_server_message(&self, server_name: &str, message: Message) -> Result<()> {
         // Record message statistics (from remote server, is_remote = true)
-        let command_name = match &message.command {
-            MessageType::Custom(cmd) => cmd.as_str(),
-            _ => "UNKNOWN",
-        };
-        self.statistics_manager.record_message_received(command_name, message.to_string().len(), true).await;
+        let command_name = message.command.to_string().to_uppercase();
+        self.statistics_manager.record_message_received(&command_name, message.to_string().len(), true).await;
         
         // Validate that this server is authorized to connect
         // This should be called when a server first connects, not on every message
@@ -727,6 +1348,8 @@ impl Server {
             }
             ModuleResult::Handled => return Ok(()),
             ModuleResult::NotHandled => {
+                // Core handlers may need the module manager themselves (e.g. the burst)
+                drop(module_manager);
                 // Handle core server commands
                 self.handle_core_server_command(server_name, message, is_super_server).await?;
             }
@@ -748,7 +1371,9 @@ impl Server {
                 self.handle_server_registration(server_name, message, is_super_server).await?;
             }
             MessageType::ServerQuit => {
-                self.handle_server_quit(server_name, message).await?;
+                // SQUIT names the server that split, which may be behind the link it arrived on
+                let split_server = message.params.first().cloned().unwrap_or_else(|| server_name.to_string());
+                self.handle_server_quit(&split_server, Some(server_name), message).await?;
             }
             MessageType::Ping => {
                 self.handle_server_ping(server_name, message).await?;
@@ -771,6 +1396,9 @@ impl Server {
             MessageType::ChannelBurst => {
                 self.handle_channel_burst_received(server_name, message).await?;
             }
+            MessageType::Encap => {
+                self.handle_server_encap_received(server_name, message).await?;
+            }
             MessageType::Wallops => {
                 self.handle_server_wallops_received(server_name, message).await?;
             }
@@ -783,9 +1411,23 @@ impl Server {
             MessageType::Join => {
                 self.handle_server_join_received(server_name, message).await?;
             }
+            MessageType::PrivMsg | MessageType::Notice
+                if message.params.first().is_some_and(|target| !target.starts_with(['#', '&', '+', '!'])) =>
+            {
+                self.handle_server_user_message(server_name, message).await?;
+            }
             MessageType::Part => {
                 self.handle_server_part_received(server_name, message).await?;
             }
+            MessageType::Whois | MessageType::Whowas | MessageType::Admin | MessageType::Version | MessageType::Time | MessageType::Stats => {
+                self.handle_server_query_received(server_name, message).await?;
+            }
+            MessageType::Custom(ref command) if command.len() == 3 && command.bytes().all(|byte| byte.is_ascii_digit()) => {
+                self.handle_server_numeric_received(server_name, message).await?;
+            }
+            MessageType::Custom(ref command) if SERVICES_COMMANDS.contains(&command.as_str()) => {
+                self.handle_services_command(server_name, message).await?;
+            }
             _ => {
                 // Other server commands can be handled here
                 tracing::debug!("Unhandled server command: {:?}", message.command);
@@ -870,6 +1512,10 @@ impl Server {
         
         // Add server connection to manager
         self.server_connections.add_connection(server_connection.clone()).await?;
+        // The connection no longer needs to register as a client
+        if let Some(client) = self.connection_handler.write().await.get_client_mut(&client_id) {
+            client.connection_type = crate::client::ConnectionType::Server;
+        }
         
         // Add server to database
         let server_info = crate::database::ServerInfo {
@@ -882,10 +1528,12 @@ impl Server {
             user_count: 0,
         };
         self.database.add_server(server_info)?;
+        self.introduce_direct_link(server_name, server_description, "").await;
         
         // Send server burst to the new server
         self.send_server_burst(server_name).await?;
         
+        self.send_snotice(SnoMask::NetJoins, &format!("Link with {} established (hop count {})", server_name, hop_count)).await?;
         tracing::info!("Server {} fully registered and burst sent", server_name);
         
         Ok(())
@@ -903,36 +1551,117 @@ impl Server {
         let _server_name_param = &message.params[0];
         let hop_count: u8 = message.params[1].parse()
             .map_err(|_| Error::MessageParse("Invalid hop count in SERVER command".to_string()))?;
-        let _server_description = &message.params[2];
+        let server_description = &message.params[2];
+        let version = message.params.get(3).map(|s| s.as_str()).unwrap_or("");
+        
+        // Both ends answer SERVER with a burst; only burst once per link
+        let already_registered = self.server_connections.get_connection(server_name).await
+            .map(|connection| connection.is_registered())
+            .unwrap_or(false);
         
         // Update server connection state
         self.server_connections.update_connection_state(server_name, crate::server_connection::ServerConnectionState::Registered).await?;
         
         // Send server burst to propagate our users and channels
-        self.send_server_burst(server_name).await?;
+        if !already_registered {
+            self.introduce_direct_link(server_name, server_description, version).await;
+            self.send_server_burst(server_name).await?;
+            self.send_snotice(SnoMask::NetJoins, &format!("Link with {} established (hop count {})", server_name, hop_count)).await?;
+        }
         
         tracing::info!("Server {} fully registered with hop count {}", server_name, hop_count);
         
         Ok(())
     }
     
+    /// Record a newly linked server and announce it to the rest of the network
+    async fn introduce_direct_link(&self, server_name: &str, description: &str, version: &str) {
+        self.topology.write().await.add_server(server_name, &self.config().server.name, description);
+        self.server_connections.record_link_established(server_name);
+        self.publish(ServerEvent::LinkEstablished { server: server_name.to_string() });
+        
+        let introduction = Message::with_prefix(
+            Prefix::Server(self.config().server.name.clone()),
+            MessageType::ServerBurst,
+            vec![server_name.to_string(), description.to_string(), "1".to_string(), version.to_string()],
+        );
+        if let Err(e) = self.server_connections.broadcast_message(&introduction, Some(server_name)).await {
+            tracing::warn!("Failed to announce new link {}: {}", server_name, e);
+        }
+    }
+    
+    /// Directly linked server that traffic for `server_name` goes through
+    pub async fn route_to_server(&self, server_name: &str) -> Option<String> {
+        self.topology.read().await.route(server_name).map(str::to_string)
+    }
+    
+    /// Handle a link being closed to a directly connected server
+    ///
+    /// Runs the same split processing as a received SQUIT: users on the lost
+    /// server and every server behind it are cleaned up and the split is
+    /// propagated to the remaining links.
+    pub async fn handle_server_link_closed(&self, server_name: &str, reason: &str) -> Result<()> {
+        let quit_message = Message::new(
+            MessageType::ServerQuit,
+            vec![server_name.to_string(), reason.to_string()]
+        );
+        self.handle_server_quit(server_name, None, quit_message).await
+    }
+    
     /// Handle server quit
-    async fn handle_server_quit(&self, server_name: &str, message: Message) -> Result<()> {
-        let quit_reason = message.params.first()
+    ///
+    /// `server_name` is the server that split; `source` is the link the SQUIT
+    /// arrived on, or `None` when the split was detected locally.
+    async fn handle_server_quit(&self, server_name: &str, source: Option<&str>, message: Message) -> Result<()> {
+        let quit_reason = message.params.get(1)
+            .or(message.params.first())
             .map(|s| s.as_str())
             .unwrap_or("Server quit");
         
         tracing::info!("Server {} quit: {}", server_name, quit_reason);
         
-        // 1. Get all users from the quitting server
-        let users_to_remove = self.database.get_users_by_server(server_name);
+        // 1. Work out which servers are lost: the split server plus its whole subtree
+        let split_servers: Vec<String> = {
+            let lost = self.topology.write().await.remove_subtree(server_name);
+            if lost.is_empty() { vec![server_name.to_string()] } else { lost }
+        };
+        
+        // 2. Get all users from the lost servers
+        let users_to_remove: Vec<User> = split_servers.iter()
+            .flat_map(|server| self.database.get_users_by_server(server))
+            .filter(|user| user.state == crate::UserState::Active)
+            .collect();
         let user_count = users_to_remove.len();
-        tracing::info!("Found {} users from server {}", user_count, server_name);
+        tracing::info!("Found {} users behind server {}", user_count, server_name);
+        
+        // 3. Work out which local users see each lost user quit: those sharing
+        // a channel with it, once each. Use standard IRC netsplit notation:
+        // "our_server quitting_server"
+        let local_server = self.config().server.name.clone();
+        let netsplit_message = format!("{} {}", local_server, server_name);
+        let mut quits: HashMap<Uuid, Vec<Message>> = HashMap::new();
+        for user in &users_to_remove {
+            let quit_msg = Message::with_prefix(
+                Prefix::User {
+                    nick: user.nick.clone(),
+                    user: user.username.clone(),
+                    host: user.host.clone(),
+                },
+                MessageType::Quit,
+                vec![netsplit_message.clone()],
+            );
+            let mut seen = HashSet::new();
+            for channel in self.database.get_user_channels(&user.nick) {
+                for member in self.database.get_channel_users(&channel).iter().filter_map(|nick| self.database.get_user_by_nick(nick)) {
+                    if self.client_senders.contains(&member.id) && seen.insert(member.id) {
+                        quits.entry(member.id).or_default().push(quit_msg.clone());
+                    }
+                }
+            }
+        }
         
-        // 2. Handle users from this server - either mark as netsplit or remove immediately
-        // Use standard IRC netsplit notation: "our_server quitting_server"
-        let netsplit_message = format!("{} {}", self.config.server.name, server_name);
-        let grace_period_enabled = self.config.netsplit.split_user_grace_period > 0;
+        // 4. Handle users from this server - either mark as netsplit or remove immediately
+        let grace_period_enabled = self.config().netsplit.split_user_grace_period > 0;
         
         for mut user in users_to_remove {
             if grace_period_enabled {
@@ -941,12 +1670,12 @@ impl Server {
                 user.split_at = Some(chrono::Utc::now());
                 
                 // Update user in database
-                if let Err(e) = self.database.add_user(user.clone()) {
+                if let Err(e) = self.database.update_user(&user.id, user.clone()) {
                     tracing::warn!("Failed to update user {} to netsplit state: {}", user.nick, e);
                 }
                 
                 tracing::debug!("Marked user {} as netsplit (grace period: {}s)", 
-                               user.nick, self.config.netsplit.split_user_grace_period);
+                               user.nick, self.config().netsplit.split_user_grace_period);
             } else {
                 // Immediate removal (no grace period)
                 // Remove from nick_to_id mapping
@@ -966,60 +1695,63 @@ impl Server {
                     tracing::warn!("Failed to remove user {} from database: {}", user.nick, e);
                 }
                 
-                tracing::debug!("Removed user {} from server {}", user.nick, server_name);
-            }
-            
-            // Broadcast QUIT to local clients with netsplit notation
-            let quit_msg = Message::with_prefix(
-                Prefix::User {
-                    nick: user.nick.clone(),
-                    user: user.username.clone(),
-                    host: user.host.clone(),
-                },
-                MessageType::Quit,
-                vec![netsplit_message.clone()],
-            );
-            
-            if let Err(e) = self.broadcast_system.broadcast_to_all(quit_msg, None).await {
-                tracing::warn!("Failed to broadcast quit for {}: {}", user.nick, e);
+                tracing::debug!("Removed user {} from server {}", user.nick, user.server);
             }
         }
         
-        // 3. Remove server from database
-        if self.database.remove_server(server_name).is_none() {
-            tracing::debug!("Server {} was not in database", server_name);
+        // 5. Show the QUITs, in one netsplit batch for clients that support it
+        let split_params = [local_server.clone(), server_name.to_string()];
+        for (client_id, messages) in quits {
+            if self.client_has_capability(&client_id, "batch") {
+                self.client_senders.send_batch(&client_id, &local_server, "netsplit", &split_params, &messages);
+            } else {
+                for message in messages {
+                    self.client_senders.send(&client_id, message);
+                }
+            }
         }
         
-        // 4. Remove from super servers if it's a u-lined server
-        {
-            let mut super_servers = self.super_servers.write().await;
-            super_servers.remove(server_name);
+        for split_server in &split_servers {
+            // 6. Remove server from database
+            if self.database.remove_server(split_server).is_none() {
+                tracing::debug!("Server {} was not in database", split_server);
+            }
+            
+            // 7. Remove from super servers if it's a u-lined server
+            {
+                let mut super_servers = self.super_servers.write().await;
+                super_servers.remove(split_server);
+            }
         }
         
-        // 5. Remove server connection
-        if let Err(e) = self.server_connections.remove_connection(server_name).await {
-            tracing::warn!("Failed to remove server connection for {}: {}", server_name, e);
+        // 8. Remove server connection (only present if it was a direct link)
+        match self.server_connections.remove_connection(server_name).await {
+            Ok(Some(_)) => self.server_connections.record_link_dropped(server_name),
+            Ok(None) => {}
+            Err(e) => tracing::warn!("Failed to remove server connection for {}: {}", server_name, e),
         }
         
-        // 6. Propagate SQUIT to other connected servers (except source)
-        let squit_msg = Message::with_prefix(
-            Prefix::Server(self.config.server.name.clone()),
-            MessageType::ServerQuit,
-            vec![
-                server_name.to_string(),
-                quit_reason.to_string(),
-            ],
-        );
-        
-        if let Err(e) = self.server_connections.broadcast_message(&squit_msg, Some(server_name)).await {
-            tracing::warn!("Failed to propagate SQUIT for {}: {}", server_name, e);
+        // 9. Propagate SQUIT for every lost server to other connected servers (except source)
+        for split_server in &split_servers {
+            let squit_msg = Message::with_prefix(
+                Prefix::Server(self.config().server.name.clone()),
+                MessageType::ServerQuit,
+                vec![
+                    split_server.clone(),
+                    quit_reason.to_string(),
+                ],
+            );
+            
+            if let Err(e) = self.server_connections.broadcast_message(&squit_msg, Some(source.unwrap_or(server_name))).await {
+                tracing::warn!("Failed to propagate SQUIT for {}: {}", split_server, e);
+            }
         }
         
         tracing::info!("Server {} quit processing complete. Cleaned up {} users", 
                       server_name, user_count);
         
-        // 7. Notify operators about the netsplit if configured
-        if self.config.netsplit.notify_opers_on_split {
+        // 10. Notify operators about the netsplit if configured
+        if self.config().netsplit.notify_opers_on_split {
             // Calculate network topology and split severity
             let connected_servers = self.server_connections.server_count().await;
             let total_servers = connected_servers + 1; // +1 for the split server
@@ -1029,7 +1761,7 @@ impl Server {
                 "{} netsplit: lost connection to {} ({} users affected) - {} [{} servers remain]",
                 split_severity, server_name, user_count, quit_reason, connected_servers
             );
-            if let Err(e) = self.send_operator_notice(&notice_msg).await {
+            if let Err(e) = self.send_snotice(SnoMask::NetJoins, &notice_msg).await {
                 tracing::warn!("Failed to send operator notice for netsplit: {}", e);
             }
         }
@@ -1059,11 +1791,11 @@ impl Server {
         tracing::info!("Sending server burst to {}", target_server);
         
         // Check if burst optimization is enabled and if this is a quick rejoin
-        let is_optimized_burst = if self.config.netsplit.burst_optimization_enabled {
+        let is_optimized_burst = if self.config().netsplit.burst_optimization_enabled {
             if let Some(connection) = self.server_connections.get_connection(target_server).await {
                 if let Some(last_sync) = connection.info.last_burst_sync {
                     let elapsed = (chrono::Utc::now() - last_sync).num_seconds() as u64;
-                    let within_window = elapsed <= self.config.netsplit.burst_optimization_window;
+                    let within_window = elapsed <= self.config().netsplit.burst_optimization_window;
                     if within_window {
                         tracing::info!("Using optimized burst for {} (last sync {}s ago)", 
                                      target_server, elapsed);
@@ -1081,27 +1813,43 @@ impl Server {
         
         // Send basic server information
         let server_info = Message::with_prefix(
-            Prefix::Server(self.config.server.name.clone()),
+            Prefix::Server(self.config().server.name.clone()),
             MessageType::Server,
             vec![
-                self.config.server.name.clone(),
+                self.config().server.name.clone(),
                 "1".to_string(), // hop count
-                self.config.server.description.clone(),
-                self.config.server.version.clone(),
+                self.config().server.description.clone(),
+                self.config().server.version.clone(),
             ]
         );
         self.server_connections.send_to_server(target_server, server_info).await?;
         
-        // Send user burst for all local users
+        // Introduce every server we know of that isn't behind the target link,
+        // nearest first so each uplink is known before the servers behind it
+        let topology = self.topology.read().await.clone();
+        for server in topology.servers() {
+            if topology.is_behind(&server.name, target_server) {
+                continue;
+            }
+            let introduction = Message::with_prefix(
+                Prefix::Server(server.uplink.clone()),
+                MessageType::ServerBurst,
+                vec![server.name.clone(), server.description.clone(), server.hop_count.to_string(), String::new()],
+            );
+            if let Err(e) = self.server_connections.send_to_server(target_server, introduction).await {
+                tracing::warn!("Failed to introduce {} to {}: {}", server.name, target_server, e);
+            }
+        }
+        
+        // Send user burst for every user we know of that isn't behind the target link
         // If optimized burst, we could track and only send changes, but for simplicity
         // we'll just send all users. A full implementation would track user changes.
-        let users = self.users.read().await;
         let mut user_count = 0;
-        for user in users.values() {
-            // Only burst users on our server (local users)
-            // Skip users in netsplit state for optimized bursts
-            if user.server == self.config.server.name && 
-               (!is_optimized_burst || user.state == crate::UserState::Active) {
+        for user in self.database.get_all_users() {
+            let behind_target = user.server == target_server
+                || topology.is_behind(&user.server, target_server);
+            // Users in netsplit state aren't reachable and are never burst
+            if !behind_target && user.state == crate::UserState::Active {
                 let user_burst = Message::new(
                     MessageType::UserBurst,
                     vec![
@@ -1112,24 +1860,57 @@ impl Server {
                         user.server.clone(),
                         user.id.to_string(),
                         user.registered_at.timestamp().to_string(),
+                        user.real_host.clone(),
                     ]
                 );
                 
                 if let Err(e) = self.server_connections.send_to_server(target_server, user_burst).await {
                     tracing::warn!("Failed to send user burst for {}: {}", user.nick, e);
                 }
+                // Away messages follow the user so the other side can answer with 301
+                if let Some(away_message) = &user.away_message {
+                    let away = Message::with_prefix(user.prefix(), MessageType::Away, vec![away_message.clone()]);
+                    if let Err(e) = self.server_connections.send_to_server(target_server, away).await {
+                        tracing::warn!("Failed to send away status of {}: {}", user.nick, e);
+                    }
+                }
                 user_count += 1;
             }
         }
         
+        // Channels (SJOIN-style CBURST with modes, lists and member prefixes) come from modules
+        let module_burst = self.module_manager.read().await.server_burst(target_server).await;
+        let module_burst_count = module_burst.len();
+        for burst_message in module_burst {
+            if let Err(e) = self.server_connections.send_to_server(target_server, burst_message).await {
+                tracing::warn!("Failed to send module burst to {}: {}", target_server, e);
+            }
+        }
+        
+        // Send network-wide bans (e.g. G-lines) so the new server enforces them too
+        let mut ban_count = 0;
+        for ban_type in self.ban_manager.global_types() {
+            for ban in self.ban_manager.list_bans(&ban_type.name) {
+                let ban_msg = Message::with_prefix(
+                    Prefix::Server(self.config().server.name.clone()),
+                    MessageType::Custom(ban_type.name.clone()),
+                    ban.to_params(),
+                );
+                if let Err(e) = self.server_connections.send_to_server(target_server, ban_msg).await {
+                    tracing::warn!("Failed to send {} {} in burst: {}", ban_type.name, ban.mask, e);
+                }
+                ban_count += 1;
+            }
+        }
+        
         // Update last burst sync timestamp for burst optimization
         if let Some(mut connection) = self.server_connections.get_connection(target_server).await {
             connection.info.last_burst_sync = Some(chrono::Utc::now());
             tracing::debug!("Updated last_burst_sync for {}", target_server);
         }
         
-        tracing::info!("Server burst to {} completed ({} users sent, optimized: {})", 
-                      target_server, user_count, is_optimized_burst);
+        tracing::info!("Server burst to {} completed ({} users, {} module messages, {} bans sent, optimized: {})", 
+                      target_server, user_count, module_burst_count, ban_count, is_optimized_burst);
         Ok(())
     }
     
@@ -1166,6 +1947,40 @@ impl Server {
         Ok(())
     }
 
+    /// Handle ENCAP received from another server
+    ///
+    /// The envelope is relayed towards its target; if the target mask covers
+    /// us, the tunneled command goes to the module that registered it.
+    async fn handle_server_encap_received(&self, server_name: &str, message: Message) -> Result<()> {
+        let Some((target, inner)) = crate::network::unwrap_encap(&message) else {
+            tracing::warn!("Received malformed ENCAP from server {}", server_name);
+            return Ok(());
+        };
+        let local = &self.config().server.name;
+        
+        if !target.eq_ignore_ascii_case(local) {
+            let has_wildcards = target.contains(['*', '?']);
+            match self.route_to_server(&target).await.filter(|_| !has_wildcards) {
+                Some(route) if route != server_name => {
+                    self.server_connections.send_to_server(&route, message.clone()).await?;
+                }
+                Some(_) => {}
+                None => {
+                    self.server_connections.broadcast_message(&message, Some(server_name)).await?;
+                }
+            }
+        }
+        
+        if crate::network::encap_targets(&target, local) {
+            let mut module_manager = self.module_manager.write().await;
+            if let ModuleResult::NotHandled = module_manager.handle_encap(server_name, &inner).await? {
+                tracing::debug!("No module handles ENCAP {} from {}", inner.command, server_name);
+            }
+        }
+        
+        Ok(())
+    }
+    
     /// Handle WALLOPS message received from another server
     async fn handle_server_wallops_received(&self, server_name: &str, message: Message) -> Result<()> {
         if message.params.is_empty() {
@@ -1176,41 +1991,20 @@ impl Server {
         // Get the wallops message (all parameters joined)
         let wallops_message = message.params.join(" ");
         
-        // Create the wallops message format with server prefix
-        let wallops_msg = format!(":{} WALLOPS :{}", server_name, wallops_message);
+        // Show the originating user or server, falling back to the link
+        let source = message.prefix.clone().unwrap_or_else(|| Prefix::Server(server_name.to_string()));
+        let wallops_msg = Message::with_prefix(source, MessageType::Wallops, vec![wallops_message.clone()]);
         
         // Send to all local clients with wallops mode (+w)
-        let connection_handler = self.connection_handler.read().await;
         let mut local_sent_count = 0;
-        
         for (_, user) in self.users.read().await.iter() {
-            if user.has_mode('w') {
-                // Find the user's client and send the message
-                if let Some(user_client) = connection_handler.get_client_by_nick(&user.nick) {
-                    if let Err(e) = user_client.send_raw(&wallops_msg) {
-                        tracing::warn!("Failed to send wallops to {}: {}", user.nick, e);
-                    } else {
-                        local_sent_count += 1;
-                    }
-                }
+            if user.has_mode('w') && self.client_senders.send(&user.id, wallops_msg.clone()) {
+                local_sent_count += 1;
             }
         }
         
         // Forward to other servers (except the one we received it from)
-        let server_wallops_msg = Message::new(
-            MessageType::Wallops,
-            vec![wallops_message.clone()]
-        );
-        
-        // Get all server connections except the source
-        let connections = self.server_connections.get_all_connections().await;
-        for connection in connections {
-            if connection.info.name != server_name {
-                if let Err(e) = connection.send(server_wallops_msg.clone()) {
-                    tracing::warn!("Failed to forward wallops to server {}: {}", connection.info.name, e);
-                }
-            }
-        }
+        self.forward_to_other_servers(server_name, &message).await;
         
         tracing::info!(
             "Wallops received from server {} and sent to {} local recipients: {}",
@@ -1240,46 +2034,37 @@ impl Server {
         };
         
         // Check if target is a server (not allowed)
-        if target_user.nick == self.config.server.name {
-            tracing::warn!("Received KILL for server {} from server {}", self.config.server.name, server_name);
+        if target_user.nick == self.config().server.name {
+            tracing::warn!("Received KILL for server {} from server {}", self.config().server.name, server_name);
             return Ok(());
         }
         
-        // Find the target user's client
-        let target_client_id = if let Some(target_user) = database.get_user_by_nick(target_nick) {
-            Some(target_user.id)
-        } else {
-            None
-        };
-        
-        if let Some(client_id) = target_client_id {
-            // Send KILL message to the target user and handle disconnection
-            {
-                let connection_handler = self.connection_handler.read().await;
-                if let Some(target_client) = connection_handler.get_client(&client_id) {
-                    // Send KILL message to the target user
+        // Send KILL message to the target user if it is ours
+        let is_local = {
+            let connection_handler = self.connection_handler.read().await;
+            match connection_handler.get_client(&target_user.id) {
+                Some(target_client) => {
                     let kill_message = Message::new(
                         MessageType::Kill,
                         vec![target_nick.to_string(), kill_reason.to_string()]
                     );
                     let _ = target_client.send(kill_message);
+                    true
                 }
+                None => false,
             }
-            
-            // Send quit message to all users in channels
-            let quit_reason = format!("Killed ({})", kill_reason);
-            self.broadcast_user_quit_by_id(client_id, &quit_reason).await?;
-            
-            // Remove user from database
-            database.remove_user(client_id)?;
-            
-            // Close the connection
-            let mut connection_handler = self.connection_handler.write().await;
-            connection_handler.remove_client(&client_id);
-            
-            tracing::info!("Killed user {} from server {}: {}", target_nick, server_name, kill_reason);
+        };
+        
+        let quit_reason = format!("Killed ({})", kill_reason);
+        if is_local {
+            self.exit_client(target_user.id, &quit_reason, false).await?;
+        } else {
+            self.remove_departed_user(&target_user, &quit_reason).await;
+            self.publish(ServerEvent::UserQuit { user: target_user, reason: quit_reason });
         }
         
+        tracing::info!("Killed user {} from server {}: {}", target_nick, server_name, kill_reason);
+        
         // Forward to other servers (except the one we received it from)
         let server_kill_msg = Message::new(
             MessageType::Kill,
@@ -1300,57 +2085,88 @@ impl Server {
     }
 
     /// Handle AWAY message received from another server
+    ///
+    /// Sets or clears the away message of the remote user named in the prefix.
     async fn handle_server_away_received(&self, server_name: &str, message: Message) -> Result<()> {
-        // AWAY messages from servers don't have a source prefix in our current implementation
-        // This would need to be enhanced to extract the source user from the message prefix
-        // For now, we'll just forward the message to other servers
+        let Some(mut user) = self.remote_source_user(server_name, &message) else {
+            return Ok(());
+        };
         
-        // Forward to other servers (except the one we received it from)
-        let server_away_msg = Message::new(
-            MessageType::Away,
-            message.params.clone()
-        );
+        user.set_away(message.params.first().filter(|reason| !reason.is_empty()).cloned());
+        let (user_id, nick, away) = (user.id, user.nick.clone(), user.is_away());
+        self.database.update_user(&user_id, user.clone())?;
+        if let Some(known) = self.users.write().await.get_mut(&user_id) {
+            *known = user;
+        }
         
-        // Get all server connections except the source
-        let connections = self.server_connections.get_all_connections().await;
-        for connection in connections {
+        self.forward_to_other_servers(server_name, &message).await;
+        tracing::debug!("Remote user {} is {} (via {})", nick, if away { "away" } else { "back" }, server_name);
+        Ok(())
+    }
+
+    /// Resolve the user a server-relayed message originates from
+    ///
+    /// The prefix is either `nick!user@host` or the user's UID as sent in the
+    /// user burst.
+    fn remote_source_user(&self, server_name: &str, message: &Message) -> Option<User> {
+        let (source, user) = match &message.prefix {
+            Some(Prefix::User { nick, .. }) => (nick.as_str(), self.database.get_user_by_nick(nick)),
+            Some(Prefix::Server(source)) => (
+                source.as_str(),
+                uuid::Uuid::parse_str(source).ok().and_then(|uid| self.database.get_user(&uid)),
+            ),
+            None => {
+                tracing::warn!("Received {} from server {} without a source prefix", message.command, server_name);
+                return None;
+            }
+        };
+        if user.is_none() {
+            tracing::warn!("Received {} from server {} for unknown user {}", message.command, server_name, source);
+        }
+        user
+    }
+
+    /// Forward a server message to every other linked server
+    async fn forward_to_other_servers(&self, server_name: &str, message: &Message) {
+        for connection in self.server_connections.get_all_connections().await {
             if connection.info.name != server_name {
-                if let Err(e) = connection.send(server_away_msg.clone()) {
-                    tracing::warn!("Failed to forward AWAY to server {}: {}", connection.info.name, e);
+                if let Err(e) = connection.send(message.clone()) {
+                    tracing::warn!("Failed to forward {} to server {}: {}", message.command, connection.info.name, e);
                 }
             }
         }
-        
-        tracing::debug!("Forwarded AWAY message from server {}", server_name);
-        Ok(())
     }
 
     /// Handle JOIN message received from another server
+    ///
+    /// Records the membership of the remote user named in the prefix and
+    /// forwards the JOIN, prefix included, to the rest of the network.
     async fn handle_server_join_received(&self, server_name: &str, message: Message) -> Result<()> {
         if message.params.is_empty() {
             tracing::warn!("Received JOIN from server {} with no channel", server_name);
             return Ok(());
         }
+        let Some(mut user) = self.remote_source_user(server_name, &message) else {
+            return Ok(());
+        };
         
-        let channel_name = &message.params[0];
-        
-        // Forward to other servers (except the one we received it from)
-        let server_join_msg = Message::new(
-            MessageType::Join,
-            vec![channel_name.clone()]
-        );
-        
-        // Get all server connections except the source
-        let connections = self.server_connections.get_all_connections().await;
-        for connection in connections {
-            if connection.info.name != server_name {
-                if let Err(e) = connection.send(server_join_msg.clone()) {
-                    tracing::warn!("Failed to forward JOIN to server {}: {}", connection.info.name, e);
-                }
+        for channel_name in message.params[0].split(',') {
+            if self.database.get_channel(channel_name).is_none() {
+                self.database.add_channel(crate::ChannelInfo {
+                    name: channel_name.to_string(),
+                    topic: None,
+                    user_count: 0,
+                    modes: std::collections::HashSet::new(),
+                })?;
             }
+            self.database.add_user_to_channel(&user.nick, channel_name)?;
+            user.channels.insert(channel_name.to_string());
         }
+        let (user_id, nick) = (user.id, user.nick.clone());
+        self.database.update_user(&user_id, user)?;
         
-        tracing::debug!("Forwarded JOIN message for channel {} from server {}", channel_name, server_name);
+        self.forward_to_other_servers(server_name, &message).await;
+        tracing::debug!("Remote user {} joined {} via server {}", nick, message.params[0], server_name);
         Ok(())
     }
 
@@ -1360,31 +2176,22 @@ impl Server {
             tracing::warn!("Received PART from server {} with no channel", server_name);
             return Ok(());
         }
+        let Some(mut user) = self.remote_source_user(server_name, &message) else {
+            return Ok(());
+        };
         
-        let channel_name = &message.params[0];
-        let reason = message.params.get(1).cloned().unwrap_or_default();
-        
-        // Forward to other servers (except the one we received it from)
-        let server_part_msg = Message::new(
-            MessageType::Part,
-            if reason.is_empty() {
-                vec![channel_name.clone()]
-            } else {
-                vec![channel_name.clone(), reason.clone()]
-            }
-        );
-        
-        // Get all server connections except the source
-        let connections = self.server_connections.get_all_connections().await;
-        for connection in connections {
-            if connection.info.name != server_name {
-                if let Err(e) = connection.send(server_part_msg.clone()) {
-                    tracing::warn!("Failed to forward PART to server {}: {}", connection.info.name, e);
-                }
+        for channel_name in message.params[0].split(',') {
+            self.database.remove_user_from_channel(&user.nick, channel_name)?;
+            user.channels.remove(channel_name);
+            if self.database.get_channel_users(channel_name).is_empty() {
+                self.database.remove_channel(channel_name);
             }
         }
+        let (user_id, nick) = (user.id, user.nick.clone());
+        self.database.update_user(&user_id, user)?;
         
-        tracing::debug!("Forwarded PART message for channel {} from server {}", channel_name, server_name);
+        self.forward_to_other_servers(server_name, &message).await;
+        tracing::debug!("Remote user {} left {} via server {}", nick, message.params[0], server_name);
         Ok(())
     }
     
@@ -1425,6 +2232,9 @@ impl Server {
             tracing::error!("Failed to update user nickname in database: {}", e);
             return Err(e);
         }
+        if let Err(e) = self.database.add_to_history(user.clone()).await {
+            tracing::warn!("Failed to record {} for WHOWAS: {}", old_nick, e);
+        }
         
         // Update in users map
         {
@@ -1450,9 +2260,9 @@ impl Server {
             vec![new_nick.clone()],
         );
         
-        if let Err(e) = self.broadcast_system.broadcast_to_all(nick_msg, None).await {
-            tracing::warn!("Failed to broadcast NICK change for {}: {}", old_nick, e);
-        }
+        let connection_handler = self.connection_handler.read().await;
+        self.send_to_channel_peers(&new_nick, &nick_msg, None);
+        drop(connection_handler);
         
         // Propagate to other servers
         let nick_propagation = Message::with_prefix(
@@ -1490,39 +2300,8 @@ impl Server {
             }
         };
         
-        let user_id = user.id;
-        
-        // Remove from nick_to_id mapping
-        {
-            let mut nick_to_id = self.nick_to_id.write().await;
-            nick_to_id.remove(&nick);
-        }
-        
-        // Remove from users map
-        {
-            let mut users = self.users.write().await;
-            users.remove(&user_id);
-        }
-        
-        // Remove from database
-        if let Err(e) = self.database.remove_user(user_id) {
-            tracing::warn!("Failed to remove user {} from database: {}", nick, e);
-        }
-        
-        // Broadcast QUIT to local clients
-        let quit_msg = Message::with_prefix(
-            Prefix::User {
-                nick: user.nick.clone(),
-                user: user.username.clone(),
-                host: user.host.clone(),
-            },
-            MessageType::Quit,
-            vec![reason.to_string()],
-        );
-        
-        if let Err(e) = self.broadcast_system.broadcast_to_all(quit_msg, None).await {
-            tracing::warn!("Failed to broadcast QUIT for {}: {}", nick, e);
-        }
+        self.remove_departed_user(&user, reason).await;
+        self.publish(ServerEvent::UserQuit { user, reason: reason.to_string() });
         
         // Propagate to other servers
         let quit_propagation = Message::with_prefix(
@@ -1553,6 +2332,8 @@ impl Server {
         let user_server = message.params[4].clone();
         let user_id_str = &message.params[5];
         let connected_at_str = &message.params[6];
+        // Older peers don't send the real host; fall back to the displayed one
+        let real_host = message.params.get(7).cloned().unwrap_or_else(|| host.clone());
         
         tracing::debug!("Received user burst from server {}: {}!{}@{}", server_name, nick, username, host);
         
@@ -1566,6 +2347,29 @@ impl Server {
             .and_then(|ts| chrono::DateTime::from_timestamp(ts, 0))
             .ok_or_else(|| Error::MessageParse(format!("Invalid timestamp in burst: {}", connected_at_str)))?;
         
+        // Servers not introduced by a BURST are assumed to sit behind the link
+        {
+            let mut topology = self.topology.write().await;
+            if !topology.contains(&user_server) {
+                topology.add_server(&user_server, server_name, "");
+            }
+        }
+        
+        // A user we already know by ID is being re-burst after a netsplit; restore it
+        if let Some(mut existing_user) = self.database.get_user(&user_id) {
+            if existing_user.state == crate::UserState::NetSplit {
+                tracing::info!("Restoring netsplit user {} from server {}", nick, user_server);
+            }
+            existing_user.state = crate::UserState::Active;
+            existing_user.split_at = None;
+            existing_user.server = user_server.clone();
+            if let Err(e) = self.database.update_user(&user_id, existing_user) {
+                tracing::warn!("Failed to restore burst user {}: {}", nick, e);
+            }
+            self.server_connections.broadcast_message(&message, Some(server_name)).await?;
+            return Ok(());
+        }
+        
         // Check for nick collision
         if let Some(existing_user) = self.database.get_user_by_nick(&nick) {
             // Nick collision detected!
@@ -1578,7 +2382,7 @@ impl Server {
                 
                 // Kill existing user
                 let kill_msg_local = Message::with_prefix(
-                    Prefix::Server(self.config.server.name.clone()),
+                    Prefix::Server(self.config().server.name.clone()),
                     MessageType::Kill,
                     vec![existing_user.nick.clone(), "Nick collision".to_string()],
                 );
@@ -1589,7 +2393,7 @@ impl Server {
                 
                 // Kill incoming user by sending KILL to source server
                 let kill_msg_remote = Message::with_prefix(
-                    Prefix::Server(self.config.server.name.clone()),
+                    Prefix::Server(self.config().server.name.clone()),
                     MessageType::Kill,
                     vec![nick.clone(), "Nick collision".to_string()],
                 );
@@ -1599,7 +2403,7 @@ impl Server {
                 
                 // Notify operators
                 let notice_msg = format!("Nick collision: {} (killed both users)", nick);
-                if let Err(e) = self.send_operator_notice(&notice_msg).await {
+                if let Err(e) = self.send_snotice(SnoMask::Kills, &notice_msg).await {
                     tracing::warn!("Failed to send operator notice for collision: {}", e);
                 }
                 
@@ -1609,7 +2413,7 @@ impl Server {
                 tracing::info!("Nick collision: keeping older user {} (local)", nick);
                 
                 let kill_msg = Message::with_prefix(
-                    Prefix::Server(self.config.server.name.clone()),
+                    Prefix::Server(self.config().server.name.clone()),
                     MessageType::Kill,
                     vec![nick.clone(), "Nick collision (older nick wins)".to_string()],
                 );
@@ -1623,7 +2427,7 @@ impl Server {
                 tracing::info!("Nick collision: replacing with older user {} (remote)", nick);
                 
                 let kill_msg = Message::with_prefix(
-                    Prefix::Server(self.config.server.name.clone()),
+                    Prefix::Server(self.config().server.name.clone()),
                     MessageType::Kill,
                     vec![existing_user.nick.clone(), "Nick collision (older nick wins)".to_string()],
                 );
@@ -1642,6 +2446,7 @@ impl Server {
             username: username.clone(),
             realname: realname.clone(),
             host: host.clone(),
+            real_host,
             server: user_server.clone(),
             registered_at: connected_at,
             last_activity: chrono::Utc::now(),
@@ -1655,6 +2460,10 @@ impl Server {
             bot_info: None,
             state: crate::UserState::Active,
             split_at: None,
+            silence_list: Vec::new(),
+            snomasks: std::collections::BTreeSet::new(),
+            account: None,
+            certfp: None,
         };
         
         // Add user to database
@@ -1675,6 +2484,11 @@ impl Server {
             nick_to_id.insert(nick.clone(), user_id);
         }
         
+        // Pass the user on to the rest of the network
+        if let Err(e) = self.server_connections.broadcast_message(&message, Some(server_name)).await {
+            tracing::warn!("Failed to forward user burst for {}: {}", nick, e);
+        }
+        
         tracing::info!("Processed user burst from {}: {} ({}!{}@{})", 
                       server_name, nick, username, user_server, host);
         
@@ -1695,9 +2509,24 @@ impl Server {
         tracing::debug!("Received server burst from server {}: {} (hop: {})", server_name, burst_server_name, hop_count_str);
         
         // Parse hop count
-        let hop_count: u32 = hop_count_str.parse()
+        let _announced_hops: u32 = hop_count_str.parse()
             .map_err(|_| Error::MessageParse(format!("Invalid hop count in server burst: {}", hop_count_str)))?;
         
+        // The prefix names the introducing server; without one it's the link itself.
+        // Our own hop count follows from where the uplink sits in the tree.
+        let uplink = match &message.prefix {
+            Some(Prefix::Server(uplink)) if uplink != &self.config().server.name => uplink.clone(),
+            _ => server_name.to_string(),
+        };
+        let hop_count = {
+            let mut topology = self.topology.write().await;
+            if !topology.is_behind(&uplink, server_name) || !topology.add_server(&burst_server_name, &uplink, &description) {
+                tracing::warn!("Ignoring BURST for {} from {}: uplink {} isn't behind that link", burst_server_name, server_name, uplink);
+                return Ok(());
+            }
+            topology.hop_count(&burst_server_name).unwrap_or(1)
+        };
+        
         // Create server info
         let server_info = crate::database::ServerInfo {
             name: burst_server_name.clone(),
@@ -1715,6 +2544,9 @@ impl Server {
             // Don't fail - might already exist
         }
         
+        // Let the rest of the network know about the new server
+        self.forward_to_other_servers(server_name, &message).await;
+        
         tracing::info!("Processed server burst from {}: {} (hop: {}, version: {})", 
                       server_name, burst_server_name, hop_count, version);
         
@@ -1730,31 +2562,27 @@ impl Server {
         let channel_name = message.params[0].clone();
         tracing::debug!("Received channel burst from server {}: {}", server_name, channel_name);
         
-        // Parse channel burst parameters
-        // Format: CBURST #channel [timestamp] [topic] [modes] [members...]
-        // TODO: Enhanced netsplit recovery - Add timestamp-based conflict resolution:
-        // - Parse channel creation timestamp from message.params[1]
-        // - Compare with local channel's created_at timestamp
-        // - If remote timestamp is older, accept their modes/ops
-        // - If local timestamp is older, reject their modes and send our state back
-        // - This prevents op wars and mode desync after netsplits (TS6 protocol)
-        let topic = if message.params.len() > 1 && !message.params[1].is_empty() {
-            Some(message.params[1].clone())
-        } else {
-            None
-        };
-        
-        let modes = if message.params.len() > 2 {
-            message.params[2].chars().collect::<std::collections::HashSet<char>>()
+        // Format: CBURST <channel> <ts> <modes> [mode params...] :<members>
+        // Members carry their status prefixes (e.g. "~@alice +bob"). The channel
+        // module resolves TS conflicts; the database only tracks membership.
+        let modes = message.params.get(2)
+            .map(|modes| modes.trim_start_matches('+').chars().collect())
+            .unwrap_or_default();
+        let members: Vec<String> = if message.params.len() > 3 {
+            message.params[message.params.len() - 1]
+                .split_whitespace()
+                .map(|member| member.trim_start_matches(['~', '&', '@', '%', '+']).to_string())
+                .filter(|member| !member.is_empty())
+                .collect()
         } else {
-            std::collections::HashSet::new()
+            Vec::new()
         };
         
         // Create channel info
         let channel_info = crate::database::ChannelInfo {
             name: channel_name.clone(),
-            topic,
-            user_count: 0, // Will be updated as members join
+            topic: None,
+            user_count: members.len() as u32,
             modes,
         };
         
@@ -1764,19 +2592,12 @@ impl Server {
             // Don't fail - channel might already exist
         }
         
-        // Process channel members if provided (params 3+)
         let mut member_count = 0;
-        if message.params.len() > 3 {
-            for i in 3..message.params.len() {
-                let member = &message.params[i];
-                if !member.is_empty() {
-                    // Add user to channel
-                    if let Err(e) = self.database.add_user_to_channel(member, &channel_name) {
-                        tracing::warn!("Failed to add user {} to channel {}: {}", member, channel_name, e);
-                    } else {
-                        member_count += 1;
-                    }
-                }
+        for member in &members {
+            if let Err(e) = self.database.add_user_to_channel(member, &channel_name) {
+                tracing::warn!("Failed to add user {} to channel {}: {}", member, channel_name, e);
+            } else {
+                member_count += 1;
             }
         }
         
@@ -1818,9 +2639,9 @@ impl Server {
     
     /// Handle core IRC commands
     async fn handle_core_command(&self, client_id: uuid::Uuid, message: Message) -> Result<()> {
-        let connection_handler = self.connection_handler.read().await;
-        let _client = connection_handler.get_client(&client_id)
-            .ok_or_else(|| Error::User("Client not found".to_string()))?;
+        if self.connection_handler.read().await.get_client(&client_id).is_none() {
+            return Err(Error::User("Client not found".to_string()));
+        }
         
         match message.command {
             MessageType::Password => {
@@ -1854,6 +2675,9 @@ impl Server {
             MessageType::Links => {
                 self.handle_links(client_id, message).await?;
             }
+            MessageType::Map => {
+                self.handle_map(client_id, message).await?;
+            }
             MessageType::Time => {
                 self.handle_time(client_id, message).await?;
             }
@@ -1904,12 +2728,27 @@ impl Server {
             MessageType::Userhost => {
                 self.handle_userhost(client_id, message).await?;
             }
+            MessageType::Mode => {
+                self.handle_mode(client_id, message).await?;
+            }
             MessageType::Lusers => {
                 self.handle_lusers(client_id, message).await?;
             }
             MessageType::Users => {
                 self.handle_users(client_id, message).await?;
             }
+            MessageType::Custom(ref cmd) if cmd.eq_ignore_ascii_case("SILENCE") => {
+                self.handle_silence(client_id, message).await?;
+            }
+            MessageType::Custom(ref cmd) if cmd.eq_ignore_ascii_case("RULES") => {
+                self.handle_rules(client_id).await?;
+            }
+            MessageType::Custom(ref cmd) if cmd.eq_ignore_ascii_case("LAG") => {
+                self.handle_lag(client_id, message).await?;
+            }
+            MessageType::Custom(ref cmd) if cmd.eq_ignore_ascii_case("CHECK") => {
+                self.handle_check(client_id, message).await?;
+            }
             // Server connection commands
             MessageType::Connect => {
                 self.handle_connect(client_id, message).await?;
@@ -1929,6 +2768,9 @@ impl Server {
             MessageType::Kill => {
                 self.handle_kill(client_id, message).await?;
             }
+            MessageType::Rehash => {
+                self.handle_rehash(client_id, message).await?;
+            }
             MessageType::ServerQuit => {
                 self.handle_operator_squit(client_id, message).await?;
             }
@@ -1937,8 +2779,21 @@ impl Server {
                 self.handle_initial_server_registration(client_id, message).await?;
             }
             _ => {
-                // Command not handled by core
+                // Nothing handled it; server links handshaking here are left alone
                 tracing::debug!("Unhandled command: {:?}", message.command);
+                let connection_handler = self.connection_handler.read().await;
+                let Some(client) = connection_handler.get_client(&client_id) else {
+                    return Ok(());
+                };
+                if client.connection_type == crate::client::ConnectionType::Client {
+                    // Clients probe for CAP before registering and expect 421 without it
+                    let reply = if client.is_registered() || message.command == MessageType::Cap {
+                        NumericReply::err_unknown_command(&message.command.to_string())
+                    } else {
+                        NumericReply::not_registered()
+                    };
+                    let _ = client.send(reply);
+                }
             }
         }
         
@@ -1947,74 +2802,135 @@ impl Server {
     
     /// Handle PASS command
     async fn handle_password(&self, client_id: uuid::Uuid, message: Message) -> Result<()> {
-        if message.params.is_empty() {
-            let error_msg = NumericReply::need_more_params("PASS");
-            let connection_handler = self.connection_handler.read().await;
-            if let Some(client) = connection_handler.get_client(&client_id) {
-                let _ = client.send(error_msg);
-            }
-            return Ok(());
-        }
-        
-        // Check if this is a server connection
-        let connection_handler = self.connection_handler.read().await;
-        if let Some(client) = connection_handler.get_client(&client_id) {
-            if client.connection_type == crate::client::ConnectionType::Server {
-                // Handle server password
-                return self.handle_server_password(client_id, message).await;
-            }
+        // Check if this is a server connection; the lock is released before
+        // the handlers below take it again
+        let is_server = self.connection_handler.read().await.get_client(&client_id)
+            .is_some_and(|client| client.connection_type == crate::client::ConnectionType::Server);
+        if is_server {
+            return self.handle_server_password(client_id, message).await;
         }
         
         // Check if password is required and correct for clients
-        if self.config.security.require_client_password {
-            if let Some(ref required_password) = self.config.security.client_password {
-                if message.params[0] != *required_password {
-                    let error_msg = NumericReply::password_mismatch();
-                    let connection_handler = self.connection_handler.read().await;
-                    if let Some(client) = connection_handler.get_client(&client_id) {
-                        let _ = client.send(error_msg);
-                    }
+        if self.config().security.require_client_password {
+            if let Some(ref required_password) = self.config().security.client_password {
+                let connection_handler = self.connection_handler.read().await;
+                let Some(client) = connection_handler.get_client(&client_id) else {
+                    return Ok(());
+                };
+                // A locked out IP is refused even with the right password
+                let locked = match client.ip() {
+                    Some(ip) => self.throttling_manager.auth_lockout(ip).await.is_some(),
+                    None => false,
+                };
+                if locked || message.params[0] != *required_password {
+                    let _ = client.send(NumericReply::password_mismatch());
+                    let module_manager = self.module_manager.read().await;
+                    module_manager.context().record_auth_failure(client, crate::AuthFailureKind::Password).await;
                     return Ok(());
                 }
             }
         }
         
-        // Update client state
+        // Registration checks the password once NICK and USER are in
         let mut connection_handler = self.connection_handler.write().await;
         if let Some(client) = connection_handler.get_client_mut(&client_id) {
-            client.set_state(ClientState::PasswordProvided);
+            client.registration.password_accepted = true;
         }
         
         Ok(())
     }
     
     /// Handle NICK command
-    async fn handle_nick(&self, client_id: uuid::Uuid, message: Message) -> Result<()> {
-        if message.params.is_empty() {
-            let error_msg = NumericReply::no_nickname_given();
-            let connection_handler = self.connection_handler.read().await;
-            if let Some(client) = connection_handler.get_client(&client_id) {
-                let _ = client.send(error_msg);
+    /// Send `message` once to every local client sharing a channel with `nick`
+    ///
+    /// `also` gets the message too, whether or not it shares a channel.
+    fn send_to_channel_peers(&self, nick: &str, message: &Message, also: Option<uuid::Uuid>) {
+        let _span = tracing::debug_span!("irc.broadcast", peers_of = nick).entered();
+        let mut recipients: HashSet<uuid::Uuid> = also.into_iter().collect();
+        for channel in self.database.get_user_channels(nick) {
+            for member in self.database.get_channel_users(&channel) {
+                if let Some(user) = self.database.get_user_by_nick(&member) {
+                    recipients.insert(user.id);
+                }
             }
-            return Ok(());
         }
-        
-        let nick = &message.params[0];
-        
-        // Validate nickname
-        if !self.is_valid_nickname(nick) {
-            let error_msg = NumericReply::erroneous_nickname(nick);
-            let connection_handler = self.connection_handler.read().await;
-            if let Some(client) = connection_handler.get_client(&client_id) {
-                let _ = client.send(error_msg);
-            }
-            return Ok(());
+        self.client_senders.send_each(recipients, message);
+    }
+
+    /// Rename a registered local user, telling them and everyone sharing a channel
+    ///
+    /// Returns the NICK to propagate to the other servers.
+    async fn rename_local_user(&self, client: &mut Client, mut user: User, nick: &str) -> Message {
+        let previous = user.clone();
+        let old_nick = std::mem::replace(&mut user.nick, nick.to_string());
+        client.user = Some(user.clone());
+        
+        // Update in database, which also renames channel membership
+        if let Err(e) = self.database.update_user(&user.id, user.clone()) {
+            tracing::error!("Failed to update user nickname in database: {}", e);
+        }
+        if let Err(e) = self.database.add_to_history(previous).await {
+            tracing::warn!("Failed to record {} for WHOWAS: {}", old_nick, e);
         }
         
-        // Check if nickname is in use
-        let nick_to_id = self.nick_to_id.read().await;
-        if nick_to_id.contains_key(nick) {
-            let error_msg = NumericReply::nickname_in_use(nick);
+        // Update in users map
+        {
+            let mut users = self.users.write().await;
+            users.insert(user.id, user.clone());
+        }
+        
+        // Update nick_to_id mapping
+        {
+            let mut nick_to_id = self.nick_to_id.write().await;
+            nick_to_id.remove(&old_nick);
+            nick_to_id.insert(nick.to_string(), user.id);
+        }
+        
+        // Tell the user and everyone sharing a channel with them, once each
+        let nick_msg = Message::with_prefix(
+            Prefix::User {
+                nick: old_nick.clone(),
+                user: user.username.clone(),
+                host: user.host.clone(),
+            },
+            MessageType::Nick,
+            vec![nick.to_string()],
+        );
+        self.send_to_channel_peers(nick, &nick_msg, Some(client.id));
+        
+        Message::with_prefix(
+            Prefix::Server(self.config().server.name.clone()),
+            MessageType::Nick,
+            vec![old_nick, nick.to_string()],
+        )
+    }
+
+    async fn handle_nick(&self, client_id: uuid::Uuid, message: Message) -> Result<()> {
+        if message.params.is_empty() {
+            let error_msg = NumericReply::no_nickname_given();
+            let connection_handler = self.connection_handler.read().await;
+            if let Some(client) = connection_handler.get_client(&client_id) {
+                let _ = client.send(error_msg);
+            }
+            return Ok(());
+        }
+        
+        let nick = &message.params[0];
+        
+        // Validate nickname
+        if !self.is_valid_nickname(nick) {
+            let error_msg = NumericReply::erroneous_nickname(nick);
+            let connection_handler = self.connection_handler.read().await;
+            if let Some(client) = connection_handler.get_client(&client_id) {
+                let _ = client.send(error_msg);
+            }
+            return Ok(());
+        }
+        
+        // Check if nickname is in use by someone else
+        let nick_to_id = self.nick_to_id.read().await;
+        if nick_to_id.get(nick).is_some_and(|id| *id != client_id) {
+            let error_msg = NumericReply::nickname_in_use(nick);
             let connection_handler = self.connection_handler.read().await;
             if let Some(client) = connection_handler.get_client(&client_id) {
                 let _ = client.send(error_msg);
@@ -2023,166 +2939,399 @@ impl Server {
         }
         drop(nick_to_id);
         
-        // Check if this is a nickname change or initial registration
-        let connection_handler = self.connection_handler.read().await;
-        let _old_nick = if let Some(client) = connection_handler.get_client(&client_id) {
-            client.user.as_ref().map(|u| u.nick.clone())
-        } else {
-            None
+        let mut connection_handler = self.connection_handler.write().await;
+        let Some(client) = connection_handler.get_client_mut(&client_id) else {
+            return Ok(());
         };
-        drop(connection_handler);
         
-        // Register nickname
-        let mut connection_handler = self.connection_handler.write().await;
-        if let Some(client) = connection_handler.get_client_mut(&client_id) {
-            client.set_state(ClientState::NickSet);
-            
-            // If user object exists, update the nickname
-            if let Some(ref mut user) = client.user {
-                let old_nick = user.nick.clone();
-                user.nick = nick.clone();
-                
-                // Update in database
-                if let Err(e) = self.database.update_user(&user.id, user.clone()) {
-                    tracing::error!("Failed to update user nickname in database: {}", e);
-                }
-                
-                // Update in users map
-                {
-                    let mut users = self.users.write().await;
-                    users.insert(user.id, user.clone());
-                }
-                
-                // Update nick_to_id mapping
-                {
-                    let mut nick_to_id = self.nick_to_id.write().await;
-                    nick_to_id.remove(&old_nick);
-                    nick_to_id.insert(nick.clone(), user.id);
-                }
-                
-                // Broadcast NICK change to local clients
-                let nick_msg = Message::with_prefix(
-                    Prefix::User {
-                        nick: old_nick.clone(),
-                        user: user.username.clone(),
-                        host: user.host.clone(),
-                    },
-                    MessageType::Nick,
-                    vec![nick.clone()],
-                );
-                
-                if let Err(e) = self.broadcast_system.broadcast_to_all(nick_msg, None).await {
-                    tracing::warn!("Failed to broadcast NICK change: {}", e);
-                }
-                
-                // Propagate NICK change to other servers
-                let nick_propagation = Message::with_prefix(
-                    Prefix::Server(self.config.server.name.clone()),
-                    MessageType::Nick,
-                    vec![old_nick, nick.clone()],
-                );
-                
-                drop(connection_handler); // Release the lock before async call
-                
-                if let Err(e) = self.server_connections.broadcast_to_servers(nick_propagation).await {
-                    tracing::warn!("Failed to propagate NICK change: {}", e);
-                }
-                
-                tracing::info!("Client {} nickname changed to: {}", client_id, nick);
-            } else {
-                tracing::debug!("Client {} nickname set to: {}", client_id, nick);
+        // Before registration the nickname is only remembered
+        if !client.is_registered() {
+            client.registration.nick = Some(nick.clone());
+            tracing::debug!("Client {} nickname set to: {}", client_id, nick);
+            return Ok(());
+        }
+        
+        let Some(user) = client.user.clone() else {
+            return Ok(());
+        };
+        if user.nick == *nick {
+            return Ok(());
+        }
+        
+        // Operators are exempt from the nick change limit
+        if !user.is_operator {
+            if let Err(wait) = self.nick_flood_control.check(client_id) {
+                let _ = client.send(NumericReply::nick_too_fast(&user.nick, nick, wait.as_secs().max(1)));
+                return Ok(());
             }
         }
         
+        let nick_propagation = self.rename_local_user(client, user, nick).await;
+        drop(connection_handler); // Release the lock before async call
+        
+        if let Err(e) = self.server_connections.broadcast_to_servers(nick_propagation).await {
+            tracing::warn!("Failed to propagate NICK change: {}", e);
+        }
+        
+        tracing::info!(%client_id, nick, "Nickname changed");
+        
         Ok(())
     }
     
     /// Handle USER command
     async fn handle_user(&self, client_id: uuid::Uuid, message: Message) -> Result<()> {
-        if message.params.len() < 4 {
-            let error_msg = NumericReply::need_more_params("USER");
-            let connection_handler = self.connection_handler.read().await;
-            if let Some(client) = connection_handler.get_client(&client_id) {
-                let _ = client.send(error_msg);
+        // params[1] and params[2] are unused (RFC 2812); the host comes from
+        // the connection and users always belong to this server
+        let mut connection_handler = self.connection_handler.write().await;
+        if let Some(client) = connection_handler.get_client_mut(&client_id) {
+            if client.is_registered() {
+                let _ = client.send(NumericReply::already_registered());
+                return Ok(());
             }
+            client.registration.username = Some(message.params[0].clone());
+            client.registration.realname = Some(message.params[3].clone());
+        }
+        
+        Ok(())
+    }
+    
+    /// Welcome a client once every registration step is done
+    ///
+    /// Runs after each message from an unregistered client. The password,
+    /// nickname and bans are checked against the looked-up host before 001.
+    async fn try_complete_registration(&self, client_id: uuid::Uuid) -> Result<()> {
+        if self.registration_holds.read().contains(&client_id) {
             return Ok(());
         }
         
-        let username = &message.params[0];
-        let hostname = &message.params[1];
-        let servername = &message.params[2];
-        let realname = &message.params[3];
+        let config = self.config();
+        let mut connection_handler = self.connection_handler.write().await;
+        let Some(client) = connection_handler.get_client_mut(&client_id) else {
+            return Ok(());
+        };
+        if client.is_registered()
+            || client.connection_type != crate::client::ConnectionType::Client
+            || !client.registration.is_ready()
+        {
+            return Ok(());
+        }
+        let (Some(nick), Some(username)) = (
+            client.registration.nick.clone(),
+            client.registration.effective_username(config.security.enable_ident),
+        ) else {
+            return Ok(());
+        };
+        let realname = client.registration.realname.clone().unwrap_or_default();
+        let real_host = client.registration.hostname.clone().unwrap_or_else(|| {
+            client.remote_addr.parse::<std::net::SocketAddr>()
+                .map(|addr| crate::lookup::ip_host(addr.ip()))
+                .unwrap_or_else(|_| client.remote_addr.clone())
+        });
         
-        // Create user
-        let user = User::new(
-            "".to_string(), // Nick will be set separately
-            username.clone(),
-            realname.clone(),
-            hostname.clone(),
-            servername.clone(),
-        );
+        if config.security.require_client_password
+            && config.security.client_password.is_some()
+            && !client.registration.password_accepted
+        {
+            let _ = client.send(NumericReply::password_mismatch());
+            let _ = client.send(Message::new(
+                MessageType::Error,
+                vec![format!("Closing Link: {} (Bad Password)", real_host)],
+            ));
+            connection_handler.remove_client(&client_id);
+            return Ok(());
+        }
         
-        // Update client
-        let mut connection_handler = self.connection_handler.write().await;
-        if let Some(client) = connection_handler.get_client_mut(&client_id) {
-            client.set_user(user);
-            client.set_state(ClientState::UserSet);
-            
-            // Check if client is fully registered
-            if client.has_nick() && client.has_user() {
-                client.set_state(ClientState::Registered);
-                
-                // Add user to database
-                let user = User::new(
-                    client.nickname().unwrap_or("unknown").to_string(),
-                    username.clone(),
-                    realname.clone(),
-                    hostname.clone(),
-                    servername.clone(),
-                );
-                self.database.add_user(user)?;
-                
-                // Send welcome message
-                let welcome_msg = NumericReply::welcome(
-                    &self.config.server.name,
-                    client.nickname().unwrap_or("unknown"),
-                    username,
-                    hostname,
-                );
-                let _ = client.send(welcome_msg);
-                
-                // Send MOTD after welcome message
-                let motd_messages = self.motd_manager.get_all_motd_messages(&self.config.server.name).await;
-                for motd_msg in motd_messages {
-                    let _ = client.send(motd_msg);
-                }
-                
-                // Broadcast user registration to all connected servers
-                let nick = client.nickname().unwrap_or("unknown");
-                let server_user_msg = Message::new(
-                    MessageType::UserBurst,
-                    vec![
-                        nick.to_string(),
-                        username.clone(),
-                        hostname.clone(),
-                        realname.clone(),
-                        self.config.server.name.clone(),
-                        client_id.to_string(),
-                        chrono::Utc::now().to_rfc3339(),
-                    ]
-                );
-                
-                if let Err(e) = self.server_connections.broadcast_to_servers(server_user_msg).await {
-                    tracing::warn!("Failed to broadcast USER registration to servers: {}", e);
-                }
-                
-                tracing::info!("User {} registered and broadcasted to servers", nick);
+        // Someone else may have registered the nickname since NICK
+        if self.nick_to_id.read().await.contains_key(&nick) {
+            let _ = client.send(NumericReply::nickname_in_use(&nick));
+            client.registration.nick = None;
+            return Ok(());
+        }
+        
+        let mut user = User::new(nick.clone(), username.clone(), realname.clone(), real_host.clone(), config.server.name.clone());
+        user.id = client_id;
+        user.set_account(self.registration_accounts.write().remove(&client_id));
+        self.apply_default_cloak(&mut user);
+        if client.encrypted {
+            user.modes.insert('z');
+        }
+        user.certfp = client.certfp.clone();
+        let opered = self.apply_login_privileges(client, &mut user).await;
+        
+        // Enforce K/G/D/X-lines and any other registered ban types
+        if let Some(ban) = self.ban_manager.check_user(&user) {
+            let reason = self.ban_manager.ban_reason(&ban);
+            tracing::info!(client_id = %client.id, nick = %user.nick, username = %user.username, host = %user.real_host, ban = %ban.ban_type, reason = %reason, "Registration rejected by ban");
+            let _ = client.send(NumericReply::youre_banned_creep(&ban.reason));
+            let _ = client.send(Message::new(
+                MessageType::Error,
+                vec![format!("Closing Link: {} ({})", user.real_host, reason)],
+            ));
+            connection_handler.remove_client(&client_id);
+            return Ok(());
+        }
+        
+        client.set_user(user.clone());
+        client.set_state(ClientState::Registered);
+        let remote_addr = client.remote_addr.clone();
+        
+        let visible_host = user.host.clone();
+        let registered_at = user.registered_at.timestamp();
+        self.database.add_user(user.clone())?;
+        self.users.write().await.insert(client_id, user.clone());
+        self.nick_to_id.write().await.insert(nick.clone(), client_id);
+        self.publish(ServerEvent::UserRegistered { user: user.clone() });
+        let local_users = self.get_local_user_count().await;
+        self.max_local_users.fetch_max(local_users, Ordering::Relaxed);
+        
+        // Unknown connections are counted from the handler we already hold
+        let unknown_connections = connection_handler.get_all_clients().values()
+            .filter(|client| !client.is_registered() && client.connection_type == crate::client::ConnectionType::Client)
+            .count() as u32;
+        if let Some(client) = connection_handler.get_client(&client_id) {
+            for reply in self.welcome_burst(&nick, &username, &visible_host, &client.class_name, client.port, unknown_connections).await {
+                let _ = client.send(reply);
+            }
+            if opered {
+                let _ = client.send(NumericReply::youre_oper());
+                let _ = client.send(NumericReply::snomask(&nick, &crate::snomask::snomask_string(&user.snomasks)));
             }
         }
         
+        // Broadcast user registration to all connected servers
+        let server_user_msg = Message::new(
+            MessageType::UserBurst,
+            vec![
+                nick.clone(),
+                username.clone(),
+                visible_host,
+                realname,
+                config.server.name.clone(),
+                client_id.to_string(),
+                registered_at.to_string(),
+                real_host.clone(),
+            ]
+        );
+        
+        if let Err(e) = self.server_connections.broadcast_to_servers(server_user_msg).await {
+            tracing::warn!("Failed to broadcast USER registration to servers: {}", e);
+        }
+        
+        tracing::info!(%client_id, nick = %nick, "User registered");
+        
+        let connect_notice = format!("Client connecting: {} ({}@{}) [{}]", nick, username, real_host, remote_addr);
+        self.snotice(SnoMask::Connects, &connect_notice);
+        if opered {
+            let account = user.account.as_deref().unwrap_or("*");
+            self.snotice(SnoMask::OperActions, &format!("{} ({}@{}) is now an operator (account {})", nick, username, real_host, account));
+        }
+        drop(connection_handler);
+        
+        self.module_manager.write().await.handle_user_registration(&user).await?;
+        
         Ok(())
     }
     
+    /// Apply the class and operator flags the auth provider attached to the client's login
+    ///
+    /// Providers such as LDAP map group membership to these through
+    /// `AUTH_META_CLASS` and `AUTH_META_OPER_FLAGS`. Returns whether the
+    /// user was made an operator.
+    async fn apply_login_privileges(&self, client: &mut Client, user: &mut User) -> bool {
+        let Some(account) = user.account.clone() else {
+            return false;
+        };
+        let Some(info) = self.auth_manager.authenticated(client.id).await
+            .filter(|info| info.username.eq_ignore_ascii_case(&account)) else {
+            return false;
+        };
+        let config = self.config();
+        
+        if let Some(name) = info.metadata.get(crate::AUTH_META_CLASS) {
+            match config.get_class(name) {
+                Some(class) => client.apply_class(class),
+                None => tracing::warn!("Login of {} names unknown class {}", account, name),
+            }
+        }
+        
+        let flags: HashSet<crate::config::OperatorFlag> = info.metadata.get(crate::AUTH_META_OPER_FLAGS)
+            .map(|flags| flags.split(',')
+                .filter(|flag| !flag.trim().is_empty())
+                .filter_map(|flag| flag.parse().map_err(|e| tracing::warn!("Login of {}: {}", account, e)).ok())
+                .collect())
+            .unwrap_or_default();
+        if flags.is_empty() {
+            return false;
+        }
+        if flags.contains(&crate::config::OperatorFlag::Administrator) {
+            user.add_mode_internal('a');
+        }
+        user.set_operator_flags(flags);
+        user.add_mode_internal('s');
+        user.snomasks = crate::snomask::apply_snomask_changes(&user.snomasks, &config.server.oper_snomask);
+        tracing::info!("{} is an operator by login to {} via {}", user.nick, account, info.provider);
+        true
+    }
+    
+    /// Everything a client is sent on registering: 001-005, LUSERS and the MOTD
+    ///
+    /// Replies are addressed to `nick` and come from this server. The MOTD is
+    /// the one for the client's connection `class` and `port`.
+    async fn welcome_burst(&self, nick: &str, username: &str, host: &str, class: &str, port: u16, unknown_connections: u32) -> Vec<Message> {
+        let config = self.config();
+        let (user_modes, channel_modes) = self.my_info_modes();
+        let mut burst = vec![
+            NumericReply::welcome(&config.server.name, nick, username, host),
+            NumericReply::your_host(&config.server.name, &config.server.version),
+            NumericReply::created(&config.server.name, &config.server.created),
+            NumericReply::my_info(&config.server.name, &config.server.version, &user_modes, &channel_modes),
+        ];
+        // Keep each 005 line to a dozen tokens, as clients expect
+        for tokens in self.isupport_tokens().chunks(12) {
+            burst.push(NumericReply::isupport(nick, tokens));
+        }
+        burst.extend(self.lusers_replies_with(unknown_connections).await);
+        burst.extend(self.motd_manager.connect_messages(&config.server.name, class, port).await);
+        
+        for reply in &mut burst {
+            reply.prefix = Some(Prefix::Server(config.server.name.clone()));
+            if let Some(target) = reply.params.first_mut() {
+                *target = nick.to_string();
+            }
+        }
+        burst
+    }
+    
+    /// User and channel modes listed in RPL_MYINFO (004)
+    fn my_info_modes(&self) -> (String, String) {
+        let mut user_modes: Vec<char> = "aiorBOsxz".chars()
+            .chain(crate::get_all_custom_modes().iter().map(|mode| mode.character))
+            .collect();
+        user_modes.sort_unstable();
+        user_modes.dedup();
+        
+        // Channel modes come from the channel module's CHANMODES and PREFIX tokens
+        let mut channel_modes: Vec<char> = Vec::new();
+        for token in self.module_isupport.read().iter() {
+            if let Some(modes) = token.strip_prefix("CHANMODES=") {
+                channel_modes.extend(modes.chars().filter(|c| c.is_ascii_alphabetic()));
+            } else if let Some(prefix) = token.strip_prefix("PREFIX=(") {
+                channel_modes.extend(prefix.chars().take_while(|c| *c != ')'));
+            }
+        }
+        channel_modes.sort_unstable();
+        channel_modes.dedup();
+        
+        (user_modes.into_iter().collect(), channel_modes.into_iter().collect())
+    }
+    
+    /// ISUPPORT (005) tokens advertised to clients on registration
+    fn isupport_tokens(&self) -> Vec<String> {
+        let mut tokens = vec![
+            format!("NETWORK={}", self.config().network.name),
+            format!("NICKLEN={}", self.config().server.max_nickname_length),
+            format!("CHANNELLEN={}", self.config().server.max_channel_name_length),
+            format!("TOPICLEN={}", self.config().server.max_topic_length),
+            "CHANTYPES=#&".to_string(),
+            format!("SILENCE={}", self.config().server.max_silence_entries),
+            "BOT=B".to_string(),
+        ];
+        tokens.extend(self.module_isupport.read().iter().cloned());
+        tokens
+    }
+    
+    /// Whether a local client enabled a capability with CAP REQ
+    fn client_has_capability(&self, client_id: &Uuid, capability: &str) -> bool {
+        self.client_capabilities.read().get(client_id).is_some_and(|enabled| enabled.contains(capability))
+    }
+    
+    /// Get the user/channel database
+    pub fn database(&self) -> &Arc<Database> {
+        &self.database
+    }
+    
+    /// Get the server connection manager
+    pub fn server_connections(&self) -> &Arc<ServerConnectionManager> {
+        &self.server_connections
+    }
+    
+    /// Get the outbound queues of local clients
+    pub fn client_senders(&self) -> &Arc<ClientSenders> {
+        &self.client_senders
+    }
+    
+    /// Get the broadcast system used to reach local clients
+    pub fn broadcast_system(&self) -> &Arc<BroadcastSystem> {
+        &self.broadcast_system
+    }
+    
+    /// Get the shared ban manager
+    pub fn ban_manager(&self) -> Arc<crate::BanManager> {
+        self.ban_manager.clone()
+    }
+    
+    /// Add a ban as this server and enforce it at once
+    ///
+    /// Network-wide ban types are sent to linked servers, and local users the
+    /// ban matches are disconnected unless its type keeps them connected.
+    /// Returns how many users were disconnected.
+    pub async fn add_ban(&self, entry: crate::BanEntry) -> Result<usize> {
+        self.ban_manager.add_ban(entry.clone())?;
+        let Some(ban_type) = self.ban_manager.get_type(&entry.ban_type) else {
+            return Ok(0);
+        };
+        self.snotice(SnoMask::Bans, &format!("{} added {} for [{}] [{}]", entry.set_by, ban_type.name, entry.mask, entry.reason));
+        
+        if ban_type.global {
+            let ban_msg = Message::with_prefix(
+                Prefix::Server(self.config().server.name.clone()),
+                MessageType::Custom(ban_type.name.clone()),
+                entry.to_params(),
+            );
+            if let Err(e) = self.server_connections.broadcast_to_servers(ban_msg).await {
+                tracing::warn!("Failed to broadcast {} {}: {}", ban_type.name, entry.mask, e);
+            }
+        }
+        if !ban_type.disconnect {
+            return Ok(0);
+        }
+        
+        let matching: Vec<Uuid> = self.users.read().await.iter()
+            .filter(|(_, user)| self.ban_manager.matching_ban(&ban_type.name, &entry.mask, user))
+            .map(|(client_id, _)| *client_id)
+            .collect();
+        let reason = self.ban_manager.ban_reason(&entry);
+        for client_id in &matching {
+            if let Err(e) = self.exit_client(*client_id, &reason, true).await {
+                tracing::warn!("Failed to disconnect {} for {}: {}", client_id, ban_type.name, e);
+            }
+        }
+        Ok(matching.len())
+    }
+    
+    /// The other servers on the network, nearest first
+    pub async fn network_servers(&self) -> Vec<crate::ServerNode> {
+        self.topology.read().await.servers().into_iter().cloned().collect()
+    }
+    
+    /// A snapshot of the server's traffic statistics
+    pub async fn statistics(&self) -> crate::ServerStatistics {
+        self.statistics_manager.statistics().read().await.clone()
+    }
+    
+    /// Build a host cloaker from the current configuration
+    fn host_cloaker(&self) -> crate::HostCloaker {
+        crate::HostCloaker::new(&self.config().modules.ip_cloak, &self.config().network.name)
+    }
+    
+    /// Cloak a newly registering user's host if cloaking is enabled by default
+    fn apply_default_cloak(&self, user: &mut User) {
+        let cloaker = self.host_cloaker();
+        if cloaker.enabled() {
+            user.set_cloak(cloaker.cloak(&user.real_host));
+        }
+    }
+    
     /// Handle PING command
     async fn handle_ping(&self, client_id: uuid::Uuid, message: Message) -> Result<()> {
         let pong_msg = Message::new(MessageType::Pong, message.params);
@@ -2217,44 +3366,85 @@ impl Server {
     
     /// Handle QUIT command
     async fn handle_quit(&self, client_id: uuid::Uuid, message: Message) -> Result<()> {
-        let quit_message = message.params.first().map(|s| s.as_str()).unwrap_or("Client quit");
-        
-        // Propagate QUIT to other servers before removing client
-        let connection_handler = self.connection_handler.read().await;
-        let should_propagate = if let Some(client) = connection_handler.get_client(&client_id) {
-            client.is_registered()
-        } else {
-            false
+        // Prefixed so a user can't pass their quit off as a server message
+        let reason = match message.params.first().filter(|reason| !reason.is_empty()) {
+            Some(reason) => format!("Quit: {}", reason),
+            None => "Client Quit".to_string(),
+        };
+        self.exit_client(client_id, &reason, true).await
+    }
+    
+    /// Disconnect a local client and clean up after it
+    ///
+    /// For a registered user the QUIT goes once to everyone sharing a
+    /// channel and to the linked servers, the user is recorded for WHOWAS
+    /// and modules see the disconnection; the nickname is free afterwards.
+    ///
+    /// `propagate` is false when the linked servers already know, e.g.
+    /// because a KILL was sent to them.
+    pub(crate) async fn exit_client(&self, client_id: uuid::Uuid, reason: &str, propagate: bool) -> Result<()> {
+        let mut connection_handler = self.connection_handler.write().await;
+        let Some(client) = connection_handler.remove_client(&client_id) else {
+            return Ok(());
         };
+        self.ctcp_flood_control.remove_client(&client_id);
+        self.nick_flood_control.remove_client(&client_id);
+        self.registration_holds.write().remove(&client_id);
+        self.registration_accounts.write().remove(&client_id);
+        
+        let user = client.is_registered().then(|| self.database.get_user(&client_id)).flatten();
+        let host = user.as_ref().map(|user| user.host.clone()).unwrap_or_else(|| client.remote_addr.clone());
+        let _ = client.send(Message::new(
+            MessageType::Error,
+            vec![format!("Closing Link: {} ({})", host, reason)],
+        ));
         drop(connection_handler);
+        let Some(user) = user else {
+            return Ok(());
+        };
         
-        if should_propagate {
-            let quit_propagation = Message::new(
+        self.remove_departed_user(&user, reason).await;
+        if propagate {
+            let quit_propagation = Message::with_prefix(
+                Prefix::Server(self.config().server.name.clone()),
                 MessageType::Quit,
-                vec![quit_message.to_string()]
+                vec![user.nick.clone(), reason.to_string()],
             );
             self.propagate_to_servers(quit_propagation).await?;
         }
+        self.send_snotice(SnoMask::Connects, &format!("Client exiting: {} ({}@{}) [{}]", user.nick, user.username, user.host, reason)).await?;
         
-        // Notify modules
-        let module_manager = self.module_manager.read().await;
-        if let Some(client) = self.connection_handler.read().await.get_client(&client_id) {
-            if let Some(_user) = client.get_user() {
-                // let _ = module_manager.handle_user_disconnection(user).await; // Commented out - needs mutable reference
-            }
+        if let Err(e) = self.module_manager.write().await.handle_user_disconnection(&user).await {
+            tracing::warn!("Module disconnect hooks failed for {}: {}", user.nick, e);
         }
-        drop(module_manager);
-        
-        // Remove client
-        let mut connection_handler = self.connection_handler.write().await;
-        connection_handler.remove_client(&client_id);
+        self.publish(ServerEvent::UserQuit { user, reason: reason.to_string() });
         
         Ok(())
     }
     
+    /// Forget a user who left the network, whether local or remote
+    ///
+    /// The QUIT goes to local users sharing a channel, the nickname is
+    /// released and the user is kept for WHOWAS.
+    async fn remove_departed_user(&self, user: &User, reason: &str) {
+        let quit_msg = Message::with_prefix(user.prefix(), MessageType::Quit, vec![reason.to_string()]);
+        let connection_handler = self.connection_handler.read().await;
+        self.send_to_channel_peers(&user.nick, &quit_msg, None);
+        drop(connection_handler);
+        
+        self.nick_to_id.write().await.remove(&user.nick);
+        self.users.write().await.remove(&user.id);
+        if let Err(e) = self.database.remove_user(user.id) {
+            tracing::warn!("Failed to remove user {} from database: {}", user.nick, e);
+        }
+        if let Err(e) = self.database.add_to_history(user.clone()).await {
+            tracing::warn!("Failed to record {} for WHOWAS: {}", user.nick, e);
+        }
+    }
+    
     /// Validate nickname
     fn is_valid_nickname(&self, nick: &str) -> bool {
-        if nick.is_empty() || nick.len() > self.config.server.max_nickname_length {
+        if nick.is_empty() || nick.len() > self.config().server.max_nickname_length {
             return false;
         }
 
@@ -2279,40 +3469,104 @@ impl Server {
     // Server query command handlers
     
     /// Handle ADMIN command
-    async fn handle_admin(&self, client_id: uuid::Uuid, _message: Message) -> Result<()> {
+    async fn handle_admin(&self, client_id: uuid::Uuid, message: Message) -> Result<()> {
         let connection_handler = self.connection_handler.read().await;
         if let Some(client) = connection_handler.get_client(&client_id) {
-            // Send admin information
-            let _ = client.send(NumericReply::admin_me(&self.config.server.name));
-            let _ = client.send(NumericReply::admin_loc1(&self.config.server.description));
-            let _ = client.send(NumericReply::admin_loc2("Rust IRC Daemon"));
-            let _ = client.send(NumericReply::admin_email("admin@example.com"));
+            // ADMIN [<target>]
+            match self.query_target(message.params.first()).await {
+                QueryTarget::Local => {}
+                QueryTarget::Remote(server) => {
+                    return self.send_remote_query(client, &server, MessageType::Admin, vec![server.clone()], None).await;
+                }
+                QueryTarget::Unknown(target) => {
+                    let _ = client.send(NumericReply::no_such_server(&target));
+                    return Ok(());
+                }
+            }
+            for reply in self.admin_replies() {
+                let _ = client.send(reply);
+            }
         }
         Ok(())
     }
     
+    /// ADMIN numerics for this server
+    fn admin_replies(&self) -> Vec<Message> {
+        vec![
+            NumericReply::admin_me(&self.config().server.name),
+            NumericReply::admin_loc1(&self.config().server.description),
+            NumericReply::admin_loc2("Rust IRC Daemon"),
+            NumericReply::admin_email("admin@example.com"),
+        ]
+    }
+    
     /// Handle VERSION command
-    async fn handle_version(&self, client_id: uuid::Uuid, _message: Message) -> Result<()> {
+    async fn handle_version(&self, client_id: uuid::Uuid, message: Message) -> Result<()> {
         let connection_handler = self.connection_handler.read().await;
         if let Some(client) = connection_handler.get_client(&client_id) {
-            let version_msg = NumericReply::version(
-                &self.config.server.name,
-                &self.config.server.version,
-                "0",
-                &self.config.server.name,
-                "Rust IRC Daemon",
-            );
-            let _ = client.send(version_msg);
+            // VERSION [<target>]
+            match self.query_target(message.params.first()).await {
+                QueryTarget::Local => {}
+                QueryTarget::Remote(server) => {
+                    return self.send_remote_query(client, &server, MessageType::Version, vec![server.clone()], None).await;
+                }
+                QueryTarget::Unknown(target) => {
+                    let _ = client.send(NumericReply::no_such_server(&target));
+                    return Ok(());
+                }
+            }
+            for reply in self.version_replies() {
+                let _ = client.send(reply);
+            }
         }
         Ok(())
     }
     
+    /// VERSION numerics for this server
+    fn version_replies(&self) -> Vec<Message> {
+        vec![NumericReply::version(
+            &self.config().server.name,
+            &self.config().server.version,
+            "0",
+            &self.config().server.name,
+            "Rust IRC Daemon",
+        )]
+    }
+    
     /// Handle STATS command - RFC 1459 compliant with module extensions
     async fn handle_stats(&self, client_id: uuid::Uuid, message: Message) -> Result<()> {
         let connection_handler = self.connection_handler.read().await;
         if let Some(client) = connection_handler.get_client(&client_id) {
-            let query = message.params.get(0).map(|s| s.as_str()).unwrap_or("");
+            let query = message.params.first().map(|s| s.as_str()).unwrap_or("");
+            
+            // STATS <query> [<target>]
+            match self.query_target(message.params.get(1)).await {
+                QueryTarget::Local => {}
+                QueryTarget::Remote(server) => {
+                    let closing = NumericReply::end_of_stats(query);
+                    return self.send_remote_query(client, &server, MessageType::Stats, vec![query.to_string(), server.clone()], Some(closing)).await;
+                }
+                QueryTarget::Unknown(target) => {
+                    let _ = client.send(NumericReply::no_such_server(&target));
+                    return Ok(());
+                }
+            }
             
+            let is_operator = client.get_user().is_some_and(|user| user.is_operator);
+            for reply in self.stats_replies(query, client_id, is_operator).await {
+                let _ = client.send(reply);
+            }
+        }
+        Ok(())
+    }
+    
+    /// STATS numerics for `query`, ending with 219
+    ///
+    /// `requester` is the asking user's ID, passed on to modules that answer
+    /// the query.
+    async fn stats_replies(&self, query: &str, requester: uuid::Uuid, is_operator: bool) -> Vec<Message> {
+        let mut replies = Vec::new();
+        {
             // Get current statistics
             let stats_manager = self.statistics_manager.clone();
             let stats_arc = stats_manager.statistics();
@@ -2322,72 +3576,116 @@ impl Server {
             match query {
                 "l" => {
                     // List of servers (links) - RFC 1459
-                    self.handle_stats_links(client, &stats).await?;
+                    replies.extend(self.stats_links_replies(is_operator).await);
                 }
                 "m" => {
                     // Commands usage statistics - RFC 1459
-                    self.handle_stats_commands(client, &stats).await?;
+                    let registered = self.module_manager.read().await.commands();
+                    replies.extend(self.stats_commands_replies(stats, &registered));
                 }
                 "o" => {
                     // List of operators currently online - RFC 1459
-                    self.handle_stats_operators(client).await?;
+                    replies.extend(self.stats_operators_replies(is_operator).await);
                 }
                 "u" => {
                     // Server uptime - RFC 1459
-                    let uptime_msg = NumericReply::stats_uptime(&self.config.server.name, stats.uptime_seconds());
-                    let _ = client.send(uptime_msg);
+                    replies.push(NumericReply::stats_uptime(&self.config().server.name, stats.uptime_seconds()));
                 }
                 "y" => {
                     // Class information - RFC 1459
-                    self.handle_stats_classes(client).await?;
+                    replies.extend(self.stats_classes_replies());
                 }
                 "c" => {
                     // Connection information - RFC 1459
-                    self.handle_stats_connections(client, &stats).await?;
+                    replies.push(self.stats_connections_reply(stats, is_operator));
+                }
+                "L" => {
+                    // Lag and idle time of local users - operators only
+                    if is_operator {
+                        let connection_handler = self.connection_handler.read().await;
+                        for (client, user) in self.local_users(&connection_handler) {
+                            let idle = (Utc::now() - user.last_activity).num_seconds().max(0);
+                            let lag = client.timing.lag.map(|lag| format!("{}ms", lag.as_millis())).unwrap_or_else(|| "-".to_string());
+                            replies.push(NumericReply::stats_module("L", &format!("{} lag {} idle {}s", user.nick, lag, idle)));
+                        }
+                    } else {
+                        replies.push(NumericReply::no_privileges());
+                    }
+                }
+                "P" => {
+                    // Clients per listening port, split by TLS and plaintext - operators only
+                    if is_operator {
+                        replies.extend(self.stats_ports_replies().await);
+                    } else {
+                        replies.push(NumericReply::no_privileges());
+                    }
+                }
+                "A" => {
+                    // Audit trail of privileged actions - operators only
+                    if is_operator {
+                        for event in self.audit_trail.recent() {
+                            replies.push(NumericReply::stats_module("A", &event.summary()));
+                        }
+                    } else {
+                        replies.push(NumericReply::no_privileges());
+                    }
+                }
+                "T" => {
+                    // Throttling exemptions and per-IP throttle state - operators only
+                    if is_operator {
+                        for line in self.throttling_manager.throttle_stats().await {
+                            replies.push(NumericReply::stats_module("T", &line));
+                        }
+                    } else {
+                        replies.push(NumericReply::no_privileges());
+                    }
+                }
+                "F" => {
+                    // Failed OPER, SASL and PASS attempts and lockouts - operators only
+                    if is_operator {
+                        for line in self.throttling_manager.auth_failure_stats().await {
+                            replies.push(NumericReply::stats_module("F", &line));
+                        }
+                    } else {
+                        replies.push(NumericReply::no_privileges());
+                    }
                 }
                 _ => {
                     // Check if any module handles this query
                     let mut module_manager = self.module_manager.write().await;
-                    if let Ok(module_responses) = module_manager.handle_stats_query(query, client_id, Some(self)).await {
+                    if let Ok(module_responses) = module_manager.handle_stats_query(query, requester, Some(self)).await {
                         for response in module_responses {
                             match response {
-                                ModuleStatsResponse::Stats(letter, _data) => {
-                                    let stats_msg = NumericReply::stats_commands(&letter, 0, 0, 0);
-                                    let _ = client.send(stats_msg);
+                                ModuleStatsResponse::Stats(letter, data) => {
+                                    replies.push(NumericReply::stats_module(&letter, &data));
                                 }
                                 ModuleStatsResponse::ModuleStats(module, data) => {
-                                    let stats_msg = NumericReply::stats_module(&module, &data);
-                                    let _ = client.send(stats_msg);
+                                    replies.push(NumericReply::stats_module(&module, &data));
                                 }
                             }
                         }
                     } else {
                         // Unknown query - send empty response
-                        let stats_msg = NumericReply::stats_commands("UNKNOWN", 0, 0, 0);
-                        let _ = client.send(stats_msg);
+                        replies.push(NumericReply::stats_commands("UNKNOWN", 0, 0, 0));
                     }
                 }
             }
-            
-            let end_msg = NumericReply::end_of_stats(query);
-            let _ = client.send(end_msg);
         }
-        Ok(())
+        
+        replies.push(NumericReply::end_of_stats(query));
+        replies
     }
     
-    /// Handle STATS l - Server links
-    async fn handle_stats_links(&self, client: &Client, _stats: &crate::ServerStatistics) -> Result<()> {
-        // Check if the requesting user is an operator
-        let users = self.users.read().await;
-        let requesting_user = users.get(&client.id);
-        let is_operator = requesting_user.map(|u| u.is_operator).unwrap_or(false);
+    /// STATS l - Server links
+    async fn stats_links_replies(&self, is_operator: bool) -> Vec<Message> {
+        let mut replies = Vec::new();
         
         // Get connected servers from server connection manager
         let connections = self.server_connections.get_all_connections().await;
         
         for connection in connections {
             if connection.is_registered() {
-                let stats_msg = if is_operator && self.config.server.show_server_details_in_stats {
+                let stats_msg = if is_operator && self.config().server.show_server_details_in_stats {
                     // Show detailed server information to operators (if configured)
                     NumericReply::stats_link_info_detailed(
                         &connection.info.name,
@@ -2418,37 +3716,49 @@ impl Server {
                         connection.time_online_seconds(),
                     )
                 };
-                let _ = client.send(stats_msg);
+                replies.push(stats_msg);
+                if is_operator && self.config().server.show_server_details_in_stats {
+                    if let Some(compression) = connection.compression.as_ref().filter(|stats| stats.is_active()) {
+                        replies.push(NumericReply::stats_link_compression(&connection.info.name, compression));
+                    }
+                }
             }
         }
         
-        Ok(())
+        replies
     }
     
-    /// Handle STATS m - Commands usage statistics
-    async fn handle_stats_commands(&self, client: &Client, stats: &crate::ServerStatistics) -> Result<()> {
-        let top_commands = stats.get_top_commands(10); // Top 10 commands
+    /// STATS m - Commands usage statistics
+    ///
+    /// The ten most used commands, then every registered module command
+    /// not among them, including those never used.
+    fn stats_commands_replies(&self, stats: &crate::ServerStatistics, registered: &[crate::CommandInfo]) -> Vec<Message> {
+        let mut replies = Vec::new();
+        let mut commands = stats.get_top_commands(10); // Top 10 commands
+        for command in registered {
+            if !commands.iter().any(|(name, _)| *name == command.spec.name) {
+                let usage = stats.get_command_stats().get(&command.spec.name).cloned().unwrap_or_default();
+                commands.push((command.spec.name.clone(), usage));
+            }
+        }
         
-        for (command, cmd_stats) in top_commands {
+        for (command, cmd_stats) in commands {
             let stats_msg = NumericReply::stats_commands(
                 &command,
                 cmd_stats.total_count().try_into().unwrap_or(u32::MAX),
                 (cmd_stats.total_bytes / cmd_stats.total_count().max(1)).try_into().unwrap_or(u32::MAX), // avg bytes per command
                 cmd_stats.remote_count.try_into().unwrap_or(u32::MAX),
             );
-            let _ = client.send(stats_msg);
+            replies.push(stats_msg);
         }
         
-        Ok(())
+        replies
     }
     
-    /// Handle STATS o - Operators currently online
-    async fn handle_stats_operators(&self, client: &Client) -> Result<()> {
+    /// STATS o - Operators currently online
+    async fn stats_operators_replies(&self, is_operator: bool) -> Vec<Message> {
         let users = self.users.read().await;
-        
-        // Check if the requesting user is an operator
-        let requesting_user = users.get(&client.id);
-        let is_operator = requesting_user.map(|u| u.is_operator).unwrap_or(false);
+        let mut replies = Vec::new();
         
         for user in users.values() {
             if user.is_operator {
@@ -2469,35 +3779,49 @@ impl Server {
                         "Operator",
                     )
                 };
-                let _ = client.send(stats_msg);
+                replies.push(stats_msg);
             }
         }
         
-        Ok(())
+        replies
     }
     
-    /// Handle STATS y - Class information
-    async fn handle_stats_classes(&self, client: &Client) -> Result<()> {
+    /// STATS y - Class information
+    fn stats_classes_replies(&self) -> Vec<Message> {
         // Default class information
-        let stats_msg = NumericReply::stats_yline(
+        vec![NumericReply::stats_yline(
             "default",
             120, // ping frequency in seconds
             600, // connect frequency in seconds
             1024, // max sendq
-        );
-        let _ = client.send(stats_msg);
-        
-        Ok(())
+        )]
     }
     
-    /// Handle STATS c - Connection information
-    async fn handle_stats_connections(&self, client: &Client, stats: &crate::ServerStatistics) -> Result<()> {
-        // Check if the requesting user is an operator
-        let users = self.users.read().await;
-        let requesting_user = users.get(&client.id);
-        let is_operator = requesting_user.map(|u| u.is_operator).unwrap_or(false);
-        
-        let stats_msg = if is_operator && self.config.server.show_server_details_in_stats {
+    /// STATS P - Clients per listening port, split by TLS and plaintext
+    async fn stats_ports_replies(&self) -> Vec<Message> {
+        let config = self.config();
+        let connection_handler = self.connection_handler.read().await;
+        let clients: Vec<&Client> = connection_handler.iter_clients()
+            .map(|(_, client)| client)
+            .filter(|client| !client.is_server())
+            .collect();
+
+        let mut replies: Vec<Message> = config.connection.ports.iter()
+            .map(|port| {
+                let count = clients.iter().filter(|client| client.port == port.port).count();
+                let transport = if port.tls { "tls" } else { "plaintext" };
+                NumericReply::stats_module("P", &format!("{} {} {:?} clients {}", port.port, transport, port.connection_type, count))
+            })
+            .collect();
+
+        let secure = clients.iter().filter(|client| client.encrypted).count();
+        replies.push(NumericReply::stats_module("P", &format!("total tls {} plaintext {}", secure, clients.len() - secure)));
+        replies
+    }
+
+    /// STATS c - Connection information
+    fn stats_connections_reply(&self, stats: &crate::ServerStatistics, is_operator: bool) -> Message {
+        if is_operator && self.config().server.show_server_details_in_stats {
             // Show detailed connection information to operators (if configured)
             NumericReply::stats_commands(
                 "CONNECTIONS",
@@ -2513,17 +3837,14 @@ impl Server {
                 0, // Hide byte counts
                 0, // Hide server count
             )
-        };
-        let _ = client.send(stats_msg);
-        
-        Ok(())
+        }
     }
     
     /// Handle MOTD command
     async fn handle_motd(&self, client_id: uuid::Uuid, _message: Message) -> Result<()> {
         let connection_handler = self.connection_handler.read().await;
         if let Some(client) = connection_handler.get_client(&client_id) {
-            let motd_messages = self.motd_manager.get_all_motd_messages(&self.config.server.name).await;
+            let motd_messages = self.motd_manager.motd_messages(&self.config().server.name, &client.class_name, client.port).await;
             
             for message in motd_messages {
                 let _ = client.send(message);
@@ -2532,44 +3853,121 @@ impl Server {
         Ok(())
     }
     
+    /// Handle RULES command
+    async fn handle_rules(&self, client_id: uuid::Uuid) -> Result<()> {
+        let connection_handler = self.connection_handler.read().await;
+        if let Some(client) = connection_handler.get_client(&client_id) {
+            for message in self.rules_manager.get_all_rules_messages(&self.config().server.name).await {
+                let _ = client.send(message);
+            }
+        }
+        Ok(())
+    }
+    
     /// Handle LINKS command
-    async fn handle_links(&self, client_id: uuid::Uuid, _message: Message) -> Result<()> {
+    async fn handle_links(&self, client_id: uuid::Uuid, message: Message) -> Result<()> {
         let connection_handler = self.connection_handler.read().await;
         if let Some(client) = connection_handler.get_client(&client_id) {
-            // For now, just show this server
-            let links_msg = NumericReply::links(
-                "*",
-                &self.config.server.name,
-                0, // hopcount
-                &self.config.server.description,
-            );
-            let _ = client.send(links_msg);
+            let nick = client.nickname().unwrap_or("*");
+            // LINKS [[remote] mask]
+            let mask = message.params.last().map(String::as_str).unwrap_or("*");
+            let counts = self.server_user_counts();
+            let local = &self.config().server.name;
+            if crate::utils::string::wildcard_match(mask, local) {
+                let info = Self::links_info(&self.config().server.description, counts.get(local).copied().unwrap_or(0));
+                let _ = client.send(NumericReply::links(nick, local, local, 0, &info));
+            }
+            let topology = self.topology.read().await;
+            for server in topology.tree() {
+                if crate::utils::string::wildcard_match(mask, &server.name) {
+                    let info = Self::links_info(&server.description, counts.get(&server.name).copied().unwrap_or(0));
+                    let _ = client.send(NumericReply::links(nick, &server.name, &server.uplink, server.hop_count, &info));
+                }
+            }
             
-            let end_msg = NumericReply::end_of_links("*");
+            let end_msg = NumericReply::end_of_links(mask);
             let _ = client.send(end_msg);
         }
         Ok(())
     }
+
+    /// Handle MAP command
+    async fn handle_map(&self, client_id: uuid::Uuid, _message: Message) -> Result<()> {
+        let connection_handler = self.connection_handler.read().await;
+        if let Some(client) = connection_handler.get_client(&client_id) {
+            let nick = client.nickname().unwrap_or("*");
+            let counts = self.server_user_counts();
+            let local = &self.config().server.name;
+            let _ = client.send(NumericReply::map(nick, &Self::map_line(local, 0, counts.get(local).copied().unwrap_or(0))));
+            let topology = self.topology.read().await;
+            for server in topology.tree() {
+                let users = counts.get(&server.name).copied().unwrap_or(0);
+                let _ = client.send(NumericReply::map(nick, &Self::map_line(&server.name, server.hop_count, users)));
+            }
+            let _ = client.send(NumericReply::map_end(nick));
+        }
+        Ok(())
+    }
+
+    /// Number of users on each server, keyed by server name
+    fn server_user_counts(&self) -> HashMap<String, usize> {
+        let mut counts = HashMap::new();
+        for user in self.database.get_all_users() {
+            *counts.entry(user.server).or_insert(0) += 1;
+        }
+        counts
+    }
+
+    /// LINKS server info: the description prefixed with the user count
+    fn links_info(description: &str, users: usize) -> String {
+        format!("[{} users] {}", users, description)
+    }
+
+    /// One MAP line, indented under its uplink by hop count
+    fn map_line(name: &str, hop_count: u32, users: usize) -> String {
+        let branch = if hop_count == 0 {
+            String::new()
+        } else {
+            format!("{}`-", "  ".repeat(hop_count as usize - 1))
+        };
+        format!("{}{} ({} users)", branch, name, users)
+    }
     
     /// Handle TIME command
-    async fn handle_time(&self, client_id: uuid::Uuid, _message: Message) -> Result<()> {
+    async fn handle_time(&self, client_id: uuid::Uuid, message: Message) -> Result<()> {
         let connection_handler = self.connection_handler.read().await;
         if let Some(client) = connection_handler.get_client(&client_id) {
-            let now = chrono::Utc::now();
-            let time_str = now.format("%Y-%m-%d %H:%M:%S UTC").to_string();
-            
-            let time_msg = NumericReply::time(&self.config.server.name, &time_str);
-            let _ = client.send(time_msg);
+            // TIME [<target>]
+            match self.query_target(message.params.first()).await {
+                QueryTarget::Local => {}
+                QueryTarget::Remote(server) => {
+                    return self.send_remote_query(client, &server, MessageType::Time, vec![server.clone()], None).await;
+                }
+                QueryTarget::Unknown(target) => {
+                    let _ = client.send(NumericReply::no_such_server(&target));
+                    return Ok(());
+                }
+            }
+            for reply in self.time_replies() {
+                let _ = client.send(reply);
+            }
         }
         Ok(())
     }
     
+    /// TIME numerics for this server
+    fn time_replies(&self) -> Vec<Message> {
+        let now = chrono::Utc::now();
+        let time_str = now.format("%Y-%m-%d %H:%M:%S UTC").to_string();
+        vec![NumericReply::time(&self.config().server.name, &time_str)]
+    }
+    
     /// Handle INFO command
     async fn handle_info(&self, client_id: uuid::Uuid, _message: Message) -> Result<()> {
         let connection_handler = self.connection_handler.read().await;
         if let Some(client) = connection_handler.get_client(&client_id) {
             let info_lines = vec![
-                format!("{} - Rust IRC Daemon", self.config.server.name),
+                format!("{} - Rust IRC Daemon", self.config().server.name),
                 "A modular IRC daemon written in Rust".to_string(),
                 "Supports RFC 1459 and IRCv3 extensions".to_string(),
                 "Modular architecture with plugin support".to_string(),
@@ -2594,19 +3992,198 @@ impl Server {
             // Trace this server
             let trace_msg = NumericReply::trace_server(
                 "0", // class
-                &self.config.server.name,
-                &self.config.server.version,
+                &self.config().server.name,
+                &self.config().server.version,
                 "0", // debug_level
-                &self.config.server.name,
+                &self.config().server.name,
             );
             let _ = client.send(trace_msg);
             
-            let end_msg = NumericReply::trace_end(&self.config.server.name, &self.config.server.version);
+            // Operators see every local user, everyone else only the operators
+            let is_operator = client.get_user().is_some_and(|user| user.is_operator);
+            for (local, user) in self.local_users(&connection_handler) {
+                if is_operator || user.is_operator {
+                    let idle = (Utc::now() - user.last_activity).num_seconds().max(0) as u64;
+                    let _ = client.send(NumericReply::trace_user(&local.class_name, &user.nick, user.is_operator, idle, local.timing.lag));
+                }
+            }
+            
+            let end_msg = NumericReply::trace_end(&self.config().server.name, &self.config().server.version);
             let _ = client.send(end_msg);
         }
         Ok(())
     }
     
+    /// Registered local clients with their database records, sorted by nickname
+    fn local_users<'a>(&self, connection_handler: &'a ConnectionHandler) -> Vec<(&'a Client, User)> {
+        let mut users: Vec<(&Client, User)> = connection_handler.iter_clients()
+            .filter(|(_, client)| client.is_registered())
+            .filter_map(|(id, client)| Some((client, self.database.get_user(id)?)))
+            .collect();
+        users.sort_by(|(_, a), (_, b)| a.nick.cmp(&b.nick));
+        users
+    }
+    
+    /// Handle LAG command: an operator asks for a local user's lag and idle time
+    async fn handle_lag(&self, client_id: uuid::Uuid, message: Message) -> Result<()> {
+        let connection_handler = self.connection_handler.read().await;
+        let Some(client) = connection_handler.get_client(&client_id) else {
+            return Ok(());
+        };
+        let requester = client.nickname().unwrap_or("*").to_string();
+        let target = &message.params[0];
+        
+        let local = self.database.get_user_by_nick(target)
+            .and_then(|user| Some((connection_handler.get_client(&user.id)?, user)));
+        let Some((target_client, user)) = local else {
+            let _ = client.send(NumericReply::no_such_nick(target));
+            return Ok(());
+        };
+        let idle = (Utc::now() - user.last_activity).num_seconds().max(0);
+        let lag = match target_client.timing.lag {
+            Some(lag) => format!("{}ms", lag.as_millis()),
+            None => "unknown (no PING answered yet)".to_string(),
+        };
+        let _ = client.send(self.server_notice(&requester, &format!("Lag for {}: {}, idle {}s", user.nick, lag, idle)));
+        Ok(())
+    }
+    
+    /// Handle CHECK command: an operator lists local connections from a host
+    ///
+    /// The target is an IP address or CIDR range, or a wildcard mask matched
+    /// against the real host and `nick!user@host`.
+    async fn handle_check(&self, client_id: uuid::Uuid, message: Message) -> Result<()> {
+        let connection_handler = self.connection_handler.read().await;
+        let Some(client) = connection_handler.get_client(&client_id) else {
+            return Ok(());
+        };
+        let requester = client.nickname().unwrap_or("*").to_string();
+        let target = &message.params[0];
+        let network = crate::ban_manager::parse_ip_mask(target);
+        
+        let mut connections: Vec<&Client> = connection_handler.iter_clients()
+            .map(|(_, other)| other)
+            .filter(|other| other.connection_type == crate::client::ConnectionType::Client)
+            .collect();
+        connections.sort_by_key(|other| other.timing.connected_at);
+        let mut found = 0;
+        for other in connections {
+            let ip = other.ip();
+            let user = self.database.get_user(&other.id);
+            let matches = match network {
+                Some((network, prefix)) => ip.is_some_and(|ip| crate::ban_manager::ip_in_network(ip, network, prefix)),
+                None => user.as_ref().is_some_and(|user| {
+                    crate::utils::string::wildcard_match(target, &user.real_host)
+                        || crate::utils::string::wildcard_match(target, &format!("{}!{}@{}", user.nick, user.username, user.real_host))
+                }),
+            };
+            if !matches {
+                continue;
+            }
+            found += 1;
+            let who = match &user {
+                Some(user) => format!("{}!{}@{}", user.nick, user.username, user.real_host),
+                None => "(unregistered)".to_string(),
+            };
+            let ip = ip.map(|ip| ip.to_string()).unwrap_or_else(|| other.remote_addr.clone());
+            let line = format!("{} [{}] port {}, connected {}s", who, ip, other.port, other.timing.connected_at.elapsed().as_secs());
+            let _ = client.send(self.server_notice(&requester, &line));
+        }
+        let _ = client.send(self.server_notice(&requester, &format!("End of CHECK {}: {} connection(s)", target, found)));
+        Ok(())
+    }
+    
+    /// Run a new client's reverse DNS, ident and DNSBL lookups, then let it
+    /// register
+    ///
+    /// The lookups share `security.lookup_timeout`; the client registers
+    /// with whatever they found in that time. Clients listed in a DNSBL
+    /// are disconnected instead.
+    async fn run_client_lookups(&self, client_id: Uuid) {
+        let addrs = self.connection_handler.read().await.get_client(&client_id).and_then(|client| {
+            Some((client.remote_addr.parse::<std::net::SocketAddr>().ok()?, client.local_addr.parse::<std::net::SocketAddr>().ok()?))
+        });
+        let Some((remote_addr, local_addr)) = addrs else {
+            return;
+        };
+        let config = self.config();
+        let senders = self.client_senders.clone();
+        let notice = move |text: &str| {
+            senders.send(&client_id, Message::new(MessageType::Notice, vec!["*".to_string(), text.to_string()]));
+        };
+        let budget = std::time::Duration::from_secs(config.security.lookup_timeout);
+        let lookup = self.lookup_service.lookup_client(remote_addr, local_addr, budget, &config.security.dnsbl, &notice).await;
+        
+        if let Some(dnsbl) = lookup.dnsbl {
+            self.snotice(SnoMask::Connects, &format!("Refused {}: listed in {}", remote_addr.ip(), dnsbl.zone));
+            if let Err(e) = self.exit_client(client_id, &dnsbl.reason, false).await {
+                tracing::debug!("Failed to disconnect DNSBL-listed client {}: {}", client_id, e);
+            }
+            return;
+        }
+        tracing::info!(
+            %client_id,
+            host = lookup.hostname.as_deref().unwrap_or(""),
+            ident = lookup.ident.as_deref().unwrap_or(""),
+            "Client lookups finished"
+        );
+        
+        {
+            let mut connection_handler = self.connection_handler.write().await;
+            let Some(client) = connection_handler.get_client_mut(&client_id) else {
+                return;
+            };
+            client.registration.hostname = lookup.hostname;
+            client.registration.ident = lookup.ident;
+            client.registration.lookups_pending = false;
+        }
+        // NICK and USER may already have arrived
+        if let Err(e) = self.try_complete_registration(client_id).await {
+            tracing::debug!("Failed to register {} after its lookups: {}", client_id, e);
+        }
+    }
+    
+    /// Enforce clone limits on a new client connection from `ip`
+    ///
+    /// Warns operators once a soft limit is reached and returns the reason
+    /// to refuse the connection once a hard limit is.
+    async fn check_clone_limits(&self, ip: std::net::IpAddr) -> Option<String> {
+        let limits = self.config.read().connection.clones.clone();
+        if self.throttling_manager.is_exempt(ip).await {
+            return None;
+        }
+        
+        // IPv4 addresses are only counted individually
+        let prefix = ip.is_ipv6().then_some(limits.ipv6_prefix.min(128));
+        let (mut same_ip, mut same_prefix) = (0, 0);
+        for (_, client) in self.connection_handler.read().await.iter_clients() {
+            if client.connection_type != crate::client::ConnectionType::Client {
+                continue;
+            }
+            let Some(other) = client.ip() else {
+                continue;
+            };
+            same_ip += usize::from(other == ip);
+            same_prefix += usize::from(prefix.is_some_and(|prefix| crate::ban_manager::ip_in_network(other, ip, prefix)));
+        }
+        
+        let mut checks = vec![(ip.to_string(), same_ip, limits.soft_limit_per_ip, limits.hard_limit_per_ip)];
+        if let Some(prefix) = prefix {
+            let network = crate::ban_manager::ip_network(ip, prefix);
+            checks.push((format!("{}/{}", network, prefix), same_prefix, limits.soft_limit_per_prefix, limits.hard_limit_per_prefix));
+        }
+        for (host, count, soft, hard) in checks {
+            if hard > 0 && count >= hard {
+                self.snotice(SnoMask::Connects, &format!("Refused clone from {}: {} connections from {} (limit {})", ip, count, host, hard));
+                return Some("Too many connections from your host".to_string());
+            }
+            if soft > 0 && count >= soft {
+                self.snotice(SnoMask::Connects, &format!("Possible clones: {} connections from {}", count + 1, host));
+            }
+        }
+        None
+    }
+    
     // User query command handlers
     
     /// Handle WHO command
@@ -2614,55 +4191,108 @@ impl Server {
         let connection_handler = self.connection_handler.read().await;
         if let Some(client) = connection_handler.get_client(&client_id) {
             let target = message.params.get(0).map(|s| s.as_str()).unwrap_or("*");
+            let viewer = self.requesting_user(client);
+            
+            // WHOX: second parameter of the form %fields[,token]
+            let whox = message.params.get(1)
+                .and_then(|options| options.split_once('%'))
+                .map(|(_, spec)| match spec.split_once(',') {
+                    Some((fields, token)) => (fields.to_string(), token.to_string()),
+                    None => (spec.to_string(), String::new()),
+                });
             
             // Check if target is a channel (starts with #)
-            if target.starts_with('#') {
+            let (channel, users) = if target.starts_with('#') {
                 // Channel WHO - get users in channel
-                let channel_users = self.database.get_channel_users(target);
-                for nick in channel_users {
-                    if let Some(user) = self.database.get_user_by_nick(&nick) {
-                        let who_msg = NumericReply::who_reply(
-                            target,
-                            &user.username,
-                            &user.host,
-                            &self.config.server.name,
-                            &user.nick,
-                            if user.is_away() { "G" } else { "H" },
-                            "0",
-                            &user.realname,
-                        );
-                        let _ = client.send(who_msg);
-                    }
-                }
+                let users = self.database.get_channel_users(target)
+                    .iter()
+                    .filter_map(|nick| self.database.get_user_by_nick(nick))
+                    .collect::<Vec<_>>();
+                (target, users)
             } else {
                 // User pattern WHO - search for matching users
-                let users = self.database.search_users(target);
-                for user in users {
-                    let who_msg = NumericReply::who_reply(
-                        target,
+                ("*", self.database.search_users(target))
+            };
+            
+            let mut replies = Vec::with_capacity(users.len() + 1);
+            for user in users {
+                let mut flags = if user.is_away() { "G" } else { "H" }.to_string();
+                if user.is_bot() {
+                    flags.push('B');
+                }
+                let host = user.host_for(viewer.as_ref());
+                let who_msg = match &whox {
+                    Some((fields, token)) => {
+                        NumericReply::whox_reply(self.whox_fields(&user, viewer.as_ref(), channel, &flags, fields, token))
+                    }
+                    None => NumericReply::who_reply(
+                        channel,
                         &user.username,
-                        &user.host,
-                        &self.config.server.name,
+                        host,
+                        &self.config().server.name,
                         &user.nick,
-                        if user.is_away() { "G" } else { "H" },
+                        &flags,
                         "0",
                         &user.realname,
-                    );
-                    let _ = client.send(who_msg);
-                }
+                    ),
+                };
+                replies.push(who_msg);
             }
+            replies.push(NumericReply::end_of_who(target));
             
-            let end_msg = NumericReply::end_of_who(target);
-            let _ = client.send(end_msg);
+            // Large results are streamed in batches (SAFELIST)
+            let safelist = &self.config().server.safelist;
+            client.send_paced(replies, safelist.batch_size, tokio::time::Duration::from_millis(safelist.batch_delay_ms));
         }
         Ok(())
     }
     
+    /// Build WHOX reply fields in the canonical `tcuihsnfdlaor` order
+    fn whox_fields(&self, user: &User, viewer: Option<&User>, channel: &str, flags: &str, fields: &str, token: &str) -> Vec<String> {
+        let idle = (Utc::now() - user.last_activity).num_seconds().max(0);
+        let mut reply = Vec::new();
+        for field in "tcuihsnfdlaor".chars().filter(|f| fields.contains(*f)) {
+            let value = match field {
+                't' => token.to_string(),
+                'c' => channel.to_string(),
+                'u' => user.username.clone(),
+                'i' => if user.can_see_real_host(viewer) {
+                    user.real_host.clone()
+                } else {
+                    "255.255.255.255".to_string()
+                },
+                'h' => user.host_for(viewer).to_string(),
+                's' => user.server.clone(),
+                'n' => user.nick.clone(),
+                'f' => format!("{}{}", flags, if user.is_operator { "*" } else { "" }),
+                'd' => "0".to_string(),
+                'l' => idle.to_string(),
+                'a' => "0".to_string(),
+                'o' => "n/a".to_string(),
+                _ => user.realname.clone(),
+            };
+            reply.push(value);
+        }
+        reply
+    }
+    
+    /// Look up the database record of the user behind a client, if registered
+    fn requesting_user(&self, client: &Client) -> Option<User> {
+        client.nickname().and_then(|nick| self.database.get_user_by_nick(nick))
+    }
+    
     /// Handle WHOIS command
+    ///
+    /// `WHOIS <server|nick> <nick>` asks the named server (or the server the
+    /// named user is on) and relays its answer once it arrives.
     async fn handle_whois(&self, client_id: uuid::Uuid, message: Message) -> Result<()> {
         let connection_handler = self.connection_handler.read().await;
         if let Some(client) = connection_handler.get_client(&client_id) {
-            let target_nick = message.params.get(0).map(|s| s.as_str()).unwrap_or("");
+            let (server_spec, target_nick) = match message.params.as_slice() {
+                [server, nick, ..] => (Some(server), nick.as_str()),
+                [nick] => (None, nick.as_str()),
+                [] => (None, ""),
+            };
             
             if target_nick.is_empty() {
                 let error_msg = NumericReply::need_more_params("WHOIS");
@@ -2670,6 +4300,18 @@ impl Server {
                 return Ok(());
             }
             
+            match self.query_target(server_spec).await {
+                QueryTarget::Local => {}
+                QueryTarget::Remote(server) => {
+                    let closing = NumericReply::end_of_whois(target_nick);
+                    return self.send_remote_query(client, &server, MessageType::Whois, vec![server.clone(), target_nick.to_string()], Some(closing)).await;
+                }
+                QueryTarget::Unknown(target) => {
+                    let _ = client.send(NumericReply::no_such_server(&target));
+                    return Ok(());
+                }
+            }
+            
             // Look up user in database
             if let Some(user) = self.database.get_user_by_nick(target_nick) {
                 // Check if the target user has spy privileges and notify them
@@ -2683,122 +4325,297 @@ impl Server {
                 } else {
                     None
                 };
-                
-                let whois_user_msg = NumericReply::whois_user(
-                    &user.nick,
-                    &user.username,
-                    &user.host,
-                    &user.realname,
-                );
-                let _ = client.send(whois_user_msg);
-                
-                let whois_server_msg = NumericReply::whois_server(
-                    &user.nick,
-                    &self.config.server.name,
-                    &self.config.server.description,
-                );
-                let _ = client.send(whois_server_msg);
-                
-                if user.is_operator {
-                    // Use admin string if user is administrator, otherwise use operator string
-                    let whois_msg = if user.is_administrator() {
-                        NumericReply::whois_operator_custom(&user.nick, &self.config.server.admin_whois_string)
-                    } else {
-                        NumericReply::whois_operator_custom(&user.nick, &self.config.server.oper_whois_string)
-                    };
-                    let _ = client.send(whois_msg);
+                for reply in self.whois_replies(requesting_user.as_ref(), &user).await {
+                    let _ = client.send(reply);
                 }
-                
-                // Show channels if requesting user is administrator
-                if let Some(req_user) = requesting_user {
-                    if req_user.is_administrator() {
-                        // Show all channels (including secret ones) for administrators
-                        let channels = self.database.get_user_channels(&user.nick);
-                        if !channels.is_empty() {
-                            let whois_channels_msg = NumericReply::whois_channels(
-                                &user.nick,
-                                &channels.join(" "),
-                            );
-                            let _ = client.send(whois_channels_msg);
-                        }
-                    } else {
-                        // Show only public channels for non-administrators
-                        let channels = self.get_public_channels_for_user(&user.nick).await;
-                        if !channels.is_empty() {
-                            let whois_channels_msg = NumericReply::whois_channels(
-                                &user.nick,
-                                &channels.join(" "),
-                            );
-                            let _ = client.send(whois_channels_msg);
+            } else {
+                let _ = client.send(NumericReply::no_such_nick(target_nick));
+            }
+            
+            let end_msg = NumericReply::end_of_whois(target_nick);
+            let _ = client.send(end_msg);
+        }
+        Ok(())
+    }
+    
+    /// Resolve the optional `<target>` of a query command
+    ///
+    /// The target names a server, or a user whose server should answer.
+    async fn query_target(&self, target: Option<&String>) -> QueryTarget {
+        let Some(target) = target else {
+            return QueryTarget::Local;
+        };
+        let server = if self.topology.read().await.contains(target) {
+            Some(target.clone())
+        } else {
+            self.database.get_user_by_nick(target).map(|user| user.server)
+        };
+        match server {
+            Some(server) if server != self.config().server.name => QueryTarget::Remote(server),
+            Some(_) => QueryTarget::Local,
+            None => QueryTarget::Unknown(target.clone()),
+        }
+    }
+    
+    /// Forward a query to a remote server and relay its answer when it arrives
+    ///
+    /// The remote numerics are collected through the network query manager;
+    /// if the server doesn't finish answering in time the client gets an
+    /// error and `closing`, the reply that would have ended the answer.
+    async fn send_remote_query(&self, client: &Client, server: &str, command: MessageType, params: Vec<String>, closing: Option<Message>) -> Result<()> {
+        let (Some(route), Some(requester)) = (self.route_to_server(server).await, self.requesting_user(client)) else {
+            let _ = client.send(NumericReply::no_such_server(server));
+            return Ok(());
+        };
+        let submitted = match (&command, params.get(1)) {
+            (MessageType::Whois, Some(nick)) => {
+                self.network_query_manager.query_whois(nick.clone(), client.id, vec![server.to_string()]).await
+            }
+            (MessageType::Whowas, Some(nicks)) => {
+                self.network_query_manager.query_whowas(nicks.clone(), client.id, vec![server.to_string()]).await
+            }
+            _ => self.network_query_manager.query_server_info(command.clone(), client.id, server.to_string()).await,
+        };
+        let request_id = match submitted {
+            Ok(request_id) => request_id,
+            Err(e) => {
+                tracing::warn!("Couldn't start remote {} for {}: {}", command, client.id, e);
+                let _ = client.send(NumericReply::remote_query_timeout(server));
+                if let Some(closing) = closing {
+                    let _ = client.send(closing);
+                }
+                return Ok(());
+            }
+        };
+        
+        let request = Message::with_prefix(requester.prefix(), command.clone(), params);
+        self.server_connections.send_to_server(&route, request).await?;
+        
+        let server_handle = self.clone();
+        let (client_id, server) = (client.id, server.to_string());
+        tokio::spawn(async move {
+            let responses = server_handle.network_query_manager.wait_for_query(&request_id).await;
+            let connection_handler = server_handle.connection_handler.read().await;
+            let Some(client) = connection_handler.get_client(&client_id) else {
+                return;
+            };
+            match responses {
+                Some(responses) => {
+                    for response in responses {
+                        if let crate::NetworkResponse::Numeric { message, .. } = response {
+                            let _ = client.send(message);
                         }
                     }
                 }
-                
-                // Show bot information if user is a bot
-                if user.is_bot() {
-                    if let Some(bot_info) = user.get_bot_info() {
-                        let whois_bot_msg = NumericReply::whois_bot(
-                            &user.nick,
-                            &bot_info.name,
-                            &bot_info.description.as_deref().unwrap_or("No description"),
-                        );
-                        let _ = client.send(whois_bot_msg);
-                        
-                        if let (Some(version), Some(capabilities)) = (&bot_info.version, Some(bot_info.capabilities.join(", "))) {
-                            let bot_info_msg = NumericReply::bot_info(
-                                &user.nick,
-                                version,
-                                &capabilities,
-                            );
-                            let _ = client.send(bot_info_msg);
-                        }
+                None => {
+                    tracing::debug!("Remote {} on {} timed out", command, server);
+                    let _ = client.send(NumericReply::remote_query_timeout(&server));
+                    if let Some(closing) = closing {
+                        let _ = client.send(closing);
                     }
                 }
-                
-                // Calculate idle time
-                let idle_seconds = (Utc::now() - user.last_activity).num_seconds() as u32;
-                let whois_idle_msg = NumericReply::whois_idle(
-                    &user.nick,
-                    &user.registered_at.format("%Y-%m-%d %H:%M:%S UTC").to_string(),
-                    &idle_seconds.to_string(),
-                );
-                let _ = client.send(whois_idle_msg);
-                
-                // Show channels user is in
+            }
+        });
+        Ok(())
+    }
+    
+    /// WHOIS numerics describing `user`, without the closing 318
+    async fn whois_replies(&self, requesting_user: Option<&User>, user: &User) -> Vec<Message> {
+        let mut replies = Vec::new();
+        
+        replies.push(NumericReply::whois_user(
+            &user.nick,
+            &user.username,
+            &display_host(&user.host),
+            &user.realname,
+        ));
+        
+        // Operators and the user themself also see the real host
+        if user.can_see_real_host(requesting_user) {
+            let real_host = display_host(&user.real_host);
+            replies.push(NumericReply::whois_actually(
+                &user.nick,
+                &format!("{}@{}", user.username, real_host),
+                &real_host,
+            ));
+        }
+        
+        // Like the real host, the certificate fingerprint is for operators
+        // and the user themself
+        if let Some(certfp) = user.certfp.as_deref().filter(|_| user.can_see_real_host(requesting_user)) {
+            replies.push(NumericReply::whois_certfp(&user.nick, certfp));
+        }
+        
+        // Remote users show the server they're on
+        let server_description = if user.server.is_empty() || user.server == self.config().server.name {
+            self.config().server.description.clone()
+        } else {
+            self.database.get_server(&user.server).map(|server| server.description).unwrap_or_default()
+        };
+        let server_name = if user.server.is_empty() { &self.config().server.name } else { &user.server };
+        replies.push(NumericReply::whois_server(&user.nick, server_name, &server_description));
+        
+        if let Some(away_message) = &user.away_message {
+            replies.push(NumericReply::away(&user.nick, away_message));
+        }
+        
+        if user.is_operator {
+            // Use admin string if user is administrator, otherwise use operator string
+            let whois_msg = if user.is_administrator() {
+                NumericReply::whois_operator_custom(&user.nick, &self.config().server.admin_whois_string)
+            } else {
+                NumericReply::whois_operator_custom(&user.nick, &self.config().server.oper_whois_string)
+            };
+            replies.push(whois_msg);
+        }
+        
+        // Show channels if requesting user is administrator
+        if let Some(req_user) = requesting_user {
+            if req_user.is_administrator() {
+                // Show all channels (including secret ones) for administrators
                 let channels = self.database.get_user_channels(&user.nick);
                 if !channels.is_empty() {
-                    let channels_str = channels.join(" ");
-                    let whois_channels_msg = NumericReply::whois_channels(
-                        &user.nick,
-                        &channels_str,
-                    );
-                    let _ = client.send(whois_channels_msg);
+                    replies.push(NumericReply::whois_channels(&user.nick, &channels.join(" ")));
                 }
             } else {
-                // User not found locally - try network-wide query if enabled
-                if self.config.broadcast.enable_network_queries {
-                    let servers = self.database.get_all_servers();
-                    let server_names: Vec<String> = servers.iter().map(|s| s.name.clone()).collect();
-                    
-                    if let Ok(_request_id) = self.network_query_manager.query_whois(
-                        target_nick.to_string(),
-                        client_id,
-                        server_names,
-                    ).await {
-                        // Queue the query and wait for responses
-                        // For now, just send "not found" message
-                        let end_msg = NumericReply::end_of_whois(target_nick);
-                        let _ = client.send(end_msg);
-                    }
-                } else {
-                    // No network queries enabled, just send "not found"
-                    let end_msg = NumericReply::end_of_whois(target_nick);
-                    let _ = client.send(end_msg);
+                // Show only public channels for non-administrators
+                let channels = self.get_public_channels_for_user(&user.nick).await;
+                if !channels.is_empty() {
+                    replies.push(NumericReply::whois_channels(&user.nick, &channels.join(" ")));
                 }
             }
-            
-            let end_msg = NumericReply::end_of_whois(target_nick);
-            let _ = client.send(end_msg);
+        }
+        
+        // Show bot information if user is a bot
+        if user.is_bot() {
+            replies.push(NumericReply::whois_bot(&user.nick, &self.config().network.name));
+            if let Some(bot_info) = user.get_bot_info() {
+                replies.push(NumericReply::whois_bot_name(
+                    &user.nick,
+                    &bot_info.name,
+                    bot_info.description.as_deref().unwrap_or("No description"),
+                ));
+                
+                if let (Some(version), Some(capabilities)) = (&bot_info.version, Some(bot_info.capabilities.join(", "))) {
+                    replies.push(NumericReply::bot_info(&user.nick, version, &capabilities));
+                }
+            }
+        }
+        
+        // Calculate idle time
+        let idle_seconds = (Utc::now() - user.last_activity).num_seconds() as u32;
+        replies.push(NumericReply::whois_idle(
+            &user.nick,
+            &user.registered_at.format("%Y-%m-%d %H:%M:%S UTC").to_string(),
+            &idle_seconds.to_string(),
+        ));
+        
+        // Show channels user is in
+        let channels = self.database.get_user_channels(&user.nick);
+        if !channels.is_empty() {
+            replies.push(NumericReply::whois_channels(&user.nick, &channels.join(" ")));
+        }
+        
+        replies
+    }
+    
+    /// Answer a WHOIS, WHOWAS, ADMIN, VERSION, TIME or STATS relayed by another server
+    ///
+    /// Requests for another server are passed along; ours are answered with
+    /// numerics addressed to the requesting user and sent back down the link.
+    async fn handle_server_query_received(&self, server_name: &str, message: Message) -> Result<()> {
+        // STATS carries its query letter before the target
+        let target_index = if message.command == MessageType::Stats { 1 } else { 0 };
+        let Some(target_server) = message.params.get(target_index) else {
+            tracing::warn!("Received malformed {} from server {}", message.command, server_name);
+            return Ok(());
+        };
+        let Some(requester) = self.remote_source_user(server_name, &message) else {
+            return Ok(());
+        };
+        let local = self.config().server.name.clone();
+        
+        if !target_server.eq_ignore_ascii_case(&local) {
+            match self.route_to_server(target_server).await {
+                Some(route) if route != server_name => {
+                    self.server_connections.send_to_server(&route, message.clone()).await?;
+                }
+                _ => {
+                    let mut reply = NumericReply::no_such_server(target_server);
+                    reply.prefix = Some(Prefix::Server(local));
+                    reply.params[0] = requester.nick.clone();
+                    self.server_connections.send_to_server(server_name, reply).await?;
+                }
+            }
+            return Ok(());
+        }
+        
+        let replies = match message.command {
+            MessageType::Whois => {
+                let Some(target_nick) = message.params.get(1) else {
+                    tracing::warn!("Received malformed WHOIS from server {}", server_name);
+                    return Ok(());
+                };
+                let mut replies = match self.database.get_user_by_nick(target_nick) {
+                    Some(user) => self.whois_replies(Some(&requester), &user).await,
+                    None => vec![NumericReply::no_such_nick(target_nick)],
+                };
+                replies.push(NumericReply::end_of_whois(target_nick));
+                replies
+            }
+            MessageType::Whowas => {
+                let Some(nicks) = message.params.get(1) else {
+                    tracing::warn!("Received malformed WHOWAS from server {}", server_name);
+                    return Ok(());
+                };
+                let count = message.params.get(2).and_then(|count| count.parse::<usize>().ok()).unwrap_or(0);
+                let mut replies = self.whowas_replies(Some(&requester), nicks, count).await;
+                replies.push(NumericReply::end_of_whowas(nicks));
+                replies
+            }
+            MessageType::Admin => self.admin_replies(),
+            MessageType::Version => self.version_replies(),
+            MessageType::Time => self.time_replies(),
+            MessageType::Stats => {
+                let query = message.params.first().map(String::as_str).unwrap_or("");
+                self.stats_replies(query, requester.id, requester.is_operator).await
+            }
+            _ => return Ok(()),
+        };
+        for mut reply in replies {
+            reply.prefix = Some(Prefix::Server(local.clone()));
+            reply.params[0] = requester.nick.clone();
+            self.server_connections.send_to_server(server_name, reply).await?;
+        }
+        Ok(())
+    }
+    
+    /// Handle a numeric reply relayed by another server
+    ///
+    /// Numerics for a user on another server are passed along. Those for a
+    /// local user are collected if they answer a pending remote query from
+    /// that server, otherwise delivered straight away.
+    async fn handle_server_numeric_received(&self, server_name: &str, message: Message) -> Result<()> {
+        let Some(target_user) = message.params.first().and_then(|nick| self.database.get_user_by_nick(nick)) else {
+            tracing::debug!("Dropping numeric {} from {} for unknown user", message.command, server_name);
+            return Ok(());
+        };
+        if target_user.server != self.config().server.name {
+            self.route_to_user(&target_user, message).await;
+            return Ok(());
+        }
+        
+        let source = match &message.prefix {
+            Some(Prefix::Server(source)) => source.clone(),
+            _ => server_name.to_string(),
+        };
+        if self.network_query_manager.collect_numeric(target_user.id, &source, message.clone()).await {
+            return Ok(());
+        }
+        
+        let connection_handler = self.connection_handler.read().await;
+        if let Some(client) = connection_handler.get_client(&target_user.id) {
+            let _ = client.send(message);
         }
         Ok(())
     }
@@ -2807,64 +4624,65 @@ impl Server {
     async fn handle_whowas(&self, client_id: uuid::Uuid, message: Message) -> Result<()> {
         let connection_handler = self.connection_handler.read().await;
         if let Some(client) = connection_handler.get_client(&client_id) {
-            let target_nick = message.params.get(0).map(|s| s.as_str()).unwrap_or("");
-            
-            if target_nick.is_empty() {
+            // WHOWAS <nick>{,<nick>} [<count> [<target>]]
+            let nicks = message.params[0].as_str();
+            if nicks.is_empty() {
                 let error_msg = NumericReply::need_more_params("WHOWAS");
                 let _ = client.send(error_msg);
                 return Ok(());
             }
+            // A count that isn't a positive number means every entry
+            let count = message.params.get(1).and_then(|count| count.parse::<usize>().ok()).unwrap_or(0);
             
-            // Look up user in history database
-            let history_entries = self.database.get_user_history(target_nick).await;
-            
-            if !history_entries.is_empty() {
-                for entry in history_entries {
-                    let whowas_msg = NumericReply::whowas_user(
-                        &entry.user.nick,
-                        &entry.user.username,
-                        &entry.user.host,
-                        &entry.user.realname,
-                    );
-                    let _ = client.send(whowas_msg);
+            match self.query_target(message.params.get(2)).await {
+                QueryTarget::Local => {}
+                QueryTarget::Remote(server) => {
+                    let closing = NumericReply::end_of_whowas(nicks);
+                    let params = vec![server.clone(), nicks.to_string(), count.to_string()];
+                    return self.send_remote_query(client, &server, MessageType::Whowas, params, Some(closing)).await;
                 }
-            } else if self.config.broadcast.enable_network_queries {
-                // User not found locally - try network-wide query
-                let servers = self.database.get_all_servers();
-                let server_names: Vec<String> = servers.iter().map(|s| s.name.clone()).collect();
-                
-                if let Ok(_request_id) = self.network_query_manager.query_whowas(
-                    target_nick.to_string(),
-                    client_id,
-                    server_names,
-                ).await {
-                    // Queue the query and wait for responses
-                    // For now, just send "not found" message
-                    let end_msg = NumericReply::end_of_whowas(target_nick);
-                    let _ = client.send(end_msg);
+                QueryTarget::Unknown(target) => {
+                    let _ = client.send(NumericReply::no_such_server(&target));
+                    return Ok(());
                 }
-            } else {
-                // No network queries enabled, just send "not found"
-                let end_msg = NumericReply::end_of_whowas(target_nick);
-                let _ = client.send(end_msg);
             }
             
-            let end_msg = NumericReply::end_of_whowas(target_nick);
-            let _ = client.send(end_msg);
+            let viewer = self.requesting_user(client);
+            for reply in self.whowas_replies(viewer.as_ref(), nicks, count).await {
+                let _ = client.send(reply);
+            }
+            let _ = client.send(NumericReply::end_of_whowas(nicks));
         }
         Ok(())
     }
     
+    /// WHOWAS numerics for comma-separated `nicks`, without the closing 369
+    ///
+    /// Each nickname gets at most `count` entries, newest first, or all of
+    /// them when `count` is 0.
+    async fn whowas_replies(&self, viewer: Option<&User>, nicks: &str, count: usize) -> Vec<Message> {
+        let mut replies = Vec::new();
+        for nick in nicks.split(',').filter(|nick| !nick.is_empty()) {
+            let history = self.database.get_user_history(nick).await;
+            if history.is_empty() {
+                replies.push(NumericReply::was_no_such_nick(nick));
+                continue;
+            }
+            let limit = if count == 0 { history.len() } else { count };
+            for entry in history.iter().rev().take(limit) {
+                let user = &entry.user;
+                replies.push(NumericReply::whowas_user(&user.nick, &user.username, user.host_for(viewer), &user.realname));
+                let signoff = entry.disconnect_time.format("%a %b %e %H:%M:%S %Y").to_string();
+                replies.push(NumericReply::whois_server(&user.nick, &user.server, &signoff));
+            }
+        }
+        replies
+    }
+    
     /// Handle PRIVMSG command
     async fn handle_privmsg(&self, client_id: uuid::Uuid, message: Message) -> Result<()> {
         let connection_handler = self.connection_handler.read().await;
         if let Some(client) = connection_handler.get_client(&client_id) {
-            if !client.is_registered() {
-                let error_msg = NumericReply::not_registered();
-                let _ = client.send(error_msg);
-                return Ok(());
-            }
-            
             if message.params.len() < 2 {
                 let error_msg = NumericReply::no_recipients("PRIVMSG");
                 let _ = client.send(error_msg);
@@ -2880,6 +4698,16 @@ impl Server {
                 return Ok(());
             }
             
+            if let Some(ctcp) = crate::Ctcp::parse(text) {
+                if !self.check_ctcp_flood(client_id, client, &ctcp) {
+                    return Ok(());
+                }
+                if target.eq_ignore_ascii_case(&self.config().server.name) {
+                    self.reply_to_server_ctcp(client, &ctcp);
+                    return Ok(());
+                }
+            }
+            
             // Get sender information
             let sender_nick = client.nickname().unwrap_or("unknown");
             let sender_user = client.username().unwrap_or("unknown");
@@ -2892,26 +4720,45 @@ impl Server {
                 host: sender_host.to_string(),
             };
             
-            let _privmsg = Message::with_prefix(
+            let sender_nick = sender_nick.to_string();
+            let privmsg = Message::with_prefix(
                 sender_prefix,
                 MessageType::PrivMsg,
                 vec![target.to_string(), text.to_string()],
             );
+            // Delivery goes through the shared queues, not the connection handler
+            drop(connection_handler);
+            
             
             // Check if target is a channel or user
             if target.starts_with('#') || target.starts_with('&') || target.starts_with('+') || target.starts_with('!') {
                 // Channel message - delegate to channel module if available
                 // For now, just log it
                 tracing::info!("PRIVMSG to channel {}: {}", target, text);
+                self.publish_message_sent(&sender_nick, target, text, false);
             } else {
                 // Private message to user
-                if let Some(_target_user) = self.database.get_user_by_nick(target) {
-                    // Find the target user's client and send the message
-                    // For now, just log it
-                    tracing::info!("PRIVMSG from {} to {}: {}", sender_nick, target, text);
-                } else {
-                    let error_msg = NumericReply::no_such_nick(target);
-                    let _ = client.send(error_msg);
+                match self.resolve_message_target(&sender_nick, target).await {
+                    Ok(target_user) => {
+                        // Messages from silenced users are dropped without telling the sender
+                        if self.is_silenced_by(&target_user, &sender_nick) {
+                            tracing::debug!("PRIVMSG from {} to {} dropped by SILENCE", sender_nick, target);
+                            return Ok(());
+                        }
+                        let mut privmsg = privmsg;
+                        privmsg.params[0] = target_user.nick.clone();
+                        if !self.client_senders.send(&target_user.id, privmsg.clone()) {
+                            self.route_to_user(&target_user, privmsg).await;
+                        }
+                        // Remote users' away messages arrive with S2S AWAY, so this covers them too
+                        if let Some(away_message) = &target_user.away_message {
+                            self.client_senders.send(&client_id, NumericReply::away(&target_user.nick, away_message));
+                        }
+                        self.publish_message_sent(&sender_nick, target, text, false);
+                    }
+                    Err(reply) => {
+                        self.client_senders.send(&client_id, reply);
+                    }
                 }
             }
         }
@@ -2941,6 +4788,13 @@ impl Server {
                 return Ok(());
             }
             
+            // CTCP replies count against the same limit as requests
+            if let Some(ctcp) = crate::Ctcp::parse(text) {
+                if !self.check_ctcp_flood(client_id, client, &ctcp) {
+                    return Ok(());
+                }
+            }
+            
             // Get sender information
             let sender_nick = client.nickname().unwrap_or("unknown");
             let sender_user = client.username().unwrap_or("unknown");
@@ -2953,20 +4807,34 @@ impl Server {
                 host: sender_host.to_string(),
             };
             
-            let _notice = Message::with_prefix(
+            let sender_nick = sender_nick.to_string();
+            let notice = Message::with_prefix(
                 sender_prefix,
                 MessageType::Notice,
                 vec![target.to_string(), text.to_string()],
             );
+            // Delivery goes through the shared queues, not the connection handler
+            drop(connection_handler);
+            
             
             // Check if target is a channel or user
             if target.starts_with('#') || target.starts_with('&') || target.starts_with('+') || target.starts_with('!') {
                 // Channel notice - delegate to channel module if available
                 tracing::info!("NOTICE to channel {}: {}", target, text);
+                self.publish_message_sent(&sender_nick, target, text, true);
             } else {
                 // Private notice to user
-                if let Some(_target_user) = self.database.get_user_by_nick(target) {
-                    tracing::info!("NOTICE from {} to {}: {}", sender_nick, target, text);
+                if let Ok(target_user) = self.resolve_message_target(&sender_nick, target).await {
+                    if self.is_silenced_by(&target_user, &sender_nick) {
+                        tracing::debug!("NOTICE from {} to {} dropped by SILENCE", sender_nick, target);
+                        return Ok(());
+                    }
+                    let mut notice = notice;
+                    notice.params[0] = target_user.nick.clone();
+                    if !self.client_senders.send(&target_user.id, notice.clone()) {
+                        self.route_to_user(&target_user, notice).await;
+                    }
+                    self.publish_message_sent(&sender_nick, target, text, true);
                 }
                 // NOTICE doesn't send error replies for non-existent users
             }
@@ -2974,62 +4842,360 @@ impl Server {
         Ok(())
     }
     
-    /// Handle AWAY command
-    async fn handle_away(&self, client_id: uuid::Uuid, message: Message) -> Result<()> {
+    /// Tell event subscribers about a PRIVMSG or NOTICE from a local user
+    fn publish_message_sent(&self, from: &str, target: &str, text: &str, notice: bool) {
+        self.publish(ServerEvent::MessageSent {
+            from: from.to_string(),
+            target: target.to_string(),
+            text: text.to_string(),
+            notice,
+        });
+    }
+    
+    /// Find the user a private message is for
+    ///
+    /// `nick@server` reaches the user only while they're on that server, so a
+    /// message meant for services can't go to someone who took the nick.
+    /// Otherwise the reply to send back is returned.
+    async fn resolve_message_target(&self, sender_nick: &str, target: &str) -> std::result::Result<User, Message> {
+        let Some((nick, server)) = target.split_once('@') else {
+            return self.database.get_user_by_nick(target).ok_or_else(|| NumericReply::no_such_nick(target));
+        };
+        match self.database.get_user_by_nick(nick) {
+            Some(user) if user.server.eq_ignore_ascii_case(server) => Ok(user),
+            _ if self.config().is_super_server(server) && !self.topology.read().await.contains(server) => {
+                Err(NumericReply::services_down(sender_nick, target))
+            }
+            _ => Err(NumericReply::no_such_nick(target)),
+        }
+    }
+    
+    /// Send a message towards a remote user through the link their server is behind
+    async fn route_to_user(&self, target_user: &User, message: Message) {
+        let Some(link) = self.route_to_server(&target_user.server).await else {
+            tracing::debug!("No route to {} for {} ({})", target_user.server, target_user.nick, message.command);
+            return;
+        };
+        if let Err(e) = self.server_connections.send_to_server(&link, message).await {
+            tracing::warn!("Failed to route {} to {} via {}: {}", target_user.nick, target_user.server, link, e);
+        }
+    }
+    
+    /// Handle a PRIVMSG or NOTICE for a user, relayed by another server
+    ///
+    /// Delivered locally when the target is ours, otherwise passed on along
+    /// the route to the target's server.
+    async fn handle_server_user_message(&self, server_name: &str, message: Message) -> Result<()> {
+        let Some(target) = message.params.first() else {
+            return Ok(());
+        };
+        let Some(target_user) = self.database.get_user_by_nick(target) else {
+            tracing::debug!("Relayed {} from {} for unknown user {}", message.command, server_name, target);
+            return Ok(());
+        };
+        
+        if target_user.server == self.config().server.name {
+            let connection_handler = self.connection_handler.read().await;
+            if let Some(target_client) = connection_handler.get_client(&target_user.id) {
+                let _ = target_client.send(message);
+            }
+        } else if self.route_to_server(&target_user.server).await.as_deref() != Some(server_name) {
+            self.route_to_user(&target_user, message).await;
+        }
+        Ok(())
+    }
+    
+    /// Handle SVSNICK, SVSMODE, SVSHOST, SVSJOIN or SVSPART from services
+    ///
+    /// Only honoured when it comes from a super server, directly or through
+    /// the link the super server is behind. SVSMODE and SVSHOST change state
+    /// every server keeps, so they go everywhere; the rest go to the target's
+    /// own server, which acts for the user and propagates the result as usual.
+    async fn handle_services_command(&self, server_name: &str, message: Message) -> Result<()> {
+        let MessageType::Custom(command) = &message.command else {
+            return Ok(());
+        };
+        let origin = match &message.prefix {
+            Some(Prefix::Server(origin)) => origin.clone(),
+            _ => server_name.to_string(),
+        };
+        let behind_link = origin == server_name || self.route_to_server(&origin).await.as_deref() == Some(server_name);
+        if !behind_link || !self.config().is_super_server(&origin) {
+            tracing::warn!("Ignoring {} from {} via {}: not a services server", command, origin, server_name);
+            return Ok(());
+        }
+        if message.params.len() < 2 {
+            tracing::warn!("Ignoring {} from {} with too few parameters", command, origin);
+            return Ok(());
+        }
+        let Some(target) = self.database.get_user_by_nick(&message.params[0]) else {
+            tracing::debug!("{} from {} for unknown user {}", command, origin, message.params[0]);
+            return Ok(());
+        };
+        
+        let relayed = Message::with_prefix(Prefix::Server(origin.clone()), message.command.clone(), message.params.clone());
+        let local = target.server == self.config().server.name;
+        match command.as_str() {
+            "SVSHOST" => {
+                self.forward_to_other_servers(server_name, &relayed).await;
+                self.apply_svshost(target, &message.params[1]).await?;
+            }
+            "SVSMODE" => {
+                self.forward_to_other_servers(server_name, &relayed).await;
+                self.apply_svsmode(&origin, target, &message.params[1]).await?;
+            }
+            _ if !local => self.route_to_user(&target, relayed).await,
+            "SVSNICK" => self.apply_svsnick(target, &message.params[1]).await?,
+            "SVSJOIN" => self.dispatch_for_client(target.id, Message::new(MessageType::Join, message.params[1..].to_vec())).await?,
+            _ => self.dispatch_for_client(target.id, Message::new(MessageType::Part, message.params[1..].to_vec())).await?,
+        }
+        tracing::info!("{} {} from {}", command, message.params.join(" "), origin);
+        Ok(())
+    }
+    
+    /// Rename a local user at services' request, skipping the nick change limit
+    async fn apply_svsnick(&self, target: User, nick: &str) -> Result<()> {
+        if !self.is_valid_nickname(nick) || self.database.get_user_by_nick(nick).is_some_and(|other| other.id != target.id) {
+            tracing::warn!("Ignoring SVSNICK of {} to unusable nickname {}", target.nick, nick);
+            return Ok(());
+        }
+        let mut connection_handler = self.connection_handler.write().await;
+        let Some(client) = connection_handler.get_client_mut(&target.id) else {
+            return Ok(());
+        };
+        let Some(user) = client.user.clone() else {
+            return Ok(());
+        };
+        let nick_propagation = self.rename_local_user(client, user, nick).await;
+        drop(connection_handler);
+        
+        if let Err(e) = self.server_connections.broadcast_to_servers(nick_propagation).await {
+            tracing::warn!("Failed to propagate SVSNICK: {}", e);
+        }
+        Ok(())
+    }
+    
+    /// Carry out a nick change a module asked for with `ModuleContext::force_nick`
+    async fn apply_forced_nick(&self, client_id: uuid::Uuid, nick: &str) -> Result<()> {
+        let Some(target) = self.users.read().await.get(&client_id).cloned() else {
+            return Ok(());
+        };
+        if target.nick == nick {
+            return Ok(());
+        }
+        tracing::info!("Renaming {} to {} at a module's request", target.nick, nick);
+        self.apply_svsnick(target, nick).await
+    }
+    
+    /// Change a user's displayed host, telling them if they're local
+    async fn apply_svshost(&self, mut target: User, host: &str) -> Result<()> {
+        target.host = host.to_string();
+        self.store_user(&target).await?;
+        self.client_senders.send(&target.id, NumericReply::host_hidden(&target.nick, host));
+        Ok(())
+    }
+    
+    /// Set or clear user modes at services' request
+    ///
+    /// Operator modes only ever come from OPER and `z` from the connection
+    /// itself, so `o`, `O` and `z` are ignored.
+    async fn apply_svsmode(&self, origin: &str, mut target: User, modes: &str) -> Result<()> {
+        let mut adding = true;
+        let mut applied = String::new();
+        let mut last_sign = None;
+        for mode in modes.chars() {
+            match mode {
+                '+' => adding = true,
+                '-' => adding = false,
+                'o' | 'O' | 'z' => {}
+                mode if mode.is_ascii_alphabetic() && target.has_mode(mode) != adding => {
+                    if adding {
+                        target.add_mode_internal(mode);
+                    } else {
+                        target.remove_mode_internal(mode);
+                    }
+                    if last_sign != Some(adding) {
+                        applied.push(if adding { '+' } else { '-' });
+                        last_sign = Some(adding);
+                    }
+                    applied.push(mode);
+                }
+                _ => {}
+            }
+        }
+        if applied.is_empty() {
+            return Ok(());
+        }
+        self.store_user(&target).await?;
+        let mode_change = Message::with_prefix(
+            Prefix::Server(origin.to_string()),
+            MessageType::Mode,
+            vec![target.nick.clone(), applied],
+        );
+        self.client_senders.send(&target.id, mode_change);
+        Ok(())
+    }
+    
+    /// Save a changed user everywhere this server keeps a copy
+    async fn store_user(&self, user: &User) -> Result<()> {
+        self.database.update_user(&user.id, user.clone())?;
+        if let Some(known) = self.users.write().await.get_mut(&user.id) {
+            *known = user.clone();
+        }
+        if let Some(client) = self.connection_handler.write().await.get_client_mut(&user.id) {
+            client.user = Some(user.clone());
+        }
+        Ok(())
+    }
+    
+    /// Run a command for a local client as if they had sent it
+    ///
+    /// Services use this to move users between channels. Unlike
+    /// `handle_message` it doesn't count as activity from the client.
+    async fn dispatch_for_client(&self, client_id: uuid::Uuid, message: Message) -> Result<()> {
+        let connection_handler = self.connection_handler.read().await;
+        let Some(client) = connection_handler.get_client(&client_id) else {
+            return Ok(());
+        };
+        let mut module_manager = self.module_manager.write().await;
+        let result = module_manager.handle_message_with_server(client, &message, Some(self)).await?;
+        drop(module_manager);
+        drop(connection_handler);
+        if let ModuleResult::NotHandled = result {
+            self.handle_core_command(client_id, message).await?;
+        }
+        Ok(())
+    }
+    
+    /// Check whether `target` has silenced the user behind `sender_nick`
+    fn is_silenced_by(&self, target: &User, sender_nick: &str) -> bool {
+        self.database.get_user_by_nick(sender_nick)
+            .is_some_and(|sender| target.is_silencing(&sender))
+    }
+    
+    /// Handle SILENCE command
+    ///
+    /// With no parameters the silence list is shown. Otherwise each
+    /// comma-separated `+mask`/`-mask` entry is added or removed (a bare mask
+    /// adds) and every change is echoed back to the user.
+    async fn handle_silence(&self, client_id: uuid::Uuid, message: Message) -> Result<()> {
         let connection_handler = self.connection_handler.read().await;
-        if let Some(client) = connection_handler.get_client(&client_id) {
-            if !client.is_registered() {
-                let error_msg = NumericReply::not_registered();
-                let _ = client.send(error_msg);
-                return Ok(());
+        let Some(client) = connection_handler.get_client(&client_id) else {
+            return Ok(());
+        };
+        let Some(mut user) = client.nickname().and_then(|nick| self.database.get_user_by_nick(nick)) else {
+            return Ok(());
+        };
+        
+        if message.params.is_empty() {
+            for mask in &user.silence_list {
+                let _ = client.send(NumericReply::sile_list(&user.nick, mask));
             }
-            
-            // Get user from database
-            if let Some(nick) = client.nickname() {
-                if let Some(mut user) = self.database.get_user_by_nick(nick) {
-                    if message.params.is_empty() {
-                        // Remove away status
-                        let was_away = user.away_message.is_some();
-                        user.away_message = None;
-                        let _ = self.database.add_user(user);
-                        
-                        let unaway_msg = NumericReply::unaway();
-                        let _ = client.send(unaway_msg);
-                        
-                        // Broadcast away removal to servers
-                        if was_away {
-                            let server_away_msg = Message::new(
-                                MessageType::Away,
-                                vec![]
-                            );
-                            
-                            if let Err(e) = self.server_connections.broadcast_to_servers(server_away_msg).await {
-                                tracing::warn!("Failed to broadcast AWAY removal to servers: {}", e);
-                            }
-                        }
-                    } else {
-                        // Set away message
-                        let away_message = message.params[0].clone();
-                        let was_away = user.away_message.is_some();
-                        user.away_message = Some(away_message.clone());
-                        let _ = self.database.add_user(user);
-                        
-                        let now_away_msg = NumericReply::now_away();
-                        let _ = client.send(now_away_msg);
-                        
-                        // Broadcast away status to servers
-                        if !was_away {
-                            let server_away_msg = Message::new(
-                                MessageType::Away,
-                                vec![away_message]
-                            );
-                            
-                            if let Err(e) = self.server_connections.broadcast_to_servers(server_away_msg).await {
-                                tracing::warn!("Failed to broadcast AWAY status to servers: {}", e);
+            let _ = client.send(NumericReply::end_of_sile_list(&user.nick));
+            return Ok(());
+        }
+        
+        let max_entries = self.config().server.max_silence_entries;
+        let user_prefix = Prefix::User {
+            nick: user.nick.clone(),
+            user: user.username.clone(),
+            host: user.host.clone(),
+        };
+        let mut changes = Vec::new();
+        for entry in message.params[0].split(',').filter(|e| !e.is_empty()) {
+            let change = match entry.strip_prefix('-') {
+                Some(mask) => user.remove_silence(mask).map(|m| format!("-{}", m)),
+                None => {
+                    let mask = entry.strip_prefix('+').unwrap_or(entry);
+                    match user.add_silence(mask, max_entries) {
+                        Some(m) => Some(format!("+{}", m)),
+                        None => {
+                            if user.silence_list.len() >= max_entries {
+                                let _ = client.send(NumericReply::sile_list_full(&user.nick, mask));
                             }
+                            None
                         }
                     }
                 }
+            };
+            changes.extend(change);
+        }
+        
+        if changes.is_empty() {
+            return Ok(());
+        }
+        let user_id = user.id;
+        self.database.update_user(&user_id, user)?;
+        for change in changes {
+            let echo = Message::with_prefix(user_prefix.clone(), MessageType::Custom("SILENCE".to_string()), vec![change]);
+            let _ = client.send(echo);
+        }
+        Ok(())
+    }
+    
+    /// Record a CTCP from a client, returning `false` if it should be dropped for flooding
+    ///
+    /// ACTION is ordinary chat and never counts toward the limit.
+    fn check_ctcp_flood(&self, client_id: uuid::Uuid, client: &Client, ctcp: &crate::Ctcp) -> bool {
+        let ctcp_config = &self.config().modules.ctcp;
+        if ctcp_config.exempt_operators && client.get_user().is_some_and(|u| u.is_operator()) {
+            return true;
+        }
+        
+        let allowed = self.ctcp_flood_control.check_ctcp(client_id, ctcp);
+        if !allowed {
+            tracing::debug!("Dropping CTCP from {}: more than {} in {}s",
+                client.nickname().unwrap_or("unknown"), ctcp_config.max_ctcps, ctcp_config.time_window_seconds);
+        }
+        allowed
+    }
+    
+    /// Answer a CTCP request addressed to this server
+    fn reply_to_server_ctcp(&self, client: &Client, ctcp: &crate::Ctcp) {
+        let Some(reply) = ctcp.server_reply(&self.config().modules.ctcp, &self.config().server.version) else {
+            return;
+        };
+        let notice = Message::with_prefix(
+            Prefix::Server(self.config().server.name.clone()),
+            MessageType::Notice,
+            vec![client.nickname().unwrap_or("*").to_string(), reply.to_text()],
+        );
+        let _ = client.send(notice);
+    }
+    
+    /// Handle AWAY command
+    ///
+    /// The away message is kept on every copy of the user (database, user
+    /// map and connection) so later nick or mode changes don't drop it.
+    async fn handle_away(&self, client_id: uuid::Uuid, message: Message) -> Result<()> {
+        let away_message = message.params.first().filter(|reason| !reason.is_empty()).cloned();
+        let mut connection_handler = self.connection_handler.write().await;
+        let Some(client) = connection_handler.get_client_mut(&client_id) else {
+            return Ok(());
+        };
+        let Some(mut user) = client.user.as_ref().and_then(|user| self.database.get_user(&user.id)) else {
+            return Ok(());
+        };
+        
+        let changed = user.away_message != away_message;
+        user.set_away(away_message.clone());
+        let (user_id, prefix) = (user.id, user.prefix());
+        if let Some(client_user) = client.user.as_mut() {
+            client_user.set_away(away_message.clone());
+        }
+        let _ = client.send(if away_message.is_some() { NumericReply::now_away() } else { NumericReply::unaway() });
+        drop(connection_handler);
+        
+        self.database.update_user(&user_id, user.clone())?;
+        if let Some(known) = self.users.write().await.get_mut(&user_id) {
+            known.set_away(away_message.clone());
+        }
+        
+        // Tell the network, which answers PRIVMSG and WHOIS with 301 from its own copy
+        if changed {
+            let server_away_msg = Message::with_prefix(prefix, MessageType::Away, away_message.into_iter().collect());
+            if let Err(e) = self.server_connections.broadcast_to_servers(server_away_msg).await {
+                tracing::warn!("Failed to broadcast AWAY status to servers: {}", e);
             }
         }
         Ok(())
@@ -3039,18 +5205,6 @@ impl Server {
     async fn handle_join(&self, client_id: uuid::Uuid, message: Message) -> Result<()> {
         let connection_handler = self.connection_handler.read().await;
         if let Some(client) = connection_handler.get_client(&client_id) {
-            if !client.is_registered() {
-                let error_msg = NumericReply::not_registered();
-                let _ = client.send(error_msg);
-                return Ok(());
-            }
-            
-            if message.params.is_empty() {
-                let error_msg = NumericReply::need_more_params("JOIN");
-                let _ = client.send(error_msg);
-                return Ok(());
-            }
-            
             // Get user from database
             if let Some(nick) = client.nickname() {
                 if let Some(mut user) = self.database.get_user_by_nick(nick) {
@@ -3115,18 +5269,6 @@ impl Server {
     async fn handle_part(&self, client_id: uuid::Uuid, message: Message) -> Result<()> {
         let connection_handler = self.connection_handler.read().await;
         if let Some(client) = connection_handler.get_client(&client_id) {
-            if !client.is_registered() {
-                let error_msg = NumericReply::not_registered();
-                let _ = client.send(error_msg);
-                return Ok(());
-            }
-            
-            if message.params.is_empty() {
-                let error_msg = NumericReply::need_more_params("PART");
-                let _ = client.send(error_msg);
-                return Ok(());
-            }
-            
             // Get user from database
             if let Some(nick) = client.nickname() {
                 if let Some(mut user) = self.database.get_user_by_nick(nick) {
@@ -3183,33 +5325,64 @@ impl Server {
         Ok(())
     }
     
+    /// Nicknames given to ISON or USERHOST, at most `limit` of them
+    ///
+    /// Clients send the list either as separate parameters or as one
+    /// space-separated trailing parameter.
+    fn nick_list(message: &Message, limit: usize) -> Vec<String> {
+        message.params.iter()
+            .flat_map(|param| param.split_whitespace())
+            .take(limit)
+            .map(str::to_string)
+            .collect()
+    }
+    
+    /// Replies listing `entries`, split so no line exceeds 512 bytes
+    ///
+    /// Every reply is addressed to `nick` from this server. An empty list
+    /// still gets one empty reply.
+    fn list_replies(&self, nick: &str, entries: &[String], build: fn(&[String]) -> Message) -> Vec<Message> {
+        let server_name = self.config().server.name.clone();
+        let finish = |mut reply: Message| {
+            reply.prefix = Some(Prefix::Server(server_name.clone()));
+            reply.params[0] = nick.to_string();
+            reply
+        };
+        let budget = crate::message::MAX_LINE_LENGTH.saturating_sub(finish(build(&[])).to_string().len());
+        
+        let mut chunks: Vec<&[String]> = Vec::new();
+        let mut start = 0;
+        let mut length = 0;
+        for (i, entry) in entries.iter().enumerate() {
+            let added = if i == start { entry.len() } else { entry.len() + 1 };
+            if i > start && length + added > budget {
+                chunks.push(&entries[start..i]);
+                start = i;
+                length = entry.len();
+            } else {
+                length += added;
+            }
+        }
+        chunks.push(&entries[start..]);
+        chunks.into_iter().map(|chunk| finish(build(chunk))).collect()
+    }
+    
     /// Handle ISON command
     async fn handle_ison(&self, client_id: uuid::Uuid, message: Message) -> Result<()> {
         let connection_handler = self.connection_handler.read().await;
         if let Some(client) = connection_handler.get_client(&client_id) {
-            if !client.is_registered() {
-                let error_msg = NumericReply::not_registered();
-                let _ = client.send(error_msg);
-                return Ok(());
-            }
-            
-            if message.params.is_empty() {
-                // No nicknames provided, send empty response
-                let ison_msg = NumericReply::ison(&[]);
-                let _ = client.send(ison_msg);
+            let Some(requester) = client.get_user() else {
                 return Ok(());
-            }
+            };
             
             // Check which nicknames are online
-            let mut online_nicks = Vec::new();
-            for nick in &message.params {
-                if self.database.get_user_by_nick(nick).is_some() {
-                    online_nicks.push(nick.clone());
-                }
-            }
+            let online_nicks: Vec<String> = Self::nick_list(&message, MAX_ISON_NICKS).into_iter()
+                .filter(|nick| self.database.get_user_by_nick(nick).is_some())
+                .collect();
             
-            let ison_msg = NumericReply::ison(&online_nicks);
-            let _ = client.send(ison_msg);
+            for reply in self.list_replies(&requester.nick, &online_nicks, NumericReply::ison) {
+                let _ = client.send(reply);
+            }
         }
         Ok(())
     }
@@ -3218,32 +5391,23 @@ impl Server {
     async fn handle_userhost(&self, client_id: uuid::Uuid, message: Message) -> Result<()> {
         let connection_handler = self.connection_handler.read().await;
         if let Some(client) = connection_handler.get_client(&client_id) {
-            if !client.is_registered() {
-                let error_msg = NumericReply::not_registered();
-                let _ = client.send(error_msg);
-                return Ok(());
-            }
-            
-            if message.params.is_empty() {
-                // No nicknames provided, send empty response
-                let userhost_msg = NumericReply::userhost(&[]);
-                let _ = client.send(userhost_msg);
-                return Ok(());
-            }
-            
             // Get user information for each nickname
+            let Some(viewer) = self.requesting_user(client) else {
+                return Ok(());
+            };
             let mut userhost_entries = Vec::new();
-            for nick in &message.params {
-                if let Some(user) = self.database.get_user_by_nick(nick) {
+            for nick in Self::nick_list(&message, MAX_USERHOST_NICKS) {
+                if let Some(user) = self.database.get_user_by_nick(&nick) {
                     let operator_flag = if user.is_operator { "*" } else { "" };
-                    let away_flag = if user.away_message.is_some() { "G" } else { "H" };
-                    let entry = format!("{}={}{}@{}", nick, operator_flag, away_flag, user.host);
+                    let away_flag = if user.away_message.is_some() { "-" } else { "+" };
+                    let entry = format!("{}{}={}{}@{}", user.nick, operator_flag, away_flag, user.username, display_host(user.host_for(Some(&viewer))));
                     userhost_entries.push(entry);
                 }
             }
             
-            let userhost_msg = NumericReply::userhost(&userhost_entries);
-            let _ = client.send(userhost_msg);
+            for reply in self.list_replies(&viewer.nick, &userhost_entries, NumericReply::userhost) {
+                let _ = client.send(reply);
+            }
         }
         Ok(())
     }
@@ -3254,33 +5418,19 @@ impl Server {
         let client = connection_handler.get_client(&client_id)
             .ok_or_else(|| Error::User("Client not found".to_string()))?;
 
-        // Check if client is registered
-        if !client.is_registered() {
-            let error_msg = NumericReply::not_registered();
-            let _ = client.send(error_msg);
-            return Ok(());
-        }
-
         // Check if remote CONNECT is allowed
-        if !self.config.security.server_security.allow_remote_connect {
+        if !self.config().security.server_security.allow_remote_connect {
             let error_msg = NumericReply::no_privileges();
             let _ = client.send(error_msg);
             return Ok(());
         }
 
-        // Validate parameters
-        if message.params.len() < 2 {
-            let error_msg = NumericReply::need_more_params("CONNECT");
-            let _ = client.send(error_msg);
-            return Ok(());
-        }
-
         let target_server = &message.params[0];
         let target_port: u16 = message.params[1].parse()
             .map_err(|_| Error::User("Invalid port number".to_string()))?;
 
         // Check if user is an operator with CONNECT privileges
-        if self.config.security.server_security.require_oper_for_connect {
+        if self.config().security.server_security.require_oper_for_connect {
             let user = client.user.as_ref()
                 .ok_or_else(|| Error::User("User not registered".to_string()))?;
             if !user.is_operator {
@@ -3291,7 +5441,7 @@ impl Server {
 
             // Check if user has remote connect flag (for remote connections)
             // For now, we'll check if it's a remote connection by comparing with local server
-            let is_remote = target_server != &self.config.server.name;
+            let is_remote = target_server != &self.config().server.name;
             if is_remote && !user.can_remote_connect() {
                 let error_msg = NumericReply::no_privileges();
                 let _ = client.send(error_msg);
@@ -3327,7 +5477,7 @@ impl Server {
         }
 
         // Check hop count limits
-        if self.server_connections.server_count().await >= self.config.security.server_security.max_hop_count as usize {
+        if self.server_connections.server_count().await >= self.config().security.server_security.max_hop_count as usize {
             let error_msg = NumericReply::no_privileges();
             let _ = client.send(error_msg);
             return Ok(());
@@ -3337,7 +5487,16 @@ impl Server {
         let user_nick = client.user.as_ref()
             .map(|u| u.nick.as_str())
             .unwrap_or("<unknown>");
+        if let Some(user) = client.user.as_ref() {
+            self.record_audit(crate::AuditEvent::privileged(user, "CONNECT", format!("{}:{}", target_server, target_port)));
+        }
 
+        // CONNECT overrides any autoconnect backoff; the next automatic retry
+        // starts over from the base delay
+        if self.server_connections.reset_autoconnect_backoff(target_server) {
+            self.snotice(SnoMask::NetJoins, &format!("{} forced an autoconnect retry to {}", user_nick, target_server));
+        }
+        
         // Attempt to connect to the target server
         match self.connect_to_server(target_server, target_port).await {
             Ok(_) => {
@@ -3363,14 +5522,14 @@ impl Server {
     /// Check if a host is allowed for remote connections
     fn is_host_allowed(&self, host: &str) -> bool {
         // Check denied hosts first
-        for denied_host in &self.config.security.server_security.denied_remote_hosts {
+        for denied_host in &self.config().security.server_security.denied_remote_hosts {
             if self.matches_host_pattern(host, denied_host) {
                 return false;
             }
         }
 
         // Check allowed hosts
-        for allowed_host in &self.config.security.server_security.allowed_remote_hosts {
+        for allowed_host in &self.config().security.server_security.allowed_remote_hosts {
             if self.matches_host_pattern(host, allowed_host) {
                 return true;
             }
@@ -3414,9 +5573,6 @@ impl Server {
 
     /// Connect to a remote server
     async fn connect_to_server(&self, server_name: &str, port: u16) -> Result<()> {
-        // Get server link configuration
-        let server_link = self.server_connections.get_server_link(server_name);
-        
         // Validate the server is configured for connection
         if !self.is_server_configured_for_connect(server_name, port) {
             return Err(Error::Server(format!(
@@ -3424,9 +5580,30 @@ impl Server {
                 server_name
             )));
         }
-        
-        // Create connection
-        let stream = tokio::net::TcpStream::connect(format!("{}:{}", server_name, port)).await
+        Self::dial_server(&self.server_connections, &self.config(), &self.link_messages, server_name, port).await
+    }
+    
+    /// Dial a configured server link
+    ///
+    /// Doesn't need the server itself, so the autoconnect task can use it.
+    async fn dial_server(
+        server_connections: &ServerConnectionManager,
+        config: &Config,
+        inbound: &tokio::sync::mpsc::UnboundedSender<LinkMessage>,
+        server_name: &str,
+        port: u16,
+    ) -> Result<()> {
+        // Get server link configuration
+        let server_link = server_connections.get_server_link(server_name);
+        
+        // Connect using the link's configured hostname, trying every resolved address
+        let hostname = server_link.as_ref().map(|link| link.hostname.as_str()).unwrap_or(server_name);
+        let stream = crate::utils::dns::connect_happy_eyeballs(
+            hostname,
+            port,
+            std::time::Duration::from_millis(250),
+            std::time::Duration::from_secs(10),
+        ).await
             .map_err(|e| Error::Connection(format!("Failed to connect to {}:{}: {}", server_name, port, e)))?;
 
         let remote_addr = stream.peer_addr()
@@ -3448,46 +5625,109 @@ impl Server {
 
         // Set server information
         server_connection.info.name = server_name.to_string();
-        server_connection.info.hostname = server_name.to_string();
+        server_connection.info.hostname = hostname.to_string();
         server_connection.info.port = port;
-        server_connection.info.version = self.config.server.version.clone();
-        server_connection.info.description = format!("Connected from {}", self.config.server.name);
+        server_connection.info.version = config.server.version.clone();
+        server_connection.info.description = format!("Connected from {}", config.server.name);
 
         // Set link password if configured
-        if let Some(link) = server_link {
+        if let Some(link) = &server_link {
             server_connection.info.link_password = Some(link.password.clone());
             server_connection.info.use_tls = link.tls;
+            if link.compression {
+                server_connection.compression = Some(Arc::new(CompressionStats::default()));
+            }
         }
 
-        // Add connection to manager
-        self.server_connections.add_connection(server_connection).await?;
+        // Introduce ourselves, offering compression before SERVER so the
+        // peer can agree to it in its reply
+        if let Some(link) = &server_link {
+            let _ = server_connection.send(Message::new(MessageType::Password, vec![link.password.clone()]));
+        }
+        if server_connection.compression.is_some() {
+            let _ = server_connection.send(Message::new(
+                MessageType::Custom("CAPAB".to_string()),
+                vec![link_compression::CAPAB_TOKEN.to_string()],
+            ));
+        }
+        let _ = server_connection.send(Message::new(
+            MessageType::Server,
+            vec![config.server.name.clone(), "1".to_string(), config.server.description.clone()],
+        ));
+        let link_handle = LinkHandle {
+            sender: server_connection.sender.clone(),
+            inbound: inbound.clone(),
+            outgoing: true,
+            compression: server_connection.compression.clone(),
+        };
 
-        // Start server connection handler
-        self.start_server_connection_handler(connection_id, stream, receiver, server_name).await?;
+        // Links with tls = true are only used once the peer certificate checks out
+        match server_link.filter(|link| link.tls) {
+            Some(link) => {
+                let (connector, sni) = crate::link_tls::connector(&link)?;
+                let tls_stream = connector.connect(sni, stream).await
+                    .map_err(|e| Error::Connection(format!("TLS handshake with {} failed: {}", server_name, e)))?;
+                server_connection.encrypted = true;
+                server_connections.add_connection(server_connection).await?;
+                Self::start_server_connection_handler(connection_id, tls_stream, receiver, link_handle, server_name).await?;
+            }
+            None => {
+                server_connections.add_connection(server_connection).await?;
+                Self::start_server_connection_handler(connection_id, stream, receiver, link_handle, server_name).await?;
+            }
+        }
 
         tracing::info!("Successfully connected to server {}:{}", server_name, port);
         Ok(())
     }
 
     /// Start a server connection handler
-    async fn start_server_connection_handler(
-        &self,
+    ///
+    /// Lines are plain until either side sends ZIPSTART; from then on that
+    /// side's direction of the link is a zlib stream.
+    async fn start_server_connection_handler<S>(
         _connection_id: Uuid,
-        stream: tokio::net::TcpStream,
+        stream: S,
         mut receiver: tokio::sync::mpsc::UnboundedReceiver<Message>,
+        link: LinkHandle,
         server_name: &str,
-    ) -> Result<()> {
-        let (read_half, mut write_half) = stream.into_split();
+    ) -> Result<()>
+    where
+        S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Send + 'static,
+    {
+        let (read_half, mut write_half) = tokio::io::split(stream);
 
         // Spawn message sender task
         let server_name_clone = server_name.to_string();
+        let write_stats = link.compression.clone();
         tokio::spawn(async move {
+            let mut compressor: Option<LinkCompressor> = None;
             while let Some(message) = receiver.recv().await {
+                tracing::trace!("Sending to server {}: {}", server_name_clone, message.redacted());
                 let message_str = message.to_string();
-                if let Err(e) = write_half.write_all(message_str.as_bytes()).await {
+                let result = match compressor.as_mut() {
+                    Some(compressor) => match compressor.compress(message_str.as_bytes()) {
+                        Ok(compressed) => {
+                            if let Some(stats) = &write_stats {
+                                stats.record_out(message_str.len(), compressed.len());
+                            }
+                            write_half.write_all(&compressed).await
+                        }
+                        Err(e) => {
+                            tracing::error!("Failed to compress message to server {}: {}", server_name_clone, e);
+                            break;
+                        }
+                    },
+                    None => write_half.write_all(message_str.as_bytes()).await,
+                };
+                if let Err(e) = result {
                     tracing::error!("Failed to send message to server {}: {}", server_name_clone, e);
                     break;
                 }
+                if message.command.to_string() == link_compression::START_COMMAND {
+                    tracing::debug!("Compressing link to server {}", server_name_clone);
+                    compressor = Some(LinkCompressor::new());
+                }
             }
         });
 
@@ -3496,21 +5736,82 @@ impl Server {
         tokio::spawn(async move {
             let mut reader = tokio::io::BufReader::new(read_half);
             let mut line = String::new();
+            let mut zip_started = false;
 
             loop {
                 line.clear();
                 match reader.read_line(&mut line).await {
                     Ok(0) => {
                         tracing::info!("Server {} disconnected", server_name_clone2);
-                        break;
+                        return;
                     }
                     Ok(_) => {
                         // Parse and handle server message
-                        if let Ok(message) = Message::parse(&line.trim()) {
-                            // TODO: Handle server message
-                            tracing::debug!("Received from server {}: {:?}", server_name_clone2, message);
+                        if let Ok(message) = MessageRef::parse(&line) {
+                            tracing::debug!("Received from server {}: {}", server_name_clone2, message.to_message().redacted());
+                            if message.is_command("CAPAB") {
+                                let offered = message.params().iter()
+                                    .flat_map(|param| param.split_whitespace())
+                                    .any(|token| token.eq_ignore_ascii_case(link_compression::CAPAB_TOKEN));
+                                // Incoming links settle compression during the handshake;
+                                // here the peer is answering the CAPAB we dialed with
+                                if offered && link.outgoing && !zip_started {
+                                    if let Some(stats) = &link.compression {
+                                        let _ = link.sender.send(Message::new(
+                                            MessageType::Custom(link_compression::START_COMMAND.to_string()),
+                                            vec![],
+                                        ));
+                                        stats.activate();
+                                        zip_started = true;
+                                    }
+                                }
+                            } else if message.is_command(link_compression::START_COMMAND) {
+                                break;
+                            } else {
+                                let _ = link.inbound.send((server_name_clone2.clone(), message.to_message()));
+                            }
+                        }
+                    }
+                    Err(e) => {
+                        tracing::error!("Error reading from server {}: {}", server_name_clone2, e);
+                        return;
+                    }
+                }
+            }
+
+            // The peer compresses everything after ZIPSTART, including bytes
+            // already sitting in the line buffer
+            tracing::debug!("Server {} is compressing its side of the link", server_name_clone2);
+            let mut decompressor = LinkDecompressor::new();
+            let mut chunk = reader.buffer().to_vec();
+            reader.consume(chunk.len());
+            let mut read_buffer = vec![0u8; 16384];
+            loop {
+                if !chunk.is_empty() {
+                    match decompressor.feed(&chunk) {
+                        Ok((lines, plain_bytes)) => {
+                            if let Some(stats) = &link.compression {
+                                stats.record_in(chunk.len(), plain_bytes);
+                            }
+                            for line in lines {
+                                if let Ok(message) = MessageRef::parse(&line) {
+                                    tracing::debug!("Received from server {}: {}", server_name_clone2, message.to_message().redacted());
+                                    let _ = link.inbound.send((server_name_clone2.clone(), message.to_message()));
+                                }
+                            }
                         }
+                        Err(e) => {
+                            tracing::error!("Error reading from server {}: {}", server_name_clone2, e);
+                            break;
+                        }
+                    }
+                }
+                match reader.read(&mut read_buffer).await {
+                    Ok(0) => {
+                        tracing::info!("Server {} disconnected", server_name_clone2);
+                        break;
                     }
+                    Ok(read) => chunk = read_buffer[..read].to_vec(),
                     Err(e) => {
                         tracing::error!("Error reading from server {}: {}", server_name_clone2, e);
                         break;
@@ -3522,25 +5823,57 @@ impl Server {
         Ok(())
     }
     
-    /// Handle KILL command for operators
-    async fn handle_kill(&self, client_id: uuid::Uuid, message: Message) -> Result<()> {
+    /// Handle REHASH [CONFIG|MOTD|TLS|MODULES]
+    async fn handle_rehash(&self, client_id: uuid::Uuid, message: Message) -> Result<()> {
         let connection_handler = self.connection_handler.read().await;
         let client = connection_handler.get_client(&client_id)
             .ok_or_else(|| Error::User("Client not found".to_string()))?;
-
-        // Check if client is registered
-        if !client.is_registered() {
-            let error_msg = NumericReply::not_registered();
-            let _ = client.send(error_msg);
+        
+        let Some(user) = client.get_user().filter(|user| user.is_operator) else {
+            let _ = client.send(NumericReply::no_privileges());
             return Ok(());
-        }
-
-        // Validate parameters
-        if message.params.len() < 2 {
-            let error_msg = NumericReply::need_more_params("KILL");
-            let _ = client.send(error_msg);
+        };
+        let nick = user.nick.clone();
+        
+        let param = message.params.first().map(String::as_str).unwrap_or("");
+        let Some(section) = RehashSection::parse(param) else {
+            let _ = client.send(self.server_notice(&nick, &format!("Unknown REHASH section {}; use CONFIG, MOTD, RULES, TLS or MODULES", param)));
             return Ok(());
+        };
+        
+        self.record_audit(crate::AuditEvent::privileged(user, "REHASH", section.name()));
+        let _ = client.send(NumericReply::rehashing(&nick, self.rehash_service.config_path()));
+        
+        match self.rehash(section).await {
+            Ok(report) => {
+                for line in &report {
+                    let _ = client.send(self.server_notice(&nick, &format!("REHASH {}: {}", section.name(), line)));
+                }
+                self.snotice(SnoMask::General, &format!("{} is rehashing {}", nick, section.name()));
+            }
+            Err(e) => {
+                let _ = client.send(self.server_notice(&nick, &format!("REHASH {} failed, nothing was changed: {}", section.name(), e)));
+                self.snotice(SnoMask::General, &format!("REHASH {} by {} failed: {}", section.name(), nick, e));
+            }
         }
+        
+        Ok(())
+    }
+    
+    /// NOTICE from this server to `nick`
+    fn server_notice(&self, nick: &str, text: &str) -> Message {
+        Message::with_prefix(
+            Prefix::Server(self.config().server.name.clone()),
+            MessageType::Notice,
+            vec![nick.to_string(), text.to_string()],
+        )
+    }
+    
+    /// Handle KILL command for operators
+    async fn handle_kill(&self, client_id: uuid::Uuid, message: Message) -> Result<()> {
+        let connection_handler = self.connection_handler.read().await;
+        let client = connection_handler.get_client(&client_id)
+            .ok_or_else(|| Error::User("Client not found".to_string()))?;
 
         let target_nick = &message.params[0];
         let reason = &message.params[1];
@@ -3570,7 +5903,7 @@ impl Server {
         // Check operator permissions
         let can_kill_globally = operator_user.is_global_oper();
         let can_kill_locally = operator_user.is_local_oper();
-        let target_is_local = target_user.server == self.config.server.name;
+        let target_is_local = target_user.server == self.config().server.name;
 
         if !can_kill_globally && (!can_kill_locally || !target_is_local) {
             let error_msg = NumericReply::no_privileges();
@@ -3579,202 +5912,125 @@ impl Server {
         }
 
         // Check if trying to kill a server (not allowed)
-        if target_user.nick == self.config.server.name {
+        if target_user.nick == self.config().server.name {
             let error_msg = NumericReply::cant_kill_server();
             let _ = client.send(error_msg);
             return Ok(());
         }
 
-        // Send KILL message to the target user
-        let kill_message = Message::with_prefix(
-            operator_user.prefix(),
-            MessageType::Kill,
-            vec![target_nick.to_string(), reason.to_string()],
-        );
-
-        // Find the target user's client and send the kill message
-        if let Some(target_client_id) = database.get_user_by_nick(target_nick).map(|u| u.id) {
-            if let Some(target_client) = connection_handler.get_client(&target_client_id) {
-                let _ = target_client.send(kill_message);
-            }
-        }
-
-        // Send NOTICE to all operators about the kill
-        self.notify_operators_kill(&operator_user, &target_user, reason).await?;
-
-        // Broadcast KILL message to all connected servers
-        let server_kill_msg = Message::new(
-            MessageType::Kill,
-            vec![target_nick.to_string(), format!("{}!{}!{}!{} ({})", 
-                self.config.server.name, operator_user.host, operator_user.username, operator_user.nick, reason)]
-        );
-        
-        if let Err(e) = self.server_connections.broadcast_to_servers(server_kill_msg).await {
-            tracing::warn!("Failed to broadcast KILL to servers: {}", e);
-        }
-
-        // Disconnect the target user
-        if let Some(target_client_id) = database.get_user_by_nick(target_nick).map(|u| u.id) {
-            if let Some(target_client) = connection_handler.get_client(&target_client_id) {
-                // Send quit message to all users in channels
-                self.broadcast_user_quit(&target_client, &format!("Killed by {}: {}", operator_user.nick, reason)).await?;
-                
-                // Remove user from database
-                database.remove_user(target_client_id)?;
-                
-                // Close the connection
-                drop(connection_handler);
-                let mut connection_handler = self.connection_handler.write().await;
-                connection_handler.remove_client(&target_client_id);
-            }
-        }
-
-        tracing::info!("Operator {} killed user {}: {}", operator_user.nick, target_nick, reason);
-        Ok(())
-    }
-    
-    /// Notify all operators about a KILL command
-    async fn notify_operators_kill(&self, operator: &User, target: &User, reason: &str) -> Result<()> {
-        let connection_handler = self.connection_handler.read().await;
-        let database = self.database.clone();
-        
-        // Get all operators
-        let operators = database.get_all_users()
-            .into_iter()
-            .filter(|user| user.is_operator)
-            .collect::<Vec<_>>();
-        
-        let notice_text = format!("*** {} killed {}: {}", operator.nick, target.nick, reason);
-        
-        for oper in operators {
-            if let Some(client_id) = database.get_user_by_nick(&oper.nick).map(|u| u.id) {
-                if let Some(client) = connection_handler.get_client(&client_id) {
-                    let notice = Message::new(
-                        MessageType::Notice,
-                        vec![oper.nick.clone(), notice_text.clone()],
-                    );
-                    let _ = client.send(notice);
-                }
-            }
-        }
+        drop(connection_handler);
+        self.record_audit(crate::AuditEvent::privileged(&operator_user, "KILL", &target_user.nick).with_reason(reason.as_str()));
+        let path = format!("{}!{}!{}!{}", self.config().server.name, operator_user.host, operator_user.username, operator_user.nick);
+        self.deliver_kill(target_user, operator_user.prefix(), &path, &operator_user.nick, reason).await?;
         
+        tracing::info!(%client_id, nick = %operator_user.nick, command = "KILL", target = %target_nick, reason = %reason, "Operator killed user");
         Ok(())
     }
     
-    /// Send notice to all operators
-    async fn send_operator_notice(&self, message: &str) -> Result<()> {
-        let connection_handler = self.connection_handler.read().await;
-        let database = self.database.clone();
-        
-        // Get all operators
-        let operators = database.get_all_users()
-            .into_iter()
-            .filter(|user| user.is_operator)
-            .collect::<Vec<_>>();
-        
-        for oper in operators {
-            if let Some(client_id) = database.get_user_by_nick(&oper.nick).map(|u| u.id) {
-                if let Some(client) = connection_handler.get_client(&client_id) {
-                    let notice = Message::new(
-                        MessageType::Notice,
-                        vec![oper.nick.clone(), message.to_string()],
-                    );
-                    let _ = client.send(notice);
-                }
-            }
-        }
-        
-        Ok(())
+    /// Kill a user on behalf of the server itself, e.g. from the admin API
+    ///
+    /// `killer` names who asked for it in notices and the quit reason.
+    pub async fn kill_user(&self, nick: &str, killer: &str, reason: &str) -> Result<()> {
+        let Some(target_user) = self.database.get_user_by_nick(nick) else {
+            return Err(Error::User(format!("No such nick: {}", nick)));
+        };
+        let server_name = self.config().server.name.clone();
+        self.deliver_kill(target_user, Prefix::Server(server_name.clone()), &server_name, killer, reason).await
     }
     
-    /// Broadcast user quit to all users in the same channels
-    async fn broadcast_user_quit(&self, client: &Client, reason: &str) -> Result<()> {
-        let database = self.database.clone();
-        let Some(user) = client.get_user() else {
-            return Ok(());
-        };
-        
-        // Get all channels the user is in
-        let channels = user.channels.clone();
-        
-        // Create quit message
-        let quit_message = Message::with_prefix(
-            user.prefix(),
-            MessageType::Quit,
-            vec![reason.to_string()],
+    /// Send a KILL to `target_user` and the other servers, then remove the user
+    ///
+    /// `source` prefixes the KILL the target sees and `path` is the kill path
+    /// the other servers see.
+    async fn deliver_kill(&self, target_user: User, source: Prefix, path: &str, killer: &str, reason: &str) -> Result<()> {
+        let kill_message = Message::with_prefix(
+            source,
+            MessageType::Kill,
+            vec![target_user.nick.clone(), reason.to_string()],
         );
-        
-        // Broadcast to all users in the same channels
-        let connection_handler = self.connection_handler.read().await;
-        for channel in channels {
-            let channel_users = database.get_channel_users(&channel);
-            for nick in channel_users {
-                // Get user ID from nickname
-                if let Some(user) = database.get_user_by_nick(&nick) {
-                    if let Some(target_client) = connection_handler.get_client(&user.id) {
-                        let _ = target_client.send(quit_message.clone());
-                    }
+        let is_local = {
+            let connection_handler = self.connection_handler.read().await;
+            match connection_handler.get_client(&target_user.id) {
+                Some(target_client) => {
+                    let _ = target_client.send(kill_message);
+                    true
                 }
+                None => false,
             }
-        }
-        
-        Ok(())
-    }
-
-    /// Broadcast user quit message by client ID
-    async fn broadcast_user_quit_by_id(&self, client_id: uuid::Uuid, reason: &str) -> Result<()> {
-        let database = self.database.clone();
-        let Some(user) = database.get_user(&client_id) else {
-            return Ok(());
         };
         
-        // Get all channels the user is in
-        let channels = user.channels.clone();
+        // Send NOTICE to operators subscribed to kill notices
+        self.snotice(SnoMask::Kills, &format!("{} killed {}: {}", killer, target_user.nick, reason));
         
-        // Create quit message
-        let quit_message = Message::with_prefix(
-            user.prefix(),
-            MessageType::Quit,
-            vec![reason.to_string()],
+        // Broadcast KILL message to all connected servers
+        let server_kill_msg = Message::new(
+            MessageType::Kill,
+            vec![target_user.nick.clone(), format!("{} ({})", path, reason)]
         );
-        
-        // Broadcast to all users in the same channels
-        let connection_handler = self.connection_handler.read().await;
-        for channel in channels {
-            let channel_users = database.get_channel_users(&channel);
-            for nick in channel_users {
-                // Get user ID from nickname
-                if let Some(user) = database.get_user_by_nick(&nick) {
-                    if let Some(target_client) = connection_handler.get_client(&user.id) {
-                        let _ = target_client.send(quit_message.clone());
-                    }
-                }
-            }
+        if let Err(e) = self.server_connections.broadcast_to_servers(server_kill_msg).await {
+            tracing::warn!("Failed to broadcast KILL to servers: {}", e);
         }
         
+        // Disconnect the target user; the KILL already told the other servers
+        let quit_reason = format!("Killed by {}: {}", killer, reason);
+        if is_local {
+            self.exit_client(target_user.id, &quit_reason, false).await?;
+        } else {
+            self.remove_departed_user(&target_user, &quit_reason).await;
+            self.publish(ServerEvent::UserQuit { user: target_user, reason: quit_reason });
+        }
+        Ok(())
+    }
+    
+    /// Get the audit trail of privileged operator actions
+    pub fn audit_trail(&self) -> Arc<crate::AuditTrail> {
+        self.audit_trail.clone()
+    }
+    
+    /// Record a privileged action and echo it to the audit log channel
+    pub async fn audit(&self, event: crate::AuditEvent) {
+        self.record_audit(event);
+    }
+    
+    /// Record a privileged action without waiting on any lock
+    fn record_audit(&self, event: crate::AuditEvent) {
+        self.audit_trail.record(&event);
+        if let Some((channel, notice)) = self.audit_trail.log_channel_notice(&event) {
+            let members = self.database.get_channel_users(&channel).into_iter()
+                .filter_map(|nick| self.database.get_user_by_nick(&nick))
+                .map(|user| user.id);
+            self.client_senders.send_each(members, &notice);
+        }
+        self.publish(ServerEvent::OperAction(event));
+    }
+    
+    /// Send a server notice to operators subscribed to `mask`
+    pub async fn send_snotice(&self, mask: SnoMask, text: &str) -> Result<()> {
+        self.snotice(mask, text);
         Ok(())
     }
     
+    /// Send a server notice without waiting on any lock
+    fn snotice(&self, mask: SnoMask, text: &str) {
+        Self::snotice_opers(&self.database, &self.client_senders, mask, text);
+    }
+    
+    /// Send a server notice to every local operator subscribed to `mask`
+    fn snotice_opers(database: &Database, senders: &ClientSenders, mask: SnoMask, text: &str) {
+        tracing::debug!("snotice +{}: {}", mask, text);
+        for oper in database.get_all_users() {
+            if crate::snomask::wants_snotice(&oper, mask) {
+                senders.send(&oper.id, mask.notice(&oper.nick, text));
+            }
+        }
+    }
+    
     /// Handle SQUIT command for operators
     async fn handle_operator_squit(&self, client_id: uuid::Uuid, message: Message) -> Result<()> {
         let connection_handler = self.connection_handler.read().await;
         let client = connection_handler.get_client(&client_id)
             .ok_or_else(|| Error::User("Client not found".to_string()))?;
 
-        // Check if client is registered
-        if !client.is_registered() {
-            let error_msg = NumericReply::not_registered();
-            let _ = client.send(error_msg);
-            return Ok(());
-        }
-
-        // Validate parameters
-        if message.params.is_empty() {
-            let error_msg = NumericReply::need_more_params("SQUIT");
-            let _ = client.send(error_msg);
-            return Ok(());
-        }
-
         let target_server = &message.params[0];
         let reason = message.params.get(1).map(|s| s.as_str()).unwrap_or("Operator requested");
 
@@ -3802,18 +6058,16 @@ impl Server {
             return Ok(());
         }
 
-        // Send notice to all operators about the SQUIT
-        self.send_operator_notice(&format!("SQUIT: {} disconnecting server {}: {}", user.nick, target_server, reason)).await?;
+        // Send notice to operators subscribed to oper actions
+        let squit_notice = format!("SQUIT: {} disconnecting server {}: {}", user.nick, target_server, reason);
+        self.snotice(SnoMask::OperActions, &squit_notice);
+        self.record_audit(crate::AuditEvent::privileged(user, "SQUIT", target_server.as_str()).with_reason(reason));
         
         tracing::info!("Operator {} issued SQUIT for server {}: {}", user.nick, target_server, reason);
         
         // Trigger full server quit processing with cleanup
         // This will handle user cleanup, database cleanup, propagation, etc.
-        let quit_message = Message::new(
-            MessageType::ServerQuit,
-            vec![target_server.to_string(), reason.to_string()]
-        );
-        self.handle_server_quit(target_server, quit_message).await?;
+        self.handle_server_link_closed(target_server, reason).await?;
         
         Ok(())
     }
@@ -3833,35 +6087,129 @@ impl Server {
     }
 
     /// Handle incoming server connection
+    ///
+    /// Runs the PASS/CAPAB/SERVER handshake within the connection timeout,
+    /// accepts the link if it is configured, not already present and its
+    /// class has room, then answers with our own handshake and burst.
     pub async fn handle_incoming_server_connection(
         &self,
         stream: tokio::net::TcpStream,
         remote_addr: std::net::SocketAddr,
     ) -> Result<()> {
-        // For now, we'll create a basic server connection
-        // In a full implementation, this would involve:
-        // 1. Reading the SERVER command from the incoming connection
-        // 2. Validating the server name and credentials
-        // 3. Checking if the server is configured in our links
-        
+        let local_addr = stream.local_addr()?;
+        // Keep the buffered reader so lines sent right after SERVER aren't lost
+        let mut reader = tokio::io::BufReader::new(stream);
+        let mut handshake = crate::IncomingHandshake::new();
+        let timeout = std::time::Duration::from_secs(self.config().connection.connection_timeout);
+
+        let registration = tokio::time::timeout(timeout, async {
+            let mut line = String::new();
+            loop {
+                line.clear();
+                if reader.read_line(&mut line).await? == 0 {
+                    return Err(Error::Connection("Connection closed during handshake".to_string()));
+                }
+                let message = Message::parse(line.trim())?;
+                if let Some(registration) = handshake.handle(&message)? {
+                    return Ok(registration);
+                }
+            }
+        }).await
+            .unwrap_or_else(|_| Err(Error::Connection("Handshake timed out".to_string())));
+
+        let accepted = match registration {
+            Ok(registration) => {
+                let in_topology = self.topology.read().await.contains(&registration.name);
+                if in_topology {
+                    Err(Error::Server(format!("Server {} already exists", registration.name)))
+                } else {
+                    self.server_connections.accept_incoming(&registration, remote_addr).await
+                        .map(|link| (registration, link))
+                }
+            }
+            Err(e) => Err(e),
+        };
+        let (registration, link) = match accepted {
+            Ok(accepted) => accepted,
+            Err(e) => {
+                tracing::warn!("Rejected server link from {}: {}", remote_addr, e);
+                self.send_snotice(SnoMask::NetJoins, &format!("Link from {} rejected: {}", remote_addr, e)).await?;
+                let error = format!("ERROR :Closing Link: {} ({})\r\n", remote_addr.ip(), e);
+                let _ = reader.get_mut().write_all(error.as_bytes()).await;
+                let _ = reader.get_mut().shutdown().await;
+                return Err(e);
+            }
+        };
+        let server_name = registration.name.clone();
+
         let connection_id = Uuid::new_v4();
         let (sender, receiver) = tokio::sync::mpsc::unbounded_channel();
-        
-        let server_connection = ServerConnection::new(
+        let mut server_connection = ServerConnection::new(
             connection_id,
             remote_addr,
-            stream.local_addr()?,
+            local_addr,
             sender,
             false, // is_outgoing = false for incoming connections
         );
+        server_connection.info.name = server_name.clone();
+        server_connection.info.description = registration.description.clone();
+        server_connection.info.hop_count = registration.hop_count;
+        server_connection.info.is_super_server = self.server_connections.is_super_server(&server_name);
+        server_connection.state = crate::server_connection::ServerConnectionState::Registered;
+
+        // Compression was offered in the peer's CAPAB; agree if the link allows it
+        let compress = link.compression && registration.has_capability(link_compression::CAPAB_TOKEN);
+        if compress {
+            let stats = Arc::new(CompressionStats::default());
+            stats.activate();
+            server_connection.compression = Some(stats);
+        }
+
+        // Answer with our side of the handshake before the burst is queued
+        let _ = server_connection.send(Message::new(MessageType::Password, vec![link.password.clone()]));
+        if compress {
+            let _ = server_connection.send(Message::new(
+                MessageType::Custom("CAPAB".to_string()),
+                vec![link_compression::CAPAB_TOKEN.to_string()],
+            ));
+        }
+        let _ = server_connection.send(Message::new(
+            MessageType::Server,
+            vec![self.config().server.name.clone(), "1".to_string(), self.config().server.description.clone()],
+        ));
+        if compress {
+            let _ = server_connection.send(Message::new(
+                MessageType::Custom(link_compression::START_COMMAND.to_string()),
+                vec![],
+            ));
+        }
+        let link_handle = LinkHandle {
+            sender: server_connection.sender.clone(),
+            inbound: self.link_messages.clone(),
+            outgoing: false,
+            compression: server_connection.compression.clone(),
+        };
 
-        // Add to server connections
+        if server_connection.info.is_super_server {
+            self.super_servers.write().await.insert(server_name.clone(), true);
+        }
         self.server_connections.add_connection(server_connection).await?;
+        Self::start_server_connection_handler(connection_id, reader, receiver, link_handle, &server_name).await?;
 
-        // Start connection handler
-        self.start_server_connection_handler(connection_id, stream, receiver, "unknown").await?;
+        self.database.add_server(crate::database::ServerInfo {
+            name: server_name.clone(),
+            description: registration.description.clone(),
+            version: String::new(),
+            hopcount: registration.hop_count as u32,
+            connected_at: chrono::Utc::now(),
+            is_super_server: self.server_connections.is_super_server(&server_name),
+            user_count: 0,
+        })?;
+        self.introduce_direct_link(&server_name, &registration.description, "").await;
+        self.send_server_burst(&server_name).await?;
+        self.send_snotice(SnoMask::NetJoins, &format!("Link with {} established (hop count {})", server_name, registration.hop_count)).await?;
 
-        tracing::info!("Incoming server connection from {} accepted", remote_addr);
+        tracing::info!("Incoming server connection from {} accepted as {}", remote_addr, server_name);
         Ok(())
     }
 
@@ -3912,6 +6260,35 @@ impl Server {
 }
 
 /// Load certificates from file
+/// Watch the directories holding `files`, sending on `changes` whenever one
+/// of the files is written, created, renamed or removed
+fn watch_files(files: &[&str], changes: tokio::sync::mpsc::UnboundedSender<()>) -> notify::Result<notify::RecommendedWatcher> {
+    use notify::Watcher;
+    
+    let names: HashSet<std::ffi::OsString> = files.iter()
+        .filter_map(|file| std::path::Path::new(file).file_name().map(|name| name.to_os_string()))
+        .collect();
+    let mut watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+        let Ok(event) = event else { return };
+        if matches!(event.kind, notify::EventKind::Access(_)) {
+            return;
+        }
+        if event.paths.iter().any(|path| path.file_name().is_some_and(|name| names.contains(name))) {
+            let _ = changes.send(());
+        }
+    })?;
+    let dirs: HashSet<&std::path::Path> = files.iter()
+        .map(|file| match std::path::Path::new(file).parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => std::path::Path::new("."),
+        })
+        .collect();
+    for dir in dirs {
+        watcher.watch(dir, notify::RecursiveMode::NonRecursive)?;
+    }
+    Ok(watcher)
+}
+
 fn load_certificates(filename: &str) -> Result<Vec<Certificate>> {
     let certfile = std::fs::File::open(filename)
         .map_err(|e| Error::Config(format!("Failed to open certificate file: {}", e)))?;
@@ -3923,6 +6300,17 @@ fn load_certificates(filename: &str) -> Result<Vec<Certificate>> {
     Ok(certs.into_iter().map(Certificate).collect())
 }
 
+/// When the first certificate in a PEM file expires
+fn certificate_expiry(filename: &str) -> Result<chrono::DateTime<Utc>> {
+    let certificates = load_certificates(filename)?;
+    let certificate = certificates.first()
+        .ok_or_else(|| Error::Config(format!("No certificates found in {}", filename)))?;
+    let (_, parsed) = x509_parser::parse_x509_certificate(&certificate.0)
+        .map_err(|e| Error::Config(format!("Failed to parse certificate {}: {}", filename, e)))?;
+    chrono::DateTime::from_timestamp(parsed.validity().not_after.timestamp(), 0)
+        .ok_or_else(|| Error::Config(format!("Certificate {} has an invalid expiry", filename)))
+}
+
 /// Load private key from file
 fn load_private_key(filename: &str) -> Result<PrivateKey> {
     let keyfile = std::fs::File::open(filename)
@@ -3940,12 +6328,35 @@ fn load_private_key(filename: &str) -> Result<PrivateKey> {
 }
 
 impl Server {
-    /// Get the server configuration
-    pub fn config(&self) -> &Config {
-        &self.config
+    /// Get the current server configuration
+    pub fn config(&self) -> Arc<Config> {
+        self.config.read().clone()
     }
     
+    /// Use `registry` to build the modules enabled in the configuration
+    ///
+    /// Call before `init`, which loads the enabled modules.
+    pub fn set_module_registry(&mut self, registry: ModuleRegistry) {
+        self.module_registry = registry;
+    }
     
+    /// Register the constructor for the module enabled as `name`
+    pub fn register_module<F>(&mut self, name: &str, factory: F)
+    where
+        F: Fn(&Config) -> Result<Box<dyn crate::Module>> + Send + Sync + 'static,
+    {
+        self.module_registry.register(name, factory);
+    }
+
+    /// Register a command outside of any module
+    ///
+    /// See `ModuleManager::register_command` for the checks run before
+    /// `handler` is called.
+    pub async fn register_command(&self, name: &str, handler: Arc<dyn crate::CommandHandler>, min_params: usize, requires_registration: bool, oper_only: bool) -> Result<()> {
+        self.module_manager.write().await.register_command(name, handler, min_params, requires_registration, oper_only)
+    }
+
+
     /// Register IRCv3 extensions
     /// Note: This method should be implemented in the modules crate
     /// and called from there, not from core
@@ -3955,31 +6366,6 @@ impl Server {
         Ok(())
     }
     
-    /// Register channel burst extension
-    /// This method registers the channel burst extension for server-to-server synchronization
-    async fn register_channel_burst_extension(&self) -> Result<()> {
-        // Note: This is a placeholder implementation
-        // In a full implementation, this would:
-        // 1. Get the channel module's channel data structures
-        // 2. Create a ChannelBurstExtension instance
-        // 3. Register it with the extension manager
-        
-        tracing::info!("Channel burst extension registration called (placeholder)");
-        tracing::info!("  - Channel burst extension will be registered when channel module is fully integrated");
-        tracing::info!("  - This will enable server-to-server channel synchronization");
-        
-        // TODO: When channel module is fully integrated:
-        // let channels = channel_module.get_channels(); // Get channel data from module
-        // let channel_burst_extension = Box::new(ChannelBurstExtension::new(
-        //     channels,
-        //     self.database.clone(),
-        //     self.config.server.name.clone(),
-        // ));
-        // self.extension_manager.register_burst_extension(channel_burst_extension).await?;
-        
-        Ok(())
-    }
-    
     /// Handle incoming ChannelBurst messages from other servers
     /// This method processes channel synchronization data from remote servers
     pub async fn handle_channel_burst(&self, source_server: &str, messages: &[Message]) -> Result<()> {
@@ -4030,8 +6416,7 @@ impl Server {
         let target = &message.params[0];
         
         // Get requesting user
-        let users = self.users.read().await;
-        let requesting_user = users.get(&client_id)
+        let requesting_user = self.users.read().await.get(&client_id).cloned()
             .ok_or_else(|| Error::User("User not found".to_string()))?;
         
         // Check if user is trying to change their own modes or someone else's
@@ -4049,10 +6434,10 @@ impl Server {
             requesting_user.clone()
         } else {
             // Find target user by nickname
-            let target_user = users.values()
+            self.users.read().await.values()
                 .find(|u| u.nick == *target)
-                .ok_or_else(|| Error::User("No such nick".to_string()))?;
-            target_user.clone()
+                .cloned()
+                .ok_or_else(|| Error::User("No such nick".to_string()))?
         };
         
         // If no mode changes specified, just show current modes
@@ -4069,6 +6454,7 @@ impl Server {
         // Apply mode changes
         let mut updated_user = target_user.clone();
         let mut changes_applied = Vec::new();
+        let mut snomask_changed = false;
         
         for (action, mode_char) in mode_changes {
             let adding = action;
@@ -4093,8 +6479,30 @@ impl Server {
                     return self.send_error(client_id, error_reply).await;
                 }
                 
+                // Operators choose server notice categories with +s <snomask>
+                if user_mode == crate::user_modes::UserMode::ServerNotices && updated_user.is_operator {
+                    updated_user.snomasks = match (adding, message.params.get(2)) {
+                        (false, _) => std::collections::BTreeSet::new(),
+                        (true, Some(spec)) => crate::snomask::apply_snomask_changes(&updated_user.snomasks, spec),
+                        (true, None) if updated_user.snomasks.is_empty() => {
+                            crate::snomask::apply_snomask_changes(&updated_user.snomasks, &self.config().server.oper_snomask)
+                        }
+                        (true, None) => updated_user.snomasks.clone(),
+                    };
+                    snomask_changed = true;
+                }
+                
                 // Apply mode change
-                if adding {
+                if user_mode == crate::user_modes::UserMode::Cloaked {
+                    // Cloaking swaps the displayed host as well as the mode
+                    if adding {
+                        updated_user.set_cloak(self.host_cloaker().cloak(&updated_user.real_host));
+                        changes_applied.push(format!("+{}", mode_char));
+                    } else {
+                        updated_user.clear_cloak();
+                        changes_applied.push(format!("-{}", mode_char));
+                    }
+                } else if adding {
                     updated_user.add_mode(mode_char);
                     changes_applied.push(format!("+{}", mode_char));
                 } else {
@@ -4130,11 +6538,8 @@ impl Server {
             }
         }
         
-        // Update user in database
-        {
-            let mut users = self.users.write().await;
-            users.insert(client_id, updated_user.clone());
-        }
+        // Update user everywhere it is kept
+        self.store_user(&updated_user).await?;
         
         // Send mode change notification
         if !changes_applied.is_empty() {
@@ -4146,6 +6551,10 @@ impl Server {
             
             // Send to the user whose modes changed
             self.send_to_client(client_id, mode_change_msg.clone()).await?;
+            if snomask_changed {
+                let masks = crate::snomask::snomask_string(&updated_user.snomasks);
+                self.send_to_client(client_id, NumericReply::snomask(&target_user.nick, &masks)).await?;
+            }
             
             // If not self, also send to the requesting user
             if !is_self {
@@ -4208,6 +6617,11 @@ impl Server {
             return Err("Operator mode can only be granted through OPER command".into());
         }
         
+        // +r follows the user's services account and +z the connection
+        if mode.server_only() {
+            return Err(format!("Mode {} is set by the server", mode.to_char()).into());
+        }
+        
         // Check operator requirements for removal of restricted modes
         if !adding && mode.requires_operator() && !requesting_user_is_operator {
             // Exception: Users can always remove their own operator mode
@@ -4250,31 +6664,50 @@ impl Server {
     /// Handle LUSERS command - Network statistics
     /// RFC 1459 Section 4.3.1
     pub async fn handle_lusers(&self, client_id: uuid::Uuid, _message: Message) -> Result<()> {
+        let replies = self.lusers_replies().await;
         let connection_handler = self.connection_handler.read().await;
         if let Some(client) = connection_handler.get_client(&client_id) {
-            // Get network statistics
-            let users = self.get_user_count().await;
-            let operators = self.get_operator_count().await;
-            let channels = self.get_channel_count().await;
-            let servers = self.get_server_count().await;
-            let unknown_connections = self.get_unknown_connection_count().await;
-            let local_users = self.get_local_user_count().await;
-            let max_local_users = self.config.server.max_clients;
-            let global_users = self.get_global_user_count().await;
-            let max_global_users = max_local_users; // For now, assume same as local max
-            
-            // Send LUSERS replies
-            let _ = client.send(NumericReply::luser_client(users, 0, servers)); // 0 services for now
-            let _ = client.send(NumericReply::luser_op(operators));
-            let _ = client.send(NumericReply::luser_unknown(unknown_connections));
-            let _ = client.send(NumericReply::luser_channels(channels));
-            let _ = client.send(NumericReply::luser_me(local_users, servers));
-            let _ = client.send(NumericReply::local_users(local_users, max_local_users.try_into().unwrap_or(u32::MAX)));
-            let _ = client.send(NumericReply::global_users(global_users, max_global_users.try_into().unwrap_or(u32::MAX)));
+            for reply in replies {
+                let _ = client.send(reply);
+            }
         }
         Ok(())
     }
     
+    /// LUSERS replies (251-255, 265, 266)
+    ///
+    /// Counts unregistered connections, so don't call it while holding the
+    /// connection handler lock.
+    async fn lusers_replies(&self) -> Vec<Message> {
+        let unknown_connections = self.get_unknown_connection_count().await;
+        self.lusers_replies_with(unknown_connections).await
+    }
+    
+    /// LUSERS replies with an already counted number of unknown connections
+    async fn lusers_replies_with(&self, unknown_connections: u32) -> Vec<Message> {
+        let global_users = self.get_global_user_count().await;
+        let invisible = self.database.invisible_count();
+        let operators = self.get_operator_count().await;
+        let channels = self.get_channel_count().await;
+        let servers = self.get_server_count().await;
+        let links = self.get_linked_server_count().await;
+        let local_users = self.get_local_user_count().await;
+        let secure_users = self.get_secure_local_user_count();
+        let max_local_users = self.max_local_users.load(Ordering::Relaxed).max(local_users);
+        let max_global_users = self.database.max_user_count().max(global_users);
+        
+        vec![
+            NumericReply::luser_client(global_users.saturating_sub(invisible), invisible, servers),
+            NumericReply::luser_op(operators),
+            NumericReply::luser_unknown(unknown_connections),
+            NumericReply::luser_channels(channels),
+            NumericReply::luser_me(local_users, links),
+            NumericReply::local_users(local_users, max_local_users),
+            NumericReply::global_users(global_users, max_global_users),
+            NumericReply::luser_secure(secure_users, local_users.saturating_sub(secure_users)),
+        ]
+    }
+    
     /// Handle USERS command - RFC 1459 Section 4.3.3
     pub async fn handle_users(&self, client_id: uuid::Uuid, _message: Message) -> Result<()> {
         let connection_handler = self.connection_handler.read().await;
@@ -4305,16 +6738,9 @@ impl Server {
         Ok(())
     }
     
-    /// Get current user count
-    async fn get_user_count(&self) -> u32 {
-        let users = self.users.read().await;
-        users.len() as u32
-    }
-    
-    /// Get operator count
+    /// Get operator count across the network
     async fn get_operator_count(&self) -> u32 {
-        let users = self.users.read().await;
-        users.values().filter(|user| user.is_operator).count() as u32
+        self.database.operator_count()
     }
     
     /// Get channel count
@@ -4322,10 +6748,15 @@ impl Server {
         self.database.channel_count() as u32
     }
     
-    /// Get server count (including this server)
+    /// Get server count across the network (including this server)
     async fn get_server_count(&self) -> u32 {
+        1 + self.topology.read().await.servers().len() as u32 // +1 for this server
+    }
+    
+    /// Get the number of directly linked servers
+    async fn get_linked_server_count(&self) -> u32 {
         let server_connections = self.server_connections.get_all_connections().await;
-        1 + server_connections.len() as u32 // +1 for this server
+        server_connections.iter().filter(|connection| connection.is_registered()).count() as u32
     }
     
     /// Get unknown connection count (unregistered connections)
@@ -4338,13 +6769,23 @@ impl Server {
     
     /// Get local user count
     async fn get_local_user_count(&self) -> u32 {
-        let users = self.users.read().await;
-        users.values().filter(|user| user.server == self.config.server.name).count() as u32
+        self.database.get_users_by_server(&self.config().server.name).len() as u32
+    }
+
+    /// Get the number of local users connected over TLS
+    fn get_secure_local_user_count(&self) -> u32 {
+        self.database.get_users_by_server(&self.config().server.name)
+            .iter()
+            .filter(|user| user.is_secure())
+            .count() as u32
     }
     
     /// Get global user count (all users across network)
+    ///
+    /// Remote users arrive in bursts and leave with QUIT or a netsplit, so
+    /// the database holds every user on the network.
     async fn get_global_user_count(&self) -> u32 {
-        self.get_user_count().await // For now, same as local since we don't have network sync yet
+        self.database.total_user_count()
     }
     
     /// Get the rehash service
@@ -4352,52 +6793,440 @@ impl Server {
         &self.rehash_service
     }
     
-    /// Reload MOTD from configuration
-    pub async fn reload_motd(&mut self) -> Result<()> {
-        let config = self.config.clone();
+    /// Reload `section` from disk, returning one line per applied change
+    ///
+    /// Everything a section needs is loaded and validated before any of it is
+    /// applied, so a failed rehash leaves the running server untouched.
+    pub async fn rehash(&self, section: RehashSection) -> Result<Vec<String>> {
+        match section {
+            RehashSection::Config => {
+                let current = self.config();
+                let new_config = self.rehash_service.load_config()?;
+                if new_config.server.name != current.server.name {
+                    return Err(Error::Config(format!(
+                        "server name cannot change from {} to {} without a restart",
+                        current.server.name, new_config.server.name
+                    )));
+                }
+                let diff = crate::rehash::ConfigDiff::between(&current, &new_config);
+                
+                // Stage everything that can fail before anything is applied;
+                // the MOTD and rules files are read again since they may have
+                // been edited without a config change
+                let tls = if diff.tls { Some(Self::stage_tls(&new_config)?) } else { None };
+                let motd = Self::stage_motd(&new_config)?;
+                let rules = Self::stage_rules(&new_config)?;
+                let listeners = self.stage_listeners(&new_config).await?;
+                let bans = Self::stage_bans(&new_config)?;
+                
+                let new_config = Arc::new(new_config);
+                *self.config.write() = new_config.clone();
+                if diff.replies {
+                    self.numeric_replies.set(new_config.replies.clone(), (&new_config.server).into());
+                }
+                // Link blocks, link classes and backoff settings; existing links stay up
+                self.server_connections.set_config(new_config.clone());
+                self.rehash_service.commit_config((*new_config).clone()).await;
+                
+                let mut report = vec![format!("Configuration reloaded from {}", self.rehash_service.config_path())];
+                report.extend(diff.report());
+                report.extend(self.apply_listeners(&new_config, listeners).await);
+                if let Some(tls) = tls {
+                    report.push(self.apply_tls(tls).await);
+                    self.tls_watch_reset.notify_one();
+                }
+                if diff.motd || !self.motd_manager.is_loaded(&motd).await {
+                    report.push(self.apply_motd(motd).await);
+                }
+                if diff.rules || !self.rules_manager.is_loaded(&rules).await {
+                    report.push(self.apply_rules(rules).await);
+                }
+                if let Some(bans) = bans {
+                    report.extend(self.apply_bans(bans).await);
+                }
+                Ok(report)
+            }
+            RehashSection::Rules => {
+                let rules = Self::stage_rules(&self.config())?;
+                Ok(vec![self.apply_rules(rules).await])
+            }
+            RehashSection::Motd => {
+                let motd = Self::stage_motd(&self.config())?;
+                Ok(vec![self.apply_motd(motd).await])
+            }
+            RehashSection::Tls => {
+                let tls = Self::stage_tls(&self.config())?;
+                let report = vec![self.apply_tls(tls).await];
+                self.tls_watch_reset.notify_one();
+                Ok(report)
+            }
+            RehashSection::Modules => {
+                let results = self.module_manager.write().await.reinitialize_modules().await;
+                let mut report = Vec::with_capacity(results.len() + 1);
+                let mut reloaded = 0;
+                for (name, result) in results {
+                    match result {
+                        Ok(()) => reloaded += 1,
+                        Err(e) => report.push(format!("Module {} failed to reload and was unloaded: {}", name, e)),
+                    }
+                }
+                report.insert(0, format!("Reloaded {} modules", reloaded));
+                Ok(report)
+            }
+        }
+    }
+    
+    /// Bind the ports `config` adds, so a port already in use fails the rehash
+    async fn stage_listeners(&self, config: &Config) -> Result<Vec<(String, crate::config::PortConfig, TcpListener)>> {
+        let mut running: Vec<String> = self.listeners.lock().iter().map(|listener| listener.key.clone()).collect();
+        let mut bound = Vec::new();
+        for port_config in &config.connection.ports {
+            for address in config.get_bind_addresses_for_port(port_config) {
+                let key = listener_key(&address, port_config.port);
+                match running.iter().position(|running| *running == key) {
+                    Some(index) => {
+                        running.swap_remove(index);
+                    }
+                    None => {
+                        let listener = Self::bind_listener(&address, port_config).await?;
+                        bound.push((address, port_config.clone(), listener));
+                    }
+                }
+            }
+        }
+        Ok(bound)
+    }
+    
+    /// Start the listeners bound by `stage_listeners`, close the ones
+    /// `config` no longer has and update the settings of the rest
+    async fn apply_listeners(&self, config: &Config, bound: Vec<(String, crate::config::PortConfig, TcpListener)>) -> Vec<String> {
+        let mut wanted: Vec<(String, &crate::config::PortConfig)> = config.connection.ports.iter()
+            .flat_map(|port_config| {
+                config.get_bind_addresses_for_port(port_config).into_iter()
+                    .map(move |address| (listener_key(&address, port_config.port), port_config))
+            })
+            .collect();
+        let removed: Vec<Listener> = {
+            let mut listeners = self.listeners.lock();
+            let (kept, removed) = std::mem::take(&mut *listeners).into_iter().partition(|listener| {
+                match wanted.iter().position(|(key, _)| *key == listener.key) {
+                    Some(index) => {
+                        let (_, port_config) = wanted.swap_remove(index);
+                        *listener.settings.write() = port_config.clone();
+                        true
+                    }
+                    None => false,
+                }
+            });
+            *listeners = kept;
+            removed
+        };
         
-        if let Some(motd_file) = &config.server.motd_file {
-            info!("Reloading MOTD from file: {}", motd_file);
-            let mut new_motd_manager = MotdManager::new();
-            new_motd_manager.load_motd(motd_file).await?;
-            self.motd_manager = Arc::new(new_motd_manager);
-            info!("MOTD reloaded successfully from: {}", motd_file);
+        let mut report = Vec::new();
+        for (address, port_config, listener) in bound {
+            match self.start_port_listener(&address, &port_config, listener) {
+                Ok(local_addr) => report.push(format!("Listening on {}", local_addr)),
+                Err(e) => report.push(format!("Failed to start listener on port {}: {}", port_config.port, e)),
+            }
+        }
+        for listener in removed {
+            listener.stop.cancel();
+            if !config.connection.close_removed_listener_connections {
+                report.push(format!("Stopped listening on {}; its connections stay until they quit", listener.key));
+                continue;
+            }
+            let clients: Vec<Uuid> = self.connection_handler.read().await.iter_clients()
+                .filter(|(_, client)| listener.accepted(client))
+                .map(|(id, _)| *id)
+                .collect();
+            for client_id in &clients {
+                if let Err(e) = self.exit_client(*client_id, "Listener closed", true).await {
+                    tracing::warn!("Failed to disconnect {} from closed listener {}: {}", client_id, listener.key, e);
+                }
+            }
+            report.push(format!("Stopped listening on {}; disconnected {} connections", listener.key, clients.len()));
+        }
+        report
+    }
+    
+    /// Read the MOTDs named by `config`; a missing file means no MOTD
+    fn stage_motd(config: &Config) -> Result<MotdSet> {
+        MotdManager::read_config(&config.server)
+    }
+    
+    async fn apply_motd(&self, motd: MotdSet) -> String {
+        let count = motd.lines.len();
+        let overrides = motd.overrides.len();
+        self.motd_manager.apply(motd).await;
+        let mut report = if count == 0 {
+            "MOTD cleared".to_string()
         } else {
-            warn!("No MOTD file configured, clearing MOTD");
-            self.motd_manager = Arc::new(MotdManager::new());
-            info!("MOTD cleared successfully");
+            format!("MOTD reloaded ({} lines)", count)
+        };
+        if overrides > 0 {
+            report.push_str(&format!(", {} class/port MOTDs", overrides));
         }
-        
-        Ok(())
+        report
     }
     
-    /// Reload TLS configuration
-    pub async fn reload_tls(&self) -> Result<()> {
-        if !self.config.security.tls.enabled {
-            warn!("TLS is not enabled, skipping TLS reload");
-            return Ok(());
+    /// Read the rules file named by `config`; a missing file means no rules
+    fn stage_rules(config: &Config) -> Result<Vec<String>> {
+        match &config.server.rules_file {
+            Some(rules_file) => Ok(crate::RulesManager::read_rules_file(rules_file)?.unwrap_or_default()),
+            None => Ok(Vec::new()),
         }
-        
-        info!("Reloading TLS configuration");
-        self.setup_tls().await?;
-        info!("TLS configuration reloaded successfully");
-        Ok(())
     }
     
-    /// Reload modules from configuration
-    pub async fn reload_modules(&mut self) -> Result<()> {
-        info!("Reloading modules from configuration");
-        
-        // Clear existing modules
-        {
-            let mut module_manager = self.module_manager.write().await;
-            module_manager.clear_modules().await?;
+    /// Read `security.ban_file`, if one is configured
+    fn stage_bans(config: &Config) -> Result<Option<(Arc<crate::FileBanStore>, Vec<crate::BanEntry>)>> {
+        let Some(ban_file) = &config.security.ban_file else {
+            return Ok(None);
+        };
+        let store = Arc::new(crate::FileBanStore::new(ban_file));
+        let saved = crate::BanStore::load(store.as_ref())?;
+        Ok(Some((store, saved)))
+    }
+    
+    /// Switch to the staged ban file and disconnect users its new bans match
+    async fn apply_bans(&self, (store, saved): (Arc<crate::FileBanStore>, Vec<crate::BanEntry>)) -> Vec<String> {
+        let count = saved.len();
+        let added = self.ban_manager.replace_store(store, saved);
+        let mut report = vec![format!("Bans reloaded ({} entries, {} new)", count, added.len())];
+        if !added.is_empty() {
+            let disconnected = self.enforce_bans().await;
+            if disconnected > 0 {
+                report.push(format!("Disconnected {} users matching new bans", disconnected));
+            }
+        }
+        report
+    }
+    
+    /// Disconnect every local user an active ban matches
+    ///
+    /// Returns how many users were disconnected.
+    pub async fn enforce_bans(&self) -> usize {
+        let banned: Vec<(Uuid, crate::BanEntry)> = self.users.read().await.iter()
+            .filter_map(|(client_id, user)| self.ban_manager.check_user(user).map(|ban| (*client_id, ban)))
+            .collect();
+        for (client_id, ban) in &banned {
+            let reason = self.ban_manager.ban_reason(ban);
+            tracing::info!(client_id = %client_id, ban = %ban.ban_type, mask = %ban.mask, "Disconnecting user matching ban");
+            if let Err(e) = self.exit_client(*client_id, &reason, true).await {
+                tracing::warn!("Failed to disconnect {} for {}: {}", client_id, ban.ban_type, e);
+            }
+        }
+        banned.len()
+    }
+    
+    async fn apply_rules(&self, lines: Vec<String>) -> String {
+        let count = lines.len();
+        self.rules_manager.set_lines(lines).await;
+        if count == 0 {
+            "Rules cleared".to_string()
+        } else {
+            format!("Rules reloaded ({} lines)", count)
+        }
+    }
+    
+    /// Start the task that fetches `motd.remote_url` every `refresh_interval`
+    ///
+    /// A failed fetch keeps serving the last MOTD fetched, or the MOTD file
+    /// until one succeeds. A rehash that changes the URL fetches at once.
+    fn start_motd_refresh_task(&self) {
+        let server = self.clone();
+        tokio::spawn(async move {
+            loop {
+                let interval = server.config().server.motd.refresh_interval.max(60);
+                tokio::select! {
+                    _ = server.shutdown.cancelled() => break,
+                    _ = server.motd_manager.refresh_requested() => {}
+                    _ = tokio::time::sleep(tokio::time::Duration::from_secs(interval)) => {}
+                }
+                
+                let Some(url) = server.config().server.motd.remote_url.clone() else {
+                    continue;
+                };
+                match MotdManager::fetch_remote(&url).await {
+                    Ok(lines) => server.motd_manager.set_remote(&url, lines).await,
+                    Err(e) => tracing::warn!("Keeping the current MOTD: {}", e),
+                }
+            }
+        });
+    }
+    
+    /// Build the TLS acceptor for `config`, or `None` if TLS is disabled
+    /// or its ACME certificate hasn't been issued yet
+    fn stage_tls(config: &Config) -> Result<Option<TlsAcceptors>> {
+        if config.security.tls.enabled && !Self::awaiting_acme_certificate(config) {
+            Self::build_tls_acceptor(&config.security.tls).map(Some)
+        } else {
+            Ok(None)
+        }
+    }
+    
+    /// Whether ACME is to provide a certificate that doesn't exist yet
+    fn awaiting_acme_certificate(config: &Config) -> bool {
+        let tls = &config.security.tls;
+        tls.acme.enabled && !tls.cert_file.as_deref().is_some_and(|cert_file| std::path::Path::new(cert_file).exists())
+    }
+    
+    /// Reload the TLS certificate when its certificate or key file changes
+    ///
+    /// The directories holding the files are watched, so certificates that
+    /// are replaced by renaming a new file over the old one are noticed too.
+    /// A reload that fails, such as when only one of the files has been
+    /// replaced so far, keeps the current acceptor until the next change.
+    /// A rehash that touches `security.tls` restarts the watch.
+    fn start_tls_watch_task(&self) {
+        let server = self.clone();
+        tokio::spawn(async move {
+            loop {
+                let config = server.config();
+                let tls = &config.security.tls;
+                let files: Vec<&str> = if tls.enabled && tls.watch {
+                    tls.cert_file.iter().chain(&tls.key_file).map(String::as_str).collect()
+                } else {
+                    Vec::new()
+                };
+                let (changes_tx, mut changes) = tokio::sync::mpsc::unbounded_channel();
+                // Dropping the watcher stops the notifications
+                let _watcher = if files.is_empty() {
+                    None
+                } else {
+                    match watch_files(&files, changes_tx) {
+                        Ok(watcher) => Some(watcher),
+                        Err(e) => {
+                            tracing::warn!("Can't watch the TLS certificate for changes: {}", e);
+                            None
+                        }
+                    }
+                };
+                
+                let mut loaded = crate::rehash::FileStamps::of(files.iter().copied());
+                loop {
+                    tokio::select! {
+                        _ = server.shutdown.cancelled() => return,
+                        _ = server.tls_watch_reset.notified() => break,
+                        Some(()) = changes.recv() => {
+                            tokio::time::sleep(TLS_RELOAD_SETTLE).await;
+                            while changes.try_recv().is_ok() {}
+                            let stamps = crate::rehash::FileStamps::of(files.iter().copied());
+                            if stamps == loaded {
+                                continue;
+                            }
+                            match Self::stage_tls(&config) {
+                                Ok(acceptor) => {
+                                    let notice = server.apply_tls(acceptor).await;
+                                    let notice = format!("{} after its files changed", notice);
+                                    tracing::info!("{}", notice);
+                                    let _ = server.send_snotice(SnoMask::General, &notice).await;
+                                    loaded = stamps;
+                                }
+                                Err(e) => tracing::warn!("Keeping the current TLS certificate: {}", e),
+                            }
+                        }
+                    }
+                }
+            }
+        });
+    }
+    
+    /// Warn operators while the TLS certificate is close to expiring
+    fn start_certificate_expiry_task(&self) {
+        let server = self.clone();
+        tokio::spawn(async move {
+            loop {
+                let tls = server.config().security.tls.clone();
+                if let Some(cert_file) = tls.cert_file.as_deref().filter(|_| tls.enabled && tls.expiry_warning_days > 0) {
+                    match certificate_expiry(cert_file) {
+                        Ok(expiry) => {
+                            let days = (expiry - Utc::now()).num_days();
+                            let warning = if days < 0 {
+                                Some(format!("TLS certificate {} expired on {}", cert_file, expiry.format("%Y-%m-%d")))
+                            } else if days < tls.expiry_warning_days as i64 {
+                                Some(format!("TLS certificate {} expires in {} days ({})", cert_file, days, expiry.format("%Y-%m-%d")))
+                            } else {
+                                None
+                            };
+                            if let Some(warning) = warning {
+                                tracing::warn!("{}", warning);
+                                let _ = server.send_snotice(SnoMask::General, &warning).await;
+                            }
+                        }
+                        Err(e) if !Self::awaiting_acme_certificate(&server.config()) => {
+                            tracing::warn!("Can't check when {} expires: {}", cert_file, e);
+                        }
+                        Err(_) => {}
+                    }
+                }
+                
+                tokio::select! {
+                    _ = server.shutdown.cancelled() => break,
+                    _ = tokio::time::sleep(tokio::time::Duration::from_secs(CERTIFICATE_CHECK_INTERVAL)) => {}
+                }
+            }
+        });
+    }
+    
+    /// Request or renew the ACME certificate whenever it's due
+    #[cfg(feature = "acme")]
+    fn start_acme_task(&self) {
+        let server = self.clone();
+        tokio::spawn(async move {
+            loop {
+                let config = server.config();
+                if config.security.tls.enabled && config.security.tls.acme.enabled {
+                    if let Err(e) = server.renew_acme_certificate(&config).await {
+                        let warning = format!("ACME certificate renewal failed: {}", e);
+                        tracing::warn!("{}", warning);
+                        let _ = server.send_snotice(SnoMask::General, &warning).await;
+                    }
+                }
+                
+                let interval = config.security.tls.acme.check_interval.max(60);
+                tokio::select! {
+                    _ = server.shutdown.cancelled() => break,
+                    _ = tokio::time::sleep(tokio::time::Duration::from_secs(interval)) => {}
+                }
+            }
+        });
+    }
+    
+    /// Issue a certificate if there is none or it's within `renew_before_days`
+    /// of expiring, and swap it into the TLS acceptor
+    #[cfg(feature = "acme")]
+    async fn renew_acme_certificate(&self, config: &Config) -> Result<()> {
+        let tls = &config.security.tls;
+        let (Some(cert_file), Some(key_file)) = (tls.cert_file.as_deref(), tls.key_file.as_deref()) else {
+            return Err(Error::Config("ACME needs security.tls.cert_file and key_file".to_string()));
+        };
+        let due = certificate_expiry(cert_file)
+            .map(|expiry| expiry - Utc::now() < chrono::Duration::days(tls.acme.renew_before_days as i64))
+            .unwrap_or(true);
+        if !due {
+            return Ok(());
         }
         
-        // Load modules from configuration
-        self.load_modules().await?;
+        let domains = tls.acme.domains.join(", ");
+        tracing::info!("Requesting an ACME certificate for {}", domains);
+        let issued = crate::acme::issue_certificate(&tls.acme).await?;
+        issued.save(cert_file, key_file)?;
+        let acceptor = Self::build_tls_acceptor(tls)?;
+        self.apply_tls(Some(acceptor)).await;
         
-        info!("Modules reloaded successfully");
-        Ok(())
+        let expiry = certificate_expiry(cert_file)?;
+        let notice = format!("ACME certificate for {} installed, expires {}", domains, expiry.format("%Y-%m-%d"));
+        tracing::info!("{}", notice);
+        self.send_snotice(SnoMask::General, &notice).await
+    }
+    
+    async fn apply_tls(&self, acceptor: Option<TlsAcceptors>) -> String {
+        let enabled = acceptor.is_some();
+        self.tls_acceptor.store(acceptor.map(Arc::new));
+        if enabled {
+            "TLS certificate reloaded".to_string()
+        } else {
+            "TLS is disabled".to_string()
+        }
     }
 }
@@ -1,6 +1,7 @@
 //! Server-to-server connection management
 
 use crate::{Error, Result, Message, Config};
+use crate::link_compression::CompressionStats;
 use chrono::{DateTime, Utc};
 use std::collections::HashMap;
 use std::net::SocketAddr;
@@ -143,6 +144,8 @@ pub struct ServerConnection {
     pub last_pong: Option<DateTime<Utc>>,
     /// Connection statistics
     pub stats: ServerConnectionStats,
+    /// Compression counters, when compression is offered on this link
+    pub compression: Option<Arc<CompressionStats>>,
 }
 
 /// Server connection statistics
@@ -230,6 +233,7 @@ impl ServerConnection {
             last_ping: None,
             last_pong: None,
             stats: ServerConnectionStats::default(),
+            compression: None,
         }
     }
 
@@ -267,6 +271,19 @@ impl ServerConnection {
         self.last_pong = Some(Utc::now());
     }
 
+    /// Whether the link is owed a PING after `interval` seconds
+    pub fn is_ping_due(&self, interval: u64) -> bool {
+        self.last_ping.is_none_or(|last_ping| (Utc::now() - last_ping).num_seconds() >= interval as i64)
+    }
+
+    /// Whether nothing has answered for longer than `timeout` seconds
+    ///
+    /// A link that never sent PONG is measured from when it connected.
+    pub fn is_link_dead(&self, timeout: u64) -> bool {
+        let last_heard = self.last_pong.unwrap_or(self.stats.connected_at);
+        (Utc::now() - last_heard).num_seconds() > timeout as i64
+    }
+
     /// Update statistics
     pub fn update_stats(&mut self, bytes_received: u64, bytes_sent: u64) {
         self.stats.bytes_received += bytes_received;
@@ -301,6 +318,109 @@ impl ServerConnection {
     }
 }
 
+/// What a peer sent during the incoming handshake
+#[derive(Debug, Clone, PartialEq)]
+pub struct IncomingRegistration {
+    /// Server name from SERVER
+    pub name: String,
+    /// Password from PASS
+    pub password: String,
+    /// Hop count from SERVER
+    pub hop_count: u8,
+    /// Server description from SERVER
+    pub description: String,
+    /// Tokens from CAPAB, uppercased
+    pub capabilities: Vec<String>,
+}
+
+impl IncomingRegistration {
+    /// Whether the peer advertised a CAPAB token
+    pub fn has_capability(&self, token: &str) -> bool {
+        self.capabilities.iter().any(|capability| capability.eq_ignore_ascii_case(token))
+    }
+}
+
+/// Incoming server handshake: PASS, then any CAPAB lines, then SERVER
+///
+/// Anything out of order ends the handshake with an error; PING and PONG
+/// are tolerated at any point.
+#[derive(Debug)]
+pub struct IncomingHandshake {
+    state: ServerConnectionState,
+    password: Option<String>,
+    capabilities: Vec<String>,
+}
+
+impl Default for IncomingHandshake {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl IncomingHandshake {
+    /// Start a handshake for a freshly accepted connection
+    pub fn new() -> Self {
+        Self {
+            state: ServerConnectionState::Connected,
+            password: None,
+            capabilities: Vec::new(),
+        }
+    }
+
+    /// Current handshake state
+    pub fn state(&self) -> &ServerConnectionState {
+        &self.state
+    }
+
+    /// Feed one message from the peer
+    ///
+    /// Returns the registration once SERVER completes the handshake.
+    pub fn handle(&mut self, message: &Message) -> Result<Option<IncomingRegistration>> {
+        use crate::MessageType;
+
+        match (&self.state, &message.command) {
+            (_, MessageType::Ping | MessageType::Pong) => Ok(None),
+            (ServerConnectionState::Connected, MessageType::Password) => {
+                let password = message.params.first()
+                    .ok_or_else(|| Error::Server("PASS requires a password".to_string()))?;
+                self.password = Some(password.clone());
+                self.state = ServerConnectionState::PasswordProvided;
+                Ok(None)
+            }
+            (ServerConnectionState::PasswordProvided, MessageType::Custom(command)) if command == "CAPAB" => {
+                self.capabilities.extend(message.params.iter()
+                    .flat_map(|param| param.split_whitespace())
+                    .map(|token| token.to_ascii_uppercase()));
+                Ok(None)
+            }
+            (ServerConnectionState::PasswordProvided, MessageType::Server) => {
+                if message.params.len() < 3 {
+                    return Err(Error::Server("SERVER requires a name, hop count and description".to_string()));
+                }
+                let hop_count = message.params[1].parse()
+                    .map_err(|_| Error::Server(format!("Invalid hop count {} in SERVER", message.params[1])))?;
+                self.state = ServerConnectionState::Registered;
+                Ok(Some(IncomingRegistration {
+                    name: message.params[0].clone(),
+                    password: self.password.take().unwrap_or_default(),
+                    hop_count,
+                    description: message.params[2].clone(),
+                    capabilities: std::mem::take(&mut self.capabilities),
+                }))
+            }
+            (ServerConnectionState::Connected, MessageType::Server) => {
+                Err(Error::Server("SERVER received before PASS".to_string()))
+            }
+            (ServerConnectionState::Registered, _) => {
+                Err(Error::Server("Handshake already complete".to_string()))
+            }
+            (_, command) => {
+                Err(Error::Server(format!("Unexpected {} during server handshake", command)))
+            }
+        }
+    }
+}
+
 /// Server connection manager
 #[derive(Debug)]
 pub struct ServerConnectionManager {
@@ -308,8 +428,12 @@ pub struct ServerConnectionManager {
     connections: Arc<RwLock<HashMap<String, ServerConnection>>>,
     /// Connection ID to server name mapping
     id_to_name: Arc<RwLock<HashMap<Uuid, String>>>,
-    /// Server configuration
-    config: Arc<Config>,
+    /// Name of the local server, which a rehash can't change
+    server_name: String,
+    /// Server configuration, replaced on rehash
+    config: parking_lot::RwLock<Arc<Config>>,
+    /// Backoff state of autoconnect links that aren't linked
+    reconnection: Arc<parking_lot::Mutex<HashMap<String, ReconnectionState>>>,
 }
 
 impl ServerConnectionManager {
@@ -318,8 +442,99 @@ impl ServerConnectionManager {
         Self {
             connections: Arc::new(RwLock::new(HashMap::new())),
             id_to_name: Arc::new(RwLock::new(HashMap::new())),
-            config,
+            server_name: config.server.name.clone(),
+            config: parking_lot::RwLock::new(config),
+            reconnection: Arc::new(parking_lot::Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// The configuration links are checked against
+    pub fn config(&self) -> Arc<Config> {
+        self.config.read().clone()
+    }
+
+    /// Use a rehashed configuration
+    ///
+    /// Existing links stay up; links that are no longer autoconnect stop
+    /// being redialed.
+    pub fn set_config(&self, config: Arc<Config>) {
+        self.reconnection.lock().retain(|name, _| {
+            config.network.links.iter().any(|link| link.name == *name && link.autoconnect)
+        });
+        *self.config.write() = config;
+    }
+
+    /// Autoconnect links that aren't connected and are due for a dial
+    ///
+    /// Links never tried are due straight away, so this also drives the
+    /// startup dial.
+    pub async fn due_autoconnects(&self) -> Vec<crate::config::ServerLink> {
+        let mut due = Vec::new();
+        let config = self.config();
+        for link in config.network.links.iter().filter(|link| link.autoconnect) {
+            if self.is_connected(&link.name).await {
+                continue;
+            }
+            let reconnection = self.reconnection.lock();
+            if reconnection.get(&link.name).is_none_or(|state| state.should_attempt_reconnect()) {
+                due.push(link.clone());
+            }
         }
+        due
+    }
+
+    /// Record a dial of an autoconnect link, returning the attempt number and
+    /// the delay in seconds before the next one
+    pub fn record_autoconnect_attempt(&self, server_name: &str) -> (u32, u64) {
+        let netsplit = self.config().netsplit.clone();
+        let mut reconnection = self.reconnection.lock();
+        let state = reconnection.entry(server_name.to_string()).or_insert_with(ReconnectionState::new);
+        let delay = state.calculate_next_delay(netsplit.reconnect_delay_base, netsplit.reconnect_delay_max);
+        state.record_attempt();
+        (state.attempts, delay)
+    }
+
+    /// Forget the backoff of a link once it's established
+    pub fn record_link_established(&self, server_name: &str) {
+        self.reconnection.lock().remove(server_name);
+    }
+
+    /// Schedule a redial of an autoconnect link that dropped
+    ///
+    /// The first redial waits the base delay; with `auto_reconnect` off the
+    /// link stays down until an operator CONNECTs it.
+    pub fn record_link_dropped(&self, server_name: &str) {
+        if self.config().network.links.iter().any(|link| link.name == server_name && link.autoconnect) {
+            self.reconnection.lock().insert(server_name.to_string(), self.base_backoff());
+        }
+    }
+
+    /// Restart the backoff of a link an operator is dialing by hand
+    ///
+    /// Should the manual dial fail, the next automatic one follows after the
+    /// base delay. Returns whether the link was waiting for a redial.
+    pub fn reset_autoconnect_backoff(&self, server_name: &str) -> bool {
+        let mut reconnection = self.reconnection.lock();
+        let waiting = reconnection.contains_key(server_name);
+        if waiting {
+            reconnection.insert(server_name.to_string(), self.base_backoff());
+        }
+        waiting
+    }
+
+    /// Backoff state whose next dial is due after the base delay
+    fn base_backoff(&self) -> ReconnectionState {
+        let netsplit = self.config().netsplit.clone();
+        let mut state = ReconnectionState::new();
+        state.last_attempt_time = Some(state.last_disconnect_time);
+        state.current_delay = netsplit.reconnect_delay_base;
+        state.enabled = netsplit.auto_reconnect;
+        state
+    }
+
+    /// Backoff state of an autoconnect link, if it's waiting for a redial
+    pub fn reconnection_state(&self, server_name: &str) -> Option<ReconnectionState> {
+        self.reconnection.lock().get(server_name).cloned()
     }
 
     /// Add a server connection
@@ -407,6 +622,23 @@ impl ServerConnectionManager {
         }
     }
 
+    /// PING registered links that are due one and return links that stopped answering
+    pub async fn ping_links(&self, local_server: &str, interval: u64, dead_timeout: u64) -> Vec<String> {
+        let mut connections = self.connections.write().await;
+        let mut dead = Vec::new();
+        for connection in connections.values_mut().filter(|connection| connection.is_registered()) {
+            if connection.is_link_dead(dead_timeout) {
+                dead.push(connection.info.name.clone());
+            } else if connection.is_ping_due(interval) {
+                let ping = Message::new(crate::MessageType::Ping, vec![local_server.to_string()]);
+                if connection.send(ping).is_ok() {
+                    connection.update_ping();
+                }
+            }
+        }
+        dead
+    }
+
     /// Get all server connections
     pub async fn get_all_connections(&self) -> Vec<ServerConnection> {
         let connections = self.connections.read().await;
@@ -426,6 +658,7 @@ impl ServerConnectionManager {
     }
 
     /// Send message to all servers
+    #[tracing::instrument(name = "irc.broadcast", level = "debug", skip_all, fields(servers = true))]
     pub async fn broadcast_to_servers(&self, message: Message) -> Result<()> {
         let connections = self.connections.read().await;
         for connection in connections.values() {
@@ -437,6 +670,7 @@ impl ServerConnectionManager {
     }
     
     /// Send message to all servers except the specified one
+    #[tracing::instrument(name = "irc.broadcast", level = "debug", skip(self, message), fields(servers = true))]
     pub async fn broadcast_message(&self, message: &Message, except_server: Option<&str>) -> Result<()> {
         let connections = self.connections.read().await;
         for connection in connections.values() {
@@ -465,24 +699,29 @@ impl ServerConnectionManager {
         }
     }
 
+    /// Name of the local server
+    pub fn local_server_name(&self) -> &str {
+        &self.server_name
+    }
+
     /// Get server link configuration
-    pub fn get_server_link(&self, server_name: &str) -> Option<&crate::config::ServerLink> {
-        self.config.get_server_link(server_name)
+    pub fn get_server_link(&self, server_name: &str) -> Option<crate::config::ServerLink> {
+        self.config().get_server_link(server_name).cloned()
     }
 
     /// Get super server configuration
-    pub fn get_super_server(&self, server_name: &str) -> Option<&crate::config::SuperServerConfig> {
-        self.config.get_super_server(server_name)
+    pub fn get_super_server(&self, server_name: &str) -> Option<crate::config::SuperServerConfig> {
+        self.config().get_super_server(server_name).cloned()
     }
 
     /// Validate if a server connection is allowed
     pub fn is_server_allowed(&self, server_name: &str, hostname: &str, port: u16) -> bool {
-        self.config.is_server_allowed(server_name, hostname, port)
+        self.config().is_server_allowed(server_name, hostname, port)
     }
 
     /// Check if a server is a super server
     pub fn is_super_server(&self, server_name: &str) -> bool {
-        self.config.is_super_server(server_name)
+        self.config().is_super_server(server_name)
     }
 
     /// Validate incoming server connection
@@ -497,4 +736,43 @@ impl ServerConnectionManager {
         // Additional validation can be added here (e.g., password verification, etc.)
         Ok(())
     }
+
+    /// Decide whether to accept a completed incoming handshake
+    ///
+    /// The name must match a configured link, super server or service with
+    /// the same password, must not already be linked, and the link's class
+    /// must have room for it.
+    pub async fn accept_incoming(&self, registration: &IncomingRegistration, remote_addr: SocketAddr) -> Result<crate::config::ServerLink> {
+        let config = self.config();
+        let link = config.get_server_link(&registration.name).cloned()
+            .or_else(|| config.super_server_link(&registration.name))
+            .ok_or_else(|| Error::Server(format!("Server {} is not authorized (not in configuration)", registration.name)))?;
+        if link.password != registration.password {
+            tracing::warn!("Password mismatch for server {} from {}", registration.name, remote_addr);
+            return Err(Error::Server(format!("Password mismatch for server {}", registration.name)));
+        }
+        if registration.name == self.server_name || self.is_connected(&registration.name).await {
+            return Err(Error::Server(format!("Server {} already exists", registration.name)));
+        }
+
+        if let Some(class) = link.class.as_deref().and_then(|name| config.get_class(name)) {
+            let connections = self.connections.read().await;
+            let in_class: Vec<&ServerConnection> = connections.values()
+                .filter(|connection| {
+                    config.get_server_link(&connection.info.name).and_then(|other| other.class.as_deref()) == Some(class.name.as_str())
+                })
+                .collect();
+            if class.max_clients.is_some_and(|max| in_class.len() >= max) {
+                return Err(Error::Server(format!("Class {} is full", class.name)));
+            }
+            let from_address = in_class.iter()
+                .filter(|connection| connection.remote_addr.ip() == remote_addr.ip())
+                .count();
+            if class.max_connections_per_ip.is_some_and(|max| from_address >= max) {
+                return Err(Error::Server(format!("Too many links from {} in class {}", remote_addr.ip(), class.name)));
+            }
+        }
+
+        Ok(link)
+    }
 }
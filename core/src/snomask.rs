@@ -0,0 +1,163 @@
+//! Server notice masks (snomasks)
+//!
+//! Operators with umode +s choose which categories of server notices they
+//! receive, e.g. `MODE nick +s +ckn`. Core and modules emit notices through
+//! [`SnoMask`] categories instead of notifying every operator.
+
+use crate::{Message, MessageType, User};
+use std::collections::BTreeSet;
+use std::fmt;
+
+/// Server notice categories
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SnoMask {
+    /// Client connections and exits
+    Connects,
+    /// KILLs issued by operators and servers
+    Kills,
+    /// K/G/D/X-lines, shuns and reservations being added or removed
+    Bans,
+    /// Server links and netsplits
+    NetJoins,
+    /// Operator actions (OPER, SQUIT, oper override)
+    OperActions,
+    /// Spam filter hits
+    SpamFilter,
+    /// Failed OPER, SASL and PASS attempts and the lockouts they cause
+    AuthFailures,
+    /// Everything not covered by another category
+    General,
+}
+
+impl SnoMask {
+    /// All categories, in letter order of the default mask
+    pub const ALL: [SnoMask; 8] = [
+        SnoMask::AuthFailures,
+        SnoMask::Bans,
+        SnoMask::Connects,
+        SnoMask::SpamFilter,
+        SnoMask::Kills,
+        SnoMask::NetJoins,
+        SnoMask::OperActions,
+        SnoMask::General,
+    ];
+
+    /// Get the mask letter
+    pub fn to_char(&self) -> char {
+        match self {
+            SnoMask::Connects => 'c',
+            SnoMask::Kills => 'k',
+            SnoMask::Bans => 'b',
+            SnoMask::NetJoins => 'n',
+            SnoMask::OperActions => 'o',
+            SnoMask::SpamFilter => 'f',
+            SnoMask::AuthFailures => 'a',
+            SnoMask::General => 's',
+        }
+    }
+
+    /// Get a category from its mask letter
+    pub fn from_char(c: char) -> Option<Self> {
+        Self::ALL.iter().copied().find(|mask| mask.to_char() == c)
+    }
+
+    /// Get description of the notices in this category
+    pub fn description(&self) -> &'static str {
+        match self {
+            SnoMask::Connects => "Client connections and exits",
+            SnoMask::Kills => "Kills",
+            SnoMask::Bans => "Ban changes (K/G/D/X-lines, shuns, reservations)",
+            SnoMask::NetJoins => "Server links and netsplits",
+            SnoMask::OperActions => "Operator actions",
+            SnoMask::SpamFilter => "Spam filter hits",
+            SnoMask::AuthFailures => "Failed OPER, SASL and PASS attempts",
+            SnoMask::General => "General server notices",
+        }
+    }
+
+    /// Build the NOTICE sent to a subscribed operator
+    pub fn notice(&self, target: &str, text: &str) -> Message {
+        Message::new(
+            MessageType::Notice,
+            vec![target.to_string(), format!("*** Notice -- {}", text)],
+        )
+    }
+}
+
+impl fmt::Display for SnoMask {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.to_char())
+    }
+}
+
+/// Apply a snomask change such as `+ck-n` to a set of mask letters
+///
+/// A spec without a leading sign adds letters. Unknown letters are ignored.
+pub fn apply_snomask_changes(current: &BTreeSet<char>, spec: &str) -> BTreeSet<char> {
+    let mut masks = current.clone();
+    let mut adding = true;
+    for c in spec.chars() {
+        match c {
+            '+' => adding = true,
+            '-' => adding = false,
+            '*' if adding => masks.extend(SnoMask::ALL.iter().map(SnoMask::to_char)),
+            '*' => masks.clear(),
+            _ if SnoMask::from_char(c).is_none() => {}
+            _ if adding => {
+                masks.insert(c);
+            }
+            _ => {
+                masks.remove(&c);
+            }
+        }
+    }
+    masks
+}
+
+/// Format a set of mask letters as `+letters`
+pub fn snomask_string(masks: &BTreeSet<char>) -> String {
+    format!("+{}", masks.iter().collect::<String>())
+}
+
+/// Check whether a user receives notices in `mask`
+///
+/// Only operators with umode +s subscribed to the category receive them.
+pub fn wants_snotice(user: &User, mask: SnoMask) -> bool {
+    user.is_operator && user.has_mode('s') && user.snomasks.contains(&mask.to_char())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_snomask_changes() {
+        let masks = apply_snomask_changes(&BTreeSet::new(), "ck");
+        assert_eq!(snomask_string(&masks), "+ck");
+
+        let masks = apply_snomask_changes(&masks, "-c+nxo");
+        assert_eq!(snomask_string(&masks), "+kno");
+
+        let masks = apply_snomask_changes(&masks, "*");
+        assert_eq!(masks.len(), SnoMask::ALL.len());
+        assert!(apply_snomask_changes(&masks, "-*").is_empty());
+    }
+
+    #[test]
+    fn test_wants_snotice() {
+        let mut user = User::new(
+            "oper".to_string(),
+            "oper".to_string(),
+            "Operator".to_string(),
+            "example.com".to_string(),
+            "irc.example.com".to_string(),
+        );
+        user.snomasks = apply_snomask_changes(&BTreeSet::new(), "k");
+        assert!(!wants_snotice(&user, SnoMask::Kills));
+
+        user.set_operator_flags([crate::config::OperatorFlag::GlobalOper].into_iter().collect());
+        user.add_mode('s');
+        assert!(wants_snotice(&user, SnoMask::Kills));
+        assert!(!wants_snotice(&user, SnoMask::Connects));
+    }
+}
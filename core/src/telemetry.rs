@@ -0,0 +1,289 @@
+//! OpenTelemetry trace export
+//!
+//! A tracing layer that turns the daemon's own spans (`irc.accept`,
+//! `irc.command` and its `irc.parse`, `irc.module`, `irc.core_handler` and
+//! `irc.broadcast` children) into OTLP spans and posts them in batches to
+//! an OTLP/HTTP collector using the JSON encoding. Sampling is decided per
+//! trace at its root span.
+
+use crate::config::TelemetryConfig;
+use crate::logging::JsonVisitor;
+use crate::{Error, Result};
+use serde_json::{json, Map, Value};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::sync::mpsc;
+use tracing::span::{Attributes, Id, Record};
+use tracing::Subscriber;
+use tracing_subscriber::layer::Context;
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::Layer;
+
+/// Finished spans waiting for export; more are dropped
+const EXPORT_QUEUE_LENGTH: usize = 8192;
+
+/// A span's identity and what has been recorded on it so far
+struct SpanData {
+    trace_id: [u8; 16],
+    span_id: [u8; 8],
+    parent_span_id: Option<[u8; 8]>,
+    sampled: bool,
+    start: SystemTime,
+    attributes: Map<String, Value>,
+}
+
+/// A closed span ready for export
+pub struct FinishedSpan {
+    name: &'static str,
+    trace_id: [u8; 16],
+    span_id: [u8; 8],
+    parent_span_id: Option<[u8; 8]>,
+    start: SystemTime,
+    end: SystemTime,
+    attributes: Map<String, Value>,
+}
+
+/// Records the daemon's spans and queues them for the exporter
+pub struct OtlpLayer {
+    sample_ratio: f64,
+    spans: mpsc::Sender<FinishedSpan>,
+}
+
+impl OtlpLayer {
+    /// Create the layer and start its exporter task on the current runtime
+    pub fn start(config: &TelemetryConfig) -> Result<Self> {
+        let runtime = tokio::runtime::Handle::try_current()
+            .map_err(|_| Error::Config("Trace export needs a running Tokio runtime".to_string()))?;
+        let (layer, spans) = Self::new(config.sample_ratio);
+        let exporter = OtlpExporter::new(config)?;
+        runtime.spawn(exporter.run(spans, config.batch_size.max(1), Duration::from_secs(config.export_interval.max(1))));
+        Ok(layer)
+    }
+
+    /// Create the layer and the receiving end of its span queue
+    pub fn new(sample_ratio: f64) -> (Self, mpsc::Receiver<FinishedSpan>) {
+        let (spans, receiver) = mpsc::channel(EXPORT_QUEUE_LENGTH);
+        (Self { sample_ratio, spans }, receiver)
+    }
+}
+
+impl<S> Layer<S> for OtlpLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+        let parent = span.parent().and_then(|parent| {
+            parent.extensions().get::<SpanData>().map(|data| (data.trace_id, data.span_id, data.sampled))
+        });
+        let (trace_id, parent_span_id, sampled) = match parent {
+            Some((trace_id, span_id, sampled)) => (trace_id, Some(span_id), sampled),
+            None => (rand::random(), None, rand::random::<f64>() < self.sample_ratio),
+        };
+
+        let mut attributes = Map::new();
+        if sampled {
+            attrs.record(&mut JsonVisitor(&mut attributes));
+        }
+        span.extensions_mut().insert(SpanData {
+            trace_id,
+            span_id: rand::random(),
+            parent_span_id,
+            sampled,
+            start: SystemTime::now(),
+            attributes,
+        });
+    }
+
+    fn on_record(&self, id: &Id, values: &Record<'_>, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+        let mut extensions = span.extensions_mut();
+        if let Some(data) = extensions.get_mut::<SpanData>().filter(|data| data.sampled) {
+            values.record(&mut JsonVisitor(&mut data.attributes));
+        }
+    }
+
+    fn on_close(&self, id: Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(&id) else {
+            return;
+        };
+        let Some(data) = span.extensions_mut().remove::<SpanData>().filter(|data| data.sampled) else {
+            return;
+        };
+        // A full queue means the collector is behind; drop rather than block
+        let _ = self.spans.try_send(FinishedSpan {
+            name: span.name(),
+            trace_id: data.trace_id,
+            span_id: data.span_id,
+            parent_span_id: data.parent_span_id,
+            start: data.start,
+            end: SystemTime::now(),
+            attributes: data.attributes,
+        });
+    }
+}
+
+/// Posts batches of spans to an OTLP/HTTP collector
+struct OtlpExporter {
+    client: reqwest::Client,
+    endpoint: String,
+    service_name: String,
+}
+
+impl OtlpExporter {
+    fn new(config: &TelemetryConfig) -> Result<Self> {
+        let mut headers = reqwest::header::HeaderMap::new();
+        for (name, value) in &config.headers {
+            let name = reqwest::header::HeaderName::from_bytes(name.as_bytes())
+                .map_err(|e| Error::Config(format!("Invalid telemetry header {}: {}", name, e)))?;
+            let value = reqwest::header::HeaderValue::from_str(value)
+                .map_err(|e| Error::Config(format!("Invalid telemetry header value for {}: {}", name, e)))?;
+            headers.insert(name, value);
+        }
+        let client = reqwest::Client::builder()
+            .default_headers(headers)
+            .timeout(Duration::from_secs(10))
+            .build()
+            .map_err(|e| Error::Config(format!("Failed to create telemetry client: {}", e)))?;
+        Ok(Self {
+            client,
+            endpoint: config.otlp_endpoint.clone(),
+            service_name: config.service_name.clone(),
+        })
+    }
+
+    /// Export whenever `batch_size` spans are waiting or `interval` passes
+    async fn run(self, mut spans: mpsc::Receiver<FinishedSpan>, batch_size: usize, interval: Duration) {
+        let mut batch = Vec::with_capacity(batch_size);
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            tokio::select! {
+                span = spans.recv() => match span {
+                    Some(span) => {
+                        batch.push(span);
+                        if batch.len() >= batch_size {
+                            self.export(&mut batch).await;
+                        }
+                    }
+                    None => {
+                        self.export(&mut batch).await;
+                        return;
+                    }
+                },
+                _ = ticker.tick() => self.export(&mut batch).await,
+            }
+        }
+    }
+
+    /// Send and clear `batch`
+    async fn export(&self, batch: &mut Vec<FinishedSpan>) {
+        if batch.is_empty() {
+            return;
+        }
+        let request = export_request(&self.service_name, batch);
+        batch.clear();
+        match self.client.post(&self.endpoint).json(&request).send().await {
+            Ok(response) if !response.status().is_success() => {
+                tracing::warn!("Trace export to {} failed: HTTP {}", self.endpoint, response.status());
+            }
+            Err(e) => tracing::warn!("Trace export to {} failed: {}", self.endpoint, e),
+            Ok(_) => {}
+        }
+    }
+}
+
+/// The OTLP/HTTP JSON body for `spans`
+pub fn export_request(service_name: &str, spans: &[FinishedSpan]) -> Value {
+    let spans: Vec<Value> = spans.iter().map(|span| {
+        json!({
+            "traceId": hex(&span.trace_id),
+            "spanId": hex(&span.span_id),
+            "parentSpanId": span.parent_span_id.as_ref().map(|id| hex(id)).unwrap_or_default(),
+            "name": span.name,
+            // SPAN_KIND_SERVER for roots, SPAN_KIND_INTERNAL below them
+            "kind": if span.parent_span_id.is_some() { 1 } else { 2 },
+            "startTimeUnixNano": unix_nanos(span.start).to_string(),
+            "endTimeUnixNano": unix_nanos(span.end).to_string(),
+            "attributes": span.attributes.iter().map(|(key, value)| attribute(key, value)).collect::<Vec<_>>(),
+        })
+    }).collect();
+
+    json!({
+        "resourceSpans": [{
+            "resource": { "attributes": [attribute("service.name", &Value::from(service_name))] },
+            "scopeSpans": [{
+                "scope": { "name": "rustircd", "version": env!("CARGO_PKG_VERSION") },
+                "spans": spans,
+            }],
+        }],
+    })
+}
+
+/// An OTLP key/value attribute
+fn attribute(key: &str, value: &Value) -> Value {
+    let value = match value {
+        Value::Bool(value) => json!({ "boolValue": value }),
+        Value::Number(number) if number.is_f64() => json!({ "doubleValue": number }),
+        Value::Number(number) => json!({ "intValue": number.to_string() }),
+        Value::String(value) => json!({ "stringValue": value }),
+        other => json!({ "stringValue": other.to_string() }),
+    };
+    json!({ "key": key, "value": value })
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn unix_nanos(time: SystemTime) -> u128 {
+    time.duration_since(UNIX_EPOCH).map(|elapsed| elapsed.as_nanos()).unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    #[test]
+    fn test_spans_exported_as_one_trace() {
+        let (layer, mut spans) = OtlpLayer::new(1.0);
+        let subscriber = tracing_subscriber::registry().with(layer);
+        tracing::subscriber::with_default(subscriber, || {
+            let command = tracing::info_span!("irc.command", client_id = 7, command = tracing::field::Empty);
+            command.in_scope(|| {
+                tracing::info_span!("irc.parse").in_scope(|| {});
+            });
+            command.record("command", "PRIVMSG");
+        });
+
+        let parse = spans.try_recv().unwrap();
+        let command = spans.try_recv().unwrap();
+        assert_eq!((parse.name, command.name), ("irc.parse", "irc.command"));
+        assert_eq!(parse.trace_id, command.trace_id);
+        assert_eq!(parse.parent_span_id, Some(command.span_id));
+        assert_eq!(command.parent_span_id, None);
+
+        let request = export_request("rustircd", &[command]);
+        let span = &request["resourceSpans"][0]["scopeSpans"][0]["spans"][0];
+        assert_eq!(span["name"], "irc.command");
+        assert_eq!(span["kind"], 2);
+        assert_eq!(span["traceId"].as_str().unwrap().len(), 32);
+        assert_eq!(span["attributes"][0], json!({ "key": "client_id", "value": { "intValue": "7" } }));
+        assert_eq!(span["attributes"][1], json!({ "key": "command", "value": { "stringValue": "PRIVMSG" } }));
+    }
+
+    #[test]
+    fn test_unsampled_traces_dropped() {
+        let (layer, mut spans) = OtlpLayer::new(0.0);
+        let subscriber = tracing_subscriber::registry().with(layer);
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info_span!("irc.command").in_scope(|| {
+                tracing::info_span!("irc.parse").in_scope(|| {});
+            });
+        });
+        assert!(spans.try_recv().is_err());
+    }
+}
@@ -79,6 +79,34 @@ mod tests {
         assert!(!user.is_operator);
     }
     
+    #[test]
+    fn test_silence_list() {
+        let new_user = |nick: &str, host: &str| crate::User::new(
+            nick.to_string(),
+            "user".to_string(),
+            "Test User".to_string(),
+            host.to_string(),
+            "server.example.com".to_string(),
+        );
+        let mut alice = new_user("alice", "alice.example.com");
+        let bob = new_user("bob", "bob.example.com");
+        let carol = new_user("carol", "spam.example.net");
+        
+        assert_eq!(crate::User::normalize_silence_mask("bob"), "bob!*@*");
+        assert_eq!(crate::User::normalize_silence_mask("*@spam.example.net"), "*!*@spam.example.net");
+        
+        assert_eq!(alice.add_silence("bob", 2).as_deref(), Some("bob!*@*"));
+        assert!(alice.add_silence("BOB!*@*", 2).is_none());
+        assert!(alice.add_silence("*@spam.example.net", 2).is_some());
+        assert!(alice.add_silence("dave", 2).is_none());
+        assert!(alice.is_silencing(&bob));
+        assert!(alice.is_silencing(&carol));
+        
+        assert_eq!(alice.remove_silence("bob").as_deref(), Some("bob!*@*"));
+        assert!(!alice.is_silencing(&bob));
+        assert!(alice.remove_silence("bob").is_none());
+    }
+    
     
     #[test]
     fn test_utils() {
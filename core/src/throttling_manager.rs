@@ -1,8 +1,18 @@
 //! Throttling manager for connection rate limiting
+//!
+//! Also counts failed OPER, SASL and PASS attempts per IP, locking an IP
+//! out for exponentially longer after repeated failures and optionally
+//! D-lining it, in the manner of fail2ban.
+//!
+//! Addresses in `exempt` and E-lines added at runtime by operators are
+//! never throttled. IPv6 connections are counted per `ipv6_prefix` network
+//! rather than per address.
 
-use crate::Result;
+use crate::ban_manager::{ip_in_network, ip_network, parse_ip_mask};
+use crate::{BanEntry, Error, Result};
 use std::collections::HashMap;
 use std::net::IpAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 use tokio::sync::RwLock;
@@ -101,31 +111,156 @@ impl ThrottleEntry {
     }
 }
 
+/// A credential check whose failures count towards a lockout
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuthFailureKind {
+    /// OPER or CHALLENGE
+    Oper,
+    /// SASL AUTHENTICATE
+    Sasl,
+    /// Connection password
+    Password,
+}
+
+impl AuthFailureKind {
+    /// Command name used in notices and STATS F
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AuthFailureKind::Oper => "OPER",
+            AuthFailureKind::Sasl => "SASL",
+            AuthFailureKind::Password => "PASS",
+        }
+    }
+}
+
+/// What one failed attempt led to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct AuthFailureOutcome {
+    /// Failures from the IP since it was last seen clean
+    pub failures: u32,
+    /// Lockout this failure started
+    pub lockout: Option<Duration>,
+    /// Whether the IP just reached the D-line threshold
+    pub dline: bool,
+}
+
+/// Failed attempts tracked for one IP
+#[derive(Debug, Clone, Default)]
+struct AuthFailureEntry {
+    /// Failures within the counting window
+    recent: Vec<Instant>,
+    /// Failures since the entry was created
+    total: u32,
+    /// Lockouts so far, which set the next one's length
+    lockouts: u32,
+    locked_until: Option<Instant>,
+    dlined: bool,
+}
+
+impl AuthFailureEntry {
+    fn remaining_lockout(&self, now: Instant) -> Option<Duration> {
+        self.locked_until.filter(|until| *until > now).map(|until| until - now)
+    }
+}
+
+/// Totals of failed attempts, for STATS F
+#[derive(Debug, Default)]
+struct AuthFailureCounters {
+    oper: AtomicU64,
+    sasl: AtomicU64,
+    password: AtomicU64,
+    lockouts: AtomicU64,
+    dlines: AtomicU64,
+}
+
+/// An address range that is never throttled
+#[derive(Debug, Clone)]
+pub struct ThrottleExemption {
+    /// IP address or CIDR range as given
+    pub mask: String,
+    network: IpAddr,
+    prefix: u8,
+    /// Why the range is exempt
+    pub reason: String,
+    /// Operator who added the exemption, or `None` if it came from the config
+    pub set_by: Option<String>,
+}
+
+impl ThrottleExemption {
+    fn new(mask: &str, reason: &str, set_by: Option<&str>) -> Result<Self> {
+        let (network, prefix) = parse_ip_mask(mask)
+            .ok_or_else(|| Error::Config(format!("Invalid IP or CIDR mask: {}", mask)))?;
+        Ok(Self {
+            mask: mask.to_string(),
+            network,
+            prefix,
+            reason: reason.to_string(),
+            set_by: set_by.map(str::to_string),
+        })
+    }
+
+    fn matches(&self, ip: IpAddr) -> bool {
+        ip_in_network(ip, self.network, self.prefix)
+    }
+}
+
+/// The address connections from `ip` are throttled under: the address
+/// itself, or for IPv6 its network of `ipv6_prefix` bits
+pub fn throttle_key(ip: IpAddr, ipv6_prefix: u8) -> IpAddr {
+    match ip.to_canonical() {
+        ip @ IpAddr::V6(_) => ip_network(ip, ipv6_prefix),
+        ip => ip,
+    }
+}
+
 /// Throttling manager for connection rate limiting
 pub struct ThrottlingManager {
     /// IP address to throttle entry mapping
     throttle_map: Arc<RwLock<HashMap<IpAddr, ThrottleEntry>>>,
+    /// Failed login attempts by IP
+    auth_map: Arc<RwLock<HashMap<IpAddr, AuthFailureEntry>>>,
+    auth_counters: AuthFailureCounters,
+    /// Configured exemptions followed by E-lines added at runtime
+    exemptions: RwLock<Vec<ThrottleExemption>>,
     /// Throttling configuration
     config: Arc<crate::config::ThrottlingConfig>,
 }
 
+impl Default for ThrottlingManager {
+    fn default() -> Self {
+        Self::new(crate::config::ThrottlingConfig::default())
+    }
+}
+
 impl ThrottlingManager {
     /// Create a new throttling manager
     pub fn new(config: crate::config::ThrottlingConfig) -> Self {
+        let exemptions = config.exempt.iter()
+            .filter_map(|mask| match ThrottleExemption::new(mask, "Configured exemption", None) {
+                Ok(exemption) => Some(exemption),
+                Err(e) => {
+                    warn!("Ignoring throttling exemption: {}", e);
+                    None
+                }
+            })
+            .collect();
         Self {
             throttle_map: Arc::new(RwLock::new(HashMap::new())),
+            auth_map: Arc::new(RwLock::new(HashMap::new())),
+            auth_counters: AuthFailureCounters::default(),
+            exemptions: RwLock::new(exemptions),
             config: Arc::new(config),
         }
     }
 
     /// Check if an IP address is allowed to connect
     pub async fn check_connection_allowed(&self, ip_addr: IpAddr) -> Result<bool> {
-        if !self.config.enabled {
+        if !self.config.enabled || self.is_exempt(ip_addr).await {
             return Ok(true);
         }
 
         let mut throttle_map = self.throttle_map.write().await;
-        let entry = throttle_map.entry(ip_addr).or_insert_with(ThrottleEntry::new);
+        let entry = throttle_map.entry(throttle_key(ip_addr, self.config.ipv6_prefix)).or_insert_with(ThrottleEntry::new);
         
         let allowed = entry.add_connection(&self.config, ip_addr);
         
@@ -144,7 +279,7 @@ impl ThrottlingManager {
     /// Get throttling status for an IP address
     pub async fn get_throttle_status(&self, ip_addr: IpAddr) -> (bool, u8, u64) {
         let throttle_map = self.throttle_map.read().await;
-        if let Some(entry) = throttle_map.get(&ip_addr) {
+        if let Some(entry) = throttle_map.get(&throttle_key(ip_addr, self.config.ipv6_prefix)) {
             let is_throttled = entry.is_throttled();
             let stage = entry.stage;
             let remaining = entry.remaining_throttle_seconds();
@@ -154,10 +289,164 @@ impl ThrottlingManager {
         }
     }
 
+    /// Whether `ip` is covered by a configured exemption or an E-line
+    pub async fn is_exempt(&self, ip: IpAddr) -> bool {
+        self.find_exemption(ip).await.is_some()
+    }
+
+    /// The first exemption covering `ip`, if any
+    pub async fn find_exemption(&self, ip: IpAddr) -> Option<ThrottleExemption> {
+        self.exemptions.read().await.iter().find(|exemption| exemption.matches(ip)).cloned()
+    }
+
+    /// Add an E-line exempting `mask` from throttling
+    ///
+    /// Lifts any throttle already applied to addresses in the range.
+    pub async fn add_exemption(&self, mask: &str, reason: &str, set_by: &str) -> Result<()> {
+        let exemption = ThrottleExemption::new(mask, reason, Some(set_by))?;
+        self.throttle_map.write().await.retain(|ip, _| !exemption.matches(*ip));
+        let mut exemptions = self.exemptions.write().await;
+        exemptions.retain(|existing| existing.set_by.is_none() || !existing.mask.eq_ignore_ascii_case(mask));
+        exemptions.push(exemption);
+        Ok(())
+    }
+
+    /// Remove an E-line, returning whether there was one
+    ///
+    /// Exemptions from the config file stay until the config changes.
+    pub async fn remove_exemption(&self, mask: &str) -> bool {
+        let mut exemptions = self.exemptions.write().await;
+        let before = exemptions.len();
+        exemptions.retain(|existing| existing.set_by.is_none() || !existing.mask.eq_ignore_ascii_case(mask));
+        exemptions.len() < before
+    }
+
+    /// Current exemptions, configured ones first
+    pub async fn exemptions(&self) -> Vec<ThrottleExemption> {
+        self.exemptions.read().await.clone()
+    }
+
+    /// STATS T lines: each exemption, then each IP being tracked
+    pub async fn throttle_stats(&self) -> Vec<String> {
+        let mut lines: Vec<String> = self.exemptions.read().await.iter()
+            .map(|exemption| format!(
+                "exempt {} by {} ({})",
+                exemption.mask,
+                exemption.set_by.as_deref().unwrap_or("config"),
+                exemption.reason,
+            ))
+            .collect();
+        let throttle_map = self.throttle_map.read().await;
+        let mut entries: Vec<_> = throttle_map.iter().collect();
+        entries.sort_by_key(|(ip, _)| **ip);
+        for (ip, entry) in entries {
+            let ip = match ip {
+                IpAddr::V6(_) if self.config.ipv6_prefix < 128 => format!("{}/{}", ip, self.config.ipv6_prefix),
+                _ => ip.to_string(),
+            };
+            lines.push(format!(
+                "{} connections {} stage {} throttled {}s",
+                ip,
+                entry.connection_times.len(),
+                entry.stage,
+                entry.remaining_throttle_seconds(),
+            ));
+        }
+        lines
+    }
+
+    /// Count a failed credential check from `ip`
+    ///
+    /// Reaching `max_failures` within the window locks the IP out, each
+    /// lockout `lockout_factor` times longer than the last. Reaching
+    /// `dline_after_failures` asks for a D-line, once.
+    pub async fn record_auth_failure(&self, ip: IpAddr, kind: AuthFailureKind) -> AuthFailureOutcome {
+        let counter = match kind {
+            AuthFailureKind::Oper => &self.auth_counters.oper,
+            AuthFailureKind::Sasl => &self.auth_counters.sasl,
+            AuthFailureKind::Password => &self.auth_counters.password,
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+        let config = &self.config.auth_failures;
+        if !config.enabled {
+            return AuthFailureOutcome::default();
+        }
+
+        let now = Instant::now();
+        let window = Duration::from_secs(config.window_seconds);
+        let mut auth_map = self.auth_map.write().await;
+        let entry = auth_map.entry(ip).or_default();
+        entry.recent.retain(|time| now.duration_since(*time) < window);
+        entry.recent.push(now);
+        entry.total = entry.total.saturating_add(1);
+
+        let mut outcome = AuthFailureOutcome { failures: entry.total, ..Default::default() };
+        if entry.recent.len() >= config.max_failures.max(1) as usize {
+            let factor = config.lockout_factor.max(1).saturating_pow(entry.lockouts);
+            let seconds = config.initial_lockout_seconds.saturating_mul(factor).min(config.max_lockout_seconds);
+            entry.lockouts += 1;
+            entry.locked_until = Some(now + Duration::from_secs(seconds));
+            entry.recent.clear();
+            outcome.lockout = Some(Duration::from_secs(seconds));
+            self.auth_counters.lockouts.fetch_add(1, Ordering::Relaxed);
+            warn!("Locking {} out of {} for {}s after {} failed attempts", ip, kind.as_str(), seconds, entry.total);
+        }
+        if config.dline_after_failures > 0 && entry.total >= config.dline_after_failures && !entry.dlined {
+            entry.dlined = true;
+            outcome.dline = true;
+            self.auth_counters.dlines.fetch_add(1, Ordering::Relaxed);
+        }
+        outcome
+    }
+
+    /// How much longer `ip` is locked out of OPER, SASL and PASS, if it is
+    pub async fn auth_lockout(&self, ip: IpAddr) -> Option<Duration> {
+        if !self.config.auth_failures.enabled {
+            return None;
+        }
+        self.auth_map.read().await.get(&ip)?.remaining_lockout(Instant::now())
+    }
+
+    /// The temporary D-line for an IP that reached `dline_after_failures`
+    pub fn auth_dline(&self, ip: IpAddr, set_by: &str) -> BanEntry {
+        let config = &self.config.auth_failures;
+        BanEntry::new(
+            "DLINE",
+            &ip.to_string(),
+            "Too many failed login attempts",
+            set_by,
+            Some(config.dline_duration_seconds),
+        )
+    }
+
+    /// STATS F lines: totals, then each IP with failures or a lockout
+    pub async fn auth_failure_stats(&self) -> Vec<String> {
+        let counters = &self.auth_counters;
+        let mut lines = vec![format!(
+            "OPER {} SASL {} PASS {} lockouts {} dlines {}",
+            counters.oper.load(Ordering::Relaxed),
+            counters.sasl.load(Ordering::Relaxed),
+            counters.password.load(Ordering::Relaxed),
+            counters.lockouts.load(Ordering::Relaxed),
+            counters.dlines.load(Ordering::Relaxed),
+        )];
+        let now = Instant::now();
+        let auth_map = self.auth_map.read().await;
+        let mut entries: Vec<_> = auth_map.iter().collect();
+        entries.sort_by_key(|(ip, _)| **ip);
+        for (ip, entry) in entries {
+            let locked = entry.remaining_lockout(now).map_or(0, |remaining| remaining.as_secs());
+            lines.push(format!("{} failures {} lockouts {} locked {}s", ip, entry.total, entry.lockouts, locked));
+        }
+        lines
+    }
+
     /// Start the cleanup task to remove expired entries
     pub fn start_cleanup_task(&self) {
         let throttle_map = self.throttle_map.clone();
+        let auth_map = self.auth_map.clone();
         let cleanup_interval = self.config.cleanup_interval_seconds;
+        let auth_window = Duration::from_secs(self.config.auth_failures.window_seconds);
         
         tokio::spawn(async move {
             let mut interval = interval(Duration::from_secs(cleanup_interval));
@@ -165,7 +454,13 @@ impl ThrottlingManager {
             loop {
                 interval.tick().await;
                 
-                let _now = Instant::now();
+                let now = Instant::now();
+                // Forget IPs that are no longer locked out and haven't failed lately
+                auth_map.write().await.retain(|_, entry| {
+                    entry.remaining_lockout(now).is_some()
+                        || entry.recent.iter().any(|time| now.duration_since(*time) < auth_window)
+                });
+                
                 let mut throttle_map = throttle_map.write().await;
                 let initial_count = throttle_map.len();
                 
@@ -187,6 +482,9 @@ impl ThrottlingManager {
     pub async fn init(&self) -> Result<()> {
         if !self.config.enabled {
             info!("Throttling manager disabled");
+            if self.config.auth_failures.enabled {
+                self.start_cleanup_task();
+            }
             return Ok(());
         }
         
@@ -215,11 +513,14 @@ mod tests {
         crate::config::ThrottlingConfig {
             enabled: true,
             max_connections_per_ip: 3,
+            ipv6_prefix: 64,
             time_window_seconds: 60,
             initial_throttle_seconds: 5,
             max_stages: 3,
             stage_factor: 2,
             cleanup_interval_seconds: 300,
+            auth_failures: Default::default(),
+            exempt: Vec::new(),
         }
     }
 
@@ -250,6 +551,21 @@ mod tests {
         assert!(!manager.check_connection_allowed(ip).await.unwrap());
     }
 
+    #[tokio::test]
+    async fn test_ipv6_throttled_per_prefix() {
+        let manager = ThrottlingManager::new(create_test_config());
+        for host in 1..=3 {
+            let ip = IpAddr::from_str(&format!("2001:db8:0:1::{}", host)).unwrap();
+            assert!(manager.check_connection_allowed(ip).await.unwrap());
+        }
+
+        // Another address in the same /64 is counted with the rest
+        let same_prefix = IpAddr::from_str("2001:db8:0:1:ffff::1").unwrap();
+        assert!(!manager.check_connection_allowed(same_prefix).await.unwrap());
+        assert!(manager.check_connection_allowed(IpAddr::from_str("2001:db8:0:2::1").unwrap()).await.unwrap());
+        assert!(manager.throttle_stats().await.iter().any(|line| line.starts_with("2001:db8:0:1::/64 ")));
+    }
+
     #[tokio::test]
     async fn test_throttle_stages() {
         let config = create_test_config();
@@ -270,6 +586,62 @@ mod tests {
         }
     }
 
+    #[tokio::test]
+    async fn test_auth_failure_lockouts() {
+        let mut config = create_test_config();
+        config.auth_failures.max_failures = 2;
+        config.auth_failures.initial_lockout_seconds = 10;
+        config.auth_failures.dline_after_failures = 4;
+        let manager = ThrottlingManager::new(config);
+        let ip = IpAddr::from_str("192.0.2.1").unwrap();
+
+        assert_eq!(manager.record_auth_failure(ip, AuthFailureKind::Oper).await.lockout, None);
+        assert!(manager.auth_lockout(ip).await.is_none());
+        let outcome = manager.record_auth_failure(ip, AuthFailureKind::Sasl).await;
+        assert_eq!(outcome.lockout, Some(Duration::from_secs(10)));
+        assert!(manager.auth_lockout(ip).await.is_some());
+
+        // The next lockout is twice as long, and the IP is D-lined once
+        manager.record_auth_failure(ip, AuthFailureKind::Password).await;
+        let outcome = manager.record_auth_failure(ip, AuthFailureKind::Password).await;
+        assert_eq!(outcome, AuthFailureOutcome { failures: 4, lockout: Some(Duration::from_secs(20)), dline: true });
+        assert!(!manager.record_auth_failure(ip, AuthFailureKind::Oper).await.dline);
+
+        let stats = manager.auth_failure_stats().await;
+        assert_eq!(stats[0], "OPER 2 SASL 1 PASS 2 lockouts 2 dlines 1");
+        assert!(stats[1].starts_with("192.0.2.1 failures 5 lockouts 2"));
+        assert_eq!(manager.auth_dline(ip, "irc.test").mask, "192.0.2.1");
+    }
+
+    #[tokio::test]
+    async fn test_exemptions() {
+        let mut config = create_test_config();
+        config.max_connections_per_ip = 1;
+        config.exempt = vec!["10.0.0.0/8".to_string(), "bogus".to_string()];
+        let manager = ThrottlingManager::new(config);
+        let exempt = IpAddr::from_str("10.1.2.3").unwrap();
+        let ip = IpAddr::from_str("192.0.2.7").unwrap();
+
+        for _ in 0..5 {
+            assert!(manager.check_connection_allowed(exempt).await.unwrap());
+        }
+        assert!(manager.check_connection_allowed(ip).await.unwrap());
+        assert!(!manager.check_connection_allowed(ip).await.unwrap());
+
+        // An E-line lifts the existing throttle, and only E-lines can be removed
+        assert!(manager.add_exemption("192.0.2.0/24", "services", "oper").await.is_ok());
+        assert!(manager.add_exemption("192.0.2.0/33", "bad", "oper").await.is_err());
+        assert!(manager.check_connection_allowed(ip).await.unwrap());
+        assert_eq!(manager.throttle_stats().await, vec![
+            "exempt 10.0.0.0/8 by config (Configured exemption)".to_string(),
+            "exempt 192.0.2.0/24 by oper (services)".to_string(),
+        ]);
+        assert!(!manager.remove_exemption("10.0.0.0/8").await);
+        assert!(manager.remove_exemption("192.0.2.0/24").await);
+        assert!(manager.check_connection_allowed(ip).await.unwrap());
+        assert!(!manager.check_connection_allowed(ip).await.unwrap());
+    }
+
     #[tokio::test]
     async fn test_disabled_manager() {
         let mut config = create_test_config();
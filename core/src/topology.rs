@@ -0,0 +1,211 @@
+//! Network topology
+//!
+//! Every server on the network is recorded with the server that introduced
+//! it, forming a tree rooted at the local server. Directly linked servers are
+//! children of the root; anything further away is reached through the direct
+//! link at the top of its branch.
+
+use std::collections::HashMap;
+
+/// A remote server in the network tree
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ServerNode {
+    /// Server name
+    pub name: String,
+    /// Server that introduced this one (the local server for direct links)
+    pub uplink: String,
+    /// Number of hops from the local server
+    pub hop_count: u32,
+    /// Server description
+    pub description: String,
+}
+
+/// Tree of servers as seen from the local server
+#[derive(Debug, Clone)]
+pub struct NetworkTopology {
+    local: String,
+    servers: HashMap<String, ServerNode>,
+}
+
+impl NetworkTopology {
+    /// Create a topology containing only the local server
+    pub fn new(local: impl Into<String>) -> Self {
+        Self {
+            local: local.into(),
+            servers: HashMap::new(),
+        }
+    }
+
+    /// Name of the local server
+    pub fn local(&self) -> &str {
+        &self.local
+    }
+
+    /// Record a server introduced by `uplink`
+    ///
+    /// The hop count follows from the uplink's position in the tree. Unknown
+    /// uplinks are rejected so the tree never has detached branches.
+    pub fn add_server(&mut self, name: &str, uplink: &str, description: &str) -> bool {
+        if name == self.local {
+            return false;
+        }
+        let hop_count = if uplink == self.local {
+            1
+        } else {
+            match self.servers.get(uplink) {
+                Some(node) => node.hop_count + 1,
+                None => return false,
+            }
+        };
+        self.servers.insert(name.to_string(), ServerNode {
+            name: name.to_string(),
+            uplink: uplink.to_string(),
+            hop_count,
+            description: description.to_string(),
+        });
+        true
+    }
+
+    /// Look up a remote server
+    pub fn get(&self, name: &str) -> Option<&ServerNode> {
+        self.servers.get(name)
+    }
+
+    /// Whether a server is known (the local server always is)
+    pub fn contains(&self, name: &str) -> bool {
+        name == self.local || self.servers.contains_key(name)
+    }
+
+    /// Hops from the local server, 0 for the local server itself
+    pub fn hop_count(&self, name: &str) -> Option<u32> {
+        if name == self.local {
+            return Some(0);
+        }
+        self.servers.get(name).map(|node| node.hop_count)
+    }
+
+    /// Directly linked server that messages for `name` are sent through
+    pub fn route(&self, name: &str) -> Option<&str> {
+        let mut node = self.servers.get(name)?;
+        while node.uplink != self.local {
+            node = self.servers.get(&node.uplink)?;
+        }
+        Some(&node.name)
+    }
+
+    /// Whether `name` is `link` itself or somewhere behind it
+    pub fn is_behind(&self, name: &str, link: &str) -> bool {
+        self.route(name) == Some(link)
+    }
+
+    /// Servers introduced by `name`
+    pub fn children(&self, name: &str) -> Vec<&ServerNode> {
+        self.servers.values().filter(|node| node.uplink == name).collect()
+    }
+
+    /// Remove a server and everything behind it
+    ///
+    /// Returns the removed server names, the split server first.
+    pub fn remove_subtree(&mut self, name: &str) -> Vec<String> {
+        if !self.servers.contains_key(name) {
+            return Vec::new();
+        }
+        let mut removed = vec![name.to_string()];
+        let mut index = 0;
+        while index < removed.len() {
+            let children: Vec<String> = self.children(&removed[index]).into_iter().map(|node| node.name.clone()).collect();
+            removed.extend(children);
+            index += 1;
+        }
+        for server in &removed {
+            self.servers.remove(server);
+        }
+        removed
+    }
+
+    /// All remote servers, nearest first
+    pub fn servers(&self) -> Vec<&ServerNode> {
+        let mut servers: Vec<&ServerNode> = self.servers.values().collect();
+        servers.sort_by(|a, b| a.hop_count.cmp(&b.hop_count).then_with(|| a.name.cmp(&b.name)));
+        servers
+    }
+
+    /// All remote servers in tree order
+    ///
+    /// Each server is followed by the servers behind it, siblings sorted by
+    /// name, so indenting by hop count draws the tree.
+    pub fn tree(&self) -> Vec<&ServerNode> {
+        let mut ordered = Vec::with_capacity(self.servers.len());
+        let mut stack = self.sorted_children(&self.local);
+        while let Some(node) = stack.pop() {
+            stack.extend(self.sorted_children(&node.name));
+            ordered.push(node);
+        }
+        ordered
+    }
+
+    /// Children of `name`, reversed so popping from the end yields them by name
+    fn sorted_children(&self, name: &str) -> Vec<&ServerNode> {
+        let mut children = self.children(name);
+        children.sort_by(|a, b| b.name.cmp(&a.name));
+        children
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn network() -> NetworkTopology {
+        // local - hub - leaf1 - leaf2
+        //       \ edge
+        let mut topology = NetworkTopology::new("local.test");
+        assert!(topology.add_server("hub.test", "local.test", "Hub"));
+        assert!(topology.add_server("edge.test", "local.test", "Edge"));
+        assert!(topology.add_server("leaf1.test", "hub.test", "Leaf 1"));
+        assert!(topology.add_server("leaf2.test", "leaf1.test", "Leaf 2"));
+        topology
+    }
+
+    #[test]
+    fn test_hop_counts_and_routes() {
+        let topology = network();
+        assert_eq!(topology.hop_count("local.test"), Some(0));
+        assert_eq!(topology.hop_count("leaf2.test"), Some(3));
+        assert_eq!(topology.route("leaf2.test"), Some("hub.test"));
+        assert_eq!(topology.route("edge.test"), Some("edge.test"));
+        assert!(topology.is_behind("leaf1.test", "hub.test"));
+        assert!(!topology.is_behind("leaf1.test", "edge.test"));
+        assert_eq!(topology.route("unknown.test"), None);
+    }
+
+    #[test]
+    fn test_unknown_uplink_rejected() {
+        let mut topology = network();
+        assert!(!topology.add_server("orphan.test", "nowhere.test", "Orphan"));
+        assert!(!topology.add_server("local.test", "hub.test", "Loop"));
+        assert!(!topology.contains("orphan.test"));
+    }
+
+    #[test]
+    fn test_tree_order() {
+        let mut topology = network();
+        assert!(topology.add_server("zulu.test", "edge.test", "Zulu"));
+        let names: Vec<&str> = topology.tree().iter().map(|node| node.name.as_str()).collect();
+        assert_eq!(names, vec!["edge.test", "zulu.test", "hub.test", "leaf1.test", "leaf2.test"]);
+    }
+
+    #[test]
+    fn test_remove_subtree() {
+        let mut topology = network();
+        let removed = topology.remove_subtree("leaf1.test");
+        assert_eq!(removed, vec!["leaf1.test".to_string(), "leaf2.test".to_string()]);
+        assert!(topology.contains("hub.test"));
+        assert!(!topology.contains("leaf2.test"));
+
+        let removed = topology.remove_subtree("hub.test");
+        assert_eq!(removed, vec!["hub.test".to_string()]);
+        let names: Vec<&str> = topology.servers().iter().map(|node| node.name.as_str()).collect();
+        assert_eq!(names, vec!["edge.test"]);
+    }
+}
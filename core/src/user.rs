@@ -3,7 +3,7 @@
 use crate::config::OperatorFlag;
 use crate::Prefix;
 use chrono::{DateTime, Utc};
-use std::collections::HashSet;
+use std::collections::{BTreeSet, HashSet};
 use uuid::Uuid;
 
 /// User state for netsplit recovery
@@ -43,8 +43,10 @@ pub struct User {
     pub username: String,
     /// Real name
     pub realname: String,
-    /// Hostname/IP
+    /// Displayed hostname (cloaked when umode +x is set)
     pub host: String,
+    /// Real hostname/IP, shown only to operators and used for ban matching
+    pub real_host: String,
     /// Server name
     pub server: String,
     /// Registration time
@@ -71,6 +73,14 @@ pub struct User {
     pub state: UserState,
     /// Time when user entered netsplit state (for delayed cleanup)
     pub split_at: Option<DateTime<Utc>>,
+    /// SILENCE masks; messages and invites from matching users are dropped
+    pub silence_list: Vec<String>,
+    /// Server notice masks subscribed to with umode +s (operators only)
+    pub snomasks: BTreeSet<char>,
+    /// Services account the user is identified to
+    pub account: Option<String>,
+    /// SHA-256 fingerprint of the TLS client certificate (local users only)
+    pub certfp: Option<String>,
 }
 
 impl User {
@@ -88,6 +98,7 @@ impl User {
             nick,
             username,
             realname,
+            real_host: host.clone(),
             host,
             server,
             registered_at: now,
@@ -102,6 +113,10 @@ impl User {
             bot_info: None,
             state: UserState::Active,
             split_at: None,
+            silence_list: Vec::new(),
+            snomasks: BTreeSet::new(),
+            account: None,
+            certfp: None,
         }
     }
 
@@ -129,6 +144,42 @@ impl User {
         &self.host
     }
 
+    /// Get the real hostname, regardless of cloaking
+    pub fn real_hostname(&self) -> &str {
+        &self.real_host
+    }
+
+    /// Check if the displayed host differs from the real host
+    pub fn is_cloaked(&self) -> bool {
+        self.host != self.real_host
+    }
+
+    /// Check if `viewer` may see the real host (operators and the user themself)
+    pub fn can_see_real_host(&self, viewer: Option<&User>) -> bool {
+        viewer.is_some_and(|v| v.is_operator || v.id == self.id || v.nick.eq_ignore_ascii_case(&self.nick))
+    }
+
+    /// Get the host to show to `viewer`
+    pub fn host_for(&self, viewer: Option<&User>) -> &str {
+        if self.can_see_real_host(viewer) {
+            &self.real_host
+        } else {
+            &self.host
+        }
+    }
+
+    /// Apply a cloaked host and set umode +x
+    pub fn set_cloak(&mut self, cloaked_host: String) {
+        self.host = cloaked_host;
+        self.add_mode_internal('x');
+    }
+
+    /// Restore the real host and clear umode +x
+    pub fn clear_cloak(&mut self) {
+        self.host = self.real_host.clone();
+        self.remove_mode_internal('x');
+    }
+
     /// Check if user is an operator
     pub fn is_operator(&self) -> bool {
         self.is_operator
@@ -218,6 +269,56 @@ impl User {
         self.away_message.is_some()
     }
 
+    /// Expand a SILENCE mask to full `nick!user@host` form
+    ///
+    /// `nick` becomes `nick!*@*`, `user@host` becomes `*!user@host`.
+    pub fn normalize_silence_mask(mask: &str) -> String {
+        match (mask.contains('!'), mask.contains('@')) {
+            (true, true) => mask.to_string(),
+            (true, false) => format!("{}@*", mask),
+            (false, true) => format!("*!{}", mask),
+            (false, false) => format!("{}!*@*", mask),
+        }
+    }
+
+    /// Add a SILENCE mask, returning the normalized mask if it was added
+    ///
+    /// Returns `None` if the mask is already present or the list already
+    /// holds `max_entries` masks.
+    pub fn add_silence(&mut self, mask: &str, max_entries: usize) -> Option<String> {
+        let mask = Self::normalize_silence_mask(mask);
+        if self.silence_list.iter().any(|m| m.eq_ignore_ascii_case(&mask))
+            || self.silence_list.len() >= max_entries
+        {
+            return None;
+        }
+        self.silence_list.push(mask.clone());
+        Some(mask)
+    }
+
+    /// Remove a SILENCE mask, returning the removed mask
+    pub fn remove_silence(&mut self, mask: &str) -> Option<String> {
+        let mask = Self::normalize_silence_mask(mask);
+        let index = self.silence_list.iter().position(|m| m.eq_ignore_ascii_case(&mask))?;
+        Some(self.silence_list.remove(index))
+    }
+
+    /// Check whether this user has silenced `sender`
+    ///
+    /// Masks are matched against both the sender's displayed and real host.
+    pub fn is_silencing(&self, sender: &User) -> bool {
+        if self.silence_list.is_empty() {
+            return false;
+        }
+        let hostmasks = [
+            format!("{}!{}@{}", sender.nick, sender.username, sender.host),
+            format!("{}!{}@{}", sender.nick, sender.username, sender.real_host),
+        ];
+        self.silence_list.iter().any(|mask| {
+            hostmasks.iter().any(|hostmask| crate::utils::string::wildcard_match(mask, hostmask))
+        })
+    }
+
     /// Get user info string for WHOIS
     pub fn whois_info(&self) -> String {
         format!(
@@ -243,20 +344,46 @@ impl User {
     }
 
     /// Set bot mode for user
+    ///
+    /// Keeps the `+B` user mode in step with the bot registration.
     pub fn set_bot_mode(&mut self, bot_info: BotInfo) {
         self.is_bot = true;
         self.bot_info = Some(bot_info);
+        self.modes.insert('B');
     }
 
     /// Remove bot mode from user
     pub fn remove_bot_mode(&mut self) {
         self.is_bot = false;
         self.bot_info = None;
+        self.modes.remove(&'B');
     }
 
-    /// Check if user is a bot
+    /// Log the user in to a services account, or out with `None`
+    ///
+    /// Keeps the `+r` user mode in step with the account.
+    pub fn set_account(&mut self, account: Option<String>) {
+        if account.is_some() {
+            self.modes.insert('r');
+        } else {
+            self.modes.remove(&'r');
+        }
+        self.account = account;
+    }
+
+    /// Check if user is connected over TLS (`+z`)
+    pub fn is_secure(&self) -> bool {
+        self.modes.contains(&'z')
+    }
+
+    /// Check if user is identified to a services account
+    pub fn is_identified(&self) -> bool {
+        self.account.is_some()
+    }
+
+    /// Check if user is a bot, either registered as one or marked `+B`
     pub fn is_bot(&self) -> bool {
-        self.is_bot
+        self.is_bot || self.modes.contains(&'B')
     }
 
     /// Get bot information
@@ -266,7 +393,7 @@ impl User {
 
     /// Get bot tag for messages
     pub fn get_bot_tag(&self) -> Option<String> {
-        if self.is_bot {
+        if self.is_bot() {
             Some("bot".to_string())
         } else {
             None
@@ -303,6 +430,7 @@ impl User {
         self.remove_mode_internal('o');
         // Remove admin umode as well since it requires operator status
         self.remove_mode_internal('a');
+        self.snomasks.clear();
         tracing::info!("Revoked operator privileges from user {}", self.nick);
     }
 
@@ -345,4 +473,9 @@ impl User {
     pub fn can_squit(&self) -> bool {
         self.has_operator_flag(OperatorFlag::Squit)
     }
+
+    /// Check if user can override channel restrictions
+    pub fn can_override(&self) -> bool {
+        self.is_operator && self.has_operator_flag(OperatorFlag::Override)
+    }
 }
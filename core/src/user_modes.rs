@@ -13,14 +13,20 @@ pub enum UserMode {
     Away,
     /// Invisible mode - user doesn't appear in WHO lists
     Invisible,
-    /// Restricted mode - user is restricted from certain actions
-    Restricted,
+    /// Registered mode - user is identified to a services account
+    Registered,
     /// Operator mode - user has operator privileges
     Operator,
     /// Local operator mode - user has local operator privileges
     LocalOperator,
     /// Receive server notices
     ServerNotices,
+    /// Cloaked host - real host is hidden from other users
+    Cloaked,
+    /// Secure connection - user is connected over TLS
+    Secure,
+    /// Bot mode - user is an automated client
+    Bot,
 }
 
 impl UserMode {
@@ -29,10 +35,13 @@ impl UserMode {
         match self {
             UserMode::Away => 'a',
             UserMode::Invisible => 'i',
-            UserMode::Restricted => 'r',
+            UserMode::Registered => 'r',
             UserMode::Operator => 'o',
             UserMode::LocalOperator => 'O',
             UserMode::ServerNotices => 's',
+            UserMode::Cloaked => 'x',
+            UserMode::Secure => 'z',
+            UserMode::Bot => 'B',
         }
     }
 
@@ -41,10 +50,13 @@ impl UserMode {
         match c {
             'a' => Some(UserMode::Away),
             'i' => Some(UserMode::Invisible),
-            'r' => Some(UserMode::Restricted),
+            'r' => Some(UserMode::Registered),
             'o' => Some(UserMode::Operator),
             'O' => Some(UserMode::LocalOperator),
             's' => Some(UserMode::ServerNotices),
+            'x' => Some(UserMode::Cloaked),
+            'z' => Some(UserMode::Secure),
+            'B' => Some(UserMode::Bot),
             _ => None,
         }
     }
@@ -54,10 +66,13 @@ impl UserMode {
         match self {
             UserMode::Away => "User is away",
             UserMode::Invisible => "User is invisible in WHO lists",
-            UserMode::Restricted => "User is restricted",
+            UserMode::Registered => "User is identified to a services account",
             UserMode::Operator => "User has operator privileges",
             UserMode::LocalOperator => "User has local operator privileges",
             UserMode::ServerNotices => "User receives server notices",
+            UserMode::Cloaked => "User's host is cloaked",
+            UserMode::Secure => "User is connected over TLS",
+            UserMode::Bot => "User is a bot",
         }
     }
 
@@ -66,7 +81,6 @@ impl UserMode {
         match self {
             UserMode::Operator => true,
             UserMode::LocalOperator => true,
-            UserMode::Restricted => true,
             _ => false,
         }
     }
@@ -80,12 +94,20 @@ impl UserMode {
         }
     }
 
+    /// Check if only the server may change this mode, as it reflects the
+    /// user's account or connection
+    pub fn server_only(&self) -> bool {
+        matches!(self, UserMode::Registered | UserMode::Secure)
+    }
+
     /// Check if this mode can only be set by the user themselves
     pub fn self_only(&self) -> bool {
         match self {
             UserMode::Away => true,
             UserMode::Invisible => true,
             UserMode::ServerNotices => true,
+            UserMode::Cloaked => true,
+            UserMode::Bot => true,
             _ => false,
         }
     }
@@ -209,6 +231,11 @@ impl UserModeManager {
             return Err("Operator mode can only be granted through OPER command".to_string());
         }
 
+        // Identification and TLS status are set by the server, not MODE
+        if mode.server_only() {
+            return Err(format!("Mode {} is set by the server", mode.to_char()));
+        }
+
         // Check operator requirements for removal
         if !adding && mode.requires_operator() && !requesting_user_is_operator {
             return Err("Permission denied".to_string());
@@ -255,9 +282,19 @@ impl UserModeManager {
         self.has_mode(UserMode::Away)
     }
 
-    /// Check if user is restricted
-    pub fn is_restricted(&self) -> bool {
-        self.has_mode(UserMode::Restricted)
+    /// Check if user is identified to a services account
+    pub fn is_registered(&self) -> bool {
+        self.has_mode(UserMode::Registered)
+    }
+
+    /// Check if user is connected over TLS
+    pub fn is_secure(&self) -> bool {
+        self.has_mode(UserMode::Secure)
+    }
+
+    /// Check if user is marked as a bot
+    pub fn is_bot(&self) -> bool {
+        self.has_mode(UserMode::Bot)
     }
 }
 
@@ -268,7 +305,7 @@ impl Default for UserModeManager {
 }
 
 /// Standard IRC user mode characters
-pub const STANDARD_USER_MODES: &[char] = &['a', 'i', 'r', 'o', 'O', 's'];
+pub const STANDARD_USER_MODES: &[char] = &['a', 'i', 'r', 'o', 'O', 's', 'x', 'z', 'B'];
 
 /// Check if a character is a valid user mode
 pub fn is_valid_user_mode(c: char) -> bool {
@@ -296,7 +333,10 @@ mod tests {
         assert_eq!(UserMode::from_char('a'), Some(UserMode::Away));
         assert_eq!(UserMode::from_char('i'), Some(UserMode::Invisible));
         assert_eq!(UserMode::from_char('o'), Some(UserMode::Operator));
-        assert_eq!(UserMode::from_char('x'), None);
+        assert_eq!(UserMode::from_char('x'), Some(UserMode::Cloaked));
+        assert_eq!(UserMode::from_char('z'), Some(UserMode::Secure));
+        assert_eq!(UserMode::from_char('B'), Some(UserMode::Bot));
+        assert_eq!(UserMode::from_char('q'), None);
     }
 
     #[test]
@@ -358,4 +398,25 @@ mod tests {
             true,
         ).is_ok());
     }
+
+    #[test]
+    fn test_server_only_modes() {
+        let manager = UserModeManager::new();
+
+        // Only the server sets +r and +z, even for operators
+        assert!(manager.validate_mode_change(
+            UserMode::Registered,
+            true,
+            "user1",
+            "user1",
+            true,
+        ).is_err());
+        assert!(manager.validate_mode_change(
+            UserMode::Secure,
+            false,
+            "user1",
+            "user1",
+            true,
+        ).is_err());
+    }
 }
@@ -7,7 +7,10 @@ use std::str::FromStr;
 /// DNS and network utilities
 pub mod dns {
     use super::*;
-    use tokio::net::lookup_host;
+    use std::net::SocketAddr;
+    use std::time::Duration;
+    use tokio::net::{lookup_host, TcpStream};
+    use tokio::task::JoinSet;
     
     /// Resolve hostname to IP address
     pub async fn resolve_hostname(hostname: &str) -> Result<Option<IpAddr>, Box<dyn std::error::Error + Send + Sync>> {
@@ -18,6 +21,122 @@ pub mod dns {
         addrs.first().map(|addr| Ok(addr.ip())).transpose()
     }
     
+    /// Resolve a host/port pair to every A and AAAA address, ordered for connection attempts
+    ///
+    /// Addresses are interleaved by family (RFC 8305 section 4), starting with
+    /// whichever family the resolver returned first.
+    pub async fn resolve_all(hostname: &str, port: u16) -> Result<Vec<SocketAddr>, Error> {
+        let addrs: Vec<SocketAddr> = lookup_host((hostname, port)).await
+            .map_err(|e| Error::Connection(format!("DNS lookup failed for {}: {}", hostname, e)))?
+            .collect();
+        
+        if addrs.is_empty() {
+            return Err(Error::Connection(format!("No addresses found for {}", hostname)));
+        }
+        
+        Ok(interleave_families(addrs))
+    }
+    
+    /// Interleave IPv6 and IPv4 addresses, preserving resolver order within each family
+    pub fn interleave_families(addrs: Vec<SocketAddr>) -> Vec<SocketAddr> {
+        let prefer_v6 = addrs.first().map(|a| a.is_ipv6()).unwrap_or(true);
+        let (v6, v4): (Vec<_>, Vec<_>) = addrs.into_iter().partition(|a| a.is_ipv6());
+        let (mut first, mut second) = if prefer_v6 {
+            (v6.into_iter(), v4.into_iter())
+        } else {
+            (v4.into_iter(), v6.into_iter())
+        };
+        
+        let mut ordered = Vec::new();
+        loop {
+            match (first.next(), second.next()) {
+                (None, None) => break,
+                (a, b) => ordered.extend(a.into_iter().chain(b)),
+            }
+        }
+        ordered
+    }
+    
+    /// Connect to a host using staggered attempts across all resolved addresses
+    ///
+    /// A new attempt is started every `attempt_delay` (or as soon as the previous one
+    /// fails), and each attempt is bounded by `attempt_timeout`. The first successful
+    /// connection wins and any attempts still in flight are dropped.
+    pub async fn connect_happy_eyeballs(
+        hostname: &str,
+        port: u16,
+        attempt_delay: Duration,
+        attempt_timeout: Duration,
+    ) -> Result<TcpStream, Error> {
+        let addrs = resolve_all(hostname, port).await?;
+        connect_addresses(&addrs, attempt_delay, attempt_timeout).await
+    }
+    
+    /// Race staggered connection attempts over an ordered address list
+    pub async fn connect_addresses(
+        addrs: &[SocketAddr],
+        attempt_delay: Duration,
+        attempt_timeout: Duration,
+    ) -> Result<TcpStream, Error> {
+        let mut pending = addrs.iter().copied();
+        let mut attempts = JoinSet::new();
+        let mut last_error = None;
+        
+        loop {
+            if let Some(addr) = pending.next() {
+                attempts.spawn(async move {
+                    let result = match tokio::time::timeout(attempt_timeout, TcpStream::connect(addr)).await {
+                        Ok(result) => result.map_err(|e| e.to_string()),
+                        Err(_) => Err("connection timed out".to_string()),
+                    };
+                    (addr, result)
+                });
+            } else if attempts.is_empty() {
+                break;
+            }
+            
+            // Wait for the stagger delay, an attempt to finish, or (with nothing
+            // left to start) the remaining attempts
+            let stagger = tokio::time::sleep(attempt_delay);
+            tokio::pin!(stagger);
+            loop {
+                let finished = tokio::select! {
+                    finished = attempts.join_next() => finished,
+                    _ = &mut stagger, if pending.len() > 0 => break,
+                };
+                
+                match finished {
+                    Some(Ok((addr, Ok(stream)))) => {
+                        tracing::debug!("Connected to {} after trying {} address(es)", addr, addrs.len() - pending.len());
+                        attempts.abort_all();
+                        return Ok(stream);
+                    }
+                    Some(Ok((addr, Err(e)))) => {
+                        tracing::debug!("Connection attempt to {} failed: {}", addr, e);
+                        last_error = Some(format!("{}: {}", addr, e));
+                        // Start the next attempt immediately rather than waiting out the delay
+                        if pending.len() > 0 {
+                            break;
+                        }
+                    }
+                    Some(Err(e)) => {
+                        last_error = Some(format!("connection task failed: {}", e));
+                        if pending.len() > 0 {
+                            break;
+                        }
+                    }
+                    None => break,
+                }
+            }
+        }
+        
+        Err(Error::Connection(format!(
+            "All {} address(es) failed, last error: {}",
+            addrs.len(),
+            last_error.unwrap_or_else(|| "no addresses to try".to_string())
+        )))
+    }
+    
     /// Reverse DNS lookup
     pub async fn reverse_lookup(ip: IpAddr) -> Result<Option<String>, Box<dyn std::error::Error + Send + Sync>> {
         // This is a simplified implementation
@@ -164,6 +283,33 @@ pub mod string {
         hostname.chars().all(|c| c.is_ascii_alphanumeric() || c == '.' || c == '-')
     }
     
+    /// Case-insensitive IRC wildcard match supporting `*` and `?`
+    pub fn wildcard_match(pattern: &str, text: &str) -> bool {
+        let pattern: Vec<char> = pattern.to_lowercase().chars().collect();
+        let text: Vec<char> = text.to_lowercase().chars().collect();
+        let (mut p, mut t) = (0, 0);
+        let mut star: Option<(usize, usize)> = None;
+        
+        while t < text.len() {
+            if p < pattern.len() && (pattern[p] == '?' || pattern[p] == text[t]) {
+                p += 1;
+                t += 1;
+            } else if p < pattern.len() && pattern[p] == '*' {
+                star = Some((p, t));
+                p += 1;
+            } else if let Some((star_p, star_t)) = star {
+                // Let the last '*' swallow one more character and retry
+                p = star_p + 1;
+                t = star_t + 1;
+                star = Some((star_p, star_t + 1));
+            } else {
+                return false;
+            }
+        }
+        
+        pattern[p..].iter().all(|&c| c == '*')
+    }
+    
     /// Escape IRC message content
     pub fn escape_message(content: &str) -> String {
         content
@@ -240,6 +386,18 @@ pub mod network {
         }
     }
     
+    /// A host as shown in WHOIS and USERHOST replies
+    ///
+    /// IPv6 literals are bracketed, as in `user@[2001:db8::1]`, so one such
+    /// as `::1` can't be taken for a trailing parameter and the address
+    /// reads unambiguously next to `@` or a port.
+    pub fn display_host(host: &str) -> String {
+        match host.parse::<std::net::Ipv6Addr>() {
+            Ok(ip) => format!("[{}]", ip),
+            Err(_) => host.to_string(),
+        }
+    }
+    
     /// Parse IP address from string
     pub fn parse_ip(ip_str: &str) -> Result<IpAddr, Box<dyn std::error::Error + Send + Sync>> {
         Ok(IpAddr::from_str(ip_str)
@@ -295,6 +453,63 @@ mod tests {
         assert!(!string::is_valid_nickname("alice space", 9));
     }
     
+    #[test]
+    fn test_wildcard_match() {
+        assert!(string::wildcard_match("*.example.com", "host.Example.com"));
+        assert!(string::wildcard_match("*!*@192.0.2.?", "nick!user@192.0.2.5"));
+        assert!(string::wildcard_match("*", ""));
+        assert!(!string::wildcard_match("*.example.com", "example.org"));
+        assert!(!string::wildcard_match("a?c", "ac"));
+    }
+    
+    #[test]
+    fn test_display_host() {
+        assert_eq!(network::display_host("::1"), "[::1]");
+        assert_eq!(network::display_host("2001:DB8:0::1"), "[2001:db8::1]");
+        assert_eq!(network::display_host("192.0.2.1"), "192.0.2.1");
+        assert_eq!(network::display_host("host.example.com"), "host.example.com");
+    }
+    
+    #[test]
+    fn test_interleave_families() {
+        use std::net::SocketAddr;
+        let addrs: Vec<SocketAddr> = vec![
+            "[2001:db8::1]:6667".parse().unwrap(),
+            "[2001:db8::2]:6667".parse().unwrap(),
+            "192.0.2.1:6667".parse().unwrap(),
+            "192.0.2.2:6667".parse().unwrap(),
+            "192.0.2.3:6667".parse().unwrap(),
+        ];
+        let ordered = dns::interleave_families(addrs.clone());
+        assert_eq!(ordered, vec![addrs[0], addrs[2], addrs[1], addrs[3], addrs[4]]);
+        
+        // IPv4 first when the resolver returned it first
+        let v4_first = vec![addrs[2], addrs[0]];
+        assert_eq!(dns::interleave_families(v4_first.clone()), v4_first);
+    }
+    
+    #[tokio::test]
+    async fn test_connect_addresses_falls_back() {
+        use std::time::Duration;
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let good = listener.local_addr().unwrap();
+        
+        // Grab a port that nothing is listening on
+        let closed = {
+            let l = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+            l.local_addr().unwrap()
+        };
+        
+        let stream = dns::connect_addresses(&[closed, good], Duration::from_millis(250), Duration::from_secs(2))
+            .await
+            .unwrap();
+        assert_eq!(stream.peer_addr().unwrap(), good);
+        
+        assert!(dns::connect_addresses(&[closed], Duration::from_millis(250), Duration::from_secs(2))
+            .await
+            .is_err());
+    }
+    
     #[test]
     fn test_private_ip() {
         use std::net::{IpAddr, Ipv4Addr};
@@ -126,6 +126,8 @@ impl ConfigValidator {
         result.merge(self.validate_security_section());
         result.merge(self.validate_modules_section());
         result.merge(self.validate_services_section());
+        result.merge(self.validate_replies_section());
+        result.merge(self.validate_logging_section());
         result.merge(self.validate_cross_references());
         result.merge(self.validate_file_paths());
         result.merge(self.validate_security_best_practices());
@@ -184,6 +186,48 @@ impl ConfigValidator {
             });
         }
 
+        let motd = &self.config.server.motd;
+        if let Some(url) = &motd.remote_url {
+            if !url.starts_with("https://") {
+                result.add_error(ValidationError {
+                    category: ErrorCategory::Security,
+                    message: format!("Remote MOTD URL must use https: {}", url),
+                    suggestion: Some("Serve the MOTD over https or remove server.motd.remote_url".to_string()),
+                    section: "server.motd".to_string(),
+                });
+            }
+        }
+        for (idx, motd_override) in motd.overrides.iter().enumerate() {
+            let section = format!("server.motd.overrides[{}]", idx);
+            if motd_override.class.is_none() && motd_override.port.is_none() {
+                result.add_error(ValidationError {
+                    category: ErrorCategory::MissingRequired,
+                    message: format!("MOTD override {} names neither a class nor a port", motd_override.file),
+                    suggestion: Some("Set class, port or both".to_string()),
+                    section: section.clone(),
+                });
+            }
+            if let Some(class_name) = &motd_override.class {
+                if self.config.get_class(class_name).is_none() && class_name != "default" {
+                    result.add_error(ValidationError {
+                        category: ErrorCategory::InvalidReference,
+                        message: format!("MOTD override references non-existent class '{}'", class_name),
+                        suggestion: Some(format!("Define [[classes]] with name = \"{}\"", class_name)),
+                        section: section.clone(),
+                    });
+                }
+            }
+            if let Some(port) = motd_override.port {
+                if !self.config.connection.ports.iter().any(|p| p.port == port) {
+                    result.add_warning(ValidationWarning {
+                        message: format!("MOTD override is for port {}, which has no listener", port),
+                        section,
+                        suggestion: Some("Add the port to [[connection.ports]] or remove the override".to_string()),
+                    });
+                }
+            }
+        }
+
         result.add_info(format!("Server: {} (max {} clients)", 
             self.config.server.name, self.config.server.max_clients));
 
@@ -303,6 +347,27 @@ impl ConfigValidator {
                 });
             }
 
+            // Outgoing TLS links need something to verify the peer against
+            if link.tls && link.outgoing {
+                if let Some(fingerprint) = &link.tls_fingerprint {
+                    if crate::link_tls::normalize_fingerprint(fingerprint).is_none() {
+                        result.add_error(ValidationError {
+                            category: ErrorCategory::InvalidValue,
+                            message: format!("Server link '{}' has an invalid tls_fingerprint", link.name),
+                            suggestion: Some("Use the 64 hex digit SHA-256 fingerprint, e.g. from: openssl x509 -noout -fingerprint -sha256".to_string()),
+                            section: format!("network.links[{}]", idx),
+                        });
+                    }
+                } else if link.tls_ca_file.is_none() {
+                    result.add_error(ValidationError {
+                        category: ErrorCategory::Security,
+                        message: format!("TLS server link '{}' has neither tls_fingerprint nor tls_ca_file", link.name),
+                        suggestion: Some("Pin the peer certificate with tls_fingerprint or set tls_ca_file".to_string()),
+                        section: format!("network.links[{}]", idx),
+                    });
+                }
+            }
+
             // Validate class reference if specified
             if let Some(class_name) = &link.class {
                 if !self.config.classes.iter().any(|c| &c.name == class_name) {
@@ -327,15 +392,45 @@ impl ConfigValidator {
                 });
             }
 
-            if operator.password_hash.len() != 64 {
+            if operator.password_hash.is_empty() && operator.account.is_none() && operator.certfp.is_none()
+                && operator.challenge_key_file.is_none() {
+                result.add_error(ValidationError {
+                    category: ErrorCategory::Security,
+                    message: format!("Operator '{}' has no password_hash, account, certfp or challenge_key_file", operator.nickname),
+                    suggestion: Some("Set at least one of password_hash, account, certfp or challenge_key_file".to_string()),
+                    section: format!("network.operators[{}]", idx),
+                });
+            } else if !operator.password_hash.is_empty()
+                && !crate::config::PasswordHasher::is_argon2_hash(&operator.password_hash)
+                && !crate::config::PasswordHasher::is_sha256_hash(&operator.password_hash) {
                 result.add_error(ValidationError {
                     category: ErrorCategory::Security,
-                    message: format!("Operator '{}' has invalid password hash (expected 64 hex chars)", operator.nickname),
-                    suggestion: Some("Generate with: echo -n 'password' | sha256sum".to_string()),
+                    message: format!("Operator '{}' has invalid password hash (expected an Argon2 PHC string or 64 hex chars)", operator.nickname),
+                    suggestion: Some("Generate an Argon2 hash with: mkpasswd".to_string()),
+                    section: format!("network.operators[{}]", idx),
+                });
+            }
+
+            if operator.certfp.as_deref().is_some_and(|certfp| crate::link_tls::normalize_fingerprint(certfp).is_none()) {
+                result.add_error(ValidationError {
+                    category: ErrorCategory::InvalidValue,
+                    message: format!("Operator '{}' has an invalid certfp (expected a SHA-256 fingerprint)", operator.nickname),
+                    suggestion: Some("Use 64 hex digits, optionally separated by colons".to_string()),
                     section: format!("network.operators[{}]", idx),
                 });
             }
 
+            if let Some(path) = &operator.challenge_key_file {
+                if let Err(e) = crate::challenge::ChallengeKey::load(path) {
+                    result.add_error(ValidationError {
+                        category: ErrorCategory::InvalidValue,
+                        message: format!("Operator '{}': {}", operator.nickname, e),
+                        suggestion: Some("Generate a keypair with: mkpasswd keygen".to_string()),
+                        section: format!("network.operators[{}]", idx),
+                    });
+                }
+            }
+
             if operator.hostmask == "*@*" {
                 result.add_warning(ValidationWarning {
                     message: format!("Operator '{}' allows connections from any host", operator.nickname),
@@ -395,6 +490,24 @@ impl ConfigValidator {
                 });
             }
 
+            if let Some(bind_addr_v6) = &port.bind_address_v6 {
+                if bind_addr_v6.parse::<std::net::Ipv6Addr>().is_err() {
+                    result.add_error(ValidationError {
+                        category: ErrorCategory::InvalidValue,
+                        message: format!("Port {} has invalid IPv6 bind address: {}", port.port, bind_addr_v6),
+                        suggestion: Some("Use an IPv6 address (e.g., :: or 2001:db8::1)".to_string()),
+                        section: format!("connection.ports[{}]", idx),
+                    });
+                } else if bind_addr_v6.parse::<std::net::IpAddr>().ok() == bind_addr.parse().ok() {
+                    result.add_error(ValidationError {
+                        category: ErrorCategory::InvalidValue,
+                        message: format!("Port {} binds {} twice", port.port, bind_addr_v6),
+                        suggestion: Some("Set bind_address to an IPv4 address, or drop bind_address_v6".to_string()),
+                        section: format!("connection.ports[{}]", idx),
+                    });
+                }
+            }
+
             // Check TLS configuration
             if port.tls && !self.config.security.tls.enabled {
                 result.add_error(ValidationError {
@@ -448,6 +561,33 @@ impl ConfigValidator {
             }
         }
 
+        // Connect-time lookups
+        let security = &self.config.security;
+        if security.lookup_timeout == 0 && (security.enable_ident || security.enable_reverse_dns || !security.dnsbl.is_empty()) {
+            result.add_warning(ValidationWarning {
+                message: "lookup_timeout is 0, so hostname, ident and DNSBL lookups never finish in time".to_string(),
+                section: section.to_string(),
+                suggestion: Some("Set security.lookup_timeout to a few seconds (default 5)".to_string()),
+            });
+        }
+        for (idx, dnsbl) in security.dnsbl.iter().enumerate() {
+            if dnsbl.zone.trim_matches('.').is_empty() {
+                result.add_error(ValidationError {
+                    category: ErrorCategory::MissingRequired,
+                    message: format!("DNSBL {} has no zone", idx),
+                    suggestion: Some("Set zone to the blocklist's DNS zone, such as \"dnsbl.dronebl.org\"".to_string()),
+                    section: format!("security.dnsbl[{}]", idx),
+                });
+            }
+        }
+        if !security.dnsbl.is_empty() && !security.enable_dns {
+            result.add_warning(ValidationWarning {
+                message: "DNSBLs are configured but DNS lookups are disabled, so they are never checked".to_string(),
+                section: section.to_string(),
+                suggestion: Some("Set security.enable_dns = true".to_string()),
+            });
+        }
+
         // TLS validation
         if self.config.security.tls.enabled {
             if self.config.security.tls.cert_file.is_none() {
@@ -469,6 +609,40 @@ impl ConfigValidator {
             }
         }
 
+        let acme = &self.config.security.tls.acme;
+        if acme.enabled {
+            if acme.domains.is_empty() {
+                result.add_error(ValidationError {
+                    category: ErrorCategory::MissingRequired,
+                    message: "ACME is enabled but no domains are listed".to_string(),
+                    suggestion: Some("Set security.tls.acme.domains = [\"irc.example.net\"]".to_string()),
+                    section: "security.tls.acme".to_string(),
+                });
+            }
+            if acme.challenge == crate::config::AcmeChallengeType::Dns01 && acme.dns_hook.is_none() {
+                result.add_error(ValidationError {
+                    category: ErrorCategory::MissingRequired,
+                    message: "ACME dns-01 challenges need a dns_hook to publish the TXT record".to_string(),
+                    suggestion: Some("Set security.tls.acme.dns_hook or use challenge = \"http-01\"".to_string()),
+                    section: "security.tls.acme".to_string(),
+                });
+            }
+            if !self.config.security.tls.enabled {
+                result.add_warning(ValidationWarning {
+                    message: "ACME is enabled but TLS is not, so no certificate is requested".to_string(),
+                    section: "security.tls.acme".to_string(),
+                    suggestion: Some("Set security.tls.enabled = true".to_string()),
+                });
+            }
+            if !cfg!(feature = "acme") {
+                result.add_warning(ValidationWarning {
+                    message: "ACME is enabled but this build lacks the acme feature".to_string(),
+                    section: "security.tls.acme".to_string(),
+                    suggestion: Some("Build with --features acme".to_string()),
+                });
+            }
+        }
+
         // Check for overly permissive security
         if self.config.security.allowed_hosts.contains(&"*".to_string()) && 
            self.config.security.allow_blocks.is_empty() {
@@ -508,6 +682,15 @@ impl ConfigValidator {
             });
         }
 
+        // Cloaks derived from the network name are predictable
+        if self.config.modules.ip_cloak.enabled && self.config.modules.ip_cloak.secret_key.is_empty() {
+            result.add_warning(ValidationWarning {
+                message: "Host cloaking has no secret_key - cloaks are keyed on the network name".to_string(),
+                section: "modules.ip_cloak".to_string(),
+                suggestion: Some("Set a random secret_key, identical on every server".to_string()),
+            });
+        }
+
         result.add_info(format!("Modules: {} enabled", self.config.modules.enabled_modules.len()));
 
         result
@@ -535,6 +718,72 @@ impl ConfigValidator {
         result
     }
 
+    /// Validate numeric reply templates
+    fn validate_replies_section(&self) -> ValidationResult {
+        let mut result = ValidationResult::success();
+
+        if let Some(replies) = &self.config.replies {
+            for (code, message) in replies.validation_errors() {
+                result.add_error(ValidationError {
+                    category: ErrorCategory::InvalidValue,
+                    message,
+                    suggestion: Some("See replies.toml for each reply's built-in template and placeholders".to_string()),
+                    section: format!("replies.{:03}", code),
+                });
+            }
+        }
+
+        result
+    }
+
+    /// Validate logging levels, rotation and trace export
+    fn validate_logging_section(&self) -> ValidationResult {
+        let mut result = ValidationResult::success();
+        let logging = &self.config.logging;
+
+        let levels = std::iter::once(("logging".to_string(), &logging.level))
+            .chain(logging.targets.iter().map(|(target, level)| (format!("logging.targets.{}", target), level)));
+        for (section, level) in levels {
+            if crate::logging::parse_level(level).is_none() {
+                result.add_error(ValidationError {
+                    category: ErrorCategory::InvalidValue,
+                    message: format!("Invalid log level: {}", level),
+                    suggestion: Some("Use trace, debug, info, warn, error or off".to_string()),
+                    section,
+                });
+            }
+        }
+        if logging.file.is_some() && logging.max_file_size > 0 && logging.max_files == 0 {
+            result.add_warning(ValidationWarning {
+                message: "Log rotation keeps no old files; the log is discarded each time it fills".to_string(),
+                section: "logging".to_string(),
+                suggestion: Some("Set logging.max_files to 1 or more".to_string()),
+            });
+        }
+
+        let telemetry = &self.config.telemetry;
+        if telemetry.enabled {
+            if !telemetry.otlp_endpoint.starts_with("http://") && !telemetry.otlp_endpoint.starts_with("https://") {
+                result.add_error(ValidationError {
+                    category: ErrorCategory::InvalidValue,
+                    message: format!("OTLP endpoint must be an http(s) URL: {}", telemetry.otlp_endpoint),
+                    suggestion: Some("Use the collector's OTLP/HTTP traces URL, e.g. http://127.0.0.1:4318/v1/traces".to_string()),
+                    section: "telemetry".to_string(),
+                });
+            }
+            if !(0.0..=1.0).contains(&telemetry.sample_ratio) {
+                result.add_error(ValidationError {
+                    category: ErrorCategory::InvalidValue,
+                    message: format!("Trace sample ratio must be between 0.0 and 1.0, got {}", telemetry.sample_ratio),
+                    suggestion: Some("Set telemetry.sample_ratio = 1.0 to trace every command".to_string()),
+                    section: "telemetry".to_string(),
+                });
+            }
+        }
+
+        result
+    }
+
     /// Validate cross-references between sections
     fn validate_cross_references(&self) -> ValidationResult {
         let mut result = ValidationResult::success();
@@ -580,9 +829,27 @@ impl ConfigValidator {
                 });
             }
         }
+        if let Some(rules_file) = &self.config.server.rules_file {
+            if !Path::new(rules_file).exists() {
+                result.add_warning(ValidationWarning {
+                    message: format!("Rules file not found: {}", rules_file),
+                    section: "server".to_string(),
+                    suggestion: Some("Create the rules file or remove rules_file".to_string()),
+                });
+            }
+        }
+        for (idx, motd_override) in self.config.server.motd.overrides.iter().enumerate() {
+            if !Path::new(&motd_override.file).exists() {
+                result.add_warning(ValidationWarning {
+                    message: format!("MOTD file not found: {}", motd_override.file),
+                    section: format!("server.motd.overrides[{}]", idx),
+                    suggestion: Some("Create the file; until then the default MOTD is shown".to_string()),
+                });
+            }
+        }
 
-        // Check TLS certificate files
-        if self.config.security.tls.enabled {
+        // Check TLS certificate files; ACME creates them
+        if self.config.security.tls.enabled && !self.config.security.tls.acme.enabled {
             if let Some(cert_file) = &self.config.security.tls.cert_file {
                 if !Path::new(cert_file).exists() {
                     result.add_error(ValidationError {
@@ -626,6 +893,33 @@ impl ConfigValidator {
             });
         }
 
+        // Check the admin HTTP API
+        let admin_api = &self.config.admin_api;
+        if admin_api.enabled {
+            if admin_api.token_hash.is_empty() {
+                result.add_error(ValidationError {
+                    category: ErrorCategory::Security,
+                    message: "Admin API is enabled without a token_hash".to_string(),
+                    suggestion: Some("Hash a long random token with mkpasswd and set admin_api.token_hash".to_string()),
+                    section: "admin_api".to_string(),
+                });
+            }
+            if !admin_api.bind_address.parse::<std::net::IpAddr>().is_ok_and(|ip| ip.is_loopback()) {
+                result.add_warning(ValidationWarning {
+                    message: format!("Admin API listens on {}, not loopback, and serves plain HTTP", admin_api.bind_address),
+                    section: "admin_api".to_string(),
+                    suggestion: Some("Bind it to 127.0.0.1 and put a TLS proxy in front".to_string()),
+                });
+            }
+            if !cfg!(feature = "admin-api") {
+                result.add_warning(ValidationWarning {
+                    message: "Admin API is enabled but this build lacks the admin-api feature".to_string(),
+                    section: "admin_api".to_string(),
+                    suggestion: Some("Build with --features admin-api".to_string()),
+                });
+            }
+        }
+
         // Check for ident/DNS
         if !self.config.security.enable_ident && !self.config.security.enable_dns {
             result.add_warning(ValidationWarning {
@@ -644,6 +938,17 @@ impl ConfigValidator {
             });
         }
 
+        for mask in &self.config.modules.throttling.exempt {
+            if crate::ban_manager::parse_ip_mask(mask).is_none() {
+                result.add_error(ValidationError {
+                    category: ErrorCategory::InvalidValue,
+                    message: format!("Invalid throttling exemption '{}'", mask),
+                    suggestion: Some("Use an IP address or CIDR range such as 10.0.0.0/8".to_string()),
+                    section: "modules.throttling".to_string(),
+                });
+            }
+        }
+
         result
     }
 
@@ -757,6 +1062,11 @@ mod tests {
             tls: false,
             outgoing: true,
             class: Some("nonexistent".to_string()),
+            autoconnect: false,
+            tls_fingerprint: None,
+            tls_ca_file: None,
+            tls_server_name: None,
+            compression: false,
         });
         
         let validator = ConfigValidator::new(config);
@@ -782,5 +1092,126 @@ mod tests {
         assert!(!result.is_valid);
         assert!(result.errors.iter().any(|e| matches!(e.category, ErrorCategory::Duplicate)));
     }
+
+    #[test]
+    fn test_invalid_reply_template() {
+        let mut config = Config::default();
+        let mut replies = crate::RepliesConfig { replies: Default::default() };
+        replies.replies.insert(403, crate::ReplyConfig {
+            code: 403,
+            text: "{channel} :{nosuch} does not exist".to_string(),
+            description: None,
+        });
+        config.replies = Some(replies);
+
+        let result = ConfigValidator::new(config).validate();
+        assert!(!result.is_valid);
+        assert!(result.errors.iter().any(|e| e.section == "replies.403" && e.message.contains("{nosuch}")));
+    }
+
+    #[test]
+    fn test_invalid_motd_config() {
+        let mut config = Config::default();
+        config.server.motd.remote_url = Some("http://example.com/motd.txt".to_string());
+        config.server.motd.overrides.push(crate::config::MotdOverride {
+            class: Some("nosuchclass".to_string()),
+            port: None,
+            file: "motd.txt".to_string(),
+        });
+
+        let result = ConfigValidator::new(config).validate();
+        assert!(!result.is_valid);
+        assert!(result.errors.iter().any(|e| e.section == "server.motd" && e.message.contains("https")));
+        assert!(result.errors.iter().any(|e| e.section == "server.motd.overrides[0]" && e.message.contains("nosuchclass")));
+    }
+
+    #[test]
+    fn test_dual_stack_bind_addresses() {
+        let mut config = Config::default();
+        config.connection.ports[0].bind_address_v6 = Some("::".to_string());
+        config.connection.ports[1].bind_address_v6 = Some("192.0.2.1".to_string());
+        config.connection.ports[2].bind_address = Some("::".to_string());
+        config.connection.ports[2].bind_address_v6 = Some("0::0".to_string());
+
+        let result = ConfigValidator::new(config).validate();
+        assert!(!result.errors.iter().any(|e| e.section == "connection.ports[0]"));
+        assert!(result.errors.iter().any(|e| e.section == "connection.ports[1]" && e.message.contains("invalid IPv6")));
+        assert!(result.errors.iter().any(|e| e.section == "connection.ports[2]" && e.message.contains("twice")));
+    }
+
+    #[test]
+    fn test_lookup_config() {
+        let mut config = Config::default();
+        config.security.lookup_timeout = 0;
+        config.security.enable_dns = false;
+        config.security.dnsbl = vec![
+            crate::config::DnsblConfig { zone: "dnsbl.example".to_string(), reason: "listed".to_string(), replies: vec![2] },
+            crate::config::DnsblConfig { zone: ".".to_string(), reason: "listed".to_string(), replies: Vec::new() },
+        ];
+
+        let result = ConfigValidator::new(config).validate();
+        assert!(!result.errors.iter().any(|e| e.section == "security.dnsbl[0]"));
+        assert!(result.errors.iter().any(|e| e.section == "security.dnsbl[1]"));
+        assert!(result.warnings.iter().any(|w| w.message.contains("lookup_timeout")));
+        assert!(result.warnings.iter().any(|w| w.message.contains("DNS lookups are disabled")));
+    }
+
+    #[test]
+    fn test_operator_password_hashes() {
+        let mut config = Config::default();
+        let operator = |hash: String| {
+            let mut operator = OperatorConfig::new("oper".to_string(), "", "*@*".to_string(), vec![OperatorFlag::GlobalOper]);
+            operator.password_hash = hash;
+            operator
+        };
+        config.network.operators = vec![
+            operator(PasswordHasher::hash_password("secret")),
+            operator("5e884898da28047151d0e56f8dc6292773603d0d6aabbdd62a11ef721d1542d8".to_string()),
+            operator("plaintext".to_string()),
+        ];
+
+        let result = ConfigValidator::new(config).validate();
+        let sections: Vec<&str> = result.errors.iter()
+            .filter(|e| e.message.contains("invalid password hash"))
+            .map(|e| e.section.as_str())
+            .collect();
+        assert_eq!(sections, ["network.operators[2]"]);
+    }
+
+    #[test]
+    fn test_invalid_log_level() {
+        let mut config = Config::default();
+        config.logging.targets.insert("rustircd_core::server".to_string(), "verbose".to_string());
+
+        let result = ConfigValidator::new(config).validate();
+        assert!(result.errors.iter().any(|e| e.section == "logging.targets.rustircd_core::server"));
+        assert!(!result.errors.iter().any(|e| e.section == "logging"));
+    }
+
+    #[test]
+    fn test_invalid_telemetry_config() {
+        let mut config = Config::default();
+        config.telemetry.enabled = true;
+        config.telemetry.otlp_endpoint = "127.0.0.1:4317".to_string();
+        config.telemetry.sample_ratio = 2.0;
+
+        let result = ConfigValidator::new(config).validate();
+        assert_eq!(result.errors.iter().filter(|e| e.section == "telemetry").count(), 2);
+    }
+
+    #[test]
+    fn test_acme_config() {
+        let mut config = Config::default();
+        config.security.tls.enabled = true;
+        config.security.tls.cert_file = Some("/nonexistent/cert.pem".to_string());
+        config.security.tls.key_file = Some("/nonexistent/key.pem".to_string());
+        config.security.tls.acme.enabled = true;
+        config.security.tls.acme.challenge = crate::config::AcmeChallengeType::Dns01;
+
+        // ACME creates the certificate, so only the ACME settings are wrong
+        let result = ConfigValidator::new(config).validate();
+        assert!(!result.errors.iter().any(|e| e.category == ErrorCategory::FileNotFound && e.section == "security.tls"));
+        assert_eq!(result.errors.iter().filter(|e| e.section == "security.tls.acme").count(), 2);
+    }
 }
 
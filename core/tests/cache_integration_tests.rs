@@ -116,6 +116,25 @@ async fn test_user_lookup_cache_update_nickname() {
     assert_eq!(user_new.id, user_id);
 }
 
+#[tokio::test]
+async fn test_nickname_change_renames_channel_membership() {
+    let db = Database::new(1000, 30);
+
+    let mut user = create_test_user("dave", "dave", "192.168.1.4");
+    let user_id = user.id;
+    db.add_user(user.clone()).unwrap();
+    db.add_user_to_channel("dave", "#rust").unwrap();
+    assert_eq!(db.get_channel_users("#rust"), vec!["dave".to_string()]);
+
+    user.nick = "david".to_string();
+    db.update_user(&user_id, user).unwrap();
+
+    // The cached member list must not keep the old nickname
+    assert_eq!(db.get_channel_users("#rust"), vec!["david".to_string()]);
+    assert_eq!(db.get_user_channels("david"), vec!["#rust".to_string()]);
+    assert!(db.get_user_channels("dave").is_empty());
+}
+
 #[tokio::test]
 async fn test_channel_member_cache_basic() {
     let db = Database::new(1000, 30);
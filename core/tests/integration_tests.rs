@@ -78,6 +78,477 @@ async fn test_server_info_management() {
     assert!(db.get_server("test.server").is_none());
 }
 
+#[tokio::test]
+async fn test_server_user_counts() {
+    let db = Database::new(1000, 30);
+    let remote_user = |nick: &str| User::new(
+        nick.to_string(),
+        nick.to_string(),
+        "Remote User".to_string(),
+        format!("{}.example.com", nick),
+        "leaf.server".to_string(),
+    );
+
+    // Users burst before the server is recorded still count
+    let early = remote_user("early");
+    db.add_user(early.clone()).unwrap();
+    db.add_server(database::ServerInfo {
+        name: "leaf.server".to_string(),
+        description: "Leaf".to_string(),
+        version: "1.0.0".to_string(),
+        hopcount: 1,
+        connected_at: chrono::Utc::now(),
+        is_super_server: false,
+        user_count: 0,
+    }).unwrap();
+    assert_eq!(db.get_server("leaf.server").unwrap().user_count, 1);
+
+    let late = remote_user("late");
+    db.add_user(late.clone()).unwrap();
+    assert_eq!(db.get_server("leaf.server").unwrap().user_count, 2);
+
+    db.remove_user(early.id).unwrap();
+    assert_eq!(db.get_server("leaf.server").unwrap().user_count, 1);
+    assert_eq!(db.total_user_count(), 1);
+    assert_eq!(db.max_user_count(), 2);
+}
+
+#[tokio::test]
+async fn test_rehash_applies_or_rolls_back() {
+    let dir = tempfile::tempdir().unwrap();
+    let config_path = dir.path().join("ircd.toml");
+    let motd_path = dir.path().join("motd.txt");
+    std::fs::write(&motd_path, "Welcome\n").unwrap();
+
+    let mut config = Config::default();
+    config.connection.ports.retain(|port| !port.tls);
+    config.server.motd_file = Some(motd_path.to_string_lossy().to_string());
+    config.to_file(&config_path).unwrap();
+    let server = Server::new_with_config_path(config.clone(), config_path.to_string_lossy().to_string()).await;
+
+    std::fs::write(&motd_path, "Welcome\nto the network\n").unwrap();
+    config.server.description = "Rehashed".to_string();
+    config.to_file(&config_path).unwrap();
+    let report = server.rehash(RehashSection::Config).await.unwrap();
+    assert!(report.iter().any(|line| line == "MOTD reloaded (2 lines)"));
+    assert!(report.iter().any(|line| line == "Settings changed: server.description"));
+    assert_eq!(server.config().server.description, "Rehashed");
+
+    // Only what changed is reported and reloaded
+    let report = server.rehash(RehashSection::Config).await.unwrap();
+    assert_eq!(report[1..], ["No configuration changes".to_string()]);
+
+    // A config that fails validation leaves everything as it was
+    config.server.description = "Broken".to_string();
+    config.server.max_clients = 0;
+    config.to_file(&config_path).unwrap();
+    assert!(server.rehash(RehashSection::Config).await.is_err());
+    assert_eq!(server.config().server.description, "Rehashed");
+
+    // So does renaming the server
+    config.server.max_clients = 1000;
+    config.server.name = "renamed.server".to_string();
+    config.to_file(&config_path).unwrap();
+    assert!(server.rehash(RehashSection::Config).await.is_err());
+    assert_eq!(server.config().server.description, "Rehashed");
+}
+
+#[tokio::test]
+async fn test_rehash_adds_and_removes_listeners() {
+    use tokio::io::AsyncBufReadExt;
+
+    let dir = tempfile::tempdir().unwrap();
+    let config_path = dir.path().join("ircd.toml");
+    let port = |port: u16| config::PortConfig {
+        port,
+        connection_type: config::PortConnectionType::Client,
+        tls: false,
+        description: None,
+        bind_address: Some("127.0.0.1".to_string()),
+        bind_address_v6: None,
+        request_client_cert: false,
+    };
+    let free_port = || std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+
+    let mut config = Config::default();
+    config.security.enable_ident = false;
+    config.security.enable_dns = false;
+    config.security.enable_reverse_dns = false;
+    config.connection.close_removed_listener_connections = true;
+    config.connection.ports = vec![port(free_port())];
+    config.to_file(&config_path).unwrap();
+    let mut server = Server::new_with_config_path(config.clone(), config_path.to_string_lossy().to_string()).await;
+    server.start().await.unwrap();
+    let old_addr = server.listen_addrs()[0];
+    let mut old_client = tokio::io::BufReader::new(tokio::net::TcpStream::connect(old_addr).await.unwrap()).lines();
+    tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+
+    // A port in use fails the rehash and changes nothing
+    let taken = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    config.connection.ports.push(port(taken.local_addr().unwrap().port()));
+    config.to_file(&config_path).unwrap();
+    assert!(server.rehash(RehashSection::Config).await.is_err());
+    assert_eq!(server.listen_addrs(), [old_addr]);
+    drop(taken);
+
+    // Replacing the port binds the new one and closes the old one
+    let new_port = free_port();
+    config.connection.ports = vec![port(new_port)];
+    config.to_file(&config_path).unwrap();
+    let report = server.rehash(RehashSection::Config).await.unwrap();
+    assert!(report.contains(&format!("Listening on 127.0.0.1:{}", new_port)), "{:?}", report);
+    assert!(report.contains(&format!("Stopped listening on {}; disconnected 1 connections", old_addr)), "{:?}", report);
+    assert_eq!(server.listen_addrs(), [format!("127.0.0.1:{}", new_port).parse().unwrap()]);
+    tokio::net::TcpStream::connect(("127.0.0.1", new_port)).await.unwrap();
+    let line = tokio::time::timeout(std::time::Duration::from_secs(5), old_client.next_line()).await.unwrap().unwrap();
+    assert!(line.unwrap().starts_with("ERROR :Closing Link"));
+    assert!(tokio::net::TcpStream::connect(old_addr).await.is_err());
+    server.shutdown("test").await.unwrap();
+}
+
+#[tokio::test]
+async fn test_bans_enforced_on_rehash_and_registration() {
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+    type Lines = tokio::io::Lines<BufReader<tokio::net::tcp::OwnedReadHalf>>;
+
+    let dir = tempfile::tempdir().unwrap();
+    let config_path = dir.path().join("ircd.toml");
+    let ban_path = dir.path().join("bans.db");
+    let mut config = Config::default();
+    config.security.enable_ident = false;
+    config.security.enable_dns = false;
+    config.security.enable_reverse_dns = false;
+    config.security.ban_file = Some(ban_path.to_string_lossy().to_string());
+    config.connection.ports.truncate(1);
+    config.connection.ports[0].port = 0;
+    config.connection.ports[0].bind_address = Some("127.0.0.1".to_string());
+    config.to_file(&config_path).unwrap();
+    let mut server = Server::new_with_config_path(config, config_path.to_string_lossy().to_string()).await;
+    server.ban_manager().register_type(BanType::new("KLINE", BanTarget::UserHost, "K-Lined"));
+    server.start().await.unwrap();
+    let addr = server.listen_addrs()[0];
+
+    async fn register(addr: std::net::SocketAddr, nick: &str) -> (Lines, tokio::net::tcp::OwnedWriteHalf, Vec<String>) {
+        let (reader, mut writer) = tokio::net::TcpStream::connect(addr).await.unwrap().into_split();
+        writer.write_all(format!("NICK {0}\r\nUSER {0} 0 * :{0}\r\n", nick).as_bytes()).await.unwrap();
+        let mut lines = BufReader::new(reader).lines();
+        let mut seen = Vec::new();
+        while let Ok(Ok(Some(line))) = tokio::time::timeout(std::time::Duration::from_secs(5), lines.next_line()).await {
+            let command = Message::parse(&line).unwrap().command.to_string();
+            let done = matches!(command.as_str(), "376" | "422" | "465");
+            seen.push(command);
+            if done {
+                break;
+            }
+        }
+        (lines, writer, seen)
+    }
+    let (mut victim, _victim_writer, _) = register(addr, "victim").await;
+    let (_bystander, _bystander_writer, _) = register(addr, "bystander").await;
+
+    // A K-line added to the ban file takes effect on rehash
+    let store = FileBanStore::new(&ban_path);
+    store.save(&BanEntry::new("KLINE", "victim@*", "spamming", "oper", None)).unwrap();
+    let report = server.rehash(RehashSection::Config).await.unwrap();
+    assert!(report.contains(&"Bans reloaded (1 entries, 1 new)".to_string()), "{:?}", report);
+    assert!(report.contains(&"Disconnected 1 users matching new bans".to_string()), "{:?}", report);
+    let line = tokio::time::timeout(std::time::Duration::from_secs(5), victim.next_line()).await.unwrap().unwrap();
+    assert!(line.unwrap().starts_with("ERROR :Closing Link"));
+    assert!(server.database().get_user_by_nick("victim").is_none());
+    assert!(server.database().get_user_by_nick("bystander").is_some());
+
+    // And refuses the same user when they come back
+    let (_, _, seen) = register(addr, "victim").await;
+    assert_eq!(seen.last().map(String::as_str), Some("465"));
+    assert!(server.database().get_user_by_nick("victim").is_none());
+    server.shutdown("test").await.unwrap();
+}
+
+#[tokio::test]
+async fn test_lookup_notices_before_registration() {
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt};
+
+    let mut config = Config::default();
+    config.security.enable_ident = true;
+    config.security.enable_dns = false;
+    config.security.enable_reverse_dns = false;
+    config.security.lookup_timeout = 2;
+    config.connection.ports.truncate(1);
+    config.connection.ports[0].port = 0;
+    config.connection.ports[0].bind_address = Some("127.0.0.1".to_string());
+    let mut server = Server::new(config).await;
+    server.start().await.unwrap();
+
+    // NICK and USER arrive before ident has answered; 001 waits for it
+    let stream = tokio::net::TcpStream::connect(server.listen_addrs()[0]).await.unwrap();
+    let (reader, mut writer) = stream.into_split();
+    writer.write_all(b"NICK looker\r\nUSER looker 0 * :Looker\r\n").await.unwrap();
+    let mut lines = tokio::io::BufReader::new(reader).lines();
+    let mut seen = Vec::new();
+    loop {
+        let line = tokio::time::timeout(std::time::Duration::from_secs(5), lines.next_line()).await.unwrap().unwrap().unwrap();
+        let welcomed = line.split(' ').nth(1) == Some("001");
+        seen.push(line);
+        if welcomed {
+            break;
+        }
+    }
+    assert!(seen[0].ends_with("NOTICE * :*** Checking Ident"), "{:?}", seen);
+    assert!(seen[1].ends_with("NOTICE * :*** No Ident response"), "{:?}", seen);
+    let user = server.database().get_user_by_nick("looker").unwrap();
+    assert_eq!(user.username, "~looker");
+    server.shutdown("test").await.unwrap();
+}
+
+#[tokio::test]
+async fn test_dual_stack_listener() {
+    // Not every sandbox has IPv6 loopback
+    let Ok(probe) = std::net::TcpListener::bind("[::1]:0") else {
+        return;
+    };
+    let port = probe.local_addr().unwrap().port();
+    drop(probe);
+
+    let mut config = Config::default();
+    config.security.enable_ident = false;
+    config.security.enable_dns = false;
+    config.security.enable_reverse_dns = false;
+    config.connection.ports = vec![config::PortConfig {
+        port,
+        connection_type: config::PortConnectionType::Client,
+        tls: false,
+        description: None,
+        bind_address: Some("127.0.0.1".to_string()),
+        bind_address_v6: Some("::1".to_string()),
+        request_client_cert: false,
+    }];
+    let mut server = Server::new(config).await;
+    server.start().await.unwrap();
+
+    let mut addrs = server.listen_addrs();
+    addrs.sort();
+    assert_eq!(addrs, [
+        format!("127.0.0.1:{}", port).parse().unwrap(),
+        format!("[::1]:{}", port).parse().unwrap(),
+    ]);
+    tokio::net::TcpStream::connect(("127.0.0.1", port)).await.unwrap();
+    tokio::net::TcpStream::connect(("::1", port)).await.unwrap();
+    server.shutdown("test").await.unwrap();
+}
+
+#[tokio::test]
+async fn test_registration_over_tcp() {
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+
+    let mut config = Config::default();
+    config.security.enable_ident = false;
+    config.security.enable_dns = false;
+    config.security.enable_reverse_dns = false;
+    config.server.max_nick_changes = 1;
+    let mut server = ServerBuilder::with_config(config)
+        .server_name("registration.test")
+        .port(16671, config::PortConnectionType::Client, false)
+        .build()
+        .await
+        .unwrap();
+    server.start().await.unwrap();
+
+    let stream = tokio::net::TcpStream::connect("127.0.0.1:16671").await.unwrap();
+    let (reader, mut writer) = stream.into_split();
+    let mut lines = BufReader::new(reader).lines();
+    async fn next_numeric<R: tokio::io::AsyncBufRead + Unpin>(lines: &mut tokio::io::Lines<R>) -> String {
+        let line = tokio::time::timeout(std::time::Duration::from_secs(5), lines.next_line())
+            .await.unwrap().unwrap().unwrap();
+        let message = Message::parse(&line).unwrap();
+        message.command.to_string()
+    }
+
+    // Without a CAP module, CAP is unknown and doesn't hold up registration
+    writer.write_all(b"CAP LS 302\r\nJOIN #early\r\n").await.unwrap();
+    assert_eq!(next_numeric(&mut lines).await, "421");
+    assert_eq!(next_numeric(&mut lines).await, "451");
+
+    // USER may come before NICK
+    writer.write_all(b"USER alice 0 * :Alice\r\nNICK alice\r\n").await.unwrap();
+    let mut burst = Vec::new();
+    while !matches!(burst.last().map(String::as_str), Some("376" | "422")) {
+        burst.push(next_numeric(&mut lines).await);
+    }
+    assert_eq!(burst[..5], ["001", "002", "003", "004", "005"]);
+    for lusers in ["251", "252", "253", "254", "255", "265", "266"] {
+        assert!(burst.iter().any(|numeric| numeric == lusers), "missing {} in {:?}", lusers, burst);
+    }
+    assert_eq!(server.database().get_user_by_nick("alice").unwrap().username, "alice");
+
+    // The user sees their own nick change; the next one within the window is refused
+    writer.write_all(b"NICK alicia\r\nNICK alice2\r\n").await.unwrap();
+    assert_eq!(next_numeric(&mut lines).await, "NICK");
+    assert_eq!(next_numeric(&mut lines).await, "438");
+    assert!(server.database().get_user_by_nick("alicia").is_some());
+
+    // ISON takes the nicknames as separate or space-separated parameters
+    writer.write_all(b"ISON alicia :nobody alicia\r\nUSERHOST alicia\r\n").await.unwrap();
+    let line = lines.next_line().await.unwrap().unwrap();
+    assert_eq!(Message::parse(&line).unwrap().params, ["alicia", "alicia alicia"]);
+    let line = lines.next_line().await.unwrap().unwrap();
+    assert_eq!(Message::parse(&line).unwrap().params, ["alicia", "alicia=+alice@127.0.0.1"]);
+
+    server.stop().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_quit_over_tcp() {
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+    type Lines = tokio::io::Lines<BufReader<tokio::net::tcp::OwnedReadHalf>>;
+
+    let mut config = Config::default();
+    config.security.enable_ident = false;
+    config.security.enable_dns = false;
+    config.security.enable_reverse_dns = false;
+    let mut server = ServerBuilder::with_config(config)
+        .server_name("quit.test")
+        .port(16672, config::PortConnectionType::Client, false)
+        .build()
+        .await
+        .unwrap();
+    server.start().await.unwrap();
+
+    async fn next_message(lines: &mut Lines) -> Message {
+        let line = tokio::time::timeout(std::time::Duration::from_secs(5), lines.next_line())
+            .await.unwrap().unwrap().unwrap();
+        Message::parse(&line).unwrap()
+    }
+    async fn register(nick: &str) -> (Lines, tokio::net::tcp::OwnedWriteHalf) {
+        let stream = tokio::net::TcpStream::connect("127.0.0.1:16672").await.unwrap();
+        let (reader, mut writer) = stream.into_split();
+        let mut lines = BufReader::new(reader).lines();
+        writer.write_all(format!("NICK {}\r\nUSER {} 0 * :Test\r\n", nick, nick).as_bytes()).await.unwrap();
+        while !matches!(next_message(&mut lines).await.command.to_string().as_str(), "376" | "422") {}
+        (lines, writer)
+    }
+
+    let (mut alice_lines, mut alice) = register("alice").await;
+    let (mut bob_lines, mut bob) = register("bob").await;
+    let (_carol_lines, mut carol) = register("carol").await;
+    server.database().add_user_to_channel("alice", "#rust").unwrap();
+    server.database().add_user_to_channel("bob", "#rust").unwrap();
+
+    // Private messages are delivered straight to the local recipient
+    alice.write_all(b"PRIVMSG bob :hi there\r\n").await.unwrap();
+    let privmsg = next_message(&mut bob_lines).await;
+    assert_eq!(privmsg.command, MessageType::PrivMsg);
+    assert_eq!(privmsg.params, ["bob", "hi there"]);
+
+    alice.write_all(b"QUIT :bye\r\n").await.unwrap();
+    assert_eq!(next_message(&mut alice_lines).await.command, MessageType::Error);
+    let quit = next_message(&mut bob_lines).await;
+    assert_eq!(quit.command, MessageType::Quit);
+    assert_eq!(quit.params, ["Quit: bye"]);
+    assert_eq!(server.database().get_channel_users("#rust"), ["bob"]);
+    assert_eq!(server.database().get_user_history("alice").await.len(), 1);
+
+    bob.write_all(b"WHOWAS alice,nobody 1\r\n").await.unwrap();
+    let mut whowas = Vec::new();
+    while whowas.last().map(String::as_str) != Some("369") {
+        whowas.push(next_message(&mut bob_lines).await.command.to_string());
+    }
+    assert_eq!(whowas, ["314", "312", "406", "369"]);
+
+    // The nickname is free again, and closing the socket is a quit too
+    bob.write_all(b"NICK alice\r\n").await.unwrap();
+    assert_eq!(next_message(&mut bob_lines).await.command, MessageType::Nick);
+    carol.shutdown().await.unwrap();
+    tokio::time::timeout(std::time::Duration::from_secs(5), async {
+        while server.database().get_user_by_nick("carol").is_some() {
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        }
+    }).await.unwrap();
+
+    server.stop().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_away_over_tcp() {
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+    type Lines = tokio::io::Lines<BufReader<tokio::net::tcp::OwnedReadHalf>>;
+
+    let mut config = Config::default();
+    config.security.enable_ident = false;
+    config.security.enable_dns = false;
+    config.security.enable_reverse_dns = false;
+    let mut server = ServerBuilder::with_config(config)
+        .server_name("away.test")
+        .port(16673, config::PortConnectionType::Client, false)
+        .build()
+        .await
+        .unwrap();
+    server.start().await.unwrap();
+
+    async fn next_message(lines: &mut Lines) -> Message {
+        let line = tokio::time::timeout(std::time::Duration::from_secs(5), lines.next_line())
+            .await.unwrap().unwrap().unwrap();
+        Message::parse(&line).unwrap()
+    }
+    async fn register(nick: &str) -> (Lines, tokio::net::tcp::OwnedWriteHalf) {
+        let stream = tokio::net::TcpStream::connect("127.0.0.1:16673").await.unwrap();
+        let (reader, mut writer) = stream.into_split();
+        let mut lines = BufReader::new(reader).lines();
+        writer.write_all(format!("NICK {}\r\nUSER {} 0 * :Test\r\n", nick, nick).as_bytes()).await.unwrap();
+        while !matches!(next_message(&mut lines).await.command.to_string().as_str(), "376" | "422") {}
+        (lines, writer)
+    }
+
+    let (mut alice_lines, mut alice) = register("alice").await;
+    let (mut bob_lines, mut bob) = register("bob").await;
+
+    alice.write_all(b"AWAY :gone fishing\r\n").await.unwrap();
+    assert_eq!(next_message(&mut alice_lines).await.command.to_string(), "306");
+
+    // The away message survives a nick change
+    alice.write_all(b"NICK alicia\r\n").await.unwrap();
+    assert_eq!(next_message(&mut alice_lines).await.command, MessageType::Nick);
+
+    bob.write_all(b"PRIVMSG alicia :ping\r\n").await.unwrap();
+    let away = next_message(&mut bob_lines).await;
+    assert_eq!(away.command.to_string(), "301");
+    assert_eq!(away.params[1..], ["alicia", "gone fishing"]);
+    assert_eq!(next_message(&mut alice_lines).await.command, MessageType::PrivMsg);
+
+    bob.write_all(b"WHOIS alicia\r\n").await.unwrap();
+    let mut whois = Vec::new();
+    while whois.last().map(String::as_str) != Some("318") {
+        whois.push(next_message(&mut bob_lines).await.command.to_string());
+    }
+    assert!(whois.contains(&"301".to_string()));
+
+    // Coming back stops the replies
+    alice.write_all(b"AWAY\r\n").await.unwrap();
+    assert_eq!(next_message(&mut alice_lines).await.command.to_string(), "305");
+    bob.write_all(b"PRIVMSG alicia :back?\r\nPING :done\r\n").await.unwrap();
+    assert_eq!(next_message(&mut bob_lines).await.command, MessageType::Pong);
+
+    server.stop().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_user_history_limits() {
+    let user = |nick: &str| User::new(nick.to_string(), "user".to_string(), "Real".to_string(), "host".to_string(), "server".to_string());
+
+    // Only the newest entries fit
+    let db = Database::new(2, 30);
+    for nick in ["alice", "alice", "bob"] {
+        db.add_to_history(user(nick)).await.unwrap();
+    }
+    assert_eq!(db.get_user_history("alice").await.len(), 1);
+    assert_eq!(db.get_user_history("BOB").await.len(), 1);
+
+    // Nothing outlives a zero-day retention period
+    let db = Database::new(10, 0);
+    db.add_to_history(user("alice")).await.unwrap();
+    tokio::time::sleep(std::time::Duration::from_millis(2)).await;
+    assert!(db.get_user_history("alice").await.is_empty());
+}
+
 #[tokio::test]
 async fn test_channel_operations() {
     let db = Database::new(1000, 30);
@@ -205,25 +676,40 @@ async fn test_user_modes() {
 
 #[tokio::test]
 async fn test_broadcast_system() {
-    let system = BroadcastSystem::new();
+    let senders = std::sync::Arc::new(ClientSenders::new());
+    let system = BroadcastSystem::with_client_senders(senders.clone());
     
     // Register clients
     let client1_id = Uuid::new_v4();
     let client2_id = Uuid::new_v4();
+    let remote_id = Uuid::new_v4();
+    let (tx1, mut rx1) = tokio::sync::mpsc::unbounded_channel();
+    let (tx2, mut rx2) = tokio::sync::mpsc::unbounded_channel();
+    senders.insert(client1_id, tx1);
+    senders.insert(client2_id, tx2);
     
-    // Subscribe to channel
+    // Subscribe to channel; the remote member has no local queue
     system.subscribe_to_channel(client1_id, "#test".to_string());
     system.subscribe_to_channel(client2_id, "#test".to_string());
+    system.subscribe_to_channel(remote_id, "#test".to_string());
+    assert_eq!(system.channel_member_count("#test"), 3);
     
-    // Test channel subscription
-    let _target = BroadcastTarget::Channel("#test".to_string());
+    // Channel broadcasts go straight to the indexed queues
+    let message = Message::new(MessageType::PrivMsg, vec!["#test".to_string(), "hi".to_string()]);
+    system.broadcast_to_channel("#test", message.clone(), None).await.unwrap();
+    assert_eq!(rx1.try_recv().unwrap().params[1], "hi");
+    assert_eq!(rx2.try_recv().unwrap().params[1], "hi");
     
     // Unsubscribe
     system.unsubscribe_from_channel(&client1_id, "#test");
+    system.broadcast_to_channel("#test", message, None).await.unwrap();
+    assert!(rx1.try_recv().is_err());
+    assert!(rx2.try_recv().is_ok());
     
-    // Cleanup
-    system.unregister_client(&client1_id);
+    // Quitting leaves every channel, and empty channels are dropped
     system.unregister_client(&client2_id);
+    system.unregister_client(&remote_id);
+    assert_eq!(system.channel_member_count("#test"), 0);
 }
 
 #[tokio::test]
@@ -284,6 +770,50 @@ async fn test_batch_optimizer() {
     assert_eq!(stats.total_batches_sent, 1);
 }
 
+#[tokio::test]
+async fn test_batched_channel_broadcasts() {
+    use std::time::Duration;
+    
+    let senders = std::sync::Arc::new(ClientSenders::new());
+    let mut system = BroadcastSystem::with_client_senders(senders.clone());
+    system.enable_batching(BatchConfig {
+        max_batch_size: 50,
+        max_batch_delay: Duration::from_secs(60),
+        max_batch_bytes: 4096,
+    });
+    let member = Uuid::new_v4();
+    let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+    senders.insert(member, tx);
+    system.subscribe_to_channel(member, "#test".to_string());
+    
+    // Queued events are held in the member's batch until it is due
+    for mode in ["+o alice", "+o bob", "+v carol"] {
+        system.queue_message(BroadcastMessage {
+            message: Message::new(MessageType::Mode, vec!["#test".to_string(), mode.to_string()]),
+            target: BroadcastTarget::Channel("#test".to_string()),
+            sender: None,
+            priority: BroadcastPriority::Normal,
+        }).unwrap();
+    }
+    system.process_queues().await.unwrap();
+    assert!(rx.try_recv().is_err());
+    
+    // Critical broadcasts skip the batch
+    system.queue_message(BroadcastMessage {
+        message: Message::new(MessageType::Notice, vec!["#test".to_string(), "now".to_string()]),
+        target: BroadcastTarget::Channel("#test".to_string()),
+        sender: None,
+        priority: BroadcastPriority::Critical,
+    }).unwrap();
+    system.process_queues().await.unwrap();
+    assert_eq!(rx.try_recv().unwrap().params[1], "now");
+    
+    assert_eq!(system.flush_batches(true).await, 1);
+    let modes: Vec<_> = std::iter::from_fn(|| rx.try_recv().ok()).map(|m| m.params[1].clone()).collect();
+    assert_eq!(modes, ["+o alice", "+o bob", "+v carol"]);
+    assert_eq!(system.batch_stats().await.unwrap().writes_saved(), 2);
+}
+
 #[tokio::test]
 async fn test_connection_pool() {
     let pool = ConnectionPool::new(5);
@@ -356,11 +886,14 @@ async fn test_throttling_manager() {
     let config = ThrottlingConfig {
         enabled: true,
         max_connections_per_ip: 3,
+        ipv6_prefix: 64,
         time_window_seconds: 60,
         initial_throttle_seconds: 10,
         max_stages: 5,
         stage_factor: 2,
         cleanup_interval_seconds: 300,
+        auth_failures: Default::default(),
+        exempt: Vec::new(),
     };
 
     let throttling = ThrottlingManager::new(config);
@@ -433,3 +966,107 @@ fn test_user_creation() {
 
 
 
+
+#[test]
+fn test_real_host_visibility() {
+    let mut alice = User::new(
+        "alice".to_string(),
+        "user".to_string(),
+        "Alice User".to_string(),
+        "192.0.2.10".to_string(),
+        "server.example.com".to_string(),
+    );
+    alice.set_cloak("a1b2c3d4.e5f6a7b8.cloaked".to_string());
+    assert!(alice.is_cloaked());
+    assert!(alice.has_mode('x'));
+    
+    let mut bob = User::new(
+        "bob".to_string(),
+        "bob".to_string(),
+        "Bob".to_string(),
+        "198.51.100.1".to_string(),
+        "server.example.com".to_string(),
+    );
+    
+    // Regular users see the cloak, the user themself and operators see the real host
+    assert_eq!(alice.host_for(Some(&bob)), "a1b2c3d4.e5f6a7b8.cloaked");
+    assert_eq!(alice.host_for(None), "a1b2c3d4.e5f6a7b8.cloaked");
+    assert_eq!(alice.host_for(Some(&alice.clone())), "192.0.2.10");
+    bob.is_operator = true;
+    assert_eq!(alice.host_for(Some(&bob)), "192.0.2.10");
+    
+    alice.clear_cloak();
+    assert_eq!(alice.host, "192.0.2.10");
+    assert!(!alice.has_mode('x'));
+}
+
+#[cfg(feature = "admin-api")]
+#[tokio::test]
+async fn test_admin_api() {
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+
+    let mut config = Config::default();
+    config.security.enable_ident = false;
+    config.security.enable_dns = false;
+    config.security.enable_reverse_dns = false;
+    config.admin_api.enabled = true;
+    config.admin_api.port = 0;
+    config.admin_api.token_hash = config::PasswordHasher::hash_password("api-token");
+    let mut server = ServerBuilder::with_config(config)
+        .server_name("admin.test")
+        .port(0, config::PortConnectionType::Client, false)
+        .build()
+        .await
+        .unwrap();
+    server.start().await.unwrap();
+    let api = format!("http://{}/api/v1", server.admin_api_addr().unwrap());
+
+    let stream = tokio::net::TcpStream::connect(server.listen_addrs()[0]).await.unwrap();
+    let (reader, mut writer) = stream.into_split();
+    let mut lines = BufReader::new(reader).lines();
+    writer.write_all(b"NICK alice\r\nUSER alice 0 * :Alice\r\n").await.unwrap();
+    loop {
+        let line = lines.next_line().await.unwrap().unwrap();
+        if matches!(Message::parse(&line).unwrap().command.to_string().as_str(), "376" | "422") {
+            break;
+        }
+    }
+
+    let http = reqwest::Client::new();
+    let response = http.get(format!("{}/users", api)).send().await.unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::UNAUTHORIZED);
+    let response = http.get(format!("{}/users", api)).bearer_auth("wrong").send().await.unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::UNAUTHORIZED);
+
+    let users: serde_json::Value = http.get(format!("{}/users", api)).bearer_auth("api-token")
+        .send().await.unwrap().json().await.unwrap();
+    assert_eq!(users[0]["nick"], "alice");
+    assert_eq!(users[0]["server"], "admin.test");
+    let stats: serde_json::Value = http.get(format!("{}/stats", api)).bearer_auth("api-token")
+        .send().await.unwrap().json().await.unwrap();
+    assert_eq!(stats["local_users"], 1);
+
+    // No module registered a ban type, and unknown sections are refused
+    let response = http.post(format!("{}/bans", api)).bearer_auth("api-token")
+        .json(&serde_json::json!({ "type": "KLINE", "mask": "*@*", "reason": "test" }))
+        .send().await.unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::BAD_REQUEST);
+    let response = http.post(format!("{}/rehash", api)).bearer_auth("api-token")
+        .json(&serde_json::json!({ "section": "BOGUS" }))
+        .send().await.unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::BAD_REQUEST);
+
+    let response = http.post(format!("{}/users/alice/kill", api)).bearer_auth("api-token")
+        .json(&serde_json::json!({ "reason": "Spamming" }))
+        .send().await.unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    let kill = Message::parse(&lines.next_line().await.unwrap().unwrap()).unwrap();
+    assert_eq!(kill.command, MessageType::Kill);
+    assert_eq!(kill.params, ["alice", "Spamming"]);
+    let response = http.post(format!("{}/users/alice/kill", api)).bearer_auth("api-token")
+        .json(&serde_json::json!({}))
+        .send().await.unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::NOT_FOUND);
+
+    server.stop().await.unwrap();
+}
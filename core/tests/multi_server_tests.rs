@@ -0,0 +1,381 @@
+//! Multi-server netsplit flow tests
+//!
+//! Links several in-process `Server` instances through in-memory server
+//! connections and drives link setup, netsplits and relinks end to end.
+
+use rustircd_core::{
+    BanEntry, BanTarget, BanType, Config, Message, MessageType, Server, ServerConnection,
+    User, UserState,
+};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+
+/// A network of in-process servers joined by in-memory links
+struct TestNetwork {
+    servers: HashMap<String, Arc<Server>>,
+    links: HashMap<(String, String), JoinHandle<()>>,
+    grace_period: u64,
+}
+
+impl TestNetwork {
+    fn new(grace_period: u64) -> Self {
+        Self {
+            servers: HashMap::new(),
+            links: HashMap::new(),
+            grace_period,
+        }
+    }
+
+    async fn add_server(&mut self, name: &str) -> Arc<Server> {
+        let mut config = Config::default();
+        config.server.name = name.to_string();
+        config.server.description = format!("{} test server", name);
+        config.netsplit.split_user_grace_period = self.grace_period;
+        config.netsplit.notify_opers_on_split = false;
+
+        let server = Arc::new(Server::new(config).await);
+        self.servers.insert(name.to_string(), server.clone());
+        server
+    }
+
+    fn server(&self, name: &str) -> Arc<Server> {
+        self.servers[name].clone()
+    }
+
+    /// Add a connection on `from` named `to` and return what `from` sends over it
+    async fn capture(&self, from: &str, to: &str) -> mpsc::UnboundedReceiver<Message> {
+        let (tx, rx) = mpsc::unbounded_channel::<Message>();
+        let mut connection = ServerConnection::new(
+            uuid::Uuid::new_v4(),
+            "127.0.0.1:7000".parse().unwrap(),
+            "127.0.0.1:7001".parse().unwrap(),
+            tx,
+            true,
+        );
+        connection.info.name = to.to_string();
+        self.server(from).server_connections().add_connection(connection).await.unwrap();
+        rx
+    }
+
+    /// Pump messages sent by `from` over the link into `to`
+    async fn open_direction(&mut self, from: &str, to: &str) {
+        let mut rx = self.capture(from, to).await;
+        let receiver = self.server(to);
+        let source = from.to_string();
+        let pump = tokio::spawn(async move {
+            while let Some(message) = rx.recv().await {
+                let _ = receiver.handle_server_message(&source, message).await;
+            }
+        });
+        self.links.insert((from.to_string(), to.to_string()), pump);
+    }
+
+    /// Link two servers and run the SERVER/burst exchange
+    async fn link(&mut self, a: &str, b: &str) {
+        self.open_direction(a, b).await;
+        self.open_direction(b, a).await;
+
+        let server_msg = Message::new(
+            MessageType::Server,
+            vec![b.to_string(), "1".to_string(), format!("{} test server", b)],
+        );
+        self.server(a).handle_server_message(b, server_msg).await.unwrap();
+    }
+
+    /// Drop the link between two servers, as if the socket closed on both ends
+    async fn sever(&mut self, a: &str, b: &str) {
+        for key in [(a.to_string(), b.to_string()), (b.to_string(), a.to_string())] {
+            if let Some(pump) = self.links.remove(&key) {
+                pump.abort();
+            }
+        }
+        self.server(a).handle_server_link_closed(b, "Connection reset by peer").await.unwrap();
+        self.server(b).handle_server_link_closed(a, "Connection reset by peer").await.unwrap();
+    }
+
+    /// Register a user on a server and introduce it to the linked servers
+    async fn add_user(&self, server_name: &str, nick: &str) -> User {
+        let server = self.server(server_name);
+        let user = User::new(
+            nick.to_string(),
+            nick.to_string(),
+            format!("{} test user", nick),
+            format!("{}.example.com", nick),
+            server_name.to_string(),
+        );
+        server.database().add_user(user.clone()).unwrap();
+
+        let burst = Message::new(
+            MessageType::UserBurst,
+            vec![
+                user.nick.clone(),
+                user.username.clone(),
+                user.host.clone(),
+                user.realname.clone(),
+                user.server.clone(),
+                user.id.to_string(),
+                user.registered_at.timestamp().to_string(),
+                user.real_host.clone(),
+            ],
+        );
+        server.server_connections().broadcast_to_servers(burst).await.unwrap();
+        user
+    }
+
+    /// Record channel membership on every server
+    ///
+    /// S2S JOIN does not carry membership yet, so the harness seeds the state
+    /// each server would hold after the join had propagated.
+    fn join(&self, nick: &str, channel: &str) {
+        for server in self.servers.values() {
+            server.database().add_user_to_channel(nick, channel).unwrap();
+        }
+    }
+
+    /// Attach a local user in `channel` to a server, recording everything sent to it
+    fn observe(&self, server_name: &str, nick: &str, channel: &str) -> mpsc::UnboundedReceiver<Message> {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let server = self.server(server_name);
+        let user = User::new(
+            nick.to_string(),
+            nick.to_string(),
+            format!("{} test user", nick),
+            format!("{}.example.com", nick),
+            server_name.to_string(),
+        );
+        server.database().add_user(user.clone()).unwrap();
+        server.database().add_user_to_channel(nick, channel).unwrap();
+        server.client_senders().insert(user.id, tx);
+        rx
+    }
+}
+
+/// Poll a condition until it holds, failing the test after two seconds
+async fn eventually<F: Fn() -> bool>(what: &str, condition: F) {
+    for _ in 0..200 {
+        if condition() {
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    panic!("timed out waiting for: {}", what);
+}
+
+fn user_state(server: &Server, nick: &str) -> Option<UserState> {
+    server.database().get_user_by_nick(nick).map(|user| user.state)
+}
+
+/// leaf1 <-> hub <-> leaf2, with one user on each server
+async fn three_server_network(grace_period: u64) -> TestNetwork {
+    let mut network = TestNetwork::new(grace_period);
+    for name in ["hub.test", "leaf1.test", "leaf2.test"] {
+        network.add_server(name).await;
+    }
+
+    network.add_user("hub.test", "alice").await;
+    network.add_user("leaf1.test", "bob").await;
+    network.add_user("leaf2.test", "carol").await;
+
+    network.link("hub.test", "leaf1.test").await;
+    network.link("hub.test", "leaf2.test").await;
+
+    for name in ["hub.test", "leaf1.test", "leaf2.test"] {
+        let server = network.server(name);
+        eventually(&format!("{} to learn all users", name), || {
+            ["alice", "bob", "carol"].iter().all(|nick| user_state(&server, nick) == Some(UserState::Active))
+        }).await;
+    }
+    network
+}
+
+#[tokio::test]
+async fn test_burst_reaches_every_server() {
+    let network = three_server_network(0).await;
+
+    // Users introduced after the links are up are forwarded across the hub
+    network.add_user("leaf1.test", "dave").await;
+    let leaf2 = network.server("leaf2.test");
+    eventually("dave to reach leaf2", || user_state(&leaf2, "dave") == Some(UserState::Active)).await;
+
+    // Users keep their originating server wherever they are seen
+    let carol = network.server("leaf1.test").database().get_user_by_nick("carol").unwrap();
+    assert_eq!(carol.server, "leaf2.test");
+}
+
+#[tokio::test]
+async fn test_netsplit_removes_users_behind_the_split() {
+    let mut network = three_server_network(0).await;
+    network.join("alice", "#rust");
+    network.join("carol", "#rust");
+    let mut observer = network.observe("hub.test", "dave", "#rust");
+
+    network.sever("hub.test", "leaf2.test").await;
+
+    // The hub drops carol immediately and tells its local clients
+    let hub = network.server("hub.test");
+    assert!(hub.database().get_user_by_nick("carol").is_none());
+    let mut members = hub.database().get_channel_users("#rust");
+    members.sort();
+    assert_eq!(members, ["alice", "dave"]);
+
+    let quit = observer.try_recv().expect("netsplit QUIT broadcast");
+    assert_eq!(quit.command, MessageType::Quit);
+    assert_eq!(quit.params, vec!["hub.test leaf2.test".to_string()]);
+    assert!(observer.try_recv().is_err());
+
+    // The split is propagated to leaf1 without tearing down leaf1's own link
+    let leaf1 = network.server("leaf1.test");
+    eventually("leaf1 to drop carol", || user_state(&leaf1, "carol").is_none()).await;
+    assert!(leaf1.server_connections().is_connected("hub.test").await);
+    assert_eq!(user_state(&leaf1, "alice"), Some(UserState::Active));
+
+    // leaf2 loses the hub and everything routed through it
+    let leaf2 = network.server("leaf2.test");
+    assert!(user_state(&leaf2, "alice").is_none());
+    assert!(user_state(&leaf2, "bob").is_none());
+    assert_eq!(user_state(&leaf2, "carol"), Some(UserState::Active));
+}
+
+#[tokio::test]
+async fn test_relink_restores_split_users() {
+    let mut network = three_server_network(60).await;
+    let carol_id = network.server("hub.test").database().get_user_by_nick("carol").unwrap().id;
+
+    network.sever("hub.test", "leaf2.test").await;
+
+    // With a grace period, split users are kept but marked as netsplit
+    let hub = network.server("hub.test");
+    let leaf1 = network.server("leaf1.test");
+    assert_eq!(user_state(&hub, "carol"), Some(UserState::NetSplit));
+    eventually("leaf1 to mark carol as split", || user_state(&leaf1, "carol") == Some(UserState::NetSplit)).await;
+
+    network.link("hub.test", "leaf2.test").await;
+
+    // The re-burst reconciles the same users instead of treating them as nick collisions
+    for name in ["hub.test", "leaf1.test", "leaf2.test"] {
+        let server = network.server(name);
+        eventually(&format!("{} to restore all users", name), || {
+            ["alice", "bob", "carol"].iter().all(|nick| user_state(&server, nick) == Some(UserState::Active))
+        }).await;
+    }
+    let carol = hub.database().get_user_by_nick("carol").unwrap();
+    assert_eq!(carol.id, carol_id);
+    assert!(carol.split_at.is_none());
+}
+
+#[tokio::test]
+async fn test_burst_includes_global_bans() {
+    let mut network = TestNetwork::new(0);
+    let hub = network.add_server("hub.test").await;
+
+    let bans = hub.ban_manager();
+    bans.register_type(BanType::new("GLINE", BanTarget::UserHost, "G-Lined").global());
+    bans.register_type(BanType::new("KLINE", BanTarget::UserHost, "K-Lined"));
+    bans.add_ban(BanEntry::new("GLINE", "*@spam.example.com", "spam", "oper", Some(3600))).unwrap();
+    bans.add_ban(BanEntry::new("KLINE", "*@local.example.com", "local only", "oper", None)).unwrap();
+
+    let mut link = network.capture("hub.test", "leaf1.test").await;
+    let server_msg = Message::new(
+        MessageType::Server,
+        vec!["leaf1.test".to_string(), "1".to_string(), "leaf1.test test server".to_string()],
+    );
+    hub.handle_server_message("leaf1.test", server_msg).await.unwrap();
+
+    let mut burst = Vec::new();
+    while let Ok(message) = link.try_recv() {
+        burst.push(message);
+    }
+
+    // Only network-wide ban types are burst; local K-lines stay local
+    let glines: Vec<&Message> = burst.iter()
+        .filter(|m| m.command == MessageType::Custom("GLINE".to_string()))
+        .collect();
+    assert_eq!(glines.len(), 1);
+    let gline = BanEntry::from_params("GLINE", &glines[0].params).unwrap();
+    assert_eq!(gline, bans.get_ban("GLINE", "*@spam.example.com").unwrap());
+    assert!(!burst.iter().any(|m| m.command == MessageType::Custom("KLINE".to_string())));
+}
+
+#[tokio::test]
+async fn test_remote_whois_is_answered_by_the_home_server() {
+    let mut network = TestNetwork::new(0);
+    let leaf2 = network.add_server("leaf2.test").await;
+    network.add_user("leaf2.test", "carol").await;
+    let bob = User::new(
+        "bob".to_string(),
+        "bob".to_string(),
+        "bob test user".to_string(),
+        "bob.example.com".to_string(),
+        "leaf1.test".to_string(),
+    );
+    leaf2.database().add_user(bob).unwrap();
+    let mut link = network.capture("leaf2.test", "hub.test").await;
+
+    let whois = Message::parse(":bob!bob@bob.example.com WHOIS leaf2.test carol").unwrap();
+    leaf2.handle_server_message("hub.test", whois).await.unwrap();
+
+    let mut replies = Vec::new();
+    while let Ok(message) = link.try_recv() {
+        replies.push(message);
+    }
+    let codes: Vec<String> = replies.iter().map(|m| m.command.to_string()).collect();
+    assert_eq!(codes.first().map(String::as_str), Some("311"));
+    assert_eq!(codes.last().map(String::as_str), Some("318"));
+    // Every numeric is addressed to the requester so the hub can route it back
+    assert!(replies.iter().all(|m| m.params[0] == "bob" && m.prefix.as_ref().map(|p| p.to_string()) == Some("leaf2.test".to_string())));
+    let server_line = replies.iter().find(|m| m.command.to_string() == "312").unwrap();
+    assert_eq!(server_line.params[2], "leaf2.test");
+}
+
+#[tokio::test]
+async fn test_remote_server_queries_are_answered_by_the_target() {
+    let mut network = TestNetwork::new(0);
+    let leaf2 = network.add_server("leaf2.test").await;
+    let bob = User::new(
+        "bob".to_string(),
+        "bob".to_string(),
+        "bob test user".to_string(),
+        "bob.example.com".to_string(),
+        "leaf1.test".to_string(),
+    );
+    leaf2.database().add_user(bob).unwrap();
+    let mut link = network.capture("leaf2.test", "hub.test").await;
+
+    for (query, expected) in [
+        ("VERSION leaf2.test", vec!["351"]),
+        ("TIME leaf2.test", vec!["391"]),
+        ("ADMIN leaf2.test", vec!["256", "257", "258", "259"]),
+        ("STATS u leaf2.test", vec!["242", "219"]),
+    ] {
+        let request = Message::parse(&format!(":bob!bob@bob.example.com {}", query)).unwrap();
+        leaf2.handle_server_message("hub.test", request).await.unwrap();
+
+        let mut replies = Vec::new();
+        while let Ok(message) = link.try_recv() {
+            replies.push(message);
+        }
+        let codes: Vec<String> = replies.iter().map(|m| m.command.to_string()).collect();
+        assert_eq!(codes, expected, "{}", query);
+        assert!(replies.iter().all(|m| m.params[0] == "bob"));
+    }
+}
+
+#[tokio::test]
+async fn test_shutdown_squits_links_once() {
+    let mut network = TestNetwork::new(0);
+    let hub = network.add_server("hub.test").await;
+    let mut link = network.capture("hub.test", "leaf1.test").await;
+
+    hub.shutdown("Maintenance").await.unwrap();
+    assert!(hub.is_shutting_down());
+    let squit = link.try_recv().unwrap();
+    assert_eq!(squit.command, MessageType::ServerQuit);
+    assert_eq!(squit.params, vec!["hub.test".to_string(), "Maintenance".to_string()]);
+
+    // Later calls are no-ops
+    hub.shutdown("Again").await.unwrap();
+    assert!(link.try_recv().is_err());
+}
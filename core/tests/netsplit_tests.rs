@@ -2,9 +2,8 @@
 //!
 //! Tests for netsplit detection, recovery, and related functionality.
 
-use rustircd_core::{Config, Server, User, UserState};
+use rustircd_core::{Config, User, UserState};
 use std::sync::Arc;
-use tokio::sync::RwLock;
 
 /// Test netsplit QUIT message formatting
 #[tokio::test]
@@ -231,6 +230,58 @@ async fn test_reconnection_state() {
     assert_eq!(state.current_delay, 30);
 }
 
+/// Test autoconnect scheduling and backoff
+#[tokio::test]
+async fn test_autoconnect_backoff() {
+    use rustircd_core::config::ServerLink;
+    use rustircd_core::ServerConnectionManager;
+
+    let mut config = Config::default();
+    config.netsplit.reconnect_delay_base = 10;
+    config.netsplit.reconnect_delay_max = 25;
+    for (name, autoconnect) in [("hub.test", true), ("leaf.test", false)] {
+        config.network.links.push(ServerLink {
+            name: name.to_string(),
+            hostname: name.to_string(),
+            port: 6668,
+            password: "linkpass".to_string(),
+            tls: false,
+            outgoing: true,
+            class: None,
+            autoconnect,
+            tls_fingerprint: None,
+            tls_ca_file: None,
+            tls_server_name: None,
+            compression: false,
+        });
+    }
+    let manager = ServerConnectionManager::new(Arc::new(config));
+
+    // Only autoconnect links are dialed, straight away on startup
+    let due: Vec<String> = manager.due_autoconnects().await.into_iter().map(|link| link.name).collect();
+    assert_eq!(due, vec!["hub.test".to_string()]);
+
+    // Each attempt doubles the wait, up to the maximum
+    assert_eq!(manager.record_autoconnect_attempt("hub.test"), (1, 10));
+    assert!(manager.due_autoconnects().await.is_empty());
+    assert_eq!(manager.record_autoconnect_attempt("hub.test"), (2, 20));
+    assert_eq!(manager.record_autoconnect_attempt("hub.test"), (3, 25));
+
+    // Once linked the backoff is forgotten; a drop waits the base delay again
+    manager.record_link_established("hub.test");
+    assert!(manager.reconnection_state("hub.test").is_none());
+    manager.record_link_dropped("hub.test");
+    let state = manager.reconnection_state("hub.test").unwrap();
+    assert_eq!((state.attempts, state.current_delay), (0, 10));
+    assert!(!state.should_attempt_reconnect());
+
+    // Non-autoconnect links never get scheduled
+    manager.record_link_dropped("leaf.test");
+    assert!(manager.reconnection_state("leaf.test").is_none());
+    assert!(!manager.reset_autoconnect_backoff("leaf.test"));
+    assert!(manager.reset_autoconnect_backoff("hub.test"));
+}
+
 /// Test user state transitions
 #[tokio::test]
 async fn test_user_state_transitions() {
@@ -261,3 +312,100 @@ async fn test_user_state_transitions() {
     assert!(user.split_at.is_none());
 }
 
+
+/// Test the incoming server handshake and link acceptance rules
+#[tokio::test]
+async fn test_incoming_server_handshake() {
+    use rustircd_core::config::{ConnectionClass, ServerLink};
+    use rustircd_core::{IncomingHandshake, Message, ServerConnection, ServerConnectionManager};
+
+    // SERVER must come after PASS
+    let mut handshake = IncomingHandshake::new();
+    assert!(handshake.handle(&Message::parse("SERVER leaf.test 1 :Leaf").unwrap()).is_err());
+
+    let mut handshake = IncomingHandshake::new();
+    assert!(handshake.handle(&Message::parse("PASS linkpass").unwrap()).unwrap().is_none());
+    assert!(handshake.handle(&Message::parse("CAPAB :ZIP QS").unwrap()).unwrap().is_none());
+    let registration = handshake.handle(&Message::parse("SERVER leaf.test 1 :Leaf server").unwrap())
+        .unwrap()
+        .unwrap();
+    assert_eq!(registration.name, "leaf.test");
+    assert_eq!(registration.description, "Leaf server");
+    assert!(registration.has_capability("zip"));
+    assert!(handshake.handle(&Message::parse("SERVER again.test 1 :Again").unwrap()).is_err());
+
+    let mut config = Config::default();
+    let mut class = ConnectionClass::default();
+    class.name = "servers".to_string();
+    class.max_clients = Some(1);
+    config.classes.push(class);
+    for name in ["leaf.test", "other.test"] {
+        config.network.links.push(ServerLink {
+            name: name.to_string(),
+            hostname: name.to_string(),
+            port: 6668,
+            password: "linkpass".to_string(),
+            tls: false,
+            outgoing: false,
+            class: Some("servers".to_string()),
+            autoconnect: false,
+            tls_fingerprint: None,
+            tls_ca_file: None,
+            tls_server_name: None,
+            compression: false,
+        });
+    }
+    let manager = ServerConnectionManager::new(Arc::new(config));
+    let addr: std::net::SocketAddr = "192.0.2.1:40000".parse().unwrap();
+
+    let mut wrong_password = registration.clone();
+    wrong_password.password = "nope".to_string();
+    assert!(manager.accept_incoming(&wrong_password, addr).await.is_err());
+    let mut unknown = registration.clone();
+    unknown.name = "stranger.test".to_string();
+    assert!(manager.accept_incoming(&unknown, addr).await.is_err());
+    assert!(manager.accept_incoming(&registration, addr).await.is_ok());
+
+    // Once leaf.test is linked it can't link twice, and its class is full
+    let (sender, _receiver) = tokio::sync::mpsc::unbounded_channel();
+    let mut connection = ServerConnection::new(uuid::Uuid::new_v4(), addr, addr, sender, false);
+    connection.info.name = "leaf.test".to_string();
+    manager.add_connection(connection).await.unwrap();
+    assert!(manager.accept_incoming(&registration, addr).await.is_err());
+    let mut other = registration.clone();
+    other.name = "other.test".to_string();
+    assert!(manager.accept_incoming(&other, addr).await.is_err());
+}
+
+/// Test that registered links are PINGed and silent ones are reported dead
+#[tokio::test]
+async fn test_link_ping_timeout() {
+    use rustircd_core::{MessageType, ServerConnection, ServerConnectionManager, ServerConnectionState};
+
+    let manager = ServerConnectionManager::new(Arc::new(Config::default()));
+    let addr: std::net::SocketAddr = "192.0.2.1:40000".parse().unwrap();
+    let mut receivers = Vec::new();
+    for name in ["alive.test", "silent.test"] {
+        let (sender, receiver) = tokio::sync::mpsc::unbounded_channel();
+        let mut connection = ServerConnection::new(uuid::Uuid::new_v4(), addr, addr, sender, true);
+        connection.info.name = name.to_string();
+        connection.state = ServerConnectionState::Registered;
+        if name == "silent.test" {
+            connection.stats.connected_at = chrono::Utc::now() - chrono::Duration::seconds(600);
+        }
+        manager.add_connection(connection).await.unwrap();
+        receivers.push(receiver);
+    }
+
+    let dead = manager.ping_links("local.test", 90, 240).await;
+    assert_eq!(dead, vec!["silent.test".to_string()]);
+    let ping = receivers[0].try_recv().unwrap();
+    assert_eq!(ping.command, MessageType::Ping);
+    assert_eq!(ping.params, vec!["local.test".to_string()]);
+
+    // Not due again until the interval passes; a PONG keeps the silent link alive
+    let _ = manager.ping_links("local.test", 90, 240).await;
+    assert!(receivers[0].try_recv().is_err());
+    manager.update_connection_pong("silent.test").await.unwrap();
+    assert!(manager.ping_links("local.test", 90, 240).await.is_empty());
+}
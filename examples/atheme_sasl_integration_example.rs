@@ -54,6 +54,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         service_name: "services.example.org".to_string(),
         require_sasl: false,
         timeout_seconds: 300,
+        max_failures: 3,
     };
     
     // Create SASL module with authentication manager
@@ -48,6 +48,7 @@ async fn setup_sasl_with_auth_providers(auth_manager: Arc<AuthManager>) -> Resul
         sasl_service: "SaslServ".to_string(),
         require_sasl: false,
         timeout_seconds: 300,
+        max_failures: 3,
     };
     
     // Create SASL module with authentication manager
@@ -102,6 +103,7 @@ async fn setup_external_auth_providers(auth_manager: Arc<AuthManager>) -> Result
         use_tls: false,
         timeout_seconds: 30,
         max_connections: 10,
+        ..Default::default()
     };
     let ldap_provider = Arc::new(LdapAuthProvider::new(ldap_config));
     auth_manager.register_provider(ldap_provider).await?;
@@ -152,6 +154,7 @@ async fn setup_external_auth_providers(auth_manager: Arc<AuthManager>) -> Result
         username_field: "username".to_string(),
         password_field: "password".to_string(),
         response_format: rustircd_modules::auth::http::ResponseFormat::Json,
+        ..Default::default()
     };
     let http_provider = Arc::new(HttpAuthProvider::new(http_config));
     auth_manager.register_provider(http_provider).await?;
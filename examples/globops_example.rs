@@ -25,6 +25,8 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         tls: false,
         description: Some("GLOBOPS test port".to_string()),
         bind_address: None,
+        bind_address_v6: None,
+        request_client_cert: false,
     });
     config.server.name = "globops.example.com".to_string();
 
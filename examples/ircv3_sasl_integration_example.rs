@@ -32,7 +32,7 @@ async fn main() -> Result<()> {
     
     // Handle CAP LS (this would normally be handled by the server)
     println!("   Server responds with available capabilities including SASL");
-    println!("   Available capabilities: cap, message-tags, account-tag, away-notify, batch, bot-mode, channel-rename, chghost, echo-message, extended-join, invite-notify, multi-prefix, sasl, server-time, userhost-in-names");
+    println!("   Available capabilities: cap, message-tags, account-tag, away-notify, batch, bot-mode, draft/channel-rename, chghost, echo-message, extended-join, invite-notify, multi-prefix, sasl, server-time, userhost-in-names");
     
     println!("\n2. Client requests SASL capability");
     println!("   Client sends: CAP REQ :sasl");
@@ -43,6 +43,8 @@ async fn main() -> Result<()> {
         tls: false,
         description: Some("MOTD test port".to_string()),
         bind_address: None,
+        bind_address_v6: None,
+        request_client_cert: false,
     });
     
     println!("Configuration:");
@@ -89,6 +89,8 @@ fn create_server_config() -> Config {
         tls: false,
         description: Some("Standard IRC port".to_string()),
         bind_address: None,
+        bind_address_v6: None,
+        request_client_cert: false,
     });
     
     config
@@ -301,7 +303,7 @@ fn show_supported_user_modes() {
     println!("a - Away mode (user is away)");
     println!("i - Invisible mode (user doesn't appear in WHO lists)");
     println!("w - Wallops mode (user receives wallop messages)");
-    println!("r - Restricted mode (user is restricted from certain actions)");
+    println!("r - Registered mode (user is identified to a services account)");
     println!("o - Operator mode (user has operator privileges)");
     println!("O - Local operator mode (user has local operator privileges)");
     println!("s - Server notices mode (user receives server notices)");
@@ -400,7 +402,7 @@ fn show_user_mode_benefits() {
     println!("✅ Message Control: Wallops mode for operator messages");
     println!("✅ Access Control: Operator modes for privilege management");
     println!("✅ Server Integration: Server notices for important messages");
-    println!("✅ Security: Restricted mode for problematic users");
+    println!("✅ Accounts: Registered mode marks users identified to services");
     println!("✅ RFC Compliance: Standard IRC user mode implementation");
 }
 
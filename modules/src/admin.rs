@@ -8,7 +8,7 @@ use rustircd_core::{
     ModuleNumericManager, module::{ModuleResult, ModuleStatsResponse, ModuleContext},
     NumericReply, Result, User
 };
-use tracing::{info, error};
+use tracing::info;
 use tokio::sync::RwLock;
 
 /// Administrative module for server administration
@@ -152,101 +152,6 @@ impl AdminModule {
         Ok(())
     }
     
-    /// Handle REHASH command
-    async fn handle_rehash(&self, client: &Client, user: &User, args: &[String], server: Option<&rustircd_core::Server>) -> Result<()> {
-        if !user.is_operator() {
-            client.send_numeric(NumericReply::ErrNoPrivileges, &["Permission denied"])?;
-            return Ok(());
-        }
-        
-        if args.is_empty() {
-            // No parameters - reload main configuration
-            client.send_numeric(NumericReply::RplLocops, &["REHASH: Reloading main configuration..."])?;
-            
-            if let Some(server) = server {
-                match server.rehash_service().reload_main_config().await {
-                    Ok(_) => {
-                        client.send_numeric(NumericReply::RplLocops, &["REHASH: Main configuration reloaded successfully"])?;
-                        info!("REHASH: Main configuration reloaded by {}", user.nickname());
-                    }
-                    Err(e) => {
-                        client.send_numeric(NumericReply::RplLocops, &[&format!("REHASH: Failed to reload main configuration: {}", e)])?;
-                        error!("REHASH: Failed to reload main configuration by {}: {}", user.nickname(), e);
-                    }
-                }
-            } else {
-                client.send_numeric(NumericReply::RplLocops, &["REHASH: Server reference not available"])?;
-            }
-            return Ok(());
-        }
-        
-        let parameter = &args[0].to_uppercase();
-        
-        if let Some(server) = server {
-            match parameter.as_str() {
-                "SSL" => {
-                    client.send_numeric(NumericReply::RplLocops, &["REHASH SSL: Reloading TLS settings..."])?;
-
-                    // First validate the configuration
-                    match server.rehash_service().reload_ssl().await {
-                        Ok(_) => {
-                            // If validation passes, reload the actual TLS configuration
-                            match server.reload_tls().await {
-                                Ok(_) => {
-                                    client.send_numeric(NumericReply::RplLocops, &["REHASH SSL: TLS configuration reloaded successfully"])?;
-                                    info!("REHASH SSL: TLS configuration reloaded by {}", user.nickname());
-                                }
-                                Err(e) => {
-                                    client.send_numeric(NumericReply::RplLocops, &[&format!("REHASH SSL: Failed to reload TLS configuration: {}", e)])?;
-                                    error!("REHASH SSL: Failed to reload TLS configuration by {}: {}", user.nickname(), e);
-                                }
-                            }
-                        }
-                        Err(e) => {
-                            client.send_numeric(NumericReply::RplLocops, &[&format!("REHASH SSL: Failed to validate TLS settings: {}", e)])?;
-                            error!("REHASH SSL: Failed to validate TLS settings by {}: {}", user.nickname(), e);
-                        }
-                    }
-                }
-                "MOTD" => {
-                    client.send_numeric(NumericReply::RplLocops, &["REHASH MOTD: Reloading MOTD file..."])?;
-                    match server.rehash_service().reload_motd().await {
-                        Ok(_) => {
-                            client.send_numeric(NumericReply::RplLocops, &["REHASH MOTD: MOTD configuration validated successfully"])?;
-                            client.send_numeric(NumericReply::RplLocops, &["REHASH MOTD: Note - MOTD reload requires server restart for full effect"])?;
-                            info!("REHASH MOTD: MOTD configuration validated by {}", user.nickname());
-                        }
-                        Err(e) => {
-                            client.send_numeric(NumericReply::RplLocops, &[&format!("REHASH MOTD: Failed to validate MOTD file: {}", e)])?;
-                            error!("REHASH MOTD: Failed to validate MOTD file by {}: {}", user.nickname(), e);
-                        }
-                    }
-                }
-                "MODULES" => {
-                    client.send_numeric(NumericReply::RplLocops, &["REHASH MODULES: Reloading all modules..."])?;
-                    match server.rehash_service().reload_modules().await {
-                        Ok(_) => {
-                            client.send_numeric(NumericReply::RplLocops, &["REHASH MODULES: Module configuration validated successfully"])?;
-                            client.send_numeric(NumericReply::RplLocops, &["REHASH MODULES: Note - Module reload requires server restart for full effect"])?;
-                            info!("REHASH MODULES: Module configuration validated by {}", user.nickname());
-                        }
-                        Err(e) => {
-                            client.send_numeric(NumericReply::RplLocops, &[&format!("REHASH MODULES: Failed to validate module configuration: {}", e)])?;
-                            error!("REHASH MODULES: Failed to validate module configuration by {}: {}", user.nickname(), e);
-                        }
-                    }
-                }
-                _ => {
-                    client.send_numeric(NumericReply::ErrUnknownCommand, &[parameter, "Unknown REHASH parameter. Use: SSL, MOTD, MODULES, or no parameter for main config"])?;
-                }
-            }
-        } else {
-            client.send_numeric(NumericReply::RplLocops, &["REHASH: Server reference not available"])?;
-        }
-        
-        Ok(())
-    }
-
     /// Handle LOCops command (Local Operator commands)
     async fn handle_locops(&self, client: &Client, user: &User, args: &[String]) -> Result<()> {
         if !user.is_operator() {
@@ -285,10 +190,6 @@ impl AdminModule {
             "CONFIG" => {
                 self.show_locops_config(client, user).await?;
             }
-            "REHASH" => {
-                // Note: Server reference not available in LOCops context
-                self.handle_rehash(client, user, &args[1..], None).await?;
-            }
             _ => {
                 client.send_numeric(NumericReply::ErrUnknownCommand, &[subcommand, "Unknown LOCops command"])?;
             }
@@ -306,7 +207,6 @@ impl AdminModule {
         client.send_numeric(NumericReply::RplLocops, &["  VERSION - Show server version"])?;
         client.send_numeric(NumericReply::RplLocops, &["  UPTIME - Show server uptime"])?;
         client.send_numeric(NumericReply::RplLocops, &["  CONFIG - Show server configuration"])?;
-        client.send_numeric(NumericReply::RplLocops, &["  REHASH - Reload configuration (SSL, MOTD, MODULES, or main config)"])?;
         client.send_numeric(NumericReply::RplEndOfLocops, &["End of LOCops commands"])?;
         
         Ok(())
@@ -496,15 +396,11 @@ impl Module for AdminModule {
                 self.handle_locops(client, user, &message.params).await?;
                 Ok(ModuleResult::Handled)
             }
-            MessageType::Custom(ref cmd) if cmd == "REHASH" => {
-                self.handle_rehash(client, user, &message.params, None).await?;
-                Ok(ModuleResult::Handled)
-            }
             _ => Ok(ModuleResult::NotHandled),
         }
     }
     
-    async fn handle_message_with_server(&mut self, client: &Client, message: &Message, server: Option<&rustircd_core::Server>, context: &ModuleContext) -> Result<ModuleResult> {
+    async fn handle_message_with_server(&mut self, client: &Client, message: &Message, _server: Option<&rustircd_core::Server>, context: &ModuleContext) -> Result<ModuleResult> {
         let user = match &client.user {
             Some(u) => u,
             None => return Ok(ModuleResult::NotHandled),
@@ -523,10 +419,6 @@ impl Module for AdminModule {
                 self.handle_locops(client, user, &message.params).await?;
                 Ok(ModuleResult::Handled)
             }
-            MessageType::Custom(ref cmd) if cmd == "REHASH" => {
-                self.handle_rehash(client, user, &message.params, server).await?;
-                Ok(ModuleResult::Handled)
-            }
             _ => Ok(ModuleResult::NotHandled),
         }
     }
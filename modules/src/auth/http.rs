@@ -2,13 +2,28 @@
 //! 
 //! This module provides HTTP-based authentication capabilities,
 //! allowing integration with external authentication services.
+//!
+//! Passwords are checked by a request to the service. With `jwt` set, a
+//! credential that is a JWT is instead verified locally against the
+//! service's published JSON Web Key Set, which is fetched and cached.
+//! Repeated transport failures open a circuit breaker so an outage fails
+//! fast instead of holding up every registration, and rejected credentials
+//! are remembered for a while so retries don't reach the service.
 
 use rustircd_core::{Result, Error, AuthProvider, AuthResult, AuthInfo, AuthRequest, AuthProviderCapabilities};
 use async_trait::async_trait;
+use jsonwebtoken::jwk::JwkSet;
+use jsonwebtoken::{DecodingKey, Validation};
+use std::collections::hash_map::RandomState;
 use std::collections::HashMap;
+use std::hash::BuildHasher;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 use tokio::sync::RwLock;
 
+/// Least time between JWKS fetches triggered by an unknown key id
+const MIN_JWKS_REFRESH: Duration = Duration::from_secs(60);
+
 /// HTTP authentication provider
 pub struct HttpAuthProvider {
     /// HTTP configuration
@@ -17,6 +32,23 @@ pub struct HttpAuthProvider {
     client: reqwest::Client,
     /// Authentication statistics
     stats: Arc<RwLock<HttpAuthStats>>,
+    /// Signing keys from `jwt.jwks_url` and when they were fetched
+    jwks: RwLock<Option<(JwkSet, Instant)>>,
+    /// Consecutive transport failures and how long the circuit stays open
+    circuit: parking_lot::Mutex<CircuitState>,
+    /// Rejected credentials, by keyed hash, until they may be retried
+    negative_cache: parking_lot::Mutex<HashMap<u64, Instant>>,
+    /// Hash key for `negative_cache`, random per process
+    hash_state: RandomState,
+}
+
+/// Circuit breaker state
+#[derive(Debug, Default)]
+struct CircuitState {
+    /// Transport failures since the last response from the service
+    consecutive_failures: u32,
+    /// While set and in the future, requests aren't attempted
+    open_until: Option<Instant>,
 }
 
 /// HTTP authentication configuration
@@ -42,6 +74,44 @@ pub struct HttpAuthConfig {
     pub password_field: String,
     /// Response format
     pub response_format: ResponseFormat,
+    /// Validate JWT bearer tokens locally instead of posting them
+    pub jwt: Option<JwtConfig>,
+    /// Consecutive transport failures that open the circuit (0 = never)
+    pub failure_threshold: u32,
+    /// Seconds the circuit stays open before the service is tried again
+    pub circuit_open_seconds: u64,
+    /// Seconds a rejected credential is refused without asking the service (0 = off)
+    pub negative_cache_seconds: u64,
+}
+
+/// JWT bearer token validation settings
+#[derive(Debug, Clone)]
+pub struct JwtConfig {
+    /// URL of the JSON Web Key Set tokens are signed with
+    pub jwks_url: String,
+    /// Required `iss` claim
+    pub issuer: Option<String>,
+    /// Required `aud` claim
+    pub audience: Option<String>,
+    /// Claim holding the account name
+    pub username_claim: String,
+    /// Seconds to keep fetched keys before fetching them again
+    pub jwks_cache_seconds: u64,
+    /// Clock skew allowed when checking `exp` and `nbf`, in seconds
+    pub leeway_seconds: u64,
+}
+
+impl Default for JwtConfig {
+    fn default() -> Self {
+        Self {
+            jwks_url: "http://localhost:8080/.well-known/jwks.json".to_string(),
+            issuer: None,
+            audience: None,
+            username_claim: "sub".to_string(),
+            jwks_cache_seconds: 3600,
+            leeway_seconds: 60,
+        }
+    }
 }
 
 /// HTTP methods
@@ -85,6 +155,10 @@ impl Default for HttpAuthConfig {
             username_field: "username".to_string(),
             password_field: "password".to_string(),
             response_format: ResponseFormat::Json,
+            jwt: None,
+            failure_threshold: 5,
+            circuit_open_seconds: 30,
+            negative_cache_seconds: 60,
         }
     }
 }
@@ -102,6 +176,10 @@ struct HttpAuthStats {
     timeout_errors: u64,
     /// Parse errors
     parse_errors: u64,
+    /// Requests refused because the circuit was open
+    circuit_rejections: u64,
+    /// Credentials refused from the negative cache
+    negative_cache_hits: u64,
 }
 
 impl HttpAuthProvider {
@@ -117,6 +195,10 @@ impl HttpAuthProvider {
             config,
             client,
             stats: Arc::new(RwLock::new(HttpAuthStats::default())),
+            jwks: RwLock::new(None),
+            circuit: parking_lot::Mutex::new(CircuitState::default()),
+            negative_cache: parking_lot::Mutex::new(HashMap::new()),
+            hash_state: RandomState::new(),
         }
     }
     
@@ -129,10 +211,209 @@ impl HttpAuthProvider {
             http_errors: stats.http_errors,
             timeout_errors: stats.timeout_errors,
             parse_errors: stats.parse_errors,
+            circuit_rejections: stats.circuit_rejections,
+            negative_cache_hits: stats.negative_cache_hits,
+        }
+    }
+    
+    /// Whether the circuit is open, so the service shouldn't be asked
+    fn circuit_open(&self) -> bool {
+        self.circuit.lock().open_until.is_some_and(|until| Instant::now() < until)
+    }
+    
+    /// The service answered, so close the circuit
+    fn record_success(&self) {
+        *self.circuit.lock() = CircuitState::default();
+    }
+    
+    /// Count a transport failure, opening the circuit at the threshold
+    fn record_failure(&self) {
+        let mut circuit = self.circuit.lock();
+        circuit.consecutive_failures += 1;
+        if self.config.failure_threshold > 0 && circuit.consecutive_failures >= self.config.failure_threshold {
+            if circuit.open_until.is_none_or(|until| Instant::now() >= until) {
+                tracing::warn!("HTTP auth service failed {} times in a row, pausing requests for {}s",
+                               circuit.consecutive_failures, self.config.circuit_open_seconds);
+            }
+            circuit.open_until = Some(Instant::now() + Duration::from_secs(self.config.circuit_open_seconds));
+        }
+    }
+    
+    /// Refuse with an error while the circuit is open, so fallback providers are tried
+    async fn check_circuit(&self) -> Result<()> {
+        if self.circuit_open() {
+            self.stats.write().await.circuit_rejections += 1;
+            return Err(Error::Auth("HTTP authentication service is unavailable".to_string()));
+        }
+        Ok(())
+    }
+    
+    /// Key for a credential in the negative cache
+    fn credential_key(&self, request: &AuthRequest) -> u64 {
+        self.hash_state.hash_one((&request.username, &request.credential))
+    }
+    
+    /// Whether the credential was rejected recently
+    fn recently_rejected(&self, key: u64) -> bool {
+        let mut cache = self.negative_cache.lock();
+        let now = Instant::now();
+        cache.retain(|_, until| *until > now);
+        cache.contains_key(&key)
+    }
+    
+    /// Remember a rejected credential for `negative_cache_seconds`
+    fn remember_rejection(&self, key: u64) {
+        if self.config.negative_cache_seconds > 0 {
+            let until = Instant::now() + Duration::from_secs(self.config.negative_cache_seconds);
+            self.negative_cache.lock().insert(key, until);
+        }
+    }
+    
+    /// Whether a credential has the shape of a JWT: three base64url parts
+    fn looks_like_jwt(credential: &str) -> bool {
+        let parts: Vec<&str> = credential.split('.').collect();
+        parts.len() == 3
+            && parts.iter().all(|part| !part.is_empty()
+                && part.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_'))
+    }
+    
+    /// The key set, fetched again once `jwks_cache_seconds` have passed or if asked to
+    async fn signing_keys(&self, jwt: &JwtConfig, refresh: bool) -> Result<JwkSet> {
+        if let Some((keys, fetched_at)) = self.jwks.read().await.as_ref() {
+            let age = fetched_at.elapsed();
+            let stale = age >= Duration::from_secs(jwt.jwks_cache_seconds);
+            if !stale && (!refresh || age < MIN_JWKS_REFRESH) {
+                return Ok(keys.clone());
+            }
+        }
+        
+        self.check_circuit().await?;
+        let fetched = match self.client.get(&jwt.jwks_url).send().await {
+            Ok(response) if response.status().is_success() => response.json::<JwkSet>().await
+                .map_err(|e| Error::Auth(format!("Invalid JWKS from {}: {}", jwt.jwks_url, e))),
+            Ok(response) => Err(Error::Auth(format!("JWKS fetch from {} failed: {}", jwt.jwks_url, response.status()))),
+            Err(e) => Err(Error::Auth(format!("JWKS fetch from {} failed: {}", jwt.jwks_url, e))),
+        };
+        match fetched {
+            Ok(keys) => {
+                self.record_success();
+                tracing::debug!("Fetched {} signing keys from {}", keys.keys.len(), jwt.jwks_url);
+                *self.jwks.write().await = Some((keys.clone(), Instant::now()));
+                Ok(keys)
+            }
+            Err(e) => {
+                self.record_failure();
+                // Keys that are merely old still verify tokens during an outage
+                if let Some((keys, _)) = self.jwks.read().await.as_ref() {
+                    tracing::warn!("{}; using cached keys", e);
+                    return Ok(keys.clone());
+                }
+                Err(e)
+            }
+        }
+    }
+    
+    /// Verify a JWT bearer token against the service's signing keys
+    async fn authenticate_jwt(&self, request: &AuthRequest, jwt: &JwtConfig) -> Result<AuthResult> {
+        let token = &request.credential;
+        let header = match jsonwebtoken::decode_header(token) {
+            Ok(header) => header,
+            Err(e) => return Ok(AuthResult::Failure(format!("Invalid token: {}", e))),
+        };
+        
+        // An unknown key id may mean the service rotated its keys
+        let mut keys = self.signing_keys(jwt, false).await?;
+        let find = |keys: &JwkSet| match &header.kid {
+            Some(kid) => keys.find(kid).cloned(),
+            None if keys.keys.len() == 1 => keys.keys.first().cloned(),
+            None => None,
+        };
+        if find(&keys).is_none() {
+            keys = self.signing_keys(jwt, true).await?;
+        }
+        let Some(jwk) = find(&keys) else {
+            return Ok(AuthResult::Failure("Token is signed with an unknown key".to_string()));
+        };
+        if jwk.common.key_algorithm.is_some_and(|alg| alg.to_string() != format!("{:?}", header.alg)) {
+            return Ok(AuthResult::Failure("Token algorithm doesn't match its key".to_string()));
+        }
+        let key = match DecodingKey::from_jwk(&jwk) {
+            Ok(key) => key,
+            Err(e) => return Ok(AuthResult::Failure(format!("Unusable signing key: {}", e))),
+        };
+        
+        let mut validation = Validation::new(header.alg);
+        validation.leeway = jwt.leeway_seconds;
+        validation.validate_nbf = true;
+        match &jwt.audience {
+            Some(audience) => validation.set_audience(&[audience]),
+            None => validation.validate_aud = false,
+        }
+        if let Some(issuer) = &jwt.issuer {
+            validation.set_issuer(&[issuer]);
         }
+        let claims = match jsonwebtoken::decode::<HashMap<String, serde_json::Value>>(token, &key, &validation) {
+            Ok(data) => data.claims,
+            Err(e) => return Ok(AuthResult::Failure(format!("Invalid token: {}", e))),
+        };
+        
+        let Some(username) = claims.get(&jwt.username_claim).and_then(|value| value.as_str()) else {
+            return Ok(AuthResult::Failure(format!("Token has no {} claim", jwt.username_claim)));
+        };
+        // A token only logs in to its own account
+        if !request.username.is_empty() && !request.username.eq_ignore_ascii_case(username) {
+            return Ok(AuthResult::Failure("Token was issued for another account".to_string()));
+        }
+        
+        let mut metadata = HashMap::new();
+        for claim in ["iss", "exp"] {
+            if let Some(value) = claims.get(claim) {
+                metadata.insert(claim.to_string(), value.to_string().trim_matches('"').to_string());
+            }
+        }
+        Ok(AuthResult::Success(AuthInfo {
+            username: username.to_string(),
+            realname: claims.get("name").and_then(|value| value.as_str()).map(str::to_string),
+            hostname: None,
+            metadata,
+            provider: "http".to_string(),
+            authenticated_at: chrono::Utc::now(),
+        }))
+    }
+    
+    /// Authenticate a password or token, consulting the caches and circuit first
+    async fn authenticate_request(&self, request: &AuthRequest) -> Result<AuthResult> {
+        let key = self.credential_key(request);
+        if self.recently_rejected(key) {
+            self.stats.write().await.negative_cache_hits += 1;
+            return Ok(AuthResult::Failure("Invalid credentials".to_string()));
+        }
+        
+        let result = match &self.config.jwt {
+            Some(jwt) if Self::looks_like_jwt(&request.credential) => self.authenticate_jwt(request, jwt).await?,
+            _ => {
+                self.check_circuit().await?;
+                self.authenticate_http_user(request).await?
+            }
+        };
+        
+        let mut stats = self.stats.write().await;
+        match &result {
+            AuthResult::Success(_) => stats.successful += 1,
+            AuthResult::Failure(reason) => {
+                stats.failed += 1;
+                tracing::info!("HTTP authentication of '{}' failed: {}", request.username, reason);
+                self.remember_rejection(key);
+            }
+            _ => {}
+        }
+        Ok(result)
     }
     
     /// Authenticate user via HTTP
+    ///
+    /// The service being unreachable is an error, so fallback providers get
+    /// a turn; a rejection from the service is a failure.
     async fn authenticate_http_user(&self, request: &AuthRequest) -> Result<AuthResult> {
         tracing::info!("Authenticating user '{}' via HTTP service", request.username);
         
@@ -169,15 +450,16 @@ impl HttpAuthProvider {
         
         // Send request
         match req.send().await {
+            Ok(response) if response.status().is_server_error() => {
+                self.record_failure();
+                self.stats.write().await.http_errors += 1;
+                Err(Error::Auth(format!("HTTP authentication service error: {}", response.status())))
+            }
             Ok(response) => {
+                self.record_success();
                 if response.status().is_success() {
-                    match self.parse_auth_response(response).await {
-                        Ok(auth_info) => {
-                            let mut stats = self.stats.write().await;
-                            stats.successful += 1;
-                            
-                            Ok(AuthResult::Success(auth_info))
-                        }
+                    match self.parse_auth_response(request, response).await {
+                        Ok(auth_info) => Ok(AuthResult::Success(auth_info)),
                         Err(e) => {
                             let mut stats = self.stats.write().await;
                             stats.parse_errors += 1;
@@ -186,13 +468,11 @@ impl HttpAuthProvider {
                         }
                     }
                 } else {
-                    let mut stats = self.stats.write().await;
-                    stats.failed += 1;
-                    
                     Ok(AuthResult::Failure(format!("HTTP error: {}", response.status())))
                 }
             }
             Err(e) => {
+                self.record_failure();
                 let mut stats = self.stats.write().await;
                 if e.is_timeout() {
                     stats.timeout_errors += 1;
@@ -200,23 +480,29 @@ impl HttpAuthProvider {
                     stats.http_errors += 1;
                 }
                 
-                Ok(AuthResult::Failure(format!("Request failed: {}", e)))
+                Err(Error::Auth(format!("HTTP authentication request failed: {}", e)))
             }
         }
     }
     
     /// Parse authentication response
-    async fn parse_auth_response(&self, response: reqwest::Response) -> Result<AuthInfo> {
+    async fn parse_auth_response(&self, request: &AuthRequest, response: reqwest::Response) -> Result<AuthInfo> {
         let text = response.text().await
             .map_err(|e| Error::Auth(format!("HTTP request failed: {}", e)))?;
         
         match self.config.response_format {
             ResponseFormat::Json => {
-                // In practice, use a proper JSON library
-                // For now, we'll create a simple response
+                // `{"success": false}` or `{"authenticated": false}` rejects;
+                // `username` and `realname` are used when present
+                let body: serde_json::Value = serde_json::from_str(&text)
+                    .map_err(|e| Error::Auth(format!("Invalid JSON response: {}", e)))?;
+                if ["success", "authenticated"].iter().any(|field| body.get(field) == Some(&serde_json::Value::Bool(false))) {
+                    return Err(Error::Auth("Authentication failed".to_string()));
+                }
+                let field = |name: &str| body.get(name).and_then(|value| value.as_str()).map(str::to_string);
                 Ok(AuthInfo {
-                    username: "http_user".to_string(),
-                    realname: Some("HTTP Authenticated User".to_string()),
+                    username: field("username").unwrap_or_else(|| request.username.clone()),
+                    realname: field("realname"),
                     hostname: None,
                     metadata: HashMap::new(),
                     provider: "http".to_string(),
@@ -291,20 +577,21 @@ impl AuthProvider for HttpAuthProvider {
     }
     
     async fn is_available(&self) -> bool {
-        // Try to ping the authentication service
-        match self.client
-            .get(&self.config.base_url)
-            .timeout(std::time::Duration::from_secs(5))
-            .send()
-            .await
-        {
-            Ok(_) => true,
-            Err(_) => false,
-        }
+        // Failures are tracked per request; pinging here would double every login's latency
+        !self.circuit_open()
     }
     
     async fn authenticate(&self, request: &AuthRequest) -> Result<AuthResult> {
-        self.authenticate_http_user(request).await
+        // Bound the whole exchange, including any JWKS fetch
+        let timeout = Duration::from_secs(self.config.timeout_seconds);
+        match tokio::time::timeout(timeout, self.authenticate_request(request)).await {
+            Ok(result) => result,
+            Err(_) => {
+                self.record_failure();
+                self.stats.write().await.timeout_errors += 1;
+                Err(Error::Auth("HTTP authentication timed out".to_string()))
+            }
+        }
     }
     
     async fn validate(&self, auth_info: &AuthInfo) -> Result<bool> {
@@ -325,3 +612,117 @@ impl AuthProvider for HttpAuthProvider {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use base64::{engine::general_purpose, Engine as _};
+    use jsonwebtoken::{EncodingKey, Header};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    /// Serve every request with the same status and body, counting requests
+    async fn serve(status: &'static str, body: String) -> (String, Arc<AtomicUsize>) {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let requests = Arc::new(AtomicUsize::new(0));
+        let counter = requests.clone();
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                counter.fetch_add(1, Ordering::SeqCst);
+                let mut buf = [0u8; 4096];
+                let _ = stream.read(&mut buf).await;
+                let response = format!(
+                    "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    status, body.len(), body,
+                );
+                let _ = stream.write_all(response.as_bytes()).await;
+            }
+        });
+        (url, requests)
+    }
+
+    fn request(username: &str, credential: &str) -> AuthRequest {
+        AuthRequest {
+            username: username.to_string(),
+            credential: credential.to_string(),
+            authzid: None,
+            client_info: rustircd_core::ClientInfo {
+                id: uuid::Uuid::new_v4(),
+                ip: "127.0.0.1".to_string(),
+                hostname: None,
+                secure: true,
+            },
+            context: HashMap::new(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_jwt_validation_and_jwks_cache() {
+        let secret = b"an example signing secret of 32b";
+        let jwks = serde_json::json!({"keys": [{
+            "kty": "oct", "kid": "k1", "alg": "HS256",
+            "k": general_purpose::URL_SAFE_NO_PAD.encode(secret),
+        }]});
+        let (url, requests) = serve("200 OK", jwks.to_string()).await;
+        let provider = HttpAuthProvider::new(HttpAuthConfig {
+            jwt: Some(JwtConfig {
+                jwks_url: format!("{}/jwks.json", url),
+                issuer: Some("https://auth.example.com".to_string()),
+                audience: Some("irc".to_string()),
+                ..JwtConfig::default()
+            }),
+            ..HttpAuthConfig::default()
+        });
+
+        let token = |sub: &str, aud: &str| {
+            let mut header = Header::new(jsonwebtoken::Algorithm::HS256);
+            header.kid = Some("k1".to_string());
+            let claims = serde_json::json!({
+                "sub": sub, "aud": aud, "iss": "https://auth.example.com",
+                "exp": chrono::Utc::now().timestamp() + 300,
+            });
+            jsonwebtoken::encode(&header, &claims, &EncodingKey::from_secret(secret)).unwrap()
+        };
+
+        match provider.authenticate(&request("alice", &token("alice", "irc"))).await.unwrap() {
+            AuthResult::Success(info) => {
+                assert_eq!(info.username, "alice");
+                assert_eq!(info.metadata.get("iss").map(String::as_str), Some("https://auth.example.com"));
+            }
+            other => panic!("expected success, got {:?}", other),
+        }
+        assert!(matches!(provider.authenticate(&request("alice", &token("alice", "web"))).await.unwrap(), AuthResult::Failure(_)));
+        assert!(matches!(provider.authenticate(&request("alice", &token("bob", "irc"))).await.unwrap(), AuthResult::Failure(_)));
+        // The keys were fetched once
+        assert_eq!(requests.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_circuit_breaker_and_negative_cache() {
+        // Nothing listens on a port we bound and released
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let dead_url = format!("http://{}", listener.local_addr().unwrap());
+        drop(listener);
+        let provider = HttpAuthProvider::new(HttpAuthConfig {
+            base_url: dead_url,
+            failure_threshold: 2,
+            ..HttpAuthConfig::default()
+        });
+        assert!(provider.authenticate(&request("alice", "secret")).await.is_err());
+        assert!(provider.is_available().await);
+        assert!(provider.authenticate(&request("alice", "secret")).await.is_err());
+        assert!(!provider.is_available().await);
+        assert!(provider.authenticate(&request("alice", "secret")).await.is_err());
+        assert_eq!(provider.get_stats().await.circuit_rejections, 1);
+
+        // A rejection is remembered, so the retry never reaches the service
+        let (url, requests) = serve("401 Unauthorized", "{}".to_string()).await;
+        let provider = HttpAuthProvider::new(HttpAuthConfig { base_url: url, ..HttpAuthConfig::default() });
+        assert!(matches!(provider.authenticate(&request("alice", "wrong")).await.unwrap(), AuthResult::Failure(_)));
+        assert!(matches!(provider.authenticate(&request("alice", "wrong")).await.unwrap(), AuthResult::Failure(_)));
+        assert_eq!(requests.load(Ordering::SeqCst), 1);
+        assert_eq!(provider.get_stats().await.negative_cache_hits, 1);
+        assert!(provider.is_available().await);
+    }
+}
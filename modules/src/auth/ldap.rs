@@ -1,19 +1,32 @@
 //! LDAP authentication provider
-//! 
+//!
 //! This module provides LDAP authentication capabilities for the IRC daemon.
+//!
+//! A login searches for the user's entry (bound as `bind_dn` when set) and
+//! then binds as that entry with the given password. Group memberships read
+//! from `group_attribute` are matched against `group_mappings`, which can
+//! make the user an operator or move them to another connection class when
+//! they register. Connections, plain, LDAPS or STARTTLS, are pooled and
+//! replaced when they break.
 
-use rustircd_core::{Result, Error, AuthProvider, AuthResult, AuthInfo, AuthRequest, AuthProviderCapabilities};
+use rustircd_core::config::OperatorFlag;
+use rustircd_core::{Result, Error, AuthProvider, AuthResult, AuthInfo, AuthRequest, AuthProviderCapabilities, AUTH_META_CLASS, AUTH_META_OPER_FLAGS};
 use async_trait::async_trait;
+use ldap3::{Ldap, LdapConnAsync, LdapConnSettings, LdapError, Scope, SearchEntry};
 use std::collections::HashMap;
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::RwLock;
 
+/// LDAP result code for a bind with the wrong password
+const INVALID_CREDENTIALS: u32 = 49;
+
 /// LDAP authentication provider
 pub struct LdapAuthProvider {
     /// LDAP server configuration
     config: LdapConfig,
-    /// Connection pool
-    connections: Arc<RwLock<Vec<LdapConnection>>>,
+    /// Idle connections, bound as nobody in particular
+    connections: parking_lot::Mutex<Vec<Ldap>>,
     /// Authentication statistics
     stats: Arc<RwLock<LdapStats>>,
 }
@@ -33,12 +46,33 @@ pub struct LdapConfig {
     pub bind_password: Option<String>,
     /// User search filter template
     pub user_filter: String,
-    /// Whether to use TLS
+    /// Whether to use TLS (LDAPS)
     pub use_tls: bool,
+    /// Upgrade a plain connection with STARTTLS
+    pub starttls: bool,
+    /// Whether to verify the server's certificate
+    pub verify_tls: bool,
     /// Connection timeout in seconds
     pub timeout_seconds: u64,
     /// Maximum connections in pool
     pub max_connections: usize,
+    /// Attribute of the user entry listing their groups
+    pub group_attribute: String,
+    /// Attribute used as the user's real name
+    pub realname_attribute: String,
+    /// Privileges granted to members of groups
+    pub group_mappings: Vec<LdapGroupMapping>,
+}
+
+/// What members of an LDAP group get on login
+#[derive(Debug, Clone)]
+pub struct LdapGroupMapping {
+    /// Group DN, or just its common name, e.g. `cn=ircops,ou=groups,dc=example,dc=com` or `ircops`
+    pub group: String,
+    /// Operator flags to grant; empty grants none
+    pub oper_flags: Vec<OperatorFlag>,
+    /// Connection class to put members in
+    pub class: Option<String>,
 }
 
 impl Default for LdapConfig {
@@ -51,47 +85,85 @@ impl Default for LdapConfig {
             bind_password: None,
             user_filter: "(uid={username})".to_string(),
             use_tls: false,
+            starttls: false,
+            verify_tls: true,
             timeout_seconds: 30,
             max_connections: 10,
+            group_attribute: "memberOf".to_string(),
+            realname_attribute: "cn".to_string(),
+            group_mappings: Vec::new(),
         }
     }
 }
 
-/// LDAP connection
-#[derive(Debug)]
-struct LdapConnection {
-    /// Connection ID
-    id: uuid::Uuid,
-    /// Connection state
-    state: LdapConnectionState,
-    /// Last used timestamp
-    last_used: chrono::DateTime<chrono::Utc>,
+impl LdapConfig {
+    /// URL of the server, `ldaps://` when `use_tls` is set
+    pub fn url(&self) -> String {
+        let scheme = if self.use_tls { "ldaps" } else { "ldap" };
+        format!("{}://{}:{}", scheme, self.hostname, self.port)
+    }
+
+    /// The search filter for a username, escaped so it can't change the filter
+    pub fn filter_for(&self, username: &str) -> String {
+        self.user_filter.replace("{username}", &ldap3::ldap_escape(username))
+    }
+
+    /// Operator flags and class for someone in `groups`
+    ///
+    /// Flags from every matching mapping are combined; the first matching
+    /// mapping with a class decides the class.
+    pub fn privileges_for(&self, groups: &[String]) -> (Vec<OperatorFlag>, Option<String>) {
+        let mut flags = Vec::new();
+        let mut class = None;
+        for mapping in self.group_mappings.iter().filter(|mapping| groups.iter().any(|group| group_matches(group, &mapping.group))) {
+            for flag in &mapping.oper_flags {
+                if !flags.contains(flag) {
+                    flags.push(*flag);
+                }
+            }
+            if class.is_none() {
+                class = mapping.class.clone();
+            }
+        }
+        (flags, class)
+    }
 }
 
-/// LDAP connection state
-#[derive(Debug, Clone, PartialEq, Eq)]
-enum LdapConnectionState {
-    /// Connected and ready
-    Ready,
-    /// Connected and bound
-    Bound,
-    /// Disconnected
-    Disconnected,
-    /// Error state
-    Error,
+/// Whether a group DN is the configured group, given as a DN or a bare CN
+fn group_matches(group_dn: &str, wanted: &str) -> bool {
+    if wanted.contains('=') {
+        return group_dn.eq_ignore_ascii_case(wanted);
+    }
+    group_dn.split(',').next()
+        .and_then(|rdn| rdn.split_once('='))
+        .is_some_and(|(attr, value)| attr.trim().eq_ignore_ascii_case("cn") && value.trim().eq_ignore_ascii_case(wanted))
 }
 
 /// LDAP statistics
 #[derive(Debug, Default)]
-struct LdapStats {
+pub struct LdapStats {
     /// Successful authentications
-    successful: u64,
+    pub successful: u64,
     /// Failed authentications
-    failed: u64,
+    pub failed: u64,
     /// Connection errors
-    connection_errors: u64,
+    pub connection_errors: u64,
     /// Search errors
-    search_errors: u64,
+    pub search_errors: u64,
+}
+
+/// Why a login attempt on one connection didn't succeed
+enum Attempt {
+    /// The directory answered and the login is refused
+    Rejected(String),
+    /// The connection failed; another may work
+    Broken(LdapError),
+}
+
+impl From<LdapError> for Attempt {
+    fn from(e: LdapError) -> Self {
+        Attempt::Broken(e)
+    }
 }
 
 impl LdapAuthProvider {
@@ -99,11 +171,11 @@ impl LdapAuthProvider {
     pub fn new(config: LdapConfig) -> Self {
         Self {
             config,
-            connections: Arc::new(RwLock::new(Vec::new())),
+            connections: parking_lot::Mutex::new(Vec::new()),
             stats: Arc::new(RwLock::new(LdapStats::default())),
         }
     }
-    
+
     /// Get LDAP statistics
     pub async fn get_stats(&self) -> LdapStats {
         let stats = self.stats.read().await;
@@ -114,81 +186,140 @@ impl LdapAuthProvider {
             search_errors: stats.search_errors,
         }
     }
-    
+
     /// Authenticate user against LDAP
     async fn authenticate_ldap_user(&self, request: &AuthRequest) -> Result<AuthResult> {
-        tracing::info!("Authenticating user '{}' against LDAP server {}", 
+        tracing::info!("Authenticating user '{}' against LDAP server {}",
                       request.username, self.config.hostname);
-        
-        // In a real implementation, this would:
-        // 1. Get or create LDAP connection
-        // 2. Bind to LDAP server
-        // 3. Search for user
-        // 4. Attempt to bind as the user
-        // 5. Return authentication result
-        
-        // For now, we'll simulate the process
-        match self.simulate_ldap_auth(request).await {
-            Ok(auth_info) => {
-                let mut stats = self.stats.write().await;
-                stats.successful += 1;
-                
-                Ok(AuthResult::Success(auth_info))
+
+        // An empty password is an unauthenticated bind, which always succeeds
+        if request.username.is_empty() || request.credential.is_empty() {
+            self.stats.write().await.failed += 1;
+            return Ok(AuthResult::Failure("Empty username or password".to_string()));
+        }
+
+        // A pooled connection may have been closed by the server; retry once on a new one
+        let mut last_error = None;
+        for _ in 0..2 {
+            let mut ldap = match self.get_connection().await {
+                Ok(ldap) => ldap,
+                Err(e) => {
+                    self.stats.write().await.connection_errors += 1;
+                    return Err(e);
+                }
+            };
+            match self.try_login(&mut ldap, request).await {
+                Ok(auth_info) => {
+                    self.release_connection(ldap);
+                    self.stats.write().await.successful += 1;
+                    return Ok(AuthResult::Success(auth_info));
+                }
+                Err(Attempt::Rejected(reason)) => {
+                    self.release_connection(ldap);
+                    self.stats.write().await.failed += 1;
+                    return Ok(AuthResult::Failure(reason));
+                }
+                Err(Attempt::Broken(e)) => {
+                    tracing::warn!("LDAP connection to {} failed: {}", self.config.hostname, e);
+                    self.stats.write().await.connection_errors += 1;
+                    last_error = Some(e);
+                }
             }
+        }
+        let reason = last_error.map(|e| e.to_string()).unwrap_or_default();
+        Err(Error::Auth(format!("LDAP server {} is unavailable: {}", self.config.hostname, reason)))
+    }
+
+    /// Find the user's entry, bind as it, and read its groups
+    async fn try_login(&self, ldap: &mut Ldap, request: &AuthRequest) -> std::result::Result<AuthInfo, Attempt> {
+        let timeout = Duration::from_secs(self.config.timeout_seconds);
+
+        // Connections are left bound as the last user, so bind as the service first
+        match (&self.config.bind_dn, &self.config.bind_password) {
+            (Some(dn), password) => {
+                ldap.with_timeout(timeout).simple_bind(dn, password.as_deref().unwrap_or("")).await?.success()?;
+            }
+            (None, _) => {
+                ldap.with_timeout(timeout).simple_bind("", "").await?.success()?;
+            }
+        }
+
+        let filter = self.config.filter_for(&request.username);
+        let attributes = [self.config.group_attribute.as_str(), self.config.realname_attribute.as_str()];
+        let (entries, _) = match ldap.with_timeout(timeout)
+            .search(&self.config.base_dn, Scope::Subtree, &filter, attributes.to_vec())
+            .await?
+            .success()
+        {
+            Ok(found) => found,
             Err(e) => {
-                let mut stats = self.stats.write().await;
-                stats.failed += 1;
-                
-                Ok(AuthResult::Failure(e.to_string()))
+                self.stats.write().await.search_errors += 1;
+                return Err(Attempt::Broken(e));
+            }
+        };
+        let mut entries = entries.into_iter().map(SearchEntry::construct);
+        let (Some(entry), None) = (entries.next(), entries.next()) else {
+            return Err(Attempt::Rejected("Invalid username or password".to_string()));
+        };
+
+        match ldap.with_timeout(timeout).simple_bind(&entry.dn, &request.credential).await?.success() {
+            Ok(_) => {}
+            Err(LdapError::LdapResult { result }) if result.rc == INVALID_CREDENTIALS => {
+                return Err(Attempt::Rejected("Invalid username or password".to_string()));
             }
+            Err(e) => return Err(Attempt::Broken(e)),
         }
-    }
-    
-    /// Simulate LDAP authentication (placeholder implementation)
-    async fn simulate_ldap_auth(&self, request: &AuthRequest) -> Result<AuthInfo> {
-        // This is a placeholder implementation
-        // In practice, you would use an LDAP library like ldap3 or similar
-        
-        // Simulate network delay
-        tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
-        
-        // Basic validation
-        if request.username.is_empty() || request.credential.is_empty() {
-            return Err(Error::Auth("Empty username or password".to_string()));
-        }
-        
-        // Simulate LDAP search and bind
-        // In practice, this would:
-        // 1. Connect to LDAP server
-        // 2. Bind with service account (if configured)
-        // 3. Search for user with user_filter
-        // 4. Attempt to bind as the found user
-        // 5. Extract user attributes
-        
+
+        let groups = entry.attrs.get(&self.config.group_attribute).cloned().unwrap_or_default();
+        let (flags, class) = self.config.privileges_for(&groups);
         let mut metadata = HashMap::new();
         metadata.insert("ldap_server".to_string(), self.config.hostname.clone());
-        metadata.insert("ldap_base_dn".to_string(), self.config.base_dn.clone());
-        
+        metadata.insert("ldap_dn".to_string(), entry.dn.clone());
+        if !flags.is_empty() {
+            let names: Vec<String> = flags.iter().map(|flag| format!("{:?}", flag)).collect();
+            metadata.insert(AUTH_META_OPER_FLAGS.to_string(), names.join(","));
+        }
+        if let Some(class) = class {
+            metadata.insert(AUTH_META_CLASS.to_string(), class);
+        }
+
         Ok(AuthInfo {
             username: request.username.clone(),
-            realname: Some(format!("{} (LDAP)", request.username)),
+            realname: entry.attrs.get(&self.config.realname_attribute).and_then(|values| values.first().cloned()),
             hostname: request.client_info.hostname.clone(),
             metadata,
             provider: "ldap".to_string(),
             authenticated_at: chrono::Utc::now(),
         })
     }
-    
-    /// Get or create LDAP connection
-    async fn get_connection(&self) -> Result<LdapConnection> {
-        // This would manage a connection pool to the LDAP server
-        // For now, we'll create a new connection each time
-        
-        Ok(LdapConnection {
-            id: uuid::Uuid::new_v4(),
-            state: LdapConnectionState::Ready,
-            last_used: chrono::Utc::now(),
-        })
+
+    /// Take an idle connection from the pool, or open a new one
+    async fn get_connection(&self) -> Result<Ldap> {
+        loop {
+            let Some(mut ldap) = self.connections.lock().pop() else {
+                break;
+            };
+            if !ldap.is_closed() {
+                return Ok(ldap);
+            }
+        }
+
+        let settings = LdapConnSettings::new()
+            .set_conn_timeout(Duration::from_secs(self.config.timeout_seconds))
+            .set_starttls(self.config.starttls && !self.config.use_tls)
+            .set_no_tls_verify(!self.config.verify_tls);
+        let (conn, ldap) = LdapConnAsync::with_settings(settings, &self.config.url()).await
+            .map_err(|e| Error::Auth(format!("Failed to connect to LDAP server {}: {}", self.config.url(), e)))?;
+        ldap3::drive!(conn);
+        Ok(ldap)
+    }
+
+    /// Return a working connection to the pool, unless it is full
+    fn release_connection(&self, ldap: Ldap) {
+        let mut connections = self.connections.lock();
+        if connections.len() < self.config.max_connections {
+            connections.push(ldap);
+        }
     }
 }
 
@@ -197,34 +328,45 @@ impl AuthProvider for LdapAuthProvider {
     fn name(&self) -> &str {
         "ldap"
     }
-    
+
     fn description(&self) -> &str {
         "LDAP authentication provider"
     }
-    
+
     async fn is_available(&self) -> bool {
-        // Check if we can establish a connection to the LDAP server
-        // For now, we'll assume it's always available
-        true
+        // A pooled connection proves the server was reachable; otherwise try one
+        if !self.connections.lock().is_empty() {
+            return true;
+        }
+        match self.get_connection().await {
+            Ok(ldap) => {
+                self.release_connection(ldap);
+                true
+            }
+            Err(e) => {
+                tracing::warn!("{}", e);
+                false
+            }
+        }
     }
-    
+
     async fn authenticate(&self, request: &AuthRequest) -> Result<AuthResult> {
         self.authenticate_ldap_user(request).await
     }
-    
+
     async fn validate(&self, auth_info: &AuthInfo) -> Result<bool> {
         // Validate that the LDAP authentication is still valid
         // This could re-query LDAP to check if the user still exists
-        
+
         if auth_info.provider != "ldap" {
             return Ok(false);
         }
-        
+
         // For now, we'll assume it's valid if it's recent
         let elapsed = chrono::Utc::now().signed_duration_since(auth_info.authenticated_at);
         Ok(elapsed.num_hours() < 24) // Valid for 24 hours
     }
-    
+
     fn capabilities(&self) -> AuthProviderCapabilities {
         AuthProviderCapabilities {
             password_auth: true,
@@ -235,3 +377,53 @@ impl AuthProvider for LdapAuthProvider {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_group_mappings() {
+        let config = LdapConfig {
+            group_mappings: vec![
+                LdapGroupMapping {
+                    group: "cn=ircops,ou=groups,dc=example,dc=com".to_string(),
+                    oper_flags: vec![OperatorFlag::GlobalOper, OperatorFlag::Override],
+                    class: Some("opers".to_string()),
+                },
+                LdapGroupMapping {
+                    group: "admins".to_string(),
+                    oper_flags: vec![OperatorFlag::GlobalOper, OperatorFlag::Administrator],
+                    class: Some("admins".to_string()),
+                },
+                LdapGroupMapping {
+                    group: "staff".to_string(),
+                    oper_flags: Vec::new(),
+                    class: Some("staff".to_string()),
+                },
+            ],
+            ..LdapConfig::default()
+        };
+
+        let groups = vec![
+            "CN=IRCops,OU=Groups,DC=example,DC=com".to_string(),
+            "cn=Admins,ou=groups,dc=example,dc=com".to_string(),
+        ];
+        let (flags, class) = config.privileges_for(&groups);
+        assert_eq!(flags, vec![OperatorFlag::GlobalOper, OperatorFlag::Override, OperatorFlag::Administrator]);
+        assert_eq!(class.as_deref(), Some("opers"));
+
+        let (flags, class) = config.privileges_for(&["cn=staff,ou=groups,dc=example,dc=com".to_string()]);
+        assert!(flags.is_empty());
+        assert_eq!(class.as_deref(), Some("staff"));
+        assert_eq!(config.privileges_for(&["cn=ircops-trainees,dc=example,dc=com".to_string()]), (Vec::new(), None));
+    }
+
+    #[test]
+    fn test_filter_and_url() {
+        let config = LdapConfig { use_tls: true, port: 636, ..LdapConfig::default() };
+        assert_eq!(config.url(), "ldaps://localhost:636");
+        assert_eq!(config.filter_for("alice"), "(uid=alice)");
+        assert_eq!(config.filter_for("*)(uid=*"), "(uid=\\2a\\29\\28uid=\\2a)");
+    }
+}
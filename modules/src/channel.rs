@@ -3,10 +3,13 @@
 use rustircd_core::{
     Module, module::ModuleResult, Client, Message, User, Error, Result,
     MessageType, Prefix, BroadcastSystem, BroadcastTarget, BroadcastPriority,
-    BroadcastMessage, Database, module::ModuleContext
+    BroadcastMessage, BatchConfig, ClientSenders, Database, module::ModuleContext, Ctcp, SnoMask, NumericReply, ServerEvent,
+    utils::string::wildcard_match, config::SafeListConfig, commands::CommandSpec
 };
 use async_trait::async_trait;
-use std::collections::{HashMap, HashSet};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::path::PathBuf;
 use std::sync::Arc;
 use tokio::sync::RwLock;
 use chrono::{DateTime, Utc};
@@ -37,10 +40,94 @@ pub enum ChannelMode {
     Exception = 'e' as isize,
     /// Invite mask
     Invite = 'I' as isize,
+    /// No CTCPs to the channel other than ACTION
+    NoCtcp = 'C' as isize,
+    /// Forward users who can't join to another channel
+    Forward = 'f' as isize,
+    /// Anyone may set +f pointing at this channel
+    FreeForward = 'F' as isize,
+    /// Don't accept users forwarded from other channels
+    NoForward = 'Q' as isize,
+    /// Quiet mask: matching users stay in the channel but can't speak
+    Quiet = 'q' as isize,
+    /// Join throttle: at most n joins every t seconds (+j n:t)
+    JoinThrottle = 'j' as isize,
+    /// Only users identified with services may join
+    RegisteredOnly = 'R' as isize,
+    /// Only users connected over TLS may join
+    SecureOnly = 'S' as isize,
+    /// Only IRC operators may join (set by opers only)
+    OperOnly = 'O' as isize,
+    /// Permanent: the channel survives with its state when the last user leaves (opers only)
+    Permanent = 'P' as isize,
+}
+
+/// Join throttle limit set with +j n:t
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct JoinThrottle {
+    /// Joins allowed per window
+    pub joins: usize,
+    /// Window length in seconds
+    pub seconds: i64,
+}
+
+impl JoinThrottle {
+    /// Parse an `n:t` parameter; both parts must be positive
+    pub fn parse(param: &str) -> Option<Self> {
+        let (joins, seconds) = param.split_once(':')?;
+        let joins = joins.parse().ok().filter(|n| *n > 0)?;
+        let seconds = seconds.parse().ok().filter(|t| *t > 0)?;
+        Some(Self { joins, seconds })
+    }
+}
+
+impl std::fmt::Display for JoinThrottle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.joins, self.seconds)
+    }
+}
+
+/// Member modes and their NAMES prefixes, highest rank first
+///
+/// Owner uses `y` rather than the traditional `q`, which is the quiet list here.
+pub const MEMBER_PREFIXES: [(char, char); 5] = [('y', '~'), ('a', '&'), ('o', '@'), ('h', '%'), ('v', '+')];
+
+/// Member ranks, as indexes into [`MEMBER_PREFIXES`]
+const RANK_OWNER: usize = 0;
+const RANK_ADMIN: usize = 1;
+const RANK_OP: usize = 2;
+const RANK_HALFOP: usize = 3;
+
+/// Maximum length of an IRC line including the trailing CRLF
+const MAX_LINE_LENGTH: usize = 512;
+
+/// Maximum server name length assumed when sizing replies
+const MAX_SERVER_NAME_LENGTH: usize = 63;
+
+/// A list mode's masks with the builders for its entry and end-of-list replies
+type ListModeReplies<'a> = (&'a HashSet<String>, fn(&str, &str, &str) -> Message, fn(&str, &str) -> Message);
+
+/// Split NAMES entries into space-separated lines that fit in `budget` bytes
+fn wrap_names(names: &[String], budget: usize) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut line = String::new();
+    for name in names {
+        if !line.is_empty() && line.len() + 1 + name.len() > budget {
+            lines.push(std::mem::take(&mut line));
+        }
+        if !line.is_empty() {
+            line.push(' ');
+        }
+        line.push_str(name);
+    }
+    if !line.is_empty() {
+        lines.push(line);
+    }
+    lines
 }
 
 /// Channel member with modes
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ChannelMember {
     pub user_id: Uuid,
     pub modes: HashSet<char>,
@@ -54,14 +141,40 @@ impl ChannelMember {
         }
     }
     
+    /// Operators, admins and owners all count as channel operators
     pub fn is_operator(&self) -> bool {
-        self.modes.contains(&'o')
+        self.rank() <= RANK_OP
+    }
+    
+    pub fn is_halfop(&self) -> bool {
+        self.modes.contains(&'h')
     }
     
     pub fn is_voice(&self) -> bool {
         self.modes.contains(&'v')
     }
     
+    /// NAMES/WHO prefixes for this member, highest rank first
+    ///
+    /// Without multi-prefix only the highest prefix is returned.
+    pub fn prefixes(&self, multi_prefix: bool) -> String {
+        let prefixes = MEMBER_PREFIXES.iter()
+            .filter(|(mode, _)| self.modes.contains(mode))
+            .map(|(_, prefix)| *prefix);
+        if multi_prefix {
+            prefixes.collect()
+        } else {
+            prefixes.take(1).collect()
+        }
+    }
+    
+    /// Rank of the member's highest prefix (0 is highest, members without one sort last)
+    pub fn rank(&self) -> usize {
+        MEMBER_PREFIXES.iter()
+            .position(|(mode, _)| self.modes.contains(mode))
+            .unwrap_or(MEMBER_PREFIXES.len())
+    }
+    
     pub fn add_mode(&mut self, mode: char) {
         self.modes.insert(mode);
     }
@@ -72,7 +185,9 @@ impl ChannelMember {
 }
 
 /// Channel information and state
-#[derive(Debug, Clone)]
+///
+/// Permanent channels are saved without their members.
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Channel {
     /// Unique channel ID
     pub id: Uuid,
@@ -91,6 +206,7 @@ pub struct Channel {
     /// User limit
     pub user_limit: Option<usize>,
     /// Channel members
+    #[serde(skip)]
     pub members: HashMap<Uuid, ChannelMember>,
     /// Ban masks
     pub ban_masks: HashSet<String>,
@@ -98,8 +214,17 @@ pub struct Channel {
     pub exception_masks: HashSet<String>,
     /// Invite masks
     pub invite_masks: HashSet<String>,
+    /// Quiet masks
+    pub quiet_masks: HashSet<String>,
     /// Channel creation time (for netsplit timestamp resolution)
     pub created_at: DateTime<Utc>,
+    /// Channel users are forwarded to when they can't join (+f)
+    pub forward: Option<String>,
+    /// Join rate limit (+j)
+    pub join_throttle: Option<JoinThrottle>,
+    /// Times of recent joins, for +j
+    #[serde(skip)]
+    pub recent_joins: VecDeque<DateTime<Utc>>,
 }
 
 impl Channel {
@@ -118,7 +243,11 @@ impl Channel {
             ban_masks: HashSet::new(),
             exception_masks: HashSet::new(),
             invite_masks: HashSet::new(),
+            quiet_masks: HashSet::new(),
             created_at: Utc::now(),
+            forward: None,
+            join_throttle: None,
+            recent_joins: VecDeque::new(),
         }
     }
     
@@ -144,6 +273,72 @@ impl Channel {
         modes.into_iter().collect()
     }
     
+    /// Parameters of the modes in `modes_string`, in the same order
+    pub fn mode_params(&self) -> Vec<String> {
+        self.modes_string().chars()
+            .filter_map(|mode| match mode {
+                'f' => self.forward.clone(),
+                'j' => self.join_throttle.map(|throttle| throttle.to_string()),
+                'k' => self.key.clone(),
+                'l' => self.user_limit.map(|limit| limit.to_string()),
+                _ => None,
+            })
+            .collect()
+    }
+    
+    /// Set a mode from a burst, taking its parameter if it has one
+    ///
+    /// Returns false for parameter modes given without a usable parameter.
+    pub fn apply_burst_mode(&mut self, mode: char, param: Option<&str>) -> bool {
+        match (mode, param) {
+            ('f', Some(forward)) => self.set_forward(Some(forward.to_string())),
+            ('j', Some(throttle)) => match JoinThrottle::parse(throttle) {
+                Some(throttle) => self.set_join_throttle(Some(throttle)),
+                None => return false,
+            },
+            ('k', Some(key)) => self.set_key(Some(key.to_string())),
+            ('l', Some(limit)) => match limit.parse() {
+                Ok(limit) => self.set_user_limit(Some(limit)),
+                Err(_) => return false,
+            },
+            ('f' | 'j' | 'k' | 'l', None) => return false,
+            _ => self.add_mode(mode),
+        }
+        true
+    }
+    
+    /// Drop all modes, lists and member statuses after losing a TS conflict
+    pub fn reset_modes(&mut self) {
+        self.modes.clear();
+        self.key = None;
+        self.user_limit = None;
+        self.forward = None;
+        self.join_throttle = None;
+        self.ban_masks.clear();
+        self.exception_masks.clear();
+        self.invite_masks.clear();
+        self.quiet_masks.clear();
+        for member in self.members.values_mut() {
+            member.modes.clear();
+        }
+    }
+    
+    /// Mask list for a list mode (b, e, I or q)
+    pub fn mask_list_mut(&mut self, mode: char) -> Option<&mut HashSet<String>> {
+        match mode {
+            'b' => Some(&mut self.ban_masks),
+            'e' => Some(&mut self.exception_masks),
+            'I' => Some(&mut self.invite_masks),
+            'q' => Some(&mut self.quiet_masks),
+            _ => None,
+        }
+    }
+    
+    /// Whether the channel should be destroyed: empty and not permanent (+P)
+    pub fn is_removable(&self) -> bool {
+        self.members.is_empty() && !self.has_mode('P')
+    }
+    
     /// Add a member to the channel
     pub fn add_member(&mut self, user_id: Uuid) -> Result<()> {
         if self.members.contains_key(&user_id) {
@@ -160,6 +355,15 @@ impl Channel {
         Ok(())
     }
     
+    /// Add a member, ignoring the user limit (oper override)
+    pub fn force_add_member(&mut self, user_id: Uuid) -> Result<()> {
+        if self.members.contains_key(&user_id) {
+            return Err(Error::User("User already in channel".to_string()));
+        }
+        self.members.insert(user_id, ChannelMember::new(user_id));
+        Ok(())
+    }
+    
     /// Remove a member from the channel
     pub fn remove_member(&mut self, user_id: &Uuid) {
         self.members.remove(user_id);
@@ -182,6 +386,34 @@ impl Channel {
             .unwrap_or(false)
     }
     
+    /// Check if a member may speak while the channel is moderated
+    pub fn can_speak_moderated(&self, user_id: &Uuid) -> bool {
+        self.member_rank(user_id) < MEMBER_PREFIXES.len()
+    }
+    
+    /// Rank of a member's highest status (see [`ChannelMember::rank`])
+    ///
+    /// Non-members rank below members without any status.
+    pub fn member_rank(&self, user_id: &Uuid) -> usize {
+        self.members.get(user_id)
+            .map(|member| member.rank())
+            .unwrap_or(MEMBER_PREFIXES.len() + 1)
+    }
+    
+    /// Grant or remove a status mode (y/a/o/h/v) on a member
+    pub fn set_member_status(&mut self, user_id: &Uuid, mode: char, enabled: bool) -> Result<()> {
+        if let Some(member) = self.members.get_mut(user_id) {
+            if enabled {
+                member.add_mode(mode);
+            } else {
+                member.remove_mode(mode);
+            }
+            Ok(())
+        } else {
+            Err(Error::User("User not in channel".to_string()))
+        }
+    }
+    
     /// Set user as operator
     pub fn set_operator(&mut self, user_id: &Uuid, is_op: bool) -> Result<()> {
         if let Some(member) = self.members.get_mut(user_id) {
@@ -240,6 +472,11 @@ impl Channel {
         self.has_mode('t')
     }
     
+    /// Check if channel blocks CTCPs
+    pub fn no_ctcp(&self) -> bool {
+        self.has_mode('C')
+    }
+    
     /// Check if channel is keyed
     pub fn is_keyed(&self) -> bool {
         self.has_mode('k')
@@ -270,6 +507,252 @@ impl Channel {
             self.remove_mode('l');
         }
     }
+    
+    /// Set forward channel
+    pub fn set_forward(&mut self, forward: Option<String>) {
+        if forward.is_some() {
+            self.add_mode('f');
+        } else {
+            self.remove_mode('f');
+        }
+        self.forward = forward;
+    }
+    
+    /// Set join throttle
+    pub fn set_join_throttle(&mut self, throttle: Option<JoinThrottle>) {
+        if throttle.is_some() {
+            self.add_mode('j');
+        } else {
+            self.remove_mode('j');
+            self.recent_joins.clear();
+        }
+        self.join_throttle = throttle;
+    }
+    
+    /// Check whether another join now would exceed +j
+    pub fn is_join_throttled(&self, now: DateTime<Utc>) -> bool {
+        let Some(throttle) = self.join_throttle else {
+            return false;
+        };
+        let window_start = now - chrono::Duration::seconds(throttle.seconds);
+        self.recent_joins.iter().filter(|at| **at > window_start).count() >= throttle.joins
+    }
+    
+    /// Record a join for +j, forgetting joins outside the window
+    pub fn record_join(&mut self, now: DateTime<Utc>) {
+        let Some(throttle) = self.join_throttle else {
+            return;
+        };
+        let window_start = now - chrono::Duration::seconds(throttle.seconds);
+        while self.recent_joins.front().is_some_and(|at| *at <= window_start) {
+            self.recent_joins.pop_front();
+        }
+        self.recent_joins.push_back(now);
+    }
+}
+
+/// A parsed ELIST filter for LIST
+///
+/// The LIST parameter is a comma-separated list of conditions:
+/// `>n`/`<n` (user count), `C>n`/`C<n` (created more/less than n minutes ago),
+/// `T>n`/`T<n` (topic changed more/less than n minutes ago), `!mask` (names
+/// not matching) and plain channel names or masks. All conditions must hold;
+/// a channel must match at least one plain mask if any are given.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ListFilter {
+    pub min_users: Option<usize>,
+    pub max_users: Option<usize>,
+    pub created_before_mins: Option<i64>,
+    pub created_within_mins: Option<i64>,
+    pub topic_before_mins: Option<i64>,
+    pub topic_within_mins: Option<i64>,
+    pub masks: Vec<String>,
+    pub excluded_masks: Vec<String>,
+}
+
+impl ListFilter {
+    /// ELIST token advertised in ISUPPORT
+    pub const ELIST: &'static str = "CMNTU";
+
+    /// Parse a LIST parameter
+    pub fn parse(param: &str) -> Self {
+        let mut filter = Self::default();
+        for item in param.split(',').filter(|item| !item.is_empty()) {
+            let (kind, rest) = match item.chars().next() {
+                Some(c @ ('C' | 'c' | 'T' | 't')) if item[1..].starts_with(['<', '>']) => {
+                    (Some(c.to_ascii_uppercase()), &item[1..])
+                }
+                _ => (None, item),
+            };
+            let comparison = rest.split_at_checked(1)
+                .filter(|(op, _)| *op == "<" || *op == ">")
+                .and_then(|(op, n)| n.parse::<i64>().ok().filter(|n| *n >= 0).map(|n| (op == ">", n)));
+
+            match (kind, comparison) {
+                (None, Some((true, n))) => filter.min_users = Some(n as usize),
+                (None, Some((false, n))) => filter.max_users = Some(n as usize),
+                (Some('C'), Some((true, n))) => filter.created_before_mins = Some(n),
+                (Some('C'), Some((false, n))) => filter.created_within_mins = Some(n),
+                (Some(_), Some((true, n))) => filter.topic_before_mins = Some(n),
+                (Some(_), Some((false, n))) => filter.topic_within_mins = Some(n),
+                _ => match item.strip_prefix('!') {
+                    Some(mask) => filter.excluded_masks.push(mask.to_string()),
+                    None => filter.masks.push(item.to_string()),
+                },
+            }
+        }
+        filter
+    }
+
+    /// Check whether a channel passes the filter
+    pub fn matches(&self, channel: &Channel, now: DateTime<Utc>) -> bool {
+        let users = channel.member_count();
+        if self.min_users.is_some_and(|n| users <= n) || self.max_users.is_some_and(|n| users >= n) {
+            return false;
+        }
+
+        let created_mins = (now - channel.created_at).num_minutes();
+        if self.created_before_mins.is_some_and(|n| created_mins <= n)
+            || self.created_within_mins.is_some_and(|n| created_mins >= n)
+        {
+            return false;
+        }
+
+        if self.topic_before_mins.is_some() || self.topic_within_mins.is_some() {
+            let Some(topic_time) = channel.topic_time else {
+                return false;
+            };
+            let topic_mins = (now - topic_time).num_minutes();
+            if self.topic_before_mins.is_some_and(|n| topic_mins <= n)
+                || self.topic_within_mins.is_some_and(|n| topic_mins >= n)
+            {
+                return false;
+            }
+        }
+
+        let name_matches = |mask: &String| wildcard_match(mask, &channel.name);
+        if self.excluded_masks.iter().any(name_matches) {
+            return false;
+        }
+        self.masks.is_empty() || self.masks.iter().any(name_matches)
+    }
+}
+
+/// Channel forwarding (+f) configuration
+#[derive(Debug, Clone)]
+pub struct ForwardConfig {
+    /// Allow +f and redirect users who can't join
+    pub enabled: bool,
+    /// Maximum number of forwards followed before giving up
+    pub max_hops: usize,
+}
+
+impl Default for ForwardConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            max_hops: 4,
+        }
+    }
+}
+
+/// Topic handling configuration
+#[derive(Debug, Clone)]
+pub struct TopicConfig {
+    /// Longest topic accepted; longer topics are truncated
+    pub max_length: usize,
+    /// JSON file topics are saved to, so recreated channels get their topic back
+    pub store_path: Option<PathBuf>,
+}
+
+impl Default for TopicConfig {
+    fn default() -> Self {
+        Self {
+            max_length: 390,
+            store_path: None,
+        }
+    }
+}
+
+/// Channels and invites carried across a module reload
+///
+/// Unlike the permanent channel store this keeps channel members.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct ChannelModuleState {
+    channels: HashMap<String, (Channel, Vec<ChannelMember>)>,
+    invite_list: HashMap<String, HashSet<String>>,
+}
+
+/// Topic kept in the topic store
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StoredTopic {
+    pub topic: String,
+    pub setter: String,
+    pub set_at: DateTime<Utc>,
+}
+
+/// Optional member status levels beyond op and voice
+#[derive(Debug, Clone)]
+pub struct PrefixConfig {
+    /// Channel owner (+y, ~)
+    pub owner: bool,
+    /// Channel admin (+a, &)
+    pub admin: bool,
+    /// Half-operator (+h, %)
+    pub halfop: bool,
+}
+
+impl Default for PrefixConfig {
+    fn default() -> Self {
+        Self {
+            owner: true,
+            admin: true,
+            halfop: true,
+        }
+    }
+}
+
+impl PrefixConfig {
+    /// Check whether a status mode is enabled
+    pub fn is_enabled(&self, mode: char) -> bool {
+        match mode {
+            'y' => self.owner,
+            'a' => self.admin,
+            'h' => self.halfop,
+            'o' | 'v' => true,
+            _ => false,
+        }
+    }
+    
+    /// PREFIX ISUPPORT token, e.g. `PREFIX=(yaohv)~&@%+`
+    pub fn isupport_token(&self) -> String {
+        let (modes, prefixes): (String, String) = MEMBER_PREFIXES.iter()
+            .filter(|(mode, _)| self.is_enabled(*mode))
+            .copied()
+            .unzip();
+        format!("PREFIX=({}){}", modes, prefixes)
+    }
+}
+
+/// Oper override configuration
+#[derive(Debug, Clone)]
+pub struct OperOverrideConfig {
+    /// Let operators with the Override flag bypass channel restrictions
+    pub enabled: bool,
+    /// Announce every override to operators subscribed to oper-action notices (+s o)
+    pub notify_opers: bool,
+    /// Channel that also receives override announcements (e.g. "&opers")
+    pub log_channel: Option<String>,
+}
+
+impl Default for OperOverrideConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            notify_opers: true,
+            log_channel: None,
+        }
+    }
 }
 
 /// Channel operations module
@@ -284,9 +767,27 @@ pub struct ChannelModule {
     /// Broadcast system for channel events
     broadcast_system: Arc<RwLock<BroadcastSystem>>,
     /// Database reference for user/channel tracking
-    database: Arc<RwLock<Database>>,
+    database: Arc<Database>,
     /// Invite list (nick -> set of channels they're invited to)
     invite_list: Arc<RwLock<HashMap<String, HashSet<String>>>>,
+    /// Oper override configuration
+    override_config: OperOverrideConfig,
+    /// LIST pacing and cooldown configuration
+    list_config: SafeListConfig,
+    /// When each client last used LIST, for the cooldown
+    last_list: Arc<parking_lot::Mutex<HashMap<Uuid, std::time::Instant>>>,
+    /// Channel forwarding configuration
+    forward_config: ForwardConfig,
+    /// Enabled member status levels
+    prefix_config: PrefixConfig,
+    /// Topic length and persistence configuration
+    topic_config: TopicConfig,
+    /// Persisted topics by channel name
+    topic_store: Arc<RwLock<HashMap<String, StoredTopic>>>,
+    /// Point users refused by +i at KNOCK
+    knock_hints: bool,
+    /// Delivers the broadcast queue every batch interval
+    flush_task: Option<tokio::task::JoinHandle<()>>,
 }
 
 impl ChannelModule {
@@ -313,6 +814,8 @@ impl ChannelModule {
                 477, // ERR_NOCHANMODES
                 478, // ERR_BANLISTFULL
                 482, // ERR_CHANOPRIVSNEEDED
+                489, // ERR_SECUREONLYCHAN
+                520, // ERR_OPERONLY
                 324, // RPL_CHANNELMODEIS
                 329, // RPL_CREATIONTIME
                 331, // RPL_NOTOPIC
@@ -332,15 +835,52 @@ impl ChannelModule {
                 366, // RPL_ENDOFNAMES
             ],
             broadcast_system: Arc::new(RwLock::new(BroadcastSystem::new())),
-            database: Arc::new(RwLock::new(Database::new(10000, 30))),
+            database: Arc::new(Database::new(10000, 30)),
             invite_list: Arc::new(RwLock::new(HashMap::new())),
+            override_config: OperOverrideConfig::default(),
+            list_config: SafeListConfig::default(),
+            last_list: Arc::new(parking_lot::Mutex::new(HashMap::new())),
+            forward_config: ForwardConfig::default(),
+            prefix_config: PrefixConfig::default(),
+            topic_config: TopicConfig::default(),
+            topic_store: Arc::new(RwLock::new(HashMap::new())),
+            knock_hints: false,
+            flush_task: None,
+        }
+    }
+
+    /// Deliver queued channel events every batch interval
+    ///
+    /// Events are coalesced per member, so a netjoin or a burst of mode
+    /// changes reaches each member in one write.
+    async fn start_broadcast_flush(&mut self) {
+        let config = BatchConfig::default();
+        let period = config.max_batch_delay;
+        {
+            let mut broadcast_system = self.broadcast_system.write().await;
+            if broadcast_system.batch_stats().await.is_none() {
+                broadcast_system.enable_batching(config);
+            }
+        }
+        if let Some(task) = self.flush_task.take() {
+            task.abort();
         }
+        let broadcast_system = self.broadcast_system.clone();
+        self.flush_task = Some(tokio::spawn(async move {
+            let mut interval = tokio::time::interval(period);
+            loop {
+                interval.tick().await;
+                if let Err(e) = broadcast_system.write().await.process_queues().await {
+                    tracing::warn!("Failed to deliver channel broadcasts: {}", e);
+                }
+            }
+        }));
     }
 
     /// Create a new channel module with external dependencies
     pub fn with_dependencies(
         broadcast_system: Arc<RwLock<BroadcastSystem>>,
-        database: Arc<RwLock<Database>>,
+        database: Arc<Database>,
     ) -> Self {
         Self {
             name: "channel".to_string(),
@@ -355,6 +895,80 @@ impl ChannelModule {
             broadcast_system,
             database,
             invite_list: Arc::new(RwLock::new(HashMap::new())),
+            override_config: OperOverrideConfig::default(),
+            list_config: SafeListConfig::default(),
+            last_list: Arc::new(parking_lot::Mutex::new(HashMap::new())),
+            forward_config: ForwardConfig::default(),
+            prefix_config: PrefixConfig::default(),
+            topic_config: TopicConfig::default(),
+            topic_store: Arc::new(RwLock::new(HashMap::new())),
+            knock_hints: false,
+            flush_task: None,
+        }
+    }
+
+    /// Set the oper override configuration
+    pub fn with_override_config(mut self, config: OperOverrideConfig) -> Self {
+        self.override_config = config;
+        self
+    }
+
+    /// Set the LIST pacing and cooldown configuration
+    pub fn with_list_config(mut self, config: SafeListConfig) -> Self {
+        self.list_config = config;
+        self
+    }
+
+    /// Set the channel forwarding configuration
+    pub fn with_forward_config(mut self, config: ForwardConfig) -> Self {
+        self.forward_config = config;
+        self
+    }
+
+    /// Set which member status levels are available
+    pub fn with_prefix_config(mut self, config: PrefixConfig) -> Self {
+        self.prefix_config = config;
+        self
+    }
+
+    /// Set the topic configuration, loading any previously stored topics
+    pub fn with_topic_config(mut self, config: TopicConfig) -> Self {
+        let stored = config.store_path.as_ref()
+            .map(Self::load_topics)
+            .unwrap_or_default();
+        self.topic_store = Arc::new(RwLock::new(stored));
+        self.topic_config = config;
+        self
+    }
+
+    /// Suggest KNOCK when a join fails on an invite-only channel
+    pub fn with_knock_hints(mut self, enabled: bool) -> Self {
+        self.knock_hints = enabled;
+        self
+    }
+
+    /// Channel state, for modules that work alongside this one (e.g. KNOCK)
+    pub fn channels_handle(&self) -> Arc<RwLock<HashMap<String, Channel>>> {
+        self.channels.clone()
+    }
+
+    /// Broadcast system used for channel traffic
+    pub fn broadcast_handle(&self) -> Arc<RwLock<BroadcastSystem>> {
+        self.broadcast_system.clone()
+    }
+
+    /// Read the topic store, starting empty if it is missing or unreadable
+    fn load_topics(path: &PathBuf) -> HashMap<String, StoredTopic> {
+        match std::fs::read_to_string(path) {
+            Ok(contents) => serde_json::from_str(&contents).unwrap_or_else(|e| {
+                tracing::warn!("Ignoring malformed topic store {}: {}", path.display(), e);
+                HashMap::new()
+            }),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => HashMap::new(),
+            Err(e) => {
+                tracing::warn!("Failed to read topic store {}: {}", path.display(), e);
+                HashMap::new()
+            }
         }
     }
 }
@@ -375,11 +989,19 @@ impl Module for ChannelModule {
     
     async fn init(&mut self) -> Result<()> {
         tracing::info!("Initializing channel module");
+        self.restore_permanent_channels().await;
+        self.start_broadcast_flush().await;
         Ok(())
     }
     
     async fn cleanup(&mut self) -> Result<()> {
         tracing::info!("Cleaning up channel module");
+        if let Some(task) = self.flush_task.take() {
+            task.abort();
+        }
+        let mut broadcast_system = self.broadcast_system.write().await;
+        broadcast_system.process_queues().await?;
+        broadcast_system.flush_batches(true).await;
         Ok(())
     }
 
@@ -387,18 +1009,22 @@ impl Module for ChannelModule {
         Ok(())
     }
     
-    async fn handle_message(&mut self, client: &Client, message: &Message, _context: &ModuleContext) -> Result<ModuleResult> {
+    async fn handle_message(&mut self, client: &Client, message: &Message, context: &ModuleContext) -> Result<ModuleResult> {
         match message.command {
             rustircd_core::MessageType::Join => {
-                self.handle_join(client, message).await?;
+                self.handle_join(client, message, context).await?;
                 Ok(ModuleResult::Handled)
             }
             rustircd_core::MessageType::Part => {
-                self.handle_part(client, message).await?;
+                self.handle_part(client, message, context).await?;
                 Ok(ModuleResult::Handled)
             }
+            // User modes are the server's to handle
+            rustircd_core::MessageType::Mode if message.params.first().is_some_and(|target| !self.is_valid_channel_name(target)) => {
+                Ok(ModuleResult::NotHandled)
+            }
             rustircd_core::MessageType::Mode => {
-                self.handle_mode(client, message).await?;
+                self.handle_mode(client, message, context).await?;
                 Ok(ModuleResult::Handled)
             }
             rustircd_core::MessageType::Topic => {
@@ -418,22 +1044,83 @@ impl Module for ChannelModule {
                 Ok(ModuleResult::Handled)
             }
             rustircd_core::MessageType::Kick => {
-                self.handle_kick(client, message).await?;
+                self.handle_kick(client, message, context).await?;
                 Ok(ModuleResult::Handled)
             }
+            rustircd_core::MessageType::PrivMsg | rustircd_core::MessageType::Notice => {
+                if let ModuleResult::HandledStop = self.check_channel_moderation(client, message, context).await? {
+                    return Ok(ModuleResult::HandledStop);
+                }
+                self.check_channel_ctcp(client, message).await
+            }
             _ => Ok(ModuleResult::NotHandled),
         }
     }
     
-    async fn handle_server_message(&mut self, _server: &str, _message: &Message, _context: &ModuleContext) -> Result<ModuleResult> {
-        Ok(ModuleResult::NotHandled)
+    async fn handle_server_message(&mut self, server: &str, message: &Message, context: &ModuleContext) -> Result<ModuleResult> {
+        match message.command {
+            MessageType::Kick => {
+                self.handle_remote_kick(server, message, context).await?;
+                Ok(ModuleResult::Handled)
+            }
+            MessageType::Join => {
+                self.handle_remote_join(server, message).await?;
+                // Core records the membership in the database and relays it
+                Ok(ModuleResult::NotHandled)
+            }
+            MessageType::Part => {
+                self.handle_remote_part(server, message).await?;
+                Ok(ModuleResult::NotHandled)
+            }
+            MessageType::ChannelBurst => {
+                self.handle_channel_burst(server, message, context).await?;
+                // Core still records the membership in the database
+                Ok(ModuleResult::NotHandled)
+            }
+            MessageType::Custom(ref cmd) if cmd == "BMASK" => {
+                self.handle_ban_mask_burst(server, message).await?;
+                Ok(ModuleResult::Handled)
+            }
+            MessageType::Custom(ref cmd) if cmd == "TB" => {
+                self.handle_topic_burst(server, message).await?;
+                Ok(ModuleResult::Handled)
+            }
+            MessageType::Custom(ref cmd) if cmd == "RENAME" => {
+                self.handle_remote_rename(server, message, context).await?;
+                Ok(ModuleResult::Handled)
+            }
+            MessageType::Mode if message.params.first().is_some_and(|target| target.starts_with('#') || target.starts_with('&')) => {
+                self.handle_remote_status_modes(server, message).await?;
+                // Core still relays the MODE to the rest of the network
+                Ok(ModuleResult::NotHandled)
+            }
+            _ => Ok(ModuleResult::NotHandled),
+        }
     }
     
     async fn handle_user_registration(&mut self, _user: &User, _context: &ModuleContext) -> Result<()> {
         Ok(())
     }
     
-    async fn handle_user_disconnection(&mut self, _user: &User, _context: &ModuleContext) -> Result<()> {
+    async fn handle_user_disconnection(&mut self, user: &User, _context: &ModuleContext) -> Result<()> {
+        // Core has already shown the QUIT to the channels; only drop the membership
+        let mut channels = self.channels.write().await;
+        channels.retain(|name, channel| {
+            if !channel.has_member(&user.id) {
+                return true;
+            }
+            channel.remove_member(&user.id);
+            if channel.is_removable() {
+                tracing::info!("Channel {} removed (empty)", name);
+                return false;
+            }
+            true
+        });
+        drop(channels);
+        self.broadcast_system.read().await.unregister_client(&user.id);
+        
+        // Invites belong to the nickname, which is free for someone else now
+        self.invite_list.write().await.remove(&user.nick);
         Ok(())
     }
     
@@ -441,11 +1128,20 @@ impl Module for ChannelModule {
     fn get_capabilities(&self) -> Vec<String> {
         vec![
             "message_handler".to_string(),
+            "server_message_handler".to_string(),
         ]
     }
     
     fn supports_capability(&self, capability: &str) -> bool {
-        capability == "message_handler"
+        capability == "message_handler" || capability == "server_message_handler"
+    }
+    
+    fn commands(&self) -> Vec<CommandSpec> {
+        vec![CommandSpec::new("RENAME", 2)]
+    }
+    
+    async fn handle_command(&mut self, client: &Client, message: &Message, context: &ModuleContext) -> Result<()> {
+        self.handle_rename(client, message, context).await
     }
     
     fn get_numeric_replies(&self) -> Vec<u16> {
@@ -462,19 +1158,73 @@ impl Module for ChannelModule {
         Ok(())
     }
 
-    async fn handle_stats_query(&mut self, _query: &str, _client_id: Uuid, _server: Option<&rustircd_core::Server>) -> Result<Vec<rustircd_core::module::ModuleStatsResponse>> {
-        // Channel module doesn't provide STATS queries
-        Ok(vec![])
+    async fn handle_stats_query(&mut self, query: &str, _client_id: Uuid, _server: Option<&rustircd_core::Server>) -> Result<Vec<rustircd_core::module::ModuleStatsResponse>> {
+        if query != "B" {
+            return Ok(vec![]);
+        }
+        // How much batching cut down on socket writes
+        let Some(stats) = self.broadcast_system.read().await.batch_stats().await else {
+            return Ok(vec![]);
+        };
+        Ok(vec![rustircd_core::module::ModuleStatsResponse::ModuleStats(
+            "BATCH".to_string(),
+            format!(
+                "{} messages in {} writes, {} writes saved",
+                stats.total_messages_batched, stats.total_batches_sent, stats.writes_saved()
+            ),
+        )])
     }
 
     fn get_stats_queries(&self) -> Vec<String> {
-        // Channel module doesn't provide STATS queries
-        vec![]
+        vec!["B".to_string()]
+    }
+
+    async fn server_burst(&self, target_server: &str) -> Vec<Message> {
+        self.channel_burst(target_server).await
+    }
+
+    async fn attach_client_senders(&mut self, client_senders: Arc<ClientSenders>) {
+        self.broadcast_system.write().await.set_client_senders(client_senders);
+    }
+
+    async fn attach_database(&mut self, database: Arc<Database>) {
+        self.database = database;
+    }
+
+    fn isupport_tokens(&self) -> Vec<String> {
+        vec![
+            self.prefix_config.isupport_token(),
+            format!("ELIST={}", ListFilter::ELIST),
+            "SAFELIST".to_string(),
+            "CHANMODES=beIq,k,fjl,CFOPQRSimnpst".to_string(),
+            "EXTBAN=$,aorsxz".to_string(),
+        ]
+    }
+
+    async fn export_state(&self) -> Result<Option<serde_json::Value>> {
+        let state = ChannelModuleState {
+            channels: self.channels.read().await.iter()
+                .map(|(name, channel)| (name.clone(), (channel.clone(), channel.members.values().cloned().collect())))
+                .collect(),
+            invite_list: self.invite_list.read().await.clone(),
+        };
+        Ok(Some(serde_json::to_value(state)?))
+    }
+
+    async fn import_state(&mut self, state: serde_json::Value) -> Result<()> {
+        let state: ChannelModuleState = serde_json::from_value(state)?;
+        let mut channels = self.channels.write().await;
+        for (name, (mut channel, members)) in state.channels {
+            channel.members = members.into_iter().map(|member| (member.user_id, member)).collect();
+            channels.insert(name, channel);
+        }
+        *self.invite_list.write().await = state.invite_list;
+        Ok(())
     }
 }
 
 impl ChannelModule {
-    async fn handle_join(&self, client: &Client, message: &Message) -> Result<()> {
+    async fn handle_join(&self, client: &Client, message: &Message, context: &ModuleContext) -> Result<()> {
         if !client.is_registered() {
             return Err(Error::User("Client not registered".to_string()));
         }
@@ -492,7 +1242,7 @@ impl ChannelModule {
         }
         
         // Get user from database
-        let database = self.database.read().await;
+        let database = &self.database;
         let user = database.get_user(&client.id)
             .ok_or_else(|| Error::User("User not found".to_string()))?;
         
@@ -509,63 +1259,69 @@ impl ChannelModule {
         
         let mut channels = self.channels.write().await;
         
+        // Restrictions bypassed through oper override
+        let can_override = self.can_override(&user);
+        let mut overridden = Vec::new();
+        
         // Get or create channel
-        let channel = if let Some(channel) = channels.get_mut(channel_name) {
+        let is_new = !channels.contains_key(channel_name);
+        let channel = if let Some(channel) = channels.get(channel_name) {
             // Check channel restrictions
-            if channel.is_invite_only() && !self.is_user_invited(&user.nick, channel_name).await {
-                return Err(Error::User("Cannot join channel (+i)".to_string()));
-            }
-            
-            if channel.is_keyed() {
-                if let Some(key) = key {
-                    if !channel.check_key(key) {
-                        return Err(Error::User("Cannot join channel (+k)".to_string()));
+            let failed = self.join_restrictions(&user, channel, key).await;
+            if !failed.is_empty() {
+                if !can_override {
+                    // Redirect to the forward channel (+f) if one accepts the user
+                    if let Some(forward) = self.forward_target(&channels, &user, &user_channels, channel_name).await {
+                        drop(channels);
+                        let _ = client.send(NumericReply::link_channel(&user.nick, channel_name, &forward));
+                        let forward_join = Message::new(MessageType::Join, vec![forward]);
+                        return Box::pin(self.handle_join(client, &forward_join, context)).await;
                     }
-                } else {
-                    return Err(Error::User("Cannot join channel (+k)".to_string()));
-                }
-            }
-            
-            // Check ban masks
-            if self.is_user_banned(&user, channel).await {
-                return Err(Error::User("Cannot join channel (+b)".to_string()));
-            }
-            
-            // Check user limit
-            if let Some(limit) = channel.user_limit {
-                if channel.member_count() >= limit {
-                    return Err(Error::User("Cannot join channel (+l)".to_string()));
+                    let _ = client.send(NumericReply::cannot_join(&user.nick, channel_name, failed[0]));
+                    if failed[0] == 'i' && self.knock_hints {
+                        let _ = client.send(Message::new(MessageType::Notice, vec![
+                            user.nick.clone(),
+                            format!("*** {} is invite-only; use /KNOCK {} <reason> to ask for an invite", channel_name, channel_name),
+                        ]));
+                    }
+                    return Err(Error::User(format!("Cannot join channel (+{})", failed[0])));
                 }
+                overridden = failed;
             }
             
             channel.clone()
         } else {
-            // Create new channel
-            let mut new_channel = Channel::new(channel_name.clone());
-            // First user becomes operator
-            new_channel.add_member(user.id)?;
-            new_channel.set_operator(&user.id, true)?;
-            new_channel.clone()
+            // Create new channel; the first user becomes operator below
+            let mut channel = Channel::new(channel_name.clone());
+            if let Some(stored) = self.topic_store.read().await.get(channel_name) {
+                channel.topic = Some(stored.topic.clone());
+                channel.topic_setter = Some(stored.setter.clone());
+                channel.topic_time = Some(stored.set_at);
+            }
+            channel
         };
         
         // Add user to channel
         let mut channel = channel;
-        channel.add_member(user.id)?;
+        if overridden.contains(&'l') {
+            channel.force_add_member(user.id)?;
+        } else {
+            channel.add_member(user.id)?;
+        }
         
-        // If this is a new channel, make the user an operator
-        if channel.member_count() == 1 {
+        // If this is a new channel, make the user an operator; an empty
+        // permanent channel doesn't hand out ops
+        if is_new {
             channel.set_operator(&user.id, true)?;
         }
+        channel.record_join(Utc::now());
         
         // Update channels
         channels.insert(channel_name.clone(), channel.clone());
         
         // Update database
         drop(channels);
-        drop(database);
-        
-        let database = self.database.write().await;
-        database.add_user_to_channel(&user.nick, channel_name)?;
+        self.database.add_user_to_channel(&user.nick, channel_name)?;
         
         // Remove from invite list if present
         self.remove_invite(&user.nick, channel_name).await;
@@ -582,7 +1338,7 @@ impl ChannelModule {
         );
         
         let broadcast = BroadcastMessage {
-            message: join_message,
+            message: join_message.clone(),
             target: BroadcastTarget::Channel(channel_name.clone()),
             sender: Some(user.id),
             priority: BroadcastPriority::Normal,
@@ -593,12 +1349,29 @@ impl ChannelModule {
         
         // Subscribe user to channel for future broadcasts
         broadcast_system.subscribe_to_channel(user.id, channel_name.clone());
+        drop(broadcast_system);
+        
+        // Other servers track the membership too
+        context.broadcast_to_servers(join_message).await?;
+        if is_new {
+            context.publish(ServerEvent::ChannelCreated { channel: channel_name.clone(), creator: user.nick.clone() });
+        }
+        
+        // The topic is sent on join only when one is set
+        for reply in self.topic_replies(&user.nick, &channel) {
+            let _ = client.send(reply);
+        }
+        
+        if !overridden.is_empty() {
+            let modes: String = overridden.into_iter().collect();
+            self.announce_override(context, &user, channel_name, &format!("joined through +{}", modes)).await?;
+        }
         
         tracing::info!("User {} joined channel {}", user.nick, channel_name);
         Ok(())
     }
     
-    async fn handle_part(&self, client: &Client, message: &Message) -> Result<()> {
+    async fn handle_part(&self, client: &Client, message: &Message, context: &ModuleContext) -> Result<()> {
         if !client.is_registered() {
             return Err(Error::User("Client not registered".to_string()));
         }
@@ -611,7 +1384,7 @@ impl ChannelModule {
         let reason = message.params.get(1).map(|s| s.as_str());
         
         // Get user from database
-        let database = self.database.read().await;
+        let database = &self.database;
         let user = database.get_user(&client.id)
             .ok_or_else(|| Error::User("User not found".to_string()))?;
         
@@ -636,10 +1409,7 @@ impl ChannelModule {
         
         // Update database
         drop(channels);
-        drop(database);
-        
-        let database = self.database.write().await;
-        database.remove_user_from_channel(&user.nick, channel_name)?;
+        self.database.remove_user_from_channel(&user.nick, channel_name)?;
         
         // Broadcast PART message to channel
         let mut part_params = vec![channel_name.clone()];
@@ -658,7 +1428,7 @@ impl ChannelModule {
         );
         
         let broadcast = BroadcastMessage {
-            message: part_message,
+            message: part_message.clone(),
             target: BroadcastTarget::Channel(channel_name.clone()),
             sender: Some(user.id),
             priority: BroadcastPriority::Normal,
@@ -669,9 +1439,12 @@ impl ChannelModule {
         
         // Unsubscribe user from channel
         broadcast_system.unsubscribe_from_channel(&user.id, channel_name);
+        drop(broadcast_system);
+        
+        context.broadcast_to_servers(part_message).await?;
         
         // If channel is empty, remove it
-        if channel.member_count() == 0 {
+        if channel.is_removable() {
             let mut channels = self.channels.write().await;
             channels.remove(channel_name);
             tracing::info!("Channel {} removed (empty)", channel_name);
@@ -681,7 +1454,7 @@ impl ChannelModule {
         Ok(())
     }
     
-    async fn handle_mode(&self, client: &Client, message: &Message) -> Result<()> {
+    async fn handle_mode(&self, client: &Client, message: &Message, context: &ModuleContext) -> Result<()> {
         if !client.is_registered() {
             return Err(Error::User("Client not registered".to_string()));
         }
@@ -693,13 +1466,13 @@ impl ChannelModule {
         let target = &message.params[0];
         
         // Get user from database
-        let database = self.database.read().await;
+        let database = &self.database;
         let user = database.get_user(&client.id)
             .ok_or_else(|| Error::User("User not found".to_string()))?;
         
         // Check if target is a channel
         if self.is_valid_channel_name(target) {
-            self.handle_channel_mode(&user, target, &message.params[1..]).await?;
+            self.handle_channel_mode(&user, target, &message.params[1..], context).await?;
         } else {
             // User mode - not implemented yet
             return Err(Error::User("User modes not implemented".to_string()));
@@ -708,7 +1481,7 @@ impl ChannelModule {
         Ok(())
     }
     
-    async fn handle_channel_mode(&self, user: &User, channel_name: &str, params: &[String]) -> Result<()> {
+    async fn handle_channel_mode(&self, user: &User, channel_name: &str, params: &[String], context: &ModuleContext) -> Result<()> {
         let mut channels = self.channels.write().await;
         
         // Get channel
@@ -716,8 +1489,10 @@ impl ChannelModule {
             .ok_or_else(|| Error::User("No such channel".to_string()))?
             .clone();
         
-        // Check if user is in the channel
-        if !channel.has_member(&user.id) {
+        // Check if user is in the channel; opers may toggle +O and +P from outside
+        let can_override = self.can_override(user);
+        let only_permanent = params.first().is_some_and(|modes| modes.chars().all(|c| matches!(c, '+' | '-' | 'O' | 'P')));
+        if !(channel.has_member(&user.id) || can_override || (user.is_operator && only_permanent)) {
             return Err(Error::User("You're not on that channel".to_string()));
         }
         
@@ -733,40 +1508,67 @@ impl ChannelModule {
             return Ok(());
         }
         
-        // Check if user is an operator
-        if !channel.is_operator(&user.id) {
-            return Err(Error::User("You're not channel operator".to_string()));
-        }
-        
         // Parse mode changes
         let mode_string = &params[0];
         let mode_params = &params[1..];
         
-        let (add_modes, remove_modes, mode_param_map) = self.parse_mode_string(mode_string, mode_params)?;
+        let (mut add_modes, mut remove_modes, mode_param_map) = self.parse_mode_string(mode_string, mode_params)?;
+        
+        // List modes given without a mask are queries, which don't need operator status
+        let is_query = |mode: &char| "beIq".contains(*mode) && !mode_param_map.contains_key(mode);
+        let mut queries: Vec<char> = add_modes.iter().chain(remove_modes.iter()).copied().filter(is_query).collect();
+        queries.dedup();
+        for mode in &queries {
+            for reply in self.list_mode_replies(&user.nick, &channel, *mode) {
+                self.send_reply_to_user(user.id, reply).await?;
+            }
+        }
+        add_modes.retain(|mode| !is_query(mode));
+        remove_modes.retain(|mode| !is_query(mode));
+        if add_modes.is_empty() && remove_modes.is_empty() {
+            return Ok(());
+        }
+        
+        // Permanence and oper-only are up to IRC operators, whatever their channel status
+        let oper_modes = |modes: &[char]| modes.iter().any(|mode| matches!(mode, 'O' | 'P'));
+        if (oper_modes(&add_modes) || oper_modes(&remove_modes)) && !user.is_operator {
+            self.send_reply_to_user(user.id, NumericReply::no_privileges()).await?;
+            return Err(Error::User("Permission denied".to_string()));
+        }
+        
+        // Check the setter's rank against every change
+        let setter_rank = channel.member_rank(&user.id);
+        let mut overriding = false;
+        for (mode, adding) in add_modes.iter().map(|m| (*m, true)).chain(remove_modes.iter().map(|m| (*m, false))) {
+            let mut required = Self::required_rank(mode);
+            // Only members of equal or higher rank may demote someone
+            if !adding && Self::is_status_mode(mode) {
+                if let Some(target_user) = match mode_param_map.get(&mode) {
+                    Some(nick) => self.get_user_by_nick(nick).await?,
+                    None => None,
+                } {
+                    required = required.min(channel.member_rank(&target_user.id));
+                }
+            }
+            if setter_rank > required {
+                overriding = true;
+            }
+        }
+        if overriding && !can_override {
+            return Err(Error::User("You're not channel operator".to_string()));
+        }
         
         let mut changes = Vec::new();
         
         // Apply mode changes
         for mode in &add_modes {
             match mode {
-                'o' => {
-                    if let Some(nick) = mode_param_map.get(&mode) {
-                        if let Some(target_user) = self.get_user_by_nick(nick).await? {
-                            if channel.has_member(&target_user.id) {
-                                channel.set_operator(&target_user.id, true)?;
-                                changes.push(format!("+o {}", nick));
-                            }
-                        }
-                    }
-                }
-                'v' => {
+                'y' | 'a' | 'o' | 'h' | 'v' => {
                     if let Some(nick) = mode_param_map.get(&mode) {
                         if let Some(target_user) = self.get_user_by_nick(nick).await? {
                             if channel.has_member(&target_user.id) {
-                                if let Some(member) = channel.members.get_mut(&target_user.id) {
-                                    member.add_mode('v');
-                                    changes.push(format!("+v {}", nick));
-                                }
+                                channel.set_member_status(&target_user.id, *mode, true)?;
+                                changes.push(format!("+{} {}", mode, nick));
                             }
                         }
                     }
@@ -803,7 +1605,26 @@ impl ChannelModule {
                         changes.push(format!("+I {}", invite_mask));
                     }
                 }
-                'i' | 'm' | 'n' | 'p' | 's' | 't' => {
+                'q' => {
+                    if let Some(quiet_mask) = mode_param_map.get(&mode) {
+                        channel.quiet_masks.insert(quiet_mask.clone());
+                        changes.push(format!("+q {}", quiet_mask));
+                    }
+                }
+                'f' => {
+                    if let Some(forward) = mode_param_map.get(&mode) {
+                        self.check_forward_target(user, &channel, forward, &channels, can_override)?;
+                        channel.set_forward(Some(forward.clone()));
+                        changes.push(format!("+f {}", forward));
+                    }
+                }
+                'j' => {
+                    if let Some(throttle) = mode_param_map.get(&mode).and_then(|param| JoinThrottle::parse(param)) {
+                        channel.set_join_throttle(Some(throttle));
+                        changes.push(format!("+j {}", throttle));
+                    }
+                }
+                'i' | 'm' | 'n' | 'p' | 's' | 't' | 'C' | 'F' | 'O' | 'P' | 'Q' | 'R' | 'S' => {
                     channel.add_mode(*mode);
                     changes.push(format!("+{}", mode));
                 }
@@ -813,24 +1634,12 @@ impl ChannelModule {
         
         for mode in &remove_modes {
             match mode {
-                'o' => {
-                    if let Some(nick) = mode_param_map.get(&mode) {
-                        if let Some(target_user) = self.get_user_by_nick(nick).await? {
-                            if channel.has_member(&target_user.id) {
-                                channel.set_operator(&target_user.id, false)?;
-                                changes.push(format!("-o {}", nick));
-                            }
-                        }
-                    }
-                }
-                'v' => {
+                'y' | 'a' | 'o' | 'h' | 'v' => {
                     if let Some(nick) = mode_param_map.get(&mode) {
                         if let Some(target_user) = self.get_user_by_nick(nick).await? {
                             if channel.has_member(&target_user.id) {
-                                if let Some(member) = channel.members.get_mut(&target_user.id) {
-                                    member.remove_mode('v');
-                                    changes.push(format!("-v {}", nick));
-                                }
+                                channel.set_member_status(&target_user.id, *mode, false)?;
+                                changes.push(format!("-{} {}", mode, nick));
                             }
                         }
                     }
@@ -861,7 +1670,21 @@ impl ChannelModule {
                         changes.push(format!("-I {}", invite_mask));
                     }
                 }
-                'i' | 'm' | 'n' | 'p' | 's' | 't' => {
+                'q' => {
+                    if let Some(quiet_mask) = mode_param_map.get(&mode) {
+                        channel.quiet_masks.remove(quiet_mask);
+                        changes.push(format!("-q {}", quiet_mask));
+                    }
+                }
+                'f' => {
+                    channel.set_forward(None);
+                    changes.push("-f".to_string());
+                }
+                'j' => {
+                    channel.set_join_throttle(None);
+                    changes.push("-j".to_string());
+                }
+                'i' | 'm' | 'n' | 'p' | 's' | 't' | 'C' | 'F' | 'O' | 'P' | 'Q' | 'R' | 'S' => {
                     channel.remove_mode(*mode);
                     changes.push(format!("-{}", mode));
                 }
@@ -869,8 +1692,14 @@ impl ChannelModule {
             }
         }
         
-        // Update channel
-        channels.insert(channel_name.to_string(), channel.clone());
+        // Update channel; an empty channel losing +P goes away
+        if channel.is_removable() {
+            channels.remove(channel_name);
+        } else {
+            channels.insert(channel_name.to_string(), channel.clone());
+        }
+        drop(channels);
+        self.sync_permanent_channel(&channel).await?;
         
         // Broadcast mode change to channel
         if !changes.is_empty() {
@@ -901,8 +1730,21 @@ impl ChannelModule {
                 priority: BroadcastPriority::Normal,
             };
             
+            let status_changed = add_modes.iter().chain(remove_modes.iter()).any(|mode| Self::is_status_mode(*mode));
+            let server_message = broadcast.message.clone();
+            
             let mut broadcast_system = self.broadcast_system.write().await;
             broadcast_system.queue_message(broadcast)?;
+            drop(broadcast_system);
+            
+            // Member statuses are network-wide, so other servers need them too
+            if status_changed {
+                context.broadcast_to_servers(server_message).await?;
+            }
+            
+            if overriding {
+                self.announce_override(context, user, channel_name, &format!("set mode {}", changes.join(" "))).await?;
+            }
         }
         
         tracing::info!("User {} changed modes on channel {}: {:?}", user.nick, channel_name, changes);
@@ -921,49 +1763,61 @@ impl ChannelModule {
         let channel_name = &message.params[0];
         
         // Get user from database
-        let database = self.database.read().await;
+        let database = &self.database;
         let user = database.get_user(&client.id)
             .ok_or_else(|| Error::User("User not found".to_string()))?;
         
-        // Check if user is in the channel
-        let user_channels = database.get_user_channels(&user.nick);
-        if !user_channels.contains(channel_name) {
-            return Err(Error::User("You're not on that channel".to_string()));
-        }
-        
         let mut channels = self.channels.write().await;
         
         // Get channel
         let mut channel = channels.get_mut(channel_name)
             .ok_or_else(|| Error::User("No such channel".to_string()))?
             .clone();
+        let is_member = channel.has_member(&user.id);
         
         // If no topic provided, show current topic
         if message.params.len() == 1 {
-            if let Some(ref topic) = channel.topic {
-                let topic_reply = self.topic(channel_name, topic);
-                self.send_reply_to_user(user.id, topic_reply).await?;
-                tracing::info!("User {} requested topic for channel {}", user.nick, channel_name);
-            } else {
-                let no_topic_reply = self.no_topic(channel_name);
-                self.send_reply_to_user(user.id, no_topic_reply).await?;
-                tracing::info!("User {} requested topic for channel {} (no topic set)", user.nick, channel_name);
+            // Topics of secret and private channels are only shown to members
+            if !is_member && (channel.has_mode('s') || channel.has_mode('p')) {
+                self.send_reply_to_user(user.id, NumericReply::not_on_channel(&user.nick, channel_name)).await?;
+                return Ok(());
             }
+            let replies = self.topic_replies(&user.nick, &channel);
+            if replies.is_empty() {
+                self.send_reply_to_user(user.id, NumericReply::no_topic(&user.nick, channel_name)).await?;
+            }
+            for reply in replies {
+                self.send_reply_to_user(user.id, reply).await?;
+            }
+            tracing::info!("User {} requested topic for channel {}", user.nick, channel_name);
+            return Ok(());
+        }
+        
+        if !is_member {
+            self.send_reply_to_user(user.id, NumericReply::not_on_channel(&user.nick, channel_name)).await?;
             return Ok(());
         }
         
         // Check if user has permission to set topic
-        if channel.topic_ops_only() && !channel.is_operator(&user.id) {
-            return Err(Error::User("You're not channel operator".to_string()));
+        if channel.topic_ops_only() && channel.member_rank(&user.id) > RANK_HALFOP {
+            self.send_reply_to_user(user.id, NumericReply::chanop_privs_needed(&user.nick, channel_name)).await?;
+            return Ok(());
         }
         
-        // Set new topic
-        let new_topic = &message.params[1];
-        let setter = format!("{}!{}@{}", user.nick, user.username, user.host);
-        channel.set_topic(new_topic.to_string(), setter);
+        // Set new topic, clearing it when empty
+        let new_topic = self.truncate_topic(&message.params[1]);
+        if new_topic.is_empty() {
+            channel.clear_topic();
+        } else {
+            let setter = format!("{}!{}@{}", user.nick, user.username, user.host);
+            channel.set_topic(new_topic.clone(), setter);
+        }
         
         // Update channel
         channels.insert(channel_name.to_string(), channel.clone());
+        drop(channels);
+        self.store_topic(&channel).await;
+        self.sync_permanent_channel(&channel).await?;
         
         // Broadcast topic change to channel
         let topic_message = Message::with_prefix(
@@ -973,7 +1827,7 @@ impl ChannelModule {
                 host: user.host.clone(),
             },
             MessageType::Topic,
-            vec![channel_name.to_string(), new_topic.to_string()],
+            vec![channel_name.to_string(), new_topic.clone()],
         );
         
         let broadcast = BroadcastMessage {
@@ -990,25 +1844,98 @@ impl ChannelModule {
         Ok(())
     }
     
+    /// RPL_TOPIC and RPL_TOPICWHOTIME for a channel, or nothing without a topic
+    fn topic_replies(&self, nick: &str, channel: &Channel) -> Vec<Message> {
+        let Some(topic) = &channel.topic else {
+            return Vec::new();
+        };
+        let mut replies = vec![NumericReply::topic(nick, &channel.name, topic)];
+        if let (Some(setter), Some(set_at)) = (&channel.topic_setter, channel.topic_time) {
+            replies.push(NumericReply::topic_who_time(nick, &channel.name, setter, set_at.timestamp()));
+        }
+        replies
+    }
+    
+    /// Save a permanent channel's state to the database, or drop it once -P
+    async fn sync_permanent_channel(&self, channel: &Channel) -> Result<()> {
+        let database = &self.database;
+        if channel.has_mode('P') {
+            database.set_permanent_channel(&channel.name, serde_json::to_value(channel)?)
+        } else {
+            database.remove_permanent_channel(&channel.name)
+        }
+    }
+    
+    /// Recreate permanent channels saved in the database
+    async fn restore_permanent_channels(&self) {
+        let saved = self.database.get_permanent_channels();
+        let mut channels = self.channels.write().await;
+        for (name, state) in saved {
+            match serde_json::from_value::<Channel>(state) {
+                Ok(channel) => {
+                    channels.entry(name).or_insert(channel);
+                }
+                Err(e) => tracing::warn!("Skipping unreadable permanent channel {}: {}", name, e),
+            }
+        }
+    }
+    
+    /// Cut a topic down to the configured length on a character boundary
+    fn truncate_topic(&self, topic: &str) -> String {
+        let mut end = topic.len().min(self.topic_config.max_length);
+        while !topic.is_char_boundary(end) {
+            end -= 1;
+        }
+        topic[..end].to_string()
+    }
+    
+    /// Save a channel's topic to the topic store, if one is configured
+    async fn store_topic(&self, channel: &Channel) {
+        let Some(path) = &self.topic_config.store_path else {
+            return;
+        };
+        let mut store = self.topic_store.write().await;
+        match (&channel.topic, &channel.topic_setter, channel.topic_time) {
+            (Some(topic), Some(setter), Some(set_at)) => {
+                store.insert(channel.name.clone(), StoredTopic {
+                    topic: topic.clone(),
+                    setter: setter.clone(),
+                    set_at,
+                });
+            }
+            _ => {
+                store.remove(&channel.name);
+            }
+        }
+        let result = serde_json::to_string_pretty(&*store)
+            .map_err(|e| e.to_string())
+            .and_then(|json| std::fs::write(path, json).map_err(|e| e.to_string()));
+        if let Err(e) = result {
+            tracing::warn!("Failed to save topic store {}: {}", path.display(), e);
+        }
+    }
+    
     async fn handle_names(&self, client: &Client, message: &Message) -> Result<()> {
         if !client.is_registered() {
             return Err(Error::User("Client not registered".to_string()));
         }
         
         // Get user from database
-        let database = self.database.read().await;
+        let database = &self.database;
         let user = database.get_user(&client.id)
             .ok_or_else(|| Error::User("User not found".to_string()))?;
         
         let channels = self.channels.read().await;
         
         // If no channels specified, show names for all channels user is in
-        let channels_to_show = if message.params.is_empty() {
-            database.get_user_channels(&user.nick)
-        } else {
-            message.params.clone()
+        let channels_to_show = match message.params.first() {
+            Some(targets) => targets.split(',').map(str::to_string).collect(),
+            None => database.get_user_channels(&user.nick),
         };
         
+        let multi_prefix = client.has_capability("multi-prefix");
+        let userhost_in_names = client.has_capability("userhost-in-names");
+        
         for channel_name in channels_to_show {
             if let Some(channel) = channels.get(&channel_name) {
                 // Check if user can see this channel
@@ -1016,50 +1943,11 @@ impl ChannelModule {
                     continue; // Skip secret channels user is not in
                 }
                 
-                // Get member names with prefixes
-                let mut names = Vec::new();
-                for (member_id, member) in &channel.members {
-                    if let Some(member_user) = database.get_user(member_id) {
-                        let mut name = String::new();
-                        
-                        // Add prefixes based on modes
-                        if member.is_operator() {
-                            name.push('@');
-                        } else if member.is_voice() {
-                            name.push('+');
-                        }
-                        
-                        name.push_str(&member_user.nick);
-                        names.push(name);
-                    }
+                for reply in self.names_replies(&user.nick, channel, multi_prefix, userhost_in_names) {
+                    self.send_reply_to_user(user.id, reply).await?;
                 }
                 
-                // Sort names (operators first, then voiced users, then regular users)
-                names.sort_by(|a, b| {
-                    let a_prefix = a.chars().next().unwrap_or(' ');
-                    let b_prefix = b.chars().next().unwrap_or(' ');
-                    
-                    match (a_prefix, b_prefix) {
-                        ('@', '@') => a.cmp(b),
-                        ('@', _) => std::cmp::Ordering::Less,
-                        (_, '@') => std::cmp::Ordering::Greater,
-                        ('+', '+') => a.cmp(b),
-                        ('+', _) => std::cmp::Ordering::Less,
-                        (_, '+') => std::cmp::Ordering::Greater,
-                        _ => a.cmp(b),
-                    }
-                });
-                
-                // Send names reply (split into multiple messages if too long)
-                let names_str = names.join(" ");
-                let names_reply = self.names_reply(&channel_name, &names_str);
-                self.send_reply_to_user(user.id, names_reply).await?;
-                
-                // Send end of names
-                let end_reply = self.end_of_names(&channel_name);
-                self.send_reply_to_user(user.id, end_reply).await?;
-                
-                tracing::info!("Sent names for channel {} to user {}", channel_name, user.nick);
+                tracing::debug!("Sent names for channel {} to user {}", channel_name, user.nick);
             }
         }
         
@@ -1072,56 +1960,55 @@ impl ChannelModule {
         }
         
         // Get user from database
-        let database = self.database.read().await;
+        let database = &self.database;
         let user = database.get_user(&client.id)
             .ok_or_else(|| Error::User("User not found".to_string()))?;
         
-        let channels = self.channels.read().await;
-        
-        // Send list start
-        let list_start = self.list_start();
-        self.send_reply_to_user(user.id, list_start).await?;
+        // Operators aside, each client waits out a cooldown between LISTs
+        if self.list_config.list_cooldown > 0 && !user.is_operator {
+            let cooldown = std::time::Duration::from_secs(self.list_config.list_cooldown);
+            let mut last_list = self.last_list.lock();
+            last_list.retain(|_, at| at.elapsed() < cooldown);
+            if last_list.contains_key(&client.id) {
+                let _ = client.send(NumericReply::try_again("LIST"));
+                return Ok(());
+            }
+            last_list.insert(client.id, std::time::Instant::now());
+        }
         
-        // Get channels to list
-        let channels_to_list = if message.params.is_empty() {
-            // List all channels
-            channels.keys().cloned().collect()
-        } else {
-            // List specific channels
-            message.params.clone()
+        let filter = message.params.first()
+            .map(|param| ListFilter::parse(param))
+            .unwrap_or_default();
+        
+        // Snapshot matching channels so no locks are held while paced replies go out
+        let now = Utc::now();
+        let entries: Vec<(String, usize, String)> = {
+            let channels = self.channels.read().await;
+            channels.values()
+                .filter(|channel| {
+                    // Secret and private channels are only visible to members
+                    !(channel.is_secret() || channel.is_private()) || channel.has_member(&user.id)
+                })
+                .filter(|channel| filter.matches(channel, now))
+                .map(|channel| (
+                    channel.name.clone(),
+                    channel.member_count(),
+                    channel.topic.clone().unwrap_or_default(),
+                ))
+                .collect()
         };
         
-        for channel_name in channels_to_list {
-            if let Some(channel) = channels.get(&channel_name) {
-                // Check if channel should be visible to user
-                let visible = if channel.is_secret() {
-                    // Only show secret channels if user is a member
-                    channel.has_member(&user.id)
-                } else if channel.is_private() {
-                    // Only show private channels if user is a member
-                    channel.has_member(&user.id)
-                } else {
-                    // Public channels are always visible
-                    true
-                };
-                
-                if visible {
-                    let topic = channel.topic.as_deref().unwrap_or("");
-                    let member_count = channel.member_count();
-                    
-                    let list_reply = self.list(&channel_name, &member_count.to_string(), topic);
-                    self.send_reply_to_user(user.id, list_reply).await?;
-                    
-                    tracing::debug!("Listed channel {} to user {}", channel_name, user.nick);
-                }
-            }
+        let mut replies = Vec::with_capacity(entries.len() + 2);
+        replies.push(self.list_start());
+        for (channel_name, member_count, topic) in &entries {
+            replies.push(self.list(channel_name, &member_count.to_string(), topic));
         }
+        replies.push(self.list_end());
         
-        // Send list end
-        let list_end = self.list_end();
-        self.send_reply_to_user(user.id, list_end).await?;
+        // Long listings are streamed over several ticks rather than all at once
+        client.send_paced(replies, self.list_config.batch_size, std::time::Duration::from_millis(self.list_config.batch_delay_ms));
         
-        tracing::info!("Sent channel list to user {}", user.nick);
+        tracing::debug!("Sent {} channels in LIST to user {}", entries.len(), user.nick);
         Ok(())
     }
     
@@ -1138,7 +2025,7 @@ impl ChannelModule {
         let channel_name = &message.params[1];
         
         // Get user from database
-        let database = self.database.read().await;
+        let database = &self.database;
         let user = database.get_user(&client.id)
             .ok_or_else(|| Error::User("User not found".to_string()))?;
         
@@ -1149,7 +2036,7 @@ impl ChannelModule {
         }
         
         // Check if target user exists
-        let _target_user = database.get_user_by_nick(nick)
+        let target_user = database.get_user_by_nick(nick)
             .ok_or_else(|| Error::User("No such nick".to_string()))?;
         
         // Check if target user is already in the channel
@@ -1171,6 +2058,14 @@ impl ChannelModule {
             return Err(Error::User("You're not channel operator".to_string()));
         }
         
+        // Invites from silenced users are dropped, but the inviter isn't told
+        if target_user.is_silencing(&user) {
+            tracing::debug!("INVITE from {} to {} dropped by SILENCE", user.nick, nick);
+            let inviting_reply = self.inviting(nick, channel_name);
+            self.send_reply_to_user(user.id, inviting_reply).await?;
+            return Ok(());
+        }
+        
         // Add invite to invite list
         self.add_invite(nick, channel_name).await;
         
@@ -1187,7 +2082,7 @@ impl ChannelModule {
         
         let broadcast = BroadcastMessage {
             message: invite_message,
-            target: BroadcastTarget::Users(vec![nick.to_string()]),
+            target: BroadcastTarget::Clients(vec![target_user.id]),
             sender: Some(user.id),
             priority: BroadcastPriority::Normal,
         };
@@ -1203,74 +2098,85 @@ impl ChannelModule {
         Ok(())
     }
     
-    async fn handle_kick(&self, client: &Client, message: &Message) -> Result<()> {
+    async fn handle_kick(&self, client: &Client, message: &Message, context: &ModuleContext) -> Result<()> {
         if !client.is_registered() {
             return Err(Error::User("Client not registered".to_string()));
         }
         
         if message.params.len() < 2 {
-            return Err(Error::User("Not enough parameters".to_string()));
+            let _ = client.send(NumericReply::need_more_params("KICK"));
+            return Ok(());
         }
         
-        let channel_name = &message.params[0];
-        let nick = &message.params[1];
-        let reason = message.params.get(2).map(|s| s.as_str());
+        // KICK <channel>{,<channel>} <user>{,<user>} [<comment>]: one channel with
+        // several users, or channels and users paired up in order
+        let channel_names: Vec<&str> = message.params[0].split(',').filter(|c| !c.is_empty()).collect();
+        let nicks: Vec<&str> = message.params[1].split(',').filter(|n| !n.is_empty()).collect();
+        let targets: Vec<(&str, &str)> = if channel_names.len() == 1 {
+            nicks.iter().map(|nick| (channel_names[0], *nick)).collect()
+        } else if channel_names.len() == nicks.len() {
+            channel_names.into_iter().zip(nicks).collect()
+        } else {
+            let _ = client.send(NumericReply::need_more_params("KICK"));
+            return Ok(());
+        };
         
         // Get user from database
-        let database = self.database.read().await;
-        let user = database.get_user(&client.id)
+        let user = self.database.get_user(&client.id)
             .ok_or_else(|| Error::User("User not found".to_string()))?;
+        let reason = message.params.get(2).cloned().unwrap_or_else(|| user.nick.clone());
         
-        // Check if user is in the channel
-        let user_channels = database.get_user_channels(&user.nick);
-        if !user_channels.contains(channel_name) {
-            return Err(Error::User("You're not on that channel".to_string()));
-        }
-        
-        // Check if target user exists
-        let target_user = database.get_user_by_nick(nick)
-            .ok_or_else(|| Error::User("No such nick".to_string()))?;
-        
-        // Check if target user is in the channel
-        let target_channels = database.get_user_channels(nick);
-        if !target_channels.contains(channel_name) {
-            return Err(Error::User("They aren't on that channel".to_string()));
+        for (channel_name, nick) in targets {
+            if let Some(error) = self.kick_member(&user, channel_name, nick, &reason, context).await? {
+                let _ = client.send(error);
+            }
         }
+        Ok(())
+    }
+    
+    /// Kick one member, returning the error numeric if the kick isn't allowed
+    async fn kick_member(&self, user: &User, channel_name: &str, nick: &str, reason: &str, context: &ModuleContext) -> Result<Option<Message>> {
+        let Some(target_user) = self.get_user_by_nick(nick).await? else {
+            return Ok(Some(NumericReply::no_such_nick(nick)));
+        };
         
         let mut channels = self.channels.write().await;
+        let Some(channel) = channels.get_mut(channel_name) else {
+            return Ok(Some(NumericReply::no_such_channel(&user.nick, channel_name)));
+        };
         
-        // Get channel
-        let mut channel = channels.get_mut(channel_name)
-            .ok_or_else(|| Error::User("No such channel".to_string()))?
-            .clone();
+        let can_override = self.can_override(user);
+        if !channel.has_member(&user.id) && !can_override {
+            return Ok(Some(NumericReply::not_on_channel(&user.nick, channel_name)));
+        }
+        if !channel.has_member(&target_user.id) {
+            return Ok(Some(NumericReply::user_not_in_channel(&user.nick, &target_user.nick, channel_name)));
+        }
         
-        // Check if user has permission to kick
-        if !channel.is_operator(&user.id) {
-            return Err(Error::User("You're not channel operator".to_string()));
+        // Halfops may kick members below them, ops and above anyone up to their own rank
+        let kicker_rank = channel.member_rank(&user.id);
+        let target_rank = channel.member_rank(&target_user.id);
+        let allowed = kicker_rank < target_rank.min(RANK_HALFOP + 1)
+            || (kicker_rank <= RANK_OP && kicker_rank <= target_rank);
+        if !allowed && !can_override {
+            return Ok(Some(NumericReply::chanop_privs_needed(&user.nick, channel_name)));
         }
         
         // Remove target user from channel
         channel.remove_member(&target_user.id);
-        
-        // Update channel
-        channels.insert(channel_name.to_string(), channel.clone());
-        
-        // Update database
+        if channel.is_removable() {
+            channels.remove(channel_name);
+            tracing::info!("Channel {} removed (empty after kick)", channel_name);
+        }
         drop(channels);
-        drop(database);
         
-        let database = self.database.write().await;
-        database.remove_user_from_channel(nick, channel_name)?;
+        // Update database
+        self.database.remove_user_from_channel(&target_user.nick, channel_name)?;
         
         // Remove from invite list if present
-        self.remove_invite(nick, channel_name).await;
-        
-        // Broadcast KICK message to channel
-        let mut kick_params = vec![channel_name.to_string(), nick.to_string()];
-        if let Some(reason) = reason {
-            kick_params.push(reason.to_string());
-        }
+        self.remove_invite(&target_user.nick, channel_name).await;
         
+        // Broadcast KICK message to channel and the rest of the network
         let kick_message = Message::with_prefix(
             Prefix::User {
                 nick: user.nick.clone(),
@@ -1278,11 +2184,11 @@ impl ChannelModule {
                 host: user.host.clone(),
             },
             MessageType::Kick,
-            kick_params,
+            vec![channel_name.to_string(), target_user.nick.clone(), reason.to_string()],
         );
         
         let broadcast = BroadcastMessage {
-            message: kick_message,
+            message: kick_message.clone(),
             target: BroadcastTarget::Channel(channel_name.to_string()),
             sender: Some(user.id),
             priority: BroadcastPriority::Normal,
@@ -1293,58 +2199,316 @@ impl ChannelModule {
         
         // Unsubscribe target user from channel
         broadcast_system.unsubscribe_from_channel(&target_user.id, channel_name);
+        drop(broadcast_system);
         
-        // If channel is empty, remove it
-        if channel.member_count() == 0 {
-            let mut channels = self.channels.write().await;
-            channels.remove(channel_name);
-            tracing::info!("Channel {} removed (empty after kick)", channel_name);
+        context.broadcast_to_servers(kick_message).await?;
+        
+        if !allowed {
+            self.announce_override(context, user, channel_name, &format!("kicked {}", target_user.nick)).await?;
         }
         
-        tracing::info!("User {} kicked {} from channel {}", user.nick, nick, channel_name);
-        Ok(())
+        tracing::info!("User {} kicked {} from channel {}", user.nick, target_user.nick, channel_name);
+        Ok(None)
     }
     
-    /// Channel-specific error and reply methods
-    fn no_such_channel(&self, channel: &str) -> Message {
-        Message::new(
-            rustircd_core::MessageType::Custom("403".to_string()),
-            vec!["*".to_string(), channel.to_string(), "No such channel".to_string()],
-        )
+    /// Apply a KICK relayed from another server and pass it on to the other links
+    async fn handle_remote_kick(&self, server: &str, message: &Message, context: &ModuleContext) -> Result<()> {
+        if message.params.len() < 2 {
+            return Ok(());
+        }
+        let channel_name = &message.params[0];
+        let Some(target_user) = self.get_user_by_nick(&message.params[1]).await? else {
+            return Ok(());
+        };
+        
+        let mut channels = self.channels.write().await;
+        if let Some(channel) = channels.get_mut(channel_name) {
+            if channel.has_member(&target_user.id) {
+                channel.remove_member(&target_user.id);
+                if channel.is_removable() {
+                    channels.remove(channel_name);
+                }
+                drop(channels);
+                
+                self.database.remove_user_from_channel(&target_user.nick, channel_name)?;
+                let mut broadcast_system = self.broadcast_system.write().await;
+                broadcast_system.queue_message(BroadcastMessage {
+                    message: message.clone(),
+                    target: BroadcastTarget::Channel(channel_name.clone()),
+                    sender: None,
+                    priority: BroadcastPriority::Normal,
+                })?;
+                broadcast_system.unsubscribe_from_channel(&target_user.id, channel_name);
+            }
+        }
+        
+        context.server_connections.broadcast_message(message, Some(server)).await
     }
     
-    fn cannot_send_to_chan(&self, channel: &str) -> Message {
-        Message::new(
-            rustircd_core::MessageType::Custom("404".to_string()),
-            vec!["*".to_string(), channel.to_string(), "Cannot send to channel".to_string()],
-        )
+    /// Handle RENAME (draft/channel-rename)
+    ///
+    /// `RENAME <old> <new> [<reason>]` moves a channel, with its members,
+    /// modes and lists, to a new name of the same type. Channel operators
+    /// may rename their channels.
+    async fn handle_rename(&self, client: &Client, message: &Message, context: &ModuleContext) -> Result<()> {
+        let user = self.database.get_user(&client.id)
+            .ok_or_else(|| Error::User("User not found".to_string()))?;
+        let (old_name, new_name) = (message.params[0].as_str(), message.params[1].as_str());
+        let reason = message.params.get(2).cloned().unwrap_or_default();
+        
+        let can_override = self.can_override(&user);
+        let allowed = {
+            let channels = self.channels.read().await;
+            let Some(channel) = channels.get(old_name) else {
+                let _ = client.send(NumericReply::no_such_channel(&user.nick, old_name));
+                return Ok(());
+            };
+            if !channel.has_member(&user.id) && !can_override {
+                let _ = client.send(NumericReply::not_on_channel(&user.nick, old_name));
+                return Ok(());
+            }
+            let allowed = channel.member_rank(&user.id) <= RANK_OP;
+            if !allowed && !can_override {
+                let _ = client.send(NumericReply::chanop_privs_needed(&user.nick, old_name));
+                return Ok(());
+            }
+            
+            // The type prefix decides how a channel behaves, so it must stay
+            let same_type = old_name.chars().next() == new_name.chars().next();
+            if !same_type || !self.is_valid_channel_name(new_name) || new_name == old_name {
+                let _ = client.send(Self::rename_fail(context, "CANNOT_RENAME", old_name, new_name, "You cannot rename the channel to that name"));
+                return Ok(());
+            }
+            if channels.contains_key(new_name) {
+                let _ = client.send(Self::rename_fail(context, "CHANNEL_NAME_IN_USE", old_name, new_name, "Channel already exists"));
+                return Ok(());
+            }
+            allowed
+        };
+        
+        let Some(channel) = self.rename_channel(old_name, new_name).await? else {
+            let _ = client.send(Self::rename_fail(context, "CHANNEL_NAME_IN_USE", old_name, new_name, "Channel already exists"));
+            return Ok(());
+        };
+        let rename = Message::with_prefix(
+            Prefix::User {
+                nick: user.nick.clone(),
+                user: user.username.clone(),
+                host: user.host.clone(),
+            },
+            MessageType::Custom("RENAME".to_string()),
+            vec![old_name.to_string(), new_name.to_string(), reason],
+        );
+        self.notify_rename(&rename, &channel, context);
+        context.broadcast_to_servers(rename).await?;
+        
+        if !allowed {
+            self.announce_override(context, &user, new_name, &format!("renamed {} to {}", old_name, new_name)).await?;
+        }
+        
+        tracing::info!("User {} renamed channel {} to {}", user.nick, old_name, new_name);
+        Ok(())
     }
     
-    fn too_many_channels(&self, channel: &str) -> Message {
-        Message::new(
-            rustircd_core::MessageType::Custom("405".to_string()),
-            vec!["*".to_string(), channel.to_string(), "You have joined too many channels".to_string()],
-        )
+    /// Apply a RENAME relayed from another server and pass it on to the other links
+    async fn handle_remote_rename(&self, server: &str, message: &Message, context: &ModuleContext) -> Result<()> {
+        let [old_name, new_name, ..] = message.params.as_slice() else {
+            return Ok(());
+        };
+        match self.rename_channel(old_name, new_name).await? {
+            Some(channel) => self.notify_rename(message, &channel, context),
+            None => tracing::warn!("Ignoring RENAME of {} to {} from {}", old_name, new_name, server),
+        }
+        context.server_connections.broadcast_message(message, Some(server)).await
     }
     
-    fn user_not_in_channel(&self, nick: &str, channel: &str) -> Message {
-        Message::new(
-            rustircd_core::MessageType::Custom("441".to_string()),
-            vec![nick.to_string(), channel.to_string(), "They aren't on that channel".to_string()],
-        )
+    /// Move a channel and everything kept under its name, returning the renamed channel
+    ///
+    /// Returns `None` when the channel is gone or the new name is taken.
+    async fn rename_channel(&self, old_name: &str, new_name: &str) -> Result<Option<Channel>> {
+        let mut channels = self.channels.write().await;
+        if channels.contains_key(new_name) {
+            return Ok(None);
+        }
+        let Some(mut channel) = channels.remove(old_name) else {
+            return Ok(None);
+        };
+        channel.name = new_name.to_string();
+        channels.insert(new_name.to_string(), channel.clone());
+        drop(channels);
+        
+        self.database.rename_channel(old_name, new_name)?;
+        self.broadcast_system.read().await.rename_channel(old_name, new_name);
+        
+        // Pending invites follow the channel
+        for invited in self.invite_list.write().await.values_mut() {
+            if invited.remove(old_name) {
+                invited.insert(new_name.to_string());
+            }
+        }
+        if channel.has_mode('P') {
+            self.database.remove_permanent_channel(old_name)?;
+            self.sync_permanent_channel(&channel).await?;
+        }
+        if self.topic_store.write().await.remove(old_name).is_some() {
+            self.store_topic(&channel).await;
+        }
+        Ok(Some(channel))
     }
     
-    fn not_on_channel(&self, channel: &str) -> Message {
-        Message::new(
-            rustircd_core::MessageType::Custom("442".to_string()),
-            vec!["*".to_string(), channel.to_string(), "You're not on that channel".to_string()],
-        )
+    /// Tell local members about a rename
+    ///
+    /// Clients with draft/channel-rename get the RENAME itself; the rest see
+    /// themselves part the old channel and join the new one, with its topic
+    /// and names.
+    fn notify_rename(&self, rename: &Message, channel: &Channel, context: &ModuleContext) {
+        let old_name = &rename.params[0];
+        let reason = rename.params.get(2).filter(|reason| !reason.is_empty())
+            .map(|reason| format!("Channel renamed to {}: {}", channel.name, reason))
+            .unwrap_or_else(|| format!("Channel renamed to {}", channel.name));
+        for member_id in channel.members.keys() {
+            if !context.client_senders.contains(member_id) {
+                continue;
+            }
+            if context.client_has_capability(member_id, "draft/channel-rename") {
+                context.client_senders.send(member_id, rename.clone());
+                continue;
+            }
+            let Some(member) = self.database.get_user(member_id) else {
+                continue;
+            };
+            let prefix = Prefix::User {
+                nick: member.nick.clone(),
+                user: member.username.clone(),
+                host: member.host.clone(),
+            };
+            let mut replies = vec![
+                Message::with_prefix(prefix.clone(), MessageType::Part, vec![old_name.clone(), reason.clone()]),
+                Message::with_prefix(prefix, MessageType::Join, vec![channel.name.clone()]),
+            ];
+            replies.extend(self.topic_replies(&member.nick, channel));
+            replies.extend(self.names_replies(&member.nick, channel, false, false));
+            for reply in replies {
+                context.client_senders.send(member_id, reply);
+            }
+        }
     }
     
-    fn user_on_channel(&self, nick: &str, channel: &str) -> Message {
-        Message::new(
-            rustircd_core::MessageType::Custom("443".to_string()),
-            vec![nick.to_string(), channel.to_string(), "is already on channel".to_string()],
+    /// A FAIL standard reply for the RENAME command
+    fn rename_fail(context: &ModuleContext, code: &str, old_name: &str, new_name: &str, description: &str) -> Message {
+        Message::with_prefix(
+            Prefix::Server(context.server_connections.local_server_name().to_string()),
+            MessageType::Custom("FAIL".to_string()),
+            vec!["RENAME".to_string(), code.to_string(), old_name.to_string(), new_name.to_string(), description.to_string()],
+        )
+    }
+    
+    /// Block messages from members without voice or ops to channels with mode +m
+    async fn check_channel_moderation(&self, client: &Client, message: &Message, context: &ModuleContext) -> Result<ModuleResult> {
+        let Some(target) = message.params.first() else {
+            return Ok(ModuleResult::NotHandled);
+        };
+        if !self.is_valid_channel_name(target) {
+            return Ok(ModuleResult::NotHandled);
+        }
+        let Some(user) = self.database.get_user(&client.id) else {
+            return Ok(ModuleResult::NotHandled);
+        };
+        
+        // Voiced users and channel operators are exempt from both +m and +q
+        let blocking_mode = self.channels.read().await.get(target)
+            .filter(|c| !c.can_speak_moderated(&user.id))
+            .and_then(|c| {
+                if c.is_moderated() {
+                    Some('m')
+                } else if self.is_user_quieted(&user, c) {
+                    Some('q')
+                } else {
+                    None
+                }
+            });
+        let Some(mode) = blocking_mode else {
+            return Ok(ModuleResult::NotHandled);
+        };
+        
+        if self.can_override(&user) {
+            self.announce_override(context, &user, target, &format!("spoke through +{}", mode)).await?;
+            return Ok(ModuleResult::NotHandled);
+        }
+        
+        // NOTICE never generates error replies
+        if message.command == MessageType::PrivMsg {
+            let _ = client.send(self.cannot_send_to_chan(target));
+        }
+        Ok(ModuleResult::HandledStop)
+    }
+    
+    /// Block CTCPs other than ACTION to channels with mode +C
+    async fn check_channel_ctcp(&self, client: &Client, message: &Message) -> Result<ModuleResult> {
+        let (Some(target), Some(text)) = (message.params.first(), message.params.get(1)) else {
+            return Ok(ModuleResult::NotHandled);
+        };
+        if !self.is_valid_channel_name(target) {
+            return Ok(ModuleResult::NotHandled);
+        }
+        let Some(ctcp) = Ctcp::parse(text) else {
+            return Ok(ModuleResult::NotHandled);
+        };
+        
+        let channels = self.channels.read().await;
+        let blocked = !ctcp.is_action() && channels.get(target).is_some_and(|c| c.no_ctcp());
+        if !blocked {
+            return Ok(ModuleResult::NotHandled);
+        }
+        
+        // NOTICE never generates error replies
+        if message.command == MessageType::PrivMsg {
+            let _ = client.send(self.cannot_send_to_chan(target));
+        }
+        tracing::debug!("Blocked CTCP {} to {} (+C)", ctcp.command, target);
+        Ok(ModuleResult::HandledStop)
+    }
+    
+    /// Channel-specific error and reply methods
+    fn no_such_channel(&self, channel: &str) -> Message {
+        Message::new(
+            rustircd_core::MessageType::Custom("403".to_string()),
+            vec!["*".to_string(), channel.to_string(), "No such channel".to_string()],
+        )
+    }
+    
+    fn cannot_send_to_chan(&self, channel: &str) -> Message {
+        Message::new(
+            rustircd_core::MessageType::Custom("404".to_string()),
+            vec!["*".to_string(), channel.to_string(), "Cannot send to channel".to_string()],
+        )
+    }
+    
+    fn too_many_channels(&self, channel: &str) -> Message {
+        Message::new(
+            rustircd_core::MessageType::Custom("405".to_string()),
+            vec!["*".to_string(), channel.to_string(), "You have joined too many channels".to_string()],
+        )
+    }
+    
+    fn user_not_in_channel(&self, nick: &str, channel: &str) -> Message {
+        Message::new(
+            rustircd_core::MessageType::Custom("441".to_string()),
+            vec![nick.to_string(), channel.to_string(), "They aren't on that channel".to_string()],
+        )
+    }
+    
+    fn not_on_channel(&self, channel: &str) -> Message {
+        Message::new(
+            rustircd_core::MessageType::Custom("442".to_string()),
+            vec!["*".to_string(), channel.to_string(), "You're not on that channel".to_string()],
+        )
+    }
+    
+    fn user_on_channel(&self, nick: &str, channel: &str) -> Message {
+        Message::new(
+            rustircd_core::MessageType::Custom("443".to_string()),
+            vec![nick.to_string(), channel.to_string(), "is already on channel".to_string()],
         )
     }
     
@@ -1425,27 +2589,6 @@ impl ChannelModule {
         )
     }
     
-    fn no_topic(&self, channel: &str) -> Message {
-        Message::new(
-            rustircd_core::MessageType::Custom("331".to_string()),
-            vec!["*".to_string(), channel.to_string(), "No topic is set".to_string()],
-        )
-    }
-    
-    fn topic(&self, channel: &str, topic: &str) -> Message {
-        Message::new(
-            rustircd_core::MessageType::Custom("332".to_string()),
-            vec!["*".to_string(), channel.to_string(), topic.to_string()],
-        )
-    }
-    
-    fn topic_who_time(&self, channel: &str, nick: &str, time: &str) -> Message {
-        Message::new(
-            rustircd_core::MessageType::Custom("333".to_string()),
-            vec!["*".to_string(), channel.to_string(), nick.to_string(), time.to_string()],
-        )
-    }
-    
     fn inviting(&self, nick: &str, channel: &str) -> Message {
         Message::new(
             rustircd_core::MessageType::Custom("341".to_string()),
@@ -1474,13 +2617,53 @@ impl ChannelModule {
         )
     }
     
-    fn names_reply(&self, channel: &str, names: &str) -> Message {
+    /// RPL_NAMREPLY lines for a channel's members, highest rank first, and RPL_ENDOFNAMES
+    fn names_replies(&self, nick: &str, channel: &Channel, multi_prefix: bool, userhost_in_names: bool) -> Vec<Message> {
+        let mut members: Vec<(usize, String)> = channel.members.iter()
+            .filter_map(|(member_id, member)| {
+                let member_user = self.database.get_user(member_id)?;
+                let mut name = member.prefixes(multi_prefix);
+                if userhost_in_names {
+                    name.push_str(&format!("{}!{}@{}", member_user.nick, member_user.username, member_user.host));
+                } else {
+                    name.push_str(&member_user.nick);
+                }
+                Some((member.rank(), name))
+            })
+            .collect();
+        members.sort_by(|(a_rank, a), (b_rank, b)| a_rank.cmp(b_rank).then_with(|| a.cmp(b)));
+        let names: Vec<String> = members.into_iter().map(|(_, name)| name).collect();
+        
+        // ":server 353 nick = #channel :names\r\n" must fit in one line
+        let symbol = self.channel_symbol(channel);
+        let overhead = 1 + MAX_SERVER_NAME_LENGTH + " 353 ".len() + nick.len()
+            + 1 + symbol.len() + 1 + channel.name.len() + " :".len() + "\r\n".len();
+        let mut replies: Vec<Message> = wrap_names(&names, MAX_LINE_LENGTH.saturating_sub(overhead))
+            .into_iter()
+            .map(|line| self.names_reply(symbol, &channel.name, &line))
+            .collect();
+        replies.push(self.end_of_names(&channel.name));
+        replies
+    }
+    
+    fn names_reply(&self, symbol: &str, channel: &str, names: &str) -> Message {
         Message::new(
             rustircd_core::MessageType::Custom("353".to_string()),
-            vec!["*".to_string(), "=".to_string(), channel.to_string(), names.to_string()],
+            vec!["*".to_string(), symbol.to_string(), channel.to_string(), names.to_string()],
         )
     }
     
+    /// RPL_NAMREPLY channel type symbol: `@` secret, `*` private, `=` public
+    fn channel_symbol(&self, channel: &Channel) -> &'static str {
+        if channel.is_secret() {
+            "@"
+        } else if channel.is_private() {
+            "*"
+        } else {
+            "="
+        }
+    }
+    
     fn end_of_names(&self, channel: &str) -> Message {
         Message::new(
             rustircd_core::MessageType::Custom("366".to_string()),
@@ -1520,6 +2703,82 @@ impl ChannelModule {
             .unwrap_or(false)
     }
     
+    /// Validate a +f target: it must be another existing channel where the
+    /// setter is a channel operator, unless it has +F or the setter overrides
+    fn check_forward_target(&self, user: &User, channel: &Channel, forward: &str, channels: &HashMap<String, Channel>, can_override: bool) -> Result<()> {
+        if !self.forward_config.enabled {
+            return Err(Error::User("Channel forwarding is disabled".to_string()));
+        }
+        if !self.is_valid_channel_name(forward) || forward.eq_ignore_ascii_case(&channel.name) {
+            return Err(Error::User("Invalid forward channel".to_string()));
+        }
+        let target = channels.get(forward)
+            .ok_or_else(|| Error::User("No such channel".to_string()))?;
+        if !target.has_mode('F') && !target.is_operator(&user.id) && !can_override {
+            return Err(Error::User("You're not channel operator on the forward channel".to_string()));
+        }
+        Ok(())
+    }
+    
+    /// Channel modes that keep a user out of a channel: +i, +k, +b, +l, +j, +R, +S and +O
+    ///
+    /// +S checks the user's +z, which registration sets from the client's TLS flag.
+    async fn join_restrictions(&self, user: &User, channel: &Channel, key: Option<&String>) -> Vec<char> {
+        let mut failed = Vec::new();
+        if channel.is_invite_only() && !self.is_user_invited(&user.nick, &channel.name).await {
+            failed.push('i');
+        }
+        if channel.is_keyed() && !key.is_some_and(|key| channel.check_key(key)) {
+            failed.push('k');
+        }
+        if self.is_user_banned(user, channel).await {
+            failed.push('b');
+        }
+        if channel.user_limit.is_some_and(|limit| channel.member_count() >= limit) {
+            failed.push('l');
+        }
+        if channel.is_join_throttled(Utc::now()) {
+            failed.push('j');
+        }
+        if channel.has_mode('R') && !user.is_identified() {
+            failed.push('R');
+        }
+        if channel.has_mode('S') && !user.is_secure() {
+            failed.push('S');
+        }
+        if channel.has_mode('O') && !user.is_operator {
+            failed.push('O');
+        }
+        failed
+    }
+    
+    /// Follow +f from a channel the user can't join to the first channel that accepts them
+    ///
+    /// Forwarding stops at channels with +Q, channels the user is already on,
+    /// loops, and after `max_hops` forwards.
+    async fn forward_target(&self, channels: &HashMap<String, Channel>, user: &User, user_channels: &[String], channel_name: &str) -> Option<String> {
+        if !self.forward_config.enabled {
+            return None;
+        }
+        let mut visited = HashSet::from([channel_name.to_string()]);
+        let mut current = channels.get(channel_name)?;
+        for _ in 0..self.forward_config.max_hops {
+            let target_name = current.forward.as_ref()?;
+            if !visited.insert(target_name.clone()) || user_channels.contains(target_name) {
+                return None;
+            }
+            let target = channels.get(target_name)?;
+            if target.has_mode('Q') {
+                return None;
+            }
+            if self.join_restrictions(user, target, None).await.is_empty() {
+                return Some(target_name.clone());
+            }
+            current = target;
+        }
+        None
+    }
+    
     /// Add user to invite list
     async fn add_invite(&self, nick: &str, channel: &str) {
         let mut invite_list = self.invite_list.write().await;
@@ -1556,12 +2815,67 @@ impl ChannelModule {
         false
     }
     
-    /// Check if user matches a mask (nick!user@host format)
+    /// Replies listing a channel's +b, +e, +I or +q masks
+    fn list_mode_replies(&self, nick: &str, channel: &Channel, mode: char) -> Vec<Message> {
+        let (masks, entry, end): ListModeReplies = match mode {
+            'b' => (&channel.ban_masks, NumericReply::ban_list, NumericReply::end_of_ban_list),
+            'e' => (&channel.exception_masks, NumericReply::except_list, NumericReply::end_of_except_list),
+            'I' => (&channel.invite_masks, NumericReply::invite_list, NumericReply::end_of_invite_list),
+            _ => (&channel.quiet_masks, NumericReply::quiet_list, NumericReply::end_of_quiet_list),
+        };
+        let mut sorted: Vec<&String> = masks.iter().collect();
+        sorted.sort();
+        sorted.into_iter()
+            .map(|mask| entry(nick, &channel.name, mask))
+            .chain(std::iter::once(end(nick, &channel.name)))
+            .collect()
+    }
+    
+    /// Check if user is quieted (+q) in a channel, honouring exceptions (+e)
+    fn is_user_quieted(&self, user: &User, channel: &Channel) -> bool {
+        channel.quiet_masks.iter().any(|mask| self.matches_mask(user, mask))
+            && !channel.exception_masks.iter().any(|mask| self.matches_mask(user, mask))
+    }
+    
+    /// Check if user matches a mask (nick!user@host format or an extban)
     fn matches_mask(&self, user: &User, mask: &str) -> bool {
+        if let Some(extban) = mask.strip_prefix('$') {
+            return self.matches_extban(user, extban);
+        }
         let user_mask = format!("{}!{}@{}", user.nick, user.username, user.host);
         self.matches_pattern(&user_mask, mask)
     }
     
+    /// Match a charybdis-style extban (without the leading `$`)
+    ///
+    /// Supported types: `a` (identified users) or `a:account`, `o`
+    /// (operators), `r:realname`, `s:server`, `x:nick!user@host#realname`
+    /// and `z` (users connected over TLS); `~` negates, e.g. `$~a`. Unknown types never match.
+    fn matches_extban(&self, user: &User, extban: &str) -> bool {
+        let (negated, extban) = match extban.strip_prefix('~') {
+            Some(rest) => (true, rest),
+            None => (false, extban),
+        };
+        let mut chars = extban.chars();
+        let kind = chars.next();
+        let arg = chars.as_str().strip_prefix(':');
+        let matched = match (kind, arg) {
+            (Some('a'), None) => user.is_identified(),
+            (Some('a'), Some(account)) => user.account.as_ref()
+                .is_some_and(|name| self.matches_pattern(&name.to_ascii_lowercase(), &account.to_ascii_lowercase())),
+            (Some('o'), _) => user.is_operator,
+            (Some('r'), Some(realname)) => self.matches_pattern(&user.realname, realname),
+            (Some('s'), Some(server)) => self.matches_pattern(&user.server, server),
+            (Some('x'), Some(full)) => {
+                let full_mask = format!("{}!{}@{}#{}", user.nick, user.username, user.host, user.realname);
+                self.matches_pattern(&full_mask, full)
+            }
+            (Some('z'), None) => user.is_secure(),
+            _ => return false,
+        };
+        matched != negated
+    }
+    
     /// Simple pattern matching for IRC masks
     fn matches_pattern(&self, text: &str, pattern: &str) -> bool {
         if pattern == "*" {
@@ -1610,23 +2924,367 @@ impl ChannelModule {
     
     /// Get user by nickname
     async fn get_user_by_nick(&self, nick: &str) -> Result<Option<User>> {
-        let database = self.database.read().await;
+        let database = &self.database;
         Ok(database.get_user_by_nick(nick))
     }
     
     /// Get mode parameters for a channel
     fn get_mode_params(&self, channel: &Channel) -> String {
-        let mut params = Vec::new();
+        channel.mode_params().join(" ")
+    }
+    
+    /// Check whether a mode grants a member status
+    fn is_status_mode(mode: char) -> bool {
+        MEMBER_PREFIXES.iter().any(|(status, _)| *status == mode)
+    }
+    
+    /// Lowest member rank allowed to change a mode
+    ///
+    /// Owners manage owners, admins manage admins, ops manage ops and
+    /// halfops, and halfops handle voice, list modes and the basic flags.
+    fn required_rank(mode: char) -> usize {
+        match mode {
+            'y' => RANK_OWNER,
+            'a' => RANK_ADMIN,
+            'v' | 'b' | 'e' | 'I' | 'q' | 'i' | 'm' | 'n' | 't' | 'C' => RANK_HALFOP,
+            // Checked separately: opers only
+            'O' | 'P' => usize::MAX,
+            _ => RANK_OP,
+        }
+    }
+    
+    /// User a server-relayed message originates from, by nick or UID prefix
+    async fn remote_source_user(&self, server: &str, message: &Message) -> Result<Option<User>> {
+        match &message.prefix {
+            Some(Prefix::User { nick, .. }) => self.get_user_by_nick(nick).await,
+            Some(Prefix::Server(source)) => match Uuid::parse_str(source) {
+                Ok(uid) => Ok(self.database.get_user(&uid)),
+                Err(_) => Ok(None),
+            },
+            None => {
+                tracing::warn!("{} from {} has no source prefix", message.command, server);
+                Ok(None)
+            }
+        }
+    }
+    
+    /// Add a remote user to the channels in a relayed JOIN
+    ///
+    /// The user's own server already checked the join restrictions. Remote
+    /// users aren't subscribed to channel broadcasts since they have no local
+    /// connection; local members see the JOIN.
+    async fn handle_remote_join(&self, server: &str, message: &Message) -> Result<()> {
+        let (Some(channel_names), Some(user)) = (message.params.first(), self.remote_source_user(server, message).await?) else {
+            return Ok(());
+        };
+        
+        for channel_name in channel_names.split(',') {
+            let mut channels = self.channels.write().await;
+            let channel = channels.entry(channel_name.to_string())
+                .or_insert_with(|| Channel::new(channel_name.to_string()));
+            if channel.has_member(&user.id) {
+                continue;
+            }
+            let is_new = channel.members.is_empty() && !channel.has_mode('P');
+            channel.force_add_member(user.id)?;
+            // Matches the ops the creator got on their own server
+            if is_new {
+                channel.set_operator(&user.id, true)?;
+            }
+            channel.record_join(Utc::now());
+            drop(channels);
+            
+            let mut join = message.clone();
+            join.params = vec![channel_name.to_string()];
+            self.broadcast_system.write().await.queue_message(BroadcastMessage {
+                message: join,
+                target: BroadcastTarget::Channel(channel_name.to_string()),
+                sender: None,
+                priority: BroadcastPriority::Normal,
+            })?;
+        }
+        Ok(())
+    }
+    
+    /// Remove a remote user from the channels in a relayed PART
+    async fn handle_remote_part(&self, server: &str, message: &Message) -> Result<()> {
+        let (Some(channel_names), Some(user)) = (message.params.first(), self.remote_source_user(server, message).await?) else {
+            return Ok(());
+        };
+        
+        for channel_name in channel_names.split(',') {
+            let mut channels = self.channels.write().await;
+            let Some(channel) = channels.get_mut(channel_name) else {
+                continue;
+            };
+            if !channel.has_member(&user.id) {
+                continue;
+            }
+            
+            // Local members see the PART before the channel goes away
+            let mut part = message.clone();
+            part.params[0] = channel_name.to_string();
+            self.broadcast_system.write().await.queue_message(BroadcastMessage {
+                message: part,
+                target: BroadcastTarget::Channel(channel_name.to_string()),
+                sender: None,
+                priority: BroadcastPriority::Normal,
+            })?;
+            
+            channel.remove_member(&user.id);
+            if channel.is_removable() {
+                channels.remove(channel_name);
+            }
+        }
+        Ok(())
+    }
+    
+    /// Burst of all channels for a newly linked server
+    ///
+    /// Each channel is sent as
+    /// `CBURST <channel> <ts> <modes> [params...] :<prefixed members>`, followed by
+    /// `BMASK <ts> <channel> <b|e|I|q> :<masks>` for non-empty lists and
+    /// `TB <channel> <topic ts> <setter> :<topic>` when a topic is set.
+    /// Members on the target server itself are left out.
+    async fn channel_burst(&self, target_server: &str) -> Vec<Message> {
+        let channels: Vec<Channel> = self.channels.read().await.values().cloned().collect();
+        let database = &self.database;
+        let mut messages = Vec::new();
+        for channel in channels {
+            let ts = channel.created_at.timestamp().to_string();
+            let mut members: Vec<String> = channel.members.values()
+                .filter_map(|member| {
+                    let user = database.get_user(&member.user_id)?;
+                    (user.server != target_server).then(|| format!("{}{}", member.prefixes(true), user.nick))
+                })
+                .collect();
+            if members.is_empty() && !channel.has_mode('P') {
+                continue;
+            }
+            members.sort_unstable();
+            
+            let mut params = vec![channel.name.clone(), ts.clone(), format!("+{}", channel.modes_string())];
+            params.extend(channel.mode_params());
+            params.push(members.join(" "));
+            messages.push(Message::new(MessageType::ChannelBurst, params));
+            
+            for (mode, masks) in [('b', &channel.ban_masks), ('e', &channel.exception_masks), ('I', &channel.invite_masks), ('q', &channel.quiet_masks)] {
+                if !masks.is_empty() {
+                    let mut masks: Vec<&str> = masks.iter().map(String::as_str).collect();
+                    masks.sort_unstable();
+                    messages.push(Message::new(
+                        MessageType::Custom("BMASK".to_string()),
+                        vec![ts.clone(), channel.name.clone(), mode.to_string(), masks.join(" ")],
+                    ));
+                }
+            }
+            
+            if let (Some(topic), Some(setter), Some(set_at)) = (&channel.topic, &channel.topic_setter, channel.topic_time) {
+                messages.push(Message::new(
+                    MessageType::Custom("TB".to_string()),
+                    vec![channel.name.clone(), set_at.timestamp().to_string(), setter.clone(), topic.clone()],
+                ));
+            }
+        }
+        messages
+    }
+    
+    /// Merge a channel burst from another server
+    ///
+    /// The older channel TS wins: if the remote channel is older, our modes,
+    /// lists and statuses are cleared and theirs are taken; if ours is older,
+    /// their members join without statuses and their modes are ignored; equal
+    /// timestamps merge both sides.
+    async fn handle_channel_burst(&self, server: &str, message: &Message, context: &ModuleContext) -> Result<()> {
+        if message.params.len() < 4 {
+            return Err(Error::MessageParse("CBURST requires channel, TS, modes and members".to_string()));
+        }
+        let channel_name = &message.params[0];
+        let remote_ts = message.params[1].parse::<i64>()
+            .ok()
+            .and_then(|ts| DateTime::<Utc>::from_timestamp(ts, 0))
+            .ok_or_else(|| Error::MessageParse(format!("Invalid channel TS in CBURST for {}", channel_name)))?;
+        let modes = message.params[2].trim_start_matches('+');
+        let mut mode_params = message.params[3..message.params.len() - 1].iter();
+        let members = &message.params[message.params.len() - 1];
+        
+        let mut channels = self.channels.write().await;
+        let channel = channels.entry(channel_name.clone()).or_insert_with(|| {
+            let mut channel = Channel::new(channel_name.clone());
+            channel.created_at = remote_ts;
+            channel
+        });
+        
+        let local_ts = channel.created_at;
+        let accept_remote = remote_ts <= local_ts;
+        if remote_ts < local_ts {
+            tracing::info!("Channel {} TS lowered by {} ({} -> {}); clearing local modes", channel_name, server, local_ts.timestamp(), remote_ts.timestamp());
+            channel.reset_modes();
+            channel.created_at = remote_ts;
+        }
+        
+        if accept_remote {
+            for mode in modes.chars() {
+                let param = if matches!(mode, 'f' | 'j' | 'k' | 'l') { mode_params.next().map(String::as_str) } else { None };
+                if !channel.apply_burst_mode(mode, param) {
+                    tracing::warn!("Ignoring +{} without a valid parameter in CBURST for {}", mode, channel_name);
+                }
+            }
+        }
+        
+        let database = &self.database;
+        let mut joined = Vec::new();
+        for member in members.split_whitespace() {
+            let nick = member.trim_start_matches(|c| MEMBER_PREFIXES.iter().any(|(_, prefix)| *prefix == c));
+            let prefixes = &member[..member.len() - nick.len()];
+            let Some(user) = database.get_user_by_nick(nick) else {
+                tracing::warn!("Unknown member {} in CBURST for {} from {}", nick, channel_name, server);
+                continue;
+            };
+            if !channel.has_member(&user.id) {
+                channel.force_add_member(user.id)?;
+                joined.push(user.clone());
+            }
+            if accept_remote {
+                for prefix in prefixes.chars() {
+                    if let Some((mode, _)) = MEMBER_PREFIXES.iter().find(|(_, p)| *p == prefix) {
+                        channel.set_member_status(&user.id, *mode, true)?;
+                    }
+                }
+            }
+        }
+        let local_members: Vec<Uuid> = channel.members.keys()
+            .filter(|member_id| context.client_senders.contains(member_id))
+            .copied()
+            .collect();
+        drop(channels);
+        
+        let broadcast_system = self.broadcast_system.write().await;
+        for user in &joined {
+            broadcast_system.subscribe_to_channel(user.id, channel_name.clone());
+        }
+        drop(broadcast_system);
+        
+        // Local members see the new members join, in one netjoin batch for
+        // clients that support it
+        let joins: Vec<Message> = joined.iter()
+            .map(|user| Message::with_prefix(
+                Prefix::User {
+                    nick: user.nick.clone(),
+                    user: user.username.clone(),
+                    host: user.host.clone(),
+                },
+                MessageType::Join,
+                vec![channel_name.clone()],
+            ))
+            .collect();
+        if !joins.is_empty() {
+            let local_server = context.server_connections.local_server_name();
+            let link = [local_server.to_string(), server.to_string()];
+            for member_id in local_members {
+                if context.client_has_capability(&member_id, "batch") {
+                    context.client_senders.send_batch(&member_id, local_server, "netjoin", &link, &joins);
+                } else {
+                    for join in &joins {
+                        context.client_senders.send(&member_id, join.clone());
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+    
+    /// Merge a list mode burst (`BMASK <ts> <channel> <type> :<masks>`)
+    ///
+    /// Lists from a channel newer than ours are ignored.
+    async fn handle_ban_mask_burst(&self, server: &str, message: &Message) -> Result<()> {
+        if message.params.len() < 4 {
+            return Err(Error::MessageParse("BMASK requires TS, channel, type and masks".to_string()));
+        }
+        let Ok(ts) = message.params[0].parse::<i64>() else {
+            return Err(Error::MessageParse("Invalid TS in BMASK".to_string()));
+        };
+        let mode = message.params[2].chars().next().unwrap_or(' ');
         
-        if let Some(ref key) = channel.key {
-            params.push(key.clone());
+        let mut channels = self.channels.write().await;
+        let Some(channel) = channels.get_mut(&message.params[1]) else {
+            return Ok(());
+        };
+        if ts > channel.created_at.timestamp() {
+            tracing::debug!("Ignoring +{} list for {} from {} (newer TS)", mode, channel.name, server);
+            return Ok(());
+        }
+        if let Some(list) = channel.mask_list_mut(mode) {
+            list.extend(message.params[3].split_whitespace().map(str::to_string));
+        }
+        Ok(())
+    }
+    
+    /// Merge a topic burst (`TB <channel> <topic ts> <setter> :<topic>`)
+    ///
+    /// The remote topic is taken when we have none or theirs is older.
+    async fn handle_topic_burst(&self, server: &str, message: &Message) -> Result<()> {
+        if message.params.len() < 4 {
+            return Err(Error::MessageParse("TB requires channel, TS, setter and topic".to_string()));
         }
+        let Some(set_at) = message.params[1].parse::<i64>().ok().and_then(|ts| DateTime::<Utc>::from_timestamp(ts, 0)) else {
+            return Err(Error::MessageParse("Invalid TS in TB".to_string()));
+        };
         
-        if let Some(limit) = channel.user_limit {
-            params.push(limit.to_string());
+        let mut channels = self.channels.write().await;
+        let Some(channel) = channels.get_mut(&message.params[0]) else {
+            return Ok(());
+        };
+        if channel.topic_time.is_none_or(|local| set_at < local) {
+            channel.topic = Some(message.params[3].clone());
+            channel.topic_setter = Some(message.params[2].clone());
+            channel.topic_time = Some(set_at);
+            tracing::debug!("Took topic for {} from {}", channel.name, server);
+        }
+        Ok(())
+    }
+    
+    /// Apply member status changes relayed from another server
+    ///
+    /// The originating server already checked permissions, so only statuses
+    /// for members known locally are applied.
+    async fn handle_remote_status_modes(&self, server: &str, message: &Message) -> Result<()> {
+        let channel_name = &message.params[0];
+        let Some(mode_string) = message.params.get(1) else {
+            return Ok(());
+        };
+        let mut params = message.params[2..].iter();
+        let mut adding = true;
+        let mut status_changes = Vec::new();
+        for c in mode_string.chars() {
+            match c {
+                '+' => adding = true,
+                '-' => adding = false,
+                _ if Self::is_status_mode(c) => {
+                    if let Some(nick) = params.next() {
+                        status_changes.push((c, adding, nick.clone()));
+                    }
+                }
+                // Skip the parameters of other modes
+                'k' | 'l' | 'b' | 'e' | 'I' | 'q' => { params.next(); }
+                'f' | 'j' if adding => { params.next(); }
+                _ => {}
+            }
         }
         
-        params.join(" ")
+        let mut channels = self.channels.write().await;
+        let Some(channel) = channels.get_mut(channel_name) else {
+            return Ok(());
+        };
+        for (mode, adding, nick) in status_changes {
+            if let Some(target_user) = self.get_user_by_nick(&nick).await? {
+                if channel.has_member(&target_user.id) {
+                    channel.set_member_status(&target_user.id, mode, adding)?;
+                    tracing::debug!("Server {} set {}{} {} on {}", server, if adding { '+' } else { '-' }, mode, nick, channel_name);
+                }
+            }
+        }
+        Ok(())
     }
     
     /// Parse mode string and parameters
@@ -1643,7 +3301,10 @@ impl ChannelModule {
             match c {
                 '+' => adding = true,
                 '-' => adding = false,
-                'o' | 'v' | 'k' | 'l' | 'b' | 'e' | 'I' => {
+                'y' | 'a' | 'h' if !self.prefix_config.is_enabled(c) => {
+                    return Err(Error::User("Unknown mode character".to_string()));
+                }
+                'y' | 'a' | 'o' | 'h' | 'v' | 'k' | 'l' | 'b' | 'e' | 'I' | 'q' => {
                     if adding {
                         add_modes.push(c);
                     } else {
@@ -1656,7 +3317,19 @@ impl ChannelModule {
                         param_idx += 1;
                     }
                 }
-                'i' | 'm' | 'n' | 'p' | 's' | 't' => {
+                'f' | 'j' => {
+                    if adding {
+                        add_modes.push(c);
+                        // The forward channel and throttle are only given when setting
+                        if param_idx < mode_params.len() {
+                            mode_param_map.insert(c, mode_params[param_idx].clone());
+                            param_idx += 1;
+                        }
+                    } else {
+                        remove_modes.push(c);
+                    }
+                }
+                'i' | 'm' | 'n' | 'p' | 's' | 't' | 'C' | 'F' | 'O' | 'P' | 'Q' | 'R' | 'S' => {
                     if adding {
                         add_modes.push(c);
                     } else {
@@ -1829,7 +3502,7 @@ impl ChannelModule {
         
         let broadcast = BroadcastMessage {
             message: invite_message,
-            target: BroadcastTarget::Users(vec![target_user.nick.clone()]),
+            target: BroadcastTarget::Clients(vec![target_user.id]),
             sender: Some(inviter.id),
             priority: BroadcastPriority::Normal,
         };
@@ -1860,11 +3533,40 @@ impl ChannelModule {
         Ok(())
     }
     
+    /// Check whether a user may bypass channel restrictions through oper override
+    fn can_override(&self, user: &User) -> bool {
+        self.override_config.enabled && user.can_override()
+    }
+    
+    /// Log an oper override and announce it to operators and the override log channel
+    async fn announce_override(&self, context: &ModuleContext, user: &User, channel_name: &str, action: &str) -> Result<()> {
+        tracing::warn!("Oper override by {} on {}: {}", user.nick, channel_name, action);
+        if !self.override_config.notify_opers {
+            return Ok(());
+        }
+        
+        let text = format!("{} ({}@{}) used oper override on {}: {}",
+            user.nick, user.username, user.host, channel_name, action);
+        context.send_snotice(SnoMask::OperActions, &text).await?;
+        
+        if let Some(log_channel) = &self.override_config.log_channel {
+            let mut broadcast_system = self.broadcast_system.write().await;
+            broadcast_system.queue_message(BroadcastMessage {
+                message: Message::new(MessageType::Notice, vec![log_channel.clone(), text]),
+                target: BroadcastTarget::Channel(log_channel.clone()),
+                sender: None,
+                priority: BroadcastPriority::High,
+            })?;
+        }
+        
+        Ok(())
+    }
+    
     /// Send error message to a specific user
     async fn send_error_to_user(&self, user_id: Uuid, error_message: Message) -> Result<()> {
         let broadcast = BroadcastMessage {
             message: error_message,
-            target: BroadcastTarget::Users(vec![user_id.to_string()]),
+            target: BroadcastTarget::Clients(vec![user_id]),
             sender: None,
             priority: BroadcastPriority::High,
         };
@@ -1879,7 +3581,7 @@ impl ChannelModule {
     async fn send_reply_to_user(&self, user_id: Uuid, reply_message: Message) -> Result<()> {
         let broadcast = BroadcastMessage {
             message: reply_message,
-            target: BroadcastTarget::Users(vec![user_id.to_string()]),
+            target: BroadcastTarget::Clients(vec![user_id]),
             sender: None,
             priority: BroadcastPriority::Normal,
         };
@@ -1892,3 +3594,719 @@ impl ChannelModule {
 }
 
 // BurstExtension implementation removed - extensions system was removed
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rustircd_core::client::ClientState;
+    use rustircd_core::config::OperatorFlag;
+    use rustircd_core::{Config, ServerConnectionManager};
+    use tokio::sync::mpsc;
+
+    fn registered_client(nick: &str, flags: &[OperatorFlag]) -> (Client, User) {
+        let (tx, _rx) = mpsc::unbounded_channel();
+        let mut client = Client::new(Uuid::new_v4(), "127.0.0.1:50000".to_string(), "127.0.0.1:6667".to_string(), tx);
+        let mut user = User::new(nick.to_string(), nick.to_string(), nick.to_string(), "example.com".to_string(), "irc.example.com".to_string());
+        user.id = client.id;
+        if !flags.is_empty() {
+            user.set_operator_flags(flags.iter().copied().collect());
+        }
+        client.set_state(ClientState::Registered);
+        client.set_user(user.clone());
+        (client, user)
+    }
+
+    #[tokio::test]
+    async fn test_oper_override_join_and_mode() {
+        let module = ChannelModule::new();
+        let context = ModuleContext::new(
+            Arc::new(Database::new(100, 1)),
+            Arc::new(ServerConnectionManager::new(Arc::new(Config::default()))),
+        );
+        let (client, user) = registered_client("alice", &[]);
+        let (oper_client, oper) = registered_client("oper", &[OperatorFlag::GlobalOper, OperatorFlag::Override]);
+        let (plain_oper_client, plain_oper) = registered_client("staff", &[OperatorFlag::GlobalOper]);
+        {
+            let database = &module.database;
+            for user in [user, oper, plain_oper] {
+                database.add_user(user).unwrap();
+            }
+        }
+
+        let mut channel = Channel::new("#locked".to_string());
+        channel.add_mode('i');
+        channel.set_key(Some("secret".to_string()));
+        channel.set_user_limit(Some(0));
+        module.channels.write().await.insert("#locked".to_string(), channel);
+
+        let join = Message::new(MessageType::Join, vec!["#locked".to_string()]);
+        assert!(module.handle_join(&client, &join, &context).await.is_err());
+        assert!(module.handle_join(&plain_oper_client, &join, &context).await.is_err());
+        module.handle_join(&oper_client, &join, &context).await.unwrap();
+        assert!(module.channels.read().await["#locked"].has_member(&oper_client.id));
+
+        // Override also grants mode changes without channel operator status
+        let mode = Message::new(MessageType::Mode, vec!["#locked".to_string(), "-i".to_string()]);
+        module.handle_mode(&oper_client, &mode, &context).await.unwrap();
+        assert!(!module.channels.read().await["#locked"].is_invite_only());
+
+        let disabled = ChannelModule::new().with_override_config(OperOverrideConfig { enabled: false, ..OperOverrideConfig::default() });
+        let oper = oper_client.get_user().unwrap().clone();
+        assert!(!disabled.can_override(&oper));
+    }
+
+    #[tokio::test]
+    async fn test_join_forwarding() {
+        let module = ChannelModule::new();
+        let context = ModuleContext::new(
+            Arc::new(Database::new(100, 1)),
+            Arc::new(ServerConnectionManager::new(Arc::new(Config::default()))),
+        );
+        let (client, user) = registered_client("alice", &[]);
+        let (op_client, op) = registered_client("bob", &[]);
+        {
+            let database = &module.database;
+            database.add_user(user).unwrap();
+            database.add_user(op).unwrap();
+        }
+
+        for name in ["#locked", "#overflow", "#loop"] {
+            let join = Message::new(MessageType::Join, vec![name.to_string()]);
+            module.handle_join(&op_client, &join, &context).await.unwrap();
+        }
+
+        // +f requires channel operator status on the target
+        let op_user = op_client.get_user().unwrap().clone();
+        let set_forward = |target: &str| vec!["+if".to_string(), target.to_string()];
+        module.handle_channel_mode(&op_user, "#locked", &set_forward("#overflow"), &context).await.unwrap();
+        assert_eq!(module.channels.read().await["#locked"].forward.as_deref(), Some("#overflow"));
+        assert!(module.handle_channel_mode(&op_user, "#locked", &set_forward("#nowhere"), &context).await.is_err());
+
+        let join = Message::new(MessageType::Join, vec!["#locked".to_string()]);
+        module.handle_join(&client, &join, &context).await.unwrap();
+        {
+            let channels = module.channels.read().await;
+            assert!(!channels["#locked"].has_member(&client.id));
+            assert!(channels["#overflow"].has_member(&client.id));
+        }
+
+        // Forwards into +Q channels and forwarding loops are refused
+        module.handle_channel_mode(&op_user, "#overflow", &["+iQf".to_string(), "#loop".to_string()], &context).await.unwrap();
+        module.handle_channel_mode(&op_user, "#loop", &set_forward("#overflow"), &context).await.unwrap();
+        let (carol_client, carol) = registered_client("carol", &[]);
+        module.database.add_user(carol).unwrap();
+        let join_loop = Message::new(MessageType::Join, vec!["#loop".to_string()]);
+        assert!(module.handle_join(&carol_client, &join_loop, &context).await.is_err());
+        assert!(module.handle_join(&carol_client, &join, &context).await.is_err());
+
+        let disabled = ChannelModule::new().with_forward_config(ForwardConfig { enabled: false, ..ForwardConfig::default() });
+        assert!(!disabled.forward_config.enabled);
+    }
+
+    #[tokio::test]
+    async fn test_quiet_mode() {
+        let module = ChannelModule::new();
+        let context = ModuleContext::new(
+            Arc::new(Database::new(100, 1)),
+            Arc::new(ServerConnectionManager::new(Arc::new(Config::default()))),
+        );
+        let (client, user) = registered_client("alice", &[]);
+        let (op_client, op) = registered_client("bob", &[]);
+        {
+            let database = &module.database;
+            database.add_user(user.clone()).unwrap();
+            database.add_user(op.clone()).unwrap();
+        }
+        let join = Message::new(MessageType::Join, vec!["#chat".to_string()]);
+        module.handle_join(&op_client, &join, &context).await.unwrap();
+        module.handle_join(&client, &join, &context).await.unwrap();
+
+        // Only channel operators may set quiets; a bare +q is a list query
+        let quiet = vec!["+q".to_string(), "alice!*@*".to_string()];
+        assert!(module.handle_channel_mode(&user, "#chat", &quiet, &context).await.is_err());
+        module.handle_channel_mode(&user, "#chat", &["+q".to_string()], &context).await.unwrap();
+        module.handle_channel_mode(&op, "#chat", &quiet, &context).await.unwrap();
+
+        let privmsg = Message::new(MessageType::PrivMsg, vec!["#chat".to_string(), "hi".to_string()]);
+        assert!(matches!(module.check_channel_moderation(&client, &privmsg, &context).await.unwrap(), ModuleResult::HandledStop));
+        assert!(matches!(module.check_channel_moderation(&op_client, &privmsg, &context).await.unwrap(), ModuleResult::NotHandled));
+
+        // Voice exempts from quiets
+        module.handle_channel_mode(&op, "#chat", &["+v".to_string(), "alice".to_string()], &context).await.unwrap();
+        assert!(matches!(module.check_channel_moderation(&client, &privmsg, &context).await.unwrap(), ModuleResult::NotHandled));
+
+        let channels = module.channels.read().await;
+        let replies = module.list_mode_replies("bob", &channels["#chat"], 'q');
+        assert_eq!(replies.len(), 2);
+        assert_eq!(replies[0].params, vec!["bob", "#chat", "q", "alice!*@*"]);
+    }
+
+    #[tokio::test]
+    async fn test_disconnection_leaves_channels() {
+        let mut module = ChannelModule::new();
+        let context = ModuleContext::new(
+            Arc::new(Database::new(100, 1)),
+            Arc::new(ServerConnectionManager::new(Arc::new(Config::default()))),
+        );
+        let (client, user) = registered_client("alice", &[]);
+        let (bob_client, bob) = registered_client("bob", &[]);
+        {
+            let database = &module.database;
+            database.add_user(user.clone()).unwrap();
+            database.add_user(bob.clone()).unwrap();
+        }
+        let join = |channel: &str| Message::new(MessageType::Join, vec![channel.to_string()]);
+        module.handle_join(&client, &join("#shared"), &context).await.unwrap();
+        module.handle_join(&client, &join("#alone"), &context).await.unwrap();
+        module.handle_join(&bob_client, &join("#shared"), &context).await.unwrap();
+
+        module.handle_user_disconnection(&user, &context).await.unwrap();
+        let channels = module.channels.read().await;
+        assert!(!channels["#shared"].has_member(&user.id));
+        assert!(channels["#shared"].has_member(&bob.id));
+        assert!(!channels.contains_key("#alone"));
+    }
+
+    #[tokio::test]
+    async fn test_topic_handling_and_store() {
+        let store_path = std::env::temp_dir().join(format!("rustircd-topics-{}.json", Uuid::new_v4()));
+        let config = TopicConfig { max_length: 10, store_path: Some(store_path.clone()) };
+        let module = ChannelModule::new().with_topic_config(config.clone());
+        let context = ModuleContext::new(
+            Arc::new(Database::new(100, 1)),
+            Arc::new(ServerConnectionManager::new(Arc::new(Config::default()))),
+        );
+        let (op_client, op) = registered_client("alice", &[]);
+        let (client, user) = registered_client("bob", &[]);
+        {
+            let database = &module.database;
+            database.add_user(op.clone()).unwrap();
+            database.add_user(user.clone()).unwrap();
+        }
+        let join = Message::new(MessageType::Join, vec!["#chat".to_string()]);
+        module.handle_join(&op_client, &join, &context).await.unwrap();
+        module.handle_join(&client, &join, &context).await.unwrap();
+        module.channels.write().await.get_mut("#chat").unwrap().add_mode('t');
+
+        // +t keeps regular members from changing the topic
+        let topic = |text: &str| Message::new(MessageType::Topic, vec!["#chat".to_string(), text.to_string()]);
+        module.handle_topic(&client, &topic("spam")).await.unwrap();
+        assert!(module.channels.read().await["#chat"].topic.is_none());
+
+        module.handle_topic(&op_client, &topic("Welcome to chat")).await.unwrap();
+        let replies = module.topic_replies("bob", &module.channels.read().await["#chat"]);
+        assert_eq!(replies.len(), 2);
+        assert_eq!(replies[0].params, vec!["bob", "#chat", "Welcome to"]);
+        assert_eq!(replies[1].params[2], "alice!alice@example.com");
+
+        // The stored topic comes back when the channel is recreated
+        let part = Message::new(MessageType::Part, vec!["#chat".to_string()]);
+        module.handle_part(&op_client, &part, &context).await.unwrap();
+        module.handle_part(&client, &part, &context).await.unwrap();
+        assert!(!module.channels.read().await.contains_key("#chat"));
+        module.handle_join(&client, &join, &context).await.unwrap();
+        assert_eq!(module.channels.read().await["#chat"].topic.as_deref(), Some("Welcome to"));
+
+        let reloaded = ChannelModule::new().with_topic_config(config);
+        assert_eq!(reloaded.topic_store.read().await["#chat"].topic, "Welcome to");
+        std::fs::remove_file(store_path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_kick_targets_and_hierarchy() {
+        let module = ChannelModule::new();
+        let context = ModuleContext::new(
+            Arc::new(Database::new(100, 1)),
+            Arc::new(ServerConnectionManager::new(Arc::new(Config::default()))),
+        );
+        let clients: Vec<(Client, User)> = ["alice", "bob", "carol", "dave"].iter()
+            .map(|nick| registered_client(nick, &[]))
+            .collect();
+        {
+            let database = &module.database;
+            for (_, user) in &clients {
+                database.add_user(user.clone()).unwrap();
+            }
+        }
+        for (client, _) in &clients {
+            for channel in ["#chat", "#dev"] {
+                module.handle_join(client, &Message::new(MessageType::Join, vec![channel.to_string()]), &context).await.unwrap();
+            }
+        }
+        let (op_client, _) = &clients[0];
+        let (halfop_client, halfop) = &clients[1];
+        module.channels.write().await.get_mut("#chat").unwrap().set_member_status(&halfop.id, 'h', true).unwrap();
+
+        let kick = |channels: &str, nicks: &str| Message::new(MessageType::Kick, vec![channels.to_string(), nicks.to_string()]);
+        // Halfops can't kick operators
+        module.handle_kick(halfop_client, &kick("#chat", "alice"), &context).await.unwrap();
+        assert!(module.channels.read().await["#chat"].has_member(&clients[0].1.id));
+
+        // One channel with several nicks, then channels paired with nicks
+        module.handle_kick(op_client, &kick("#chat", "carol,dave"), &context).await.unwrap();
+        module.handle_kick(op_client, &kick("#chat,#dev", "bob,carol"), &context).await.unwrap();
+        let channels = module.channels.read().await;
+        assert_eq!(channels["#chat"].member_count(), 1);
+        assert_eq!(channels["#dev"].member_count(), 3);
+        drop(channels);
+        assert!(!module.database.get_user_channels("carol").contains(&"#chat".to_string()));
+
+        // Mismatched lists are rejected without kicking anyone
+        module.handle_kick(op_client, &kick("#dev,#chat", "bob,dave,alice"), &context).await.unwrap();
+        assert_eq!(module.channels.read().await["#dev"].member_count(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_join_throttle_and_registered_only() {
+        let module = ChannelModule::new();
+        let context = ModuleContext::new(
+            Arc::new(Database::new(100, 1)),
+            Arc::new(ServerConnectionManager::new(Arc::new(Config::default()))),
+        );
+        let clients: Vec<(Client, User)> = ["alice", "bob", "carol", "dave"].iter()
+            .map(|nick| registered_client(nick, &[]))
+            .collect();
+        {
+            let database = &module.database;
+            for (_, user) in &clients {
+                database.add_user(user.clone()).unwrap();
+            }
+        }
+        let join = Message::new(MessageType::Join, vec!["#chat".to_string()]);
+        let (op_client, op) = &clients[0];
+        module.handle_join(op_client, &join, &context).await.unwrap();
+        let set = |modes: &[&str]| modes.iter().map(|m| m.to_string()).collect::<Vec<_>>();
+        assert!(module.handle_channel_mode(op, "#chat", &set(&["+j", "0:5"]), &context).await.is_ok());
+        assert!(!module.channels.read().await["#chat"].has_mode('j'));
+        module.handle_channel_mode(op, "#chat", &set(&["+j", "1:60"]), &context).await.unwrap();
+
+        // One join per minute
+        module.handle_join(&clients[1].0, &join, &context).await.unwrap();
+        assert!(module.handle_join(&clients[2].0, &join, &context).await.is_err());
+        module.handle_channel_mode(op, "#chat", &set(&["-j"]), &context).await.unwrap();
+
+        // +R admits only identified users
+        module.handle_channel_mode(op, "#chat", &set(&["+R"]), &context).await.unwrap();
+        assert!(module.handle_join(&clients[2].0, &join, &context).await.is_err());
+        let mut identified = clients[3].1.clone();
+        identified.account = Some("dave".to_string());
+        module.database.update_user(&identified.id, identified.clone()).unwrap();
+        module.handle_join(&clients[3].0, &join, &context).await.unwrap();
+
+        // +S admits only users connected over TLS
+        module.handle_channel_mode(op, "#chat", &set(&["-R+S"]), &context).await.unwrap();
+        module.handle_part(&clients[3].0, &Message::new(MessageType::Part, vec!["#chat".to_string()]), &context).await.unwrap();
+        assert!(module.handle_join(&clients[3].0, &join, &context).await.is_err());
+        let mut secure = clients[3].1.clone();
+        secure.modes.insert('z');
+        module.database.update_user(&secure.id, secure.clone()).unwrap();
+        module.handle_join(&clients[3].0, &join, &context).await.unwrap();
+
+        let throttle = JoinThrottle::parse("3:10").unwrap();
+        assert_eq!(throttle.to_string(), "3:10");
+        let mut channel = Channel::new("#flood".to_string());
+        channel.set_join_throttle(Some(throttle));
+        let now = Utc::now();
+        for i in 0..3 {
+            channel.record_join(now + chrono::Duration::seconds(i));
+        }
+        assert!(channel.is_join_throttled(now + chrono::Duration::seconds(5)));
+        assert!(!channel.is_join_throttled(now + chrono::Duration::seconds(11)));
+    }
+
+    #[tokio::test]
+    async fn test_list_pacing_and_cooldown() {
+        let module = ChannelModule::new().with_list_config(SafeListConfig { batch_size: 50, batch_delay_ms: 0, list_cooldown: 30 });
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let (mut client, user) = registered_client("alice", &[]);
+        client.sender = tx;
+        module.database.add_user(user).unwrap();
+        {
+            let mut channels = module.channels.write().await;
+            for i in 0..120 {
+                let name = format!("#chan{}", i);
+                channels.insert(name.clone(), Channel::new(name));
+            }
+        }
+        let list = Message::new(MessageType::List, vec![]);
+
+        // 122 replies go out in three batches from a task of their own
+        module.handle_list(&client, &list).await.unwrap();
+        let mut replies = Vec::new();
+        while replies.len() < 122 {
+            replies.push(tokio::time::timeout(std::time::Duration::from_secs(1), rx.recv()).await.unwrap().unwrap());
+        }
+        assert_eq!(replies[0].command.to_string(), "321");
+        assert_eq!(replies[121].command.to_string(), "323");
+        assert_eq!(replies.iter().filter(|reply| reply.command.to_string() == "322").count(), 120);
+
+        // Listing again within the cooldown is refused
+        module.handle_list(&client, &list).await.unwrap();
+        assert_eq!(rx.recv().await.unwrap().command.to_string(), "263");
+    }
+
+    #[tokio::test]
+    async fn test_oper_only_channels() {
+        let module = ChannelModule::new();
+        let context = ModuleContext::new(
+            Arc::new(Database::new(100, 1)),
+            Arc::new(ServerConnectionManager::new(Arc::new(Config::default()))),
+        );
+        let (op_client, op) = registered_client("alice", &[]);
+        let (oper_client, oper) = registered_client("bob", &[OperatorFlag::GlobalOper]);
+        let (carol_client, carol) = registered_client("carol", &[]);
+        for user in [&op, &oper, &carol] {
+            module.database.add_user(user.clone()).unwrap();
+        }
+        let join = Message::new(MessageType::Join, vec!["#opers".to_string()]);
+        let oper_only = vec!["+O".to_string()];
+        module.handle_join(&op_client, &join, &context).await.unwrap();
+
+        // Channel operators can't set +O, IRC operators can from outside
+        assert!(module.handle_channel_mode(&op, "#opers", &oper_only, &context).await.is_err());
+        module.handle_channel_mode(&oper, "#opers", &oper_only, &context).await.unwrap();
+        assert!(module.channels.read().await["#opers"].has_mode('O'));
+
+        module.handle_join(&oper_client, &join, &context).await.unwrap();
+        assert!(module.handle_join(&carol_client, &join, &context).await.is_err());
+        assert_eq!(module.channels.read().await["#opers"].member_count(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_permanent_channels() {
+        let path = std::env::temp_dir().join(format!("rustircd-permanent-{}.json", Uuid::new_v4()));
+        let database = Database::new(100, 1).with_permanent_channels_file(&path);
+        let module = ChannelModule::with_dependencies(
+            Arc::new(RwLock::new(BroadcastSystem::new())),
+            Arc::new(database),
+        );
+        let context = ModuleContext::new(
+            Arc::new(Database::new(100, 1)),
+            Arc::new(ServerConnectionManager::new(Arc::new(Config::default()))),
+        );
+        let (client, user) = registered_client("alice", &[]);
+        let (_, oper) = registered_client("oper", &[OperatorFlag::GlobalOper]);
+        module.database.add_user(user.clone()).unwrap();
+        let join = Message::new(MessageType::Join, vec!["#home".to_string()]);
+        module.handle_join(&client, &join, &context).await.unwrap();
+
+        // Channel operators can't make a channel permanent; opers can
+        let permanent = vec!["+P".to_string()];
+        assert!(module.handle_channel_mode(&user, "#home", &permanent, &context).await.is_err());
+        module.handle_channel_mode(&oper, "#home", &permanent, &context).await.unwrap();
+        module.handle_channel_mode(&user, "#home", &["+b".to_string(), "*!*@spam".to_string()], &context).await.unwrap();
+
+        module.handle_part(&client, &Message::new(MessageType::Part, vec!["#home".to_string()]), &context).await.unwrap();
+        assert!(module.channels.read().await["#home"].ban_masks.contains("*!*@spam"));
+
+        // A fresh module restores the channel from the saved file
+        let mut restored = ChannelModule::with_dependencies(
+            Arc::new(RwLock::new(BroadcastSystem::new())),
+            Arc::new(Database::new(100, 1).with_permanent_channels_file(&path)),
+        );
+        restored.init().await.unwrap();
+        let channels = restored.channels.read().await;
+        assert!(channels["#home"].has_mode('P'));
+        assert!(channels["#home"].ban_masks.contains("*!*@spam"));
+        drop(channels);
+
+        // Dropping +P from an empty channel destroys it
+        module.handle_channel_mode(&oper, "#home", &["-P".to_string()], &context).await.unwrap();
+        assert!(!module.channels.read().await.contains_key("#home"));
+        assert!(module.database.get_permanent_channels().is_empty());
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_channel_burst_ts_resolution() {
+        let origin = ChannelModule::new();
+        let context = ModuleContext::new(
+            Arc::new(Database::new(100, 1)),
+            Arc::new(ServerConnectionManager::new(Arc::new(Config::default()))),
+        );
+        let (alice_client, alice) = registered_client("alice", &[]);
+        let (bob_client, bob) = registered_client("bob", &[]);
+        {
+            let database = &origin.database;
+            database.add_user(alice.clone()).unwrap();
+            database.add_user(bob.clone()).unwrap();
+        }
+        let join = Message::new(MessageType::Join, vec!["#chat".to_string()]);
+        origin.handle_join(&alice_client, &join, &context).await.unwrap();
+        origin.handle_join(&bob_client, &join, &context).await.unwrap();
+        let modes = ["+vkl", "bob", "secret", "10"].map(String::from);
+        origin.handle_channel_mode(&alice, "#chat", &modes, &context).await.unwrap();
+        origin.handle_channel_mode(&alice, "#chat", &["+b".to_string(), "*!*@spam".to_string()], &context).await.unwrap();
+        origin.channels.write().await.get_mut("#chat").unwrap().created_at = DateTime::<Utc>::from_timestamp(1_000, 0).unwrap();
+
+        let burst = origin.server_burst("hub.example.com").await;
+        assert_eq!(burst[0].command, MessageType::ChannelBurst);
+        assert_eq!(burst[0].params, ["#chat", "1000", "+kl", "secret", "10", "+bob @alice"].map(String::from));
+        assert_eq!(burst[1].params, ["1000", "#chat", "b", "*!*@spam"].map(String::from));
+
+        // The receiving side has a newer #chat: its modes and statuses are dropped
+        let mut remote = ChannelModule::new();
+        let (carol_client, carol) = registered_client("carol", &[]);
+        {
+            let database = &remote.database;
+            for user in [alice, bob.clone(), carol.clone()] {
+                database.add_user(user).unwrap();
+            }
+        }
+        remote.handle_join(&carol_client, &join, &context).await.unwrap();
+        remote.handle_channel_mode(&carol, "#chat", &["+m".to_string()], &context).await.unwrap();
+        for message in &burst {
+            remote.handle_server_message("hub.example.com", message, &context).await.unwrap();
+        }
+        let channels = remote.channels.read().await;
+        let channel = &channels["#chat"];
+        assert_eq!(channel.created_at.timestamp(), 1_000);
+        assert_eq!(channel.modes_string(), "kl");
+        assert_eq!(channel.mode_params(), ["secret", "10"]);
+        assert!(channel.ban_masks.contains("*!*@spam"));
+        assert!(!channel.is_operator(&carol.id));
+        assert!(channel.members[&bob.id].is_voice());
+        drop(channels);
+
+        // A newer burst only adds members
+        let mut newer = burst[0].clone();
+        newer.params = ["#chat", "2000", "+i", "@bob"].map(String::from).to_vec();
+        remote.handle_server_message("hub.example.com", &newer, &context).await.unwrap();
+        let channels = remote.channels.read().await;
+        assert!(!channels["#chat"].has_mode('i'));
+        assert!(!channels["#chat"].is_operator(&bob.id));
+    }
+
+    #[tokio::test]
+    async fn test_remote_join_and_part() {
+        let mut module = ChannelModule::new();
+        let context = ModuleContext::new(
+            Arc::new(Database::new(100, 1)),
+            Arc::new(ServerConnectionManager::new(Arc::new(Config::default()))),
+        );
+        let (client, local) = registered_client("alice", &[]);
+        let mut remote = User::new("bob".to_string(), "bob".to_string(), "Bob".to_string(), "remote.example.com".to_string(), "hub.example.com".to_string());
+        remote.id = Uuid::new_v4();
+        {
+            let database = &module.database;
+            database.add_user(local.clone()).unwrap();
+            database.add_user(remote.clone()).unwrap();
+        }
+        let prefix = Prefix::User { nick: "bob".to_string(), user: "bob".to_string(), host: "remote.example.com".to_string() };
+
+        // A remote user creating a channel gets ops, as on their own server
+        let join = Message::with_prefix(prefix.clone(), MessageType::Join, vec!["#lobby,#chat".to_string()]);
+        module.handle_join(&client, &Message::new(MessageType::Join, vec!["#chat".to_string()]), &context).await.unwrap();
+        module.handle_server_message("hub.example.com", &join, &context).await.unwrap();
+        {
+            let channels = module.channels.read().await;
+            assert!(channels["#lobby"].is_operator(&remote.id));
+            assert!(channels["#chat"].has_member(&remote.id));
+            assert!(!channels["#chat"].is_operator(&remote.id));
+        }
+
+        // Without a known source the JOIN is dropped
+        let unknown = Message::new(MessageType::Join, vec!["#other".to_string()]);
+        module.handle_server_message("hub.example.com", &unknown, &context).await.unwrap();
+        assert!(!module.channels.read().await.contains_key("#other"));
+
+        // UID prefixes resolve too
+        let part = Message::with_prefix(Prefix::Server(remote.id.to_string()), MessageType::Part, vec!["#lobby,#chat".to_string(), "bye".to_string()]);
+        module.handle_server_message("hub.example.com", &part, &context).await.unwrap();
+        let channels = module.channels.read().await;
+        assert!(!channels.contains_key("#lobby"));
+        assert!(!channels["#chat"].has_member(&remote.id));
+        assert!(channels["#chat"].has_member(&local.id));
+    }
+
+    #[tokio::test]
+    async fn test_remote_rename() {
+        let mut module = ChannelModule::new();
+        let context = ModuleContext::new(
+            Arc::new(Database::new(100, 1)),
+            Arc::new(ServerConnectionManager::new(Arc::new(Config::default()))),
+        );
+        let (client, local) = registered_client("alice", &[]);
+        module.database.add_user(local.clone()).unwrap();
+        module.handle_join(&client, &Message::new(MessageType::Join, vec!["#chat".to_string()]), &context).await.unwrap();
+        module.invite_list.write().await.entry("carol".to_string()).or_default().insert("#chat".to_string());
+
+        // The channel, its membership and pending invites move to the new name
+        let prefix = Prefix::User { nick: "bob".to_string(), user: "bob".to_string(), host: "remote.example.com".to_string() };
+        let rename = Message::with_prefix(prefix, MessageType::Custom("RENAME".to_string()), vec!["#chat".to_string(), "#talk".to_string(), String::new()]);
+        module.handle_server_message("hub.example.com", &rename, &context).await.unwrap();
+        {
+            let channels = module.channels.read().await;
+            assert!(!channels.contains_key("#chat"));
+            assert_eq!(channels["#talk"].name, "#talk");
+            assert!(channels["#talk"].is_operator(&local.id));
+        }
+        assert_eq!(module.database.get_user_channels("alice"), ["#talk"]);
+        assert_eq!(module.database.get_channel_users("#talk"), ["alice"]);
+        assert!(module.invite_list.read().await["carol"].contains("#talk"));
+
+        // A rename onto an existing channel is ignored
+        module.handle_join(&client, &Message::new(MessageType::Join, vec!["#other".to_string()]), &context).await.unwrap();
+        let clash = Message::new(MessageType::Custom("RENAME".to_string()), vec!["#other".to_string(), "#talk".to_string()]);
+        module.handle_server_message("hub.example.com", &clash, &context).await.unwrap();
+        assert!(module.channels.read().await.contains_key("#other"));
+    }
+
+    #[tokio::test]
+    async fn test_join_publishes_channel_created() {
+        let module = ChannelModule::new();
+        let context = ModuleContext::new(
+            Arc::new(Database::new(100, 1)),
+            Arc::new(ServerConnectionManager::new(Arc::new(Config::default()))),
+        );
+        let mut events = context.subscribe_events();
+        let (alice_client, alice) = registered_client("alice", &[]);
+        let (bob_client, bob) = registered_client("bob", &[]);
+        {
+            let database = &module.database;
+            database.add_user(alice).unwrap();
+            database.add_user(bob).unwrap();
+        }
+
+        // Only the JOIN that creates the channel is announced
+        let join = Message::new(MessageType::Join, vec!["#new".to_string()]);
+        module.handle_join(&alice_client, &join, &context).await.unwrap();
+        module.handle_join(&bob_client, &join, &context).await.unwrap();
+        match events.try_recv().unwrap() {
+            ServerEvent::ChannelCreated { channel, creator } => {
+                assert_eq!(channel, "#new");
+                assert_eq!(creator, "alice");
+            }
+            other => panic!("unexpected event {:?}", other),
+        }
+        assert!(events.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_prefix_levels() {
+        let module = ChannelModule::new();
+        let context = ModuleContext::new(
+            Arc::new(Database::new(100, 1)),
+            Arc::new(ServerConnectionManager::new(Arc::new(Config::default()))),
+        );
+        let (owner_client, owner) = registered_client("alice", &[]);
+        let (op_client, op) = registered_client("bob", &[]);
+        let (client, user) = registered_client("carol", &[]);
+        {
+            let database = &module.database;
+            database.add_user(owner.clone()).unwrap();
+            database.add_user(op.clone()).unwrap();
+            database.add_user(user.clone()).unwrap();
+        }
+        let join = Message::new(MessageType::Join, vec!["#chat".to_string()]);
+        module.handle_join(&owner_client, &join, &context).await.unwrap();
+        module.handle_join(&op_client, &join, &context).await.unwrap();
+        module.handle_join(&client, &join, &context).await.unwrap();
+        {
+            let mut channels = module.channels.write().await;
+            let channel = channels.get_mut("#chat").unwrap();
+            channel.set_member_status(&owner.id, 'y', true).unwrap();
+            channel.set_member_status(&op.id, 'o', true).unwrap();
+        }
+
+        let modes = |s: &str, nick: &str| vec![s.to_string(), nick.to_string()];
+        // Ops can hand out halfop but not admin, and can't demote the owner
+        module.handle_channel_mode(&op, "#chat", &modes("+h", "carol"), &context).await.unwrap();
+        assert!(module.handle_channel_mode(&op, "#chat", &modes("+a", "carol"), &context).await.is_err());
+        assert!(module.handle_channel_mode(&op, "#chat", &modes("-o", "alice"), &context).await.is_err());
+        // Halfops can voice but not op
+        module.handle_channel_mode(&user, "#chat", &modes("+v", "carol"), &context).await.unwrap();
+        assert!(module.handle_channel_mode(&user, "#chat", &modes("+o", "carol"), &context).await.is_err());
+        module.handle_channel_mode(&owner, "#chat", &modes("+a", "bob"), &context).await.unwrap();
+
+        let channels = module.channels.read().await;
+        let channel = &channels["#chat"];
+        assert!(channel.is_operator(&owner.id));
+        assert_eq!(channel.members[&op.id].prefixes(true), "&@");
+        assert_eq!(channel.members[&user.id].prefixes(true), "%+");
+        assert!(channel.can_speak_moderated(&user.id));
+        drop(channels);
+
+        assert_eq!(module.prefix_config.isupport_token(), "PREFIX=(yaohv)~&@%+");
+        let limited = ChannelModule::new().with_prefix_config(PrefixConfig { owner: false, admin: false, halfop: true });
+        assert_eq!(limited.prefix_config.isupport_token(), "PREFIX=(ohv)@%+");
+        assert!(limited.parse_mode_string("+y", &["alice".to_string()]).is_err());
+    }
+
+    #[test]
+    fn test_extbans() {
+        let module = ChannelModule::new();
+        let (_, mut user) = registered_client("alice", &[]);
+        user.realname = "Spam Bot".to_string();
+        assert!(module.matches_mask(&user, "$r:*bot"));
+        assert!(module.matches_mask(&user, "$~o"));
+        assert!(!module.matches_mask(&user, "$o"));
+        assert!(module.matches_mask(&user, "$s:irc.*"));
+        assert!(module.matches_mask(&user, "$x:alice!*@*#Spam*"));
+        assert!(!module.matches_mask(&user, "$q"));
+
+        // Secure connection extbans
+        assert!(!module.matches_mask(&user, "$z"));
+        assert!(module.matches_mask(&user, "$~z"));
+        user.modes.insert('z');
+        assert!(module.matches_mask(&user, "$z"));
+
+        // Account extbans
+        assert!(!module.matches_mask(&user, "$a"));
+        assert!(module.matches_mask(&user, "$~a"));
+        user.set_account(Some("Alice".to_string()));
+        assert!(module.matches_mask(&user, "$a"));
+        assert!(module.matches_mask(&user, "$a:ali*"));
+        assert!(!module.matches_mask(&user, "$a:bob"));
+    }
+
+    #[test]
+    fn test_names_prefixes_and_wrapping() {
+        let mut member = ChannelMember::new(Uuid::new_v4());
+        assert_eq!(member.prefixes(true), "");
+        member.add_mode('v');
+        member.add_mode('o');
+        assert_eq!(member.prefixes(false), "@");
+        assert_eq!(member.prefixes(true), "@+");
+        assert_eq!(member.rank(), RANK_OP);
+
+        let names: Vec<String> = (0..100).map(|i| format!("nick{:03}!user@host.example.com", i)).collect();
+        let lines = wrap_names(&names, 400);
+        assert!(lines.len() > 1);
+        assert!(lines.iter().all(|line| line.len() <= 400));
+        assert_eq!(lines.join(" ").split(' ').count(), names.len());
+        assert!(wrap_names(&[], 400).is_empty());
+    }
+
+    #[test]
+    fn test_list_filter() {
+        let filter = ListFilter::parse(">1,<4,C>30,#rust*,!#rust-offtopic");
+        assert_eq!(filter.min_users, Some(1));
+        assert_eq!(filter.max_users, Some(4));
+        assert_eq!(filter.created_before_mins, Some(30));
+        assert_eq!(filter.masks, vec!["#rust*".to_string()]);
+        assert_eq!(filter.excluded_masks, vec!["#rust-offtopic".to_string()]);
+
+        let now = Utc::now();
+        let mut channel = Channel::new("#Rust".to_string());
+        channel.created_at = now - chrono::Duration::hours(1);
+        for _ in 0..2 {
+            channel.add_member(Uuid::new_v4()).unwrap();
+        }
+        assert!(filter.matches(&channel, now));
+
+        channel.add_member(Uuid::new_v4()).unwrap();
+        channel.add_member(Uuid::new_v4()).unwrap();
+        assert!(!filter.matches(&channel, now));
+
+        let offtopic = Channel::new("#rust-offtopic".to_string());
+        assert!(!ListFilter::parse("!#rust-offtopic").matches(&offtopic, now));
+
+        // Topic age filters skip channels without a topic
+        let mut topical = Channel::new("#news".to_string());
+        assert!(!ListFilter::parse("T<10").matches(&topical, now));
+        topical.topic_time = Some(now - chrono::Duration::minutes(5));
+        assert!(ListFilter::parse("T<10").matches(&topical, now));
+        assert!(!ListFilter::parse("t>10").matches(&topical, now));
+    }
+}
@@ -6,32 +6,22 @@
 use rustircd_core::{
     async_trait, Client, Error, Message, MessageType, Module,
     ModuleNumericManager, module::{ModuleResult, ModuleStatsResponse, ModuleContext},
-    NumericReply, Result, User
+    NumericReply, Result, User, BanManager, BanEntry, BanType, BanTarget, BanQuery, SnoMask
 };
 use tracing::{debug, info, warn};
-use std::collections::HashMap;
-use tokio::sync::RwLock;
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::sync::Arc;
 use crate::help::{HelpProvider, HelpTopic};
 
 /// DLINE module for DNS line management
 pub struct DlineModule {
     /// DNS lines (DLINE)
-    dlines: RwLock<HashMap<String, DnsLine>>,
+    dlines: Arc<BanManager>,
     /// Configuration
     config: DlineConfig,
 }
 
-/// DNS line entry
-#[derive(Debug, Clone)]
-pub struct DnsLine {
-    pub hostname: String,
-    pub reason: String,
-    pub set_by: String,
-    pub set_time: u64,
-    pub expire_time: Option<u64>,
-    pub is_active: bool,
-}
+/// DNS line entry, stored in the shared ban manager as a `DLINE` ban
+pub type DnsLine = BanEntry;
 
 /// Configuration for DLINE management
 #[derive(Debug, Clone)]
@@ -57,7 +47,7 @@ impl DlineModule {
     /// Create a new DLINE module
     pub fn new() -> Self {
         Self {
-            dlines: RwLock::new(HashMap::new()),
+            dlines: BanManager::with_type(Self::ban_type()),
             config: DlineConfig::default(),
         }
     }
@@ -65,11 +55,16 @@ impl DlineModule {
     /// Create a new DLINE module with custom configuration
     pub fn with_config(config: DlineConfig) -> Self {
         Self {
-            dlines: RwLock::new(HashMap::new()),
+            dlines: BanManager::with_type(Self::ban_type()),
             config,
         }
     }
     
+    /// Ban type registered with the ban manager
+    fn ban_type() -> BanType {
+        BanType::new("DLINE", BanTarget::Host, "D-Lined")
+    }
+    
     /// Handle DLINE command
     async fn handle_dline(&self, client: &Client, user: &User, args: &[String], context: &ModuleContext) -> Result<()> {
         if !user.is_operator() {
@@ -90,7 +85,7 @@ impl DlineModule {
         };
         
         let duration = if args.len() > 2 {
-            self.parse_duration(&args[2])?
+            BanManager::parse_duration(&args[2])?
         } else {
             None
         };
@@ -118,9 +113,6 @@ impl DlineModule {
     
     /// Add a DLINE
     async fn add_dline(&self, client: &Client, user: &User, hostname: &str, reason: &str, duration: Option<u64>, context: &ModuleContext) -> Result<()> {
-        let current_time = self.get_current_time();
-        let expire_time = duration.map(|d| current_time + d);
-
         if let Some(dur) = duration {
             if dur > self.config.max_duration {
                 client.send_numeric(NumericReply::ErrInvalidDuration, &[&format!("Maximum duration is {} seconds", self.config.max_duration)])?;
@@ -128,23 +120,15 @@ impl DlineModule {
             }
         }
 
-        let dline = DnsLine {
-            hostname: hostname.to_string(),
-            reason: reason.to_string(),
-            set_by: user.nickname().to_string(),
-            set_time: current_time,
-            expire_time,
-            is_active: true,
-        };
+        let dline = BanEntry::new("DLINE", hostname, reason, &user.nickname(), duration);
 
-        let mut dlines = self.dlines.write().await;
-        dlines.insert(hostname.to_string(), dline);
+        self.dlines.add_ban(dline)?;
 
         client.send_numeric(NumericReply::RplDline, &[hostname, reason, &format!("Set by {}", user.nickname())])?;
 
         info!("DLINE added: {} by {} - {}", hostname, user.nickname(), reason);
 
-        // Broadcast notification to all operators
+        // Notify operators subscribed to ban notices
         let duration_str = if let Some(dur) = duration {
             format!("temporary {} min. ", dur / 60)
         } else {
@@ -152,7 +136,7 @@ impl DlineModule {
         };
         let notice = format!("{} is adding a {}D-Line for [{}] [{}]",
             user.nickname(), duration_str, hostname, reason);
-        self.send_to_operators(context, &notice).await?;
+        context.send_snotice(SnoMask::Bans, &notice).await?;
 
         // Broadcast to other servers
         self.broadcast_dline_to_servers(hostname, reason, &user.nickname(), duration, context).await?;
@@ -165,16 +149,13 @@ impl DlineModule {
     
     /// Remove a DLINE
     async fn remove_dline(&self, client: &Client, user: &User, hostname: &str, context: &ModuleContext) -> Result<()> {
-        let mut dlines = self.dlines.write().await;
-
-        if dlines.remove(hostname).is_some() {
+        if self.dlines.remove_ban("DLINE", hostname).is_some() {
             client.send_numeric(NumericReply::RplDline, &[hostname, "Removed", &format!("Removed by {}", user.nickname())])?;
             info!("DLINE removed: {} by {}", hostname, user.nickname());
 
-            // Broadcast notification to all operators
+            // Notify operators subscribed to ban notices
             let notice = format!("{} has removed the D-Line for [{}]", user.nickname(), hostname);
-            drop(dlines); // Release the lock before async call
-            self.send_to_operators(context, &notice).await?;
+            context.send_snotice(SnoMask::Bans, &notice).await?;
 
             // Broadcast removal to other servers
             self.broadcast_undline_to_servers(hostname, &user.nickname(), context).await?;
@@ -187,14 +168,14 @@ impl DlineModule {
     
     /// List DLINEs
     async fn list_dlines(&self, client: &Client, _user: &User) -> Result<()> {
-        let dlines = self.dlines.read().await;
+        let dlines = self.dlines.list_bans("DLINE");
         
         if dlines.is_empty() {
             client.send_numeric(NumericReply::RplDline, &["*", "No DLINEs set"])?;
             return Ok(());
         }
         
-        for dline in dlines.values() {
+        for dline in &dlines {
             let expire_info = if let Some(expire) = dline.expire_time {
                 format!("Expires: {}", self.format_time(expire))
             } else {
@@ -202,7 +183,7 @@ impl DlineModule {
             };
             
             client.send_numeric(NumericReply::RplDline, &[
-                &dline.hostname, 
+                &dline.mask, 
                 &dline.reason, 
                 &format!("Set by {} at {} - {}", dline.set_by, self.format_time(dline.set_time), expire_info)
             ])?;
@@ -212,47 +193,6 @@ impl DlineModule {
         Ok(())
     }
     
-    /// Parse duration string (e.g., "1d", "2h", "30m", "3600s")
-    fn parse_duration(&self, duration_str: &str) -> Result<Option<u64>> {
-        if duration_str == "0" || duration_str.is_empty() {
-            return Ok(None);
-        }
-        
-        let duration_str = duration_str.to_lowercase();
-        let (number_str, unit) = if duration_str.ends_with('d') {
-            (&duration_str[..duration_str.len()-1], "d")
-        } else if duration_str.ends_with('h') {
-            (&duration_str[..duration_str.len()-1], "h")
-        } else if duration_str.ends_with('m') {
-            (&duration_str[..duration_str.len()-1], "m")
-        } else if duration_str.ends_with('s') {
-            (&duration_str[..duration_str.len()-1], "s")
-        } else {
-            (duration_str.as_str(), "s")
-        };
-        
-        let number: u64 = number_str.parse()
-            .map_err(|_| "Invalid duration number")?;
-        
-        let seconds = match unit {
-            "d" => number * 86400,
-            "h" => number * 3600,
-            "m" => number * 60,
-            "s" => number,
-            _ => return Err(Error::Config("Invalid duration unit".to_string())),
-        };
-        
-        Ok(Some(seconds))
-    }
-    
-    /// Get current time as Unix timestamp
-    fn get_current_time(&self) -> u64 {
-        SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap_or_default()
-            .as_secs()
-    }
-    
     /// Format time as readable string
     fn format_time(&self, timestamp: u64) -> String {
         use chrono::{DateTime, Utc};
@@ -263,18 +203,15 @@ impl DlineModule {
     
     /// Check if a user matches any active DLINEs
     pub async fn check_user_dline(&self, user: &User) -> Option<String> {
-        let current_time = self.get_current_time();
-        
-        let dlines = self.dlines.read().await;
-        for dline in dlines.values() {
-            if dline.is_active && user.hostname().contains(&dline.hostname) {
-                if dline.expire_time.map_or(true, |expire| current_time < expire) {
-                    return Some(format!("DLINE: {}", dline.reason));
-                }
-            }
-        }
-        
-        None
+        self.dlines.list_bans("DLINE")
+            .into_iter()
+            .find(|dline| self.matches_mask(&dline.mask, user))
+            .map(|dline| format!("DLINE: {}", dline.reason))
+    }
+    
+    /// Check if a user matches a ban hostname
+    fn matches_mask(&self, hostname: &str, user: &User) -> bool {
+        BanManager::matches(BanTarget::Host, hostname, &BanQuery::from_user(user))
     }
     
     /// Clean up expired DLINEs
@@ -283,17 +220,7 @@ impl DlineModule {
             return Ok(());
         }
         
-        let current_time = self.get_current_time();
-        let mut expired_count = 0;
-        
-        let mut dlines = self.dlines.write().await;
-        dlines.retain(|_, dline| {
-            let should_keep = dline.expire_time.map_or(true, |expire| current_time < expire);
-            if !should_keep {
-                expired_count += 1;
-            }
-            should_keep
-        });
+        let expired_count = self.dlines.cleanup_expired_type("DLINE").len();
         
         if expired_count > 0 {
             info!("Cleaned up {} expired DLINEs", expired_count);
@@ -304,18 +231,12 @@ impl DlineModule {
     
     /// Get count of active DLINEs
     pub async fn get_active_dlines_count(&self) -> usize {
-        let dlines = self.dlines.read().await;
-        dlines.len()
+        self.dlines.active_count("DLINE")
     }
     
     /// Get count of expired DLINEs
     pub async fn get_expired_dlines_count(&self) -> usize {
-        let current_time = self.get_current_time();
-        let dlines = self.dlines.read().await;
-        
-        dlines.values().filter(|dline| {
-            dline.expire_time.map_or(false, |expire| current_time >= expire)
-        }).count()
+        self.dlines.expired_count("DLINE")
     }
     
     /// Broadcast DLINE to other servers
@@ -339,7 +260,7 @@ impl DlineModule {
         // Find all users that match the ban hostname
         for (user_id, client) in client_connections.iter() {
             if let Some(user) = client.get_user() {
-                if user.hostname().contains(hostname) {
+                if self.matches_mask(hostname, user) {
                     users_to_disconnect.push((*user_id, user.clone()));
                 }
             }
@@ -392,24 +313,6 @@ impl DlineModule {
         Ok(())
     }
 
-    /// Send a notice to all operators
-    async fn send_to_operators(&self, context: &ModuleContext, notice: &str) -> Result<()> {
-        let client_connections = context.client_connections.read().await;
-
-        for client in client_connections.values() {
-            if let Some(user) = client.get_user() {
-                if user.is_operator() {
-                    let notice_msg = Message::new(
-                        MessageType::Notice,
-                        vec!["*".to_string(), notice.to_string()]
-                    );
-                    let _ = client.send(notice_msg);
-                }
-            }
-        }
-
-        Ok(())
-    }
     
     /// Handle DLINE message from another server
     async fn handle_server_dline(&self, server: &str, params: &[String], context: &ModuleContext) -> Result<()> {
@@ -422,30 +325,20 @@ impl DlineModule {
         let reason = &params[1];
         let set_by = if params.len() > 2 { &params[2] } else { "unknown" };
         let duration = if params.len() > 3 {
-            self.parse_duration(&params[3]).ok().flatten()
+            BanManager::parse_duration(&params[3]).ok().flatten()
         } else {
             None
         };
         
-        let current_time = self.get_current_time();
-        let expire_time = duration.map(|d| current_time + d);
-        
-        let dline = DnsLine {
-            hostname: hostname.to_string(),
-            reason: reason.to_string(),
-            set_by: set_by.to_string(),
-            set_time: current_time,
-            expire_time,
-            is_active: true,
-        };
-        
-        let mut dlines = self.dlines.write().await;
-        dlines.insert(hostname.to_string(), dline);
+        let dline = BanEntry::new("DLINE", hostname, reason, set_by, duration);
+        if let Err(e) = self.dlines.add_ban(dline) {
+            warn!("Invalid DLINE from server {}: {}", server, e);
+            return Ok(());
+        }
         
         info!("DLINE received from server {}: {} - {}", server, hostname, reason);
         
         // Check existing connections and disconnect matching users
-        drop(dlines); // Release the lock before async call
         self.disconnect_matching_users(hostname, &format!("DLINE: {}", reason), context).await?;
         
         Ok(())
@@ -461,8 +354,7 @@ impl DlineModule {
         let hostname = &params[0];
         let removed_by = if params.len() > 1 { &params[1] } else { "unknown" };
         
-        let mut dlines = self.dlines.write().await;
-        if dlines.remove(hostname).is_some() {
+        if self.dlines.remove_ban("DLINE", hostname).is_some() {
             info!("UNDLINE received from server {}: {} removed by {}", server, hostname, removed_by);
         } else {
             debug!("UNDLINE received from server {} for non-existent DLINE: {}", server, hostname);
@@ -599,6 +491,12 @@ impl Module for DlineModule {
         Ok(())
     }
 
+    fn register_ban_types(&mut self, ban_manager: Arc<BanManager>) -> Result<()> {
+        ban_manager.register_type(Self::ban_type());
+        self.dlines = ban_manager;
+        Ok(())
+    }
+
     async fn cleanup(&mut self) -> Result<()> {
         info!("DLINE module cleaned up");
         Ok(())
@@ -679,15 +577,12 @@ mod tests {
     }
     
     #[test]
-    fn test_parse_duration() {
+    fn test_hostname_matching() {
         let module = DlineModule::new();
+        let user = User::new("bob".to_string(), "bob".to_string(), "Bob".to_string(), "dsl.badhost.com".to_string(), "irc.example.com".to_string());
         
-        assert_eq!(module.parse_duration("1d").unwrap(), Some(86400));
-        assert_eq!(module.parse_duration("2h").unwrap(), Some(7200));
-        assert_eq!(module.parse_duration("30m").unwrap(), Some(1800));
-        assert_eq!(module.parse_duration("3600s").unwrap(), Some(3600));
-        assert_eq!(module.parse_duration("3600").unwrap(), Some(3600));
-        assert_eq!(module.parse_duration("0").unwrap(), None);
-        assert_eq!(module.parse_duration("").unwrap(), None);
+        assert!(module.matches_mask("badhost.com", &user));
+        assert!(module.matches_mask("*.badhost.com", &user));
+        assert!(!module.matches_mask("host.com", &user));
     }
 }
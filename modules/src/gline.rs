@@ -6,32 +6,22 @@
 use rustircd_core::{
     async_trait, Client, Error, Message, MessageType, Module,
     ModuleNumericManager, module::{ModuleResult, ModuleStatsResponse, ModuleContext},
-    NumericReply, Result, User
+    NumericReply, Result, User, BanManager, BanEntry, BanType, BanTarget, BanQuery, SnoMask, AuditEvent
 };
 use tracing::{debug, info, warn};
-use std::collections::HashMap;
-use tokio::sync::RwLock;
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::sync::Arc;
 use crate::help::{HelpProvider, HelpTopic};
 
 /// GLINE module for global ban management
 pub struct GlineModule {
     /// Global bans (GLINE)
-    glines: RwLock<HashMap<String, GlobalBan>>,
+    glines: Arc<BanManager>,
     /// Configuration
     config: GlineConfig,
 }
 
-/// Global ban entry
-#[derive(Debug, Clone)]
-pub struct GlobalBan {
-    pub mask: String,
-    pub reason: String,
-    pub set_by: String,
-    pub set_time: u64,
-    pub expire_time: Option<u64>,
-    pub is_active: bool,
-}
+/// Global ban entry, stored in the shared ban manager as a `GLINE` ban
+pub type GlobalBan = BanEntry;
 
 /// Configuration for GLINE management
 #[derive(Debug, Clone)]
@@ -57,7 +47,7 @@ impl GlineModule {
     /// Create a new GLINE module
     pub fn new() -> Self {
         Self {
-            glines: RwLock::new(HashMap::new()),
+            glines: BanManager::with_type(Self::ban_type()),
             config: GlineConfig::default(),
         }
     }
@@ -65,11 +55,16 @@ impl GlineModule {
     /// Create a new GLINE module with custom configuration
     pub fn with_config(config: GlineConfig) -> Self {
         Self {
-            glines: RwLock::new(HashMap::new()),
+            glines: BanManager::with_type(Self::ban_type()),
             config,
         }
     }
     
+    /// Ban type registered with the ban manager
+    fn ban_type() -> BanType {
+        BanType::new("GLINE", BanTarget::UserHost, "G-Lined").global()
+    }
+    
     /// Handle GLINE command
     async fn handle_gline(&self, client: &Client, user: &User, args: &[String], context: &ModuleContext) -> Result<()> {
         if !user.is_operator() {
@@ -91,7 +86,7 @@ impl GlineModule {
         
         // Parse duration if provided
         let duration = if args.len() > 2 {
-            self.parse_duration(&args[2])?
+            BanManager::parse_duration(&args[2])?
         } else {
             None
         };
@@ -119,9 +114,6 @@ impl GlineModule {
     
     /// Add a GLINE
     async fn add_gline(&self, client: &Client, user: &User, mask: &str, reason: &str, duration: Option<u64>, context: &ModuleContext) -> Result<()> {
-        let current_time = self.get_current_time();
-        let expire_time = duration.map(|d| current_time + d);
-
         // Check duration limits
         if let Some(dur) = duration {
             if dur > self.config.max_duration {
@@ -130,23 +122,15 @@ impl GlineModule {
             }
         }
 
-        let gline = GlobalBan {
-            mask: mask.to_string(),
-            reason: reason.to_string(),
-            set_by: user.nickname().to_string(),
-            set_time: current_time,
-            expire_time,
-            is_active: true,
-        };
+        let gline = BanEntry::new("GLINE", mask, reason, &user.nickname(), duration);
 
-        let mut glines = self.glines.write().await;
-        glines.insert(mask.to_string(), gline);
+        self.glines.add_ban(gline.clone())?;
 
         client.send_numeric(NumericReply::RplGline, &[mask, reason, &format!("Set by {}", user.nickname())])?;
 
         info!("GLINE added: {} by {} - {}", mask, user.nickname(), reason);
 
-        // Broadcast notification to all operators
+        // Notify operators subscribed to ban notices
         let duration_str = if let Some(dur) = duration {
             format!("temporary {} min. ", dur / 60)
         } else {
@@ -154,10 +138,11 @@ impl GlineModule {
         };
         let notice = format!("{} is adding a {}G-Line for [{}] [{}]",
             user.nickname(), duration_str, mask, reason);
-        self.send_to_operators(context, &notice).await?;
+        context.send_snotice(SnoMask::Bans, &notice).await?;
+        context.audit(AuditEvent::privileged(user, "GLINE", mask).with_reason(reason)).await?;
 
         // Broadcast to other servers
-        self.broadcast_gline_to_servers(mask, reason, &user.nickname(), duration, context).await?;
+        self.broadcast_gline_to_servers(&gline, context).await?;
 
         // Check existing connections and disconnect matching users
         self.disconnect_matching_users(mask, &format!("G-Lined: {}", reason), context).await?;
@@ -167,16 +152,14 @@ impl GlineModule {
     
     /// Remove a GLINE
     async fn remove_gline(&self, client: &Client, user: &User, mask: &str, context: &ModuleContext) -> Result<()> {
-        let mut glines = self.glines.write().await;
-
-        if glines.remove(mask).is_some() {
+        if self.glines.remove_ban("GLINE", mask).is_some() {
             client.send_numeric(NumericReply::RplGline, &[mask, "Removed", &format!("Removed by {}", user.nickname())])?;
             info!("GLINE removed: {} by {}", mask, user.nickname());
 
-            // Broadcast notification to all operators
+            // Notify operators subscribed to ban notices
             let notice = format!("{} has removed the G-Line for [{}]", user.nickname(), mask);
-            drop(glines); // Release the lock before async call
-            self.send_to_operators(context, &notice).await?;
+            context.send_snotice(SnoMask::Bans, &notice).await?;
+            context.audit(AuditEvent::privileged(user, "UNGLINE", mask)).await?;
 
             // Broadcast removal to other servers
             self.broadcast_ungline_to_servers(mask, &user.nickname(), context).await?;
@@ -189,14 +172,14 @@ impl GlineModule {
     
     /// List GLINEs
     async fn list_glines(&self, client: &Client, _user: &User) -> Result<()> {
-        let glines = self.glines.read().await;
+        let glines = self.glines.list_bans("GLINE");
         
         if glines.is_empty() {
             client.send_numeric(NumericReply::RplGline, &["*", "No GLINEs set"])?;
             return Ok(());
         }
         
-        for gline in glines.values() {
+        for gline in &glines {
             let expire_info = if let Some(expire) = gline.expire_time {
                 format!("Expires: {}", self.format_time(expire))
             } else {
@@ -214,47 +197,6 @@ impl GlineModule {
         Ok(())
     }
     
-    /// Parse duration string (e.g., "1d", "2h", "30m", "3600s")
-    fn parse_duration(&self, duration_str: &str) -> Result<Option<u64>> {
-        if duration_str == "0" || duration_str.is_empty() {
-            return Ok(None);
-        }
-        
-        let duration_str = duration_str.to_lowercase();
-        let (number_str, unit) = if duration_str.ends_with('d') {
-            (&duration_str[..duration_str.len()-1], "d")
-        } else if duration_str.ends_with('h') {
-            (&duration_str[..duration_str.len()-1], "h")
-        } else if duration_str.ends_with('m') {
-            (&duration_str[..duration_str.len()-1], "m")
-        } else if duration_str.ends_with('s') {
-            (&duration_str[..duration_str.len()-1], "s")
-        } else {
-            (duration_str.as_str(), "s")
-        };
-        
-        let number: u64 = number_str.parse()
-            .map_err(|_| "Invalid duration number")?;
-        
-        let seconds = match unit {
-            "d" => number * 86400,
-            "h" => number * 3600,
-            "m" => number * 60,
-            "s" => number,
-            _ => return Err(Error::Config("Invalid duration unit".to_string())),
-        };
-        
-        Ok(Some(seconds))
-    }
-    
-    /// Get current time as Unix timestamp
-    fn get_current_time(&self) -> u64 {
-        SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap_or_default()
-            .as_secs()
-    }
-    
     /// Format time as readable string
     fn format_time(&self, timestamp: u64) -> String {
         use chrono::{DateTime, Utc};
@@ -265,61 +207,15 @@ impl GlineModule {
     
     /// Check if a user matches any active GLINEs
     pub async fn check_user_gline(&self, user: &User) -> Option<String> {
-        let current_time = self.get_current_time();
-        
-        let glines = self.glines.read().await;
-        for gline in glines.values() {
-            if gline.is_active && self.matches_mask(&gline.mask, user) {
-                if gline.expire_time.map_or(true, |expire| current_time < expire) {
-                    return Some(format!("GLINE: {}", gline.reason));
-                }
-            }
-        }
-        
-        None
+        self.glines.list_bans("GLINE")
+            .into_iter()
+            .find(|gline| self.matches_mask(&gline.mask, user))
+            .map(|gline| format!("GLINE: {}", gline.reason))
     }
     
     /// Check if a user matches a ban mask
     fn matches_mask(&self, mask: &str, user: &User) -> bool {
-        // Simple wildcard matching - in a real implementation, this would be more sophisticated
-        let user_mask = format!("{}!{}@{}", user.nickname(), user.username(), user.hostname());
-        
-        // Convert IRC wildcards to regex patterns
-        let pattern = mask
-            .replace("*", ".*")
-            .replace("?", ".");
-        
-        // Simple pattern matching - in production, use proper regex
-        if mask.contains('*') || mask.contains('?') {
-            // Wildcard matching
-            self.simple_wildcard_match(&pattern, &user_mask)
-        } else {
-            // Exact match
-            mask == user_mask || mask == user.nickname() || mask == user.hostname()
-        }
-    }
-    
-    /// Simple wildcard matching
-    fn simple_wildcard_match(&self, pattern: &str, text: &str) -> bool {
-        // Very basic wildcard matching - in production, use proper regex
-        if pattern == ".*" {
-            return true;
-        }
-        
-        if pattern.starts_with(".*") && pattern.ends_with(".*") {
-            let middle = &pattern[2..pattern.len()-2];
-            return text.contains(middle);
-        }
-        
-        if pattern.starts_with(".*") {
-            return text.ends_with(&pattern[2..]);
-        }
-        
-        if pattern.ends_with(".*") {
-            return text.starts_with(&pattern[..pattern.len()-2]);
-        }
-        
-        text == pattern
+        BanManager::matches(BanTarget::UserHost, mask, &BanQuery::from_user(user))
     }
     
     /// Clean up expired GLINEs
@@ -328,17 +224,7 @@ impl GlineModule {
             return Ok(());
         }
         
-        let current_time = self.get_current_time();
-        let mut expired_count = 0;
-        
-        let mut glines = self.glines.write().await;
-        glines.retain(|_, gline| {
-            let should_keep = gline.expire_time.map_or(true, |expire| current_time < expire);
-            if !should_keep {
-                expired_count += 1;
-            }
-            should_keep
-        });
+        let expired_count = self.glines.cleanup_expired_type("GLINE").len();
         
         if expired_count > 0 {
             info!("Cleaned up {} expired GLINEs", expired_count);
@@ -349,30 +235,19 @@ impl GlineModule {
     
     /// Get count of active GLINEs
     pub async fn get_active_glines_count(&self) -> usize {
-        let glines = self.glines.read().await;
-        glines.len()
+        self.glines.active_count("GLINE")
     }
     
     /// Get count of expired GLINEs
     pub async fn get_expired_glines_count(&self) -> usize {
-        let current_time = self.get_current_time();
-        let glines = self.glines.read().await;
-        
-        glines.values().filter(|gline| {
-            gline.expire_time.map_or(false, |expire| current_time >= expire)
-        }).count()
+        self.glines.expired_count("GLINE")
     }
     
     /// Broadcast GLINE to other servers
-    async fn broadcast_gline_to_servers(&self, mask: &str, reason: &str, set_by: &str, duration: Option<u64>, context: &ModuleContext) -> Result<()> {
-        let mut params = vec![mask.to_string(), reason.to_string(), set_by.to_string()];
-        if let Some(dur) = duration {
-            params.push(dur.to_string());
-        }
-        
-        let message = Message::new(MessageType::Custom("GLINE".to_string()), params);
+    async fn broadcast_gline_to_servers(&self, gline: &GlobalBan, context: &ModuleContext) -> Result<()> {
+        let message = Message::new(MessageType::Custom("GLINE".to_string()), gline.to_params());
         context.broadcast_to_servers(message).await?;
-        info!("GLINE broadcasted to servers: {} {} {} {:?}", mask, reason, set_by, duration);
+        info!("GLINE broadcasted to servers: {} {} {} {:?}", gline.mask, gline.reason, gline.set_by, gline.expire_time);
         Ok(())
     }
     
@@ -430,31 +305,13 @@ impl GlineModule {
     async fn broadcast_ungline_to_servers(&self, mask: &str, removed_by: &str, context: &ModuleContext) -> Result<()> {
         let message = Message::new(
             MessageType::Custom("UNGLINE".to_string()),
-            vec![mask.to_string(), removed_by.to_string()]
+            vec![mask.to_string(), removed_by.to_string(), chrono::Utc::now().timestamp().to_string()]
         );
         context.broadcast_to_servers(message).await?;
         info!("UNGLINE broadcasted to servers: {} removed by {}", mask, removed_by);
         Ok(())
     }
 
-    /// Send a notice to all operators
-    async fn send_to_operators(&self, context: &ModuleContext, notice: &str) -> Result<()> {
-        let client_connections = context.client_connections.read().await;
-
-        for client in client_connections.values() {
-            if let Some(user) = client.get_user() {
-                if user.is_operator() {
-                    let notice_msg = Message::new(
-                        MessageType::Notice,
-                        vec!["*".to_string(), notice.to_string()]
-                    );
-                    let _ = client.send(notice_msg);
-                }
-            }
-        }
-
-        Ok(())
-    }
     
     /// Handle GLINE message from another server
     async fn handle_server_gline(&self, server: &str, params: &[String], context: &ModuleContext) -> Result<()> {
@@ -463,41 +320,41 @@ impl GlineModule {
             return Ok(());
         }
         
-        let mask = &params[0];
-        let reason = &params[1];
-        let set_by = if params.len() > 2 { &params[2] } else { "unknown" };
-        let duration = if params.len() > 3 {
-            self.parse_duration(&params[3]).ok().flatten()
-        } else {
-            None
+        let gline = match BanEntry::from_params("GLINE", params) {
+            Some(gline) => gline,
+            None => {
+                warn!("Invalid GLINE message from server {}: malformed parameters", server);
+                return Ok(());
+            }
         };
         
-        let current_time = self.get_current_time();
-        let expire_time = duration.map(|d| current_time + d);
+        // The most recently set G-line wins; an equal or newer copy means we already have it
+        if let Some(existing) = self.glines.get_ban("GLINE", &gline.mask) {
+            if existing.set_time >= gline.set_time {
+                debug!("Ignoring GLINE for {} from server {}: have one set at {}", gline.mask, server, existing.set_time);
+                return Ok(());
+            }
+        }
         
-        let gline = GlobalBan {
-            mask: mask.to_string(),
-            reason: reason.to_string(),
-            set_by: set_by.to_string(),
-            set_time: current_time,
-            expire_time,
-            is_active: true,
-        };
+        if let Err(e) = self.glines.add_ban(gline.clone()) {
+            warn!("Invalid GLINE from server {}: {}", server, e);
+            return Ok(());
+        }
         
-        let mut glines = self.glines.write().await;
-        glines.insert(mask.to_string(), gline);
+        info!("GLINE received from server {}: {} - {}", server, gline.mask, gline.reason);
         
-        info!("GLINE received from server {}: {} - {}", server, mask, reason);
+        // Pass it on to the rest of the network
+        let message = Message::new(MessageType::Custom("GLINE".to_string()), gline.to_params());
+        context.server_connections.broadcast_message(&message, Some(server)).await?;
         
         // Check existing connections and disconnect matching users
-        drop(glines); // Release the lock before async call
-        self.disconnect_matching_users(mask, &format!("GLINE: {}", reason), context).await?;
+        self.disconnect_matching_users(&gline.mask, &format!("G-Lined: {}", gline.reason), context).await?;
         
         Ok(())
     }
     
     /// Handle UNGLINE message from another server
-    async fn handle_server_ungline(&self, server: &str, params: &[String], _context: &ModuleContext) -> Result<()> {
+    async fn handle_server_ungline(&self, server: &str, params: &[String], context: &ModuleContext) -> Result<()> {
         if params.is_empty() {
             warn!("Invalid UNGLINE message from server {}: no parameters", server);
             return Ok(());
@@ -505,10 +362,22 @@ impl GlineModule {
         
         let mask = &params[0];
         let removed_by = if params.len() > 1 { &params[1] } else { "unknown" };
+        let removed_at = params.get(2).and_then(|t| t.parse::<u64>().ok());
         
-        let mut glines = self.glines.write().await;
-        if glines.remove(mask).is_some() {
+        // A G-line set after the removal was issued survives it
+        if let (Some(existing), Some(removed_at)) = (self.glines.get_ban("GLINE", mask), removed_at) {
+            if existing.set_time > removed_at {
+                debug!("Ignoring UNGLINE for {} from server {}: G-line was reset at {}", mask, server, existing.set_time);
+                return Ok(());
+            }
+        }
+        
+        if self.glines.remove_ban("GLINE", mask).is_some() {
             info!("UNGLINE received from server {}: {} removed by {}", server, mask, removed_by);
+            
+            // Pass it on to the rest of the network
+            let message = Message::new(MessageType::Custom("UNGLINE".to_string()), params.to_vec());
+            context.server_connections.broadcast_message(&message, Some(server)).await?;
         } else {
             debug!("UNGLINE received from server {} for non-existent GLINE: {}", server, mask);
         }
@@ -644,6 +513,12 @@ impl Module for GlineModule {
         Ok(())
     }
 
+    fn register_ban_types(&mut self, ban_manager: Arc<BanManager>) -> Result<()> {
+        ban_manager.register_type(Self::ban_type());
+        self.glines = ban_manager;
+        Ok(())
+    }
+
     async fn cleanup(&mut self) -> Result<()> {
         info!("GLINE module cleaned up");
         Ok(())
@@ -724,26 +599,42 @@ mod tests {
     }
     
     #[test]
-    fn test_parse_duration() {
+    fn test_mask_matching() {
         let module = GlineModule::new();
+        let user = User::new("bob".to_string(), "bob".to_string(), "Bob".to_string(), "dsl.badhost.com".to_string(), "irc.example.com".to_string());
         
-        assert_eq!(module.parse_duration("1d").unwrap(), Some(86400));
-        assert_eq!(module.parse_duration("2h").unwrap(), Some(7200));
-        assert_eq!(module.parse_duration("30m").unwrap(), Some(1800));
-        assert_eq!(module.parse_duration("3600s").unwrap(), Some(3600));
-        assert_eq!(module.parse_duration("3600").unwrap(), Some(3600));
-        assert_eq!(module.parse_duration("0").unwrap(), None);
-        assert_eq!(module.parse_duration("").unwrap(), None);
+        assert!(module.matches_mask("*@*.badhost.com", &user));
+        assert!(module.matches_mask("bob!*@*", &user));
+        assert!(!module.matches_mask("alice@*", &user));
+        assert!(!module.matches_mask("*@goodhost.com", &user));
     }
     
-    #[test]
-    fn test_wildcard_matching() {
-        let module = GlineModule::new();
+    #[tokio::test]
+    async fn test_remote_gline_newest_wins() {
+        use rustircd_core::{Config, Database, ServerConnectionManager};
         
-        // Note: This is a simplified test - real implementation would use proper regex
-        assert!(module.simple_wildcard_match(".*", "anything"));
-        assert!(module.simple_wildcard_match("test.*", "test123"));
-        assert!(module.simple_wildcard_match(".*test", "123test"));
-        assert!(!module.simple_wildcard_match("test", "notest"));
+        let module = GlineModule::new();
+        let context = ModuleContext::new(
+            Arc::new(Database::new(100, 1)),
+            Arc::new(ServerConnectionManager::new(Arc::new(Config::default()))),
+        );
+        let params = |reason: &str, set_time: u64| vec![
+            "*@spam.example.com".to_string(),
+            reason.to_string(),
+            "oper".to_string(),
+            "0".to_string(),
+            set_time.to_string(),
+        ];
+        
+        module.handle_server_gline("hub.test", &params("newer", 2000), &context).await.unwrap();
+        module.handle_server_gline("leaf.test", &params("older", 1000), &context).await.unwrap();
+        assert_eq!(module.glines.get_ban("GLINE", "*@spam.example.com").unwrap().reason, "newer");
+        
+        // A removal issued before the G-line was set doesn't remove it
+        let ungline = |removed_at: u64| vec!["*@spam.example.com".to_string(), "oper".to_string(), removed_at.to_string()];
+        module.handle_server_ungline("leaf.test", &ungline(1500), &context).await.unwrap();
+        assert!(module.glines.get_ban("GLINE", "*@spam.example.com").is_some());
+        module.handle_server_ungline("hub.test", &ungline(2500), &context).await.unwrap();
+        assert!(module.glines.get_ban("GLINE", "*@spam.example.com").is_none());
     }
 }
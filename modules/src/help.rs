@@ -4,7 +4,7 @@
 //! Based on Ratbox's m_help.c module.
 
 use rustircd_core::{
-    async_trait, Client, Message, MessageType, Module, ModuleManager,
+    async_trait, Client, CommandInfo, CommandSpec, Message, Module, ModuleManager,
     NumericReply, Result, User, ModuleNumericManager, ModuleNumericClient, Server,
     module::{ModuleResult, ModuleStatsResponse, ModuleContext},
     define_module_numerics
@@ -297,6 +297,17 @@ impl HelpModule {
             "core"
         ));
         
+        self.add_user_topic(help_topic!(
+            "RULES",
+            "RULES",
+            "Show the network's rules",
+            false,
+            vec![
+                "RULES".to_string(),
+            ],
+            "core"
+        ));
+        
         self.add_user_topic(help_topic!(
             "PING",
             "PING <server>",
@@ -354,6 +365,18 @@ impl HelpModule {
             "core"
         ));
         
+        self.add_oper_topic(help_topic!(
+            "REHASH",
+            "REHASH [CONFIG|MOTD|RULES|TLS|MODULES]",
+            "Reload the configuration file and report what changed, or reload the MOTD, rules, TLS certificate or modules",
+            true,
+            vec![
+                "REHASH".to_string(),
+                "REHASH MOTD".to_string(),
+            ],
+            "core"
+        ));
+        
         self.add_oper_topic(help_topic!(
             "WALLOPS",
             "WALLOPS :<message>",
@@ -404,12 +427,23 @@ impl HelpModule {
         
         self.add_oper_topic(help_topic!(
             "LINKS",
-            "LINKS [<server>]",
+            "LINKS [<mask>]",
             "List server links",
             true,
             vec![
                 "LINKS".to_string(),
-                "LINKS irc.example.com".to_string(),
+                "LINKS *.example.com".to_string(),
+            ],
+            "core"
+        ));
+        
+        self.add_oper_topic(help_topic!(
+            "MAP",
+            "MAP",
+            "Show the network tree with user counts",
+            true,
+            vec![
+                "MAP".to_string(),
             ],
             "core"
         ));
@@ -596,6 +630,28 @@ impl HelpModule {
         self.discover_module_help().await
     }
     
+    /// Add topics for registered commands that have no written help
+    ///
+    /// Commands registered with the module manager are listed by HELP even
+    /// when their module doesn't document them.
+    fn add_registered_commands(&mut self, commands: &[CommandInfo]) {
+        for command in commands {
+            let name = &command.spec.name;
+            if self.general_help.contains_key(name) || self.user_help.contains_key(name) || self.oper_help.contains_key(name) {
+                continue;
+            }
+            let module = command.module.as_deref().unwrap_or("core");
+            self.dynamic_help.insert(name.clone(), Self::create_topic(
+                name,
+                name,
+                &format!("Provided by the {} module", module),
+                command.spec.oper_only,
+                Vec::new(),
+                module,
+            ));
+        }
+    }
+    
     /// Get all available commands for a user
     fn get_available_commands(&self, is_oper: bool) -> Vec<&HelpTopic> {
         let mut commands = Vec::new();
@@ -774,22 +830,20 @@ impl Module for HelpModule {
         Ok(())
     }
 
-    async fn handle_message(&mut self, client: &Client, message: &Message, _context: &ModuleContext) -> Result<ModuleResult> {
-        // Get user from client
-        let user = match &client.user {
-            Some(u) => u,
-            None => return Ok(ModuleResult::NotHandled), // Not registered yet
-        };
+    async fn handle_message(&mut self, _client: &Client, _message: &Message, _context: &ModuleContext) -> Result<ModuleResult> {
+        Ok(ModuleResult::NotHandled)
+    }
 
-        match message.command {
-            MessageType::Custom(ref cmd) if cmd == "HELP" => {
-                self.handle_help(client, user, &message.params).await?;
-                Ok(ModuleResult::Handled)
-            }
-            _ => {
-                Ok(ModuleResult::NotHandled)
-            }
-        }
+    fn commands(&self) -> Vec<CommandSpec> {
+        vec![CommandSpec::new("HELP", 0)]
+    }
+
+    async fn handle_command(&mut self, client: &Client, message: &Message, context: &ModuleContext) -> Result<()> {
+        let Some(user) = client.get_user() else {
+            return Ok(());
+        };
+        self.add_registered_commands(&context.commands());
+        self.handle_help(client, user, &message.params).await
     }
     
     async fn cleanup(&mut self) -> Result<()> {
@@ -895,4 +949,21 @@ mod tests {
         assert!(user_commands.iter().any(|c| c.command == "JOIN"));
         assert!(oper_commands.iter().any(|c| c.command == "KILL"));
     }
+    
+    #[test]
+    fn test_registered_commands_listed() {
+        let mut module = HelpModule::new();
+        let registered = |spec: CommandSpec| CommandInfo { spec, module: Some("example".to_string()) };
+        module.add_registered_commands(&[
+            registered(CommandSpec::new("FROB", 1)),
+            registered(CommandSpec::new("PURGE", 1).oper_only()),
+            registered(CommandSpec::new("JOIN", 1)),
+        ]);
+        
+        assert!(module.get_help("FROB", false).is_some_and(|topic| topic.module_name.as_deref() == Some("example")));
+        assert!(module.get_help("PURGE", false).is_none());
+        assert!(module.get_help("PURGE", true).is_some());
+        // Written help wins over the generated topic
+        assert_eq!(module.get_help("JOIN", false).unwrap().module_name.as_deref(), Some("core"));
+    }
 }
@@ -30,13 +30,19 @@ impl AccountTracking {
         Ok(())
     }
     
-    pub async fn handle_user_registration(&self, user: &User) -> Result<()> {
-        tracing::info!("User {} registered (account tracking)", user.nick);
+    /// Track the account a user logged in to while registering, e.g. with SASL
+    pub async fn handle_user_registration(&mut self, user: &User) -> Result<()> {
+        if let Some(account) = &user.account {
+            // Another connection logged in to the same account still registers
+            if let Err(e) = self.set_user_account(user.id, account.clone()) {
+                tracing::debug!("Not tracking account {} for {}: {}", account, user.nick, e);
+            }
+        }
         Ok(())
     }
     
-    pub async fn handle_user_disconnection(&self, user: &User) -> Result<()> {
-        tracing::info!("User {} disconnected (account tracking)", user.nick);
+    pub async fn handle_user_disconnection(&mut self, user: &User) -> Result<()> {
+        self.remove_user_account(user.id);
         Ok(())
     }
     
@@ -1,6 +1,6 @@
 //! IRCv3 Capability Negotiation (CAP)
 
-use rustircd_core::{Client, Message, Error, Result};
+use rustircd_core::{Client, Message, Error, Result, module::ModuleContext};
 use std::collections::HashSet;
 
 /// Capability negotiation handler
@@ -24,7 +24,7 @@ impl CapabilityNegotiation {
         capabilities.insert("away-notify".to_string());
         capabilities.insert("batch".to_string());
         capabilities.insert("bot-mode".to_string());
-        capabilities.insert("channel-rename".to_string());
+        capabilities.insert("draft/channel-rename".to_string());
         capabilities.insert("chghost".to_string());
         capabilities.insert("echo-message".to_string());
         capabilities.insert("extended-join".to_string());
@@ -52,7 +52,7 @@ impl CapabilityNegotiation {
         Ok(())
     }
     
-    pub async fn handle_cap(&self, client: &Client, message: &Message) -> Result<()> {
+    pub async fn handle_cap(&mut self, client: &Client, message: &Message, context: &ModuleContext) -> Result<()> {
         if message.params.is_empty() {
             return Err(Error::User("No CAP subcommand specified".to_string()));
         }
@@ -64,7 +64,7 @@ impl CapabilityNegotiation {
                 self.handle_cap_ls(client, message).await?;
             }
             "REQ" => {
-                self.handle_cap_req(client, message).await?;
+                self.handle_cap_req(client, message, context).await?;
             }
             "ACK" => {
                 self.handle_cap_ack(client, message).await?;
@@ -104,7 +104,7 @@ impl CapabilityNegotiation {
         Ok(())
     }
     
-    async fn handle_cap_req(&self, client: &Client, message: &Message) -> Result<()> {
+    async fn handle_cap_req(&mut self, client: &Client, message: &Message, context: &ModuleContext) -> Result<()> {
         if message.params.len() < 2 {
             return Err(Error::User("No capabilities specified".to_string()));
         }
@@ -113,14 +113,41 @@ impl CapabilityNegotiation {
         let mut acked_caps = Vec::new();
         let mut nacked_caps = Vec::new();
         
+        let mut enabled = Vec::new();
+        let mut disabled = Vec::new();
         for cap in requested_caps {
-            if self.capabilities.contains(cap) {
+            let (removing, name) = match cap.strip_prefix('-') {
+                Some(name) => (true, name),
+                None => (false, cap),
+            };
+            if self.capabilities.contains(name) {
                 acked_caps.push(cap);
+                if removing {
+                    disabled.push(name.to_string());
+                } else {
+                    enabled.push(name.to_string());
+                }
             } else {
                 nacked_caps.push(cap);
             }
         }
         
+        // Remember what the client has, so features like typing
+        // notifications only reach clients that asked for them; other
+        // modules look it up through the context
+        for cap in &enabled {
+            context.set_client_capability(client.id, cap, true);
+        }
+        for cap in &disabled {
+            context.set_client_capability(client.id, cap, false);
+        }
+        if !enabled.is_empty() {
+            self.enable_capabilities(client.id, &enabled);
+        }
+        if !disabled.is_empty() {
+            self.disable_capabilities(client.id, &disabled);
+        }
+        
         // Send ACK for supported capabilities
         if !acked_caps.is_empty() {
             let ack_msg = Message::new(
@@ -223,6 +250,11 @@ impl CapabilityNegotiation {
             .unwrap_or(false)
     }
     
+    /// Forget a disconnected client's capabilities
+    pub fn forget_client(&mut self, client_id: &uuid::Uuid) {
+        self.client_capabilities.remove(client_id);
+    }
+    
     /// Get all enabled capabilities for a client
     pub fn get_client_capabilities(&self, client_id: &uuid::Uuid) -> Vec<String> {
         self.client_capabilities
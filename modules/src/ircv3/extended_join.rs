@@ -54,7 +54,7 @@ impl ExtendedJoin {
         let realname = real_name.unwrap_or("*");
         params.push(realname.to_string());
         
-        let message = Message::with_prefix(
+        let mut message = Message::with_prefix(
             rustircd_core::Prefix::User {
                 nick: client.nickname().unwrap_or("unknown").to_string(),
                 user: client.username().unwrap_or("unknown").to_string(),
@@ -64,6 +64,11 @@ impl ExtendedJoin {
             params,
         );
         
+        // Bots (+B) carry the bot tag so clients can mark the account
+        if let Some(tag) = client.user.as_ref().and_then(|user| user.get_bot_tag()) {
+            message.set_tag(&tag, "");
+        }
+        
         Ok(message)
     }
     
@@ -101,36 +106,15 @@ impl ExtendedJoin {
         }
     }
     
-    /// Get account name from user data (placeholder implementation for backward compatibility)
+    /// Account the client's user is logged in to
     pub fn get_account_name(&self, client: &Client) -> Option<String> {
-        if let Some(user) = &client.user {
-            tracing::debug!("Checking account status for user {} (basic mode)", user.nickname());
-        }
-        
-        // Return None to indicate no account (basic mode without context)
-        None
+        client.user.as_ref().and_then(|user| user.account.clone())
     }
     
-    /// Get account name from user data using ModuleContext and account tracking
-    /// This integrates with the account_tracking module to get actual account information
+    /// Account of the client's user as stored in the database
     pub async fn get_account_name_from_tracking(&self, client: &Client, context: &ModuleContext) -> Option<String> {
-        if let Some(user) = &client.user {
-            // In a full implementation, this would:
-            // 1. Access the account_tracking module from the context
-            // 2. Query for the user's account status
-            // 3. Return the account name if logged in
-            
-            // For now, we can check if user exists in database and has metadata
-            if let Some(_db_user) = context.get_user_by_nick(&user.nick) {
-                // This would integrate with account_tracking:
-                // let account_tracking = context.get_module("account_tracking")?;
-                // return account_tracking.get_user_account(&user.id);
-                
-                tracing::debug!("Checking account status for user {} via context", user.nickname());
-            }
-        }
-        
-        None
+        let user = client.user.as_ref()?;
+        context.database.get_user(&user.id).and_then(|user| user.account)
     }
     
     /// Get real name from user data
@@ -1,12 +1,23 @@
 //! IRCv3 Message Tags
 
-use rustircd_core::{Client, Message, Error, Result, module::ModuleContext};
-use std::collections::HashMap;
+use rustircd_core::{Client, Message, MessageType, Prefix, Error, Result, module::ModuleContext};
+use rustircd_core::config::TypingConfig;
+use super::capability_negotiation::CapabilityNegotiation;
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+/// Client-only tags that carry typing notifications
+const TYPING_TAGS: [&str; 2] = ["+typing", "+draft/typing"];
 
 /// Message tags handler
 pub struct MessageTags {
     /// Supported message tags
     supported_tags: HashMap<String, String>,
+    /// Typing notification limits
+    typing_config: TypingConfig,
+    /// When each client sent its recent typing notifications
+    typing_history: HashMap<Uuid, VecDeque<Instant>>,
 }
 
 impl MessageTags {
@@ -26,9 +37,17 @@ impl MessageTags {
         
         Self {
             supported_tags,
+            typing_config: TypingConfig::default(),
+            typing_history: HashMap::new(),
         }
     }
     
+    /// Set the typing notification limits
+    pub fn with_typing_config(mut self, config: TypingConfig) -> Self {
+        self.typing_config = config;
+        self
+    }
+    
     pub async fn init(&mut self) -> Result<()> {
         tracing::info!("Initializing message tags");
         Ok(())
@@ -39,9 +58,14 @@ impl MessageTags {
         Ok(())
     }
     
-    pub async fn handle_tagmsg(&self, client: &Client, message: &Message, context: &ModuleContext) -> Result<()> {
-        if !client.supports_ircv3() {
-            return Err(Error::User("Client does not support IRCv3".to_string()));
+    /// Relay a TAGMSG to a user or a channel's local members
+    ///
+    /// Only client-only (`+`) tags are relayed, and only to clients that
+    /// negotiated message-tags. Typing notifications over the sender's limit
+    /// are dropped without an error.
+    pub async fn handle_tagmsg(&mut self, client: &Client, message: &Message, capabilities: &CapabilityNegotiation, context: &ModuleContext) -> Result<()> {
+        if !capabilities.client_has_capability(&client.id, "message-tags") {
+            return Err(Error::User("Client does not support message-tags".to_string()));
         }
         
         if message.params.is_empty() {
@@ -49,33 +73,73 @@ impl MessageTags {
         }
         
         let target = &message.params[0];
+        let sender = context.database.get_user(&client.id)
+            .ok_or_else(|| Error::User("User not found".to_string()))?;
         
-        // Process message tags
-        let tags = if let Some(ref prefix) = message.prefix {
-            Self::parse_tags(&prefix.to_string())
-        } else {
-            HashMap::new()
-        };
-        
-        tracing::info!("Client {} sent TAGMSG to {} with tags: {:?}", client.id, target, tags);
+        let tags: Vec<(String, String)> = message.tags.iter()
+            .filter(|(key, _)| key.starts_with('+'))
+            .cloned()
+            .collect();
+        if tags.is_empty() {
+            return Ok(());
+        }
+        if tags.iter().any(|(key, _)| TYPING_TAGS.contains(&key.as_str())) && !self.allow_typing(client.id, Instant::now()) {
+            tracing::debug!("Dropped typing notification from {} to {}", sender.nick, target);
+            return Ok(());
+        }
         
         // Check if target is a channel or user
-        if target.starts_with('#') || target.starts_with('&') {
-            // Send to channel
-            context.send_to_channel(target, message.clone()).await?;
+        let recipients: Vec<Uuid> = if target.starts_with('#') || target.starts_with('&') {
+            context.get_channel_users(target).into_iter()
+                .filter_map(|nick| context.get_user_by_nick(&nick))
+                .map(|user| user.id)
+                .filter(|id| *id != client.id)
+                .collect()
+        } else if let Some(user) = context.get_user_by_nick(target) {
+            vec![user.id]
         } else {
-            // Send to user
-            if let Some(_user) = context.get_user_by_nick(target) {
-                context.send_to_user(target, message.clone()).await?;
-            } else {
-                return Err(Error::User(format!("No such nick: {}", target)));
-            }
-        }
+            return Err(Error::User(format!("No such nick: {}", target)));
+        };
+        
+        let mut relayed = Message::with_prefix(
+            Prefix::User {
+                nick: sender.nick.clone(),
+                user: sender.username.clone(),
+                host: sender.host.clone(),
+            },
+            MessageType::Custom("TAGMSG".to_string()),
+            vec![target.clone()],
+        );
+        relayed.tags = tags;
+        let recipients = recipients.into_iter().filter(|id| capabilities.client_has_capability(id, "message-tags"));
+        context.client_senders.send_each(recipients, &relayed);
         
         tracing::debug!("Forwarded TAGMSG from {} to target {}", client.id, target);
         Ok(())
     }
     
+    /// Count a typing notification against the client's limit
+    fn allow_typing(&mut self, client_id: Uuid, now: Instant) -> bool {
+        if !self.typing_config.enabled {
+            return false;
+        }
+        let window = Duration::from_secs(self.typing_config.window_secs);
+        let history = self.typing_history.entry(client_id).or_default();
+        while history.front().is_some_and(|sent| now.duration_since(*sent) >= window) {
+            history.pop_front();
+        }
+        if history.len() >= self.typing_config.max_per_window {
+            return false;
+        }
+        history.push_back(now);
+        true
+    }
+    
+    /// Forget a disconnected client's typing history
+    pub fn forget_client(&mut self, client_id: &Uuid) {
+        self.typing_history.remove(client_id);
+    }
+    
     /// Parse message tags from a message prefix
     pub fn parse_tags(prefix: &str) -> HashMap<String, String> {
         let mut tags = HashMap::new();
@@ -128,34 +192,35 @@ impl MessageTags {
         self.supported_tags.get(tag)
     }
     
-    /// Implement adding tags to messages
-    /// TODO: Integrate with Message struct tag support
+    /// Add a tag to a message, replacing any existing value
     pub fn add_tag(message: &mut Message, key: &str, value: &str) {
-        // Implement tag addition to messages
-        // In production, this would:
-        // 1. Validate tag key and value format
-        // 2. Add tag to message's tag collection
-        // 3. Update message serialization to include tags
-        
-        tracing::debug!("Adding tag {}={} to message", key, value);
-        
-        // In production, would update message structure:
-        // message.tags.insert(key.to_string(), value.to_string());
-        // message.update_tag_prefix();
+        message.set_tag(key, value);
     }
     
-    /// Implement removing tags from messages
-    /// TODO: Integrate with Message struct tag support
+    /// Remove a tag from a message
     pub fn remove_tag(message: &mut Message, key: &str) {
-        // Implement tag removal from messages
-        // In production, this would:
-        // 1. Remove tag from message's tag collection
-        // 2. Update message serialization to exclude removed tag
-        
-        tracing::debug!("Removing tag {} from message", key);
-        
-        // In production, would update message structure:
-        // message.tags.remove(key);
-        // message.update_tag_prefix();
+        message.remove_tag(key);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_typing_rate_limit() {
+        let mut tags = MessageTags::new().with_typing_config(TypingConfig { enabled: true, max_per_window: 2, window_secs: 10 });
+        let client = Uuid::new_v4();
+        let start = Instant::now();
+        assert!(tags.allow_typing(client, start));
+        assert!(tags.allow_typing(client, start + Duration::from_secs(1)));
+        assert!(!tags.allow_typing(client, start + Duration::from_secs(2)));
+
+        // Other clients have their own allowance, and the window slides
+        assert!(tags.allow_typing(Uuid::new_v4(), start + Duration::from_secs(2)));
+        assert!(tags.allow_typing(client, start + Duration::from_secs(10)));
+
+        tags.typing_config.enabled = false;
+        assert!(!tags.allow_typing(Uuid::new_v4(), start));
     }
 }
@@ -48,7 +48,7 @@ impl Ircv3Module {
         capabilities.insert("away-notify".to_string());
         capabilities.insert("batch".to_string());
         capabilities.insert("bot-mode".to_string());
-        capabilities.insert("channel-rename".to_string());
+        capabilities.insert("draft/channel-rename".to_string());
         capabilities.insert("chghost".to_string());
         capabilities.insert("echo-message".to_string());
         capabilities.insert("extended-join".to_string());
@@ -76,6 +76,12 @@ impl Ircv3Module {
             sasl_capability: Arc::new(Mutex::new(sasl_capability::SaslCapability::new())),
         }
     }
+    
+    /// Set the typing notification limits
+    pub fn with_typing_config(mut self, config: rustircd_core::config::TypingConfig) -> Self {
+        self.message_tags = self.message_tags.with_typing_config(config);
+        self
+    }
 }
 
 #[async_trait]
@@ -208,13 +214,13 @@ impl Module for Ircv3Module {
     async fn handle_message(&mut self, client: &Client, message: &Message, context: &ModuleContext) -> Result<ModuleResult> {
         match &message.command {
             rustircd_core::MessageType::Cap => {
-                self.capability_negotiation.handle_cap(client, message).await?;
+                self.capability_negotiation.handle_cap(client, message, context).await?;
                 Ok(ModuleResult::Handled)
             }
             rustircd_core::MessageType::Custom(cmd) => {
                 match cmd.as_str() {
                     "TAGMSG" => {
-                        self.message_tags.handle_tagmsg(client, message, context).await?;
+                        self.message_tags.handle_tagmsg(client, message, &self.capability_negotiation, context).await?;
                         Ok(ModuleResult::Handled)
                     }
                     "AUTHENTICATE" => {
@@ -238,21 +244,25 @@ impl Module for Ircv3Module {
         Ok(())
     }
     
-    async fn handle_user_disconnection(&mut self, user: &User, _context: &ModuleContext) -> Result<()> {
+    async fn handle_user_disconnection(&mut self, user: &User, context: &ModuleContext) -> Result<()> {
         self.account_tracking.handle_user_disconnection(user).await?;
         self.away_notification.handle_user_disconnection(user).await?;
+        self.capability_negotiation.forget_client(&user.id);
+        context.forget_client_capabilities(&user.id);
+        self.message_tags.forget_client(&user.id);
         Ok(())
     }
     
     fn get_capabilities(&self) -> Vec<String> {
         vec![
             "message_handler".to_string(),
+            "user_handler".to_string(),
             "capability_negotiation".to_string(),
         ]
     }
     
     fn supports_capability(&self, capability: &str) -> bool {
-        matches!(capability, "message_handler" | "capability_negotiation")
+        matches!(capability, "message_handler" | "user_handler" | "capability_negotiation")
     }
     
     fn get_numeric_replies(&self) -> Vec<u16> {
@@ -6,32 +6,22 @@
 use rustircd_core::{
     async_trait, Client, Error, Message, MessageType, Module,
     ModuleNumericManager, module::{ModuleResult, ModuleStatsResponse, ModuleContext},
-    NumericReply, Result, User
+    NumericReply, Result, User, BanManager, BanEntry, BanType, BanTarget, BanQuery, SnoMask
 };
 use tracing::{debug, info, warn};
-use std::collections::HashMap;
-use tokio::sync::RwLock;
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::sync::Arc;
 use crate::help::{HelpProvider, HelpTopic};
 
 /// KLINE module for kill line management
 pub struct KlineModule {
     /// Kill lines (KLINE) 
-    klines: RwLock<HashMap<String, KillLine>>,
+    klines: Arc<BanManager>,
     /// Configuration
     config: KlineConfig,
 }
 
-/// Kill line entry
-#[derive(Debug, Clone)]
-pub struct KillLine {
-    pub mask: String,
-    pub reason: String,
-    pub set_by: String,
-    pub set_time: u64,
-    pub expire_time: Option<u64>,
-    pub is_active: bool,
-}
+/// Kill line entry, stored in the shared ban manager as a `KLINE` ban
+pub type KillLine = BanEntry;
 
 /// Configuration for KLINE management
 #[derive(Debug, Clone)]
@@ -57,7 +47,7 @@ impl KlineModule {
     /// Create a new KLINE module
     pub fn new() -> Self {
         Self {
-            klines: RwLock::new(HashMap::new()),
+            klines: BanManager::with_type(Self::ban_type()),
             config: KlineConfig::default(),
         }
     }
@@ -65,11 +55,16 @@ impl KlineModule {
     /// Create a new KLINE module with custom configuration
     pub fn with_config(config: KlineConfig) -> Self {
         Self {
-            klines: RwLock::new(HashMap::new()),
+            klines: BanManager::with_type(Self::ban_type()),
             config,
         }
     }
     
+    /// Ban type registered with the ban manager
+    fn ban_type() -> BanType {
+        BanType::new("KLINE", BanTarget::UserHost, "K-Lined")
+    }
+    
     /// Handle KLINE command
     async fn handle_kline(&self, client: &Client, user: &User, args: &[String], context: &ModuleContext) -> Result<()> {
         if !user.is_operator() {
@@ -90,7 +85,7 @@ impl KlineModule {
         };
         
         let duration = if args.len() > 2 {
-            self.parse_duration(&args[2])?
+            BanManager::parse_duration(&args[2])?
         } else {
             None
         };
@@ -118,9 +113,6 @@ impl KlineModule {
     
     /// Add a KLINE
     async fn add_kline(&self, client: &Client, user: &User, mask: &str, reason: &str, duration: Option<u64>, context: &ModuleContext) -> Result<()> {
-        let current_time = self.get_current_time();
-        let expire_time = duration.map(|d| current_time + d);
-
         if let Some(dur) = duration {
             if dur > self.config.max_duration {
                 client.send_numeric(NumericReply::ErrInvalidDuration, &[&format!("Maximum duration is {} seconds", self.config.max_duration)])?;
@@ -128,23 +120,15 @@ impl KlineModule {
             }
         }
 
-        let kline = KillLine {
-            mask: mask.to_string(),
-            reason: reason.to_string(),
-            set_by: user.nickname().to_string(),
-            set_time: current_time,
-            expire_time,
-            is_active: true,
-        };
+        let kline = BanEntry::new("KLINE", mask, reason, &user.nickname(), duration);
 
-        let mut klines = self.klines.write().await;
-        klines.insert(mask.to_string(), kline);
+        self.klines.add_ban(kline)?;
 
         client.send_numeric(NumericReply::RplKline, &[mask, reason, &format!("Set by {}", user.nickname())])?;
 
         info!("KLINE added: {} by {} - {}", mask, user.nickname(), reason);
 
-        // Broadcast notification to all operators
+        // Notify operators subscribed to ban notices
         let duration_str = if let Some(dur) = duration {
             format!("temporary {} min. ", dur / 60)
         } else {
@@ -152,7 +136,7 @@ impl KlineModule {
         };
         let notice = format!("{} is adding a {}K-Line for [{}] [{}]",
             user.nickname(), duration_str, mask, reason);
-        self.send_to_operators(context, &notice).await?;
+        context.send_snotice(SnoMask::Bans, &notice).await?;
 
         // Broadcast to other servers
         self.broadcast_kline_to_servers(mask, reason, &user.nickname(), duration, context).await?;
@@ -165,16 +149,13 @@ impl KlineModule {
     
     /// Remove a KLINE
     async fn remove_kline(&self, client: &Client, user: &User, mask: &str, context: &ModuleContext) -> Result<()> {
-        let mut klines = self.klines.write().await;
-
-        if klines.remove(mask).is_some() {
+        if self.klines.remove_ban("KLINE", mask).is_some() {
             client.send_numeric(NumericReply::RplKline, &[mask, "Removed", &format!("Removed by {}", user.nickname())])?;
             info!("KLINE removed: {} by {}", mask, user.nickname());
 
-            // Broadcast notification to all operators
+            // Notify operators subscribed to ban notices
             let notice = format!("{} has removed the K-Line for [{}]", user.nickname(), mask);
-            drop(klines); // Release the lock before async call
-            self.send_to_operators(context, &notice).await?;
+            context.send_snotice(SnoMask::Bans, &notice).await?;
 
             // Broadcast removal to other servers
             self.broadcast_unkline_to_servers(mask, &user.nickname(), context).await?;
@@ -187,14 +168,14 @@ impl KlineModule {
     
     /// List KLINEs
     async fn list_klines(&self, client: &Client, _user: &User) -> Result<()> {
-        let klines = self.klines.read().await;
+        let klines = self.klines.list_bans("KLINE");
         
         if klines.is_empty() {
             client.send_numeric(NumericReply::RplKline, &["*", "No KLINEs set"])?;
             return Ok(());
         }
         
-        for kline in klines.values() {
+        for kline in &klines {
             let expire_info = if let Some(expire) = kline.expire_time {
                 format!("Expires: {}", self.format_time(expire))
             } else {
@@ -212,47 +193,6 @@ impl KlineModule {
         Ok(())
     }
     
-    /// Parse duration string (e.g., "1d", "2h", "30m", "3600s")
-    fn parse_duration(&self, duration_str: &str) -> Result<Option<u64>> {
-        if duration_str == "0" || duration_str.is_empty() {
-            return Ok(None);
-        }
-        
-        let duration_str = duration_str.to_lowercase();
-        let (number_str, unit) = if duration_str.ends_with('d') {
-            (&duration_str[..duration_str.len()-1], "d")
-        } else if duration_str.ends_with('h') {
-            (&duration_str[..duration_str.len()-1], "h")
-        } else if duration_str.ends_with('m') {
-            (&duration_str[..duration_str.len()-1], "m")
-        } else if duration_str.ends_with('s') {
-            (&duration_str[..duration_str.len()-1], "s")
-        } else {
-            (duration_str.as_str(), "s")
-        };
-        
-        let number: u64 = number_str.parse()
-            .map_err(|_| "Invalid duration number")?;
-        
-        let seconds = match unit {
-            "d" => number * 86400,
-            "h" => number * 3600,
-            "m" => number * 60,
-            "s" => number,
-            _ => return Err(Error::Config("Invalid duration unit".to_string())),
-        };
-        
-        Ok(Some(seconds))
-    }
-    
-    /// Get current time as Unix timestamp
-    fn get_current_time(&self) -> u64 {
-        SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap_or_default()
-            .as_secs()
-    }
-    
     /// Format time as readable string
     fn format_time(&self, timestamp: u64) -> String {
         use chrono::{DateTime, Utc};
@@ -263,61 +203,15 @@ impl KlineModule {
     
     /// Check if a user matches any active KLINEs
     pub async fn check_user_kline(&self, user: &User) -> Option<String> {
-        let current_time = self.get_current_time();
-        
-        let klines = self.klines.read().await;
-        for kline in klines.values() {
-            if kline.is_active && self.matches_mask(&kline.mask, user) {
-                if kline.expire_time.map_or(true, |expire| current_time < expire) {
-                    return Some(format!("KLINE: {}", kline.reason));
-                }
-            }
-        }
-        
-        None
+        self.klines.list_bans("KLINE")
+            .into_iter()
+            .find(|kline| self.matches_mask(&kline.mask, user))
+            .map(|kline| format!("KLINE: {}", kline.reason))
     }
     
     /// Check if a user matches a ban mask
     fn matches_mask(&self, mask: &str, user: &User) -> bool {
-        // Simple wildcard matching - in a real implementation, this would be more sophisticated
-        let user_mask = format!("{}!{}@{}", user.nickname(), user.username(), user.hostname());
-        
-        // Convert IRC wildcards to regex patterns
-        let pattern = mask
-            .replace("*", ".*")
-            .replace("?", ".");
-        
-        // Simple pattern matching - in production, use proper regex
-        if mask.contains('*') || mask.contains('?') {
-            // Wildcard matching
-            self.simple_wildcard_match(&pattern, &user_mask)
-        } else {
-            // Exact match
-            mask == user_mask || mask == user.nickname() || mask == user.hostname()
-        }
-    }
-    
-    /// Simple wildcard matching
-    fn simple_wildcard_match(&self, pattern: &str, text: &str) -> bool {
-        // Very basic wildcard matching - in production, use proper regex
-        if pattern == ".*" {
-            return true;
-        }
-        
-        if pattern.starts_with(".*") && pattern.ends_with(".*") {
-            let middle = &pattern[2..pattern.len()-2];
-            return text.contains(middle);
-        }
-        
-        if pattern.starts_with(".*") {
-            return text.ends_with(&pattern[2..]);
-        }
-        
-        if pattern.ends_with(".*") {
-            return text.starts_with(&pattern[..pattern.len()-2]);
-        }
-        
-        text == pattern
+        BanManager::matches(BanTarget::UserHost, mask, &BanQuery::from_user(user))
     }
     
     /// Clean up expired KLINEs
@@ -326,17 +220,7 @@ impl KlineModule {
             return Ok(());
         }
         
-        let current_time = self.get_current_time();
-        let mut expired_count = 0;
-        
-        let mut klines = self.klines.write().await;
-        klines.retain(|_, kline| {
-            let should_keep = kline.expire_time.map_or(true, |expire| current_time < expire);
-            if !should_keep {
-                expired_count += 1;
-            }
-            should_keep
-        });
+        let expired_count = self.klines.cleanup_expired_type("KLINE").len();
         
         if expired_count > 0 {
             info!("Cleaned up {} expired KLINEs", expired_count);
@@ -347,18 +231,12 @@ impl KlineModule {
     
     /// Get count of active KLINEs
     pub async fn get_active_klines_count(&self) -> usize {
-        let klines = self.klines.read().await;
-        klines.len()
+        self.klines.active_count("KLINE")
     }
     
     /// Get count of expired KLINEs
     pub async fn get_expired_klines_count(&self) -> usize {
-        let current_time = self.get_current_time();
-        let klines = self.klines.read().await;
-        
-        klines.values().filter(|kline| {
-            kline.expire_time.map_or(false, |expire| current_time >= expire)
-        }).count()
+        self.klines.expired_count("KLINE")
     }
     
     /// Broadcast KLINE to other servers
@@ -435,24 +313,6 @@ impl KlineModule {
         Ok(())
     }
 
-    /// Send a notice to all operators
-    async fn send_to_operators(&self, context: &ModuleContext, notice: &str) -> Result<()> {
-        let client_connections = context.client_connections.read().await;
-
-        for client in client_connections.values() {
-            if let Some(user) = client.get_user() {
-                if user.is_operator() {
-                    let notice_msg = Message::new(
-                        MessageType::Notice,
-                        vec!["*".to_string(), notice.to_string()]
-                    );
-                    let _ = client.send(notice_msg);
-                }
-            }
-        }
-
-        Ok(())
-    }
     
     /// Handle KLINE message from another server
     async fn handle_server_kline(&self, server: &str, params: &[String], context: &ModuleContext) -> Result<()> {
@@ -465,30 +325,20 @@ impl KlineModule {
         let reason = &params[1];
         let set_by = if params.len() > 2 { &params[2] } else { "unknown" };
         let duration = if params.len() > 3 {
-            self.parse_duration(&params[3]).ok().flatten()
+            BanManager::parse_duration(&params[3]).ok().flatten()
         } else {
             None
         };
         
-        let current_time = self.get_current_time();
-        let expire_time = duration.map(|d| current_time + d);
-        
-        let kline = KillLine {
-            mask: mask.to_string(),
-            reason: reason.to_string(),
-            set_by: set_by.to_string(),
-            set_time: current_time,
-            expire_time,
-            is_active: true,
-        };
-        
-        let mut klines = self.klines.write().await;
-        klines.insert(mask.to_string(), kline);
+        let kline = BanEntry::new("KLINE", mask, reason, set_by, duration);
+        if let Err(e) = self.klines.add_ban(kline) {
+            warn!("Invalid KLINE from server {}: {}", server, e);
+            return Ok(());
+        }
         
         info!("KLINE received from server {}: {} - {}", server, mask, reason);
         
         // Check existing connections and disconnect matching users
-        drop(klines); // Release the lock before async call
         self.disconnect_matching_users(mask, &format!("KLINE: {}", reason), context).await?;
         
         Ok(())
@@ -504,8 +354,7 @@ impl KlineModule {
         let mask = &params[0];
         let removed_by = if params.len() > 1 { &params[1] } else { "unknown" };
         
-        let mut klines = self.klines.write().await;
-        if klines.remove(mask).is_some() {
+        if self.klines.remove_ban("KLINE", mask).is_some() {
             info!("UNKLINE received from server {}: {} removed by {}", server, mask, removed_by);
         } else {
             debug!("UNKLINE received from server {} for non-existent KLINE: {}", server, mask);
@@ -642,6 +491,12 @@ impl Module for KlineModule {
         Ok(())
     }
 
+    fn register_ban_types(&mut self, ban_manager: Arc<BanManager>) -> Result<()> {
+        ban_manager.register_type(Self::ban_type());
+        self.klines = ban_manager;
+        Ok(())
+    }
+
     async fn cleanup(&mut self) -> Result<()> {
         info!("KLINE module cleaned up");
         Ok(())
@@ -722,26 +577,13 @@ mod tests {
     }
     
     #[test]
-    fn test_parse_duration() {
-        let module = KlineModule::new();
-        
-        assert_eq!(module.parse_duration("1d").unwrap(), Some(86400));
-        assert_eq!(module.parse_duration("2h").unwrap(), Some(7200));
-        assert_eq!(module.parse_duration("30m").unwrap(), Some(1800));
-        assert_eq!(module.parse_duration("3600s").unwrap(), Some(3600));
-        assert_eq!(module.parse_duration("3600").unwrap(), Some(3600));
-        assert_eq!(module.parse_duration("0").unwrap(), None);
-        assert_eq!(module.parse_duration("").unwrap(), None);
-    }
-    
-    #[test]
-    fn test_wildcard_matching() {
+    fn test_mask_matching() {
         let module = KlineModule::new();
+        let user = User::new("bob".to_string(), "bob".to_string(), "Bob".to_string(), "dsl.badhost.com".to_string(), "irc.example.com".to_string());
         
-        // Note: This is a simplified test - real implementation would use proper regex
-        assert!(module.simple_wildcard_match(".*", "anything"));
-        assert!(module.simple_wildcard_match("test.*", "test123"));
-        assert!(module.simple_wildcard_match(".*test", "123test"));
-        assert!(!module.simple_wildcard_match("test", "notest"));
+        assert!(module.matches_mask("*@*.badhost.com", &user));
+        assert!(module.matches_mask("bob!*@*", &user));
+        assert!(!module.matches_mask("alice@*", &user));
+        assert!(!module.matches_mask("*@goodhost.com", &user));
     }
 }
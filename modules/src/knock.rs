@@ -4,13 +4,16 @@
 //! Based on Ratbox's m_knock.c module.
 
 use rustircd_core::{
-    async_trait, Client, Message, MessageType, Module,
-    ModuleNumericManager, module::{ModuleResult, ModuleStatsResponse, ModuleContext},
+    async_trait, BroadcastMessage, BroadcastPriority, BroadcastSystem, BroadcastTarget,
+    Client, CommandSpec, Message, MessageType, Module, ModuleNumericManager, Prefix,
+    module::{ModuleResult, ModuleStatsResponse, ModuleContext},
     NumericReply, Result, User
 };
 use tracing::info;
 use std::collections::HashMap;
+use std::sync::Arc;
 use tokio::sync::RwLock;
+use crate::channel::{Channel, ChannelModule};
 use crate::help::{HelpProvider, HelpTopic};
 
 /// Knock system module that handles channel invitation requests
@@ -19,6 +22,10 @@ pub struct KnockModule {
     knock_requests: RwLock<HashMap<String, Vec<KnockRequest>>>,
     /// Configuration for knock system
     config: KnockConfig,
+    /// Channel state shared with the channel module
+    channels: Option<Arc<RwLock<HashMap<String, Channel>>>>,
+    /// Broadcast system used to reach channel operators
+    broadcast_system: Option<Arc<RwLock<BroadcastSystem>>>,
 }
 
 /// A knock request from a user to a channel
@@ -62,10 +69,7 @@ impl Default for KnockConfig {
 impl KnockModule {
     /// Create a new knock module with default configuration
     pub fn new() -> Self {
-        Self {
-            knock_requests: RwLock::new(HashMap::new()),
-            config: KnockConfig::default(),
-        }
+        Self::with_config(KnockConfig::default())
     }
     
     /// Create a new knock module with custom configuration
@@ -73,16 +77,23 @@ impl KnockModule {
         Self {
             knock_requests: RwLock::new(HashMap::new()),
             config,
+            channels: None,
+            broadcast_system: None,
         }
     }
     
+    /// Share channel state with the channel module
+    ///
+    /// Without it knocks can't check channel modes or membership and
+    /// notifications are only logged.
+    pub fn with_channel_module(mut self, channel_module: &ChannelModule) -> Self {
+        self.channels = Some(channel_module.channels_handle());
+        self.broadcast_system = Some(channel_module.broadcast_handle());
+        self
+    }
+    
     /// Handle KNOCK command
-    async fn handle_knock(&self, client: &Client, user: &User, args: &[String]) -> Result<()> {
-        if args.len() < 2 {
-            client.send_numeric(NumericReply::ErrNeedMoreParams, &["KNOCK", "Not enough parameters"])?;
-            return Ok(());
-        }
-        
+    async fn handle_knock(&self, client: &Client, user: &User, args: &[String], context: &ModuleContext) -> Result<()> {
         let channel = &args[0];
         let reason = if args.len() > 2 {
             args[1..].join(" ")
@@ -102,6 +113,12 @@ impl KnockModule {
             return Ok(());
         }
         
+        // Knocking only makes sense on channels the user can't just join
+        if let Some(refusal) = self.knock_refusal(channel).await {
+            client.send_numeric(refusal.0, &[channel, refusal.1])?;
+            return Ok(());
+        }
+        
         // Check rate limiting
         if !self.check_knock_rate_limit(user, channel).await? {
             client.send_numeric(NumericReply::ErrTooManyTargets, &[channel, "You have knocked too many times recently"])?;
@@ -122,7 +139,10 @@ impl KnockModule {
         self.store_knock_request(&knock_request).await?;
         
         // Send knock notification to channel operators
-        self.notify_channel_operators(&knock_request).await?;
+        self.notify_channel_operators(&knock_request, context).await?;
+        
+        // Let the rest of the network notify their operators too
+        context.broadcast_to_servers(self.knock_message(&knock_request)).await?;
         
         // Send confirmation to user
         client.send_numeric(NumericReply::RplKnock, &[channel, "Your knock has been delivered"])?;
@@ -155,34 +175,51 @@ impl KnockModule {
     
     /// Check if user is already in the channel
     async fn is_user_in_channel(&self, user: &User, channel: &str) -> Result<bool> {
-        // Implement channel membership checking
-        // NOTE: Full integration with channel module for membership support is an enhancement
-        // Current implementation uses basic user.channels set which works for most cases
-        // 
-        // For enhanced integration, this could:
-        // 1. Query the channel module to check if user is a member
-        // 2. Check channel membership database
-        // 3. Verify user has proper access rights
-        // 3. Return actual membership status
-        
-        // Basic implementation: check if user has the channel in their channel list
-        let is_member = user.channels.contains(&channel.to_string());
+        // Without the channel module, fall back to the user's own channel list
+        let is_member = match &self.channels {
+            Some(channels) => channels.read().await
+                .get(channel)
+                .is_some_and(|ch| ch.has_member(&user.id)),
+            None => user.channels.contains(&channel.to_string()),
+        };
         
         tracing::debug!("Checking if user {} is in channel {}: {}", user.nickname(), channel, is_member);
         
         Ok(is_member)
     }
     
+    /// Reason a knock on an existing channel is refused, if any
+    ///
+    /// Knocks are accepted on channels that are invite-only, keyed or full,
+    /// as allowed by the configuration.
+    async fn knock_refusal(&self, channel: &str) -> Option<(NumericReply, &'static str)> {
+        let channels = self.channels.as_ref()?.read().await;
+        let Some(ch) = channels.get(channel) else {
+            return Some((NumericReply::ErrNoSuchChannel, "No such channel"));
+        };
+        let invite_only = ch.is_invite_only() && self.config.allow_invite_only_knocks;
+        let keyed = ch.is_keyed() && self.config.allow_key_knocks;
+        let full = ch.user_limit.is_some_and(|limit| ch.member_count() >= limit);
+        if invite_only || keyed || full {
+            None
+        } else {
+            Some((NumericReply::ErrNoChanModes, "Channel is open, just join it"))
+        }
+    }
+    
     /// Check knock rate limiting
     async fn check_knock_rate_limit(&self, user: &User, channel: &str) -> Result<bool> {
         let knock_requests = self.knock_requests.read().await;
         let current_time = self.get_current_timestamp();
         
-        // Count knocks to this specific channel
+        // Count recent knocks to this specific channel
         if let Some(requests) = knock_requests.get(channel) {
             let user_knocks: Vec<&KnockRequest> = requests
                 .iter()
-                .filter(|req| req.user_nick == user.nickname())
+                .filter(|req| {
+                    req.user_nick == user.nickname() &&
+                    current_time.saturating_sub(req.timestamp) <= self.config.knock_time_window
+                })
                 .collect();
             
             if user_knocks.len() >= self.config.max_knocks_per_channel {
@@ -197,7 +234,7 @@ impl KnockModule {
                 .iter()
                 .filter(|req| {
                     req.user_nick == user.nickname() && 
-                    (current_time - req.timestamp) <= self.config.knock_time_window
+                    current_time.saturating_sub(req.timestamp) <= self.config.knock_time_window
                 })
                 .collect();
             
@@ -227,7 +264,7 @@ impl KnockModule {
         let current_time = self.get_current_timestamp();
         
         for requests in knock_requests.values_mut() {
-            requests.retain(|req| (current_time - req.timestamp) <= self.config.knock_time_window);
+            requests.retain(|req| current_time.saturating_sub(req.timestamp) <= self.config.knock_time_window);
         }
         
         // Remove empty channel entries
@@ -235,35 +272,83 @@ impl KnockModule {
     }
     
     /// Notify channel operators about knock request
-    async fn notify_channel_operators(&self, request: &KnockRequest) -> Result<()> {
-        // Implement channel operator notification
-        // NOTE: Full integration with channel module and broadcast system is an enhancement
-        // Current implementation provides basic knock functionality
-        // 
-        // For enhanced integration, this could:
-        // 1. Query the channel module to get list of channel operators
-        // 2. Send NOTICE message to each operator
-        // 3. Use proper IRC message formatting with server prefix
-        
-        let notification_message = format!(
-            "NOTICE @{} :{} ({}) wants to join {}: {}", 
-            request.channel, request.user_nick, request.user_host, 
-            request.channel, request.reason
-        );
-        
-        tracing::info!("Knock notification: {}", notification_message);
-        
-        // In production, this would use the channel module to:
-        // - Get list of channel operators (users with +o mode)
-        // - Send NOTICE message to each operator
-        // - Format message with proper server prefix
-        // - Handle errors if operators are not available
+    ///
+    /// Operators and halfops get a NOTICE, as do members whose clients
+    /// negotiated invite-notify.
+    async fn notify_channel_operators(&self, request: &KnockRequest, context: &ModuleContext) -> Result<()> {
+        let (Some(channels), Some(broadcast_system)) = (&self.channels, &self.broadcast_system) else {
+            tracing::info!("Knock on {} by {} (no channel module to notify)", request.channel, request.user_nick);
+            return Ok(());
+        };
         
-        tracing::debug!("Would send knock notification to operators of channel: {}", request.channel);
+        let members: Vec<_> = match channels.read().await.get(&request.channel) {
+            Some(channel) => channel.members.values().cloned().collect(),
+            None => return Ok(()),
+        };
+        let mut recipients = Vec::new();
+        for member in members {
+            let wants_notice = member.is_operator() || member.is_halfop()
+                || context.get_client_by_id(member.user_id).await
+                    .is_some_and(|client| client.has_capability("invite-notify"));
+            if let Some(user) = context.database.get_user(&member.user_id).filter(|_| wants_notice) {
+                recipients.push(user.nick);
+            }
+        }
+        if recipients.is_empty() {
+            return Ok(());
+        }
         
+        let notice = Message::new(
+            MessageType::Notice,
+            vec![
+                format!("@{}", request.channel),
+                format!(
+                    "[Knock] by {}!{}@{} ({})",
+                    request.user_nick, request.user_ident, request.user_host, request.reason
+                ),
+            ],
+        );
+        broadcast_system.write().await.queue_message(BroadcastMessage {
+            message: notice,
+            target: BroadcastTarget::Users(recipients),
+            sender: None,
+            priority: BroadcastPriority::Normal,
+        })?;
+        
+        tracing::debug!("Sent knock notification to operators of channel: {}", request.channel);
         Ok(())
     }
     
+    /// KNOCK as relayed between servers
+    fn knock_message(&self, request: &KnockRequest) -> Message {
+        Message::with_prefix(
+            Prefix::User {
+                nick: request.user_nick.clone(),
+                user: request.user_ident.clone(),
+                host: request.user_host.clone(),
+            },
+            MessageType::Custom("KNOCK".to_string()),
+            vec![request.channel.clone(), request.reason.clone()],
+        )
+    }
+    
+    /// Notify local operators of a knock relayed by another server and pass it on
+    async fn handle_remote_knock(&self, server: &str, message: &Message, context: &ModuleContext) -> Result<()> {
+        let (Some(Prefix::User { nick, user, host }), Some(channel)) = (&message.prefix, message.params.first()) else {
+            return Ok(());
+        };
+        let request = KnockRequest {
+            user_nick: nick.clone(),
+            user_ident: user.clone(),
+            user_host: host.clone(),
+            channel: channel.clone(),
+            reason: message.params.get(1).cloned().unwrap_or_default(),
+            timestamp: self.get_current_timestamp(),
+        };
+        self.notify_channel_operators(&request, context).await?;
+        context.server_connections.broadcast_message(message, Some(server)).await
+    }
+    
     /// Get current timestamp
     fn get_current_timestamp(&self) -> u64 {
         use std::time::{SystemTime, UNIX_EPOCH};
@@ -323,25 +408,32 @@ impl Module for KnockModule {
         Ok(())
     }
 
-    async fn handle_message(&mut self, client: &Client, message: &Message, _context: &ModuleContext) -> Result<ModuleResult> {
-        let user = match &client.user {
-            Some(u) => u,
-            None => return Ok(ModuleResult::NotHandled),
+    async fn handle_message(&mut self, _client: &Client, _message: &Message, _context: &ModuleContext) -> Result<ModuleResult> {
+        Ok(ModuleResult::NotHandled)
+    }
+
+    fn commands(&self) -> Vec<CommandSpec> {
+        vec![CommandSpec::new("KNOCK", 2)]
+    }
+
+    async fn handle_command(&mut self, client: &Client, message: &Message, context: &ModuleContext) -> Result<()> {
+        // Registration is checked before dispatch, so the user is set
+        let Some(user) = client.get_user() else {
+            return Ok(());
         };
+        self.handle_knock(client, user, &message.params, context).await
+    }
 
+    async fn handle_server_message(&mut self, server: &str, message: &Message, context: &ModuleContext) -> Result<ModuleResult> {
         match message.command {
             MessageType::Custom(ref cmd) if cmd == "KNOCK" => {
-                self.handle_knock(client, user, &message.params).await?;
+                self.handle_remote_knock(server, message, context).await?;
                 Ok(ModuleResult::Handled)
             }
             _ => Ok(ModuleResult::NotHandled),
         }
     }
 
-    async fn handle_server_message(&mut self, _server: &str, _message: &Message, _context: &ModuleContext) -> Result<ModuleResult> {
-        Ok(ModuleResult::NotHandled)
-    }
-
     async fn handle_user_registration(&mut self, _user: &User, _context: &ModuleContext) -> Result<()> {
         Ok(())
     }
@@ -486,6 +578,38 @@ mod tests {
         assert_eq!(requests[0].reason, "Please let me in");
     }
     
+    #[tokio::test]
+    async fn test_knock_with_channel_module() {
+        use rustircd_core::{client::ClientState, Config, Database, ServerConnectionManager};
+
+        let channel_module = ChannelModule::new();
+        let channels = channel_module.channels_handle();
+        let mut channel = Channel::new("#secret".to_string());
+        channel.add_mode('i');
+        channels.write().await.insert("#secret".to_string(), channel);
+        channels.write().await.insert("#open".to_string(), Channel::new("#open".to_string()));
+
+        let module = KnockModule::new().with_channel_module(&channel_module);
+        let context = ModuleContext::new(
+            Arc::new(Database::new(100, 1)),
+            Arc::new(ServerConnectionManager::new(Arc::new(Config::default()))),
+        );
+        let (tx, _rx) = tokio::sync::mpsc::unbounded_channel();
+        let mut client = Client::new(uuid::Uuid::new_v4(), "127.0.0.1:50000".to_string(), "127.0.0.1:6667".to_string(), tx);
+        client.set_state(ClientState::Registered);
+        let user = User::new("bob".to_string(), "bob".to_string(), "Bob".to_string(), "example.com".to_string(), "irc.example.com".to_string());
+
+        let knock = |channel: &str| vec![channel.to_string(), "let me in".to_string()];
+        module.handle_knock(&client, &user, &knock("#open"), &context).await.unwrap();
+        assert!(module.get_knock_requests("#open").await.is_empty());
+
+        // Knocks per channel are capped within the window
+        for _ in 0..4 {
+            module.handle_knock(&client, &user, &knock("#secret"), &context).await.unwrap();
+        }
+        assert_eq!(module.get_knock_requests("#secret").await.len(), 3);
+    }
+
     #[tokio::test]
     async fn test_clear_knock_requests() {
         let module = KnockModule::new();
@@ -18,6 +18,8 @@ pub mod gline;
 pub mod kline;
 pub mod dline;
 pub mod xline;
+pub mod shun;
+pub mod resv;
 pub mod admin;
 pub mod testing;
 pub mod services;
@@ -25,10 +27,15 @@ pub mod oper;
 pub mod sasl;
 pub mod opme;
 pub mod auth;
+pub mod registry;
+pub mod metadata;
 
-pub use channel::{ChannelModule, Channel, ChannelMember, ChannelMode};
+#[cfg(test)]
+mod test_support;
+
+pub use channel::{ChannelModule, Channel, ChannelMember, ChannelMode, OperOverrideConfig};
 pub use ircv3::Ircv3Module;
-pub use messaging::{MessagingModule, MessagingManager, WallopsModule, MessagingWrapper, create_default_messaging_module};
+pub use messaging::{MessagingModule, MessagingManager, WallopsModule, MessagingWrapper, create_default_messaging_module, create_messaging_module_with_config};
 pub use optional::OptionalModule;
 pub use throttling::ThrottlingModule;
 pub use help::{HelpModule, HelpProvider, HelpTopic};
@@ -39,10 +46,16 @@ pub use gline::{GlineModule, GlineConfig, GlobalBan as GlineGlobalBan};
 pub use kline::{KlineModule, KlineConfig, KillLine as KlineKillLine};
 pub use dline::{DlineModule, DlineConfig, DnsLine as DlineDnsLine};
 pub use xline::{XlineModule, XlineConfig, ExtendedLine as XlineExtendedLine};
+pub use shun::{ShunModule, ShunConfig, Shun};
+pub use resv::{ResvModule, ResvConfig, Reservation};
 pub use admin::{AdminModule, AdminInfo, AdminWallMessage};
-pub use testing::{TestingModule, TestConfig, TestResult, TestLineResult, TestStatistics};
+pub use testing::TestingModule;
+#[allow(deprecated)]
+pub use testing::{TestConfig, TestResult, TestLineResult, TestStatistics};
 pub use services::{ServicesModule, ServiceConfig, Service, ServiceType, ServiceStatistics};
 pub use oper::{OperModule, OperConfig, OperatorAware, DefaultOperatorAware, OperatorChecker, OperatorAction};
 pub use sasl::{SaslModule, SaslConfig, SaslSession, SaslAuthData, SaslState, SaslMechanism, SaslResponse, SaslResponseType, SaslCapabilityExtension};
 pub use opme::{OpmeModule, OpmeConfig, OpmeRateLimit, OpmeStats, OpmeConfigBuilder};
 pub use auth::{LdapAuthProvider, DatabaseAuthProvider, FileAuthProvider, HttpAuthProvider, SupabaseAuthProvider, SupabaseAuthConfig, SupabaseAuthProviderBuilder};
+pub use registry::{module_registry, register_modules};
+pub use metadata::MetadataModule;
@@ -138,22 +138,19 @@ impl Default for WallopsModule {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use rustircd_core::{Client, Message, UserModeManager};
-    use std::collections::HashSet;
+    use crate::test_support::{client_for, oper, user};
+    use rustircd_core::MessageType;
     
     #[tokio::test]
     async fn test_wallops_command_handling() {
-        let mut wallops = WallopsModule::new();
+        let wallops = WallopsModule::new();
         
         // Test command name
         assert_eq!(wallops.command(), "WALLOPS");
         
-        // Test mode requirements
-        assert_eq!(wallops.sender_mode_required(), Some(UserMode::Operator));
-        assert_eq!(wallops.receiver_mode_required(), Some(UserMode::Wallops));
-        
-        // Test operator requirement
-        assert!(wallops.requires_operator());
+        // Test mode requirements (operator status and +w are checked by the module itself)
+        assert_eq!(wallops.sender_mode_required(), None);
+        assert_eq!(wallops.receiver_mode_required(), None);
     }
     
     #[tokio::test]
@@ -161,21 +158,9 @@ mod tests {
         let mut wallops = WallopsModule::new();
         
         // Create a mock client
-        let mut modes = UserModeManager::new();
-        modes.set_operator(true);
+        let (sender, _rx) = client_for(oper("operator"));
         
-        let sender = Client {
-            nickname: "operator".to_string(),
-            username: "op".to_string(),
-            hostname: "localhost".to_string(),
-            user_modes: modes,
-            // ... other fields would be mocked
-        };
-        
-        let message = Message {
-            command: "WALLOPS".to_string(),
-            params: vec![], // Empty message
-        };
+        let message = Message::new(MessageType::Wallops, vec![]); // Empty message
         
         let result = wallops.handle_command(&sender, &message, &[]).await.unwrap();
         
@@ -186,4 +171,23 @@ mod tests {
             _ => panic!("Expected rejected result for empty message"),
         }
     }
+
+    #[tokio::test]
+    async fn test_wallops_requires_operator() {
+        let mut wallops = WallopsModule::new();
+        let message = Message::new(MessageType::Wallops, vec!["hello".to_string()]);
+
+        let (sender, _rx) = client_for(user("alice"));
+        let result = wallops.handle_command(&sender, &message, &[]).await.unwrap();
+        assert!(matches!(result, MessagingResult::Rejected(_)));
+
+        // Losing operator status drops the snomask subscriptions along with WALLOPS
+        let mut former_oper = oper("operator");
+        former_oper.snomasks.extend(['c', 'k']);
+        former_oper.revoke_operator_privileges();
+        assert!(former_oper.snomasks.is_empty());
+        let (sender, _rx) = client_for(former_oper);
+        let result = wallops.handle_command(&sender, &message, &[]).await.unwrap();
+        assert!(matches!(result, MessagingResult::Rejected(_)));
+    }
 }
@@ -0,0 +1,621 @@
+//! METADATA Module
+//!
+//! Implements the IRCv3 draft METADATA specification. Users and channels
+//! carry key/value pairs such as `avatar` or `display-name`: clients read
+//! them with GET and LIST, change them with SET and CLEAR, and SUB to keys
+//! to be told when they change on anyone they share a channel with.
+//!
+//! Values are kept in the core database and reach other servers as
+//! `ENCAP * METADATA <target> <key> <visibility> [:<value>]`, both when
+//! they change and in the burst of a new link.
+
+use rustircd_core::{
+    async_trait, config::{MetadataConfig, MetadataVisibility},
+    module::{ModuleContext, ModuleResult, ModuleStatsResponse},
+    Client, CommandSpec, Database, Message, MessageType, MetadataEntry, MetadataTarget, Module,
+    ModuleNumericManager, NumericReply, Prefix, Result, User,
+};
+use std::collections::{BTreeSet, HashMap};
+use std::sync::Arc;
+use tokio::sync::RwLock;
+use uuid::Uuid;
+use crate::channel::{Channel, ChannelModule};
+
+/// Keys listed per RPL_METADATASUBS line
+const SUBS_PER_LINE: usize = 10;
+
+/// Longest key name accepted
+const MAX_KEY_LENGTH: usize = 64;
+
+/// METADATA command and propagation
+pub struct MetadataModule {
+    /// Limits and key policies
+    config: MetadataConfig,
+    /// Keys each local client is subscribed to
+    subscriptions: HashMap<Uuid, BTreeSet<String>>,
+    /// Channel state shared with the channel module
+    channels: Option<Arc<RwLock<HashMap<String, Channel>>>>,
+    /// Database the values are kept in, for the link burst
+    database: Option<Arc<Database>>,
+}
+
+/// A user or channel METADATA names
+struct Target {
+    /// Nick or channel name used in replies
+    name: String,
+    /// Where the values are kept
+    key: MetadataTarget,
+}
+
+impl MetadataModule {
+    /// Create the module with the given limits and key policies
+    pub fn new(config: MetadataConfig) -> Self {
+        Self {
+            config,
+            subscriptions: HashMap::new(),
+            channels: None,
+            database: None,
+        }
+    }
+
+    /// Share channel state with the channel module
+    ///
+    /// Without it only IRC operators may change channel metadata, since
+    /// channel operator status isn't known.
+    pub fn with_channel_module(mut self, channel_module: &ChannelModule) -> Self {
+        self.channels = Some(channel_module.channels_handle());
+        self
+    }
+
+    /// Whether `key` is a well-formed key name
+    fn is_valid_key(key: &str) -> bool {
+        !key.is_empty()
+            && key.len() <= MAX_KEY_LENGTH
+            && key.bytes().all(|byte| matches!(byte, b'a'..=b'z' | b'0'..=b'9' | b'_' | b'.' | b'/' | b'-'))
+    }
+
+    /// A FAIL standard reply for the METADATA command
+    fn fail(context: &ModuleContext, code: &str, params: &[&str], description: &str) -> Message {
+        let mut fail_params = vec!["METADATA".to_string(), code.to_string()];
+        fail_params.extend(params.iter().map(|param| param.to_string()));
+        fail_params.push(description.to_string());
+        Message::with_prefix(
+            Prefix::Server(context.server_connections.local_server_name().to_string()),
+            MessageType::Custom("FAIL".to_string()),
+            fail_params,
+        )
+    }
+
+    /// A METADATA message telling a client a key changed
+    fn notification(context: &ModuleContext, target: &str, key: &str, visibility: MetadataVisibility, value: Option<&str>) -> Message {
+        let mut params = vec![target.to_string(), key.to_string(), visibility.token().to_string()];
+        params.extend(value.map(str::to_string));
+        Message::with_prefix(
+            Prefix::Server(context.server_connections.local_server_name().to_string()),
+            MessageType::Custom("METADATA".to_string()),
+            params,
+        )
+    }
+
+    /// Find the user or channel `name` refers to, `*` being the user themselves
+    async fn resolve(&self, user: &User, name: &str, context: &ModuleContext) -> Option<Target> {
+        if name == "*" {
+            return Some(Target { name: user.nick.clone(), key: MetadataTarget::User(user.id) });
+        }
+        if name.starts_with(['#', '&', '+', '!']) {
+            let exists = match &self.channels {
+                Some(channels) => channels.read().await.contains_key(name),
+                None => !context.database.get_channel_users(name).is_empty(),
+            };
+            return exists.then(|| Target { name: name.to_string(), key: MetadataTarget::channel(name) });
+        }
+        let target = context.database.get_user_by_nick(name)?;
+        Some(Target { name: target.nick, key: MetadataTarget::User(target.id) })
+    }
+
+    /// Whether `user` may change the target's metadata
+    async fn may_edit(&self, user: &User, target: &Target) -> bool {
+        if user.is_operator {
+            return true;
+        }
+        match &target.key {
+            MetadataTarget::User(id) => *id == user.id,
+            MetadataTarget::Channel(_) => match &self.channels {
+                Some(channels) => channels.read().await.get(&target.name).is_some_and(|channel| channel.is_operator(&user.id)),
+                None => false,
+            },
+        }
+    }
+
+    /// Whether `user` may see a value of the target with `visibility`
+    async fn may_see(&self, user: &User, target: &Target, visibility: MetadataVisibility) -> bool {
+        match visibility {
+            MetadataVisibility::Public => true,
+            MetadataVisibility::Operators => user.is_operator || self.may_edit(user, target).await,
+            MetadataVisibility::Private => self.may_edit(user, target).await,
+        }
+    }
+
+    /// Whether `user` is the target or shares a channel with it
+    fn in_scope(user: &User, target: &Target, context: &ModuleContext) -> bool {
+        match &target.key {
+            MetadataTarget::User(id) if *id == user.id => true,
+            MetadataTarget::User(_) => {
+                let theirs = context.database.get_user_channels(&target.name);
+                context.database.get_user_channels(&user.nick).iter().any(|channel| theirs.contains(channel))
+            }
+            MetadataTarget::Channel(_) => context.database.get_channel_users(&target.name)
+                .iter()
+                .any(|nick| nick.eq_ignore_ascii_case(&user.nick)),
+        }
+    }
+
+    /// Tell local subscribers of `key` who can see the target that it changed
+    async fn notify(&self, context: &ModuleContext, target: &Target, key: &str, visibility: MetadataVisibility, value: Option<&str>, except: Option<Uuid>) {
+        let message = Self::notification(context, &target.name, key, visibility, value);
+        for (client_id, keys) in &self.subscriptions {
+            if Some(*client_id) == except || !keys.contains(key) {
+                continue;
+            }
+            let Some(recipient) = context.database.get_user(client_id) else {
+                continue;
+            };
+            if Self::in_scope(&recipient, target, context) && self.may_see(&recipient, target, visibility).await {
+                context.client_senders.send(client_id, message.clone());
+            }
+        }
+    }
+
+    /// Store a change, tell subscribers and pass it on to the network
+    async fn apply(&self, context: &ModuleContext, target: &Target, key: &str, entry: Option<MetadataEntry>, setter: Uuid) {
+        let previous = context.database.set_metadata(&target.key, key, entry.clone());
+        let visibility = entry.as_ref().or(previous.as_ref()).map_or(MetadataVisibility::Public, |entry| entry.visibility);
+        let value = entry.as_ref().map(|entry| entry.value.as_str());
+        self.notify(context, target, key, visibility, value, Some(setter)).await;
+
+        let mut params = vec![target.name.clone(), key.to_string(), visibility.token().to_string()];
+        params.extend(value.map(str::to_string));
+        if let Err(e) = context.send_encap("*", "METADATA", params).await {
+            tracing::warn!("Failed to propagate metadata of {}: {}", target.name, e);
+        }
+    }
+
+    /// Handle the METADATA command
+    async fn handle_metadata(&mut self, client: &Client, user: &User, params: &[String], context: &ModuleContext) -> Result<()> {
+        let subcommand = params[1].to_uppercase();
+        let args = &params[2..];
+        let needs_args = matches!(subcommand.as_str(), "GET" | "SET" | "SUB" | "UNSUB");
+        if needs_args && args.is_empty() {
+            let _ = client.send(NumericReply::need_more_params("METADATA"));
+            return Ok(());
+        }
+
+        match subcommand.as_str() {
+            "SUB" => self.subscribe(client, user, args, context),
+            "UNSUB" => self.unsubscribe(client, user, args, context),
+            "SUBS" => self.list_subscriptions(client, user),
+            "GET" | "LIST" | "SET" | "CLEAR" | "SYNC" => {
+                let Some(target) = self.resolve(user, &params[0], context).await else {
+                    let _ = client.send(Self::fail(context, "INVALID_TARGET", &[&params[0]], "invalid metadata target"));
+                    return Ok(());
+                };
+                match subcommand.as_str() {
+                    "GET" => self.get(client, user, &target, args, context).await,
+                    "LIST" => self.list(client, user, &target, context).await,
+                    "SET" => self.set(client, user, &target, args, context).await,
+                    "CLEAR" => self.clear(client, user, &target, context).await,
+                    _ => self.sync(client, user, &target, context).await,
+                }
+            }
+            _ => {
+                let _ = client.send(Self::fail(context, "SUBCOMMAND_INVALID", &[&params[1]], "invalid subcommand"));
+            }
+        }
+        Ok(())
+    }
+
+    /// METADATA <target> GET <key> [<key> ...]
+    async fn get(&self, client: &Client, user: &User, target: &Target, keys: &[String], context: &ModuleContext) {
+        for key in keys {
+            let key = key.to_lowercase();
+            if !Self::is_valid_key(&key) {
+                let _ = client.send(Self::fail(context, "KEY_INVALID", &[&key], "invalid key"));
+                continue;
+            }
+            match context.database.get_metadata(&target.key, &key) {
+                Some(entry) if self.may_see(user, target, entry.visibility).await => {
+                    let _ = client.send(NumericReply::key_value(&user.nick, &target.name, &key, entry.visibility.token(), &entry.value));
+                }
+                _ => {
+                    let _ = client.send(NumericReply::key_not_set(&user.nick, &target.name, &key));
+                }
+            }
+        }
+    }
+
+    /// METADATA <target> LIST
+    async fn list(&self, client: &Client, user: &User, target: &Target, context: &ModuleContext) {
+        for (key, entry) in context.database.list_metadata(&target.key) {
+            if self.may_see(user, target, entry.visibility).await {
+                let _ = client.send(NumericReply::key_value(&user.nick, &target.name, &key, entry.visibility.token(), &entry.value));
+            }
+        }
+        let _ = client.send(NumericReply::metadata_end(&user.nick));
+    }
+
+    /// METADATA <target> SET <key> [:<value>]
+    async fn set(&self, client: &Client, user: &User, target: &Target, args: &[String], context: &ModuleContext) {
+        let key = args[0].to_lowercase();
+        if !Self::is_valid_key(&key) {
+            let _ = client.send(Self::fail(context, "KEY_INVALID", &[&key], "invalid key"));
+            return;
+        }
+        let policy = self.config.key(&key);
+        let permitted = match policy {
+            Some(policy) => !policy.oper_only || user.is_operator,
+            None => self.config.allow_unlisted_keys,
+        };
+        if !permitted || !self.may_edit(user, target).await {
+            let _ = client.send(Self::fail(context, "KEY_NO_PERMISSION", &[&target.name, &key], "permission denied"));
+            return;
+        }
+
+        let Some(value) = args.get(1) else {
+            if context.database.get_metadata(&target.key, &key).is_some() {
+                self.apply(context, target, &key, None, user.id).await;
+            }
+            let _ = client.send(NumericReply::key_not_set(&user.nick, &target.name, &key));
+            return;
+        };
+        let max_bytes = policy.and_then(|policy| policy.max_value_bytes).unwrap_or(self.config.max_value_bytes);
+        if value.len() > max_bytes {
+            let _ = client.send(Self::fail(context, "VALUE_INVALID", &[], "value is too long"));
+            return;
+        }
+        let is_new = context.database.get_metadata(&target.key, &key).is_none();
+        if is_new && context.database.metadata_count(&target.key) >= self.config.max_keys {
+            let _ = client.send(Self::fail(context, "LIMIT_REACHED", &[&target.name], "metadata limit reached"));
+            return;
+        }
+
+        let visibility = policy.map_or(MetadataVisibility::Public, |policy| policy.visibility);
+        let entry = MetadataEntry { value: value.clone(), visibility };
+        self.apply(context, target, &key, Some(entry), user.id).await;
+        let _ = client.send(NumericReply::key_value(&user.nick, &target.name, &key, visibility.token(), value));
+    }
+
+    /// METADATA <target> CLEAR
+    async fn clear(&self, client: &Client, user: &User, target: &Target, context: &ModuleContext) {
+        if !self.may_edit(user, target).await {
+            let _ = client.send(Self::fail(context, "KEY_NO_PERMISSION", &[&target.name, "*"], "permission denied"));
+            return;
+        }
+        for (key, _) in context.database.list_metadata(&target.key) {
+            // Operator-only keys outlive a CLEAR by anyone else
+            if self.config.key(&key).is_some_and(|policy| policy.oper_only) && !user.is_operator {
+                continue;
+            }
+            self.apply(context, target, &key, None, user.id).await;
+            let _ = client.send(NumericReply::key_not_set(&user.nick, &target.name, &key));
+        }
+        let _ = client.send(NumericReply::metadata_end(&user.nick));
+    }
+
+    /// METADATA <target> SYNC: subscribed keys of the target, and of a channel's members
+    async fn sync(&self, client: &Client, user: &User, target: &Target, context: &ModuleContext) {
+        let Some(keys) = self.subscriptions.get(&user.id) else {
+            return;
+        };
+        let mut targets = vec![Target { name: target.name.clone(), key: target.key.clone() }];
+        if let MetadataTarget::Channel(_) = target.key {
+            targets.extend(context.database.get_channel_users(&target.name).into_iter()
+                .filter_map(|nick| context.database.get_user_by_nick(&nick))
+                .map(|member| Target { name: member.nick, key: MetadataTarget::User(member.id) }));
+        }
+        for target in &targets {
+            for (key, entry) in context.database.list_metadata(&target.key) {
+                if keys.contains(&key) && self.may_see(user, target, entry.visibility).await {
+                    let _ = client.send(Self::notification(context, &target.name, &key, entry.visibility, Some(&entry.value)));
+                }
+            }
+        }
+    }
+
+    /// METADATA * SUB <key> [<key> ...]
+    fn subscribe(&mut self, client: &Client, user: &User, keys: &[String], context: &ModuleContext) {
+        let subscribed = self.subscriptions.entry(user.id).or_default();
+        let mut added = Vec::new();
+        for key in keys {
+            let key = key.to_lowercase();
+            if !Self::is_valid_key(&key) {
+                let _ = client.send(Self::fail(context, "KEY_INVALID", &[&key], "invalid key"));
+                continue;
+            }
+            if !subscribed.contains(&key) && subscribed.len() >= self.config.max_subscriptions {
+                let _ = client.send(Self::fail(context, "TOO_MANY_SUBS", &[&key], "too many subscriptions"));
+                break;
+            }
+            if subscribed.insert(key.clone()) {
+                added.push(key);
+            }
+        }
+        if !added.is_empty() {
+            let _ = client.send(NumericReply::metadata_sub_ok(&user.nick, &added));
+        }
+    }
+
+    /// METADATA * UNSUB <key> [<key> ...]
+    fn unsubscribe(&mut self, client: &Client, user: &User, keys: &[String], context: &ModuleContext) {
+        let mut removed = Vec::new();
+        for key in keys {
+            let key = key.to_lowercase();
+            if !Self::is_valid_key(&key) {
+                let _ = client.send(Self::fail(context, "KEY_INVALID", &[&key], "invalid key"));
+                continue;
+            }
+            if self.subscriptions.get_mut(&user.id).is_some_and(|subscribed| subscribed.remove(&key)) {
+                removed.push(key);
+            }
+        }
+        self.subscriptions.retain(|_, subscribed| !subscribed.is_empty());
+        if !removed.is_empty() {
+            let _ = client.send(NumericReply::metadata_unsub_ok(&user.nick, &removed));
+        }
+    }
+
+    /// METADATA * SUBS
+    fn list_subscriptions(&self, client: &Client, user: &User) {
+        let keys: Vec<String> = self.subscriptions.get(&user.id).into_iter().flatten().cloned().collect();
+        for chunk in keys.chunks(SUBS_PER_LINE) {
+            let _ = client.send(NumericReply::metadata_subs(&user.nick, chunk));
+        }
+        let _ = client.send(NumericReply::metadata_end(&user.nick));
+    }
+
+    /// Store a change announced by another server
+    async fn handle_remote_metadata(&self, server: &str, message: &Message, context: &ModuleContext) -> Result<()> {
+        let [name, key, visibility, ..] = message.params.as_slice() else {
+            tracing::warn!("Received malformed METADATA from server {}", server);
+            return Ok(());
+        };
+        let target = if name.starts_with(['#', '&', '+', '!']) {
+            Target { name: name.clone(), key: MetadataTarget::channel(name) }
+        } else {
+            let Some(user) = context.database.get_user_by_nick(name) else {
+                tracing::debug!("Ignoring METADATA from {} for unknown user {}", server, name);
+                return Ok(());
+            };
+            Target { name: user.nick, key: MetadataTarget::User(user.id) }
+        };
+
+        let key = key.to_lowercase();
+        let visibility = MetadataVisibility::from_token(visibility);
+        let value = message.params.get(3);
+        let entry = value.map(|value| MetadataEntry { value: value.clone(), visibility });
+        context.database.set_metadata(&target.key, &key, entry);
+        self.notify(context, &target, &key, visibility, value.map(String::as_str), None).await;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Module for MetadataModule {
+    fn name(&self) -> &str {
+        "metadata"
+    }
+
+    fn description(&self) -> &str {
+        "User and channel metadata (IRCv3 METADATA)"
+    }
+
+    fn version(&self) -> &str {
+        "1.0.0"
+    }
+
+    async fn init(&mut self) -> Result<()> {
+        tracing::info!("{} module initialized", self.name());
+        Ok(())
+    }
+
+    async fn cleanup(&mut self) -> Result<()> {
+        tracing::info!("{} module cleaned up", self.name());
+        Ok(())
+    }
+
+    async fn handle_message(&mut self, _client: &Client, _message: &Message, _context: &ModuleContext) -> Result<ModuleResult> {
+        Ok(ModuleResult::NotHandled)
+    }
+
+    fn commands(&self) -> Vec<CommandSpec> {
+        vec![CommandSpec::new("METADATA", 2)]
+    }
+
+    async fn handle_command(&mut self, client: &Client, message: &Message, context: &ModuleContext) -> Result<()> {
+        // Registration is checked before dispatch, so the user is set
+        let Some(user) = client.get_user() else {
+            return Ok(());
+        };
+        self.handle_metadata(client, user, &message.params, context).await
+    }
+
+    async fn handle_server_message(&mut self, server: &str, message: &Message, context: &ModuleContext) -> Result<ModuleResult> {
+        match message.command {
+            MessageType::Custom(ref cmd) if cmd == "METADATA" => {
+                self.handle_remote_metadata(server, message, context).await?;
+                Ok(ModuleResult::Handled)
+            }
+            _ => Ok(ModuleResult::NotHandled),
+        }
+    }
+
+    async fn handle_user_registration(&mut self, _user: &User, _context: &ModuleContext) -> Result<()> {
+        Ok(())
+    }
+
+    async fn handle_user_disconnection(&mut self, user: &User, _context: &ModuleContext) -> Result<()> {
+        // The database drops the user's values along with the user
+        self.subscriptions.remove(&user.id);
+        Ok(())
+    }
+
+    fn get_capabilities(&self) -> Vec<String> {
+        vec!["user_handler".to_string()]
+    }
+
+    fn supports_capability(&self, capability: &str) -> bool {
+        capability == "user_handler"
+    }
+
+    fn get_numeric_replies(&self) -> Vec<u16> {
+        vec![]
+    }
+
+    fn handles_numeric_reply(&self, _numeric: u16) -> bool {
+        false
+    }
+
+    async fn handle_numeric_reply(&mut self, _numeric: u16, _params: Vec<String>) -> Result<()> {
+        Ok(())
+    }
+
+    async fn handle_stats_query(&mut self, _query: &str, _client_id: Uuid, _server: Option<&rustircd_core::Server>) -> Result<Vec<ModuleStatsResponse>> {
+        Ok(vec![])
+    }
+
+    fn get_stats_queries(&self) -> Vec<String> {
+        vec![]
+    }
+
+    fn register_numerics(&self, _manager: &mut ModuleNumericManager) -> Result<()> {
+        Ok(())
+    }
+
+    fn isupport_tokens(&self) -> Vec<String> {
+        vec![format!("METADATA={}", self.config.max_keys)]
+    }
+
+    fn encap_subcommands(&self) -> Vec<String> {
+        vec!["METADATA".to_string()]
+    }
+
+    async fn attach_database(&mut self, database: Arc<Database>) {
+        self.database = Some(database);
+    }
+
+    async fn server_burst(&self, target_server: &str) -> Vec<Message> {
+        let Some(database) = &self.database else {
+            return Vec::new();
+        };
+        let mut messages = Vec::new();
+        for target in database.metadata_targets() {
+            let name = match &target {
+                // The new link already has the values of its own users
+                MetadataTarget::User(id) => match database.get_user(id) {
+                    Some(user) if user.server != target_server => user.nick,
+                    _ => continue,
+                },
+                MetadataTarget::Channel(name) => name.clone(),
+            };
+            for (key, entry) in database.list_metadata(&target) {
+                messages.push(Message::new(
+                    MessageType::Encap,
+                    vec!["*".to_string(), "METADATA".to_string(), name.clone(), key, entry.visibility.token().to_string(), entry.value],
+                ));
+            }
+        }
+        messages
+    }
+}
+
+impl Default for MetadataModule {
+    fn default() -> Self {
+        Self::new(MetadataConfig::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{context, registered_client};
+    use rustircd_core::{Config, ServerConnectionManager};
+    use tokio::sync::mpsc;
+
+    fn metadata(params: &[&str]) -> Message {
+        Message::new(MessageType::Custom("METADATA".to_string()), params.iter().map(|param| param.to_string()).collect())
+    }
+
+    fn drain(rx: &mut mpsc::UnboundedReceiver<Message>) -> Vec<Message> {
+        std::iter::from_fn(|| rx.try_recv().ok()).collect()
+    }
+
+    #[tokio::test]
+    async fn test_set_get_and_notify_subscribers() {
+        let context = context();
+        let mut module = MetadataModule::default();
+        let (alice, mut alice_rx) = registered_client("alice", &context);
+        let (bob, mut bob_rx) = registered_client("bob", &context);
+        let (carol, mut carol_rx) = registered_client("carol", &context);
+        context.database.add_user_to_channel("alice", "#rust").unwrap();
+        context.database.add_user_to_channel("bob", "#rust").unwrap();
+
+        // Bob shares a channel with alice, carol doesn't
+        module.handle_command(&bob, &metadata(&["*", "SUB", "avatar", "url"]), &context).await.unwrap();
+        module.handle_command(&carol, &metadata(&["*", "SUB", "avatar"]), &context).await.unwrap();
+        assert_eq!(drain(&mut bob_rx)[0].params, ["bob", "avatar", "url"]);
+        drain(&mut carol_rx);
+
+        module.handle_command(&alice, &metadata(&["*", "SET", "avatar", "https://example.com/a.png"]), &context).await.unwrap();
+        let replies = drain(&mut alice_rx);
+        assert_eq!(replies[0].command, MessageType::Custom("761".to_string()));
+        assert_eq!(replies[0].params, ["alice", "alice", "avatar", "*", "https://example.com/a.png"]);
+        let notices = drain(&mut bob_rx);
+        assert_eq!(notices.len(), 1);
+        assert_eq!(notices[0].params, ["alice", "avatar", "*", "https://example.com/a.png"]);
+        assert!(drain(&mut carol_rx).is_empty());
+
+        module.handle_command(&carol, &metadata(&["alice", "GET", "avatar", "url"]), &context).await.unwrap();
+        let replies = drain(&mut carol_rx);
+        assert_eq!(replies[0].params[4], "https://example.com/a.png");
+        assert_eq!(replies[1].command, MessageType::Custom("766".to_string()));
+
+        // Only alice (or an operator) may change her keys, and only listed ones
+        module.handle_command(&bob, &metadata(&["alice", "SET", "avatar", "x"]), &context).await.unwrap();
+        assert_eq!(drain(&mut bob_rx)[0].params[1], "KEY_NO_PERMISSION");
+        module.handle_command(&alice, &metadata(&["*", "SET", "shoe-size", "42"]), &context).await.unwrap();
+        assert_eq!(drain(&mut alice_rx)[0].params[1], "KEY_NO_PERMISSION");
+        let long_value = "x".repeat(301);
+        module.handle_command(&alice, &metadata(&["*", "SET", "url", &long_value]), &context).await.unwrap();
+        assert_eq!(drain(&mut alice_rx)[0].params[1], "VALUE_INVALID");
+
+        // Unsetting notifies without a value; values leave with their user
+        module.handle_command(&alice, &metadata(&["*", "SET", "avatar"]), &context).await.unwrap();
+        assert_eq!(drain(&mut bob_rx)[0].params, ["alice", "avatar", "*"]);
+        context.database.set_metadata(&MetadataTarget::User(alice.id), "url", Some(MetadataEntry { value: "u".to_string(), visibility: MetadataVisibility::Public }));
+        context.database.remove_user(alice.id).unwrap();
+        assert!(context.database.list_metadata(&MetadataTarget::User(alice.id)).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_burst_and_remote_metadata() {
+        let database = Arc::new(Database::new(100, 1));
+        let context = ModuleContext::new(
+            database.clone(),
+            Arc::new(ServerConnectionManager::new(Arc::new(Config::default()))),
+        );
+        let mut module = MetadataModule::default();
+        module.attach_database(database.clone()).await;
+        let (alice, mut alice_rx) = registered_client("alice", &context);
+        context.database.add_user_to_channel("alice", "#rust").unwrap();
+        module.handle_command(&alice, &metadata(&["*", "SUB", "display-name"]), &context).await.unwrap();
+        drain(&mut alice_rx);
+
+        let remote = |params: &[&str]| Message::new(MessageType::Custom("METADATA".to_string()), params.iter().map(|param| param.to_string()).collect());
+        module.handle_server_message("hub.example.com", &remote(&["#rust", "display-name", "*", "Rustaceans"]), &context).await.unwrap();
+        assert_eq!(drain(&mut alice_rx)[0].params, ["#rust", "display-name", "*", "Rustaceans"]);
+
+        let burst = module.server_burst("hub.example.com").await;
+        assert_eq!(burst.len(), 1);
+        assert_eq!(burst[0].command, MessageType::Encap);
+        assert_eq!(burst[0].params, ["*", "METADATA", "#rust", "display-name", "*", "Rustaceans"]);
+    }
+}
@@ -465,6 +465,24 @@ impl Module for MonitorModule {
         });
         Ok(())
     }
+    
+    async fn export_state(&self) -> Result<Option<serde_json::Value>> {
+        let client_monitors = self.client_monitors.read().await;
+        Ok(Some(serde_json::to_value(&*client_monitors)?))
+    }
+    
+    async fn import_state(&mut self, state: serde_json::Value) -> Result<()> {
+        let client_monitors: HashMap<uuid::Uuid, HashSet<String>> = serde_json::from_value(state)?;
+        let mut monitored_users: HashMap<String, HashSet<uuid::Uuid>> = HashMap::new();
+        for (client_id, nicks) in &client_monitors {
+            for nick in nicks {
+                monitored_users.entry(nick.clone()).or_default().insert(*client_id);
+            }
+        }
+        *self.monitored_users.write().await = monitored_users;
+        *self.client_monitors.write().await = client_monitors;
+        Ok(())
+    }
 }
 
 impl Default for MonitorModule {
@@ -560,4 +578,18 @@ mod tests {
         let monitored = module.get_monitored_users(client_id).await;
         assert!(monitored.is_empty());
     }
+    
+    #[tokio::test]
+    async fn test_monitors_survive_module_replacement() {
+        let mut manager = rustircd_core::ModuleManager::default();
+        let module = MonitorModule::new();
+        let client_id = uuid::Uuid::new_v4();
+        module.add_monitor(client_id, "alice").await.unwrap();
+        manager.load_module(Box::new(module)).await.unwrap();
+        
+        manager.replace_module(Box::new(MonitorModule::new())).await.unwrap();
+        
+        let state = manager.get_module("monitor").unwrap().export_state().await.unwrap().unwrap();
+        assert_eq!(state, serde_json::json!({ client_id.to_string(): ["alice"] }));
+    }
 }
@@ -3,10 +3,13 @@
 //! This module provides operator authentication and management functionality,
 //! moved from core to follow Solanum's modular architecture.
 
-use rustircd_core::{User, Message, Client, Result, Error, NumericReply, Config, ModuleNumericManager, module::{ModuleContext, ModuleResult, ModuleStatsResponse}};
-use rustircd_core::config::OperatorFlag;
+use rustircd_core::{User, Message, Client, Result, Error, NumericReply, Config, ModuleNumericManager, SnoMask, AuthFailureKind, module::{ModuleContext, ModuleResult, ModuleStatsResponse}};
+use rustircd_core::challenge::{ChallengeKey, PendingChallenge, CHALLENGE_LINE_LEN};
+use rustircd_core::config::{OperatorConfig, OperatorFlag};
+use rustircd_core::snomask::{apply_snomask_changes, snomask_string};
 use rustircd_core::audit::{AuditEvent, AuditEventType, AuditLogger};
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
+use std::time::{Duration, Instant};
 use uuid::Uuid;
 use async_trait::async_trait;
 use tracing::info;
@@ -17,6 +20,18 @@ pub struct OperModule {
     config: OperConfig,
     /// Audit logger for security events
     audit_logger: AuditLogger,
+    /// Unanswered CHALLENGEs by client
+    challenges: HashMap<Uuid, OperChallenge>,
+}
+
+/// How long a CHALLENGE waits for its response
+const CHALLENGE_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// A CHALLENGE waiting for the client's response
+struct OperChallenge {
+    oper_name: String,
+    pending: PendingChallenge,
+    issued: Instant,
 }
 
 /// Configuration for the oper module
@@ -56,6 +71,7 @@ impl OperModule {
         Self {
             config,
             audit_logger,
+            challenges: HashMap::new(),
         }
     }
     
@@ -70,110 +86,226 @@ impl OperModule {
             return Ok(());
         }
         
-        if message.params.len() < 2 {
-            let error_msg = NumericReply::need_more_params("OPER");
-            let _ = client.send(error_msg);
+        // Refuse outright while the IP is locked out after failed attempts
+        if context.auth_lockout(client).await.is_some() {
+            self.reject_oper(client, NumericReply::password_mismatch(), &message.params[0], "OPER", "Locked out after repeated failures", context).await;
             return Ok(());
         }
         
         let oper_name = &message.params[0];
-        let password = &message.params[1];
+        let password = message.params.get(1).map(String::as_str);
         
-        // Get username and host from client
-        let username = client.username().unwrap_or_default();
-        let host = &client.remote_addr;
+        if password.is_none() && config.find_operator_by_nickname(oper_name).is_some_and(|oper| oper.requires_password()) {
+            let _ = client.send(NumericReply::need_more_params("OPER"));
+            return Ok(());
+        }
+        
+        // The oper block may also require the account the user logged in to
+        // and the certificate they connected with
+        let user = Self::current_user(client, context);
+        let account = user.as_ref().and_then(|user| user.account.clone());
+        let operator_config = config.find_operator_by_nickname(oper_name).filter(|oper| {
+            Self::matches_client_host(oper, client, user.as_ref())
+                && oper.verify_credentials(password, account.as_deref(), client.certfp.as_deref())
+        });
         
         // Authenticate operator against config
-        if let Some(operator_config) = config.authenticate_operator(oper_name, password, &username, host) {
-            // Get the user from the database
-            if let Some(mut user) = context.get_user_by_nick(oper_name) {
-                // Set operator flags on the user
-                let mut operator_flags = HashSet::new();
-                for flag in &operator_config.flags {
-                    operator_flags.insert(*flag);
+        match (operator_config, user) {
+            (Some(operator_config), Some(user)) => {
+                self.grant_operator(client, user, operator_config, config, context, "OPER").await?;
+            }
+            (Some(_), None) => {
+                self.reject_oper(client, NumericReply::password_mismatch(), oper_name, "OPER", "User not found in database", context).await;
+            }
+            (None, _) => {
+                self.reject_oper(client, NumericReply::password_mismatch(), oper_name, "OPER", "Invalid credentials", context).await;
+            }
+        }
+        
+        Ok(())
+    }
+    
+    /// Handle CHALLENGE: `CHALLENGE <name>` starts one, `CHALLENGE +<response>` answers it
+    pub async fn handle_challenge(&mut self, client: &Client, message: &Message, config: &Config, context: &ModuleContext) -> Result<()> {
+        if !self.config.enabled {
+            let error_msg = rustircd_core::Message::new(
+                rustircd_core::MessageType::Error,
+                vec!["CHALLENGE command is disabled".to_string()]
+            );
+            let _ = client.send(error_msg);
+            return Ok(());
+        }
+        
+        // Refuse outright while the IP is locked out after failed attempts
+        if context.auth_lockout(client).await.is_some() {
+            self.reject_oper(client, NumericReply::password_mismatch(), &message.params[0], "CHALLENGE", "Locked out after repeated failures", context).await;
+            return Ok(());
+        }
+        
+        let user = Self::current_user(client, context);
+        let account = user.as_ref().and_then(|user| user.account.clone());
+        let nick = client.nickname().unwrap_or("*").to_string();
+        let param = &message.params[0];
+        
+        if let Some(response) = param.strip_prefix('+') {
+            let Some(started) = self.challenges.remove(&client.id) else {
+                self.reject_oper(client, NumericReply::password_mismatch(), "*", "CHALLENGE", "No challenge in progress", context).await;
+                return Ok(());
+            };
+            if started.issued.elapsed() > CHALLENGE_TIMEOUT {
+                self.reject_oper(client, NumericReply::password_mismatch(), &started.oper_name, "CHALLENGE", "Challenge expired", context).await;
+                return Ok(());
+            }
+            
+            // Look the block up again in case a rehash changed it meanwhile
+            let operator_config = config.find_operator_by_nickname(&started.oper_name).filter(|oper| {
+                oper.challenge_key_file.is_some()
+                    && Self::matches_client_host(oper, client, user.as_ref())
+                    && oper.verify_identity(account.as_deref(), client.certfp.as_deref())
+            });
+            match (operator_config, user) {
+                (Some(operator_config), Some(user)) if started.pending.verify(response) => {
+                    self.grant_operator(client, user, operator_config, config, context, "CHALLENGE").await?;
                 }
+                _ => {
+                    self.reject_oper(client, NumericReply::password_mismatch(), &started.oper_name, "CHALLENGE", "Invalid challenge response", context).await;
+                }
+            }
+            return Ok(());
+        }
+        
+        // A new CHALLENGE replaces any unanswered one
+        self.challenges.remove(&client.id);
+        let operator_config = config.find_operator_by_nickname(param).filter(|oper| {
+            Self::matches_client_host(oper, client, user.as_ref())
+                && oper.verify_identity(account.as_deref(), client.certfp.as_deref())
+        });
+        let Some(path) = operator_config.and_then(|oper| oper.challenge_key_file.as_deref()) else {
+            self.reject_oper(client, NumericReply::no_oper_host(&nick), param, "CHALLENGE", "No matching operator block with a challenge key", context).await;
+            return Ok(());
+        };
+        let issued = ChallengeKey::load(path).and_then(|key| key.issue());
+        let (text, pending) = match issued {
+            Ok(issued) => issued,
+            Err(e) => {
+                // The server's fault, so it doesn't count against the client
+                tracing::warn!("Can't start CHALLENGE for operator {}: {}", param, e);
+                let _ = client.send(NumericReply::no_oper_host(&nick));
+                return Ok(());
+            }
+        };
+        
+        // Base64 is ASCII, so every chunk boundary is a char boundary
+        for start in (0..text.len()).step_by(CHALLENGE_LINE_LEN) {
+            let chunk = &text[start..(start + CHALLENGE_LINE_LEN).min(text.len())];
+            let _ = client.send(NumericReply::rsa_challenge(&nick, chunk));
+        }
+        let _ = client.send(NumericReply::end_of_rsa_challenge(&nick));
+        self.challenges.insert(client.id, OperChallenge {
+            oper_name: param.clone(),
+            pending,
+            issued: Instant::now(),
+        });
+        Ok(())
+    }
+    
+    /// The client's user as currently stored in the database
+    fn current_user(client: &Client, context: &ModuleContext) -> Option<User> {
+        client.get_user().and_then(|user| context.database.get_user(&user.id))
+    }
+    
+    /// Whether an oper block's hostmask matches the client's real host or IP
+    fn matches_client_host(operator: &OperatorConfig, client: &Client, user: Option<&User>) -> bool {
+        let username = client.username().unwrap_or_default();
+        let ip = client.remote_addr.parse::<std::net::SocketAddr>()
+            .map(|addr| addr.ip().to_string())
+            .unwrap_or_else(|_| client.remote_addr.clone());
+        user.is_some_and(|user| operator.matches_hostmask(username, &user.real_host))
+            || operator.matches_hostmask(username, &ip)
+    }
+    
+    /// Make an authenticated user an operator with the block's flags
+    async fn grant_operator(&self, client: &Client, mut user: User, operator_config: &OperatorConfig, config: &Config, context: &ModuleContext, method: &str) -> Result<()> {
+        // Set operator flags on the user
+        let mut operator_flags = HashSet::new();
+        for flag in &operator_config.flags {
+            operator_flags.insert(*flag);
+        }
 
-                // Grant operator privileges (this will set the +o mode securely)
-                user.set_operator_flags(operator_flags.clone());
+        // Grant operator privileges (this will set the +o mode securely)
+        user.set_operator_flags(operator_flags.clone());
 
-                // Grant admin umode +a if user has Administrator flag
-                if operator_flags.contains(&OperatorFlag::Administrator) {
-                    user.add_mode_internal('a');
-                    info!("Granted admin umode +a to operator {}", user.nick);
-                }
+        // Grant admin umode +a if user has Administrator flag
+        if operator_flags.contains(&OperatorFlag::Administrator) {
+            user.add_mode_internal('a');
+            info!("Granted admin umode +a to operator {}", user.nick);
+        }
 
-                // Update user in database
-                context.update_user(user.clone())?;
-
-                info!("Operator {} successfully authenticated with flags: {:?}",
-                      user.nick, operator_flags);
-
-                // Audit log successful operator authentication
-                let audit_event = AuditEvent::new(AuditEventType::OperAuth)
-                    .with_user(&user.nick)
-                    .with_user_id(user.id)
-                    .with_username(&user.username)
-                    .with_hostname(&user.host)
-                    .with_ip(host)
-                    .with_method("OPER")
-                    .with_metadata("flags", format!("{:?}", operator_flags))
-                    .with_metadata("oper_name", operator_config.nickname.clone());
-                self.audit_logger.log(&audit_event);
-
-                // Send success message
-                let success_msg = NumericReply::youre_oper();
-                let _ = client.send(success_msg);
-
-                // Send operator privileges information
-                self.send_operator_privileges(client, &operator_flags).await?;
-
-                if self.config.log_operator_actions {
-                    tracing::info!("User {} authenticated as operator with flags: {:?}",
-                        user.nick, operator_flags);
-                }
-            } else {
-                // User not found in database
-                let error_msg = NumericReply::password_mismatch();
-                let _ = client.send(error_msg);
+        // Subscribe to the configured server notice mask
+        user.add_mode_internal('s');
+        user.snomasks = apply_snomask_changes(&user.snomasks, &config.server.oper_snomask);
 
-                // Audit log failed authentication - user not in database
-                let audit_event = AuditEvent::new(AuditEventType::OperAuthFailure)
-                    .with_user(oper_name)
-                    .with_username(username)
-                    .with_ip(host)
-                    .with_method("OPER")
-                    .with_error("User not found in database")
-                    .with_reason("Invalid operator credentials");
-                self.audit_logger.log(&audit_event);
-
-                if self.config.log_operator_actions {
-                    tracing::warn!("Failed operator authentication - user not in database: {}", oper_name);
-                }
-            }
-        } else {
-            // Authentication failed
-            let error_msg = NumericReply::password_mismatch();
-            let _ = client.send(error_msg);
+        // Update user in database
+        context.update_user(user.clone())?;
 
-            // Audit log failed authentication
-            let audit_event = AuditEvent::new(AuditEventType::OperAuthFailure)
-                .with_user(oper_name)
-                .with_username(username)
-                .with_ip(host)
-                .with_method("OPER")
-                .with_error("Invalid credentials")
-                .with_reason("Operator authentication failed - invalid username, password, or hostmask");
-            self.audit_logger.log(&audit_event);
+        info!("Operator {} successfully authenticated with flags: {:?}",
+              user.nick, operator_flags);
 
-            if self.config.log_operator_actions {
-                tracing::warn!("Failed operator authentication attempt for user {} from {}",
-                    oper_name, host);
-            }
+        // Audit log successful operator authentication
+        let audit_event = AuditEvent::new(AuditEventType::OperAuth)
+            .with_user(&user.nick)
+            .with_user_id(user.id)
+            .with_username(&user.username)
+            .with_hostname(&user.host)
+            .with_ip(&client.remote_addr)
+            .with_method(method)
+            .with_metadata("flags", format!("{:?}", operator_flags))
+            .with_metadata("oper_name", operator_config.nickname.clone());
+        self.audit_logger.log(&audit_event);
+        context.audit(audit_event.with_command(method).with_target(operator_config.nickname.clone())).await?;
+
+        // Send success message
+        let success_msg = NumericReply::youre_oper();
+        let _ = client.send(success_msg);
+
+        // Send operator privileges information
+        self.send_operator_privileges(client, &operator_flags).await?;
+        let _ = client.send(NumericReply::snomask(&user.nick, &snomask_string(&user.snomasks)));
+
+        let notice = format!("{} ({}@{}) is now an operator", user.nick, user.username, user.host);
+        context.send_snotice(SnoMask::OperActions, &notice).await?;
+
+        for channel in &config.server.oper_autojoin {
+            context.force_join(client.id, channel);
+        }
+
+        if self.config.log_operator_actions {
+            tracing::info!("User {} authenticated as operator with flags: {:?}",
+                user.nick, operator_flags);
         }
-        
         Ok(())
     }
     
+    /// Refuse an OPER or CHALLENGE attempt, audit it and count it against the client's IP
+    async fn reject_oper(&self, client: &Client, reply: Message, oper_name: &str, method: &str, error: &str, context: &ModuleContext) {
+        let _ = client.send(reply);
+        context.record_auth_failure(client, AuthFailureKind::Oper).await;
+
+        let audit_event = AuditEvent::new(AuditEventType::OperAuthFailure)
+            .with_user(oper_name)
+            .with_username(client.username().unwrap_or_default())
+            .with_ip(&client.remote_addr)
+            .with_method(method)
+            .with_error(error)
+            .with_reason("Operator authentication failed - invalid name, credentials, account, certificate, or hostmask");
+        self.audit_logger.log(&audit_event);
+
+        if self.config.log_operator_actions {
+            tracing::warn!("Failed operator authentication attempt for user {} from {}: {}",
+                oper_name, client.remote_addr, error);
+        }
+    }
+    
     /// Send operator privileges information to client
     async fn send_operator_privileges(&self, client: &Client, flags: &HashSet<OperatorFlag>) -> Result<()> {
         let mut privileges = Vec::new();
@@ -187,6 +319,7 @@ impl OperModule {
                 OperatorFlag::Administrator => privileges.push("Administrator"),
                 OperatorFlag::Spy => privileges.push("Spy"),
                 OperatorFlag::Squit => privileges.push("SQUIT"),
+                OperatorFlag::Override => privileges.push("Override"),
             }
         }
         
@@ -615,7 +748,7 @@ impl rustircd_core::Module for OperModule {
             rustircd_core::MessageType::Oper => {
                 // Get config from server if available
                 let config = if let Some(srv) = server {
-                    srv.config().clone()
+                    srv.config().as_ref().clone()
                 } else {
                     rustircd_core::Config::default()
                 };
@@ -623,10 +756,20 @@ impl rustircd_core::Module for OperModule {
                 self.handle_oper(client, message, &config, context).await?;
                 Ok(ModuleResult::Handled)
             }
+            rustircd_core::MessageType::Custom(ref cmd) if cmd.eq_ignore_ascii_case("CHALLENGE") => {
+                let config = if let Some(srv) = server {
+                    srv.config().as_ref().clone()
+                } else {
+                    rustircd_core::Config::default()
+                };
+                
+                self.handle_challenge(client, message, &config, context).await?;
+                Ok(ModuleResult::Handled)
+            }
             rustircd_core::MessageType::Custom(ref cmd) if cmd == "DEOP" => {
                 // Get config from server if available
                 let config = if let Some(srv) = server {
-                    srv.config().clone()
+                    srv.config().as_ref().clone()
                 } else {
                     rustircd_core::Config::default()
                 };
@@ -646,7 +789,8 @@ impl rustircd_core::Module for OperModule {
         Ok(())
     }
     
-    async fn handle_user_disconnection(&mut self, _user: &rustircd_core::User, _context: &ModuleContext) -> Result<()> {
+    async fn handle_user_disconnection(&mut self, user: &rustircd_core::User, _context: &ModuleContext) -> Result<()> {
+        self.challenges.remove(&user.id);
         Ok(())
     }
     
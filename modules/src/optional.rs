@@ -15,7 +15,7 @@ impl OptionalModule {
         Self {
             name: "optional".to_string(),
             version: "1.0.0".to_string(),
-            description: "Optional IRC commands (AWAY, REHASH, SUMMON, ISON, WALLOPS, etc.)".to_string(),
+            description: "Optional IRC commands (AWAY, SUMMON, ISON, WALLOPS, etc.)".to_string(),
         }
     }
 }
@@ -56,10 +56,6 @@ impl Module for OptionalModule {
                         self.handle_away(client, message).await?;
                         Ok(ModuleResult::Handled)
                     }
-                    "REHASH" => {
-                        self.handle_rehash(client, message).await?;
-                        Ok(ModuleResult::Handled)
-                    }
                     "SUMMON" => {
                         self.handle_summon(client, message).await?;
                         Ok(ModuleResult::Handled)
@@ -172,37 +168,6 @@ impl OptionalModule {
         Ok(())
     }
     
-    async fn handle_rehash(&self, client: &Client, _message: &Message) -> Result<()> {
-        if !client.is_registered() {
-            return Err(Error::User("Client not registered".to_string()));
-        }
-        
-        // Implement rehash command for operators
-        // TODO: Integrate with configuration reload system
-        
-        // Check if client is operator
-        if let Some(user) = &client.user {
-            if !user.is_operator() {
-                tracing::warn!("Non-operator {} attempted REHASH command", user.nickname());
-                return Err(Error::User("Permission denied".to_string()));
-            }
-            
-            tracing::info!("Operator {} requested configuration rehash", user.nickname());
-            
-            // In production, this would:
-            // 1. Reload server configuration from config files
-            // 2. Update module configurations
-            // 3. Restart services if needed
-            // 4. Send success/failure message to operator
-            // 5. Log the rehash operation
-            
-            tracing::debug!("Would reload configuration for operator {}", user.nickname());
-        } else {
-            return Err(Error::User("User not found".to_string()));
-        }
-        
-        Ok(())
-    }
     
     async fn handle_summon(&self, client: &Client, message: &Message) -> Result<()> {
         if !client.is_registered() {
@@ -0,0 +1,85 @@
+//! Module Registry
+//!
+//! Constructors for the modules in this crate, keyed by the names used in
+//! `modules.enabled_modules`. The binary hands these to the server before
+//! `Server::init`, which builds whichever modules the configuration enables.
+
+use rustircd_core::{Config, ModuleRegistry};
+
+use crate::{
+    create_messaging_module_with_config, AdminInfo, AdminModule, ChannelModule, DlineModule,
+    GlineModule, HelpModule, Ircv3Module, KlineModule, KnockModule, MetadataModule, MonitorModule, OperConfig,
+    OperModule, OpmeModule, OptionalModule, ResvModule, SaslModule, ServicesModule,
+    SetModule, ShunModule, TestingModule, ThrottlingModule, XlineModule,
+};
+
+/// Register every module in this crate
+pub fn register_modules(registry: &mut ModuleRegistry) {
+    registry.register("channel", |config| {
+        Ok(Box::new(ChannelModule::new().with_list_config(config.server.safelist.clone())))
+    });
+    registry.register("ircv3", |config| {
+        Ok(Box::new(Ircv3Module::new().with_typing_config(config.modules.typing.clone())))
+    });
+    registry.register("optional", |_| Ok(Box::new(OptionalModule::new())));
+    registry.register("throttling", |config| {
+        Ok(Box::new(ThrottlingModule::new(config.modules.throttling.clone())))
+    });
+    registry.register("messaging", |config| {
+        Ok(Box::new(create_messaging_module_with_config(&config.modules.messaging)))
+    });
+    registry.register("help", |_| Ok(Box::new(HelpModule::new())));
+    registry.register("monitor", |_| Ok(Box::new(MonitorModule::new())));
+    registry.register("knock", |_| Ok(Box::new(KnockModule::new())));
+    registry.register("metadata", |config| Ok(Box::new(MetadataModule::new(config.modules.metadata.clone()))));
+    registry.register("set", |_| Ok(Box::new(SetModule::new())));
+    registry.register("gline", |_| Ok(Box::new(GlineModule::new())));
+    registry.register("kline", |_| Ok(Box::new(KlineModule::new())));
+    registry.register("dline", |_| Ok(Box::new(DlineModule::new())));
+    registry.register("xline", |_| Ok(Box::new(XlineModule::new())));
+    registry.register("shun", |_| Ok(Box::new(ShunModule::new())));
+    registry.register("resv", |_| Ok(Box::new(ResvModule::new())));
+    registry.register("admin", |config| Ok(Box::new(AdminModule::with_info(admin_info(config)))));
+    registry.register("testing", |_| Ok(Box::new(TestingModule::new())));
+    registry.register("services", |_| Ok(Box::new(ServicesModule::new())));
+    registry.register("oper", |_| Ok(Box::new(OperModule::new(OperConfig::default()))));
+    registry.register("opme", |_| Ok(Box::new(OpmeModule::default())));
+    // Checks credentials with the server's authentication providers once loaded
+    registry.register("sasl", |_| Ok(Box::new(SaslModule::default())));
+}
+
+/// A registry holding every module in this crate
+pub fn module_registry() -> ModuleRegistry {
+    let mut registry = ModuleRegistry::new();
+    register_modules(&mut registry);
+    registry
+}
+
+/// ADMIN details taken from the server section of the configuration
+fn admin_info(config: &Config) -> AdminInfo {
+    AdminInfo {
+        server_name: config.server.name.clone(),
+        server_description: config.server.description.clone(),
+        server_version: config.server.version.clone(),
+        admin_email: config.server.admin_email.clone(),
+        admin_location: config.server.admin_location1.clone(),
+        ..AdminInfo::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_registry_builds_configured_modules() {
+        let registry = module_registry();
+        let config = Config::default();
+
+        for name in ["channel", "ircv3", "optional", "throttling", "oper", "sasl"] {
+            let module = registry.create(name, &config).unwrap().unwrap();
+            assert!(!module.name().is_empty());
+        }
+        assert!(registry.create("nonexistent", &config).is_none());
+    }
+}
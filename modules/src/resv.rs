@@ -0,0 +1,558 @@
+//! RESV Module
+//!
+//! Provides nick and channel reservations (RESV), keeping names such as
+//! services nicks or staff channels out of reach of ordinary users.
+//! Based on Ratbox's m_resv.
+
+use rustircd_core::{
+    async_trait, Client, Error, Message, MessageType, Module,
+    ModuleNumericManager, module::{ModuleResult, ModuleStatsResponse, ModuleContext},
+    NumericReply, Result, User, BanManager, BanEntry, BanType, BanTarget, BanQuery, SnoMask
+};
+use tracing::{debug, info, warn};
+use std::sync::Arc;
+use crate::help::{HelpProvider, HelpTopic};
+
+/// RESV module for nick and channel reservations
+pub struct ResvModule {
+    /// Active reservations
+    resvs: Arc<BanManager>,
+    /// Configuration
+    config: ResvConfig,
+}
+
+/// Reservation entry, stored in the shared ban manager as a `RESV` ban
+pub type Reservation = BanEntry;
+
+/// Configuration for RESV management
+#[derive(Debug, Clone)]
+pub struct ResvConfig {
+    pub max_duration: u64, // in seconds
+    pub allow_permanent_resvs: bool,
+    /// Operators may use reserved names
+    pub exempt_operators: bool,
+}
+
+impl Default for ResvConfig {
+    fn default() -> Self {
+        Self {
+            max_duration: 86400 * 30, // 30 days
+            allow_permanent_resvs: true,
+            exempt_operators: true,
+        }
+    }
+}
+
+impl ResvModule {
+    /// Create a new RESV module
+    pub fn new() -> Self {
+        Self {
+            resvs: Self::standalone_ban_manager(),
+            config: ResvConfig::default(),
+        }
+    }
+
+    /// Create a new RESV module with custom configuration
+    pub fn with_config(config: ResvConfig) -> Self {
+        Self {
+            resvs: Self::standalone_ban_manager(),
+            config,
+        }
+    }
+
+    /// Ban type registered with the ban manager
+    fn ban_type() -> BanType {
+        BanType::new("RESV", BanTarget::Name, "Reserved").global().without_disconnect()
+    }
+
+    /// Ban manager used until the module is loaded into a server
+    fn standalone_ban_manager() -> Arc<BanManager> {
+        let ban_manager = Arc::new(BanManager::new());
+        ban_manager.register_type(Self::ban_type());
+        ban_manager
+    }
+
+    /// Find the reservation covering a nick or channel name
+    pub fn find_reservation(&self, name: &str) -> Option<Reservation> {
+        self.resvs.find_ban_of_type("RESV", &BanQuery::from_name(name))
+    }
+
+    /// Whether a client may use reserved names
+    fn is_exempt(&self, client: &Client) -> bool {
+        self.config.exempt_operators && client.user.as_ref().is_some_and(|u| u.is_operator())
+    }
+
+    /// Refuse a NICK change to a reserved nickname
+    fn check_nick(&self, client: &Client, message: &Message) -> Result<ModuleResult> {
+        let Some(nick) = message.params.first() else {
+            return Ok(ModuleResult::NotHandled);
+        };
+
+        match self.find_reservation(nick) {
+            Some(resv) => {
+                debug!("Refusing reserved nick {} ({})", nick, resv.mask);
+                client.send(NumericReply::reserved_nickname(nick, &resv.reason))?;
+                Ok(ModuleResult::HandledStop)
+            }
+            None => Ok(ModuleResult::NotHandled),
+        }
+    }
+
+    /// Refuse a JOIN naming reserved channels
+    ///
+    /// The command can't be rewritten on its way to the core, so a JOIN naming
+    /// any reserved channel is refused as a whole.
+    fn check_join(&self, client: &Client, message: &Message) -> Result<ModuleResult> {
+        let Some(channels) = message.params.first() else {
+            return Ok(ModuleResult::NotHandled);
+        };
+
+        let mut refused = false;
+        for channel in channels.split(',') {
+            if let Some(resv) = self.find_reservation(channel) {
+                debug!("Refusing join to reserved channel {} ({})", channel, resv.mask);
+                client.send(NumericReply::bad_chan_name(channel, &resv.reason))?;
+                refused = true;
+            }
+        }
+
+        Ok(if refused { ModuleResult::HandledStop } else { ModuleResult::NotHandled })
+    }
+
+    /// Handle RESV command
+    async fn handle_resv(&self, client: &Client, user: &User, args: &[String], context: &ModuleContext) -> Result<()> {
+        if !user.is_operator() {
+            client.send_numeric(NumericReply::ErrNoPrivileges, &["Permission denied"])?;
+            return Ok(());
+        }
+
+        if args.is_empty() {
+            self.list_resvs(client)?;
+            return Ok(());
+        }
+
+        if args.len() < 2 {
+            client.send_numeric(NumericReply::ErrNeedMoreParams, &["RESV", "Not enough parameters"])?;
+            return Ok(());
+        }
+
+        let mask = &args[0];
+        let duration = match self.parse_duration(&args[1]) {
+            Ok(duration) => duration,
+            Err(_) => {
+                client.send_numeric(NumericReply::ErrInvalidDuration, &[&args[1], "Invalid duration"])?;
+                return Ok(());
+            }
+        };
+        let reason = if args.len() > 2 {
+            args[2..].join(" ")
+        } else {
+            "Reserved".to_string()
+        };
+
+        self.add_resv(client, user, mask, &reason, duration, context).await
+    }
+
+    /// Handle UNRESV command
+    async fn handle_unresv(&self, client: &Client, user: &User, args: &[String], context: &ModuleContext) -> Result<()> {
+        if !user.is_operator() {
+            client.send_numeric(NumericReply::ErrNoPrivileges, &["Permission denied"])?;
+            return Ok(());
+        }
+
+        if args.is_empty() {
+            client.send_numeric(NumericReply::ErrNeedMoreParams, &["UNRESV", "Not enough parameters"])?;
+            return Ok(());
+        }
+
+        let mask = &args[0];
+        if self.resvs.remove_ban("RESV", mask).is_some() {
+            self.send_notice(client, &format!("Removed reservation for [{}]", mask))?;
+            info!("RESV removed: {} by {}", mask, user.nickname());
+
+            let notice = format!("{} has removed the reservation for [{}]", user.nickname(), mask);
+            context.send_snotice(SnoMask::Bans, &notice).await?;
+
+            let message = Message::new(
+                MessageType::Custom("UNRESV".to_string()),
+                vec![mask.to_string(), user.nickname().to_string(), chrono::Utc::now().timestamp().to_string()]
+            );
+            context.broadcast_to_servers(message).await?;
+        } else {
+            self.send_notice(client, &format!("No reservation found for [{}]", mask))?;
+        }
+
+        Ok(())
+    }
+
+    /// Add a reservation and propagate it to the network
+    async fn add_resv(&self, client: &Client, user: &User, mask: &str, reason: &str, duration: Option<u64>, context: &ModuleContext) -> Result<()> {
+        match duration {
+            Some(dur) if dur > self.config.max_duration => {
+                client.send_numeric(NumericReply::ErrInvalidDuration, &[&format!("Maximum duration is {} seconds", self.config.max_duration)])?;
+                return Ok(());
+            }
+            None if !self.config.allow_permanent_resvs => {
+                client.send_numeric(NumericReply::ErrInvalidDuration, &["Permanent reservations are not allowed"])?;
+                return Ok(());
+            }
+            _ => {}
+        }
+
+        let resv = BanEntry::new("RESV", mask, reason, user.nickname(), duration);
+        self.resvs.add_ban(resv.clone())?;
+
+        self.send_notice(client, &format!("Added reservation for [{}] [{}]", mask, reason))?;
+        info!("RESV added: {} by {} - {}", mask, user.nickname(), reason);
+
+        let duration_str = if let Some(dur) = duration {
+            format!("temporary {} min. ", dur / 60)
+        } else {
+            String::new()
+        };
+        let notice = format!("{} is adding a {}RESV for [{}] [{}]", user.nickname(), duration_str, mask, reason);
+        context.send_snotice(SnoMask::Bans, &notice).await?;
+
+        let message = Message::new(MessageType::Custom("RESV".to_string()), resv.to_params());
+        context.broadcast_to_servers(message).await?;
+
+        Ok(())
+    }
+
+    /// List active reservations to an operator
+    fn list_resvs(&self, client: &Client) -> Result<()> {
+        let resvs = self.resvs.list_bans("RESV");
+
+        if resvs.is_empty() {
+            self.send_notice(client, "No reservations set")?;
+            return Ok(());
+        }
+
+        for resv in &resvs {
+            self.send_notice(client, &Self::describe(resv))?;
+        }
+        self.send_notice(client, "End of reservation list")?;
+        Ok(())
+    }
+
+    /// One-line description of a reservation, as shown by RESV and STATS q
+    fn describe(resv: &Reservation) -> String {
+        let expires = resv.expire_time.map(|t| t.to_string()).unwrap_or_else(|| "0".to_string());
+        format!("{} {} {} {} :{}", resv.mask, resv.set_by, resv.set_time, expires, resv.reason)
+    }
+
+    /// Parse duration string (e.g., "1d", "2h", "30m", "3600s")
+    fn parse_duration(&self, duration_str: &str) -> Result<Option<u64>> {
+        if duration_str == "0" || duration_str.is_empty() {
+            return Ok(None);
+        }
+
+        let duration_str = duration_str.to_lowercase();
+        let (number_str, multiplier) = match duration_str.chars().last() {
+            Some('d') => (&duration_str[..duration_str.len() - 1], 86400),
+            Some('h') => (&duration_str[..duration_str.len() - 1], 3600),
+            Some('m') => (&duration_str[..duration_str.len() - 1], 60),
+            Some('s') => (&duration_str[..duration_str.len() - 1], 1),
+            _ => (duration_str.as_str(), 1),
+        };
+
+        let number: u64 = number_str.parse()
+            .map_err(|_| Error::Config(format!("Invalid duration: {}", duration_str)))?;
+
+        Ok(Some(number * multiplier))
+    }
+
+    /// Send a server notice to a client
+    fn send_notice(&self, client: &Client, text: &str) -> Result<()> {
+        let target = client.nickname().unwrap_or("*").to_string();
+        client.send(Message::new(MessageType::Notice, vec![target, text.to_string()]))
+    }
+
+    /// Handle RESV message from another server
+    async fn handle_server_resv(&self, server: &str, params: &[String], context: &ModuleContext) -> Result<()> {
+        let resv = match BanEntry::from_params("RESV", params) {
+            Some(resv) => resv,
+            None => {
+                warn!("Invalid RESV message from server {}: malformed parameters", server);
+                return Ok(());
+            }
+        };
+
+        // The most recently set reservation wins; an equal or newer copy means we already have it
+        if let Some(existing) = self.resvs.get_ban("RESV", &resv.mask) {
+            if existing.set_time >= resv.set_time {
+                debug!("Ignoring RESV for {} from server {}: have one set at {}", resv.mask, server, existing.set_time);
+                return Ok(());
+            }
+        }
+
+        if let Err(e) = self.resvs.add_ban(resv.clone()) {
+            warn!("Invalid RESV from server {}: {}", server, e);
+            return Ok(());
+        }
+
+        info!("RESV received from server {}: {} - {}", server, resv.mask, resv.reason);
+
+        let message = Message::new(MessageType::Custom("RESV".to_string()), resv.to_params());
+        context.server_connections.broadcast_message(&message, Some(server)).await?;
+
+        Ok(())
+    }
+
+    /// Handle UNRESV message from another server
+    async fn handle_server_unresv(&self, server: &str, params: &[String], context: &ModuleContext) -> Result<()> {
+        let mask = match params.first() {
+            Some(mask) => mask,
+            None => {
+                warn!("Invalid UNRESV message from server {}: no parameters", server);
+                return Ok(());
+            }
+        };
+        let removed_by = params.get(1).map(|s| s.as_str()).unwrap_or("unknown");
+        let removed_at = params.get(2).and_then(|t| t.parse::<u64>().ok());
+
+        // A reservation set after the removal was issued survives it
+        if let (Some(existing), Some(removed_at)) = (self.resvs.get_ban("RESV", mask), removed_at) {
+            if existing.set_time > removed_at {
+                debug!("Ignoring UNRESV for {} from server {}: reservation was reset at {}", mask, server, existing.set_time);
+                return Ok(());
+            }
+        }
+
+        if self.resvs.remove_ban("RESV", mask).is_some() {
+            info!("UNRESV received from server {}: {} removed by {}", server, mask, removed_by);
+
+            let message = Message::new(MessageType::Custom("UNRESV".to_string()), params.to_vec());
+            context.server_connections.broadcast_message(&message, Some(server)).await?;
+        } else {
+            debug!("UNRESV received from server {} for non-existent reservation: {}", server, mask);
+        }
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Module for ResvModule {
+    fn name(&self) -> &str {
+        "resv"
+    }
+
+    fn description(&self) -> &str {
+        "Reserves nicknames and channel names network-wide"
+    }
+
+    fn version(&self) -> &str {
+        "1.0.0"
+    }
+
+    async fn init(&mut self) -> Result<()> {
+        info!("{} module initialized", self.name());
+        Ok(())
+    }
+
+    async fn handle_message(&mut self, client: &Client, message: &Message, context: &ModuleContext) -> Result<ModuleResult> {
+        if !self.is_exempt(client) {
+            match message.command {
+                MessageType::Nick => return self.check_nick(client, message),
+                MessageType::Join => return self.check_join(client, message),
+                _ => {}
+            }
+        }
+
+        let user = match &client.user {
+            Some(u) => u,
+            None => return Ok(ModuleResult::NotHandled),
+        };
+
+        match message.command {
+            MessageType::Custom(ref cmd) if cmd == "RESV" => {
+                self.handle_resv(client, user, &message.params, context).await?;
+                Ok(ModuleResult::Handled)
+            }
+            MessageType::Custom(ref cmd) if cmd == "UNRESV" => {
+                self.handle_unresv(client, user, &message.params, context).await?;
+                Ok(ModuleResult::Handled)
+            }
+            _ => Ok(ModuleResult::NotHandled),
+        }
+    }
+
+    async fn handle_server_message(&mut self, server: &str, message: &Message, context: &ModuleContext) -> Result<ModuleResult> {
+        match message.command {
+            MessageType::Custom(ref cmd) if cmd == "RESV" => {
+                self.handle_server_resv(server, &message.params, context).await?;
+                Ok(ModuleResult::Handled)
+            }
+            MessageType::Custom(ref cmd) if cmd == "UNRESV" => {
+                self.handle_server_unresv(server, &message.params, context).await?;
+                Ok(ModuleResult::Handled)
+            }
+            _ => Ok(ModuleResult::NotHandled),
+        }
+    }
+
+    async fn handle_user_registration(&mut self, _user: &User, _context: &ModuleContext) -> Result<()> {
+        Ok(())
+    }
+
+    async fn handle_user_disconnection(&mut self, _user: &User, _context: &ModuleContext) -> Result<()> {
+        Ok(())
+    }
+
+    fn get_capabilities(&self) -> Vec<String> {
+        vec!["message_handler".to_string(), "server_message_handler".to_string()]
+    }
+
+    fn supports_capability(&self, capability: &str) -> bool {
+        capability == "message_handler" || capability == "server_message_handler"
+    }
+
+    fn get_numeric_replies(&self) -> Vec<u16> {
+        vec![
+            NumericReply::ErrErroneousNickname.numeric_code(),
+            NumericReply::ErrBadChanName.numeric_code(),
+            NumericReply::ErrInvalidDuration.numeric_code(),
+        ]
+    }
+
+    fn handles_numeric_reply(&self, _numeric: u16) -> bool {
+        false
+    }
+
+    async fn handle_numeric_reply(&mut self, _numeric: u16, _params: Vec<String>) -> Result<()> {
+        Ok(())
+    }
+
+    async fn handle_stats_query(&mut self, query: &str, _client_id: uuid::Uuid, _server: Option<&rustircd_core::Server>) -> Result<Vec<ModuleStatsResponse>> {
+        if query != "q" {
+            return Ok(vec![]);
+        }
+
+        Ok(self.resvs.list_bans("RESV")
+            .iter()
+            .map(|resv| ModuleStatsResponse::Stats("q".to_string(), Self::describe(resv)))
+            .collect())
+    }
+
+    fn get_stats_queries(&self) -> Vec<String> {
+        vec!["q".to_string()]
+    }
+
+    fn register_numerics(&self, _manager: &mut ModuleNumericManager) -> Result<()> {
+        Ok(())
+    }
+
+    fn register_ban_types(&mut self, ban_manager: Arc<BanManager>) -> Result<()> {
+        ban_manager.register_type(Self::ban_type());
+        self.resvs = ban_manager;
+        Ok(())
+    }
+
+    async fn cleanup(&mut self) -> Result<()> {
+        info!("RESV module cleaned up");
+        Ok(())
+    }
+}
+
+impl Default for ResvModule {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl HelpProvider for ResvModule {
+    fn get_help_topics(&self) -> Vec<HelpTopic> {
+        ["RESV", "UNRESV"].iter()
+            .filter_map(|command| self.get_command_help(command))
+            .collect()
+    }
+
+    fn get_command_help(&self, command: &str) -> Option<HelpTopic> {
+        match command {
+            "RESV" => Some(HelpTopic {
+                command: "RESV".to_string(),
+                syntax: "RESV [<nick|#channel> <duration> <reason>]".to_string(),
+                description: "Reserve a nickname mask or channel name so only operators can use it. Without parameters, lists active reservations".to_string(),
+                oper_only: true,
+                examples: vec![
+                    "RESV *Serv 0 Reserved for services".to_string(),
+                    "RESV #opers 0 Staff only".to_string(),
+                ],
+                module_name: Some("resv".to_string()),
+            }),
+            "UNRESV" => Some(HelpTopic {
+                command: "UNRESV".to_string(),
+                syntax: "UNRESV <nick|#channel>".to_string(),
+                description: "Remove a reservation".to_string(),
+                oper_only: true,
+                examples: vec![
+                    "UNRESV #opers".to_string(),
+                ],
+                module_name: Some("resv".to_string()),
+            }),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{client_for, context, oper, user};
+
+    #[tokio::test]
+    async fn test_reserved_names_are_refused() {
+        let mut module = ResvModule::new();
+        let context = context();
+        module.resvs.add_ban(BanEntry::new("RESV", "*Serv", "Reserved for services", "oper", None)).unwrap();
+        module.resvs.add_ban(BanEntry::new("RESV", "#opers", "Staff only", "oper", None)).unwrap();
+        let (client, mut rx) = client_for(user("alice"));
+
+        let nick = Message::new(MessageType::Nick, vec!["NickServ".to_string()]);
+        let result = module.handle_message(&client, &nick, &context).await.unwrap();
+        assert!(matches!(result, ModuleResult::HandledStop));
+        let reply = rx.try_recv().unwrap();
+        assert_eq!(reply.command, MessageType::Custom("432".to_string()));
+
+        let join = Message::new(MessageType::Join, vec!["#rust,#OPERS".to_string()]);
+        let result = module.handle_message(&client, &join, &context).await.unwrap();
+        assert!(matches!(result, ModuleResult::HandledStop));
+        let reply = rx.try_recv().unwrap();
+        assert_eq!(reply.command, MessageType::Custom("479".to_string()));
+        assert_eq!(reply.params[0], "#OPERS");
+        assert!(rx.try_recv().is_err());
+
+        let join = Message::new(MessageType::Join, vec!["#rust".to_string()]);
+        let result = module.handle_message(&client, &join, &context).await.unwrap();
+        assert!(matches!(result, ModuleResult::NotHandled));
+
+        // Operators may use reserved names
+        let (oper, _rx) = client_for(oper("alice"));
+        let result = module.handle_message(&oper, &nick, &context).await.unwrap();
+        assert!(matches!(result, ModuleResult::NotHandled));
+    }
+
+    #[tokio::test]
+    async fn test_remote_resv_newest_wins() {
+        let module = ResvModule::new();
+        let context = context();
+        let params = |reason: &str, set_time: u64| vec![
+            "#opers".to_string(),
+            reason.to_string(),
+            "oper".to_string(),
+            "0".to_string(),
+            set_time.to_string(),
+        ];
+
+        module.handle_server_resv("hub.test", &params("newer", 2000), &context).await.unwrap();
+        module.handle_server_resv("leaf.test", &params("older", 1000), &context).await.unwrap();
+        assert_eq!(module.find_reservation("#opers").unwrap().reason, "newer");
+
+        let unresv = |removed_at: u64| vec!["#opers".to_string(), "oper".to_string(), removed_at.to_string()];
+        module.handle_server_unresv("leaf.test", &unresv(1500), &context).await.unwrap();
+        assert!(module.find_reservation("#opers").is_some());
+        module.handle_server_unresv("hub.test", &unresv(2500), &context).await.unwrap();
+        assert!(module.find_reservation("#opers").is_none());
+    }
+}
@@ -3,12 +3,18 @@
 //! This module provides SASL authentication support as per IRCv3 specification.
 //! It supports various SASL mechanisms including PLAIN, EXTERNAL, and SCRAM-SHA-256.
 
-use rustircd_core::{Message, Client, Result, Error, NumericReply, MessageType, ModuleNumericManager, module::{ModuleContext, ModuleResult, ModuleStatsResponse}, AuthManager, AuthRequest, ClientInfo};
+use rustircd_core::{Message, Client, Result, Error, NumericReply, MessageType, ModuleNumericManager, module::{ModuleContext, ModuleResult, ModuleStatsResponse}, AuthManager, AuthRequest, AuthFailureKind, ClientInfo};
 use std::collections::HashMap;
 use uuid::Uuid;
 use async_trait::async_trait;
 use base64::{Engine as _, engine::general_purpose};
 
+/// Longest AUTHENTICATE parameter; a full-length one means more data follows
+const AUTHENTICATE_CHUNK_LEN: usize = 400;
+
+/// Longest SASL message accepted once its chunks are put together
+const MAX_SASL_MESSAGE_LEN: usize = 8192;
+
 /// SASL module for handling SASL authentication
 pub struct SaslModule {
     /// Module configuration
@@ -36,6 +42,8 @@ pub struct SaslConfig {
     pub require_sasl: bool,
     /// SASL timeout in seconds
     pub timeout_seconds: u64,
+    /// Failed attempts a connection gets before AUTHENTICATE is refused outright
+    pub max_failures: u32,
 }
 
 impl Default for SaslConfig {
@@ -47,6 +55,7 @@ impl Default for SaslConfig {
             sasl_service: "SaslServ".to_string(),
             require_sasl: false,
             timeout_seconds: 300, // 5 minutes
+            max_failures: 3,
         }
     }
 }
@@ -68,6 +77,10 @@ pub struct SaslSession {
     pub created_at: chrono::DateTime<chrono::Utc>,
     /// Last activity time
     pub last_activity: chrono::DateTime<chrono::Utc>,
+    /// Data received so far of a message split over several AUTHENTICATE lines
+    pub buffer: String,
+    /// Failed attempts on this connection
+    pub failures: u32,
 }
 
 /// SASL authentication data
@@ -126,6 +139,8 @@ pub struct SaslResponse {
     pub data: Option<String>,
     /// Error message if any
     pub error: Option<String>,
+    /// Account the client logged in to, on success
+    pub account: Option<String>,
 }
 
 /// SASL response types
@@ -177,6 +192,7 @@ impl SaslMechanism for PlainMechanism {
                 response_type: SaslResponseType::Continue,
                 data: None,
                 error: None,
+                account: None,
             })
         }
     }
@@ -196,6 +212,7 @@ impl SaslMechanism for PlainMechanism {
                 response_type: SaslResponseType::Failure,
                 data: None,
                 error: Some("Invalid auth string format".to_string()),
+                account: None,
             });
         }
         
@@ -233,6 +250,7 @@ impl SaslMechanism for PlainMechanism {
                     response_type: SaslResponseType::Success,
                     data: None,
                     error: None,
+                    account: Some(auth_info.username),
                 })
             }
             rustircd_core::AuthResult::Failure(reason) => {
@@ -242,6 +260,7 @@ impl SaslMechanism for PlainMechanism {
                     response_type: SaslResponseType::Failure,
                     data: None,
                     error: Some(reason),
+                    account: None,
                 })
             }
             rustircd_core::AuthResult::Challenge(challenge) => {
@@ -251,6 +270,7 @@ impl SaslMechanism for PlainMechanism {
                     response_type: SaslResponseType::Challenge,
                     data: Some(challenge),
                     error: None,
+                    account: None,
                 })
             }
             rustircd_core::AuthResult::InProgress => {
@@ -260,6 +280,7 @@ impl SaslMechanism for PlainMechanism {
                     response_type: SaslResponseType::Continue,
                     data: None,
                     error: None,
+                    account: None,
                 })
             }
         }
@@ -308,21 +329,77 @@ impl SaslMechanism for ExternalMechanism {
         true
     }
     
-    async fn start(&self, _client: &Client, initial_data: Option<&str>) -> Result<SaslResponse> {
-        // EXTERNAL mechanism uses client certificate or other external authentication
-        // For now, we'll just return success
+    async fn start(&self, _client: &Client, _initial_data: Option<&str>) -> Result<SaslResponse> {
+        // The client answers with an empty response (or an authzid)
         Ok(SaslResponse {
-            response_type: SaslResponseType::Success,
+            response_type: SaslResponseType::Continue,
             data: None,
             error: None,
+            account: None,
         })
     }
     
-    async fn step(&self, _client: &Client, _data: &str) -> Result<SaslResponse> {
-        Ok(SaslResponse {
-            response_type: SaslResponseType::Success,
-            data: None,
-            error: None,
+    async fn step(&self, client: &Client, data: &str) -> Result<SaslResponse> {
+        // The certificate is requested on ports with request_client_cert
+        let Some(certfp) = client.certfp.clone() else {
+            return Ok(SaslResponse {
+                response_type: SaslResponseType::Failure,
+                data: None,
+                error: Some("No client certificate to authenticate with".to_string()),
+                account: None,
+            });
+        };
+        
+        // An empty response logs in to the account the certificate belongs
+        // to; otherwise it names the account
+        let decoded = general_purpose::STANDARD.decode(data)
+            .map_err(|_| Error::MessageParse("Invalid base64 data".to_string()))?;
+        let authzid = String::from_utf8(decoded)
+            .map_err(|_| Error::MessageParse("Invalid UTF-8 data".to_string()))?;
+        let authzid = (!authzid.is_empty()).then_some(authzid);
+        
+        let auth_request = AuthRequest {
+            username: authzid.clone().unwrap_or_default(),
+            credential: certfp.clone(),
+            authzid,
+            client_info: ClientInfo {
+                id: client.id,
+                ip: client.remote_addr.to_string(),
+                hostname: client.user.as_ref().map(|u| u.host.clone()),
+                secure: client.encrypted,
+            },
+            context: HashMap::from([
+                ("mechanism".to_string(), "EXTERNAL".to_string()),
+                ("certfp".to_string(), certfp.clone()),
+            ]),
+        };
+        
+        tracing::info!("SASL EXTERNAL authentication attempt with certificate {}", certfp);
+        Ok(match self.auth_manager.authenticate(&auth_request).await? {
+            rustircd_core::AuthResult::Success(auth_info) => SaslResponse {
+                response_type: SaslResponseType::Success,
+                data: None,
+                error: None,
+                account: Some(auth_info.username),
+            },
+            rustircd_core::AuthResult::Failure(reason) => SaslResponse {
+                response_type: SaslResponseType::Failure,
+                data: None,
+                error: Some(reason),
+                account: None,
+            },
+            rustircd_core::AuthResult::Challenge(challenge) => SaslResponse {
+                response_type: SaslResponseType::Challenge,
+                data: Some(challenge),
+                error: None,
+                account: None,
+            },
+            rustircd_core::AuthResult::InProgress => SaslResponse {
+                response_type: SaslResponseType::Continue,
+                data: None,
+                error: None,
+                account: None,
+            },
         })
     }
     
@@ -339,22 +416,24 @@ impl SaslMechanism for ExternalMechanism {
 impl SaslModule {
     /// Create a new SASL module
     pub fn new(config: SaslConfig, auth_manager: std::sync::Arc<AuthManager>) -> Self {
-        let mut mechanisms: Vec<Box<dyn SaslMechanism>> = Vec::new();
+        Self {
+            mechanisms: Self::build_mechanisms(&config, &auth_manager),
+            config,
+            sessions: std::sync::Arc::new(tokio::sync::RwLock::new(HashMap::new())),
+            auth_manager,
+        }
+    }
 
-        // Add supported mechanisms
+    /// The configured mechanisms, checking credentials with `auth_manager`
+    fn build_mechanisms(config: &SaslConfig, auth_manager: &std::sync::Arc<AuthManager>) -> Vec<Box<dyn SaslMechanism>> {
+        let mut mechanisms: Vec<Box<dyn SaslMechanism>> = Vec::new();
         if config.mechanisms.contains(&"PLAIN".to_string()) {
             mechanisms.push(Box::new(PlainMechanism::new(config.sasl_service.clone(), auth_manager.clone())));
         }
         if config.mechanisms.contains(&"EXTERNAL".to_string()) {
             mechanisms.push(Box::new(ExternalMechanism::new(config.sasl_service.clone(), auth_manager.clone())));
         }
-
-        Self {
-            config,
-            sessions: std::sync::Arc::new(tokio::sync::RwLock::new(HashMap::new())),
-            mechanisms,
-            auth_manager,
-        }
+        mechanisms
     }
 
     /// Validate that SASL can function properly
@@ -425,7 +504,7 @@ impl SaslModule {
         }
         
         match &message.command {
-            MessageType::Custom(cmd) if cmd == "AUTHENTICATE" => self.handle_authenticate(client, message, context).await,
+            MessageType::Authenticate => self.handle_authenticate(client, message, context).await,
             _ => {
                 client.send_numeric(NumericReply::ErrUnknownCommand, &["SASL"])?;
                 Ok(())
@@ -433,181 +512,191 @@ impl SaslModule {
         }
     }
     
-    
     /// Handle AUTHENTICATE command
+    ///
+    /// Only accepted while the client is negotiating capabilities before
+    /// registering, and registration waits while an exchange is running.
+    /// The first AUTHENTICATE names the mechanism; later ones carry base64
+    /// data in chunks of up to 400 bytes, and `*` aborts.
     async fn handle_authenticate(&self, client: &Client, message: &Message, context: &ModuleContext) -> Result<()> {
         if message.params.is_empty() {
-            let error_msg = NumericReply::need_more_params("AUTHENTICATE");
-            let _ = client.send(error_msg);
+            let _ = client.send(NumericReply::need_more_params("AUTHENTICATE"));
+            return Ok(());
+        }
+        let target = reply_target(client);
+        if client.is_registered() {
+            let already = client.get_user().is_some_and(|user| user.account.is_some());
+            let _ = client.send(if already { NumericReply::sasl_already(&target) } else { NumericReply::sasl_fail(&target) });
+            return Ok(());
+        }
+        if context.registration_account(client.id).is_some() {
+            let _ = client.send(NumericReply::sasl_already(&target));
+            return Ok(());
+        }
+        if !client.registration.cap_negotiating {
+            let _ = client.send(NumericReply::sasl_fail(&target));
+            return Ok(());
+        }
+        
+        let param = &message.params[0];
+        if param == "*" {
+            self.end_exchange(client.id, false, context).await;
+            let _ = client.send(NumericReply::sasl_aborted(&target));
+            return Ok(());
+        }
+        if param.len() > AUTHENTICATE_CHUNK_LEN {
+            self.end_exchange(client.id, false, context).await;
+            let _ = client.send(NumericReply::sasl_too_long(&target));
             return Ok(());
         }
         
-        let mechanism = &message.params[0];
+        let mut sessions = self.sessions.write().await;
+        let in_progress = sessions.get_mut(&client.id).filter(|session| {
+            matches!(session.state, SaslState::MechanismSelected | SaslState::Authenticating)
+        });
+        let Some(session) = in_progress else {
+            // No exchange running, so this names the mechanism
+            let failures = sessions.get(&client.id).map_or(0, |session| session.failures);
+            drop(sessions);
+            return self.start_exchange(client, param, failures, context).await;
+        };
+        
+        let mechanism = session.mechanism.clone();
+        session.last_activity = chrono::Utc::now();
+        if param != "+" {
+            session.buffer.push_str(param);
+        }
+        if session.buffer.len() > MAX_SASL_MESSAGE_LEN {
+            drop(sessions);
+            self.end_exchange(client.id, false, context).await;
+            let _ = client.send(NumericReply::sasl_too_long(&target));
+            return Ok(());
+        }
+        if param.len() == AUTHENTICATE_CHUNK_LEN {
+            // More of this message follows
+            return Ok(());
+        }
+        let data = std::mem::take(&mut session.buffer);
+        session.state = SaslState::Authenticating;
+        drop(sessions);
         
-        // Check if mechanism is supported
-        if !self.is_mechanism_supported(mechanism) {
-            let error_msg = NumericReply::ErrUnknownCommand.reply("", vec![format!("SASL authentication failed: mechanism '{}' not supported", mechanism)]);
-            let _ = client.send(error_msg);
+        let Some(mechanism) = self.get_mechanism(&mechanism) else {
+            return self.fail(client, "Mechanism went away", context).await;
+        };
+        let response = mechanism.step(client, &data).await;
+        self.apply_response(client, response, context).await
+    }
+    
+    /// Begin an exchange with the mechanism the client asked for
+    async fn start_exchange(&self, client: &Client, mechanism: &str, failures: u32, context: &ModuleContext) -> Result<()> {
+        let target = reply_target(client);
+        if failures >= self.config.max_failures {
+            let _ = client.send(NumericReply::sasl_fail(&target));
+            return Ok(());
+        }
+        if context.auth_lockout(client).await.is_some() {
+            let _ = client.send(NumericReply::sasl_fail(&target));
+            context.record_auth_failure(client, AuthFailureKind::Sasl).await;
             return Ok(());
         }
+        let Some(mechanism_impl) = self.get_mechanism(mechanism) else {
+            let _ = client.send(NumericReply::sasl_mechs(&target, &self.get_supported_mechanisms().join(",")));
+            let _ = client.send(NumericReply::sasl_fail(&target));
+            return Ok(());
+        };
         
-        // Create or update session
-        let session = SaslSession {
+        let now = chrono::Utc::now();
+        self.sessions.write().await.insert(client.id, SaslSession {
             id: Uuid::new_v4(),
             client_id: client.id,
-            mechanism: mechanism.clone(),
+            mechanism: mechanism_impl.name().to_string(),
             state: SaslState::MechanismSelected,
             auth_data: None,
-            created_at: chrono::Utc::now(),
-            last_activity: chrono::Utc::now(),
-        };
-        
-        let mut sessions = self.sessions.write().await;
-        sessions.insert(client.id, session);
-        drop(sessions); // Release lock before async operations
-        
-        // Process authentication directly using AuthManager
-        self.process_authentication(client, message, context).await?;
+            created_at: now,
+            last_activity: now,
+            buffer: String::new(),
+            failures,
+        });
+        // Don't welcome the client halfway through authenticating
+        context.hold_registration(client.id);
         
-        Ok(())
+        let response = mechanism_impl.start(client, None).await;
+        self.apply_response(client, response, context).await
     }
     
-    /// Process authentication using AuthManager
-    async fn process_authentication(&self, client: &Client, message: &Message, context: &ModuleContext) -> Result<()> {
-        let mechanism = &message.params[0];
-        let data = if message.params.len() > 1 { Some(&message.params[1]) } else { None };
-        
-        // Get the appropriate mechanism handler
-        if let Some(mechanism_impl) = self.get_mechanism(mechanism) {
-            // Start authentication with the mechanism
-            match mechanism_impl.start(client, data.map(|x| x.as_str())).await {
-                Ok(response) => {
-                    match response.response_type {
-                        SaslResponseType::Success => {
-                            // Authentication successful
-                            self.complete_authentication(client, mechanism_impl, context).await?;
-                        }
-                        SaslResponseType::Failure => {
-                            // Authentication failed
-                            let reason = response.error.unwrap_or_else(|| "Authentication failed".to_string());
-                            self.send_sasl_failure(client, &reason).await?;
-                        }
-                        SaslResponseType::Challenge => {
-                            // Send challenge to client
-                            if let Some(challenge_data) = response.data {
-                                self.send_sasl_challenge(client, &challenge_data).await?;
-                            } else {
-                                self.send_sasl_continue(client).await?;
-                            }
-                        }
-                        SaslResponseType::Continue => {
-                            // Send continue message
-                            self.send_sasl_continue(client).await?;
-                        }
-                    }
-                }
-                Err(e) => {
-                    // Authentication error
-                    self.send_sasl_failure(client, &format!("Authentication error: {}", e)).await?;
-                }
+    /// Answer the client with the outcome of a mechanism step
+    async fn apply_response(&self, client: &Client, response: Result<SaslResponse>, context: &ModuleContext) -> Result<()> {
+        let response = match response {
+            Ok(response) => response,
+            Err(e) => return self.fail(client, &e.to_string(), context).await,
+        };
+        match response.response_type {
+            SaslResponseType::Success => match response.account {
+                Some(account) => self.succeed(client, &account, context).await,
+                None => self.fail(client, "No account was authenticated", context).await,
+            },
+            SaslResponseType::Failure => {
+                let reason = response.error.unwrap_or_else(|| "Authentication failed".to_string());
+                self.fail(client, &reason, context).await
+            }
+            SaslResponseType::Challenge | SaslResponseType::Continue => {
+                let data = response.data.unwrap_or_else(|| "+".to_string());
+                let _ = client.send(Message::new(MessageType::Authenticate, vec![data]));
+                Ok(())
             }
-        } else {
-            // Unsupported mechanism
-            self.send_sasl_failure(client, &format!("Mechanism '{}' not supported", mechanism)).await?;
         }
-        
-        Ok(())
-    }
-    
-    /// Send SASL success response to client
-    async fn send_sasl_success(&self, client: &Client, _account: &str) -> Result<()> {
-        // SASL success - using a generic success message
-        // In a full implementation, this would use proper SASL numeric codes (900, 903, etc.)
-        let success_msg = Message::new(
-            MessageType::Custom("NOTICE".to_string()),
-            vec!["SASL authentication successful".to_string()],
-        );
-        let _ = client.send(success_msg);
-        Ok(())
     }
     
-    /// Send SASL failure response to client
-    async fn send_sasl_failure(&self, client: &Client, reason: &str) -> Result<()> {
-        let failure_msg = NumericReply::ErrUnknownCommand.reply("", vec![format!("SASL authentication failed: {}", reason)]);
-        let _ = client.send(failure_msg);
-        Ok(())
-    }
-    
-    /// Send SASL challenge to client
-    async fn send_sasl_challenge(&self, client: &Client, challenge: &str) -> Result<()> {
-        let challenge_msg = NumericReply::RplAway.reply("", vec![challenge.to_string()]);
-        let _ = client.send(challenge_msg);
-        Ok(())
-    }
-    
-    /// Send SASL continue message to client
-    async fn send_sasl_continue(&self, client: &Client) -> Result<()> {
-        let continue_msg = NumericReply::RplAway.reply("", vec!["+".to_string()]);
-        let _ = client.send(continue_msg);
-        Ok(())
-    }
-    
-    
-    /// Complete SASL authentication
-    async fn complete_authentication(&self, client: &Client, mechanism: &dyn SaslMechanism, context: &ModuleContext) -> Result<()> {
-        // For mechanisms like PLAIN, authentication is already complete in start()
-        // We need to get the auth data from the mechanism's start() result
-        // This is a simplified approach - in a real implementation, we'd store the auth data
-        
-        // For now, we'll just send success and clean up the session
-        let mut sessions = self.sessions.write().await;
-        if let Some(session) = sessions.get_mut(&client.id) {
+    /// Log the client in and let it register
+    async fn succeed(&self, client: &Client, account: &str, context: &ModuleContext) -> Result<()> {
+        if let Some(session) = self.sessions.write().await.get_mut(&client.id) {
             session.state = SaslState::Authenticated;
             session.last_activity = chrono::Utc::now();
+            session.auth_data = Some(SaslAuthData {
+                username: account.to_string(),
+                password: String::new(),
+                authzid: None,
+            });
         }
-        drop(sessions);
-        
-        // Send success message
-        self.send_sasl_success(client, "authenticated").await?;
-        
-        tracing::info!("SASL authentication successful for client {}", client.id);
+        context.set_registration_account(client.id, account);
+        context.release_registration(client.id);
         
+        let target = reply_target(client);
+        let mask = format!(
+            "{}!{}@{}",
+            target,
+            client.registration.username.as_deref().unwrap_or("*"),
+            client.registration.hostname.as_deref().unwrap_or("*"),
+        );
+        let _ = client.send(NumericReply::logged_in(&target, &mask, account));
+        let _ = client.send(NumericReply::sasl_success(&target));
+        tracing::info!("SASL authentication of client {} as {} succeeded", client.id, account);
         Ok(())
     }
     
-    /// Set user account and trigger account tracking notification
-    /// This integrates with the IRCv3 account-notify capability
-    /// 
-    /// # Integration with IRCv3 Account Tracking
-    /// 
-    /// To enable full account notification support:
-    /// 1. The server should have the IRCv3 module loaded
-    /// 2. When this method is called, the server should:
-    ///    a. Call `ircv3_module.set_user_account(user_id, account_name, context).await`
-    ///    b. This will update the account tracking and broadcast ACCOUNT messages
-    /// 
-    /// Example server-side integration:
-    /// ```rust,ignore
-    /// // In the server's module coordination code:
-    /// if let Some(ircv3) = module_manager.get_module_mut("ircv3") {
-    ///     ircv3.set_user_account(user_id, account_name, context).await?;
-    /// }
-    /// ```
-    async fn set_user_account(&self, user_id: Uuid, account_name: &str, context: &ModuleContext) -> Result<()> {
-        // Store account name as user metadata or in a dedicated account store
-        // The actual implementation depends on how the server manages user accounts
-        
-        tracing::info!("Account {} authenticated for user {} via SASL", account_name, user_id);
-        tracing::debug!("Note: IRCv3 account notification should be triggered by server-level module coordination");
-        
-        // NOTE: Server-level coordination with IRCv3 module for account change broadcasting
-        // This is a hook point where the server can integrate SASL with account tracking
-        // The User struct already has account_name field, so integration is straightforward when needed
-        // Current SASL implementation is fully functional for authentication purposes
-        
+    /// Count a failed attempt and let the client register without an account
+    async fn fail(&self, client: &Client, reason: &str, context: &ModuleContext) -> Result<()> {
+        self.end_exchange(client.id, true, context).await;
+        let _ = client.send(NumericReply::sasl_fail(&reply_target(client)));
+        context.record_auth_failure(client, AuthFailureKind::Sasl).await;
+        tracing::info!("SASL authentication of client {} failed: {}", client.id, reason);
         Ok(())
     }
     
+    /// Stop any running exchange, keeping the count of failed attempts
+    async fn end_exchange(&self, client_id: Uuid, failed: bool, context: &ModuleContext) {
+        if let Some(session) = self.sessions.write().await.get_mut(&client_id) {
+            if matches!(session.state, SaslState::MechanismSelected | SaslState::Authenticating) {
+                session.state = SaslState::Failed;
+                session.buffer.clear();
+                if failed {
+                    session.failures += 1;
+                }
+            }
+        }
+        context.release_registration(client_id);
+    }
+    
     /// Get the authenticated account name for a user
     /// Returns the account name if the user has successfully authenticated via SASL
     pub async fn get_authenticated_account(&self, user_id: Uuid) -> Option<String> {
@@ -618,11 +707,6 @@ impl SaslModule {
             .map(|auth| auth.username.clone())
     }
     
-    /// Check if a mechanism is supported
-    fn is_mechanism_supported(&self, mechanism: &str) -> bool {
-        self.mechanisms.iter().any(|m| m.name() == mechanism)
-    }
-    
     /// Get mechanism implementation
     fn get_mechanism(&self, mechanism: &str) -> Option<&dyn SaslMechanism> {
         self.mechanisms.iter().find(|m| m.name() == mechanism).map(|m| m.as_ref())
@@ -668,6 +752,14 @@ impl SaslModule {
     }
 }
 
+/// Who numerics to a client are addressed to: its nickname, or `*` before NICK
+fn reply_target(client: &Client) -> String {
+    client.nickname()
+        .or(client.registration.nick.as_deref())
+        .unwrap_or("*")
+        .to_string()
+}
+
 impl Default for SaslModule {
     fn default() -> Self {
         Self::new(SaslConfig::default(), std::sync::Arc::new(AuthManager::default()))
@@ -700,7 +792,7 @@ impl rustircd_core::Module for SaslModule {
     
     async fn handle_message(&mut self, client: &rustircd_core::Client, message: &rustircd_core::Message, context: &ModuleContext) -> Result<ModuleResult> {
         match message.command {
-            rustircd_core::MessageType::Custom(ref cmd) if cmd == "AUTHENTICATE" => {
+            rustircd_core::MessageType::Authenticate => {
                 self.handle_authenticate(client, message, context).await?;
                 Ok(ModuleResult::Handled)
             }
@@ -716,10 +808,16 @@ impl rustircd_core::Module for SaslModule {
         Ok(())
     }
     
-    async fn handle_user_disconnection(&mut self, _user: &rustircd_core::User, _context: &ModuleContext) -> Result<()> {
+    async fn handle_user_disconnection(&mut self, user: &rustircd_core::User, _context: &ModuleContext) -> Result<()> {
+        self.sessions.write().await.remove(&user.id);
         Ok(())
     }
     
+    async fn attach_auth_manager(&mut self, auth_manager: std::sync::Arc<AuthManager>) {
+        self.mechanisms = Self::build_mechanisms(&self.config, &auth_manager);
+        self.auth_manager = auth_manager;
+    }
+    
     fn get_capabilities(&self) -> Vec<String> {
         vec!["message_handler".to_string()]
     }
@@ -0,0 +1,493 @@
+//! SHUN Module
+//!
+//! Provides network-wide shuns: matching users stay connected but every
+//! command other than PING, PONG and QUIT is silently dropped.
+//! Based on the SHUN command found in Unreal and Bahamut.
+
+use rustircd_core::{
+    async_trait, Client, Message, MessageType, Module,
+    ModuleNumericManager, module::{ModuleResult, ModuleStatsResponse, ModuleContext},
+    NumericReply, Result, User, BanManager, BanEntry, BanType, BanTarget, BanQuery, SnoMask
+};
+use tracing::{debug, info, warn};
+use std::sync::Arc;
+use crate::help::{HelpProvider, HelpTopic};
+
+/// SHUN module for silencing users without disconnecting them
+pub struct ShunModule {
+    /// Active shuns
+    shuns: Arc<BanManager>,
+    /// Configuration
+    config: ShunConfig,
+}
+
+/// Shun entry, stored in the shared ban manager as a `SHUN` ban
+pub type Shun = BanEntry;
+
+/// Configuration for SHUN management
+#[derive(Debug, Clone)]
+pub struct ShunConfig {
+    pub max_duration: u64, // in seconds
+    pub allow_permanent_shuns: bool,
+    /// Operators matching a shun mask keep full access
+    pub exempt_operators: bool,
+}
+
+impl Default for ShunConfig {
+    fn default() -> Self {
+        Self {
+            max_duration: 86400 * 7, // 7 days
+            allow_permanent_shuns: true,
+            exempt_operators: true,
+        }
+    }
+}
+
+impl ShunModule {
+    /// Create a new SHUN module
+    pub fn new() -> Self {
+        Self {
+            shuns: BanManager::with_type(Self::ban_type()),
+            config: ShunConfig::default(),
+        }
+    }
+
+    /// Create a new SHUN module with custom configuration
+    pub fn with_config(config: ShunConfig) -> Self {
+        Self {
+            shuns: BanManager::with_type(Self::ban_type()),
+            config,
+        }
+    }
+
+    /// Ban type registered with the ban manager
+    ///
+    /// Shuns are enforced by this module, so the server must not refuse
+    /// connections that match one.
+    fn ban_type() -> BanType {
+        BanType::new("SHUN", BanTarget::UserHost, "Shunned").global().without_disconnect()
+    }
+
+    /// Check whether a user is currently shunned
+    pub fn is_shunned(&self, user: &User) -> bool {
+        if self.config.exempt_operators && user.is_operator() {
+            return false;
+        }
+        self.shuns.find_ban_of_type("SHUN", &BanQuery::from_user(user)).is_some()
+    }
+
+    /// Commands a shunned user may still send
+    fn allowed_while_shunned(command: &MessageType) -> bool {
+        matches!(command, MessageType::Ping | MessageType::Pong | MessageType::Quit)
+    }
+
+    /// Handle SHUN command
+    async fn handle_shun(&self, client: &Client, user: &User, args: &[String], context: &ModuleContext) -> Result<()> {
+        if !user.is_operator() {
+            client.send_numeric(NumericReply::ErrNoPrivileges, &["Permission denied"])?;
+            return Ok(());
+        }
+
+        if args.is_empty() {
+            self.list_shuns(client)?;
+            return Ok(());
+        }
+
+        if args.len() < 2 {
+            client.send_numeric(NumericReply::ErrNeedMoreParams, &["SHUN", "Not enough parameters"])?;
+            return Ok(());
+        }
+
+        let mask = &args[0];
+        let duration = match BanManager::parse_duration(&args[1]) {
+            Ok(duration) => duration,
+            Err(_) => {
+                client.send_numeric(NumericReply::ErrInvalidDuration, &[&args[1], "Invalid duration"])?;
+                return Ok(());
+            }
+        };
+        let reason = if args.len() > 2 {
+            args[2..].join(" ")
+        } else {
+            "No reason given".to_string()
+        };
+
+        self.add_shun(client, user, mask, &reason, duration, context).await
+    }
+
+    /// Handle UNSHUN command
+    async fn handle_unshun(&self, client: &Client, user: &User, args: &[String], context: &ModuleContext) -> Result<()> {
+        if !user.is_operator() {
+            client.send_numeric(NumericReply::ErrNoPrivileges, &["Permission denied"])?;
+            return Ok(());
+        }
+
+        if args.is_empty() {
+            client.send_numeric(NumericReply::ErrNeedMoreParams, &["UNSHUN", "Not enough parameters"])?;
+            return Ok(());
+        }
+
+        let mask = &args[0];
+        if self.shuns.remove_ban("SHUN", mask).is_some() {
+            self.send_notice(client, &format!("Removed shun for [{}]", mask))?;
+            info!("SHUN removed: {} by {}", mask, user.nickname());
+
+            let notice = format!("{} has removed the shun for [{}]", user.nickname(), mask);
+            context.send_snotice(SnoMask::Bans, &notice).await?;
+
+            let message = Message::new(
+                MessageType::Custom("UNSHUN".to_string()),
+                vec![mask.to_string(), user.nickname().to_string(), chrono::Utc::now().timestamp().to_string()]
+            );
+            context.broadcast_to_servers(message).await?;
+        } else {
+            self.send_notice(client, &format!("No shun found for [{}]", mask))?;
+        }
+
+        Ok(())
+    }
+
+    /// Add a shun and propagate it to the network
+    async fn add_shun(&self, client: &Client, user: &User, mask: &str, reason: &str, duration: Option<u64>, context: &ModuleContext) -> Result<()> {
+        match duration {
+            Some(dur) if dur > self.config.max_duration => {
+                client.send_numeric(NumericReply::ErrInvalidDuration, &[&format!("Maximum duration is {} seconds", self.config.max_duration)])?;
+                return Ok(());
+            }
+            None if !self.config.allow_permanent_shuns => {
+                client.send_numeric(NumericReply::ErrInvalidDuration, &["Permanent shuns are not allowed"])?;
+                return Ok(());
+            }
+            _ => {}
+        }
+
+        let shun = BanEntry::new("SHUN", mask, reason, user.nickname(), duration);
+        self.shuns.add_ban(shun.clone())?;
+
+        self.send_notice(client, &format!("Added shun for [{}] [{}]", mask, reason))?;
+        info!("SHUN added: {} by {} - {}", mask, user.nickname(), reason);
+
+        let duration_str = if let Some(dur) = duration {
+            format!("temporary {} min. ", dur / 60)
+        } else {
+            String::new()
+        };
+        let notice = format!("{} is adding a {}shun for [{}] [{}]", user.nickname(), duration_str, mask, reason);
+        context.send_snotice(SnoMask::Bans, &notice).await?;
+
+        let message = Message::new(MessageType::Custom("SHUN".to_string()), shun.to_params());
+        context.broadcast_to_servers(message).await?;
+
+        Ok(())
+    }
+
+    /// List active shuns to an operator
+    fn list_shuns(&self, client: &Client) -> Result<()> {
+        let shuns = self.shuns.list_bans("SHUN");
+
+        if shuns.is_empty() {
+            self.send_notice(client, "No shuns set")?;
+            return Ok(());
+        }
+
+        for shun in &shuns {
+            self.send_notice(client, &Self::describe(shun))?;
+        }
+        self.send_notice(client, "End of shun list")?;
+        Ok(())
+    }
+
+    /// One-line description of a shun, as shown by SHUN and STATS S
+    fn describe(shun: &Shun) -> String {
+        let expires = shun.expire_time.map(|t| t.to_string()).unwrap_or_else(|| "0".to_string());
+        format!("{} {} {} {} :{}", shun.mask, shun.set_by, shun.set_time, expires, shun.reason)
+    }
+
+    /// Send a server notice to a client
+    fn send_notice(&self, client: &Client, text: &str) -> Result<()> {
+        let target = client.nickname().unwrap_or("*").to_string();
+        client.send(Message::new(MessageType::Notice, vec![target, text.to_string()]))
+    }
+
+    /// Handle SHUN message from another server
+    async fn handle_server_shun(&self, server: &str, params: &[String], context: &ModuleContext) -> Result<()> {
+        let shun = match BanEntry::from_params("SHUN", params) {
+            Some(shun) => shun,
+            None => {
+                warn!("Invalid SHUN message from server {}: malformed parameters", server);
+                return Ok(());
+            }
+        };
+
+        // The most recently set shun wins; an equal or newer copy means we already have it
+        if let Some(existing) = self.shuns.get_ban("SHUN", &shun.mask) {
+            if existing.set_time >= shun.set_time {
+                debug!("Ignoring SHUN for {} from server {}: have one set at {}", shun.mask, server, existing.set_time);
+                return Ok(());
+            }
+        }
+
+        if let Err(e) = self.shuns.add_ban(shun.clone()) {
+            warn!("Invalid SHUN from server {}: {}", server, e);
+            return Ok(());
+        }
+
+        info!("SHUN received from server {}: {} - {}", server, shun.mask, shun.reason);
+
+        let message = Message::new(MessageType::Custom("SHUN".to_string()), shun.to_params());
+        context.server_connections.broadcast_message(&message, Some(server)).await?;
+
+        Ok(())
+    }
+
+    /// Handle UNSHUN message from another server
+    async fn handle_server_unshun(&self, server: &str, params: &[String], context: &ModuleContext) -> Result<()> {
+        let mask = match params.first() {
+            Some(mask) => mask,
+            None => {
+                warn!("Invalid UNSHUN message from server {}: no parameters", server);
+                return Ok(());
+            }
+        };
+        let removed_by = params.get(1).map(|s| s.as_str()).unwrap_or("unknown");
+        let removed_at = params.get(2).and_then(|t| t.parse::<u64>().ok());
+
+        // A shun set after the removal was issued survives it
+        if let (Some(existing), Some(removed_at)) = (self.shuns.get_ban("SHUN", mask), removed_at) {
+            if existing.set_time > removed_at {
+                debug!("Ignoring UNSHUN for {} from server {}: shun was reset at {}", mask, server, existing.set_time);
+                return Ok(());
+            }
+        }
+
+        if self.shuns.remove_ban("SHUN", mask).is_some() {
+            info!("UNSHUN received from server {}: {} removed by {}", server, mask, removed_by);
+
+            let message = Message::new(MessageType::Custom("UNSHUN".to_string()), params.to_vec());
+            context.server_connections.broadcast_message(&message, Some(server)).await?;
+        } else {
+            debug!("UNSHUN received from server {} for non-existent shun: {}", server, mask);
+        }
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Module for ShunModule {
+    fn name(&self) -> &str {
+        "shun"
+    }
+
+    fn description(&self) -> &str {
+        "Silences matching users network-wide without disconnecting them"
+    }
+
+    fn version(&self) -> &str {
+        "1.0.0"
+    }
+
+    async fn init(&mut self) -> Result<()> {
+        info!("{} module initialized", self.name());
+        Ok(())
+    }
+
+    async fn handle_message(&mut self, client: &Client, message: &Message, context: &ModuleContext) -> Result<ModuleResult> {
+        let user = match &client.user {
+            Some(u) => u,
+            None => return Ok(ModuleResult::NotHandled),
+        };
+
+        if self.is_shunned(user) && !Self::allowed_while_shunned(&message.command) {
+            debug!("Dropping {} from shunned user {}", message.command, user.nickname());
+            return Ok(ModuleResult::HandledStop);
+        }
+
+        match message.command {
+            MessageType::Custom(ref cmd) if cmd == "SHUN" => {
+                self.handle_shun(client, user, &message.params, context).await?;
+                Ok(ModuleResult::Handled)
+            }
+            MessageType::Custom(ref cmd) if cmd == "UNSHUN" => {
+                self.handle_unshun(client, user, &message.params, context).await?;
+                Ok(ModuleResult::Handled)
+            }
+            _ => Ok(ModuleResult::NotHandled),
+        }
+    }
+
+    async fn handle_server_message(&mut self, server: &str, message: &Message, context: &ModuleContext) -> Result<ModuleResult> {
+        match message.command {
+            MessageType::Custom(ref cmd) if cmd == "SHUN" => {
+                self.handle_server_shun(server, &message.params, context).await?;
+                Ok(ModuleResult::Handled)
+            }
+            MessageType::Custom(ref cmd) if cmd == "UNSHUN" => {
+                self.handle_server_unshun(server, &message.params, context).await?;
+                Ok(ModuleResult::Handled)
+            }
+            _ => Ok(ModuleResult::NotHandled),
+        }
+    }
+
+    async fn handle_user_registration(&mut self, _user: &User, _context: &ModuleContext) -> Result<()> {
+        Ok(())
+    }
+
+    async fn handle_user_disconnection(&mut self, _user: &User, _context: &ModuleContext) -> Result<()> {
+        Ok(())
+    }
+
+    fn get_capabilities(&self) -> Vec<String> {
+        vec!["message_handler".to_string(), "message_filter".to_string(), "server_message_handler".to_string()]
+    }
+
+    fn supports_capability(&self, capability: &str) -> bool {
+        capability == "message_handler" || capability == "message_filter" || capability == "server_message_handler"
+    }
+
+    fn get_numeric_replies(&self) -> Vec<u16> {
+        vec![NumericReply::ErrInvalidDuration.numeric_code()]
+    }
+
+    fn handles_numeric_reply(&self, _numeric: u16) -> bool {
+        false
+    }
+
+    async fn handle_numeric_reply(&mut self, _numeric: u16, _params: Vec<String>) -> Result<()> {
+        Ok(())
+    }
+
+    async fn handle_stats_query(&mut self, query: &str, _client_id: uuid::Uuid, _server: Option<&rustircd_core::Server>) -> Result<Vec<ModuleStatsResponse>> {
+        if query != "S" {
+            return Ok(vec![]);
+        }
+
+        Ok(self.shuns.list_bans("SHUN")
+            .iter()
+            .map(|shun| ModuleStatsResponse::Stats("S".to_string(), Self::describe(shun)))
+            .collect())
+    }
+
+    fn get_stats_queries(&self) -> Vec<String> {
+        vec!["S".to_string()]
+    }
+
+    fn register_numerics(&self, _manager: &mut ModuleNumericManager) -> Result<()> {
+        Ok(())
+    }
+
+    fn register_ban_types(&mut self, ban_manager: Arc<BanManager>) -> Result<()> {
+        ban_manager.register_type(Self::ban_type());
+        self.shuns = ban_manager;
+        Ok(())
+    }
+
+    async fn cleanup(&mut self) -> Result<()> {
+        info!("SHUN module cleaned up");
+        Ok(())
+    }
+}
+
+impl Default for ShunModule {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl HelpProvider for ShunModule {
+    fn get_help_topics(&self) -> Vec<HelpTopic> {
+        ["SHUN", "UNSHUN"].iter()
+            .filter_map(|command| self.get_command_help(command))
+            .collect()
+    }
+
+    fn get_command_help(&self, command: &str) -> Option<HelpTopic> {
+        match command {
+            "SHUN" => Some(HelpTopic {
+                command: "SHUN".to_string(),
+                syntax: "SHUN [<nick!user@host> <duration> <reason>]".to_string(),
+                description: "Silence matching users network-wide; only PING, PONG and QUIT are processed. Without parameters, lists active shuns".to_string(),
+                oper_only: true,
+                examples: vec![
+                    "SHUN *!*@*.flood.net 1h Flooding".to_string(),
+                    "SHUN spammer!*@* 0 Permanent shun".to_string(),
+                ],
+                module_name: Some("shun".to_string()),
+            }),
+            "UNSHUN" => Some(HelpTopic {
+                command: "UNSHUN".to_string(),
+                syntax: "UNSHUN <nick!user@host>".to_string(),
+                description: "Remove a shun".to_string(),
+                oper_only: true,
+                examples: vec![
+                    "UNSHUN *!*@*.flood.net".to_string(),
+                ],
+                module_name: Some("shun".to_string()),
+            }),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{client_for, context, user_at};
+
+    #[tokio::test]
+    async fn test_shunned_user_only_keeps_ping_pong_quit() {
+        let mut module = ShunModule::new();
+        let context = context();
+        module.shuns.add_ban(BanEntry::new("SHUN", "*!*@flood.example.com", "flooding", "oper", None)).unwrap();
+
+        let (client, _rx) = client_for(user_at("mallory", "flood.example.com"));
+
+        let privmsg = Message::new(MessageType::PrivMsg, vec!["#rust".to_string(), "spam".to_string()]);
+        let result = module.handle_message(&client, &privmsg, &context).await.unwrap();
+        assert!(matches!(result, ModuleResult::HandledStop));
+
+        for command in [MessageType::Ping, MessageType::Pong, MessageType::Quit] {
+            let message = Message::new(command, vec!["x".to_string()]);
+            let result = module.handle_message(&client, &message, &context).await.unwrap();
+            assert!(matches!(result, ModuleResult::NotHandled));
+        }
+
+        // Other users are unaffected
+        let (client, _rx) = client_for(user_at("alice", "good.example.com"));
+        let result = module.handle_message(&client, &privmsg, &context).await.unwrap();
+        assert!(matches!(result, ModuleResult::NotHandled));
+    }
+
+    #[tokio::test]
+    async fn test_remote_shun_newest_wins_and_stats() {
+        let mut module = ShunModule::new();
+        let context = context();
+        let params = |reason: &str, set_time: u64| vec![
+            "*!*@flood.example.com".to_string(),
+            reason.to_string(),
+            "oper".to_string(),
+            "0".to_string(),
+            set_time.to_string(),
+        ];
+
+        module.handle_server_shun("hub.test", &params("newer", 2000), &context).await.unwrap();
+        module.handle_server_shun("leaf.test", &params("older", 1000), &context).await.unwrap();
+
+        let stats = module.handle_stats_query("S", uuid::Uuid::new_v4(), None).await.unwrap();
+        assert_eq!(stats.len(), 1);
+        match &stats[0] {
+            ModuleStatsResponse::Stats(letter, data) => {
+                assert_eq!(letter, "S");
+                assert_eq!(data, "*!*@flood.example.com oper 2000 0 :newer");
+            }
+            other => panic!("unexpected response: {:?}", other),
+        }
+
+        let unshun = |removed_at: u64| vec!["*!*@flood.example.com".to_string(), "oper".to_string(), removed_at.to_string()];
+        module.handle_server_unshun("leaf.test", &unshun(1500), &context).await.unwrap();
+        assert!(module.shuns.get_ban("SHUN", "*!*@flood.example.com").is_some());
+        module.handle_server_unshun("hub.test", &unshun(2500), &context).await.unwrap();
+        assert!(module.handle_stats_query("S", uuid::Uuid::new_v4(), None).await.unwrap().is_empty());
+    }
+}
@@ -0,0 +1,57 @@
+//! Fixtures shared by module tests
+//!
+//! Builds the users, clients and module contexts that handler tests need.
+//! The services crate includes this file too, so it only depends on
+//! `rustircd_core`.
+
+use rustircd_core::client::ClientState;
+use rustircd_core::config::OperatorFlag;
+use rustircd_core::module::ModuleContext;
+use rustircd_core::{Client, Config, Database, Message, ServerConnectionManager, User};
+use std::sync::Arc;
+use tokio::sync::mpsc;
+
+/// Module context backed by an empty database and default configuration
+pub fn context() -> ModuleContext {
+    ModuleContext::new(
+        Arc::new(Database::new(100, 1)),
+        Arc::new(ServerConnectionManager::new(Arc::new(Config::default()))),
+    )
+}
+
+/// User named `nick` connected from `host`
+pub fn user_at(nick: &str, host: &str) -> User {
+    User::new(nick.to_string(), nick.to_string(), nick.to_string(), host.to_string(), "irc.example.com".to_string())
+}
+
+/// User named `nick` connected from example.com
+pub fn user(nick: &str) -> User {
+    user_at(nick, "example.com")
+}
+
+/// User named `nick` holding global operator privileges
+pub fn oper(nick: &str) -> User {
+    let mut user = user(nick);
+    user.set_operator_flags([OperatorFlag::GlobalOper].into_iter().collect());
+    user
+}
+
+/// Local client for `user`, with the receiving end of its message queue
+pub fn client_for(user: User) -> (Client, mpsc::UnboundedReceiver<Message>) {
+    let (tx, rx) = mpsc::unbounded_channel();
+    let mut client = Client::new(uuid::Uuid::new_v4(), "127.0.0.1:50000".to_string(), "127.0.0.1:6667".to_string(), tx);
+    client.set_user(user);
+    (client, rx)
+}
+
+/// Registered client named `nick`, known to the context's database and senders
+pub fn registered_client(nick: &str, context: &ModuleContext) -> (Client, mpsc::UnboundedReceiver<Message>) {
+    let (mut client, rx) = client_for(user(nick));
+    client.set_state(ClientState::Registered);
+    context.client_senders.insert(client.id, client.sender.clone());
+    if let Some(user) = client.user.as_mut() {
+        user.id = client.id;
+        context.database.add_user(user.clone()).unwrap();
+    }
+    (client, rx)
+}
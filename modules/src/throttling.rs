@@ -1,6 +1,10 @@
 //! Connection throttling module for rate limiting client connections
 
-use rustircd_core::{Module, module::{ModuleResult, ModuleStatsResponse, ModuleContext}, Client, Message, User, Error, Result, Server};
+use rustircd_core::{
+    Module, module::{ModuleResult, ModuleStatsResponse, ModuleContext}, AuditEvent, Client, CommandSpec,
+    Message, MessageType, NumericReply, User, Error, Result, Server, SnoMask,
+};
+use rustircd_core::throttling_manager::throttle_key;
 use async_trait::async_trait;
 use std::collections::HashMap;
 use std::net::IpAddr;
@@ -130,7 +134,7 @@ impl ThrottlingModule {
         }
 
         let mut throttle_map = self.throttle_map.write().await;
-        let entry = throttle_map.entry(ip_addr).or_insert_with(ThrottleEntry::new);
+        let entry = throttle_map.entry(throttle_key(ip_addr, self.config.ipv6_prefix)).or_insert_with(ThrottleEntry::new);
         
         let allowed = entry.add_connection(&self.config);
         
@@ -149,7 +153,7 @@ impl ThrottlingModule {
     /// Get throttling status for an IP address
     pub async fn get_throttle_status(&self, ip_addr: IpAddr) -> (bool, u8, u64) {
         let throttle_map = self.throttle_map.read().await;
-        if let Some(entry) = throttle_map.get(&ip_addr) {
+        if let Some(entry) = throttle_map.get(&throttle_key(ip_addr, self.config.ipv6_prefix)) {
             let is_throttled = entry.is_throttled();
             let stage = entry.stage;
             let remaining = entry.remaining_throttle_seconds();
@@ -159,6 +163,39 @@ impl ThrottlingModule {
         }
     }
 
+    /// Handle ELINE: exempt an IP or CIDR range from connection throttling
+    async fn handle_eline(&self, client: &Client, user: &User, args: &[String], context: &ModuleContext) -> Result<()> {
+        let mask = &args[0];
+        let reason = args.get(1).map(String::as_str).unwrap_or("No reason given");
+        if context.throttling_manager.add_exemption(mask, reason, user.nickname()).await.is_err() {
+            client.send_numeric(NumericReply::ErrInvalidValue, &["ELINE", "Mask must be an IP address or CIDR range"])?;
+            return Ok(());
+        }
+
+        client.send_numeric(NumericReply::RplEline, &[mask, reason, &format!("Set by {}", user.nickname())])?;
+        info!("ELINE added: {} by {} - {}", mask, user.nickname(), reason);
+        let notice = format!("{} is adding an E-Line for [{}] [{}]", user.nickname(), mask, reason);
+        context.send_snotice(SnoMask::Bans, &notice).await?;
+        context.audit(AuditEvent::privileged(user, "ELINE", mask.as_str()).with_reason(reason)).await?;
+        Ok(())
+    }
+
+    /// Handle UNELINE: remove an exemption added with ELINE
+    async fn handle_uneline(&self, client: &Client, user: &User, args: &[String], context: &ModuleContext) -> Result<()> {
+        let mask = &args[0];
+        if !context.throttling_manager.remove_exemption(mask).await {
+            client.send_numeric(NumericReply::ErrNoSuchEline, &[mask, "No such ELINE"])?;
+            return Ok(());
+        }
+
+        client.send_numeric(NumericReply::RplEline, &[mask, "Removed", &format!("Removed by {}", user.nickname())])?;
+        info!("ELINE removed: {} by {}", mask, user.nickname());
+        let notice = format!("{} has removed the E-Line for [{}]", user.nickname(), mask);
+        context.send_snotice(SnoMask::Bans, &notice).await?;
+        context.audit(AuditEvent::privileged(user, "UNELINE", mask.as_str())).await?;
+        Ok(())
+    }
+
     /// Start the cleanup task to remove expired entries
     async fn start_cleanup_task(&self) {
         let throttle_map = self.throttle_map.clone();
@@ -269,56 +306,32 @@ impl Module for ThrottlingModule {
         Ok(())
     }
     
-    async fn handle_stats_query(&mut self, query: &str, _client_id: uuid::Uuid, server: Option<&Server>) -> Result<Vec<ModuleStatsResponse>> {
-        let mut responses = Vec::new();
-        
-        if query == "T" {
-            // Handle throttling statistics
-            let throttle_map = self.throttle_map.read().await;
-            
-            if throttle_map.is_empty() {
-                responses.push(ModuleStatsResponse::ModuleStats(
-                    "THROTTLING".to_string(),
-                    "No throttled IPs".to_string()
-                ));
-            } else {
-                // Check if the requesting user is an operator and if server details are allowed
-                let is_operator = false; // TODO: Add method to check if user is operator
-
-                let show_details = is_operator && server.map(|s| s.config().server.show_server_details_in_stats).unwrap_or(false);
-                
-                if show_details {
-                    // Show detailed information to operators (if configured)
-                    for (ip, entry) in throttle_map.iter() {
-                        let status = if entry.is_throttled() {
-                            format!("THROTTLED stage={} remaining={}s", entry.stage, entry.remaining_throttle_seconds())
-                        } else {
-                            format!("ACTIVE connections={}", entry.connection_times.len())
-                        };
-                        
-                        responses.push(ModuleStatsResponse::ModuleStats(
-                            ip.to_string(),
-                            status
-                        ));
-                    }
-                } else {
-                    // Show limited information to non-operators or when configured to hide details
-                    let total_throttled = throttle_map.values().filter(|entry| entry.is_throttled()).count();
-                    let total_active = throttle_map.len() - total_throttled;
-                    
-                    responses.push(ModuleStatsResponse::ModuleStats(
-                        "THROTTLING".to_string(),
-                        format!("{} throttled IPs, {} active IPs", total_throttled, total_active)
-                    ));
-                }
-            }
-        }
-        
-        Ok(responses)
+    async fn handle_stats_query(&mut self, _query: &str, _client_id: uuid::Uuid, _server: Option<&Server>) -> Result<Vec<ModuleStatsResponse>> {
+        // STATS T is answered by the core from the server's throttling manager
+        Ok(Vec::new())
     }
     
     fn get_stats_queries(&self) -> Vec<String> {
-        vec!["T".to_string()]
+        Vec::new()
+    }
+
+    fn commands(&self) -> Vec<CommandSpec> {
+        vec![
+            CommandSpec::new("ELINE", 1).oper_only(),
+            CommandSpec::new("UNELINE", 1).oper_only(),
+        ]
+    }
+
+    async fn handle_command(&mut self, client: &Client, message: &Message, context: &ModuleContext) -> Result<()> {
+        let Some(user) = client.get_user() else {
+            return Ok(());
+        };
+        match message.command {
+            MessageType::Custom(ref cmd) if cmd.eq_ignore_ascii_case("ELINE") => {
+                self.handle_eline(client, user, &message.params, context).await
+            }
+            _ => self.handle_uneline(client, user, &message.params, context).await,
+        }
     }
 }
 
@@ -343,11 +356,14 @@ mod tests {
         rustircd_core::config::ThrottlingConfig {
             enabled: true,
             max_connections_per_ip: 3,
+            ipv6_prefix: 64,
             time_window_seconds: 60,
             initial_throttle_seconds: 5,
             max_stages: 3,
             stage_factor: 2,
             cleanup_interval_seconds: 300,
+            auth_failures: Default::default(),
+            exempt: Vec::new(),
         }
     }
 
@@ -6,33 +6,22 @@
 use rustircd_core::{
     async_trait, Client, Error, Message, MessageType, Module,
     ModuleNumericManager, module::{ModuleResult, ModuleStatsResponse, ModuleContext},
-    NumericReply, Result, User
+    NumericReply, Result, User, BanManager, BanEntry, BanType, BanTarget, BanQuery
 };
 use tracing::{debug, info, warn};
-use std::collections::HashMap;
-use tokio::sync::RwLock;
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::sync::Arc;
 use crate::help::{HelpProvider, HelpTopic};
 
 /// XLINE module for extended line management
 pub struct XlineModule {
     /// Extended lines (XLINE)
-    xlines: RwLock<HashMap<String, ExtendedLine>>,
+    xlines: Arc<BanManager>,
     /// Configuration
     config: XlineConfig,
 }
 
-/// Extended line entry
-#[derive(Debug, Clone)]
-pub struct ExtendedLine {
-    pub mask: String,
-    pub reason: String,
-    pub set_by: String,
-    pub set_time: u64,
-    pub expire_time: Option<u64>,
-    pub is_active: bool,
-    pub line_type: String,
-}
+/// Extended line entry, stored in the shared ban manager as a `XLINE` ban
+pub type ExtendedLine = BanEntry;
 
 /// Configuration for XLINE management
 #[derive(Debug, Clone)]
@@ -58,7 +47,7 @@ impl XlineModule {
     /// Create a new XLINE module
     pub fn new() -> Self {
         Self {
-            xlines: RwLock::new(HashMap::new()),
+            xlines: BanManager::with_type(Self::ban_type()),
             config: XlineConfig::default(),
         }
     }
@@ -66,11 +55,16 @@ impl XlineModule {
     /// Create a new XLINE module with custom configuration
     pub fn with_config(config: XlineConfig) -> Self {
         Self {
-            xlines: RwLock::new(HashMap::new()),
+            xlines: BanManager::with_type(Self::ban_type()),
             config,
         }
     }
     
+    /// Ban type registered with the ban manager
+    fn ban_type() -> BanType {
+        BanType::new("XLINE", BanTarget::UserHost, "X-Lined")
+    }
+    
     /// Handle XLINE command
     async fn handle_xline(&self, client: &Client, user: &User, args: &[String], context: &ModuleContext) -> Result<()> {
         if !user.is_operator() {
@@ -91,7 +85,7 @@ impl XlineModule {
         };
         
         let duration = if args.len() > 2 {
-            self.parse_duration(&args[2])?
+            BanManager::parse_duration(&args[2])?
         } else {
             None
         };
@@ -119,9 +113,6 @@ impl XlineModule {
     
     /// Add an XLINE
     async fn add_xline(&self, client: &Client, user: &User, mask: &str, reason: &str, duration: Option<u64>, context: &ModuleContext) -> Result<()> {
-        let current_time = self.get_current_time();
-        let expire_time = duration.map(|d| current_time + d);
-        
         if let Some(dur) = duration {
             if dur > self.config.max_duration {
                 client.send_numeric(NumericReply::ErrInvalidDuration, &[&format!("Maximum duration is {} seconds", self.config.max_duration)])?;
@@ -129,18 +120,9 @@ impl XlineModule {
             }
         }
         
-        let xline = ExtendedLine {
-            mask: mask.to_string(),
-            reason: reason.to_string(),
-            set_by: user.nickname().to_string(),
-            set_time: current_time,
-            expire_time,
-            is_active: true,
-            line_type: "XLINE".to_string(),
-        };
+        let xline = BanEntry::new("XLINE", mask, reason, &user.nickname(), duration);
         
-        let mut xlines = self.xlines.write().await;
-        xlines.insert(mask.to_string(), xline);
+        self.xlines.add_ban(xline)?;
         
         client.send_numeric(NumericReply::RplXline, &[mask, reason, &format!("Set by {}", user.nickname())])?;
         
@@ -157,14 +139,11 @@ impl XlineModule {
     
     /// Remove an XLINE
     async fn remove_xline(&self, client: &Client, user: &User, mask: &str, context: &ModuleContext) -> Result<()> {
-        let mut xlines = self.xlines.write().await;
-        
-        if xlines.remove(mask).is_some() {
+        if self.xlines.remove_ban("XLINE", mask).is_some() {
             client.send_numeric(NumericReply::RplXline, &[mask, "Removed", &format!("Removed by {}", user.nickname())])?;
             info!("XLINE removed: {} by {}", mask, user.nickname());
             
             // Broadcast removal to other servers
-            drop(xlines); // Release the lock before async call
             self.broadcast_unxline_to_servers(mask, &user.nickname(), context).await?;
         } else {
             client.send_numeric(NumericReply::ErrNoSuchXline, &[mask, "No such XLINE"])?;
@@ -175,14 +154,14 @@ impl XlineModule {
     
     /// List XLINEs
     async fn list_xlines(&self, client: &Client, _user: &User) -> Result<()> {
-        let xlines = self.xlines.read().await;
+        let xlines = self.xlines.list_bans("XLINE");
         
         if xlines.is_empty() {
             client.send_numeric(NumericReply::RplXline, &["*", "No XLINEs set"])?;
             return Ok(());
         }
         
-        for xline in xlines.values() {
+        for xline in &xlines {
             let expire_info = if let Some(expire) = xline.expire_time {
                 format!("Expires: {}", self.format_time(expire))
             } else {
@@ -200,47 +179,6 @@ impl XlineModule {
         Ok(())
     }
     
-    /// Parse duration string (e.g., "1d", "2h", "30m", "3600s")
-    fn parse_duration(&self, duration_str: &str) -> Result<Option<u64>> {
-        if duration_str == "0" || duration_str.is_empty() {
-            return Ok(None);
-        }
-        
-        let duration_str = duration_str.to_lowercase();
-        let (number_str, unit) = if duration_str.ends_with('d') {
-            (&duration_str[..duration_str.len()-1], "d")
-        } else if duration_str.ends_with('h') {
-            (&duration_str[..duration_str.len()-1], "h")
-        } else if duration_str.ends_with('m') {
-            (&duration_str[..duration_str.len()-1], "m")
-        } else if duration_str.ends_with('s') {
-            (&duration_str[..duration_str.len()-1], "s")
-        } else {
-            (duration_str.as_str(), "s")
-        };
-        
-        let number: u64 = number_str.parse()
-            .map_err(|_| "Invalid duration number")?;
-        
-        let seconds = match unit {
-            "d" => number * 86400,
-            "h" => number * 3600,
-            "m" => number * 60,
-            "s" => number,
-            _ => return Err(Error::Config("Invalid duration unit".to_string())),
-        };
-        
-        Ok(Some(seconds))
-    }
-    
-    /// Get current time as Unix timestamp
-    fn get_current_time(&self) -> u64 {
-        SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap_or_default()
-            .as_secs()
-    }
-    
     /// Format time as readable string
     fn format_time(&self, timestamp: u64) -> String {
         use chrono::{DateTime, Utc};
@@ -251,61 +189,15 @@ impl XlineModule {
     
     /// Check if a user matches any active XLINEs
     pub async fn check_user_xline(&self, user: &User) -> Option<String> {
-        let current_time = self.get_current_time();
-        
-        let xlines = self.xlines.read().await;
-        for xline in xlines.values() {
-            if xline.is_active && self.matches_mask(&xline.mask, user) {
-                if xline.expire_time.map_or(true, |expire| current_time < expire) {
-                    return Some(format!("XLINE: {}", xline.reason));
-                }
-            }
-        }
-        
-        None
+        self.xlines.list_bans("XLINE")
+            .into_iter()
+            .find(|xline| self.matches_mask(&xline.mask, user))
+            .map(|xline| format!("XLINE: {}", xline.reason))
     }
     
     /// Check if a user matches a ban mask
     fn matches_mask(&self, mask: &str, user: &User) -> bool {
-        // Simple wildcard matching - in a real implementation, this would be more sophisticated
-        let user_mask = format!("{}!{}@{}", user.nickname(), user.username(), user.hostname());
-        
-        // Convert IRC wildcards to regex patterns
-        let pattern = mask
-            .replace("*", ".*")
-            .replace("?", ".");
-        
-        // Simple pattern matching - in production, use proper regex
-        if mask.contains('*') || mask.contains('?') {
-            // Wildcard matching
-            self.simple_wildcard_match(&pattern, &user_mask)
-        } else {
-            // Exact match
-            mask == user_mask || mask == user.nickname() || mask == user.hostname()
-        }
-    }
-    
-    /// Simple wildcard matching
-    fn simple_wildcard_match(&self, pattern: &str, text: &str) -> bool {
-        // Very basic wildcard matching - in production, use proper regex
-        if pattern == ".*" {
-            return true;
-        }
-        
-        if pattern.starts_with(".*") && pattern.ends_with(".*") {
-            let middle = &pattern[2..pattern.len()-2];
-            return text.contains(middle);
-        }
-        
-        if pattern.starts_with(".*") {
-            return text.ends_with(&pattern[2..]);
-        }
-        
-        if pattern.ends_with(".*") {
-            return text.starts_with(&pattern[..pattern.len()-2]);
-        }
-        
-        text == pattern
+        BanManager::matches(BanTarget::UserHost, mask, &BanQuery::from_user(user))
     }
     
     /// Clean up expired XLINEs
@@ -314,17 +206,7 @@ impl XlineModule {
             return Ok(());
         }
         
-        let current_time = self.get_current_time();
-        let mut expired_count = 0;
-        
-        let mut xlines = self.xlines.write().await;
-        xlines.retain(|_, xline| {
-            let should_keep = xline.expire_time.map_or(true, |expire| current_time < expire);
-            if !should_keep {
-                expired_count += 1;
-            }
-            should_keep
-        });
+        let expired_count = self.xlines.cleanup_expired_type("XLINE").len();
         
         if expired_count > 0 {
             info!("Cleaned up {} expired XLINEs", expired_count);
@@ -335,18 +217,12 @@ impl XlineModule {
     
     /// Get count of active XLINEs
     pub async fn get_active_xlines_count(&self) -> usize {
-        let xlines = self.xlines.read().await;
-        xlines.len()
+        self.xlines.active_count("XLINE")
     }
     
     /// Get count of expired XLINEs
     pub async fn get_expired_xlines_count(&self) -> usize {
-        let current_time = self.get_current_time();
-        let xlines = self.xlines.read().await;
-        
-        xlines.values().filter(|xline| {
-            xline.expire_time.map_or(false, |expire| current_time >= expire)
-        }).count()
+        self.xlines.expired_count("XLINE")
     }
     
     /// Broadcast XLINE to other servers
@@ -434,31 +310,20 @@ impl XlineModule {
         let reason = &params[1];
         let set_by = if params.len() > 2 { &params[2] } else { "unknown" };
         let duration = if params.len() > 3 {
-            self.parse_duration(&params[3]).ok().flatten()
+            BanManager::parse_duration(&params[3]).ok().flatten()
         } else {
             None
         };
         
-        let current_time = self.get_current_time();
-        let expire_time = duration.map(|d| current_time + d);
-        
-        let xline = ExtendedLine {
-            mask: mask.to_string(),
-            reason: reason.to_string(),
-            set_by: set_by.to_string(),
-            set_time: current_time,
-            expire_time,
-            is_active: true,
-            line_type: "XLINE".to_string(),
-        };
-        
-        let mut xlines = self.xlines.write().await;
-        xlines.insert(mask.to_string(), xline);
+        let xline = BanEntry::new("XLINE", mask, reason, set_by, duration);
+        if let Err(e) = self.xlines.add_ban(xline) {
+            warn!("Invalid XLINE from server {}: {}", server, e);
+            return Ok(());
+        }
         
         info!("XLINE received from server {}: {} - {}", server, mask, reason);
         
         // Check existing connections and disconnect matching users
-        drop(xlines); // Release the lock before async call
         self.disconnect_matching_users(mask, &format!("XLINE: {}", reason), context).await?;
         
         Ok(())
@@ -474,8 +339,7 @@ impl XlineModule {
         let mask = &params[0];
         let removed_by = if params.len() > 1 { &params[1] } else { "unknown" };
         
-        let mut xlines = self.xlines.write().await;
-        if xlines.remove(mask).is_some() {
+        if self.xlines.remove_ban("XLINE", mask).is_some() {
             info!("UNXLINE received from server {}: {} removed by {}", server, mask, removed_by);
         } else {
             debug!("UNXLINE received from server {} for non-existent XLINE: {}", server, mask);
@@ -612,6 +476,12 @@ impl Module for XlineModule {
         Ok(())
     }
 
+    fn register_ban_types(&mut self, ban_manager: Arc<BanManager>) -> Result<()> {
+        ban_manager.register_type(Self::ban_type());
+        self.xlines = ban_manager;
+        Ok(())
+    }
+
     async fn cleanup(&mut self) -> Result<()> {
         info!("XLINE module cleaned up");
         Ok(())
@@ -690,26 +560,13 @@ mod tests {
     }
     
     #[test]
-    fn test_parse_duration() {
-        let module = XlineModule::new();
-        
-        assert_eq!(module.parse_duration("1d").unwrap(), Some(86400));
-        assert_eq!(module.parse_duration("2h").unwrap(), Some(7200));
-        assert_eq!(module.parse_duration("30m").unwrap(), Some(1800));
-        assert_eq!(module.parse_duration("3600s").unwrap(), Some(3600));
-        assert_eq!(module.parse_duration("3600").unwrap(), Some(3600));
-        assert_eq!(module.parse_duration("0").unwrap(), None);
-        assert_eq!(module.parse_duration("").unwrap(), None);
-    }
-    
-    #[test]
-    fn test_wildcard_matching() {
+    fn test_mask_matching() {
         let module = XlineModule::new();
+        let user = User::new("bob".to_string(), "bob".to_string(), "Bob".to_string(), "dsl.badhost.com".to_string(), "irc.example.com".to_string());
         
-        // Note: This is a simplified test - real implementation would use proper regex
-        assert!(module.simple_wildcard_match(".*", "anything"));
-        assert!(module.simple_wildcard_match("test.*", "test123"));
-        assert!(module.simple_wildcard_match(".*test", "123test"));
-        assert!(!module.simple_wildcard_match("test", "notest"));
+        assert!(module.matches_mask("*@*.badhost.com", &user));
+        assert!(module.matches_mask("bob!*@*", &user));
+        assert!(!module.matches_mask("alice@*", &user));
+        assert!(!module.matches_mask("*@goodhost.com", &user));
     }
 }
@@ -0,0 +1,1107 @@
+//! Protocol conformance tests
+//!
+//! Each test boots a server with this crate's modules on an ephemeral port
+//! and drives it with scripted client sessions over TCP, checking what a
+//! client sees on the wire. `tests/irctest` has a controller for running the
+//! external irctest suite against the rustircd binary.
+
+use async_trait::async_trait;
+use base64::{engine::general_purpose, Engine as _};
+use rustircd_core::{
+    config, AuthInfo, AuthProvider, AuthProviderCapabilities, AuthRequest, AuthResult, Config, Message, MessageType,
+    Server, ServerBuilder,
+};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader, Lines};
+use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::net::TcpStream;
+
+/// How long a test waits for an expected line
+const REPLY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How long a test waits to be sure nothing more arrives
+const QUIET_PERIOD: Duration = Duration::from_millis(300);
+
+/// A server with the given modules, listening on ports picked by the OS
+struct TestServer {
+    server: Server,
+    addr: SocketAddr,
+    /// Port for server links
+    link_addr: SocketAddr,
+}
+
+impl TestServer {
+    async fn start(modules: &[&str]) -> Self {
+        Self::start_with(Config::default(), modules).await
+    }
+
+    async fn start_with(config: Config, modules: &[&str]) -> Self {
+        Self::start_with_provider(config, modules, None).await
+    }
+
+    async fn start_with_provider(mut config: Config, modules: &[&str], provider: Option<Arc<dyn AuthProvider>>) -> Self {
+        config.security.enable_ident = false;
+        config.security.enable_dns = false;
+        config.security.enable_reverse_dns = false;
+        config.modules.throttling.enabled = false;
+        let mut builder = ServerBuilder::with_config(config)
+            .server_name("conformance.test")
+            .port(0, config::PortConnectionType::Client, false)
+            .port(0, config::PortConnectionType::Server, false)
+            .module_registry(rustircd_modules::module_registry());
+        for module in modules {
+            builder = builder.enable_module(module);
+        }
+        if let Some(provider) = provider {
+            builder = builder.auth_provider(provider);
+        }
+        let mut server = builder.build().await.unwrap();
+        server.start().await.unwrap();
+        let (addr, link_addr) = (server.listen_addrs()[0], server.listen_addrs()[1]);
+        Self { server, addr, link_addr }
+    }
+
+    async fn connect(&self) -> TestClient {
+        let (reader, writer) = TcpStream::connect(self.addr).await.unwrap().into_split();
+        TestClient { lines: BufReader::new(reader).lines(), writer }
+    }
+
+    /// Connect and register with NICK and USER, reading up to the end of the MOTD
+    async fn register(&self, nick: &str) -> TestClient {
+        let mut client = self.connect().await;
+        client.send(&format!("NICK {}", nick)).await;
+        client.send(&format!("USER {} 0 * :Conformance {}", nick, nick)).await;
+        client.expect("001").await;
+        client.expect_any(&["376", "422"]).await;
+        client
+    }
+
+    /// Link in as `name` with PASS and SERVER, reading up to the peer's SERVER
+    async fn link(&self, name: &str, password: &str) -> TestClient {
+        let (reader, writer) = TcpStream::connect(self.link_addr).await.unwrap().into_split();
+        let mut link = TestClient { lines: BufReader::new(reader).lines(), writer };
+        link.send(&format!("PASS {}", password)).await;
+        link.send(&format!("SERVER {} 1 :Linked for testing", name)).await;
+        assert_eq!(link.expect("SERVER").await.params[0], "conformance.test");
+        link
+    }
+
+    async fn stop(self) {
+        self.server.stop().await.unwrap();
+    }
+}
+
+/// Accepts `alice` with password `secret`, and `ircop` with password
+/// `secret` whose login carries operator flags and a class
+struct TestAccounts;
+
+#[async_trait]
+impl AuthProvider for TestAccounts {
+    fn name(&self) -> &str {
+        "test"
+    }
+
+    fn description(&self) -> &str {
+        "Fixed accounts for conformance tests"
+    }
+
+    async fn is_available(&self) -> bool {
+        true
+    }
+
+    async fn authenticate(&self, request: &AuthRequest) -> rustircd_core::Result<AuthResult> {
+        if !["alice", "ircop"].contains(&request.username.as_str()) || request.credential != "secret" {
+            return Ok(AuthResult::Failure("Invalid credentials".to_string()));
+        }
+        let mut metadata = HashMap::new();
+        if request.username == "ircop" {
+            metadata.insert(rustircd_core::AUTH_META_OPER_FLAGS.to_string(), "GlobalOper,Override".to_string());
+            metadata.insert(rustircd_core::AUTH_META_CLASS.to_string(), "opers".to_string());
+        }
+        Ok(AuthResult::Success(AuthInfo {
+            username: request.username.clone(),
+            realname: None,
+            hostname: None,
+            metadata,
+            provider: "test".to_string(),
+            authenticated_at: chrono::Utc::now(),
+        }))
+    }
+
+    async fn validate(&self, _auth_info: &AuthInfo) -> rustircd_core::Result<bool> {
+        Ok(true)
+    }
+
+    fn capabilities(&self) -> AuthProviderCapabilities {
+        AuthProviderCapabilities::default()
+    }
+}
+
+/// AUTHENTICATE payload for SASL PLAIN
+fn plain(account: &str, password: &str) -> String {
+    general_purpose::STANDARD.encode(format!("\0{}\0{}", account, password))
+}
+
+/// One scripted client connection
+struct TestClient {
+    lines: Lines<BufReader<OwnedReadHalf>>,
+    writer: OwnedWriteHalf,
+}
+
+impl TestClient {
+    async fn send(&mut self, line: &str) {
+        self.writer.write_all(format!("{}\r\n", line).as_bytes()).await.unwrap();
+    }
+
+    /// The next line from the server
+    async fn recv(&mut self) -> Message {
+        let line = tokio::time::timeout(REPLY_TIMEOUT, self.lines.next_line())
+            .await
+            .expect("timed out waiting for the server")
+            .unwrap()
+            .expect("server closed the connection");
+        Message::parse(&line).unwrap()
+    }
+
+    /// Skip lines until one with `command` arrives
+    async fn expect(&mut self, command: &str) -> Message {
+        self.expect_any(&[command]).await
+    }
+
+    /// Skip lines until one with any of `commands` arrives
+    async fn expect_any(&mut self, commands: &[&str]) -> Message {
+        loop {
+            let message = self.recv().await;
+            if commands.contains(&message.command.to_string().as_str()) {
+                return message;
+            }
+        }
+    }
+
+    /// Skip lines until a NOTICE containing `text` arrives
+    async fn expect_notice(&mut self, text: &str) -> Message {
+        loop {
+            let message = self.expect("NOTICE").await;
+            if message.params.last().is_some_and(|notice| notice.contains(text)) {
+                return message;
+            }
+        }
+    }
+
+    /// Assert the server sends nothing for a while
+    async fn expect_silence(&mut self) {
+        if let Ok(line) = tokio::time::timeout(QUIET_PERIOD, self.lines.next_line()).await {
+            panic!("expected silence, got {:?}", line);
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_registration_and_basic_commands() {
+    let server = TestServer::start(&[]).await;
+    let mut alice = server.connect().await;
+
+    // Registration needs both NICK and USER, in either order
+    alice.send("USER alice 0 * :Alice").await;
+    alice.expect_silence().await;
+    alice.send("NICK alice").await;
+    let welcome = alice.expect("001").await;
+    assert_eq!(welcome.params[0], "alice");
+    for numeric in ["002", "003", "004", "005"] {
+        assert_eq!(alice.recv().await.command.to_string(), numeric);
+    }
+    alice.expect_any(&["376", "422"]).await;
+
+    // A taken nickname is refused during registration
+    let mut other = server.connect().await;
+    other.send("NICK alice").await;
+    other.send("USER other 0 * :Other").await;
+    assert_eq!(other.expect("433").await.params.last().unwrap(), "Nickname is already in use");
+
+    alice.send("PING :conformance-token").await;
+    let pong = alice.expect("PONG").await;
+    assert_eq!(pong.params.last().unwrap(), "conformance-token");
+
+    alice.send("JOIN").await;
+    assert_eq!(alice.expect("461").await.params[1], "JOIN");
+
+    alice.send("QUIT :done").await;
+    assert_eq!(alice.expect("ERROR").await.command, MessageType::Error);
+
+    server.stop().await;
+}
+
+#[tokio::test]
+async fn test_connection_password() {
+    let mut config = Config::default();
+    config.security.require_client_password = true;
+    config.security.client_password = Some("letmein".to_string());
+    let server = TestServer::start_with(config, &[]).await;
+
+    // A wrong password is refused straight away and can be retried
+    let mut client = server.connect().await;
+    client.send("PASS guess").await;
+    client.expect("464").await;
+    client.send("PASS letmein").await;
+    client.send("NICK passer").await;
+    client.send("USER passer 0 * :Passer").await;
+    assert_eq!(client.expect("001").await.params[0], "passer");
+
+    // Registering without one closes the connection
+    let mut stranger = server.connect().await;
+    stranger.send("NICK stranger").await;
+    stranger.send("USER stranger 0 * :Stranger").await;
+    stranger.expect("464").await;
+    stranger.expect("ERROR").await;
+
+    server.stop().await;
+}
+
+#[tokio::test]
+async fn test_failed_passwords_lock_out_and_dline() {
+    let mut config = Config::default();
+    config.security.require_client_password = true;
+    config.security.client_password = Some("letmein".to_string());
+    config.modules.throttling.auth_failures.max_failures = 2;
+    config.modules.throttling.auth_failures.dline_after_failures = 3;
+    let server = TestServer::start_with(config, &["dline"]).await;
+
+    // Two wrong passwords lock the address out, even with the right one
+    let mut client = server.connect().await;
+    client.send("PASS guess").await;
+    client.expect("464").await;
+    client.send("PASS again").await;
+    client.expect("464").await;
+    client.send("PASS letmein").await;
+    client.expect("464").await;
+
+    // That was the third failure, which D-lines the address
+    assert!(client.expect("ERROR").await.params[0].contains("Too many failed login attempts"));
+    let mut retry = server.connect().await;
+    assert!(retry.expect("ERROR").await.params[0].contains("Too many failed login attempts"));
+
+    server.stop().await;
+}
+
+#[tokio::test]
+async fn test_throttle_exemptions() {
+    let mut config = Config::default();
+    let mut operator = config::OperatorConfig::new("netadmin".to_string(), "", "*@*".to_string(), vec![config::OperatorFlag::GlobalOper]);
+    operator.password_hash = config::PasswordHasher::hash_password("secret");
+    config.network.operators = vec![operator];
+    config.modules.throttling.exempt = vec!["10.0.0.0/8".to_string()];
+    let server = TestServer::start_with(config, &["oper", "throttling"]).await;
+
+    // Only operators can add E-lines or see the throttle state
+    let mut user = server.register("user").await;
+    user.send("ELINE 192.0.2.0/24 :services").await;
+    user.expect("481").await;
+    user.send("STATS T").await;
+    user.expect("481").await;
+
+    let mut admin = server.register("admin").await;
+    admin.send("OPER netadmin secret").await;
+    admin.expect("381").await;
+    admin.send("ELINE 192.0.2.0/24 :services").await;
+    assert_eq!(admin.expect("727").await.params[1..3], ["192.0.2.0/24", "services"]);
+    admin.send("ELINE 192.0.2.0/40").await;
+    admin.expect("530").await;
+
+    admin.send("STATS T").await;
+    assert_eq!(admin.expect("245").await.params[2], "exempt 10.0.0.0/8 by config (Configured exemption)");
+    assert_eq!(admin.expect("245").await.params[2], "exempt 192.0.2.0/24 by admin (services)");
+    admin.expect("219").await;
+
+    // Configured exemptions stay until the config changes
+    admin.send("UNELINE 10.0.0.0/8").await;
+    admin.expect("535").await;
+    admin.send("UNELINE 192.0.2.0/24").await;
+    assert_eq!(admin.expect("727").await.params[2], "Removed");
+
+    server.stop().await;
+}
+
+#[tokio::test]
+async fn test_clone_limits_and_check() {
+    let mut config = Config::default();
+    let mut operator = config::OperatorConfig::new("netadmin".to_string(), "", "*@*".to_string(), vec![config::OperatorFlag::GlobalOper]);
+    operator.password_hash = config::PasswordHasher::hash_password("secret");
+    config.network.operators = vec![operator];
+    config.modules.throttling.exempt = Vec::new();
+    config.connection.clones.soft_limit_per_ip = 2;
+    config.connection.clones.hard_limit_per_ip = 3;
+    let server = TestServer::start_with(config, &["oper"]).await;
+
+    let mut admin = server.register("admin").await;
+    admin.send("OPER netadmin secret").await;
+    admin.expect("381").await;
+    let mut user = server.register("user").await;
+
+    // The third connection passes the soft limit, the fourth the hard one
+    let _third = server.connect().await;
+    admin.expect_notice("Possible clones: 3 connections from 127.0.0.1").await;
+    let mut fourth = server.connect().await;
+    assert!(fourth.expect("ERROR").await.params[0].contains("Too many connections from your host"));
+    admin.expect_notice("Refused clone from 127.0.0.1").await;
+
+    // CHECK lists connections by address or by mask, for operators only
+    user.send("CHECK 127.0.0.1").await;
+    user.expect("481").await;
+    admin.send("CHECK 127.0.0.0/8").await;
+    assert!(admin.expect("NOTICE").await.params[1].starts_with("admin!"));
+    assert!(admin.expect("NOTICE").await.params[1].starts_with("user!"));
+    assert!(admin.expect("NOTICE").await.params[1].starts_with("(unregistered) [127.0.0.1]"));
+    assert_eq!(admin.expect("NOTICE").await.params[1], "End of CHECK 127.0.0.0/8: 3 connection(s)");
+    admin.send("CHECK user!*@*").await;
+    assert!(admin.expect("NOTICE").await.params[1].starts_with("user!"));
+    assert_eq!(admin.expect("NOTICE").await.params[1], "End of CHECK user!*@*: 1 connection(s)");
+
+    server.stop().await;
+}
+
+#[tokio::test]
+async fn test_testline_testmask_testgecos() {
+    let mut config = Config::default();
+    let mut operator = config::OperatorConfig::new("netadmin".to_string(), "", "*@*".to_string(), vec![config::OperatorFlag::GlobalOper]);
+    operator.password_hash = config::PasswordHasher::hash_password("secret");
+    config.network.operators = vec![operator];
+    let server = TestServer::start_with(config, &["oper", "kline", "xline", "testing"]).await;
+
+    let mut user = server.register("user").await;
+    user.send("TESTLINE 127.0.0.1").await;
+    user.expect("481").await;
+
+    let mut admin = server.register("admin").await;
+    admin.send("OPER netadmin secret").await;
+    admin.expect("381").await;
+    admin.send("KLINE *@192.0.2.* :spam").await;
+    admin.expect("716").await;
+    admin.send("XLINE bob@192.0.2.* :bots").await;
+    admin.expect("720").await;
+
+    // TESTLINE reports matching bans, then any exemption and the class
+    admin.send("TESTLINE bob@192.0.2.7").await;
+    assert_eq!(admin.expect("709").await.params[1..], ["KLINE", "*@192.0.2.*", "spam (set by admin)"]);
+    assert_eq!(admin.expect("709").await.params[1..3], ["XLINE", "bob@192.0.2.*"]);
+    assert_eq!(admin.expect("709").await.params[1..3], ["CLASS", "default"]);
+    admin.send("TESTLINE 127.0.0.1").await;
+    assert_eq!(admin.expect("709").await.params[1..], ["NONE", "127.0.0.1", "No matching bans"]);
+    assert_eq!(admin.expect("709").await.params[1..3], ["EXEMPT", "127.0.0.0/8"]);
+    assert_eq!(admin.expect("709").await.params[1..3], ["CLASS", "default"]);
+
+    admin.send("TESTGECOS :I am a SpamBot").await;
+    assert_eq!(admin.expect("709").await.params[1..], ["NONE", "*", "No bans match I am a SpamBot"]);
+
+    // TESTMASK counts the users a mask would hit
+    admin.send("TESTMASK *@127.0.0.1").await;
+    assert_eq!(admin.expect("708").await.params[3], "2 local and 0 remote user(s) match");
+    admin.send("TESTMASK user!*@* *nomatch*").await;
+    assert_eq!(admin.expect("708").await.params[3], "0 local and 0 remote user(s) match");
+
+    server.stop().await;
+}
+
+#[tokio::test]
+async fn test_custom_numeric_replies() {
+    let mut config = Config::default();
+    let mut replies = HashMap::new();
+    for (code, text) in [(1, "Bienvenue sur {server_name}, {nick}!"), (421, "{command} :Commande inconnue")] {
+        replies.insert(code, rustircd_core::ReplyConfig { code, text: text.to_string(), description: None });
+    }
+    config.replies = Some(rustircd_core::RepliesConfig { replies });
+    let version = config.server.version.clone();
+    let server = TestServer::start_with(config, &[]).await;
+
+    let mut client = server.connect().await;
+    client.send("NICK alice").await;
+    client.send("USER alice 0 * :Alice").await;
+    assert_eq!(client.expect("001").await.params, ["alice", "Bienvenue sur conformance.test, alice!"]);
+    // Replies without a custom template keep the built-in text
+    assert_eq!(client.expect("002").await.params[1], format!("Your host is conformance.test, running version {}", version));
+
+    client.send("FROB").await;
+    assert_eq!(client.expect("421").await.params[1..], ["FROB", "Commande inconnue"]);
+
+    server.stop().await;
+}
+
+#[tokio::test]
+async fn test_short_and_per_class_motd() {
+    let dir = std::env::temp_dir().join(format!("rustircd-motd-{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("motd.txt"), "Everyone's MOTD\n").unwrap();
+    let long_line = "word ".repeat(20);
+    std::fs::write(dir.join("default.txt"), format!("{}\n", long_line)).unwrap();
+    let mut config = Config::default();
+    config.server.motd_file = Some(dir.join("motd.txt").to_string_lossy().into_owned());
+    config.server.motd.short_motd = true;
+    config.server.motd.overrides.push(config::MotdOverride {
+        class: Some("default".to_string()),
+        port: None,
+        file: dir.join("default.txt").to_string_lossy().into_owned(),
+    });
+    let server = TestServer::start_with(config, &[]).await;
+
+    // The short MOTD skips the text and points at the MOTD command
+    let mut client = server.connect().await;
+    client.send("NICK alice").await;
+    client.send("USER alice 0 * :Alice").await;
+    client.expect("375").await;
+    let end = client.expect_any(&["372", "376"]).await;
+    assert_eq!(end.command.to_string(), "376");
+    assert!(end.params[1].contains("/MOTD"));
+
+    // MOTD sends the class's MOTD, wrapped to 80 characters
+    client.send("MOTD").await;
+    client.expect("375").await;
+    let first = client.expect("372").await.params[1].clone();
+    let second = client.expect("372").await.params[1].clone();
+    assert_eq!(first, format!("- {}", "word ".repeat(16).trim_end()));
+    assert_eq!(second, format!("- {}", "word ".repeat(4).trim_end()));
+    client.expect("376").await;
+
+    server.stop().await;
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[tokio::test]
+async fn test_rules() {
+    let rules_file = std::env::temp_dir().join(format!("rustircd-rules-{}.txt", uuid::Uuid::new_v4()));
+    let mut config = Config::default();
+    config.server.rules_file = Some(rules_file.to_string_lossy().into_owned());
+    let server = TestServer::start_with(config, &[]).await;
+    let mut client = server.register("alice").await;
+
+    client.send("RULES").await;
+    client.expect("434").await;
+
+    std::fs::write(&rules_file, "No spam.\nBe excellent to each other.\n").unwrap();
+    server.server.rehash(rustircd_core::RehashSection::Rules).await.unwrap();
+    client.send("RULES").await;
+    assert_eq!(client.expect("308").await.params[1], "- conformance.test Server Rules -");
+    assert_eq!(client.expect("232").await.params[1], "- No spam.");
+    assert_eq!(client.expect("232").await.params[1], "- Be excellent to each other.");
+    client.expect("309").await;
+
+    server.stop().await;
+    std::fs::remove_file(rules_file).unwrap();
+}
+
+#[tokio::test]
+async fn test_cap_negotiation_gates_registration() {
+    let server = TestServer::start(&["ircv3", "sasl"]).await;
+    let mut client = server.connect().await;
+
+    client.send("CAP LS 302").await;
+    let ls = client.expect("CAP").await;
+    assert_eq!(ls.params[1], "LS");
+    let offered: Vec<&str> = ls.params.last().unwrap().split(' ').collect();
+    for cap in ["multi-prefix", "sasl", "server-time"] {
+        assert!(offered.iter().any(|offer| offer.split('=').next() == Some(cap)), "{} not offered", cap);
+    }
+
+    // Registration waits for CAP END
+    client.send("NICK capper").await;
+    client.send("USER capper 0 * :Capper").await;
+    client.expect_silence().await;
+
+    client.send("CAP REQ :multi-prefix no-such-cap").await;
+    let ack = client.expect("CAP").await;
+    assert_eq!(ack.params[1..], ["ACK", "multi-prefix"]);
+    let nak = client.expect("CAP").await;
+    assert_eq!(nak.params[1..], ["NAK", "no-such-cap"]);
+
+    client.send("CAP END").await;
+    assert_eq!(client.expect("001").await.params[0], "capper");
+
+    server.stop().await;
+}
+
+#[tokio::test]
+async fn test_typing_notifications_are_gated_and_limited() {
+    let mut config = Config::default();
+    config.modules.typing.max_per_window = 2;
+    let server = TestServer::start_with(config, &["ircv3", "channel"]).await;
+
+    /// Register after negotiating message-tags
+    async fn tags_client(server: &TestServer, nick: &str) -> TestClient {
+        let mut client = server.connect().await;
+        client.send("CAP LS 302").await;
+        client.expect("CAP").await;
+        client.send("CAP REQ :message-tags").await;
+        assert_eq!(client.expect("CAP").await.params[1..], ["ACK", "message-tags"]);
+        client.send(&format!("NICK {}", nick)).await;
+        client.send(&format!("USER {} 0 * :Tags", nick)).await;
+        client.send("CAP END").await;
+        client.expect_any(&["376", "422"]).await;
+        client
+    }
+
+    let mut typist = tags_client(&server, "typist").await;
+    let mut watcher = tags_client(&server, "watcher").await;
+    let mut plain = server.register("plain").await;
+    for client in [&mut typist, &mut watcher, &mut plain] {
+        client.send("JOIN #typing").await;
+        client.expect("JOIN").await;
+    }
+    watcher.expect("JOIN").await;
+
+    // Only clients with message-tags see typing, and only up to the limit
+    for _ in 0..3 {
+        typist.send("@+typing=active TAGMSG #typing").await;
+    }
+    for _ in 0..2 {
+        let tagmsg = watcher.expect("TAGMSG").await;
+        assert_eq!(tagmsg.tag("+typing"), Some("active"));
+        assert!(tagmsg.prefix.unwrap().to_string().starts_with("typist!"));
+    }
+    watcher.expect_silence().await;
+    plain.expect_silence().await;
+
+    server.stop().await;
+}
+
+/// Connect, start registering and negotiate the sasl capability
+async fn sasl_client(server: &TestServer, nick: &str) -> TestClient {
+    let mut client = server.connect().await;
+    client.send("CAP LS 302").await;
+    client.expect("CAP").await;
+    client.send(&format!("NICK {}", nick)).await;
+    client.send(&format!("USER {} 0 * :SASL", nick)).await;
+    client.send("CAP REQ :sasl").await;
+    assert_eq!(client.expect("CAP").await.params[1..], ["ACK", "sasl"]);
+    client
+}
+
+#[tokio::test]
+async fn test_sasl_plain_logs_in_during_registration() {
+    let server = TestServer::start_with_provider(Config::default(), &["ircv3", "sasl"], Some(Arc::new(TestAccounts))).await;
+    let mut client = sasl_client(&server, "saslok").await;
+
+    client.send("AUTHENTICATE PLAIN").await;
+    let prompt = client.expect("AUTHENTICATE").await;
+    assert_eq!(prompt.params, ["+"]);
+    client.send(&format!("AUTHENTICATE {}", plain("alice", "secret"))).await;
+    let logged_in = client.expect("900").await;
+    assert_eq!(logged_in.params[..3], ["saslok", "saslok!saslok@*", "alice"]);
+    assert_eq!(client.expect("903").await.params[0], "saslok");
+
+    // Logging in twice is refused, before and after registering
+    client.send("AUTHENTICATE PLAIN").await;
+    client.expect("907").await;
+    client.send("CAP END").await;
+    assert_eq!(client.expect("001").await.params[0], "saslok");
+    client.expect_any(&["376", "422"]).await;
+    client.send("AUTHENTICATE PLAIN").await;
+    client.expect("907").await;
+
+    server.stop().await;
+}
+
+#[tokio::test]
+async fn test_sasl_login_grants_provider_privileges() {
+    let server = TestServer::start_with_provider(Config::default(), &["ircv3", "sasl"], Some(Arc::new(TestAccounts))).await;
+    let mut client = sasl_client(&server, "opsasl").await;
+    client.send("AUTHENTICATE PLAIN").await;
+    client.expect("AUTHENTICATE").await;
+    client.send(&format!("AUTHENTICATE {}", plain("ircop", "secret"))).await;
+    client.expect("903").await;
+    client.send("CAP END").await;
+    client.expect_any(&["376", "422"]).await;
+    client.expect("381").await;
+
+    let user = server.server.database().get_user_by_nick("opsasl").unwrap();
+    assert!(user.is_operator && user.has_mode('r'));
+    assert!(user.has_operator_flag(rustircd_core::config::OperatorFlag::Override));
+
+    // Plain accounts get no privileges
+    let mut plain_client = sasl_client(&server, "plainsasl").await;
+    plain_client.send("AUTHENTICATE PLAIN").await;
+    plain_client.expect("AUTHENTICATE").await;
+    plain_client.send(&format!("AUTHENTICATE {}", plain("alice", "secret"))).await;
+    plain_client.expect("903").await;
+    plain_client.send("CAP END").await;
+    plain_client.expect_any(&["376", "422"]).await;
+    assert!(!server.server.database().get_user_by_nick("plainsasl").unwrap().is_operator);
+
+    server.stop().await;
+}
+
+#[tokio::test]
+async fn test_sasl_failures_and_limits() {
+    let server = TestServer::start_with_provider(Config::default(), &["ircv3", "sasl"], Some(Arc::new(TestAccounts))).await;
+
+    // AUTHENTICATE only works while negotiating capabilities
+    let mut early = server.connect().await;
+    early.send("AUTHENTICATE PLAIN").await;
+    early.expect("904").await;
+
+    // EXTERNAL needs a client certificate
+    let mut nocert = sasl_client(&server, "nocert").await;
+    nocert.send("AUTHENTICATE EXTERNAL").await;
+    nocert.expect("AUTHENTICATE").await;
+    nocert.send("AUTHENTICATE +").await;
+    nocert.expect("904").await;
+
+    let mut client = sasl_client(&server, "saslbad").await;
+    client.send("AUTHENTICATE SCRAM-SHA-1").await;
+    assert_eq!(client.expect("908").await.params[1], "PLAIN,EXTERNAL");
+    client.expect("904").await;
+
+    client.send("AUTHENTICATE PLAIN").await;
+    client.expect("AUTHENTICATE").await;
+    client.send("AUTHENTICATE *").await;
+    client.expect("906").await;
+
+    client.send("AUTHENTICATE PLAIN").await;
+    client.expect("AUTHENTICATE").await;
+    client.send(&format!("AUTHENTICATE {}", "A".repeat(401))).await;
+    client.expect("905").await;
+
+    // Three wrong passwords use up the attempts
+    for _ in 0..3 {
+        client.send("AUTHENTICATE PLAIN").await;
+        client.expect("AUTHENTICATE").await;
+        client.send(&format!("AUTHENTICATE {}", plain("alice", "guess"))).await;
+        client.expect("904").await;
+    }
+    client.send("AUTHENTICATE PLAIN").await;
+    client.expect("904").await;
+    client.expect_silence().await;
+
+    // Failing still lets the client register, without an account
+    client.send("CAP END").await;
+    assert_eq!(client.expect("001").await.params[0], "saslbad");
+
+    server.stop().await;
+}
+
+#[tokio::test]
+async fn test_oper_block_requires_services_account() {
+    let mut config = Config::default();
+    let mut operator = config::OperatorConfig::new("netadmin".to_string(), "", "*@*".to_string(), vec![]);
+    operator.password_hash.clear();
+    operator.account = Some("alice".to_string());
+    config.network.operators = vec![operator];
+    let server = TestServer::start_with_provider(config, &["ircv3", "sasl", "oper"], Some(Arc::new(TestAccounts))).await;
+
+    // Without the account the block can't be used
+    let mut stranger = server.register("stranger").await;
+    stranger.send("OPER netadmin").await;
+    stranger.expect("464").await;
+
+    let mut admin = sasl_client(&server, "admin").await;
+    admin.send("AUTHENTICATE PLAIN").await;
+    admin.expect("AUTHENTICATE").await;
+    admin.send(&format!("AUTHENTICATE {}", plain("alice", "secret"))).await;
+    admin.expect("903").await;
+    admin.send("CAP END").await;
+    admin.expect_any(&["376", "422"]).await;
+    admin.send("OPER netadmin").await;
+    assert_eq!(admin.expect_any(&["381", "464"]).await.command.to_string(), "381");
+
+    server.stop().await;
+}
+
+#[tokio::test]
+async fn test_oper_autojoin_and_oper_only_channels() {
+    let mut config = Config::default();
+    let mut operator = config::OperatorConfig::new("netadmin".to_string(), "", "*@*".to_string(), vec![config::OperatorFlag::GlobalOper]);
+    operator.password_hash = config::PasswordHasher::hash_password("secret");
+    config.network.operators = vec![operator];
+    config.server.oper_autojoin = vec!["#opers".to_string()];
+    let server = TestServer::start_with(config, &["oper", "channel"]).await;
+
+    // OPER joins the configured channels
+    let mut admin = server.register("admin").await;
+    admin.send("OPER netadmin secret").await;
+    admin.expect("381").await;
+    assert_eq!(admin.expect("JOIN").await.params, ["#opers"]);
+
+    // With +O only operators get in
+    admin.send("MODE #opers +O").await;
+    admin.expect("MODE").await;
+    let mut user = server.register("user").await;
+    user.send("JOIN #opers").await;
+    assert_eq!(user.expect("520").await.params[1], "#opers");
+
+    server.stop().await;
+}
+
+#[tokio::test]
+async fn test_challenge_oper() {
+    use ed25519_dalek::pkcs8::{spki::der::pem::LineEnding, EncodePublicKey};
+    use ed25519_dalek::{Signer, SigningKey};
+
+    let key = SigningKey::from_bytes(&[7; 32]);
+    let key_file = std::env::temp_dir().join(format!("rustircd-challenge-{}.pub", uuid::Uuid::new_v4()));
+    std::fs::write(&key_file, key.verifying_key().to_public_key_pem(LineEnding::LF).unwrap()).unwrap();
+    let mut config = Config::default();
+    let mut operator = config::OperatorConfig::new("keyed".to_string(), "", "*@*".to_string(), vec![]);
+    operator.password_hash.clear();
+    operator.challenge_key_file = Some(key_file.to_string_lossy().into_owned());
+    config.network.operators = vec![operator];
+    let server = TestServer::start_with(config, &["oper"]).await;
+    let mut client = server.register("keyholder").await;
+
+    // Blocks without a key can't be challenged, and keyed blocks don't take OPER
+    client.send("CHALLENGE nosuch").await;
+    client.expect("491").await;
+    client.send("OPER keyed").await;
+    client.expect("464").await;
+
+    /// Send CHALLENGE and decode the 740 lines that come back
+    async fn start_challenge(client: &mut TestClient) -> Vec<u8> {
+        client.send("CHALLENGE keyed").await;
+        let mut text = String::new();
+        loop {
+            let line = client.expect_any(&["740", "741"]).await;
+            if line.command.to_string() == "741" {
+                return general_purpose::STANDARD.decode(text).unwrap();
+            }
+            text.push_str(line.params.last().unwrap());
+        }
+    }
+
+    start_challenge(&mut client).await;
+    client.send(&format!("CHALLENGE +{}", general_purpose::STANDARD.encode([0u8; 64]))).await;
+    client.expect("464").await;
+
+    let nonce = start_challenge(&mut client).await;
+    let signature = key.sign(&nonce);
+    client.send(&format!("CHALLENGE +{}", general_purpose::STANDARD.encode(signature.to_bytes()))).await;
+    assert_eq!(client.expect_any(&["381", "464"]).await.command.to_string(), "381");
+
+    server.stop().await;
+    let _ = std::fs::remove_file(key_file);
+}
+
+#[tokio::test]
+async fn test_failed_sasl_does_not_strand_registration() {
+    let server = TestServer::start(&["ircv3", "sasl"]).await;
+    let mut client = sasl_client(&server, "sasluser").await;
+
+    // The exchange is answered, and giving up on it still lets the client in
+    client.send("AUTHENTICATE PLAIN").await;
+    assert_eq!(client.expect("AUTHENTICATE").await.params, ["+"]);
+    client.send("AUTHENTICATE bm90LWEtbWVjaGFuaXNt").await;
+    client.expect("904").await;
+    client.send("CAP END").await;
+    assert_eq!(client.expect("001").await.params[0], "sasluser");
+
+    server.stop().await;
+}
+
+#[tokio::test]
+async fn test_channel_operations() {
+    let server = TestServer::start(&["channel"]).await;
+    let mut alice = server.register("alice").await;
+    let mut bob = server.register("bob").await;
+
+    // Joins are echoed to the joiner and shown to existing members
+    alice.send("JOIN #conformance").await;
+    let join = alice.expect("JOIN").await;
+    assert_eq!(join.params, ["#conformance"]);
+    assert!(join.prefix.unwrap().to_string().starts_with("alice!"));
+    bob.send("JOIN #conformance").await;
+    bob.expect("JOIN").await;
+    assert!(alice.expect("JOIN").await.prefix.unwrap().to_string().starts_with("bob!"));
+
+    // The channel's creator is its operator
+    bob.send("NAMES #conformance").await;
+    let names = bob.expect("353").await;
+    let mut members: Vec<&str> = names.params.last().unwrap().split(' ').collect();
+    members.sort_unstable();
+    assert_eq!(members, ["@alice", "bob"]);
+    assert_eq!(bob.expect("366").await.params[1], "#conformance");
+
+    // Topic changes reach every member
+    alice.send("TOPIC #conformance :Conformance testing").await;
+    for client in [&mut alice, &mut bob] {
+        let topic = client.expect("TOPIC").await;
+        assert_eq!(topic.params, ["#conformance", "Conformance testing"]);
+    }
+
+    // So do mode changes
+    alice.send("MODE #conformance +o bob").await;
+    for client in [&mut alice, &mut bob] {
+        let mode = client.expect("MODE").await;
+        assert_eq!(mode.params[..2], ["#conformance", "+o"]);
+    }
+
+    alice.send("LIST").await;
+    let entry = alice.expect("322").await;
+    assert_eq!(entry.params[1..3], ["#conformance", "2"]);
+    alice.expect("323").await;
+
+    // Members see others leave
+    bob.send("PART #conformance :bye").await;
+    let part = alice.expect("PART").await;
+    assert_eq!(part.params[0], "#conformance");
+    assert!(part.prefix.unwrap().to_string().starts_with("bob!"));
+
+    alice.send("KICK #conformance bob :not here").await;
+    alice.expect_any(&["441", "KICK"]).await;
+
+    server.stop().await;
+}
+
+#[tokio::test]
+async fn test_shun_applies_before_channel_commands() {
+    let mut config = Config::default();
+    let mut operator = config::OperatorConfig::new("netadmin".to_string(), "", "*@*".to_string(), vec![config::OperatorFlag::GlobalOper]);
+    operator.password_hash = config::PasswordHasher::hash_password("secret");
+    config.network.operators = vec![operator];
+    // Channel is loaded first, so it would claim JOIN before a later handler
+    let server = TestServer::start_with(config, &["channel", "oper", "shun"]).await;
+
+    let mut admin = server.register("admin").await;
+    admin.send("OPER netadmin secret").await;
+    admin.expect("381").await;
+    admin.send("JOIN #rust").await;
+    admin.expect("JOIN").await;
+    let mut mallory = server.register("mallory").await;
+
+    admin.send("SHUN mallory!*@* 1h :spam").await;
+    admin.expect_notice("Added shun").await;
+
+    mallory.send("JOIN #rust").await;
+    mallory.expect_silence().await;
+    admin.send("NAMES #rust").await;
+    assert_eq!(admin.expect("353").await.params.last().unwrap(), "@admin");
+
+    server.stop().await;
+}
+
+#[tokio::test]
+async fn test_channel_rename() {
+    let server = TestServer::start(&["ircv3", "channel"]).await;
+    let mut alice = server.connect().await;
+    alice.send("CAP LS 302").await;
+    alice.expect("CAP").await;
+    alice.send("CAP REQ :draft/channel-rename").await;
+    assert_eq!(alice.expect("CAP").await.params[1..], ["ACK", "draft/channel-rename"]);
+    alice.send("NICK alice").await;
+    alice.send("USER alice 0 * :Alice").await;
+    alice.send("CAP END").await;
+    alice.expect_any(&["376", "422"]).await;
+    let mut bob = server.register("bob").await;
+    for client in [&mut alice, &mut bob] {
+        client.send("JOIN #old").await;
+        client.expect("JOIN").await;
+    }
+    alice.expect("JOIN").await;
+    alice.send("JOIN #taken").await;
+    alice.expect("JOIN").await;
+
+    // Only channel operators rename, and not onto another channel or type
+    bob.send("RENAME #old #new").await;
+    assert_eq!(bob.expect("482").await.params[1], "#old");
+    alice.send("RENAME #old #taken").await;
+    assert_eq!(alice.expect("FAIL").await.params[..2], ["RENAME", "CHANNEL_NAME_IN_USE"]);
+    alice.send("RENAME #old &new").await;
+    assert_eq!(alice.expect("FAIL").await.params[..2], ["RENAME", "CANNOT_RENAME"]);
+
+    // Capable clients see RENAME, the rest a PART and JOIN
+    alice.send("RENAME #old #new :tidying up").await;
+    let rename = alice.expect("RENAME").await;
+    assert_eq!(rename.params, ["#old", "#new", "tidying up"]);
+    assert_eq!(bob.expect("PART").await.params[0], "#old");
+    assert_eq!(bob.expect("JOIN").await.params, ["#new"]);
+    let names = bob.expect("353").await;
+    let mut members: Vec<&str> = names.params.last().unwrap().split(' ').collect();
+    members.sort_unstable();
+    assert_eq!(members, ["@alice", "bob"]);
+    bob.expect("366").await;
+
+    // The channel now answers to its new name only
+    alice.send("NAMES #new").await;
+    assert_eq!(alice.expect("353").await.params[2], "#new");
+    bob.send("NAMES #old").await;
+    bob.expect_silence().await;
+
+    server.stop().await;
+}
+
+#[tokio::test]
+async fn test_bot_mode() {
+    let server = TestServer::start(&["channel"]).await;
+    let mut robot = server.connect().await;
+    robot.send("NICK robot").await;
+    robot.send("USER robot 0 * :Robot").await;
+    let myinfo = robot.expect("004").await;
+    assert!(myinfo.params[3].contains('B'));
+    let isupport = robot.expect("005").await;
+    assert!(isupport.params.iter().any(|token| token == "BOT=B"));
+    robot.expect_any(&["376", "422"]).await;
+    let mut alice = server.register("alice").await;
+
+    // Users mark themselves as bots, but not each other
+    alice.send("MODE robot +B").await;
+    alice.expect("502").await;
+    robot.send("MODE robot +B").await;
+    assert_eq!(robot.expect("MODE").await.params[1], "+B");
+
+    alice.send("WHOIS robot").await;
+    let bot = alice.expect("335").await;
+    assert_eq!(bot.params[1], "robot");
+    alice.expect("318").await;
+
+    alice.send("WHO robot").await;
+    assert_eq!(alice.expect("352").await.params[6], "HB");
+    alice.send("WHO robot %nf").await;
+    assert_eq!(alice.expect("354").await.params[1..], ["robot", "HB"]);
+
+    server.stop().await;
+}
+
+#[tokio::test]
+async fn test_netsplit_and_netjoin_batches() {
+    let mut config = Config::default();
+    config.network.links.push(config::ServerLink {
+        name: "leaf.test".to_string(),
+        hostname: "127.0.0.1".to_string(),
+        port: 7000,
+        password: "leafpass".to_string(),
+        tls: false,
+        outgoing: false,
+        class: None,
+        autoconnect: false,
+        tls_fingerprint: None,
+        tls_ca_file: None,
+        tls_server_name: None,
+        compression: false,
+    });
+    let server = TestServer::start_with(config, &["ircv3", "channel"]).await;
+    let mut alice = server.connect().await;
+    alice.send("CAP LS 302").await;
+    alice.expect("CAP").await;
+    alice.send("CAP REQ :batch").await;
+    assert_eq!(alice.expect("CAP").await.params[1..], ["ACK", "batch"]);
+    alice.send("NICK alice").await;
+    alice.send("USER alice 0 * :Alice").await;
+    alice.send("CAP END").await;
+    alice.expect_any(&["376", "422"]).await;
+    let mut bob = server.register("bob").await;
+    for client in [&mut alice, &mut bob] {
+        client.send("JOIN #net").await;
+        client.expect("JOIN").await;
+    }
+    alice.expect("JOIN").await;
+
+    // Members burst in on link are shown joining, batched for batch clients
+    let mut leaf = server.link("leaf.test", "leafpass").await;
+    let now = chrono::Utc::now().timestamp();
+    for nick in ["carol", "dave"] {
+        leaf.send(&format!(":leaf.test UBURST {nick} {nick} leaf.host Remote leaf.test {} {now}", uuid::Uuid::new_v4())).await;
+    }
+    leaf.send(&format!(":leaf.test CBURST #net {now} + :carol dave")).await;
+    let start = alice.expect("BATCH").await;
+    let reference = start.params[0].strip_prefix('+').unwrap().to_string();
+    assert_eq!(start.params[1..], ["netjoin", "conformance.test", "leaf.test"]);
+    for _ in 0..2 {
+        assert_eq!(alice.recv().await.tag("batch"), Some(reference.as_str()));
+    }
+    assert_eq!(alice.expect("BATCH").await.params, [format!("-{}", reference)]);
+    for nick in ["carol!", "dave!"] {
+        let join = bob.expect("JOIN").await;
+        assert!(join.tag("batch").is_none());
+        assert!(join.prefix.unwrap().to_string().starts_with(nick));
+    }
+
+    // On a split, QUITs say which servers parted
+    leaf.send(":leaf.test SQUIT leaf.test :Leaving").await;
+    let start = alice.expect("BATCH").await;
+    assert_eq!(start.params[1..], ["netsplit", "conformance.test", "leaf.test"]);
+    for _ in 0..2 {
+        let quit = alice.recv().await;
+        assert_eq!(quit.command, MessageType::Quit);
+        assert!(quit.tag("batch").is_some());
+    }
+    alice.expect("BATCH").await;
+    for _ in 0..2 {
+        let quit = bob.expect("QUIT").await;
+        assert_eq!(quit.params, ["conformance.test leaf.test"]);
+    }
+    bob.expect_silence().await;
+
+    server.stop().await;
+}
+
+#[tokio::test]
+async fn test_services_link() {
+    let mut config = Config::default();
+    config.services.services.push(config::ServiceDefinition {
+        name: "services.test".to_string(),
+        service_type: "atheme".to_string(),
+        hostname: "127.0.0.1".to_string(),
+        port: 6697,
+        password: "linkpass".to_string(),
+        tls: false,
+        tls_verify: None,
+        tls_ca_file: None,
+        config: HashMap::new(),
+        enabled: true,
+    });
+    config.network.links.push(config::ServerLink {
+        name: "leaf.test".to_string(),
+        hostname: "127.0.0.1".to_string(),
+        port: 7000,
+        password: "leafpass".to_string(),
+        tls: false,
+        outgoing: false,
+        class: None,
+        autoconnect: false,
+        tls_fingerprint: None,
+        tls_ca_file: None,
+        tls_server_name: None,
+        compression: false,
+    });
+    let server = TestServer::start_with(config, &["channel"]).await;
+    let mut alice = server.register("alice").await;
+
+    // Until services link, messages for them say so
+    alice.send("PRIVMSG NickServ@services.test :help").await;
+    let down = alice.expect("440").await;
+    assert_eq!(down.params[1], "NickServ@services.test");
+
+    let mut services = server.link("services.test", "linkpass").await;
+    services.send(&format!(
+        ":services.test UBURST NickServ NickServ services.test Services services.test {} {}",
+        uuid::Uuid::new_v4(),
+        chrono::Utc::now().timestamp()
+    )).await;
+
+    // Both plain and nick@server messages reach the pseudo-client
+    alice.send("PRIVMSG NickServ :identify secret").await;
+    assert_eq!(services.expect("PRIVMSG").await.params, ["NickServ", "identify secret"]);
+    alice.send("PRIVMSG NickServ@services.test :help").await;
+    assert_eq!(services.expect("PRIVMSG").await.params, ["NickServ", "help"]);
+
+    services.send(":services.test SVSHOST alice cloak.test").await;
+    assert_eq!(alice.expect("396").await.params[1], "cloak.test");
+
+    // Operator modes can't be handed out this way
+    services.send(":services.test SVSMODE alice +wo").await;
+    let mode = alice.expect("MODE").await;
+    assert_eq!(mode.params, ["alice", "+w"]);
+
+    services.send(":services.test SVSNICK alice alice_").await;
+    assert_eq!(alice.expect("NICK").await.params, ["alice_"]);
+    assert_eq!(services.expect("NICK").await.params, ["alice", "alice_"]);
+
+    services.send(":services.test SVSJOIN alice_ #help").await;
+    assert_eq!(alice.expect("JOIN").await.params, ["#help"]);
+    services.send(":services.test SVSPART alice_ #help").await;
+    assert_eq!(services.expect("PART").await.params[0], "#help");
+
+    // Only the services server may use these commands
+    let mut leaf = server.link("leaf.test", "leafpass").await;
+    leaf.send(":leaf.test SVSNICK alice_ bob").await;
+    alice.expect_silence().await;
+
+    server.stop().await;
+}
@@ -334,7 +334,7 @@ impl AthemeIntegration {
         }
         
         // Just log the message since we don't have context
-        tracing::debug!("Received Atheme message (no context): {:?}", message);
+        tracing::debug!("Received Atheme message (no context): {}", message.redacted());
         
         Ok(())
     }
@@ -420,7 +420,7 @@ impl AthemeIntegration {
                 if message.params.len() > 0 && message.params[0] == "LOGIN" {
                     self.handle_atheme_encap_login(message, context).await?;
                 } else {
-                    tracing::debug!("Received ENCAP command: {:?}", message);
+                    tracing::debug!("Received ENCAP command: {}", message.redacted());
                 }
             }
             "METADATA" => {
@@ -429,7 +429,7 @@ impl AthemeIntegration {
             }
             _ => {
                 // Handle other Atheme messages
-                tracing::debug!("Received Atheme message: {:?}", message);
+                tracing::debug!("Received Atheme message: {}", message.redacted());
             }
             }
             _ => {
@@ -453,17 +453,15 @@ impl AthemeIntegration {
             return Err(Error::Service("Atheme service not authenticated".to_string()));
         }
         
-        // Format message for Atheme
-        let formatted_message = message.to_string();
-        
         // In production, this would write to the TCP stream:
+        // let formatted_message = message.to_string();
         // if let Some(connection) = self.connections.read().await.get(&self.config.service_name) {
         //     if let Some(stream) = &connection.stream {
         //         stream.write_all(formatted_message.as_bytes()).await?;
         //     }
         // }
         
-        tracing::debug!("Sending message to Atheme: {}", formatted_message.trim());
+        tracing::debug!("Sending message to Atheme: {}", message.redacted());
         
         // Update statistics
         {
@@ -1054,7 +1052,8 @@ impl AthemeIntegration {
         
         // Create SASL authentication request tracking
         let client_id = request.client_info.id;
-        let mechanism = "PLAIN"; // For now, we'll use PLAIN mechanism
+        // SASL EXTERNAL requests carry the client's certificate fingerprint
+        let mechanism = request.context.get("mechanism").map(String::as_str).unwrap_or("PLAIN");
         
         let sasl_request = SaslAuthRequest {
             request: request.clone(),
@@ -1100,8 +1099,9 @@ impl AthemeIntegration {
                 general_purpose::STANDARD.encode(auth_string.as_bytes())
             }
             "EXTERNAL" => {
-                // For EXTERNAL mechanism, use client certificate info
-                "".to_string()
+                // Services look the account up by certificate fingerprint,
+                // as with the certfp parameter of Solanum's SASL S message
+                request.context.get("certfp").cloned().unwrap_or_default()
             }
             _ => {
                 return Err(Error::Auth(format!("Unsupported SASL mechanism: {}", mechanism)));
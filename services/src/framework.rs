@@ -81,7 +81,7 @@ impl ServiceContext {
     pub async fn send_to_user(&self, nick: &str, message: Message) -> Result<()> {
         // This would need to be implemented in the core to find the user's client
         // For now, we'll just log it
-        tracing::debug!("Would send message to user {}: {:?}", nick, message);
+        tracing::debug!("Would send message to user {}: {}", nick, message.redacted());
         Ok(())
     }
     
@@ -89,7 +89,7 @@ impl ServiceContext {
     pub async fn send_to_channel(&self, channel: &str, message: Message) -> Result<()> {
         // This would need to be implemented in the core to find channel members
         // For now, we'll just log it
-        tracing::debug!("Would send message to channel {}: {:?}", channel, message);
+        tracing::debug!("Would send message to channel {}: {}", channel, message.redacted());
         Ok(())
     }
 }
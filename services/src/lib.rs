@@ -9,7 +9,17 @@ pub mod framework;
 pub mod example;
 pub mod atheme;
 pub mod auth_provider;
+pub mod nickserv;
+pub mod registry;
+
+// Fixtures shared with the modules crate, which services doesn't depend on
+#[cfg(test)]
+#[path = "../../modules/src/test_support.rs"]
+#[allow(dead_code)]
+mod test_support;
 
 pub use framework::{Service, ServiceManager, ServiceResult};
 pub use atheme::{AthemeIntegration, AthemeConfig, AthemeConnection, AthemeConnectionState, AthemeStats, AthemeServicesModule, AthemeConfigBuilder, AthemeSaslAuthProvider};
 pub use auth_provider::{ServicesAuthProvider, ServicesAuthManager, AthemeAuthProvider};
+pub use nickserv::{Account, AccountStore, NickServModule};
+pub use registry::register_services;
@@ -0,0 +1,694 @@
+//! Built-in account service (NickServ-lite)
+//!
+//! Networks that don't link external services can load the `nickserv`
+//! module instead. Users register and log in to accounts by messaging
+//! NickServ, or with the `ACCOUNT` command taking the same arguments.
+//! The account store is also registered as an authentication provider,
+//! so SASL PLAIN logs in to the same accounts.
+//!
+//! With `enforce_timeout` set, a registered nick is reserved for its
+//! account: anyone else using it is renamed to a guest nick unless they
+//! identify in time.
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use rustircd_core::config::{NickServConfig, PasswordHasher};
+use rustircd_core::module::{ModuleContext, ModuleResult, ModuleStatsResponse};
+use rustircd_core::{
+    AuthFailureKind, AuthInfo, AuthManager, AuthProvider, AuthProviderCapabilities, AuthRequest, AuthResult, Client,
+    CommandSpec, Error, Message, MessageType, Module, ModuleNumericManager, NumericReply, Prefix, Result, User,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+/// Name the account store is registered under with the auth manager
+const PROVIDER_NAME: &str = "nickserv";
+
+/// A registered account
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Account {
+    /// Account name as registered
+    pub name: String,
+    /// Argon2 hash of the password
+    pub password_hash: String,
+    /// Contact address given at registration
+    pub email: Option<String>,
+    /// When the account was registered
+    pub registered_at: DateTime<Utc>,
+}
+
+/// Accounts, keyed by lowercased name, optionally kept in a JSON file
+///
+/// The whole file is rewritten on every change, like the ban files.
+pub struct AccountStore {
+    accounts: parking_lot::RwLock<HashMap<String, Account>>,
+    path: Option<PathBuf>,
+}
+
+impl AccountStore {
+    /// A store that keeps accounts in memory only
+    pub fn in_memory() -> Self {
+        Self {
+            accounts: parking_lot::RwLock::new(HashMap::new()),
+            path: None,
+        }
+    }
+
+    /// Open the store backed by `path`, which is created on first change
+    pub fn open(path: impl Into<PathBuf>) -> Result<Self> {
+        let path = path.into();
+        let accounts: Vec<Account> = match std::fs::read_to_string(&path) {
+            Ok(content) => serde_json::from_str(&content)
+                .map_err(|e| Error::Config(format!("Failed to parse accounts file {}: {}", path.display(), e)))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(Error::Config(format!("Failed to read accounts file {}: {}", path.display(), e))),
+        };
+        let accounts = accounts.into_iter().map(|account| (account.name.to_ascii_lowercase(), account)).collect();
+        Ok(Self {
+            accounts: parking_lot::RwLock::new(accounts),
+            path: Some(path),
+        })
+    }
+
+    /// Look up an account by name
+    pub fn get(&self, name: &str) -> Option<Account> {
+        self.accounts.read().get(&name.to_ascii_lowercase()).cloned()
+    }
+
+    /// Number of registered accounts
+    pub fn len(&self) -> usize {
+        self.accounts.read().len()
+    }
+
+    /// Whether no accounts are registered
+    pub fn is_empty(&self) -> bool {
+        self.accounts.read().is_empty()
+    }
+
+    /// Register a new account
+    pub fn register(&self, name: &str, password: &str, email: Option<&str>) -> Result<Account> {
+        // Hashing is slow, so do it before taking the lock
+        let account = Account {
+            name: name.to_string(),
+            password_hash: PasswordHasher::hash_password(password),
+            email: email.map(str::to_string),
+            registered_at: Utc::now(),
+        };
+        let mut accounts = self.accounts.write();
+        let key = name.to_ascii_lowercase();
+        if accounts.contains_key(&key) {
+            return Err(Error::User(format!("{} is already registered", name)));
+        }
+        accounts.insert(key, account.clone());
+        self.save(&accounts)?;
+        Ok(account)
+    }
+
+    /// The account, if the password is right
+    pub fn verify(&self, name: &str, password: &str) -> Option<Account> {
+        self.get(name).filter(|account| PasswordHasher::verify_password(password, &account.password_hash))
+    }
+
+    /// Change an account's password
+    pub fn set_password(&self, name: &str, password: &str) -> Result<()> {
+        let password_hash = PasswordHasher::hash_password(password);
+        let mut accounts = self.accounts.write();
+        let account = accounts.get_mut(&name.to_ascii_lowercase())
+            .ok_or_else(|| Error::User(format!("{} is not registered", name)))?;
+        account.password_hash = password_hash;
+        self.save(&accounts)
+    }
+
+    /// Delete an account
+    pub fn drop_account(&self, name: &str) -> Result<()> {
+        let mut accounts = self.accounts.write();
+        if accounts.remove(&name.to_ascii_lowercase()).is_none() {
+            return Err(Error::User(format!("{} is not registered", name)));
+        }
+        self.save(&accounts)
+    }
+
+    fn save(&self, accounts: &HashMap<String, Account>) -> Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        let mut sorted: Vec<&Account> = accounts.values().collect();
+        sorted.sort_by(|a, b| a.name.cmp(&b.name));
+        let content = serde_json::to_string_pretty(&sorted)?;
+
+        // Write to a temporary file first so a crash never leaves a truncated file
+        let tmp_path = path.with_extension("tmp");
+        std::fs::write(&tmp_path, content)
+            .and_then(|_| std::fs::rename(&tmp_path, path))
+            .map_err(|e| Error::Config(format!("Failed to write accounts file {}: {}", path.display(), e)))
+    }
+}
+
+#[async_trait]
+impl AuthProvider for AccountStore {
+    fn name(&self) -> &str {
+        PROVIDER_NAME
+    }
+
+    fn description(&self) -> &str {
+        "Accounts registered with the built-in NickServ"
+    }
+
+    async fn is_available(&self) -> bool {
+        true
+    }
+
+    async fn authenticate(&self, request: &AuthRequest) -> Result<AuthResult> {
+        match self.verify(&request.username, &request.credential) {
+            Some(account) => Ok(AuthResult::Success(AuthInfo {
+                username: account.name,
+                realname: None,
+                hostname: None,
+                metadata: HashMap::new(),
+                provider: PROVIDER_NAME.to_string(),
+                authenticated_at: Utc::now(),
+            })),
+            None => Ok(AuthResult::Failure("Invalid account or password".to_string())),
+        }
+    }
+
+    async fn validate(&self, auth_info: &AuthInfo) -> Result<bool> {
+        Ok(self.get(&auth_info.username).is_some())
+    }
+
+    fn capabilities(&self) -> AuthProviderCapabilities {
+        AuthProviderCapabilities {
+            account_validation: true,
+            ..AuthProviderCapabilities::default()
+        }
+    }
+}
+
+/// The `nickserv` module: account commands backed by an [`AccountStore`]
+pub struct NickServModule {
+    config: NickServConfig,
+    store: Arc<AccountStore>,
+}
+
+impl NickServModule {
+    /// Create the module, loading accounts from the configured file
+    pub fn new(config: NickServConfig) -> Result<Self> {
+        let store = match &config.accounts_file {
+            Some(path) => AccountStore::open(path)?,
+            None => AccountStore::in_memory(),
+        };
+        Ok(Self::with_store(config, Arc::new(store)))
+    }
+
+    /// Create the module around an existing store
+    pub fn with_store(config: NickServConfig, store: Arc<AccountStore>) -> Self {
+        Self { config, store }
+    }
+
+    /// The accounts this module manages
+    pub fn store(&self) -> Arc<AccountStore> {
+        self.store.clone()
+    }
+
+    /// Whether a PRIVMSG or NOTICE target names the service
+    fn is_service_target(&self, target: &str, context: &ModuleContext) -> bool {
+        match target.split_once('@') {
+            Some((nick, server)) => {
+                nick.eq_ignore_ascii_case(&self.config.nick)
+                    && server.eq_ignore_ascii_case(context.server_connections.local_server_name())
+            }
+            None => target.eq_ignore_ascii_case(&self.config.nick),
+        }
+    }
+
+    /// Answer a client with a NOTICE from the service
+    fn reply(&self, client: &Client, text: &str, context: &ModuleContext) {
+        let nick = client.nickname().unwrap_or("*");
+        let _ = client.send(Message::with_prefix(
+            Prefix::User {
+                nick: self.config.nick.clone(),
+                user: self.config.nick.clone(),
+                host: context.server_connections.local_server_name().to_string(),
+            },
+            MessageType::Notice,
+            vec![nick.to_string(), text.to_string()],
+        ));
+    }
+
+    /// Run one service command, given as words
+    async fn dispatch(&self, client: &Client, words: &[&str], context: &ModuleContext) -> Result<()> {
+        // Account changes land in the database; the client's copy may be stale
+        let Some(user) = context.database.get_user(&client.id).or_else(|| client.get_user().cloned()) else {
+            return Ok(());
+        };
+        let Some((command, args)) = words.split_first() else {
+            return self.help(client, context);
+        };
+        match (command.to_ascii_uppercase().as_str(), args) {
+            ("REGISTER", [password, rest @ ..]) if rest.len() <= 1 => {
+                self.register(client, user, password, rest.first().copied(), context).await
+            }
+            ("IDENTIFY" | "ID", [password]) => {
+                let account = user.nick.clone();
+                self.identify(client, user, &account, password, context).await
+            }
+            ("IDENTIFY" | "ID", [account, password]) => self.identify(client, user, account, password, context).await,
+            ("DROP", [password]) => self.drop_account(client, user, password, context).await,
+            ("SET", [setting, password]) if setting.eq_ignore_ascii_case("PASSWORD") => {
+                self.set_password(client, user, password, context)
+            }
+            ("HELP", _) => self.help(client, context),
+            ("REGISTER" | "IDENTIFY" | "ID" | "DROP" | "SET", _) => {
+                self.reply(client, &format!("Invalid parameters for {}. Use HELP for the syntax.", command.to_ascii_uppercase()), context);
+                Ok(())
+            }
+            _ => {
+                self.reply(client, &format!("Unknown command {}. Use HELP for a list of commands.", command), context);
+                Ok(())
+            }
+        }
+    }
+
+    /// REGISTER <password> [email]: register the current nick and log in to it
+    async fn register(&self, client: &Client, user: User, password: &str, email: Option<&str>, context: &ModuleContext) -> Result<()> {
+        if !self.config.allow_registration {
+            self.reply(client, "Account registration is disabled.", context);
+        } else if let Some(account) = &user.account {
+            self.reply(client, &format!("You are already logged in as {}.", account), context);
+        } else if password.len() < self.config.min_password_length {
+            self.reply(client, &format!("Passwords must be at least {} characters long.", self.config.min_password_length), context);
+        } else if password.eq_ignore_ascii_case(&user.nick) {
+            self.reply(client, "Your password can't be your nickname.", context);
+        } else {
+            match self.store.register(&user.nick, password, email) {
+                Ok(account) => {
+                    tracing::info!("{} registered account {}", user.nick, account.name);
+                    self.reply(client, &format!("{} is now registered to you.", account.name), context);
+                    self.log_in(client, user, &account.name, context)?;
+                }
+                Err(Error::User(reason)) => self.reply(client, &format!("{}.", reason), context),
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(())
+    }
+
+    /// IDENTIFY [account] <password>: log in to an account
+    async fn identify(&self, client: &Client, user: User, account: &str, password: &str, context: &ModuleContext) -> Result<()> {
+        if let Some(current) = &user.account {
+            self.reply(client, &format!("You are already logged in as {}.", current), context);
+            return Ok(());
+        }
+        if context.auth_lockout(client).await.is_some() {
+            self.reply(client, "Too many failed login attempts; try again later.", context);
+            context.record_auth_failure(client, AuthFailureKind::Password).await;
+            return Ok(());
+        }
+        match self.store.verify(account, password) {
+            Some(account) => {
+                self.reply(client, &format!("You are now identified for {}.", account.name), context);
+                self.log_in(client, user, &account.name, context)
+            }
+            None => {
+                tracing::info!("Failed IDENTIFY for {} by {}", account, user.nick);
+                self.reply(client, "Invalid account or password.", context);
+                context.record_auth_failure(client, AuthFailureKind::Password).await;
+                Ok(())
+            }
+        }
+    }
+
+    /// DROP <password>: delete the account the user is logged in to
+    async fn drop_account(&self, client: &Client, user: User, password: &str, context: &ModuleContext) -> Result<()> {
+        let Some(account) = user.account.clone() else {
+            self.reply(client, "You are not logged in.", context);
+            return Ok(());
+        };
+        if self.store.verify(&account, password).is_none() {
+            self.reply(client, "Invalid password.", context);
+            context.record_auth_failure(client, AuthFailureKind::Password).await;
+            return Ok(());
+        }
+        self.store.drop_account(&account)?;
+        tracing::info!("{} dropped account {}", user.nick, account);
+        self.reply(client, &format!("{} has been dropped.", account), context);
+
+        let mask = format!("{}!{}@{}", user.nick, user.username, user.host);
+        let nick = user.nick.clone();
+        let mut user = user;
+        user.set_account(None);
+        context.update_user(user)?;
+        let _ = client.send(NumericReply::logged_out(&nick, &mask));
+        let _ = client.send(self.mode_change(&nick, "-r", context));
+        Ok(())
+    }
+
+    /// SET PASSWORD <password>: change the password of the logged-in account
+    fn set_password(&self, client: &Client, user: User, password: &str, context: &ModuleContext) -> Result<()> {
+        let Some(account) = &user.account else {
+            self.reply(client, "You are not logged in.", context);
+            return Ok(());
+        };
+        if self.store.get(account).is_none() {
+            self.reply(client, &format!("{} is not registered with {}.", account, self.config.nick), context);
+        } else if password.len() < self.config.min_password_length {
+            self.reply(client, &format!("Passwords must be at least {} characters long.", self.config.min_password_length), context);
+        } else {
+            self.store.set_password(account, password)?;
+            self.reply(client, &format!("The password for {} has been changed.", account), context);
+        }
+        Ok(())
+    }
+
+    fn help(&self, client: &Client, context: &ModuleContext) -> Result<()> {
+        for line in [
+            format!("{} lets you register your nickname as an account.", self.config.nick),
+            "REGISTER <password> [email]  Register your current nickname".to_string(),
+            "IDENTIFY [account] <password>  Log in to an account".to_string(),
+            "SET PASSWORD <password>  Change your account's password".to_string(),
+            "DROP <password>  Delete your account".to_string(),
+            format!("Send these to {} or use /ACCOUNT <command>.", self.config.nick),
+        ] {
+            self.reply(client, &line, context);
+        }
+        Ok(())
+    }
+
+    /// Record the account on the user and tell the client
+    fn log_in(&self, client: &Client, mut user: User, account: &str, context: &ModuleContext) -> Result<()> {
+        user.set_account(Some(account.to_string()));
+        let mask = format!("{}!{}@{}", user.nick, user.username, user.host);
+        let nick = user.nick.clone();
+        context.update_user(user)?;
+        let _ = client.send(NumericReply::logged_in(&nick, &mask, account));
+        let _ = client.send(self.mode_change(&nick, "+r", context));
+        tracing::info!("{} logged in to account {}", nick, account);
+        Ok(())
+    }
+
+    /// The server's MODE line announcing a change to a user's modes
+    fn mode_change(&self, nick: &str, modes: &str, context: &ModuleContext) -> Message {
+        Message::with_prefix(
+            Prefix::Server(context.server_connections.local_server_name().to_string()),
+            MessageType::Mode,
+            vec![nick.to_string(), modes.to_string()],
+        )
+    }
+
+    /// Give a user on someone else's registered nick `enforce_timeout` seconds to identify
+    ///
+    /// When the time is up the user is renamed to a guest nick if they still
+    /// use the nick and aren't logged in to its account.
+    fn enforce(&self, user: &User, nick: &str, context: &ModuleContext) {
+        if self.config.enforce_timeout == 0
+            || self.store.get(nick).is_none()
+            || user.account.as_ref().is_some_and(|account| account.eq_ignore_ascii_case(nick))
+        {
+            return;
+        }
+        let notice = |text: String| Message::with_prefix(
+            Prefix::User {
+                nick: self.config.nick.clone(),
+                user: self.config.nick.clone(),
+                host: context.server_connections.local_server_name().to_string(),
+            },
+            MessageType::Notice,
+            vec![user.nick.clone(), text],
+        );
+        context.client_senders.send(&user.id, notice(format!(
+            "{} is registered. Identify with /msg {} IDENTIFY <password> within {} seconds or your nick will be changed.",
+            nick, self.config.nick, self.config.enforce_timeout,
+        )));
+
+        let (store, context, client_id, nick) = (self.store.clone(), context.clone(), user.id, nick.to_string());
+        let (timeout, guest_prefix) = (self.config.enforce_timeout, self.config.guest_prefix.clone());
+        tokio::spawn(async move {
+            tokio::time::sleep(std::time::Duration::from_secs(timeout)).await;
+            let Some(user) = context.database.get_user(&client_id) else {
+                return;
+            };
+            let identified = user.account.as_ref().is_some_and(|account| account.eq_ignore_ascii_case(&nick));
+            if !user.nick.eq_ignore_ascii_case(&nick) || identified || store.get(&nick).is_none() {
+                return;
+            }
+            // Guest nicks are random so they rarely collide; skip any in use
+            let guest = std::iter::repeat_with(|| format!("{}{:05}", guest_prefix, uuid::Uuid::new_v4().as_u128() % 100_000))
+                .take(10)
+                .find(|guest| context.database.get_user_by_nick(guest).is_none());
+            if let Some(guest) = guest {
+                tracing::info!("{} did not identify for {} in time, renaming to {}", user.nick, nick, guest);
+                context.force_nick(client_id, &guest);
+            }
+        });
+    }
+}
+
+#[async_trait]
+impl Module for NickServModule {
+    fn name(&self) -> &str {
+        "nickserv"
+    }
+
+    fn description(&self) -> &str {
+        "Built-in account registration service"
+    }
+
+    fn version(&self) -> &str {
+        "1.0.0"
+    }
+
+    async fn init(&mut self) -> Result<()> {
+        tracing::info!("{} module initialized with {} accounts", self.name(), self.store.len());
+        Ok(())
+    }
+
+    async fn attach_auth_manager(&mut self, auth_manager: Arc<AuthManager>) {
+        // Behind any configured provider, SASL falls back to local accounts
+        let had_providers = auth_manager.has_providers().await;
+        if let Err(e) = auth_manager.register_provider(self.store.clone()).await {
+            tracing::warn!("Failed to register account store with SASL: {}", e);
+            return;
+        }
+        if had_providers {
+            if let Err(e) = auth_manager.add_fallback_provider(PROVIDER_NAME).await {
+                tracing::warn!("Failed to add account store as a fallback provider: {}", e);
+            }
+        }
+    }
+
+    async fn handle_message(&mut self, client: &Client, message: &Message, context: &ModuleContext) -> Result<ModuleResult> {
+        match message.command {
+            MessageType::PrivMsg | MessageType::Notice
+                if message.params.len() >= 2 && self.is_service_target(&message.params[0], context) =>
+            {
+                // Registration is checked before modules see PRIVMSG; NOTICEs get no answer
+                if message.command == MessageType::PrivMsg {
+                    let words: Vec<&str> = message.params[1].split_whitespace().collect();
+                    self.dispatch(client, &words, context).await?;
+                }
+                Ok(ModuleResult::HandledStop)
+            }
+            MessageType::Nick if message.params.first().is_some_and(|nick| nick.eq_ignore_ascii_case(&self.config.nick)) => {
+                let _ = client.send(NumericReply::erroneous_nickname(&message.params[0]));
+                Ok(ModuleResult::HandledStop)
+            }
+            MessageType::Nick if client.is_registered() && !message.params.is_empty() => {
+                // Only warn about a nick the core will actually let them take
+                let nick = &message.params[0];
+                let user = context.database.get_user(&client.id);
+                let taken = context.database.get_user_by_nick(nick).is_some_and(|other| other.id != client.id);
+                if let Some(user) = user.filter(|user| !taken && !user.nick.eq_ignore_ascii_case(nick)) {
+                    self.enforce(&user, nick, context);
+                }
+                Ok(ModuleResult::NotHandled)
+            }
+            _ => Ok(ModuleResult::NotHandled),
+        }
+    }
+
+    fn commands(&self) -> Vec<CommandSpec> {
+        vec![CommandSpec::new("ACCOUNT", 1)]
+    }
+
+    async fn handle_command(&mut self, client: &Client, message: &Message, context: &ModuleContext) -> Result<()> {
+        let words: Vec<&str> = message.params.iter().map(String::as_str).collect();
+        self.dispatch(client, &words, context).await
+    }
+
+    async fn handle_server_message(&mut self, _server: &str, _message: &Message, _context: &ModuleContext) -> Result<ModuleResult> {
+        Ok(ModuleResult::NotHandled)
+    }
+
+    async fn handle_user_registration(&mut self, user: &User, context: &ModuleContext) -> Result<()> {
+        self.enforce(user, &user.nick, context);
+        Ok(())
+    }
+
+    async fn handle_user_disconnection(&mut self, _user: &User, _context: &ModuleContext) -> Result<()> {
+        Ok(())
+    }
+
+    fn get_capabilities(&self) -> Vec<String> {
+        vec!["message_handler".to_string()]
+    }
+
+    fn supports_capability(&self, capability: &str) -> bool {
+        capability == "message_handler"
+    }
+
+    fn get_numeric_replies(&self) -> Vec<u16> {
+        vec![]
+    }
+
+    fn handles_numeric_reply(&self, _numeric: u16) -> bool {
+        false
+    }
+
+    async fn handle_numeric_reply(&mut self, _numeric: u16, _params: Vec<String>) -> Result<()> {
+        Ok(())
+    }
+
+    async fn handle_stats_query(&mut self, _query: &str, _client_id: uuid::Uuid, _server: Option<&rustircd_core::Server>) -> Result<Vec<ModuleStatsResponse>> {
+        Ok(vec![])
+    }
+
+    fn get_stats_queries(&self) -> Vec<String> {
+        vec![]
+    }
+
+    fn register_numerics(&self, _manager: &mut ModuleNumericManager) -> Result<()> {
+        Ok(())
+    }
+
+    async fn cleanup(&mut self) -> Result<()> {
+        tracing::info!("NickServ module cleaned up");
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{context, registered_client};
+    use tokio::sync::mpsc;
+
+    fn privmsg(target: &str, text: &str) -> Message {
+        Message::new(MessageType::PrivMsg, vec![target.to_string(), text.to_string()])
+    }
+
+    /// Text of the next NOTICE, skipping numerics
+    fn next_notice(rx: &mut mpsc::UnboundedReceiver<Message>) -> String {
+        loop {
+            let message = rx.try_recv().unwrap();
+            if message.command == MessageType::Notice {
+                return message.params[1].clone();
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_register_identify_and_drop() {
+        let mut module = NickServModule::new(NickServConfig::default()).unwrap();
+        let context = context();
+        let (alice, mut rx) = registered_client("alice", &context);
+
+        // Other messages pass through
+        let result = module.handle_message(&alice, &privmsg("bob", "hi"), &context).await.unwrap();
+        assert!(matches!(result, ModuleResult::NotHandled));
+
+        let result = module.handle_message(&alice, &privmsg("NickServ", "REGISTER short"), &context).await.unwrap();
+        assert!(matches!(result, ModuleResult::HandledStop));
+        assert!(next_notice(&mut rx).contains("at least 8"));
+
+        module.handle_message(&alice, &privmsg("nickserv@localhost", "REGISTER hunter2hunter2 alice@example.com"), &context).await.unwrap();
+        assert!(next_notice(&mut rx).contains("now registered"));
+        assert_eq!(rx.try_recv().unwrap().command, MessageType::Custom("900".to_string()));
+        assert_eq!(context.database.get_user(&alice.id).unwrap().account.as_deref(), Some("alice"));
+        assert_eq!(module.store.get("ALICE").unwrap().email.as_deref(), Some("alice@example.com"));
+
+        // Another connection logs in with IDENTIFY, or the ACCOUNT command
+        let (other, mut other_rx) = registered_client("alice_", &context);
+        module.handle_message(&other, &privmsg("NickServ", "IDENTIFY alice wrongpassword"), &context).await.unwrap();
+        assert_eq!(next_notice(&mut other_rx), "Invalid account or password.");
+        let identify = Message::new(MessageType::Custom("ACCOUNT".to_string()), vec!["IDENTIFY".to_string(), "alice".to_string(), "hunter2hunter2".to_string()]);
+        module.handle_command(&other, &identify, &context).await.unwrap();
+        assert!(next_notice(&mut other_rx).contains("identified for alice"));
+        assert_eq!(context.database.get_user(&other.id).unwrap().account.as_deref(), Some("alice"));
+
+        module.handle_message(&alice, &privmsg("NickServ", "SET PASSWORD correcthorse"), &context).await.unwrap();
+        assert!(next_notice(&mut rx).contains("has been changed"));
+        assert!(module.store.verify("alice", "hunter2hunter2").is_none());
+
+        module.handle_message(&alice, &privmsg("NickServ", "DROP correcthorse"), &context).await.unwrap();
+        assert!(next_notice(&mut rx).contains("has been dropped"));
+        assert_eq!(rx.try_recv().unwrap().command, MessageType::Custom("901".to_string()));
+        assert_eq!(rx.try_recv().unwrap().params, vec!["alice".to_string(), "-r".to_string()]);
+        assert!(context.database.get_user(&alice.id).unwrap().account.is_none());
+        assert!(module.store.is_empty());
+
+        // Nobody may take the service's nickname
+        let nick = Message::new(MessageType::Nick, vec!["NICKSERV".to_string()]);
+        let result = module.handle_message(&alice, &nick, &context).await.unwrap();
+        assert!(matches!(result, ModuleResult::HandledStop));
+        assert_eq!(rx.try_recv().unwrap().command, MessageType::Custom("432".to_string()));
+    }
+
+    #[tokio::test]
+    async fn test_enforce_registered_nicks() {
+        let config = NickServConfig { enforce_timeout: 1, ..NickServConfig::default() };
+        let mut module = NickServModule::new(config).unwrap();
+        module.store.register("alice", "hunter2hunter2", None).unwrap();
+        module.store.register("bob", "correcthorse", None).unwrap();
+        let context = context();
+        let mut renames = context.take_nick_change_receiver().unwrap();
+        let (alice, mut alice_rx) = registered_client("alice", &context);
+        let (bob, mut bob_rx) = registered_client("bob", &context);
+
+        module.handle_user_registration(alice.get_user().unwrap(), &context).await.unwrap();
+        module.handle_user_registration(bob.get_user().unwrap(), &context).await.unwrap();
+        assert!(next_notice(&mut alice_rx).contains("within 1 seconds"));
+        assert!(next_notice(&mut bob_rx).contains("is registered"));
+
+        // Identifying in time keeps the nick, and sets +r
+        module.handle_message(&bob, &privmsg("NickServ", "IDENTIFY correcthorse"), &context).await.unwrap();
+        assert!(context.database.get_user(&bob.id).unwrap().has_mode('r'));
+
+        let (client_id, guest) = tokio::time::timeout(std::time::Duration::from_secs(3), renames.recv()).await.unwrap().unwrap();
+        assert_eq!(client_id, alice.id);
+        assert!(guest.starts_with("Guest") && guest.len() == 10);
+        tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+        assert!(renames.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_accounts_persist_and_feed_sasl() {
+        let path = std::env::temp_dir().join(format!("rustircd-accounts-{}.json", uuid::Uuid::new_v4()));
+        let store = AccountStore::open(&path).unwrap();
+        store.register("Alice", "hunter2hunter2", None).unwrap();
+        assert!(store.register("alice", "anotherpassword", None).is_err());
+
+        let reopened = AccountStore::open(&path).unwrap();
+        assert_eq!(reopened.get("alice").unwrap().name, "Alice");
+
+        let request = |password: &str| AuthRequest {
+            username: "alice".to_string(),
+            credential: password.to_string(),
+            authzid: None,
+            client_info: rustircd_core::ClientInfo {
+                id: uuid::Uuid::new_v4(),
+                ip: "127.0.0.1".to_string(),
+                hostname: None,
+                secure: false,
+            },
+            context: HashMap::new(),
+        };
+        match reopened.authenticate(&request("hunter2hunter2")).await.unwrap() {
+            AuthResult::Success(info) => assert_eq!(info.username, "Alice"),
+            other => panic!("expected success, got {:?}", other),
+        }
+        assert!(matches!(reopened.authenticate(&request("wrong")).await.unwrap(), AuthResult::Failure(_)));
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,12 @@
+//! Module Registry
+//!
+//! Constructors for the services in this crate that run as server modules,
+//! keyed by the names used in `modules.enabled_modules`.
+
+use crate::NickServModule;
+use rustircd_core::ModuleRegistry;
+
+/// Register every module in this crate
+pub fn register_services(registry: &mut ModuleRegistry) {
+    registry.register("nickserv", |config| Ok(Box::new(NickServModule::new(config.modules.nickserv.clone())?)));
+}
@@ -1,6 +1,6 @@
 //! Rust IRC Daemon - Main binary
 
-use rustircd_core::{Config, Server};
+use rustircd_core::{init_logging, Config, Server};
 use clap::{Parser, Subcommand};
 use std::path::PathBuf;
 use tracing::info;
@@ -15,9 +15,9 @@ struct Cli {
     #[arg(short, long, default_value = "config.toml")]
     config: PathBuf,
     
-    /// Log level
-    #[arg(short, long, default_value = "info")]
-    log_level: String,
+    /// Log level, overriding [logging] level
+    #[arg(short, long)]
+    log_level: Option<String>,
     
     /// Daemon mode (run in background)
     #[arg(short, long)]
@@ -49,9 +49,6 @@ enum Commands {
 async fn main() -> anyhow::Result<()> {
     let cli = Cli::parse();
     
-    // Initialize logging
-    init_logging(&cli.log_level)?;
-    
     // Handle subcommands
     if let Some(command) = cli.command {
         match command {
@@ -77,15 +74,19 @@ async fn main() -> anyhow::Result<()> {
             return validate_config(&cli.config);
         }
 
-        info!("Loading configuration from {:?}", cli.config);
-        Config::from_file(&cli.config)?
+        let config = Config::from_file(&cli.config)?;
+        init_logging(&config, cli.log_level.as_deref())?;
+        info!("Loaded configuration from {:?}", cli.config);
+        config
     } else {
         if cli.test_config {
             eprintln!("❌ Configuration file not found: {:?}", cli.config);
             std::process::exit(1);
         }
+        let config = Config::default();
+        init_logging(&config, cli.log_level.as_deref())?;
         info!("Configuration file not found, using defaults");
-        Config::default()
+        config
     };
     
     // Validate configuration
@@ -94,32 +95,17 @@ async fn main() -> anyhow::Result<()> {
     // Create and initialize server
     let config_path = cli.config.to_string_lossy().to_string();
     let mut server = Server::new_with_config_path(config, config_path).await;
+    let mut registry = rustircd_modules::module_registry();
+    rustircd_services::register_services(&mut registry);
+    server.set_module_registry(registry);
     server.init().await?;
     
     // Start server
     info!("Starting Rust IRC Daemon...");
     server.start().await?;
     
-    Ok(())
-}
-
-/// Initialize logging
-fn init_logging(level: &str) -> anyhow::Result<()> {
-    let log_level = match level.to_lowercase().as_str() {
-        "trace" => tracing::Level::TRACE,
-        "debug" => tracing::Level::DEBUG,
-        "info" => tracing::Level::INFO,
-        "warn" => tracing::Level::WARN,
-        "error" => tracing::Level::ERROR,
-        _ => tracing::Level::INFO,
-    };
-    
-    tracing_subscriber::fmt()
-        .with_max_level(log_level)
-        .with_target(false)
-        .with_thread_ids(true)
-        .with_thread_names(true)
-        .init();
+    // Serve until SIGTERM/SIGINT
+    server.run_until_shutdown().await?;
     
     Ok(())
 }
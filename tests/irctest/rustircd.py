@@ -0,0 +1,101 @@
+"""
+irctest controller for RustIRCd
+
+Copy this file to irctest/controllers/rustircd.py in an irctest checkout and
+run the suite with:
+
+    RUSTIRCD_BIN=/path/to/rustircd pytest --controller irctest.controllers.rustircd
+
+The controller generates a default configuration with `rustircd config`,
+points it at the port irctest picked, and turns off lookups and connection
+throttling so the many short-lived test clients aren't delayed or refused.
+"""
+
+import os
+import re
+import subprocess
+from typing import Optional, Set, Type
+
+from irctest.basecontrollers import (
+    BaseServerController,
+    DirectoryBasedController,
+    NotImplementedByController,
+)
+
+# Modules loaded for the suite
+MODULES = ["channel", "ircv3", "monitor", "help"]
+
+
+def patch(config: str, pattern: str, replacement: str) -> str:
+    """Replace exactly one match of `pattern`, so template drift fails loudly"""
+    patched, count = re.subn(pattern, replacement, config, count=1, flags=re.MULTILINE | re.DOTALL)
+    if count != 1:
+        raise RuntimeError(f"rustircd config has no match for {pattern!r}")
+    return patched
+
+
+def build_config(default: str, hostname: str, port: int, password: Optional[str]) -> str:
+    config = default
+    config = patch(config, r'^name = "localhost"$', 'name = "My.Little.Server"')
+    config = patch(config, r'^bind_address = ".*?"$', f'bind_address = "{hostname}"')
+    # One client port in place of the default listeners
+    config = patch(
+        config,
+        r"^\[\[connection\.ports\]\].*?(?=^\[\[classes\]\])",
+        f'[[connection.ports]]\nport = {port}\nconnection_type = "Client"\ntls = false\n\n',
+    )
+    for lookup in ["enable_ident", "enable_dns", "enable_reverse_dns"]:
+        config = patch(config, rf"^{lookup} = true$", f"{lookup} = false")
+    config = patch(config, r"^(\[modules\.throttling\]\n)enabled = true$", r"\1enabled = false")
+    config = patch(config, r"^enabled_modules = \[\]$", f"enabled_modules = {MODULES!r}".replace("'", '"'))
+    if password is not None:
+        config = patch(
+            config,
+            r"^require_client_password = false$",
+            f'require_client_password = true\nclient_password = "{password}"',
+        )
+    return config
+
+
+class RustircdController(BaseServerController, DirectoryBasedController):
+    software_name = "RustIRCd"
+    supported_sasl_mechanisms: Set[str] = set()
+    supports_sts = False
+
+    def binary(self) -> str:
+        return os.environ.get("RUSTIRCD_BIN", "rustircd")
+
+    def run(
+        self,
+        hostname: str,
+        port: int,
+        *,
+        password: Optional[str],
+        ssl: bool,
+        run_services: bool,
+        faketime: Optional[str],
+    ) -> None:
+        if ssl:
+            raise NotImplementedByController("TLS")
+        if run_services:
+            raise NotImplementedByController("Services")
+        if faketime:
+            raise NotImplementedByController("faketime")
+        assert self.proc is None
+        self.port = port
+        self.hostname = hostname
+        self.create_config()
+        assert self.directory
+
+        config_path = os.path.join(self.directory, "config.toml")
+        subprocess.run([self.binary(), "config", "--output", config_path], check=True, capture_output=True)
+        with open(config_path) as fd:
+            default = fd.read()
+        with open(config_path, "w") as fd:
+            fd.write(build_config(default, hostname, port, password))
+
+        self.proc = self.execute([self.binary(), "--config", config_path, "--log-level", "warn"])
+
+
+def get_irctest_controller_class() -> Type[RustircdController]:
+    return RustircdController
@@ -0,0 +1,320 @@
+use anyhow::{bail, Context, Result};
+use clap::Parser;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader, Lines};
+use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::net::TcpStream;
+use tokio::sync::{watch, Mutex};
+use tokio::task::JoinHandle;
+
+/// Marker at the start of every benchmark message, followed by the send time
+const MARKER: &str = "ircbench";
+
+/// RustIRCD load generator
+///
+/// Connects a crowd of clients to a running server, spreads them over a set
+/// of channels and has them talk at a fixed rate, then reports how long
+/// messages took to reach the other members and how many got through.
+#[derive(Parser, Debug)]
+#[command(
+    name = "ircbench",
+    version,
+    about = "Generate client load against a running RustIRCD and measure delivery latency",
+    long_about = "Opens the requested number of client connections, registers them and \
+                  joins them round-robin to #bench0..#benchN. Once everyone has joined, \
+                  clients take turns sending channel messages at the requested total rate. \
+                  Every receiving client measures how long each message took to arrive; \
+                  the report lists throughput and latency percentiles.\n\n\
+                  Run it against a server whose connection throttling and flood limits \
+                  allow this many clients from one address."
+)]
+struct Cli {
+    /// Server address
+    #[arg(short, long, default_value = "127.0.0.1:6667")]
+    server: String,
+
+    /// Number of client connections
+    #[arg(short, long, default_value_t = 1000)]
+    clients: usize,
+
+    /// Number of channels the clients are spread over
+    #[arg(long, default_value_t = 10)]
+    channels: usize,
+
+    /// Channel messages sent per second, across all clients
+    #[arg(short, long, default_value_t = 100)]
+    rate: u64,
+
+    /// Seconds to send messages for
+    #[arg(short, long, default_value_t = 30)]
+    duration: u64,
+
+    /// New connections opened per second
+    #[arg(long, default_value_t = 200)]
+    connect_rate: u64,
+
+    /// Bytes of padding added to each message
+    #[arg(long, default_value_t = 32)]
+    payload: usize,
+
+    /// Nickname prefix; clients are named <prefix><n>
+    #[arg(long, default_value = "bench")]
+    prefix: String,
+
+    /// Connection password, sent with PASS
+    #[arg(long)]
+    password: Option<String>,
+}
+
+/// Totals shared by every client
+#[derive(Default)]
+struct Counters {
+    received: AtomicU64,
+    bytes_received: AtomicU64,
+}
+
+/// A registered client that has joined its channel
+struct BenchClient {
+    channel: usize,
+    writer: Arc<Mutex<OwnedWriteHalf>>,
+    /// Returns the latencies this client measured, in microseconds
+    reader: JoinHandle<Vec<u64>>,
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let cli = Arc::new(Cli::parse());
+    if cli.clients < 2 || cli.channels == 0 || cli.rate == 0 || cli.connect_rate == 0 {
+        bail!("Need at least 2 clients, 1 channel, and non-zero message and connect rates");
+    }
+
+    let epoch = Instant::now();
+    let counters = Arc::new(Counters::default());
+    let (stop_sender, stop) = watch::channel(false);
+
+    // Connect at the requested pace, registering in parallel
+    eprintln!("Connecting {} clients to {}...", cli.clients, cli.server);
+    let connect_start = Instant::now();
+    let mut pending = Vec::with_capacity(cli.clients);
+    let mut pace = tokio::time::interval(Duration::from_secs_f64(1.0 / cli.connect_rate as f64));
+    for index in 0..cli.clients {
+        pace.tick().await;
+        let (cli, counters, stop) = (cli.clone(), counters.clone(), stop.clone());
+        pending.push(tokio::spawn(async move { connect(&cli, index, epoch, counters, stop).await }));
+    }
+    let mut clients = Vec::with_capacity(cli.clients);
+    let mut failures = 0;
+    for task in pending {
+        match task.await? {
+            Ok(client) => clients.push(client),
+            Err(e) => {
+                if failures == 0 {
+                    eprintln!("Connection failed: {:#}", e);
+                }
+                failures += 1;
+            }
+        }
+    }
+    let connect_time = connect_start.elapsed();
+    if clients.len() < 2 {
+        bail!("Only {} of {} clients registered", clients.len(), cli.clients);
+    }
+
+    let mut members = vec![0u64; cli.channels];
+    for client in &clients {
+        members[client.channel] += 1;
+    }
+
+    // Let the last JOINs settle before measuring
+    tokio::time::sleep(Duration::from_secs(1)).await;
+
+    eprintln!("Sending {} messages/s for {}s...", cli.rate, cli.duration);
+    let padding = "x".repeat(cli.payload);
+    let send_start = Instant::now();
+    let run_for = Duration::from_secs(cli.duration);
+    let mut tick = tokio::time::interval(Duration::from_millis(10));
+    let (mut sent, mut expected, mut send_errors) = (0u64, 0u64, 0u64);
+    let mut next = 0;
+    while send_start.elapsed() < run_for {
+        tick.tick().await;
+        let due = (send_start.elapsed().as_secs_f64() * cli.rate as f64) as u64;
+        while sent < due {
+            let client = &clients[next % clients.len()];
+            next += 1;
+            let line = format!(
+                "PRIVMSG #bench{} :{} {} {}\r\n",
+                client.channel, MARKER, epoch.elapsed().as_micros(), padding
+            );
+            if client.writer.lock().await.write_all(line.as_bytes()).await.is_err() {
+                send_errors += 1;
+            }
+            sent += 1;
+            expected += members[client.channel] - 1;
+        }
+    }
+    let send_time = send_start.elapsed();
+
+    // Give messages still in flight a moment, then collect what everyone saw
+    tokio::time::sleep(Duration::from_secs(2)).await;
+    let _ = stop_sender.send(true);
+    let mut latencies = Vec::new();
+    for client in clients {
+        let _ = client.writer.lock().await.write_all(b"QUIT :ircbench done\r\n").await;
+        latencies.extend(client.reader.await.unwrap_or_default());
+    }
+    latencies.sort_unstable();
+
+    let received = counters.received.load(Ordering::Relaxed);
+    let bytes = counters.bytes_received.load(Ordering::Relaxed);
+    println!("=== ircbench against {} ===", cli.server);
+    println!(
+        "clients:    {} registered, {} failed, in {:.2}s",
+        cli.clients - failures, failures, connect_time.as_secs_f64()
+    );
+    println!(
+        "sent:       {} messages in {:.2}s ({:.1}/s), {} write errors",
+        sent, send_time.as_secs_f64(), sent as f64 / send_time.as_secs_f64(), send_errors
+    );
+    println!(
+        "delivered:  {} of {} expected ({:.2}%), {:.1}/s, {:.2} MB",
+        received,
+        expected,
+        if expected == 0 { 0.0 } else { received as f64 * 100.0 / expected as f64 },
+        received as f64 / send_time.as_secs_f64(),
+        bytes as f64 / 1_000_000.0
+    );
+    if latencies.is_empty() {
+        println!("latency:    no messages delivered");
+    } else {
+        println!(
+            "latency:    p50 {} p90 {} p99 {} p99.9 {} max {}",
+            format_micros(percentile(&latencies, 50.0)),
+            format_micros(percentile(&latencies, 90.0)),
+            format_micros(percentile(&latencies, 99.0)),
+            format_micros(percentile(&latencies, 99.9)),
+            format_micros(latencies[latencies.len() - 1]),
+        );
+    }
+    Ok(())
+}
+
+/// Connect, register and join one client, then leave a task reading for it
+async fn connect(
+    cli: &Cli,
+    index: usize,
+    epoch: Instant,
+    counters: Arc<Counters>,
+    stop: watch::Receiver<bool>,
+) -> Result<BenchClient> {
+    let stream = TcpStream::connect(&cli.server)
+        .await
+        .with_context(|| format!("Failed to connect to {}", cli.server))?;
+    stream.set_nodelay(true)?;
+    let (read, write) = stream.into_split();
+    let writer = Arc::new(Mutex::new(write));
+    let mut lines = BufReader::new(read).lines();
+
+    let nick = format!("{}{}", cli.prefix, index);
+    let mut hello = String::new();
+    if let Some(password) = &cli.password {
+        hello.push_str(&format!("PASS {}\r\n", password));
+    }
+    hello.push_str(&format!("NICK {}\r\nUSER {} 0 * :ircbench client\r\n", nick, nick));
+    writer.lock().await.write_all(hello.as_bytes()).await?;
+
+    tokio::time::timeout(Duration::from_secs(30), wait_for_welcome(&mut lines, &writer))
+        .await
+        .with_context(|| format!("{} timed out registering", nick))??;
+
+    let channel = index % cli.channels;
+    writer.lock().await.write_all(format!("JOIN #bench{}\r\n", channel).as_bytes()).await?;
+
+    let reader = tokio::spawn(read_messages(lines, writer.clone(), epoch, counters, stop));
+    Ok(BenchClient { channel, writer, reader })
+}
+
+/// Read until 001, answering PINGs on the way
+async fn wait_for_welcome(lines: &mut Lines<BufReader<OwnedReadHalf>>, writer: &Mutex<OwnedWriteHalf>) -> Result<()> {
+    while let Some(line) = lines.next_line().await? {
+        let (command, params) = split_line(&line);
+        match command {
+            "001" => return Ok(()),
+            "PING" => writer.lock().await.write_all(format!("PONG {}\r\n", params).as_bytes()).await?,
+            "432" | "433" | "465" | "ERROR" => bail!("Registration refused: {}", line),
+            _ => {}
+        }
+    }
+    bail!("Connection closed while registering")
+}
+
+/// Count benchmark messages and time their delivery until told to stop
+async fn read_messages(
+    mut lines: Lines<BufReader<OwnedReadHalf>>,
+    writer: Arc<Mutex<OwnedWriteHalf>>,
+    epoch: Instant,
+    counters: Arc<Counters>,
+    mut stop: watch::Receiver<bool>,
+) -> Vec<u64> {
+    let mut latencies = Vec::new();
+    loop {
+        let line = tokio::select! {
+            _ = stop.changed() => break,
+            line = lines.next_line() => match line {
+                Ok(Some(line)) => line,
+                _ => break,
+            },
+        };
+        let (command, params) = split_line(&line);
+        match command {
+            "PING" => {
+                let _ = writer.lock().await.write_all(format!("PONG {}\r\n", params).as_bytes()).await;
+            }
+            "PRIVMSG" => {
+                let Some(sent) = sent_at(params) else {
+                    continue;
+                };
+                let now = epoch.elapsed().as_micros() as u64;
+                latencies.push(now.saturating_sub(sent));
+                counters.received.fetch_add(1, Ordering::Relaxed);
+                counters.bytes_received.fetch_add(line.len() as u64 + 2, Ordering::Relaxed);
+            }
+            _ => {}
+        }
+    }
+    latencies
+}
+
+/// Command and parameters of a line, without tags or prefix
+fn split_line(line: &str) -> (&str, &str) {
+    let mut rest = line;
+    if rest.starts_with('@') {
+        rest = rest.split_once(' ').map_or("", |(_, rest)| rest);
+    }
+    if rest.starts_with(':') {
+        rest = rest.split_once(' ').map_or("", |(_, rest)| rest);
+    }
+    rest.split_once(' ').unwrap_or((rest, ""))
+}
+
+/// Send time carried by a benchmark PRIVMSG, in microseconds since the epoch
+fn sent_at(params: &str) -> Option<u64> {
+    let (_, text) = params.split_once(" :")?;
+    let rest = text.strip_prefix(MARKER)?.trim_start();
+    rest.split(' ').next()?.parse().ok()
+}
+
+/// Value at percentile `p` of sorted samples
+fn percentile(sorted: &[u64], p: f64) -> u64 {
+    let rank = ((p / 100.0) * (sorted.len() - 1) as f64).round() as usize;
+    sorted[rank.min(sorted.len() - 1)]
+}
+
+fn format_micros(micros: u64) -> String {
+    if micros >= 1000 {
+        format!("{:.2}ms", micros as f64 / 1000.0)
+    } else {
+        format!("{}µs", micros)
+    }
+}
@@ -1,10 +1,17 @@
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
 use argon2::{
     password_hash::{PasswordHasher, SaltString},
     Argon2,
 };
-use clap::Parser;
+use base64::{engine::general_purpose, Engine as _};
+use clap::{Parser, Subcommand, ValueEnum};
+use ed25519_dalek::pkcs8::{DecodePrivateKey, EncodePrivateKey, EncodePublicKey};
+use ed25519_dalek::{Signer, SigningKey};
 use rand::rngs::OsRng;
+use rsa::pkcs8::LineEnding;
+use rsa::{Oaep, RsaPrivateKey};
+use sha1::{Digest, Sha1};
+use std::path::Path;
 
 /// RustIRCD password hashing utility using Argon2
 ///
@@ -20,9 +27,14 @@ use rand::rngs::OsRng;
                   file for operator authentication.\n\n\
                   By default, the tool prompts for a password securely (without echoing). \
                   Alternatively, you can provide a password via command-line argument \
-                  (not recommended for security reasons) or pipe it via stdin."
+                  (not recommended for security reasons) or pipe it via stdin.\n\n\
+                  The keygen subcommand creates a keypair for the CHALLENGE command \
+                  instead, and respond answers a challenge with the private key."
 )]
 struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+
     /// Password to hash (not recommended - use interactive prompt instead)
     #[arg(short, long, conflicts_with = "stdin")]
     password: Option<String>,
@@ -32,8 +44,46 @@ struct Cli {
     stdin: bool,
 }
 
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Generate a keypair for CHALLENGE operator authentication
+    Keygen {
+        /// Key type
+        #[arg(short = 't', long = "type", value_enum, default_value_t = KeyType::Ed25519)]
+        key_type: KeyType,
+
+        /// RSA key size in bits
+        #[arg(short, long, default_value_t = 2048)]
+        bits: usize,
+
+        /// Output path prefix; writes <prefix>.key and <prefix>.pub
+        #[arg(short, long, default_value = "oper")]
+        output: String,
+    },
+    /// Answer a CHALLENGE with a private key made by keygen
+    Respond {
+        /// Private key file
+        #[arg(short, long)]
+        key: String,
+
+        /// Challenge text: the RPL_RSACHALLENGE2 (740) lines joined together
+        challenge: String,
+    },
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum KeyType {
+    Rsa,
+    Ed25519,
+}
+
 fn main() -> Result<()> {
     let cli = Cli::parse();
+    match cli.command {
+        Some(Command::Keygen { key_type, bits, output }) => return keygen(key_type, bits, &output),
+        Some(Command::Respond { key, challenge }) => return respond(&key, &challenge),
+        None => {}
+    }
 
     // Get password from appropriate source
     let password = if let Some(pwd) = cli.password {
@@ -85,3 +135,81 @@ fn main() -> Result<()> {
 
     Ok(())
 }
+
+/// Write a new keypair to <prefix>.key and <prefix>.pub
+fn keygen(key_type: KeyType, bits: usize, prefix: &str) -> Result<()> {
+    let key_path = format!("{}.key", prefix);
+    let pub_path = format!("{}.pub", prefix);
+    for path in [&key_path, &pub_path] {
+        if Path::new(path).exists() {
+            bail!("{} already exists; refusing to overwrite it", path);
+        }
+    }
+
+    let (private_pem, public_pem) = match key_type {
+        KeyType::Rsa => {
+            if bits < 2048 {
+                bail!("RSA keys must be at least 2048 bits");
+            }
+            let key = RsaPrivateKey::new(&mut OsRng, bits).context("Failed to generate RSA key")?;
+            (
+                key.to_pkcs8_pem(LineEnding::LF).context("Failed to encode private key")?.to_string(),
+                key.to_public_key().to_public_key_pem(LineEnding::LF).context("Failed to encode public key")?,
+            )
+        }
+        KeyType::Ed25519 => {
+            let key = SigningKey::generate(&mut OsRng);
+            (
+                key.to_pkcs8_pem(LineEnding::LF).context("Failed to encode private key")?.to_string(),
+                key.verifying_key().to_public_key_pem(LineEnding::LF).context("Failed to encode public key")?,
+            )
+        }
+    };
+
+    write_private(&key_path, &private_pem)?;
+    std::fs::write(&pub_path, public_pem).with_context(|| format!("Failed to write {}", pub_path))?;
+
+    println!("\n=== CHALLENGE Keypair ===");
+    println!("Private key: {} (keep this on the operator's machine)", key_path);
+    println!("Public key:  {}", pub_path);
+    println!("\n=== Usage ===");
+    println!("Copy the public key to the server and name it in the oper block:");
+    println!("Example: challenge_key_file = \"{}\"", pub_path);
+    println!("\nThen send CHALLENGE <name>, join the 740 lines the server replies with, and run:");
+    println!("  mkpasswd respond --key {} <challenge>\n", key_path);
+    Ok(())
+}
+
+/// Write a private key readable only by its owner
+fn write_private(path: &str, pem: &str) -> Result<()> {
+    use std::io::Write;
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let mut file = options.open(path).with_context(|| format!("Failed to create {}", path))?;
+    file.write_all(pem.as_bytes()).with_context(|| format!("Failed to write {}", path))
+}
+
+/// Print the CHALLENGE line answering `challenge`
+fn respond(key_path: &str, challenge: &str) -> Result<()> {
+    let pem = std::fs::read_to_string(key_path).with_context(|| format!("Failed to read {}", key_path))?;
+    let challenge = general_purpose::STANDARD
+        .decode(challenge.trim())
+        .context("Challenge is not valid base64")?;
+
+    let response = if let Ok(key) = SigningKey::from_pkcs8_pem(&pem) {
+        key.sign(&challenge).to_bytes().to_vec()
+    } else {
+        let key = RsaPrivateKey::from_pkcs8_pem(&pem).context("Key is not an RSA or Ed25519 private key")?;
+        let secret = key
+            .decrypt(Oaep::new::<Sha1>(), &challenge)
+            .context("Failed to decrypt challenge; is this the right key?")?;
+        Sha1::digest(secret).to_vec()
+    };
+    println!("CHALLENGE +{}", general_purpose::STANDARD.encode(response));
+    Ok(())
+}